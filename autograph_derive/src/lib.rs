@@ -6,12 +6,13 @@ use autograph::{
     anyhow::Result,
     learn::neural_network::{
         autograd::{Variable4, Variable2},
-        layer::{Layer, Forward, Flatten, Conv2, Relu, MaxPool2, Dense},
+        layer::{Layer, Forward, Bilinear, Flatten, Conv2, Relu, MaxPool2, Dense},
+        onnx::ToOnnx,
     },
 };
 
-// Layer and Forward can be derived for structs composed of layers.
-#[derive(Layer, Forward)]
+// Layer, Forward, and ToOnnx can be derived for structs composed of layers.
+#[derive(Layer, Forward, ToOnnx)]
 #[autograph(forward(Variable4, Output=Variable2))]
 struct Network {
     conv: Conv2<Relu>,
@@ -19,6 +20,15 @@ struct Network {
     dense: Dense,
 }
 
+// Inputs are passed to the first layer, which may take multiple inputs as a tuple. The output
+// of each layer is the input of the next.
+#[derive(Layer, Forward)]
+#[autograph(forward((Variable2, Variable2), Output=Variable2))]
+struct Scorer {
+    bilinear: Bilinear,
+    relu: Relu,
+}
+
 // Can also be applied to enums.
 #[derive(Layer, Forward)]
 #[autograph(forward(Variable4, Output=Variable4))]
@@ -26,6 +36,39 @@ enum Dynamic {
     Conv(Conv2),
     Pool(MaxPool2),
 }
+
+// Models with multiple outputs derive Layer and implement Forward, routing the output of the
+// trunk to each head.
+#[derive(Layer)]
+struct TwoHeads {
+    trunk: Dense<Relu>,
+    head: Dense,
+    aux: Dense,
+}
+
+impl Forward<Variable2> for TwoHeads {
+    type Output = (Variable2, Variable2);
+    fn forward(&self, input: Variable2) -> Result<Self::Output> {
+        let x = self.trunk.forward(input)?;
+        Ok((self.head.forward(x.clone())?, self.aux.forward(x)?))
+    }
+}
+```
+
+The losses of the outputs are combined into a single loss before the backward pass:
+```no_run
+# use autograph::{anyhow::Result, learn::{criterion::CrossEntropyLoss, neural_network::{autograd::{Variable0, Variable2}, layer::Forward}}, ops::AddAssign, tensor::ScalarArcTensor1};
+# fn main() -> Result<()> {
+# let (model, x, t): (&dyn Forward<Variable2, Output = (Variable2, Variable2)>, Variable2, ScalarArcTensor1) = todo!();
+let (y, y_aux) = model.forward(x)?;
+let mut loss = y.cross_entropy_loss(t.clone())?;
+loss.add_assign(y_aux.cross_entropy_loss(t)?)?;
+loss.backward()?;
+# Ok(())
+# }
+```
+The backward pass consumes the graph, so calling `.backward()` for each loss separately would
+only propagate the first loss through the shared trunk.
 */
 
 // TODO: remove `#[layer]` attribute.
@@ -169,13 +212,90 @@ impl Layers {
             }
         }
     }
-    fn collect(&self, method: Ident) -> TokenStream2 {
+    fn parameters(&self, try_: bool, autograph: &Path) -> TokenStream2 {
         match self {
             Self::Struct(layers) => {
+                // Tied parameters are returned once, the copies are tied to the first mutable
+                // view.
+                if try_ {
+                    quote! {
+                        let mut parameters = #autograph::learn::neural_network::layer::ParameterMutVec::new();
+                        #(
+                            for parameter in self.#layers.parameters_mut()? {
+                                if let Some(parameter) = parameters
+                                    .iter_mut()
+                                    .try_fold(parameter, |parameter, x| x.tie(parameter))
+                                {
+                                    parameters.push(parameter);
+                                }
+                            }
+                        )*
+                        Ok(parameters)
+                    }
+                } else {
+                    quote! {
+                        let mut parameters = #autograph::learn::neural_network::layer::ParameterVec::new();
+                        #(
+                            for parameter in self.#layers.parameters() {
+                                if !parameters.iter().any(|x| x.is_shared_with(&parameter)) {
+                                    parameters.push(parameter);
+                                }
+                            }
+                        )*
+                        parameters
+                    }
+                }
+            }
+            Self::Enum(layers) => {
+                let method = if try_ {
+                    format_ident!("parameters_mut")
+                } else {
+                    format_ident!("parameters")
+                };
                 quote! {
-                    ::std::iter::empty()
-                    #(.chain(self.#layers.#method()))*
-                    .collect()
+                    match self {
+                        #(
+                            Self::#layers(layer) => layer.#method(),
+                        )*
+                    }
+                }
+            }
+        }
+    }
+    fn collect_named(&self, method: Ident, try_: bool, autograph: &Path) -> TokenStream2 {
+        match self {
+            Self::Struct(layers) => {
+                let names = layers
+                    .iter()
+                    .map(|layer| layer.to_token_stream().to_string());
+                // Tied parameters are returned once, see `parameters`.
+                if try_ {
+                    quote! {
+                        let mut parameters = #autograph::learn::neural_network::layer::NamedParameterMutVec::new();
+                        #(
+                            for (name, parameter) in self.#layers.#method()? {
+                                if let Some(parameter) = parameters
+                                    .iter_mut()
+                                    .try_fold(parameter, |parameter, (_, x)| x.tie(parameter))
+                                {
+                                    parameters.push((::std::format!("{}.{}", #names, name), parameter));
+                                }
+                            }
+                        )*
+                        Ok(parameters)
+                    }
+                } else {
+                    quote! {
+                        let mut parameters = #autograph::learn::neural_network::layer::NamedParameterVec::new();
+                        #(
+                            for (name, parameter) in self.#layers.#method() {
+                                if !parameters.iter().any(|(_, x)| x.is_shared_with(&parameter)) {
+                                    parameters.push((::std::format!("{}.{}", #names, name), parameter));
+                                }
+                            }
+                        )*
+                        parameters
+                    }
                 }
             }
             Self::Enum(layers) => {
@@ -189,15 +309,22 @@ impl Layers {
             }
         }
     }
-    fn try_collect(&self, method: Ident) -> TokenStream2 {
+    fn collect_names(&self, method: Ident) -> TokenStream2 {
         match self {
             Self::Struct(layers) => {
+                let names = layers
+                    .iter()
+                    .map(|layer| layer.to_token_stream().to_string());
                 quote! {
-                    Ok(
-                        ::std::iter::empty()
-                        #(.chain(self.#layers.#method()?))*
-                        .collect()
-                    )
+                    let mut names = ::std::vec::Vec::new();
+                    #(
+                        names.extend(
+                            self.#layers.#method()
+                                .into_iter()
+                                .map(|name| ::std::format!("{}.{}", #names, name))
+                        );
+                    )*
+                    names
                 }
             }
             Self::Enum(layers) => {
@@ -282,8 +409,14 @@ fn layer_impl(input: TokenStream2) -> Result<TokenStream2> {
     let ident = &input.ident;
     let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
     let set_training = layers.try_for_each(format_ident!("set_training"), quote! { training });
-    let parameters = layers.collect(format_ident!("parameters"));
-    let parameters_mut = layers.try_collect(format_ident!("parameters_mut"));
+    let parameters = layers.parameters(false, &autograph);
+    let parameters_mut = layers.parameters(true, &autograph);
+    let named_parameters =
+        layers.collect_named(format_ident!("named_parameters"), false, &autograph);
+    let named_parameters_mut =
+        layers.collect_named(format_ident!("named_parameters_mut"), true, &autograph);
+    let transposed_parameter_names =
+        layers.collect_names(format_ident!("transposed_parameter_names"));
     let cast_mut = layers.try_for_each(format_ident!("cast_mut"), quote!(scalar_type));
     let to_device_mut = layers.try_for_each(format_ident!("to_device_mut"), quote!(device.clone()));
     let into_device = layers.try_map(format_ident!("into_device"), quote! { device.clone() });
@@ -299,6 +432,15 @@ fn layer_impl(input: TokenStream2) -> Result<TokenStream2> {
             fn parameters_mut(&mut self) -> #autograph::anyhow::Result<#autograph::learn::neural_network::layer::ParameterMutVec> {
                 #parameters_mut
             }
+            fn named_parameters(&self) -> #autograph::learn::neural_network::layer::NamedParameterVec {
+                #named_parameters
+            }
+            fn named_parameters_mut(&mut self) -> #autograph::anyhow::Result<#autograph::learn::neural_network::layer::NamedParameterMutVec> {
+                #named_parameters_mut
+            }
+            fn transposed_parameter_names(&self) -> ::std::vec::Vec<::std::string::String> {
+                #transposed_parameter_names
+            }
             fn cast_mut(&mut self, scalar_type: #autograph::krnl::scalar::ScalarType) -> #autograph::anyhow::Result<()> {
                 #cast_mut
             }
@@ -335,7 +477,8 @@ fn forward_impl(input: TokenStream2) -> Result<TokenStream2> {
     let forward = match layers {
         Layers::Struct(layers) => {
             quote! {
-                Ok(input #(.forward(&self.#layers)?)*)
+                #(let input = Forward::forward(&self.#layers, input)?;)*
+                Ok(input)
             }
         }
         Layers::Enum(layers) => {
@@ -378,3 +521,51 @@ pub fn forward(input: TokenStream) -> TokenStream {
         Err(err) => err.into_compile_error().into(),
     }
 }
+
+fn to_onnx_impl(input: TokenStream2) -> Result<TokenStream2> {
+    let input: DeriveInput = syn::parse2(input)?;
+    let layers = Layers::parse(&input.data)?;
+    let autograph = autograph_crate(&input.attrs)?;
+    let ident = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    let to_onnx = match layers {
+        Layers::Struct(layers) => {
+            quote! {
+                #(let input = #autograph::learn::neural_network::onnx::ToOnnx::to_onnx(&self.#layers, graph, input)?;)*
+                Ok(input)
+            }
+        }
+        Layers::Enum(layers) => {
+            quote! {
+                match self {
+                    #(
+                        Self::#layers(layer) => #autograph::learn::neural_network::onnx::ToOnnx::to_onnx(layer, graph, input),
+                    )*
+                }
+            }
+        }
+    };
+    Ok(quote! {
+        #[automatically_derived]
+        impl #impl_generics #autograph::learn::neural_network::onnx::ToOnnx for #ident #ty_generics #where_clause {
+            fn to_onnx(
+                &self,
+                graph: &mut #autograph::learn::neural_network::onnx::OnnxGraph,
+                input: ::std::string::String,
+            ) -> #autograph::anyhow::Result<::std::string::String> {
+                #to_onnx
+            }
+        }
+    })
+}
+
+/// Derive for ToOnnx.
+///
+/// See [`autograph_derive`](crate).
+#[proc_macro_derive(ToOnnx, attributes(autograph, layer))]
+pub fn to_onnx(input: TokenStream) -> TokenStream {
+    match to_onnx_impl(input.into()) {
+        Ok(output) => output.into(),
+        Err(err) => err.into_compile_error().into(),
+    }
+}
@@ -170,6 +170,110 @@ pub fn criterion_benchmark(c: &mut Criterion) {
             }
         }
     }
+    #[cfg(feature = "device")]
+    {
+        // reductions
+        use autograph::tensor::Tensor;
+        use criterion::Throughput;
+
+        let device = Device::builder().index(device_index).build().unwrap();
+        let mut g = c.benchmark_group("sum(u32, device)");
+        for n in [1_000, 100_000, 10_000_000] {
+            let x = Tensor::<u32, _>::ones(device.clone(), n).unwrap();
+            g.throughput(Throughput::Elements(n as u64));
+            for block_size in [None, Some(256), Some(1024), Some(4096)] {
+                let name = if let Some(block_size) = block_size {
+                    format!("block_size = {block_size}")
+                } else {
+                    "default".to_string()
+                };
+                let id = BenchmarkId::new(name, n.to_formatted_string(&Locale::en));
+                g.bench_function(id, |b| {
+                    if let Some(block_size) = block_size {
+                        b.iter(|| x.sum_blocked(block_size).unwrap());
+                    } else {
+                        b.iter(|| x.sum().unwrap());
+                    }
+                });
+            }
+        }
+    }
+    #[cfg(feature = "device")]
+    {
+        // elementwise
+        use autograph::{
+            learn::neural_network::{autograd::Variable, layer::Relu},
+            tensor::{ElementwiseTuning, Tensor},
+        };
+        use criterion::Throughput;
+
+        let device = Device::builder().index(device_index).build().unwrap();
+        let mut g = c.benchmark_group("relu(f32, device)");
+        for n in [100_000, 10_000_000] {
+            let x = Tensor::<f32, _>::ones(device.clone(), n)
+                .unwrap()
+                .into_shared()
+                .unwrap();
+            g.throughput(Throughput::Elements(n as u64));
+            for (threads, items_per_thread) in [
+                (None, None),
+                (Some(64), None),
+                (Some(256), None),
+                (Some(256), Some(4)),
+                (Some(256), Some(16)),
+            ] {
+                let tuning = ElementwiseTuning {
+                    threads,
+                    items_per_thread,
+                };
+                let name = if tuning == ElementwiseTuning::default() {
+                    "default".to_string()
+                } else {
+                    format!("threads = {threads:?} items_per_thread = {items_per_thread:?}")
+                };
+                let id = BenchmarkId::new(name, n.to_formatted_string(&Locale::en));
+                g.bench_function(id, |b| {
+                    b.iter(|| {
+                        Relu.forward_with_tuning(Variable::from(x.clone()), tuning)
+                            .unwrap();
+                        device.wait().unwrap();
+                    });
+                });
+            }
+        }
+    }
+    #[cfg(feature = "device")]
+    {
+        // batched matmul
+        use autograph::{
+            ndarray::{linalg::Dot, Axis},
+            tensor::Tensor,
+        };
+
+        let device = Device::builder().index(device_index).build().unwrap();
+        let mut g = c.benchmark_group("bmm(f32, device)");
+        for [batch, m, k, n] in [[64, 32, 64, 32], [256, 64, 64, 64], [16, 256, 64, 256]] {
+            let a = Tensor::<f32, _>::ones(device.clone(), [batch, m, k]).unwrap();
+            let b = Tensor::<f32, _>::ones(device.clone(), [batch, k, n]).unwrap();
+            let input = format!("{batch}x[{m}, {k}]x[{k}, {n}]");
+            g.bench_function(BenchmarkId::new("bmm", &input), |bencher| {
+                bencher.iter(|| {
+                    a.bmm(&b).unwrap();
+                    device.wait().unwrap();
+                });
+            });
+            g.bench_function(BenchmarkId::new("loop", &input), |bencher| {
+                bencher.iter(|| {
+                    for i in 0..batch {
+                        a.index_axis(Axis(0), i)
+                            .dot(&b.index_axis(Axis(0), i))
+                            .unwrap();
+                    }
+                    device.wait().unwrap();
+                });
+            });
+        }
+    }
     if cfg!(all(feature = "device", feature = "tch")) {
         eprintln!("warning: sig abort in torch on exit when vulkan is used");
     }
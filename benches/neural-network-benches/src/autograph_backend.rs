@@ -6,7 +6,7 @@ use autograph::{
         criterion::CrossEntropyLoss,
         neural_network::{
             autograd::{Variable2, Variable4},
-            layer::{Conv2, Dense, Flatten, Forward, Layer, MaxPool2, Relu},
+            layer::{infer_flatten_size, Conv2, Dense, Flatten, Forward, Layer, MaxPool2, Relu},
             optimizer::{Optimizer, SGD},
         },
     },
@@ -84,6 +84,7 @@ impl LeNet5Classifier {
             optimizer.update(learning_rate, parameter)?;
         }
         self.model.set_training(false)?;
+        self.device.wait()?;
         Ok(())
     }
 }
@@ -121,11 +122,21 @@ impl LeNet5 {
             .activation(Relu)
             .build()?;
         let pool2 = MaxPool2::builder().filter([2, 2]).stride([2, 2]).build();
+        let inputs = infer_flatten_size(
+            1,
+            [28, 28],
+            &[
+                (&conv1).into(),
+                (&pool1).into(),
+                (&conv2).into(),
+                (&pool2).into(),
+            ],
+        )?;
         let flatten = Flatten;
         let dense1 = Dense::builder()
             .device(device.clone())
             .scalar_type(scalar_type)
-            .inputs(16 * 4 * 4)
+            .inputs(inputs)
             .outputs(128)
             .activation(Relu)
             .build()?;
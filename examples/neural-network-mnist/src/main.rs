@@ -1,21 +1,8 @@
 use autograph::{
     anyhow::Result,
     dataset::mnist::{Mnist, MnistKind},
-    krnl::{
-        device::Device,
-        krnl_core::half::bf16,
-        scalar::{ScalarElem, ScalarType},
-    },
-    learn::{
-        criterion::{Accuracy, CrossEntropyLoss},
-        neural_network::{
-            autograd::{Variable, Variable2, Variable4},
-            layer::{Conv2, Dense, Flatten, Forward, Layer, MaxPool2, Relu},
-            optimizer::{Optimizer, SGD},
-        },
-    },
     ndarray::{ArcArray, ArcArray1, Axis, Dimension, Ix4},
-    tensor::{CowTensor, ScalarTensor, Tensor, Tensor1, Tensor4},
+    prelude::*,
 };
 use clap::{Parser, ValueEnum};
 use num_format::{Locale, ToFormattedString};
@@ -55,11 +42,21 @@ impl LeNet5 {
             .activation(Relu)
             .build()?;
         let pool2 = MaxPool2::builder().filter([2, 2]).stride([2, 2]).build();
+        let inputs = infer_flatten_size(
+            1,
+            [28, 28],
+            &[
+                (&conv1).into(),
+                (&pool1).into(),
+                (&conv2).into(),
+                (&pool2).into(),
+            ],
+        )?;
         let flatten = Flatten;
         let dense1 = Dense::builder()
             .device(device.clone())
             .scalar_type(scalar_type)
-            .inputs(16 * 4 * 4)
+            .inputs(inputs)
             .outputs(128)
             .activation(Relu)
             .build()?;
@@ -171,11 +168,7 @@ fn main() -> Result<()> {
         parameter_count.to_formatted_string(&Locale::en)
     );
     println!("optimizer: {optimizer:#?}");
-    let image_scale = 1f32 / 255f32;
-    let image_scale = match options.scalar_type {
-        ScalarKind::BF16 => ScalarElem::BF16(bf16::from_f32(image_scale)),
-        ScalarKind::F32 => ScalarElem::F32(image_scale),
-    };
+    let image_scale = ScalarElem::F32(1f32 / 255f32).scalar_cast(model.scalar_type().unwrap());
     let start = Instant::now();
     for epoch in 1..=options.epochs {
         let epoch_start = Instant::now();
@@ -209,11 +202,13 @@ fn main() -> Result<()> {
         let test_correct = test_stats.correct;
         let test_loss = test_stats.mean_loss();
         let test_acc = test_stats.accuracy();
+        device.wait()?;
         let epoch_elapsed = epoch_start.elapsed();
         println!(
             "[{epoch}] train_loss: {train_loss} train_acc: {train_acc}% {train_correct}/{train_count} test_loss: {test_loss} test_acc: {test_acc}% {test_correct}/{test_count} elapsed: {epoch_elapsed:?}"
         );
     }
+    device.wait()?;
     println!("Finished in {:?}.", start.elapsed());
     Ok(())
 }
@@ -288,7 +283,7 @@ fn train<I: Iterator<Item = Result<(Tensor4<u8>, Tensor1<u8>)>>>(
     let mut stats = Stats::default();
     while let Some((x, t)) = iter.by_ref().next().transpose()? {
         stats.count += x.shape().first().unwrap();
-        model.set_training(true)?;
+        model.train()?;
         let x = Variable::from(ScalarTensor::from(x).scaled_cast(image_scale)?);
         let t = ScalarTensor::from(t).into_shared()?;
         let y = model.forward(x)?;
@@ -304,7 +299,7 @@ fn train<I: Iterator<Item = Result<(Tensor4<u8>, Tensor1<u8>)>>>(
         for parameter in model.parameters_mut()? {
             optimizer.update(learning_rate, parameter)?;
         }
-        model.set_training(false)?;
+        model.eval()?;
     }
     Ok(stats)
 }
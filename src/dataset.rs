@@ -1,3 +1,9 @@
+/// Datasets read lazily from disk.
+pub mod binary;
+
+/// Loading batches of samples.
+pub mod loader;
+
 /// The Iris dataset.
 #[cfg(feature = "iris")]
 pub mod iris;
@@ -0,0 +1,230 @@
+use crate::{
+    dataset::loader::DataLoader,
+    tensor::{Tensor1, TensorD},
+};
+use anyhow::{bail, format_err, Error, Result};
+use ndarray::{Array, IxDyn};
+use std::{
+    fs::File,
+    io::{Read, Seek, SeekFrom},
+    path::{Path, PathBuf},
+};
+
+/// BinaryDataset builder.
+pub mod builders {
+    use super::{BinaryDataset, Result};
+    use std::path::PathBuf;
+
+    /// BinaryDataset builder.
+    #[derive(Debug)]
+    pub struct BinaryDatasetBuilder {
+        pub(super) inputs_path: PathBuf,
+        pub(super) targets_path: PathBuf,
+        pub(super) input_shape: Vec<usize>,
+        pub(super) inputs_offset: u64,
+        pub(super) targets_offset: u64,
+    }
+
+    impl BinaryDatasetBuilder {
+        pub(super) fn new(inputs_path: PathBuf, targets_path: PathBuf) -> Self {
+            Self {
+                inputs_path,
+                targets_path,
+                input_shape: Vec::new(),
+                inputs_offset: 0,
+                targets_offset: 0,
+            }
+        }
+        /// The shape of each input sample. Defaults to a scalar, ie 1 byte per sample.
+        pub fn input_shape(self, input_shape: &[usize]) -> Self {
+            Self {
+                input_shape: input_shape.to_vec(),
+                ..self
+            }
+        }
+        /// The number of header bytes to skip in the inputs file. Defaults to 0.
+        pub fn inputs_offset(self, inputs_offset: u64) -> Self {
+            Self {
+                inputs_offset,
+                ..self
+            }
+        }
+        /// The number of header bytes to skip in the targets file. Defaults to 0.
+        pub fn targets_offset(self, targets_offset: u64) -> Self {
+            Self {
+                targets_offset,
+                ..self
+            }
+        }
+        /// Builds the dataset.
+        ///
+        /// **Errors**
+        /// - The files could not be opened.
+        /// - The input shape has a 0 dimension.
+        /// - The files are shorter than their offsets.
+        /// - The number of inputs and targets are not equal.
+        pub fn build(self) -> Result<BinaryDataset> {
+            BinaryDataset::build(self)
+        }
+    }
+}
+use builders::BinaryDatasetBuilder;
+
+/// A dataset of u8 samples read lazily from disk.
+///
+/// The inputs file contains contiguous samples of [`input_shape`](BinaryDatasetBuilder::input_shape)
+/// bytes, and the targets file contains 1 byte per sample, each after an optional header. Unlike
+/// [`Mnist`](crate::dataset::mnist::Mnist), samples are only read when a batch is requested, so
+/// the dataset does not need to fit in memory.
+///
+/// The raw MNIST idx files can be read with offsets of 16 and 8 bytes:
+///```no_run
+/// # use autograph::{anyhow::Result, dataset::{binary::BinaryDataset, loader::DataLoader}};
+/// # fn main() -> Result<()> {
+/// let mut dataset = BinaryDataset::builder(
+///     "mnist/train-images-idx3-ubyte",
+///     "mnist/train-labels-idx1-ubyte",
+/// )
+/// .input_shape(&[1, 28, 28])
+/// .inputs_offset(16)
+/// .targets_offset(8)
+/// .build()?;
+/// let mut loader = DataLoader::builder(dataset.len())
+///     .batch_size(100)
+///     .shuffle(0)
+///     .build()?;
+/// for batch in dataset.batches(&mut loader) {
+///     let (x, t) = batch?;
+///     // train on the batch
+/// }
+/// # Ok(())
+/// # }
+///```
+#[derive(Debug)]
+pub struct BinaryDataset {
+    inputs_path: PathBuf,
+    targets_path: PathBuf,
+    inputs: File,
+    targets: File,
+    input_shape: Vec<usize>,
+    inputs_offset: u64,
+    targets_offset: u64,
+    len: usize,
+}
+
+impl BinaryDataset {
+    /// Returns a builder for a dataset stored in `inputs_path` and `targets_path`.
+    pub fn builder(
+        inputs_path: impl AsRef<Path>,
+        targets_path: impl AsRef<Path>,
+    ) -> BinaryDatasetBuilder {
+        BinaryDatasetBuilder::new(
+            inputs_path.as_ref().to_owned(),
+            targets_path.as_ref().to_owned(),
+        )
+    }
+    fn build(builder: BinaryDatasetBuilder) -> Result<Self> {
+        let BinaryDatasetBuilder {
+            inputs_path,
+            targets_path,
+            input_shape,
+            inputs_offset,
+            targets_offset,
+        } = builder;
+        if input_shape.contains(&0) {
+            bail!("BinaryDataset input_shape {input_shape:?} has a 0 dimension!");
+        }
+        let sample_len: usize = input_shape.iter().product();
+        let open = |path: &Path, offset: u64| -> Result<(File, u64)> {
+            let file = File::open(path).map_err(|e| format_err!("BinaryDataset {path:?}: {e}"))?;
+            let file_len = file.metadata()?.len();
+            let Some(len) = file_len.checked_sub(offset) else {
+                bail!("BinaryDataset {path:?} len {file_len} is less than offset {offset}!");
+            };
+            Ok((file, len))
+        };
+        let (inputs, inputs_len) = open(&inputs_path, inputs_offset)?;
+        let (targets, targets_len) = open(&targets_path, targets_offset)?;
+        let len = (inputs_len / sample_len as u64) as usize;
+        if inputs_len % sample_len as u64 != 0 || targets_len != len as u64 {
+            bail!("BinaryDataset inputs len {inputs_len} does not match {targets_len} targets with input_shape {input_shape:?}!");
+        }
+        Ok(Self {
+            inputs_path,
+            targets_path,
+            inputs,
+            targets,
+            input_shape,
+            inputs_offset,
+            targets_offset,
+            len,
+        })
+    }
+    /// The path to the inputs.
+    pub fn inputs_path(&self) -> &Path {
+        &self.inputs_path
+    }
+    /// The path to the targets.
+    pub fn targets_path(&self) -> &Path {
+        &self.targets_path
+    }
+    /// The shape of each input sample.
+    pub fn input_shape(&self) -> &[usize] {
+        &self.input_shape
+    }
+    /// The number of samples.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+    /// Whether there are no samples.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+    /// Reads the samples at `indices`.
+    ///
+    /// Returns the inputs, with shape \[indices.len(), input_shape..\], and the targets, with
+    /// shape \[indices.len()\], on the host.
+    ///
+    /// **Errors**
+    /// - An index is out of bounds.
+    /// - Reading the files failed.
+    pub fn batch(&mut self, indices: &[u32]) -> Result<(TensorD<u8>, Tensor1<u8>)> {
+        let sample_len = self.input_shape.iter().product::<usize>();
+        let mut inputs = vec![0u8; indices.len() * sample_len];
+        let mut targets = vec![0u8; indices.len()];
+        for ((index, input), target) in indices
+            .iter()
+            .map(|i| *i as usize)
+            .zip(inputs.chunks_mut(sample_len))
+            .zip(targets.iter_mut())
+        {
+            if index >= self.len {
+                bail!(
+                    "BinaryDataset index {index} out of bounds for len {}!",
+                    self.len
+                );
+            }
+            self.inputs.seek(SeekFrom::Start(
+                self.inputs_offset + (index * sample_len) as u64,
+            ))?;
+            self.inputs.read_exact(input)?;
+            self.targets
+                .seek(SeekFrom::Start(self.targets_offset + index as u64))?;
+            self.targets.read_exact(std::slice::from_mut(target))?;
+        }
+        let mut shape = Vec::with_capacity(self.input_shape.len() + 1);
+        shape.push(indices.len());
+        shape.extend_from_slice(&self.input_shape);
+        let inputs = Array::from_shape_vec(IxDyn(&shape), inputs).map_err(Error::msg)?;
+        Ok((inputs.into(), Array::from(targets).into()))
+    }
+    /// Iterates over the batches of the current epoch of `loader`.
+    ///
+    /// Each batch is read when the iterator is advanced.
+    pub fn batches<'a>(
+        &'a mut self,
+        loader: &'a mut DataLoader,
+    ) -> impl Iterator<Item = Result<(TensorD<u8>, Tensor1<u8>)>> + 'a {
+        loader.map(move |indices| self.batch(&indices))
+    }
+}
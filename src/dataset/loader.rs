@@ -0,0 +1,251 @@
+use anyhow::{bail, Result};
+use rand::{rngs::StdRng, seq::SliceRandom, SeedableRng};
+use serde::{Deserialize, Serialize};
+
+/// DataLoader builder.
+pub mod builders {
+    use super::{DataLoader, Result};
+    use anyhow::bail;
+
+    /// DataLoader builder.
+    #[derive(Debug)]
+    pub struct DataLoaderBuilder {
+        pub(super) len: usize,
+        pub(super) batch_size: usize,
+        pub(super) shuffle: Option<u64>,
+        pub(super) drop_last: bool,
+    }
+
+    impl DataLoaderBuilder {
+        pub(super) fn new(len: usize) -> Self {
+            Self {
+                len,
+                batch_size: 1,
+                shuffle: None,
+                drop_last: false,
+            }
+        }
+        /// The number of samples per batch. Defaults to 1.
+        pub fn batch_size(self, batch_size: usize) -> Self {
+            Self { batch_size, ..self }
+        }
+        /// Shuffles the samples each epoch, seeded with `seed`.
+        ///
+        /// The permutation of each epoch is determined by `seed` and the epoch, so that training
+        /// is reproducible.
+        pub fn shuffle(self, seed: u64) -> Self {
+            Self {
+                shuffle: Some(seed),
+                ..self
+            }
+        }
+        /// Skips the last batch of each epoch if it is smaller than the batch size. Defaults to
+        /// false.
+        pub fn drop_last(self, drop_last: bool) -> Self {
+            Self { drop_last, ..self }
+        }
+        /// Builds the data loader.
+        ///
+        /// **Errors**
+        /// - The batch size is 0.
+        /// - The number of samples exceeds [`u32::MAX`].
+        pub fn build(self) -> Result<DataLoader> {
+            let Self {
+                len,
+                batch_size,
+                shuffle,
+                drop_last,
+            } = self;
+            if batch_size == 0 {
+                bail!("DataLoader batch_size must be greater than 0!");
+            }
+            if u32::try_from(len).is_err() {
+                bail!("DataLoader len {len} exceeds u32::MAX!");
+            }
+            let mut loader = DataLoader {
+                len,
+                batch_size,
+                shuffle,
+                drop_last,
+                epoch: 0,
+                index: 0,
+                permutation: Vec::new(),
+            };
+            loader.permute();
+            Ok(loader)
+        }
+    }
+}
+use builders::DataLoaderBuilder;
+
+/// Iterates over batches of sample indices.
+///
+/// Each epoch, the indices of the samples are optionally [shuffled](DataLoaderBuilder::shuffle)
+/// and split into batches. The iterator returns `None` at the end of each epoch, and then
+/// continues with the next epoch. The indices can be used to select samples, ie with
+/// [`TensorBase::index_select()`](crate::tensor::TensorBase::index_select).
+///
+/// The position within the epoch can be saved with [`.state()`](DataLoader::state) and
+/// restored with [`.restore()`](DataLoader::restore), to resume training from a checkpoint
+/// saved mid-epoch.
+///
+///```no_run
+/// # use autograph::{anyhow::Result, dataset::loader::DataLoader};
+/// # fn main() -> Result<()> {
+/// let mut loader = DataLoader::builder(60_000)
+///     .batch_size(100)
+///     .shuffle(0)
+///     .build()?;
+/// for indices in loader.by_ref().take(10) {
+///     // train on the batch
+/// }
+/// let state = loader.state();
+/// // save the state with the model and optimizer
+/// let mut loader = DataLoader::builder(60_000)
+///     .batch_size(100)
+///     .shuffle(0)
+///     .build()?;
+/// loader.restore(state)?;
+/// for indices in loader.by_ref() {
+///     // continue training
+/// }
+/// # Ok(())
+/// # }
+///```
+#[derive(Debug)]
+pub struct DataLoader {
+    len: usize,
+    batch_size: usize,
+    shuffle: Option<u64>,
+    drop_last: bool,
+    epoch: usize,
+    index: usize,
+    permutation: Vec<u32>,
+}
+
+impl DataLoader {
+    /// Returns a builder for a data loader of `len` samples.
+    pub fn builder(len: usize) -> DataLoaderBuilder {
+        DataLoaderBuilder::new(len)
+    }
+    /// The number of samples.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+    /// Whether there are no samples.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+    /// The batch size.
+    pub fn batch_size(&self) -> usize {
+        self.batch_size
+    }
+    /// The current epoch, starting at 0.
+    pub fn epoch(&self) -> usize {
+        self.epoch
+    }
+    /// The number of batches per epoch.
+    pub fn batches(&self) -> usize {
+        if self.drop_last {
+            self.len / self.batch_size
+        } else {
+            (self.len + self.batch_size - 1) / self.batch_size
+        }
+    }
+    /// The position of the loader.
+    pub fn state(&self) -> DataLoaderState {
+        DataLoaderState {
+            epoch: self.epoch,
+            index: self.index,
+            permutation: self.permutation.clone(),
+        }
+    }
+    /// Restores the position of the loader from `state`.
+    ///
+    /// The remaining batches of the epoch, and the following epochs, are the same as those of
+    /// the loader that the state was saved from, provided it was built with the same options.
+    ///
+    /// **Errors**
+    /// - The permutation is not a permutation of the samples.
+    /// - The index is out of bounds.
+    pub fn restore(&mut self, state: DataLoaderState) -> Result<()> {
+        let DataLoaderState {
+            epoch,
+            index,
+            permutation,
+        } = state;
+        if permutation.len() != self.len {
+            bail!(
+                "DataLoaderState permutation len {} != {}!",
+                permutation.len(),
+                self.len
+            );
+        }
+        let mut found = vec![false; self.len];
+        for i in permutation.iter().map(|i| *i as usize) {
+            if i >= self.len || found[i] {
+                bail!("DataLoaderState permutation is invalid!");
+            }
+            found[i] = true;
+        }
+        if index > self.len {
+            bail!(
+                "DataLoaderState index {index} out of bounds for len {}!",
+                self.len
+            );
+        }
+        self.epoch = epoch;
+        self.index = index;
+        self.permutation = permutation;
+        Ok(())
+    }
+    fn permute(&mut self) {
+        self.permutation.clear();
+        self.permutation.extend(0..self.len as u32);
+        if let Some(seed) = self.shuffle {
+            let mut rng = StdRng::seed_from_u64(seed.wrapping_add(self.epoch as u64));
+            self.permutation.shuffle(&mut rng);
+        }
+    }
+}
+
+impl Iterator for DataLoader {
+    type Item = Vec<u32>;
+    fn next(&mut self) -> Option<Vec<u32>> {
+        let end = (self.index + self.batch_size).min(self.len);
+        if self.index >= end || (self.drop_last && end - self.index < self.batch_size) {
+            self.epoch += 1;
+            self.index = 0;
+            self.permute();
+            return None;
+        }
+        let batch = self.permutation[self.index..end].to_vec();
+        self.index = end;
+        Some(batch)
+    }
+}
+
+/// The position of a [`DataLoader`].
+///
+/// Can be serialized with [`serde`], ie with the model and optimizer in a checkpoint.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DataLoaderState {
+    epoch: usize,
+    index: usize,
+    permutation: Vec<u32>,
+}
+
+impl DataLoaderState {
+    /// The epoch.
+    pub fn epoch(&self) -> usize {
+        self.epoch
+    }
+    /// The index of the next sample in the permutation.
+    pub fn index(&self) -> usize {
+        self.index
+    }
+    /// The order of the samples in the epoch.
+    pub fn permutation(&self) -> &[u32] {
+        &self.permutation
+    }
+}
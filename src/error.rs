@@ -0,0 +1,97 @@
+use krnl::{device::Device, scalar::ScalarType};
+use std::fmt::{self, Display};
+
+/// Errors that can be matched on.
+///
+/// Operations return [`anyhow::Error`], which can be downcast to an [`AutographError`] to react
+/// to specific failures. Other errors, ie from the device, are not converted.
+///
+///```no_run
+/// # use autograph::{anyhow::Result, error::AutographError, krnl::device::Device, tensor::Tensor};
+/// # fn main() -> Result<()> {
+/// let x = Tensor::from(vec![1f32, 2.]);
+/// let e = Tensor::from(vec![2f32, 2., 2.]);
+/// let error = x.into_scalar_tensor().pow(&e.into_scalar_tensor()).unwrap_err();
+/// if let Some(AutographError::ShapeMismatch { expected, actual, .. }) = error.downcast_ref() {
+///     eprintln!("expected {expected:?} found {actual:?}");
+/// }
+/// # Ok(())
+/// # }
+///```
+#[derive(Clone, Debug, PartialEq)]
+#[non_exhaustive]
+pub enum AutographError {
+    /// The scalar type is not implemented for the operation.
+    UnsupportedScalarType {
+        /// The operation.
+        op: String,
+        /// The scalar type.
+        scalar_type: ScalarType,
+    },
+    /// The shapes of the operands are not compatible.
+    ShapeMismatch {
+        /// The operation.
+        op: String,
+        /// The expected shape.
+        expected: Vec<usize>,
+        /// The actual shape.
+        actual: Vec<usize>,
+    },
+    /// The devices of the operands are not the same.
+    DeviceMismatch {
+        /// The operation.
+        op: String,
+        /// The expected device.
+        expected: Device,
+        /// The actual device.
+        actual: Device,
+    },
+}
+
+impl AutographError {
+    pub(crate) fn unsupported_scalar_type(op: &str, scalar_type: ScalarType) -> Self {
+        Self::UnsupportedScalarType {
+            op: op.to_owned(),
+            scalar_type,
+        }
+    }
+    pub(crate) fn shape_mismatch(op: &str, expected: &[usize], actual: &[usize]) -> Self {
+        Self::ShapeMismatch {
+            op: op.to_owned(),
+            expected: expected.to_vec(),
+            actual: actual.to_vec(),
+        }
+    }
+    pub(crate) fn device_mismatch(op: &str, expected: Device, actual: Device) -> Self {
+        Self::DeviceMismatch {
+            op: op.to_owned(),
+            expected,
+            actual,
+        }
+    }
+}
+
+impl Display for AutographError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::UnsupportedScalarType { op, scalar_type } => {
+                write!(f, "{op} {scalar_type:?} not implemented!")
+            }
+            Self::ShapeMismatch {
+                op,
+                expected,
+                actual,
+            } => write!(f, "{op} shape {expected:?} != {actual:?}!"),
+            Self::DeviceMismatch {
+                op,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "{op} device {actual:?} does not match {expected:?}! Transfer with `.into_device()` or `.to_device_mut()`."
+            ),
+        }
+    }
+}
+
+impl std::error::Error for AutographError {}
@@ -2,5 +2,11 @@
 #[cfg(feature = "neural-network")]
 pub mod neural_network;
 
+/// Augmentation.
+pub mod augment;
+
 /// Criterion.
 pub mod criterion;
+
+/// Metrics.
+pub mod metrics;
@@ -0,0 +1,199 @@
+/*!
+Batch augmentation.
+
+[`mixup()`] and [`cutmix()`] blend each sample with another sample from the same batch. They
+return the blended images and the labels of both samples along with the fraction `lambda` of
+the first sample, so that the loss is computed as
+`lambda * loss(output, labels_a) + (1 - lambda) * loss(output, labels_b)`.
+
+Randomness is drawn from `rng` on the host, so results are reproducible with a seeded
+generator, ie [`StdRng::seed_from_u64()`](rand::SeedableRng::seed_from_u64).
+*/
+use crate::tensor::{Tensor, Tensor1, TensorBase, TensorView1};
+use anyhow::{bail, Result};
+use krnl::{
+    buffer::Data,
+    device::Device,
+    scalar::{Scalar, ScalarType},
+};
+use ndarray::{s, Axis, Ix4, RemoveAxis};
+use rand::{seq::SliceRandom, Rng};
+
+/// Mixup augmentation.
+///
+/// Samples `lambda` from Beta(`alpha`, `alpha`), taking the larger of `lambda` and
+/// `1 - lambda`, and blends the batch with a random permutation of itself, ie
+/// `lambda * images + (1 - lambda) * images[perm]`. As `alpha` approaches 0, `lambda`
+/// approaches 1 and the images are not mixed. An `alpha` of 0 disables mixing.
+///
+/// Returns the blended images and `(labels, labels[perm], lambda)`.
+///
+/// **Errors**
+/// - `T` is not a floating point type.
+/// - `alpha` is negative.
+/// - The batch sizes of `images` and `labels` are not equal.
+/// - The operation could not be executed on the device.
+pub fn mixup<T, S, D, U, R>(
+    images: &TensorBase<S, D>,
+    labels: TensorView1<U>,
+    alpha: f32,
+    rng: &mut R,
+) -> Result<(Tensor<T, D>, (Tensor1<U>, Tensor1<U>, f32))>
+where
+    T: Scalar,
+    S: Data<Elem = T>,
+    D: RemoveAxis,
+    U: Scalar,
+    R: Rng,
+{
+    check_inputs(
+        "mixup",
+        images.shape(),
+        labels.shape(),
+        T::scalar_type(),
+        alpha,
+    )?;
+    let lambda = sample_lambda(rng, alpha);
+    let perm = permutation(rng, labels.len(), labels.device())?;
+    let images_b = images.index_select(Axis(0), perm.view())?;
+    let labels_b = labels.index_select(Axis(0), perm.view())?;
+    let mut output = images_b.scaled_cast(T::from_f32(1. - lambda).unwrap())?;
+    output.scaled_add(T::from_f32(lambda).unwrap(), images)?;
+    Ok((output, (labels.to_owned()?, labels_b, lambda)))
+}
+
+/// CutMix augmentation.
+///
+/// Samples `lambda` from Beta(`alpha`, `alpha`), taking the larger of `lambda` and
+/// `1 - lambda`, and replaces a random box covering about `1 - lambda` of each image with the
+/// same box from a random permutation of the batch. `lambda` is then adjusted to the fraction
+/// of each image that was kept. As `alpha` approaches 0 the box vanishes. An `alpha` of 0
+/// disables mixing.
+///
+/// `images` have shape [batch_size, channels, height, width].
+///
+/// Returns the mixed images and `(labels, labels[perm], lambda)`.
+///
+/// **Errors**
+/// - `T` is not a floating point type.
+/// - `alpha` is negative.
+/// - The batch sizes of `images` and `labels` are not equal.
+/// - The operation could not be executed on the device.
+pub fn cutmix<T, S, U, R>(
+    images: &TensorBase<S, Ix4>,
+    labels: TensorView1<U>,
+    alpha: f32,
+    rng: &mut R,
+) -> Result<(Tensor<T, Ix4>, (Tensor1<U>, Tensor1<U>, f32))>
+where
+    T: Scalar,
+    S: Data<Elem = T>,
+    U: Scalar,
+    R: Rng,
+{
+    check_inputs(
+        "cutmix",
+        images.shape(),
+        labels.shape(),
+        T::scalar_type(),
+        alpha,
+    )?;
+    let (_, _, height, width) = images.dim();
+    let lambda = sample_lambda(rng, alpha);
+    let perm = permutation(rng, labels.len(), labels.device())?;
+    let labels_b = labels.index_select(Axis(0), perm.view())?;
+    let ratio = (1. - lambda).sqrt();
+    let cut_h = (height as f32 * ratio) as usize;
+    let cut_w = (width as f32 * ratio) as usize;
+    let cy = rng.gen_range(0..height.max(1));
+    let cx = rng.gen_range(0..width.max(1));
+    let y0 = cy.saturating_sub(cut_h / 2);
+    let y1 = (cy + cut_h / 2).min(height);
+    let x0 = cx.saturating_sub(cut_w / 2);
+    let x1 = (cx + cut_w / 2).min(width);
+    let mut output = images.to_owned()?;
+    let area = (y1 - y0) * (x1 - x0);
+    if area > 0 {
+        let images_b = images.index_select(Axis(0), perm.view())?;
+        output
+            .slice_mut(s![.., .., y0..y1, x0..x1])
+            .assign(&images_b.slice(s![.., .., y0..y1, x0..x1]))?;
+    }
+    let lambda = 1. - area as f32 / (height * width).max(1) as f32;
+    Ok((output, (labels.to_owned()?, labels_b, lambda)))
+}
+
+fn check_inputs(
+    name: &str,
+    images_shape: &[usize],
+    labels_shape: &[usize],
+    scalar_type: ScalarType,
+    alpha: f32,
+) -> Result<()> {
+    if !matches!(
+        scalar_type,
+        ScalarType::F16 | ScalarType::BF16 | ScalarType::F32 | ScalarType::F64
+    ) {
+        bail!("{name} {scalar_type:?} unimplemented!");
+    }
+    if alpha.is_nan() || alpha < 0. {
+        bail!("{name} alpha {alpha} must be non-negative!");
+    }
+    if images_shape.first() != labels_shape.first() {
+        bail!(
+            "{name} images shape {images_shape:?} incompatible with labels shape {labels_shape:?}!"
+        );
+    }
+    Ok(())
+}
+
+fn permutation<R: Rng>(rng: &mut R, len: usize, device: Device) -> Result<Tensor1<u32>> {
+    let mut perm: Vec<u32> = (0..len as u32).collect();
+    perm.shuffle(rng);
+    Tensor::from(perm).into_device(device)
+}
+
+fn sample_lambda<R: Rng>(rng: &mut R, alpha: f32) -> f32 {
+    if alpha > 0. {
+        let lambda = sample_beta(rng, alpha.into());
+        lambda.max(1. - lambda) as f32
+    } else {
+        1.
+    }
+}
+
+// Beta(alpha, alpha) as X / (X + Y) with X, Y ~ Gamma(alpha), in log space to avoid 0 / 0 for
+// small alpha.
+fn sample_beta<R: Rng>(rng: &mut R, alpha: f64) -> f64 {
+    let ln_x = sample_ln_gamma(rng, alpha);
+    let ln_y = sample_ln_gamma(rng, alpha);
+    1. / (1. + (ln_y - ln_x).exp())
+}
+
+// Marsaglia and Tsang, with Gamma(a) = Gamma(a + 1) * U^(1 / a) for a < 1.
+fn sample_ln_gamma<R: Rng>(rng: &mut R, shape: f64) -> f64 {
+    if shape < 1. {
+        let u = 1. - rng.gen::<f64>();
+        return sample_ln_gamma(rng, shape + 1.) + u.ln() / shape;
+    }
+    let d = shape - 1. / 3.;
+    let c = 1. / (9. * d).sqrt();
+    loop {
+        let z = sample_normal(rng);
+        let v = 1. + c * z;
+        if v <= 0. {
+            continue;
+        }
+        let v = v * v * v;
+        let u = 1. - rng.gen::<f64>();
+        if u.ln() < 0.5 * z * z + d - d * v + d * v.ln() {
+            return (d * v).ln();
+        }
+    }
+}
+
+fn sample_normal<R: Rng>(rng: &mut R) -> f64 {
+    let u1 = 1. - rng.gen::<f64>();
+    let u2 = rng.gen::<f64>();
+    (-2. * u1.ln()).sqrt() * (std::f64::consts::TAU * u2).cos()
+}
@@ -10,7 +10,7 @@ use krnl::buffer::Slice;
 use krnl::macros::module;
 use krnl::{
     buffer::{Data, ScalarData},
-    scalar::Scalar,
+    scalar::{Scalar, ScalarType},
 };
 use ndarray::{ArrayView1, ArrayView2, Ix1, Ix2};
 #[cfg(feature = "device")]
@@ -23,7 +23,8 @@ use paste::paste;
 pub trait Accuracy<T> {
     /// Accuracy of a prediction given `target`.
     ///
-    /// Returns the number of correct predictions.
+    /// Returns the number of correct predictions, where the prediction is the class with the
+    /// largest value. Ties select the first class.
     fn accuracy(&self, target: T) -> Result<usize>;
 }
 
@@ -102,10 +103,12 @@ impl<S1: ScalarData, S2: ScalarData> Accuracy<ScalarTensorBase<S2, Ix1>>
         #[cfg(feature = "device")]
         {
             let (batch_size, classes) = self.dim();
+            let input = self.contiguous()?;
+            let target = target.contiguous()?;
             macro_for!($T1 in [bf16, f32] {
                 macro_for!($T2 in [u8, u16, u32] {
                     if self.scalar_type() == $T1::scalar_type() && target.scalar_type() == $T2::scalar_type() {
-                        let input = Slice::<$T1>::try_from(self.as_scalar_slice().unwrap()).unwrap();
+                        let input = Slice::<$T1>::try_from(input.as_scalar_slice().unwrap()).unwrap();
                         let target = Slice::<$T2>::try_from(target.as_scalar_slice().unwrap()).unwrap();
                         let mut output = unsafe { Tensor::<u32, _>::uninit(input.device(), batch_size)? };
                         paste! {
@@ -131,12 +134,101 @@ impl<S1: ScalarData, S2: ScalarData> Accuracy<ScalarTensorBase<S2, Ix1>>
     }
 }
 
+/// Options for [`CrossEntropyLoss`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CrossEntropyLossOptions {
+    /// The scalar type the loss is computed and summed in.
+    ///
+    /// Either F32 or F64, defaults to F32. Use F64 for validation. On devices, the exponentials
+    /// and logarithms are computed in F32.
+    pub accumulate_in: ScalarType,
+}
+
+impl Default for CrossEntropyLossOptions {
+    fn default() -> Self {
+        Self {
+            accumulate_in: ScalarType::F32,
+        }
+    }
+}
+
+impl CrossEntropyLossOptions {
+    /// Computes and sums the loss in `accumulate_in`.
+    pub fn accumulate_in(self, accumulate_in: ScalarType) -> Self {
+        Self {
+            accumulate_in,
+            ..self
+        }
+    }
+}
+
 /// Cross Entropy Loss.
+///
+/// Each row is reduced in two passes, first the max and then the sum of the exponentials
+/// shifted by the max, which is numerically stable and supports any number of classes.
 pub trait CrossEntropyLoss<T> {
     /// Type of the output.
     type Output;
     /// Computes the loss given `target`.
-    fn cross_entropy_loss(&self, target: T) -> Result<Self::Output>;
+    ///
+    /// See [`.cross_entropy_loss_with()`](CrossEntropyLoss::cross_entropy_loss_with).
+    fn cross_entropy_loss(&self, target: T) -> Result<Self::Output> {
+        self.cross_entropy_loss_with(target, CrossEntropyLossOptions::default())
+    }
+    /// Computes the loss given `target` with `options`.
+    ///
+    ///```no_run
+    /// # use autograph::{anyhow::Result, krnl::scalar::ScalarType, tensor::{Tensor1, Tensor2}};
+    /// # use autograph::half::bf16;
+    /// use autograph::learn::criterion::{CrossEntropyLoss, CrossEntropyLossOptions};
+    /// # fn main() -> Result<()> {
+    /// # let x: Tensor2<bf16> = todo!();
+    /// # let t: Tensor1<u8> = todo!();
+    /// let options = CrossEntropyLossOptions::default().accumulate_in(ScalarType::F64);
+    /// let loss = x.cross_entropy_loss_with(t, options)?;
+    /// # Ok(())
+    /// # }
+    ///```
+    ///
+    /// **Errors**
+    /// - `options.accumulate_in` is not F32 or F64.
+    /// - The operation could not be executed on the device.
+    fn cross_entropy_loss_with(
+        &self,
+        target: T,
+        options: CrossEntropyLossOptions,
+    ) -> Result<Self::Output>;
+}
+
+/// Multi Margin Loss, ie hinge loss for classification.
+///
+/// For each sample, the loss of each class `i` other than the target is
+/// `max(0, margin - x[target] + x[i])`. Like PyTorch, the losses of each sample are summed and
+/// divided by the number of classes, and the output is the mean over the batch. It is 0 when the
+/// target exceeds every other class by at least `margin`.
+pub trait MultiMarginLoss<T> {
+    /// Type of the output.
+    type Output;
+    /// Computes the loss given `target` with `margin`, typically 1.
+    ///
+    ///```no_run
+    /// # use autograph::{anyhow::Result, tensor::ScalarArcTensor1};
+    /// # use autograph::learn::neural_network::autograd::Variable2;
+    /// use autograph::learn::criterion::MultiMarginLoss;
+    /// # fn main() -> Result<()> {
+    /// # let x: Variable2 = todo!();
+    /// # let t: ScalarArcTensor1 = todo!();
+    /// let loss = x.multi_margin_loss(t, 1.)?;
+    /// loss.backward()?;
+    /// # Ok(())
+    /// # }
+    ///```
+    ///
+    /// **Errors**
+    /// - There are fewer than 2 classes.
+    /// - The target is out of bounds.
+    /// - The operation could not be executed on the device.
+    fn multi_margin_loss(&self, target: T, margin: f32) -> Result<Self::Output>;
 }
 
 /// Implemented for:
@@ -146,12 +238,16 @@ impl<S1: ScalarData, S2: ScalarData> CrossEntropyLoss<ScalarTensorBase<S2, Ix1>>
     for ScalarTensorBase<S1, Ix2>
 {
     type Output = f32;
-    fn cross_entropy_loss(&self, target: ScalarTensorBase<S2, Ix1>) -> Result<Self::Output> {
+    fn cross_entropy_loss_with(
+        &self,
+        target: ScalarTensorBase<S2, Ix1>,
+        options: CrossEntropyLossOptions,
+    ) -> Result<Self::Output> {
         macro_for!($T1 in [bf16, f32] {
             if let Ok(input) = TensorView2::<$T1>::try_from(self.view()) {
                 macro_for!($T2 in [u8, u16, u32] {
                     if let Ok(target) = TensorView1::<$T2>::try_from(target.view()) {
-                        return input.cross_entropy_loss(target).map(Into::into);
+                        return input.cross_entropy_loss_with(target, options);
                     }
                 });
             }
@@ -171,9 +267,21 @@ impl<T1: Scalar + Float, S1: Data<Elem = T1>, T2: Scalar + Unsigned, S2: Data<El
     CrossEntropyLoss<TensorBase<S2, Ix1>> for TensorBase<S1, Ix2>
 {
     type Output = f32;
-    fn cross_entropy_loss(&self, target: TensorBase<S2, Ix1>) -> Result<Self::Output> {
+    fn cross_entropy_loss_with(
+        &self,
+        target: TensorBase<S2, Ix1>,
+        options: CrossEntropyLossOptions,
+    ) -> Result<Self::Output> {
+        let accumulate_in = options.accumulate_in;
+        if !matches!(accumulate_in, ScalarType::F32 | ScalarType::F64) {
+            bail!("CrossEntropyLoss accumulate_in {accumulate_in:?} unimplemented!");
+        }
         if let Some((input, target)) = self.as_array().zip(target.as_array()) {
-            Ok(cross_entropy_loss_host(input, target))
+            if accumulate_in == ScalarType::F64 {
+                Ok(cross_entropy_loss_host::<T1, T2, f64>(input, target).cast())
+            } else {
+                Ok(cross_entropy_loss_host::<T1, T2, f32>(input, target))
+            }
         } else {
             #[cfg(not(feature = "device"))]
             {
@@ -181,58 +289,68 @@ impl<T1: Scalar + Float, S1: Data<Elem = T1>, T2: Scalar + Unsigned, S2: Data<El
             }
             #[cfg(feature = "device")]
             {
-                cross_entropy_loss_device(self.view().into(), target.view().into())
+                cross_entropy_loss_device(self.view().into(), target.view().into(), accumulate_in)
             }
         }
     }
 }
 
-fn cross_entropy_loss_host<T1: Scalar + Float, T2: Scalar + Unsigned>(
+fn cross_entropy_loss_host<T1: Scalar + Float, T2: Scalar + Unsigned, A: Scalar + Float>(
     input: ArrayView2<T1>,
     target: ArrayView1<T2>,
-) -> f32 {
+) -> A {
     let x = input;
     let t = target;
-    let mut y = 0f32;
+    let mut y = A::zero();
     for (x, t) in x.outer_iter().zip(t.iter().copied()) {
         let m = x
             .iter()
-            .map(|x| x.cast::<f32>())
-            .fold(x[0].cast::<f32>(), f32::max);
+            .map(|x| x.cast::<A>())
+            .fold(x[0].cast::<A>(), Float::max);
         let s = x
             .iter()
             .copied()
-            .map(|x| (x.cast::<f32>() - m).exp())
-            .sum::<f32>();
+            .map(|x| (x.cast::<A>() - m).exp())
+            .fold(A::zero(), |s, x| s + x);
         let x = x[t.to_usize().unwrap()];
-        y += s.ln() - (x.cast::<f32>() - m);
+        y += s.ln() - (x.cast::<A>() - m);
     }
     y
 }
 
 #[cfg(feature = "device")]
-fn cross_entropy_loss_device(input: ScalarTensorView2, target: ScalarTensorView1) -> Result<f32> {
+fn cross_entropy_loss_device(
+    input: ScalarTensorView2,
+    target: ScalarTensorView1,
+    accumulate_in: ScalarType,
+) -> Result<f32> {
     macro_for!($T1 in [bf16, f32] {
         if let Ok(input) = TensorView2::<$T1>::try_from(input.view()) {
             let (batch_size, classes) = input.dim();
+            let input = input.contiguous()?;
             let input = input.as_slice().unwrap();
             macro_for!($T2 in [u8, u16, u32] {
                 if let Ok(target) = TensorView1::<$T2>::try_from(target.view()) {
+                    let target = target.contiguous()?;
                     let target = target.as_slice().unwrap();
-                    let mut output = unsafe { Tensor::<f32, _>::uninit(input.device(), batch_size)? };
                     let classes = classes.to_u32().unwrap();
-                    let kernel = paste! {
-                        kernels::[<cross_entropy_loss_ $T1 _ $T2>]::builder()?
-                        .build(output.device())?
-                    };
-                    kernel.dispatch(input, target, classes, output.as_slice_mut().unwrap())?;
-                    return output.sum();
+                    macro_for!($A in [f32, f64] {
+                        if accumulate_in == $A::scalar_type() {
+                            let mut output = unsafe { Tensor::<$A, _>::uninit(input.device(), batch_size)? };
+                            let kernel = paste! {
+                                kernels::[<cross_entropy_loss_ $T1 _ $T2 _ $A>]::builder()?
+                                .build(output.device())?
+                            };
+                            kernel.dispatch(input, target, classes, output.as_slice_mut().unwrap())?;
+                            return output.sum().map(|x| x.cast());
+                        }
+                    });
                 }
             });
         }
     });
     bail!(
-        "CrossEntropyLoss {:?} {:?} unimplemented!",
+        "CrossEntropyLoss {:?} {:?} {accumulate_in:?} unimplemented!",
         input.scalar_type(),
         target.scalar_type()
     )
@@ -262,48 +380,51 @@ mod kernels {
                     let classes = classes as usize;
                     let idx = kernel.item_id as usize;
                     let t = t[idx] as usize;
-                    if t > classes {
+                    if t >= classes {
                         *y = 0;
                         return;
                     }
                     let xt = x[idx * classes + t];
                     for i in 0..classes {
-                        if i == t {
-                            continue;
-                        }
                         let x = x[idx * classes + i];
-                        if !(xt > x) {
+                        // ties select the first class like the host
+                        if (i < t && !(xt > x)) || (i > t && x > xt) {
                             *y = 0;
                             return;
                         }
                     }
                     *y = 1;
                 }
-
-                #[kernel]
-                pub fn [<cross_entropy_loss_ $T1 _ $T2>](
-                    #[global] x: Slice<$T1>,
-                    #[global] t: Slice<$T2>,
-                    classes: u32,
-                    #[item] y: &mut f32,
-                ) {
-                    let classes = classes as usize;
-                    let idx = kernel.item_id as usize;
-                    let mut m = x[(idx * classes) as usize].cast::<f32>();
-                    for i in 1..classes {
-                        let x = x[(idx * classes + i) as usize].cast::<f32>();
-                        m = m.max(x);
-                    }
-                    let mut s = 0f32;
-                    for i in 0..classes {
-                        let x = x[(idx * classes + i) as usize].cast::<f32>();
-                        s += (x - m).exp();
+            }
+            macro_for!($A in [f32, f64] {
+                paste! {
+                    #[kernel]
+                    pub fn [<cross_entropy_loss_ $T1 _ $T2 _ $A>](
+                        #[global] x: Slice<$T1>,
+                        #[global] t: Slice<$T2>,
+                        classes: u32,
+                        #[item] y: &mut $A,
+                    ) {
+                        let classes = classes as usize;
+                        let idx = kernel.item_id as usize;
+                        let mut m = x[(idx * classes) as usize].cast::<$A>();
+                        for i in 1..classes {
+                            let x = x[(idx * classes + i) as usize].cast::<$A>();
+                            m = m.max(x);
+                        }
+                        // exp and ln are only implemented for f32 on the device, the sum is
+                        // accumulated in the accumulation type
+                        let mut s: $A = 0.;
+                        for i in 0..classes {
+                            let x = x[(idx * classes + i) as usize].cast::<$A>();
+                            s += (x - m).cast::<f32>().exp().cast::<$A>();
+                        }
+                        let t = t[idx as usize] as usize;
+                        let x = x[idx * classes + t].cast::<$A>();
+                        *y = s.cast::<f32>().ln().cast::<$A>() - (x - m);
                     }
-                    let t = t[idx as usize] as usize;
-                    let x = x[idx * classes + t].cast::<f32>();
-                    *y = s.ln() - (x - m);
                 }
-            }
+            });
         });
     });
 }
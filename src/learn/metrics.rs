@@ -0,0 +1,161 @@
+use crate::tensor::ScalarTensorBase;
+use anyhow::{bail, Result};
+use krnl::{buffer::ScalarData, scalar::ScalarType};
+use ndarray::Ix1;
+use std::cmp::Ordering;
+
+/// Accumulates scores and binary labels to compute the area under the ROC and PR curves.
+///
+/// Unlike accuracy, these are independent of a decision threshold. Scores and labels are
+/// gathered on the host with [`.update()`](AucMetrics::update) for each batch, and sorted when
+/// computing the metrics.
+///
+///```no_run
+/// # use autograph::{anyhow::Result, learn::metrics::AucMetrics, tensor::{ScalarTensor1, Tensor1}};
+/// # fn main() -> Result<()> {
+/// # let batches: Vec<(ScalarTensor1, Tensor1<u8>)> = todo!();
+/// let mut metrics = AucMetrics::new();
+/// for (scores, labels) in batches {
+///     metrics.update(&scores, &labels.into_scalar_tensor())?;
+/// }
+/// let roc_auc = metrics.roc_auc()?;
+/// let pr_auc = metrics.pr_auc()?;
+/// # Ok(())
+/// # }
+///```
+#[derive(Default, Clone, Debug)]
+pub struct AucMetrics {
+    scores: Vec<f32>,
+    labels: Vec<bool>,
+}
+
+impl AucMetrics {
+    /// Creates an empty accumulator.
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// The number of samples.
+    pub fn len(&self) -> usize {
+        self.scores.len()
+    }
+    /// Whether there are no samples.
+    pub fn is_empty(&self) -> bool {
+        self.scores.is_empty()
+    }
+    /// Clears the samples.
+    pub fn reset(&mut self) {
+        self.scores.clear();
+        self.labels.clear();
+    }
+    /// Adds a batch of `scores` and `labels`.
+    ///
+    /// `scores` are the predicted probability or logit of the positive class. Non-zero `labels`
+    /// are positive.
+    ///
+    /// **Errors**
+    /// - The lengths of `scores` and `labels` are not equal.
+    /// - A score is NaN.
+    /// - The tensors could not be copied to the host.
+    pub fn update<S1: ScalarData, S2: ScalarData>(
+        &mut self,
+        scores: &ScalarTensorBase<S1, Ix1>,
+        labels: &ScalarTensorBase<S2, Ix1>,
+    ) -> Result<()> {
+        if scores.len() != labels.len() {
+            bail!(
+                "AucMetrics scores len {} != labels len {}!",
+                scores.len(),
+                labels.len()
+            );
+        }
+        let scores = scores
+            .cast(ScalarType::F32)?
+            .try_into_tensor::<f32>()
+            .unwrap();
+        let scores = scores.into_vec()?;
+        if scores.iter().any(|x| x.is_nan()) {
+            bail!("AucMetrics scores contain NaN!");
+        }
+        let labels = labels
+            .cast(ScalarType::F32)?
+            .try_into_tensor::<f32>()
+            .unwrap();
+        let labels = labels.into_vec()?;
+        self.scores.extend(scores);
+        self.labels.extend(labels.into_iter().map(|x| x != 0.));
+        Ok(())
+    }
+    /// The area under the receiver operating characteristic curve.
+    ///
+    /// The probability that a random positive sample is scored higher than a random negative
+    /// sample, with ties counted as half.
+    ///
+    /// **Errors**
+    /// - There are no positive or no negative samples.
+    pub fn roc_auc(&self) -> Result<f32> {
+        let (positives, negatives) = self.counts("roc_auc")?;
+        let mut order: Vec<usize> = (0..self.len()).collect();
+        order.sort_by(|a, b| self.compare(*a, *b));
+        // Sum of the ranks of the positives, averaging the ranks of ties.
+        let mut rank_sum = 0f64;
+        let mut start = 0;
+        for group in score_groups(&order, &self.scores) {
+            let end = start + group.len();
+            let rank = (start + 1 + end) as f64 / 2.;
+            let group_positives = group.iter().filter(|i| self.labels[**i]).count();
+            rank_sum += rank * group_positives as f64;
+            start = end;
+        }
+        let (p, n) = (positives as f64, negatives as f64);
+        Ok(((rank_sum - p * (p + 1.) / 2.) / (p * n)) as f32)
+    }
+    /// The area under the precision recall curve.
+    ///
+    /// Computed as the average precision, ie the sum of the precision at each threshold
+    /// weighted by the increase in recall.
+    ///
+    /// **Errors**
+    /// - There are no positive or no negative samples.
+    pub fn pr_auc(&self) -> Result<f32> {
+        let (positives, _) = self.counts("pr_auc")?;
+        let mut order: Vec<usize> = (0..self.len()).collect();
+        order.sort_by(|a, b| self.compare(*b, *a));
+        let mut true_positives = 0;
+        let mut count = 0;
+        let mut average_precision = 0f64;
+        for group in score_groups(&order, &self.scores) {
+            let group_positives = group.iter().filter(|i| self.labels[**i]).count();
+            true_positives += group_positives;
+            count += group.len();
+            let precision = true_positives as f64 / count as f64;
+            average_precision += precision * group_positives as f64 / positives as f64;
+        }
+        Ok(average_precision as f32)
+    }
+    fn counts(&self, name: &str) -> Result<(usize, usize)> {
+        let positives = self.labels.iter().filter(|x| **x).count();
+        let negatives = self.labels.len() - positives;
+        if positives == 0 || negatives == 0 {
+            bail!("AucMetrics::{name} requires positive and negative samples, found {positives} positives and {negatives} negatives!");
+        }
+        Ok((positives, negatives))
+    }
+    fn compare(&self, a: usize, b: usize) -> Ordering {
+        self.scores[a].partial_cmp(&self.scores[b]).unwrap()
+    }
+}
+
+// Splits indices sorted by score into groups with equal scores.
+fn score_groups<'a>(order: &'a [usize], scores: &'a [f32]) -> impl Iterator<Item = &'a [usize]> {
+    let mut rest = order;
+    std::iter::from_fn(move || {
+        let first = *rest.first()?;
+        let len = rest
+            .iter()
+            .position(|i| scores[*i] != scores[first])
+            .unwrap_or(rest.len());
+        let (group, tail) = rest.split_at(len);
+        rest = tail;
+        Some(group)
+    })
+}
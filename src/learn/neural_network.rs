@@ -30,7 +30,7 @@ Example:
 # use autograph::{krnl::{device::Device, scalar::ScalarType}, tensor::{Tensor, ScalarArcTensor}};
 # use autograph::learn::neural_network::{
 #   autograd::{Variable2, Variable4},
-#   layer::{Layer, Forward, Conv2, Dense, MaxPool2, Flatten, Relu},
+#   layer::{Layer, Forward, Conv2, Dense, MaxPool2, Flatten, Relu, infer_flatten_size},
 #   optimizer::{Optimizer, SGD},
 # };
 # use autograph::learn::criterion::CrossEntropyLoss;
@@ -67,11 +67,21 @@ impl LeNet5 {
             .activation(Relu)
             .build()?;
         let pool2 = MaxPool2::builder().filter([2, 2]).build();
+        let inputs = infer_flatten_size(
+            1,
+            [28, 28],
+            &[
+                (&conv1).into(),
+                (&pool1).into(),
+                (&conv2).into(),
+                (&pool2).into(),
+            ],
+        )?;
         let flatten = Flatten;
         let dense1 = Dense::builder()
             .device(device.clone())
             .scalar_type(scalar_type)
-            .inputs(16 * 4 * 4)
+            .inputs(inputs)
             .outputs(128)
             .activation(Relu)
             .build()?;
@@ -125,7 +135,13 @@ pub mod autograd;
 // public for testing
 #[doc(hidden)]
 pub mod criterion;
+pub mod functional;
 /// Layers.
 pub mod layer;
+/// ONNX export.
+pub mod onnx;
 /// Optimizers.
 pub mod optimizer;
+/// PyTorch import.
+#[cfg(feature = "pytorch")]
+pub mod pytorch;
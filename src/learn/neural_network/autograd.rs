@@ -1,36 +1,130 @@
+/*!
+Variables, parameters, and the graph used to compute gradients.
+
+# Custom operations
+Differentiable operations are functions of variables. Use [`Variable::builder()`] to create the
+output, adding an [edge](VariableBuilder::edge) for each input that has a [`Node`]. Each edge
+computes the gradient of the input given the gradient of the output. Edges are only executed
+during the backward pass, within [`no_grad()`] no edges are added.
+
+The gradient returned by an edge must have the same shape, scalar type, and device as the input,
+otherwise the backward pass will panic. Gradients of inputs used by multiple edges are
+accumulated.
+
+```no_run
+# use autograph::{anyhow::Result, krnl::device::Device, tensor::{ScalarArcTensor1, Tensor}};
+use autograph::learn::neural_network::autograd::{Variable, Variable1};
+
+// y = x * x
+fn square(x: &Variable1) -> Result<Variable1> {
+    let device = x.device();
+    let scalar_type = x.scalar_type();
+    let mut builder = Variable::builder();
+    if let Some(node) = x.node() {
+        let x = x.value().clone();
+        let device = device.clone();
+        builder.edge(node, move |output_grad: ScalarArcTensor1| {
+            // dx = 2 * x * dy
+            let x = x.cast_into_tensor::<f32>()?.into_array()?;
+            let dy = output_grad.cast_into_tensor::<f32>()?.into_array()?;
+            let dx = Tensor::from(2. * &x * &dy);
+            dx.into_device(device)?.into_scalar_tensor().cast_into(scalar_type)?.into_shared()
+        });
+    }
+    let x = x.value().clone().cast_into_tensor::<f32>()?.into_array()?;
+    let y = Tensor::from(&x * &x);
+    let y = y.into_device(device)?.into_scalar_tensor().cast_into(scalar_type)?;
+    Ok(builder.build(y.into_shared()?))
+}
+```
+
+# Custom layers
+Layers with trainable weights store them as [`Parameter`]s, created with [`Parameter::new()`].
+In the forward pass, [`.to_variable()`](Parameter::to_variable) converts the parameter to a
+variable, which has a node during training. [`Layer::parameters_mut()`] provides
+[mutable views](Parameter::make_view_mut) for the [`Optimizer`].
+
+```no_run
+# use autograph::{anyhow::Result, krnl::device::Device, tensor::Tensor};
+use autograph::learn::neural_network::{
+    autograd::{Parameter1, Variable1},
+    layer::{Forward, Layer, ParameterMutVec, ParameterVec},
+};
+
+// y = scale * x
+struct Scale {
+    scale: Parameter1,
+}
+
+impl Scale {
+    fn new(device: Device) -> Result<Self> {
+        let scale = Tensor::from(vec![1f32]).into_device(device)?;
+        Ok(Self {
+            scale: Parameter1::new(scale.into()),
+        })
+    }
+}
+
+impl Layer for Scale {
+    fn set_training(&mut self, training: bool) -> Result<()> {
+        self.scale.set_training(training);
+        Ok(())
+    }
+    fn parameters(&self) -> ParameterVec {
+        [self.scale.clone().into_dyn()].into_iter().collect()
+    }
+    fn parameters_mut(&mut self) -> Result<ParameterMutVec> {
+        Ok([self.scale.make_view_mut()?.into_dyn()].into_iter().collect())
+    }
+}
+
+impl Forward<Variable1> for Scale {
+    type Output = Variable1;
+    fn forward(&self, input: Variable1) -> Result<Variable1> {
+        let scale = self.scale.to_variable().broadcast(input.raw_dim()).unwrap();
+        input.mul(&scale)
+    }
+}
+```
+*/
+
 use super::{
-    layer::Forward,
+    layer::{Forward, Layer},
     optimizer::{State as OptimizerState, Value as OptimizerValue},
 };
-#[cfg(doc)]
-use crate::{learn::neural_network::optimizer::Optimizer, tensor::TensorBase};
 use crate::{
+    error::AutographError,
     ops::AddAssign,
     tensor::{
-        ArcTensor, CowTensor, ScalarArcTensor, ScalarArcTensorD, ScalarTensor, ScalarTensorBase,
-        ScalarTensorViewMut, Tensor, TensorView,
+        ArcTensor, ArcTensor1, CowTensor, MatmulShapes, ScalarArcTensor, ScalarArcTensorD,
+        ScalarTensor, ScalarTensorBase, ScalarTensorD, ScalarTensorView, ScalarTensorViewMut,
+        Tensor, TensorView,
     },
 };
+#[cfg(doc)]
+use crate::{learn::neural_network::optimizer::Optimizer, tensor::TensorBase};
 use anyhow::{bail, Error, Result};
 use dry::macro_wrap;
 use half::{bf16, f16};
 use krnl::{
     buffer::{ScalarArcBufferRepr, ScalarData, ScalarDataMut, ScalarDataOwned, ScalarSliceMutRepr},
     device::Device,
-    scalar::{Scalar, ScalarType},
+    scalar::{Scalar, ScalarElem, ScalarType},
 };
 use ndarray::{
     linalg::Dot, Axis, Dimension, IntoDimension, Ix0, Ix1, Ix2, Ix3, Ix4, Ix5, Ix6, IxDyn,
-    ShapeError,
+    RemoveAxis, ShapeError,
 };
-use parking_lot::{Mutex, RwLock};
+use parking_lot::{Mutex, RwLock, RwLockWriteGuard};
 use paste::paste;
 use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use std::{
     any::TypeId,
+    cell::Cell,
     collections::VecDeque,
     fmt::{self, Debug},
     marker::PhantomData,
+    ptr::NonNull,
     sync::{Arc, Weak},
 };
 
@@ -82,6 +176,8 @@ pub mod builder {
         ///
         /// During the backward pass, for each edge to `node`, `f` computes the gradient of `node`
         /// given the gradient of `self`.
+        /// The gradient must have the same shape, scalar type, and device as `node`, otherwise
+        /// the backward pass will panic. See [Custom operations](super#custom-operations).
         /// When multiple edges compute the same gradient, they are added together.
         /// Once there are no more edges needed to compute a gradient for a node, its edges can
         /// be computed.
@@ -129,8 +225,10 @@ pub mod builder {
             self.edges.push(EdgeInner { name, op, node })
         }
         /// Builds the variable with `value`.
+        ///
+        /// The variable does not have a node within [`no_grad()`].
         pub fn build(self, value: ScalarArcTensor<D>) -> Variable<D> {
-            let node = if let Some(grad) = self.grad {
+            let node = if let Some(grad) = self.grad.filter(|_| is_grad_enabled()) {
                 Some(Node::new(
                     value.device(),
                     value.raw_dim().into_dyn(),
@@ -144,9 +242,186 @@ pub mod builder {
             Variable { value, node }
         }
     }
+
+    /// ConcatBuilder.
+    ///
+    /// Concatenates pushed variables along an axis, see [`Variable::cat()`]. Useful when the
+    /// variables are appended conditionally.
+    ///
+    ///```no_run
+    /// # use autograph::{anyhow::Result, learn::neural_network::autograd::{Variable, Variable2}};
+    /// # use ndarray::Axis;
+    /// # fn main() -> Result<()> {
+    /// # let (x, skip): (Variable2, Option<Variable2>) = todo!();
+    /// let mut builder = Variable::concat_builder(Axis(1));
+    /// builder.push(x);
+    /// if let Some(skip) = skip {
+    ///     builder.push(skip);
+    /// }
+    /// let y = builder.build()?;
+    /// # Ok(())
+    /// # }
+    ///```
+    #[derive(Clone, Debug)]
+    pub struct ConcatBuilder<D: Dimension> {
+        axis: Axis,
+        variables: Vec<Variable<D>>,
+    }
+
+    impl<D: RemoveAxis + 'static> ConcatBuilder<D> {
+        pub(super) fn new(axis: Axis) -> Self {
+            Self {
+                axis,
+                variables: Vec::new(),
+            }
+        }
+        /// Appends `variable`.
+        pub fn push(&mut self, variable: Variable<D>) {
+            self.variables.push(variable);
+        }
+        /// The number of variables.
+        pub fn len(&self) -> usize {
+            self.variables.len()
+        }
+        /// Whether no variables have been pushed.
+        pub fn is_empty(&self) -> bool {
+            self.variables.is_empty()
+        }
+        /// Builds the concatenation.
+        ///
+        /// **Errors**
+        ///
+        /// See [`Variable::cat()`].
+        pub fn build(self) -> Result<Variable<D>> {
+            Variable::cat(&self.variables, self.axis)
+        }
+    }
 }
 use builder::*;
 
+thread_local! {
+    static GRAD_ENABLED: Cell<bool> = Cell::new(true);
+}
+
+/// Executes `f` without building the autograd graph.
+///
+/// Variables created within `f`, including by [`Parameter::to_variable()`], do not have a
+/// [`Node`], so gradients are not computed. This reduces memory usage and overhead for
+/// inference. Applies to the current thread.
+///
+///```no_run
+/// # use autograph::{anyhow::Result, learn::neural_network::{autograd::{no_grad, Variable2}, layer::{Dense, Forward}}};
+/// # fn main() -> Result<()> {
+/// # let dense: Dense = todo!();
+/// # let input: Variable2 = todo!();
+/// let output = no_grad(|| dense.forward(input))?;
+/// assert!(output.node().is_none());
+/// # Ok(())
+/// # }
+///```
+pub fn no_grad<R>(f: impl FnOnce() -> R) -> R {
+    with_grad_enabled(false, f)
+}
+
+fn with_grad_enabled<R>(enabled: bool, f: impl FnOnce() -> R) -> R {
+    struct Guard(bool);
+
+    impl Drop for Guard {
+        fn drop(&mut self) {
+            GRAD_ENABLED.with(|grad_enabled| grad_enabled.set(self.0));
+        }
+    }
+
+    let _guard = Guard(GRAD_ENABLED.with(|grad_enabled| grad_enabled.replace(enabled)));
+    f()
+}
+
+/// Whether the autograd graph is built on the current thread.
+///
+/// See [`no_grad()`].
+pub fn is_grad_enabled() -> bool {
+    GRAD_ENABLED.with(Cell::get)
+}
+
+/// Gradient checkpointing.
+///
+/// Computes `layer.forward(input)` within [`no_grad()`], so intermediate activations of
+/// `layer` are not stored in the graph. The output has a single edge that recomputes the
+/// forward pass of a clone of `layer` during the backward pass, and propagates the gradient
+/// to `input` and the parameters of `layer`. This reduces memory usage for deep models, at
+/// the cost of computing the forward pass of `layer` twice.
+///
+/// The forward pass of `layer` must be deterministic, ie without dropout, and should not
+/// update state like the running statistics of [`BatchNorm`](super::layer::BatchNorm). Clones
+/// of parameters share their gradients, so cloning `layer` is cheap.
+///
+/// If the graph is not enabled, or neither `input` nor a parameter of `layer` has a node, this
+/// is equivalent to `layer.forward(input)`.
+///
+///```no_run
+/// # use autograph::{anyhow::Result, learn::neural_network::{autograd::{checkpoint, Variable2}, layer::{Dense, Forward, Relu}}};
+/// # fn main() -> Result<()> {
+/// # let block: Dense<Relu> = todo!();
+/// # let head: Dense = todo!();
+/// # let input: Variable2 = todo!();
+/// let x = checkpoint(&block, input)?;
+/// let output = head.forward(x)?;
+/// # Ok(())
+/// # }
+///```
+///
+/// **Errors**
+///
+/// See [`Forward::forward()`].
+pub fn checkpoint<L, D, D2>(layer: &L, input: Variable<D>) -> Result<Variable<D2>>
+where
+    L: Layer + Forward<Variable<D>, Output = Variable<D2>> + Clone + Send + Sync + 'static,
+    D: Dimension + 'static,
+    D2: Dimension + 'static,
+{
+    let training = is_grad_enabled()
+        && layer
+            .parameters()
+            .iter()
+            .any(|parameter| parameter.to_variable().node().is_some());
+    if !is_grad_enabled() || (input.node().is_none() && !training) {
+        return layer.forward(input);
+    }
+    let output = no_grad(|| layer.forward(input.clone()))?;
+    let input_node = if let Some(node) = input.node() {
+        node.clone()
+    } else {
+        Variable::builder()
+            .node()
+            .build(input.value().clone())
+            .node
+            .unwrap()
+    };
+    let requires_grad = input.node().is_some();
+    let input = input.into_value();
+    let layer = layer.clone();
+    let mut builder = Variable::builder();
+    builder.edge(&input_node, move |output_grad| {
+        with_grad_enabled(true, || {
+            let x = if requires_grad {
+                Variable::builder().node().build(input.clone())
+            } else {
+                Variable::from(input.clone())
+            };
+            let y = layer.forward(x.clone())?;
+            if let Some(node) = y.node() {
+                node.backward_grad(output_grad)?;
+            }
+            if let Some(grad) = x.node().and_then(Node::grad) {
+                Ok(grad)
+            } else {
+                ScalarArcTensor::zeros(input.device(), input.raw_dim(), input.scalar_type())
+            }
+        })
+    });
+    Ok(builder.build(output.into_value()))
+}
+
 struct EdgeInner {
     name: &'static str,
     op: Box<dyn FnMut() -> Result<()> + Send + Sync + 'static>,
@@ -341,6 +616,30 @@ impl<D: Dimension> Variable<D> {
     pub fn raw_dim(&self) -> D {
         self.value.raw_dim()
     }
+    /// A variable with the same device, shape, and scalar_type filled with `elem`.
+    ///
+    /// `elem` is cast to the scalar_type of `self`. The output does not have a node, so it
+    /// is a constant that does not receive a gradient.
+    ///
+    /// **Errors**
+    ///
+    /// See [`ScalarTensorBase::from_elem()`].
+    pub fn full_like(&self, elem: ScalarElem) -> Result<Self> {
+        let elem = elem.scalar_cast(self.scalar_type());
+        ScalarArcTensor::from_elem(self.device(), self.raw_dim(), elem).map(Into::into)
+    }
+    /// A variable with the same device, shape, and scalar_type filled with 0's.
+    ///
+    /// See [`.full_like()`](Variable::full_like).
+    pub fn zeros_like(&self) -> Result<Self> {
+        self.full_like(ScalarElem::zero(self.scalar_type()))
+    }
+    /// A variable with the same device, shape, and scalar_type filled with 1's.
+    ///
+    /// See [`.full_like()`](Variable::full_like).
+    pub fn ones_like(&self) -> Result<Self> {
+        self.full_like(ScalarElem::one(self.scalar_type()))
+    }
     /// Converts into dimensionality `D2`.
     ///
     /// See [`TensorBase::into_dimensionality`].
@@ -402,6 +701,45 @@ impl<D: Dimension + 'static> Variable<D> {
         let dim = crate::tensor::flatten(self.shape());
         self.into_shape(dim)
     }
+    /// Removes the size 1 `axis`.
+    ///
+    /// The gradient is unsqueezed back to the shape of the input.
+    ///
+    /// See [`TensorBase::squeeze`].
+    pub fn squeeze(self, axis: Axis) -> Result<Variable<D::Smaller>, ShapeError>
+    where
+        D: RemoveAxis,
+    {
+        let Self { value, node } = self;
+        let value = value.squeeze(axis)?;
+        let mut builder = Variable::builder();
+        if let Some(node) = node.as_ref() {
+            builder.edge(node, move |output_grad| {
+                output_grad
+                    .unsqueeze(axis)
+                    .into_dimensionality()
+                    .map_err(Error::msg)
+            });
+        }
+        Ok(builder.build(value))
+    }
+    /// Inserts a size 1 axis at `axis`.
+    ///
+    /// The gradient is squeezed back to the shape of the input.
+    ///
+    /// See [`TensorBase::unsqueeze`].
+    pub fn unsqueeze(self, axis: Axis) -> Variable<D::Larger> {
+        let mut builder = Variable::builder();
+        if let Some(node) = self.node() {
+            builder.edge(node, move |output_grad| {
+                output_grad
+                    .squeeze(axis)
+                    .and_then(|x| x.into_dimensionality())
+                    .map_err(Error::msg)
+            });
+        }
+        builder.build(self.value.unsqueeze(axis))
+    }
     /// Reverses (transposes) the axes of the variable.
     ///
     /// See [`TensorBase::reversed_axes`].
@@ -412,10 +750,376 @@ impl<D: Dimension + 'static> Variable<D> {
         }
         builder.build(self.value.reversed_axes())
     }
+    /// Permutes the axes of the variable.
+    ///
+    /// See [`TensorBase::permuted_axes`].
+    ///
+    /// **Panics**
+    /// - Each axis 0 .. ndim must be used exactly once.
+    pub fn permuted_axes<A>(self, axes: A) -> Self
+    where
+        A: IntoDimension<Dim = D>,
+    {
+        let axes = axes.into_dimension();
+        let value = self.value.permuted_axes(axes.clone());
+        let mut builder = Self::builder();
+        if let Some(node) = self.node {
+            let mut inverse = axes.clone();
+            for (i, axis) in axes.slice().iter().enumerate() {
+                inverse[*axis] = i;
+            }
+            builder.edge(&node, move |output_grad| {
+                Ok(output_grad.permuted_axes(inverse))
+            });
+        }
+        builder.build(value)
+    }
     /// Transposes the variable.
     pub fn t(&self) -> Self {
         self.clone().reversed_axes()
     }
+    /// Fills elements where `mask` is not zero with `value`.
+    ///
+    /// The gradient is zero at masked elements.
+    ///
+    /// See [`TensorBase::masked_fill`].
+    pub fn masked_fill<D2: Dimension + 'static>(
+        &self,
+        mask: ArcTensor<u8, D2>,
+        value: ScalarElem,
+    ) -> Result<Self> {
+        let mut builder = Self::builder();
+        if let Some(node) = self.node() {
+            let mask = mask.clone();
+            builder.edge(node, move |output_grad| {
+                let zero = ScalarElem::zero(output_grad.scalar_type());
+                output_grad.masked_fill(mask.view(), zero).map(Into::into)
+            });
+        }
+        let value = self.value.masked_fill(mask.view(), value)?;
+        Ok(builder.build(value.into()))
+    }
+    /// Shifts elements by `shift` along `axis`, wrapping around.
+    ///
+    /// The gradient is rolled by `-shift`.
+    ///
+    /// See [`TensorBase::roll`].
+    pub fn roll(&self, shift: isize, axis: Axis) -> Result<Self> {
+        let mut builder = Self::builder();
+        if let Some(node) = self.node() {
+            builder.edge(node, move |output_grad| {
+                output_grad.roll(-shift, axis).map(Into::into)
+            });
+        }
+        let value = self.value.roll(shift, axis)?;
+        Ok(builder.build(value.into()))
+    }
+    /// Reverses the order of elements along `axes`.
+    ///
+    /// The gradient is flipped along the same `axes`.
+    ///
+    /// See [`TensorBase::flip`].
+    pub fn flip(&self, axes: &[Axis]) -> Result<Self> {
+        let mut builder = Self::builder();
+        if let Some(node) = self.node() {
+            let axes = axes.to_vec();
+            builder.edge(node, move |output_grad| {
+                output_grad.flip(&axes).map(Into::into)
+            });
+        }
+        let value = self.value.flip(axes)?;
+        Ok(builder.build(value.into()))
+    }
+    /// Elementwise multiplication.
+    ///
+    /// The gradient of `self` is `output_grad * rhs` and the gradient of `rhs` is
+    /// `output_grad * self`.
+    ///
+    /// **Errors**
+    /// - The shapes are not the same.
+    /// - See [`TensorBase::mul`].
+    pub fn mul(&self, rhs: &Self) -> Result<Self> {
+        if self.shape() != rhs.shape() {
+            bail!(AutographError::shape_mismatch(
+                "mul",
+                self.shape(),
+                rhs.shape()
+            ));
+        }
+        let mut builder = Self::builder();
+        if let Some(node) = self.node() {
+            let rhs = rhs.value().clone();
+            builder.edge(node, move |output_grad| {
+                output_grad.mul(&rhs)?.into_shared()
+            });
+        }
+        if let Some(node) = rhs.node() {
+            let lhs = self.value().clone();
+            builder.edge(node, move |output_grad| {
+                output_grad.mul(&lhs)?.into_shared()
+            });
+        }
+        let value = self.value.mul(&rhs.value)?;
+        Ok(builder.build(value.into_shared()?))
+    }
+    /// Elementwise division.
+    ///
+    /// The gradient of `self` is `output_grad / rhs` and the gradient of `rhs` is
+    /// `-output_grad * self / rhs^2`.
+    ///
+    /// Division by zero produces inf or nan, see [`TensorBase::div`].
+    ///
+    /// **Errors**
+    /// - The shapes are not the same.
+    /// - The scalar type is not floating point.
+    /// - See [`TensorBase::div`].
+    pub fn div(&self, rhs: &Self) -> Result<Self> {
+        if self.shape() != rhs.shape() {
+            bail!(AutographError::shape_mismatch(
+                "div",
+                self.shape(),
+                rhs.shape()
+            ));
+        }
+        let scalar_type = self.scalar_type();
+        if !matches!(
+            scalar_type,
+            ScalarType::F16 | ScalarType::BF16 | ScalarType::F32 | ScalarType::F64
+        ) {
+            bail!(AutographError::unsupported_scalar_type("div", scalar_type));
+        }
+        let mut builder = Self::builder();
+        if let Some(node) = self.node() {
+            let rhs = rhs.value().clone();
+            builder.edge(node, move |output_grad| {
+                output_grad.div(&rhs)?.into_shared()
+            });
+        }
+        if let Some(node) = rhs.node() {
+            let lhs = self.value().clone();
+            let rhs = rhs.value().clone();
+            builder.edge(node, move |output_grad| {
+                let neg_one = ScalarElem::F32(-1.).scalar_cast(scalar_type);
+                lhs.scaled_cast(neg_one)?
+                    .mul(&output_grad)?
+                    .div(&rhs.mul(&rhs)?)?
+                    .into_shared()
+            });
+        }
+        let value = self.value.div(&rhs.value)?;
+        Ok(builder.build(value.into_shared()?))
+    }
+    /// Elementwise power, `self^exponent`.
+    ///
+    /// The gradient of `self` is `output_grad * exponent * self^(exponent - 1)` and the gradient
+    /// of `exponent` is `output_grad * self^exponent * ln(self)`, which is nan where
+    /// `self <= 0`.
+    ///
+    /// **Errors**
+    /// - See [`ScalarTensorBase::pow`].
+    pub fn pow(&self, exponent: &Self) -> Result<Self> {
+        let value = self.value.pow(&exponent.value)?;
+        let mut builder = Self::builder();
+        if let Some(node) = self.node() {
+            let x = self.value().clone();
+            let e = exponent.value().clone();
+            builder.edge(node, move |output_grad| {
+                x.pow_backward(&e, &output_grad)?.0.into_shared()
+            });
+        }
+        if let Some(node) = exponent.node() {
+            let x = self.value().clone();
+            let e = exponent.value().clone();
+            builder.edge(node, move |output_grad| {
+                x.pow_backward(&e, &output_grad)?.1.into_shared()
+            });
+        }
+        Ok(builder.build(value.into_shared()?))
+    }
+    /// Elementwise maximum of `self` and `rhs`.
+    ///
+    /// The gradient is routed to whichever input was selected, `self` if they are equal.
+    ///
+    /// **Errors**
+    /// - The scalar type is not BF16 or F32.
+    /// - See [`ScalarTensorBase::maximum`].
+    pub fn maximum(&self, rhs: &Self) -> Result<Self> {
+        self.select(rhs, true)
+    }
+    /// Elementwise minimum of `self` and `rhs`.
+    ///
+    /// The gradient is routed to whichever input was selected, `self` if they are equal.
+    ///
+    /// **Errors**
+    /// - The scalar type is not BF16 or F32.
+    /// - See [`ScalarTensorBase::minimum`].
+    pub fn minimum(&self, rhs: &Self) -> Result<Self> {
+        self.select(rhs, false)
+    }
+    fn select(&self, rhs: &Self, maximum: bool) -> Result<Self> {
+        let scalar_type = self.scalar_type();
+        if !matches!(scalar_type, ScalarType::BF16 | ScalarType::F32) {
+            let name = if maximum { "maximum" } else { "minimum" };
+            bail!(AutographError::unsupported_scalar_type(name, scalar_type));
+        }
+        let value = if maximum {
+            self.value.maximum(&rhs.value)?
+        } else {
+            self.value.minimum(&rhs.value)?
+        };
+        let backward = move |x: &ScalarArcTensor<D>,
+                             y: &ScalarArcTensor<D>,
+                             output_grad: &ScalarArcTensor<D>| {
+            if maximum {
+                x.maximum_backward(y, output_grad)
+            } else {
+                x.minimum_backward(y, output_grad)
+            }
+        };
+        let mut builder = Self::builder();
+        if let Some(node) = self.node() {
+            let x = self.value().clone();
+            let y = rhs.value().clone();
+            builder.edge(node, move |output_grad| {
+                backward(&x, &y, &output_grad)?.0.into_shared()
+            });
+        }
+        if let Some(node) = rhs.node() {
+            let x = self.value().clone();
+            let y = rhs.value().clone();
+            builder.edge(node, move |output_grad| {
+                backward(&x, &y, &output_grad)?.1.into_shared()
+            });
+        }
+        Ok(builder.build(value.into_shared()?))
+    }
+    /// Moves the variable into `device`.
+    ///
+    /// The gradient is transferred back to the device of `self` in the backward pass.
+    ///
+    /// **Errors**
+    ///
+    /// See [`ScalarTensorBase::into_device_shared`].
+    pub fn into_device(self, device: Device) -> Result<Self> {
+        if self.device() == device {
+            return Ok(self);
+        }
+        let mut builder = Self::builder();
+        if let Some(node) = self.node() {
+            let device = self.device();
+            builder.edge(node, move |output_grad| {
+                output_grad.into_device_shared(device)
+            });
+        }
+        let value = self.value.into_device_shared(device)?;
+        Ok(builder.build(value))
+    }
+    /// Elementwise absolute value.
+    ///
+    /// The gradient is `output_grad * sign(self)`, which is 0 where `self` is 0.
+    ///
+    /// **Errors**
+    /// - See [`ScalarTensorBase::abs`].
+    pub fn abs(&self) -> Result<Self> {
+        let mut builder = Self::builder();
+        if let Some(node) = self.node() {
+            let x = self.value().clone();
+            builder.edge(node, move |output_grad| {
+                output_grad.mul(&x.sign()?)?.into_shared()
+            });
+        }
+        let value = self.value.abs()?;
+        Ok(builder.build(value.into_shared()?))
+    }
+    /// Elementwise sign.
+    ///
+    /// The gradient is 0.
+    ///
+    /// **Errors**
+    /// - See [`ScalarTensorBase::sign`].
+    pub fn sign(&self) -> Result<Self> {
+        let mut builder = Self::builder();
+        if let Some(node) = self.node() {
+            builder.edge(node, move |output_grad| {
+                ScalarArcTensor::zeros(
+                    output_grad.device(),
+                    output_grad.raw_dim(),
+                    output_grad.scalar_type(),
+                )
+            });
+        }
+        let value = self.value.sign()?;
+        Ok(builder.build(value.into_shared()?))
+    }
+    /// Elementwise negation.
+    ///
+    /// The gradient is `-output_grad`.
+    ///
+    /// **Errors**
+    /// - See [`ScalarTensorBase::neg`].
+    pub fn neg(&self) -> Result<Self> {
+        let mut builder = Self::builder();
+        if let Some(node) = self.node() {
+            builder.edge(node, move |output_grad| output_grad.neg()?.into_shared());
+        }
+        let value = self.value.neg()?;
+        Ok(builder.build(value.into_shared()?))
+    }
+    /// Returns a mask of elements greater than `threshold`, 1 if true, 0 otherwise.
+    ///
+    /// The mask is a plain tensor, not a variable, so it does not propagate gradients. Use it
+    /// with ie [`.masked_fill()`](Variable::masked_fill) to implement gating.
+    ///
+    /// **Errors**
+    /// - See [`ScalarTensorBase::gt`].
+    pub fn gt(&self, threshold: f32) -> Result<Tensor<u8, D>> {
+        self.value.gt(threshold.into())
+    }
+    /// Returns a mask of elements greater than or equal to `threshold`.
+    ///
+    /// See [`.gt()`](Variable::gt).
+    pub fn ge(&self, threshold: f32) -> Result<Tensor<u8, D>> {
+        self.value.ge(threshold.into())
+    }
+    /// Returns a mask of elements less than `threshold`.
+    ///
+    /// See [`.gt()`](Variable::gt).
+    pub fn lt(&self, threshold: f32) -> Result<Tensor<u8, D>> {
+        self.value.lt(threshold.into())
+    }
+    /// Returns a mask of elements less than or equal to `threshold`.
+    ///
+    /// See [`.gt()`](Variable::gt).
+    pub fn le(&self, threshold: f32) -> Result<Tensor<u8, D>> {
+        self.value.le(threshold.into())
+    }
+    /// Returns a mask of elements equal to `threshold`.
+    ///
+    /// See [`.gt()`](Variable::gt).
+    pub fn eq(&self, threshold: f32) -> Result<Tensor<u8, D>> {
+        self.value.eq(threshold.into())
+    }
+    /// Casts the variable to `scalar_type`.
+    ///
+    /// The gradient is cast back to the scalar type of `self` in the backward pass.
+    ///
+    /// **Errors**
+    ///
+    /// See [`ScalarTensorBase::cast`].
+    pub fn cast(&self, scalar_type: ScalarType) -> Result<Self> {
+        if self.scalar_type() == scalar_type {
+            return Ok(self.clone());
+        }
+        let mut builder = Self::builder();
+        if let Some(node) = self.node() {
+            let input_scalar_type = self.scalar_type();
+            builder.edge(node, move |output_grad| {
+                output_grad.cast(input_scalar_type)?.into_shared()
+            });
+        }
+        let value = self.value.cast(scalar_type)?;
+        Ok(builder.build(value.into_shared()?))
+    }
     /// Attempts to broadcast the variable into `dim`.
     ///
     /// See [`TensorBase::broadcast`].
@@ -445,6 +1149,212 @@ impl<D: Dimension + 'static> Variable<D> {
         }
         Some(builder.build(output))
     }
+    /// Expands the variable into the larger shape `dim`.
+    ///
+    /// The value is a view with strides of 0 along the expanded axes, see
+    /// [`TensorBase::expand`]. The gradient is summed over the expanded axes.
+    ///
+    /// **Errors**
+    /// - The variable can not be broadcast to `dim`.
+    pub fn expand<E>(&self, dim: E) -> Result<Variable<E::Dim>>
+    where
+        E: IntoDimension,
+    {
+        let dim = dim.into_dimension();
+        if let Some(output) = self.broadcast(dim.clone()) {
+            Ok(output)
+        } else {
+            bail!("Can not expand {:?} to {:?}!", self.shape(), dim.slice());
+        }
+    }
+    /// Matrix product with NumPy semantics.
+    ///
+    /// The gradients are computed with matmuls of the output gradient, summed over the
+    /// broadcasted batch axes.
+    ///
+    /// See [`TensorBase::matmul`].
+    ///
+    /// **Errors**
+    ///
+    /// See [`TensorBase::matmul`].
+    pub fn matmul<D2: Dimension + 'static>(&self, rhs: &Variable<D2>) -> Result<VariableD> {
+        let MatmulShapes {
+            lhs_dim, rhs_dim, ..
+        } = MatmulShapes::new(self.shape(), rhs.shape())?;
+        // The output gradient with the promoted axes, batch + [m, n].
+        let mut grad_dim = lhs_dim.clone();
+        let ndim = grad_dim.ndim();
+        grad_dim[ndim - 1] = rhs_dim[ndim - 1];
+        let mut builder = VariableD::builder();
+        if let Some(node) = self.node() {
+            let input_dim = self.raw_dim();
+            let grad_dim = grad_dim.clone();
+            let rhs = matmul_operand(rhs.value(), true);
+            builder.edge(node, move |output_grad| {
+                let output_grad = output_grad.into_shape(grad_dim).map_err(Error::msg)?;
+                let input_grad = output_grad.matmul(&rhs)?;
+                sum_to_dim(input_grad, lhs_dim)?
+                    .into_shape(input_dim)
+                    .map_err(Error::msg)
+            });
+        }
+        if let Some(node) = rhs.node() {
+            let input_dim = rhs.raw_dim();
+            let lhs = matmul_operand(self.value(), false);
+            builder.edge(node, move |output_grad| {
+                let output_grad = output_grad.into_shape(grad_dim).map_err(Error::msg)?;
+                let input_grad = swap_last_axes(output_grad).matmul(&lhs)?;
+                let input_grad = swap_last_axes(input_grad).into_standard_layout()?;
+                sum_to_dim(input_grad, rhs_dim)?
+                    .into_shape(input_dim)
+                    .map_err(Error::msg)
+            });
+        }
+        let value = self.value.matmul(&rhs.value)?;
+        Ok(builder.build(value.into_shared()?))
+    }
+}
+
+impl<D: RemoveAxis + 'static> Variable<D> {
+    /// Concatenates `variables` along `axis`.
+    ///
+    /// The gradient of each input is its slice of the output gradient.
+    ///
+    /// **Errors**
+    /// - `variables` is empty.
+    /// - `axis` is out of bounds.
+    /// - The shapes differ other than along `axis`.
+    /// - The scalar types or devices differ.
+    /// - The operation could not be executed on the device.
+    pub fn cat(variables: &[Self], axis: Axis) -> Result<Self> {
+        let Some(first) = variables.first() else {
+            bail!("cat requires at least 1 variable!");
+        };
+        let device = first.device();
+        let scalar_type = first.scalar_type();
+        let mut dim = first.raw_dim();
+        if axis.0 >= dim.ndim() {
+            bail!(
+                "cat axis {} out of bounds for shape {:?}!",
+                axis.0,
+                dim.slice()
+            );
+        }
+        dim[axis.0] = 0;
+        for variable in variables {
+            let compatible = variable.shape().len() == dim.ndim()
+                && variable
+                    .shape()
+                    .iter()
+                    .zip(first.shape())
+                    .enumerate()
+                    .all(|(i, (a, b))| i == axis.0 || a == b);
+            if !compatible {
+                bail!(
+                    "cat shapes {:?} and {:?} incompatible along {axis:?}!",
+                    first.shape(),
+                    variable.shape()
+                );
+            }
+            if variable.scalar_type() != scalar_type {
+                bail!(
+                    "cat scalar_type {:?} != {scalar_type:?}!",
+                    variable.scalar_type()
+                );
+            }
+            if variable.device() != device {
+                bail!("cat device {:?} != {device:?}!", variable.device());
+            }
+            dim[axis.0] += variable.shape()[axis.0];
+        }
+        let mut value = unsafe { ScalarTensor::uninit(device, dim, scalar_type)? };
+        let mut builder = Self::builder();
+        let mut start = 0;
+        for variable in variables {
+            let end = start + variable.shape()[axis.0];
+            value
+                .view_mut()
+                .slice_axis_into(axis, start..end)
+                .assign(variable.value())?;
+            if let Some(node) = variable.node() {
+                builder.edge(node, move |output_grad| {
+                    output_grad.slice_axis_into(axis, start..end).into_shared()
+                });
+            }
+            start = end;
+        }
+        Ok(builder.build(value.into()))
+    }
+    /// A builder for concatenating variables along `axis`.
+    ///
+    /// See [`.cat()`](Self::cat).
+    pub fn concat_builder(axis: Axis) -> ConcatBuilder<D> {
+        ConcatBuilder::new(axis)
+    }
+    /// Selects `indices` along `axis`.
+    ///
+    /// The gradient is computed with [`.scatter_add()`](TensorBase::scatter_add), accumulating
+    /// repeated indices.
+    ///
+    /// See [`TensorBase::index_select`].
+    pub fn index_select(&self, axis: Axis, indices: ArcTensor1<u32>) -> Result<Self> {
+        let mut builder = Self::builder();
+        if let Some(node) = self.node() {
+            let device = self.device();
+            let dim = self.raw_dim();
+            let scalar_type = self.scalar_type();
+            let indices = indices.clone();
+            builder.edge(node, move |output_grad| {
+                let mut input_grad = ScalarTensor::zeros(device, dim, scalar_type)?;
+                input_grad.scatter_add(axis, indices.view(), &output_grad)?;
+                input_grad.into_shared()
+            });
+        }
+        let value = self.value.index_select(axis, indices.view())?;
+        Ok(builder.build(value.into()))
+    }
+    /// Takes the rows at `indices`, ie advanced indexing along the first axis.
+    ///
+    /// Rows may be taken multiple times, accumulating their gradients. See
+    /// [`.index_select()`](Self::index_select).
+    ///
+    /// **Errors**
+    ///
+    /// See [`TensorBase::index_select`].
+    pub fn take(&self, indices: ArcTensor1<u32>) -> Result<Self> {
+        self.index_select(Axis(0), indices)
+    }
+    /// Puts the rows of `self` at `indices` of a variable with `len` rows.
+    ///
+    /// Rows not in `indices` are zero, and rows put at the same index are summed. The inverse of
+    /// [`.take()`](Self::take), ie to recombine rows selected with `.take()`. The gradient is
+    /// the output gradient taken at `indices`.
+    ///
+    /// **Errors**
+    /// - The length of `indices` is not the number of rows of `self`.
+    ///
+    /// See [`TensorBase::scatter_add`].
+    pub fn put(&self, indices: ArcTensor1<u32>, len: usize) -> Result<Self> {
+        if indices.len() != self.shape()[0] {
+            bail!(
+                "put indices len {} != rows {}!",
+                indices.len(),
+                self.shape()[0]
+            );
+        }
+        let mut builder = Self::builder();
+        if let Some(node) = self.node() {
+            let indices = indices.clone();
+            builder.edge(node, move |output_grad| {
+                Ok(output_grad.index_select(Axis(0), indices.view())?.into())
+            });
+        }
+        let mut dim = self.raw_dim();
+        dim[0] = len;
+        let mut value = ScalarTensor::zeros(self.device(), dim, self.scalar_type())?;
+        value.scatter_add(Axis(0), indices.view(), &self.value)?;
+        Ok(builder.build(value.into()))
+    }
 }
 
 fn broadcast_backward<T: Scalar, D1: Dimension, D2: Dimension>(
@@ -504,6 +1414,38 @@ fn broadcast_backward<T: Scalar, D1: Dimension, D2: Dimension>(
     Ok(output.into_owned()?.into_shape(output_dim).unwrap())
 }
 
+// Promotes a 1D matmul operand to a row [1, k], otherwise transposes the matrices if
+// `transpose`.
+fn matmul_operand<D: Dimension>(x: &ScalarArcTensor<D>, transpose: bool) -> ScalarArcTensorD {
+    if x.ndim() == 1 {
+        x.broadcast_shared([1, x.len()]).unwrap().into_dyn()
+    } else if transpose {
+        swap_last_axes(x.clone().into_dyn())
+    } else {
+        x.clone().into_dyn()
+    }
+}
+
+fn swap_last_axes<S: ScalarData>(x: ScalarTensorBase<S, IxDyn>) -> ScalarTensorBase<S, IxDyn> {
+    let mut axes: Vec<usize> = (0..x.ndim()).collect();
+    let ndim = axes.len();
+    axes.swap(ndim - 2, ndim - 1);
+    x.permuted_axes(axes)
+}
+
+// Sums `x` over the axes broadcasted from `dim`.
+fn sum_to_dim(x: ScalarTensorD, dim: IxDyn) -> Result<ScalarArcTensorD> {
+    if x.shape() == dim.slice() {
+        return x.into_shared();
+    }
+    macro_wrap!(paste! { match x.scalar_type() {
+        macro_for!($T in [u8, i8, u16, i16, f16, bf16, u32, i32, f32, u64, i64, f64] {
+            ScalarType::[<$T:upper>] => broadcast_backward::<$T, IxDyn, IxDyn>(x.view().try_into().unwrap(), dim)?.into_scalar_tensor().into_shared(),
+        })
+        _ => bail!("matmul backward {:?} unimplemented!", x.scalar_type()),
+    }})
+}
+
 impl<T: Scalar, D: Dimension> From<Tensor<T, D>> for Variable<D> {
     fn from(tensor: Tensor<T, D>) -> Self {
         Self::from(ScalarArcTensor::from(tensor))
@@ -589,6 +1531,101 @@ impl Dot<Self> for Variable2 {
     }
 }
 
+/// Matrix-vector product, `[m, k] x [k] -> [m]`.
+///
+/// The gradient of `self` is the outer product of `output_grad` and `rhs`, and the gradient of
+/// `rhs` is `self.t().dot(output_grad)`.
+impl Dot<Variable1> for Variable2 {
+    type Output = Result<Variable1>;
+    fn dot(&self, rhs: &Variable1) -> Result<Variable1> {
+        let lhs = self;
+        let (m, k) = lhs.dim();
+        let mut builder = Variable1::builder();
+        if let Some(node) = lhs.node() {
+            let rhs = rhs.value().clone();
+            builder.edge(node, move |output_grad| {
+                let output_grad = output_grad.as_standard_layout()?;
+                let rhs = rhs.as_standard_layout()?;
+                output_grad
+                    .view()
+                    .into_shape([m, 1])?
+                    .dot(&rhs.view().into_shape([1, k])?)
+                    .map(Into::into)
+            });
+        }
+        if let Some(node) = rhs.node() {
+            let lhs = lhs.value().clone();
+            builder.edge(node, move |output_grad| {
+                lhs.t().dot(&output_grad).map(Into::into)
+            });
+        }
+        let value = lhs.value().dot(rhs.value())?.into();
+        Ok(builder.build(value))
+    }
+}
+
+/// Vector-matrix product, `[k] x [k, n] -> [n]`.
+///
+/// The gradient of `self` is `rhs.dot(output_grad)`, and the gradient of `rhs` is the outer
+/// product of `self` and `output_grad`.
+impl Dot<Variable2> for Variable1 {
+    type Output = Result<Variable1>;
+    fn dot(&self, rhs: &Variable2) -> Result<Variable1> {
+        let lhs = self;
+        let (k, n) = rhs.dim();
+        let mut builder = Variable1::builder();
+        if let Some(node) = lhs.node() {
+            let rhs = rhs.value().clone();
+            builder.edge(node, move |output_grad| {
+                rhs.dot(&output_grad).map(Into::into)
+            });
+        }
+        if let Some(node) = rhs.node() {
+            let lhs = lhs.value().clone();
+            builder.edge(node, move |output_grad| {
+                let output_grad = output_grad.as_standard_layout()?;
+                let lhs = lhs.as_standard_layout()?;
+                lhs.view()
+                    .into_shape([k, 1])?
+                    .dot(&output_grad.view().into_shape([1, n])?)
+                    .map(Into::into)
+            });
+        }
+        let value = lhs.value().dot(rhs.value())?.into();
+        Ok(builder.build(value))
+    }
+}
+
+impl Variable1 {
+    /// Outer product, `[m] x [n] -> [m, n]`.
+    ///
+    /// The gradient of `self` is `output_grad.dot(rhs)`, and the gradient of `rhs` is
+    /// `output_grad.t().dot(self)`.
+    ///
+    /// See [`TensorBase::outer()`].
+    ///
+    /// **Errors**
+    /// - See [`ScalarTensorBase::outer()`].
+    pub fn outer(&self, rhs: &Variable1) -> Result<Variable2> {
+        let lhs = self;
+        let mut builder = Variable2::builder();
+        if let Some(node) = lhs.node() {
+            let rhs = rhs.value().clone();
+            builder.edge(node, move |output_grad| {
+                output_grad.dot(&rhs).map(Into::into)
+            });
+        }
+        if let Some(node) = rhs.node() {
+            let lhs = lhs.value().clone();
+            builder.edge(node, move |output_grad| {
+                output_grad.t().dot(&lhs).map(Into::into)
+            });
+        }
+        let value = lhs.value().outer(rhs.value())?.into();
+        Ok(builder.build(value))
+    }
+}
+
 /// Parameter.
 ///
 /// Parameter values are updated during training by the [`Optimizer`]. A Parameter
@@ -609,6 +1646,48 @@ pub struct ParameterBase<S: ScalarData, D: Dimension> {
     grad: Option<Arc<RwLock<Option<ScalarArcTensorD>>>>,
     #[serde(skip_serializing_if = "OptimState::is_none", default)]
     optim_state: OptimState<'static>,
+    #[serde(skip)]
+    tie: Tie,
+}
+
+/// Mutable gradient of a parameter.
+///
+/// See [`ParameterBase::grad_mut()`].
+pub struct GradMut<'a, D: Dimension> {
+    guard: RwLockWriteGuard<'a, Option<ScalarArcTensorD>>,
+    _m: PhantomData<D>,
+}
+
+impl<D: Dimension> GradMut<'_, D> {
+    /// Borrows the gradient as a tensor view.
+    pub fn view(&self) -> ScalarTensorView<D> {
+        self.guard
+            .as_ref()
+            .unwrap()
+            .view()
+            .into_dimensionality()
+            .unwrap()
+    }
+    /// Borrows the gradient as a mutable tensor view.
+    ///
+    /// Copies the gradient if it is not exclusive.
+    ///
+    /// See [`TensorBase::make_view_mut`].
+    pub fn view_mut(&mut self) -> Result<ScalarTensorViewMut<D>> {
+        Ok(self
+            .guard
+            .as_mut()
+            .unwrap()
+            .make_view_mut()?
+            .into_dimensionality()
+            .unwrap())
+    }
+}
+
+impl<D: Dimension> Debug for GradMut<'_, D> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_tuple("GradMut").field(&self.view()).finish()
+    }
 }
 
 /// Parameter with a [`ScalarArcTensor`] value.
@@ -659,6 +1738,9 @@ impl<S: ScalarData, D: Dimension> ParameterBase<S, D> {
         &self.value
     }
     /// Borrows the value of the parameter as a mutable tensor view.
+    ///
+    /// Used by an [`Optimizer`] to update the parameter, see
+    /// [Custom optimizers](super::optimizer#custom-optimizers).
     pub fn value_view_mut(&mut self) -> ScalarTensorViewMut<D>
     where
         S: ScalarDataMut,
@@ -676,6 +1758,35 @@ impl<S: ScalarData, D: Dimension> ParameterBase<S, D> {
                 .unwrap(),
         )
     }
+    /// Clears the gradient.
+    ///
+    /// Gradients accumulate across backward passes, so that the gradient of a sum of losses
+    /// can be computed by calling [`.backward()`](Node::backward) on each, and gradients can be
+    /// accumulated over several batches before an update. Call `zero_grad()` after the update
+    /// to begin the next step. Unlike [`.set_training(false)`](Self::set_training), the
+    /// parameter is still training.
+    pub fn zero_grad(&mut self) {
+        if let Some(grad) = self.grad.as_ref() {
+            grad.write().take();
+        }
+    }
+    /// Borrows the gradient of the parameter mutably.
+    ///
+    /// Returns None if the parameter is not training or the gradient has not been computed.
+    /// The gradient is locked until the returned [`GradMut`] is dropped.
+    ///
+    /// Used by an [`Optimizer`] to modify the gradient before the update, for example to clip
+    /// it, see [Custom optimizers](super::optimizer#custom-optimizers).
+    pub fn grad_mut(&mut self) -> Option<GradMut<D>> {
+        let guard = self.grad.as_ref()?.write();
+        if guard.is_none() {
+            return None;
+        }
+        Some(GradMut {
+            guard,
+            _m: PhantomData,
+        })
+    }
     /// The device.
     pub fn device(&self) -> Device {
         self.value.device()
@@ -701,11 +1812,31 @@ impl<S: ScalarData, D: Dimension> ParameterBase<S, D> {
     /// If `training`, ensures that when the parameter is converted to a [`Variable`],
     /// it will have a [`Node`] for computing a gradient.
     /// If `training` is false, discards any gradient that has been computed.
+    ///
+    /// See [`Parameter::share()`].
     pub fn set_training(&mut self, training: bool) {
         if training && self.grad.is_none() {
-            self.grad.replace(Arc::new(RwLock::default()));
+            let grad = self.tie.grad.clone().unwrap_or_default();
+            self.grad.replace(grad);
         } else if !training {
-            self.grad = None;
+            if let Some(grad) = self.grad.take() {
+                if self.tie.grad.is_some() {
+                    grad.write().take();
+                }
+            }
+        }
+    }
+    /// Whether the parameter is tied to `other`, ie they were created with
+    /// [`Parameter::share()`].
+    ///
+    /// Mutable views of a parameter are tied to the parameter and its copies.
+    pub fn is_shared_with<S2: ScalarData, D2: Dimension>(
+        &self,
+        other: &ParameterBase<S2, D2>,
+    ) -> bool {
+        match (self.tie.grad.as_ref(), other.tie.grad.as_ref()) {
+            (Some(a), Some(b)) => Arc::ptr_eq(a, b),
+            _ => false,
         }
     }
     /// Borrows the optimizer state.
@@ -713,9 +1844,14 @@ impl<S: ScalarData, D: Dimension> ParameterBase<S, D> {
         self.optim_state.get()
     }
     /// Borrows the optimizer state mutably.
-    pub fn optimzer_state_mut(&mut self) -> Option<&mut OptimizerState> {
+    pub fn optimizer_state_mut(&mut self) -> Option<&mut OptimizerState> {
         self.optim_state.get_mut()
     }
+    /// Borrows the optimizer state mutably.
+    #[deprecated(since = "0.2.0", note = "renamed to `optimizer_state_mut`")]
+    pub fn optimzer_state_mut(&mut self) -> Option<&mut OptimizerState> {
+        self.optimizer_state_mut()
+    }
     /// Borrows the value and optimizer state mutably.
     pub fn value_view_optimizer_state_mut(
         &mut self,
@@ -754,52 +1890,139 @@ impl<S: ScalarData, D: Dimension> ParameterBase<S, D> {
     {
         Ok(ParameterBase {
             value: self.value.into_dimensionality()?,
-            grad: self.grad.clone(),
+            grad: self.grad,
             optim_state: self.optim_state,
+            tie: self.tie,
         })
     }
     /// Converts into a dynamic dimensional parameter.
     pub fn into_dyn(self) -> ParameterBase<S, IxDyn> {
         ParameterBase {
             value: self.value.into_dyn(),
-            grad: self.grad.clone(),
+            grad: self.grad,
             optim_state: self.optim_state,
+            tie: self.tie,
         }
     }
 }
 
+impl<'a, D: Dimension> ParameterViewMut<'a, D> {
+    /// Ties `other` to `self` if they are views of copies of the same parameter.
+    ///
+    /// Layers that contain tied parameters return a view of each parameter once from
+    /// [`Layer::parameters_mut()`](super::layer::Layer::parameters_mut), so that the
+    /// [`Optimizer`] applies the gradient once per step. The copy of `other` is updated with the
+    /// value and optimizer state of the parameter of `self` when `self` is dropped.
+    ///
+    /// Returns `other` if it is not tied to `self`.
+    ///
+    /// See [`Parameter::share()`].
+    pub fn tie<D2: Dimension>(
+        &mut self,
+        mut other: ParameterViewMut<'a, D2>,
+    ) -> Option<ParameterViewMut<'a, D2>> {
+        if !self.is_shared_with(&other) {
+            return Some(other);
+        }
+        self.tie.copies.extend(other.tie.origin.take());
+        self.tie.copies.append(&mut other.tie.copies);
+        None
+    }
+}
+
 impl<D: Dimension> Parameter<D> {
+    /// Creates a parameter with `value`.
+    ///
+    /// The parameter does not have a gradient until [`.set_training(true)`](Parameter::set_training).
+    pub fn new(value: ScalarArcTensor<D>) -> Self {
+        Self {
+            value,
+            grad: None,
+            optim_state: OptimState::default(),
+            tie: Tie::default(),
+        }
+    }
     /// Converts to a `Variable`.
+    ///
+    /// The variable has a node if training, unless within [`no_grad()`].
     pub fn to_variable(&self) -> Variable<D> {
         let value = self.value.clone();
-        let node = self.grad.as_ref().map(|grad| {
-            Node::new(
-                value.device(),
-                value.raw_dim().into_dyn(),
-                value.scalar_type(),
-                grad.clone(),
-                Vec::new(),
-            )
-        });
+        let node = self
+            .grad
+            .as_ref()
+            .filter(|_| is_grad_enabled())
+            .map(|grad| {
+                Node::new(
+                    value.device(),
+                    value.raw_dim().into_dyn(),
+                    value.scalar_type(),
+                    grad.clone(),
+                    Vec::new(),
+                )
+            });
         Variable { value, node }
     }
+    /// Shares the parameter.
+    ///
+    /// Returns a copy of the parameter that is tied to `self`, allowing weights to be tied
+    /// between layers. The copies hold the same value and optimizer state, and variables
+    /// created from either accumulate into the same gradient. Disabling training of either
+    /// parameter discards the shared gradient.
+    ///
+    /// [`Layer::parameters()`](super::layer::Layer::parameters) and
+    /// [`Layer::parameters_mut()`](super::layer::Layer::parameters_mut) of a layer that
+    /// contains both copies return the parameter once, so that the [`Optimizer`] applies the
+    /// gradient once per step. The other copies are [tied](ParameterViewMut::tie) to the
+    /// mutable view and are updated from it when it is dropped.
+    ///
+    /// Sharing is not preserved by serialization.
+    pub fn share(&mut self) -> Self {
+        let grad = self
+            .tie
+            .grad
+            .get_or_insert_with(|| self.grad.clone().unwrap_or_default())
+            .clone();
+        Self {
+            value: self.value.clone(),
+            grad: self.grad.clone(),
+            optim_state: self.optim_state.clone(),
+            tie: Tie {
+                grad: Some(grad),
+                origin: None,
+                copies: Vec::new(),
+            },
+        }
+    }
     /// Makes a mutable parameter view.
     ///
     /// Copies the value and optimizer state if they are not exclusive.
     ///
+    /// If the parameter is shared, the copies [tied](ParameterViewMut::tie) to the view are
+    /// updated when it is dropped, see [`.share()`](Self::share).
+    ///
     /// See [`TensorBase::make_view_mut`].
     pub fn make_view_mut(&mut self) -> Result<ParameterViewMut<D>> {
-        let value = self.value.make_view_mut()?;
+        let value_ptr = &mut self.value as *mut ScalarArcTensor<D>;
+        let value = unsafe { (*value_ptr).make_view_mut()? };
         let grad = self.grad.clone();
-        let optim_state = self.optim_state.make_mut()?;
+        let optim_state_ptr = self.optim_state.make_mut()? as *mut Option<Arc<OptimizerState>>;
         let optim_state = OptimState::StateMut(unsafe {
-            let optim_state_ptr = optim_state as *mut Option<Arc<OptimizerState>>;
             &mut *optim_state_ptr as &mut Option<Arc<OptimizerState>>
         });
+        let tie = Tie {
+            grad: self.tie.grad.clone(),
+            origin: self
+                .tie
+                .grad
+                .as_ref()
+                .map(|_| TieOrigin::new(value_ptr, optim_state_ptr)),
+            copies: Vec::new(),
+        };
         Ok(ParameterViewMut {
             value,
             grad,
             optim_state,
+            tie,
         })
     }
     /// Moves the parameter into `device`.
@@ -813,7 +2036,12 @@ impl<D: Dimension> Parameter<D> {
     /// Transfers the parameter to `device` if necessary.
     pub fn to_device_mut(&mut self, device: Device) -> Result<()> {
         self.value.to_device_mut(device.clone())?;
-        if let Some(grad) = self.grad.as_mut() {
+        if let Some(grad) = self.tie.grad.as_ref() {
+            let mut guard = grad.write();
+            if let Some(value) = guard.take() {
+                guard.replace(value.into_device_shared(device.clone())?);
+            }
+        } else if let Some(grad) = self.grad.as_mut() {
             let value = if let Some(value) = grad.read().clone() {
                 Some(value.clone().into_device_shared(device.clone())?)
             } else {
@@ -824,6 +2052,27 @@ impl<D: Dimension> Parameter<D> {
         self.optim_state.to_device_mut(device)?;
         Ok(())
     }
+    /// Casts the parameter to `scalar_type` in place.
+    ///
+    /// The gradient and optimizer state are cleared if the scalar type changes, as they have
+    /// the previous scalar type.
+    ///
+    /// **Errors**
+    ///
+    /// See [`ScalarTensorBase::cast`].
+    pub fn cast_mut(&mut self, scalar_type: ScalarType) -> Result<()> {
+        if self.value.scalar_type() == scalar_type {
+            return Ok(());
+        }
+        self.value = self.value.cast(scalar_type)?.into_shared()?;
+        if let Some(grad) = self.tie.grad.as_ref() {
+            grad.write().take();
+        } else if let Some(grad) = self.grad.as_mut() {
+            *grad = Arc::new(RwLock::new(None));
+        }
+        self.optim_state.as_mut().take();
+        Ok(())
+    }
 }
 
 impl<T: Scalar, D: Dimension> From<Tensor<T, D>> for Parameter<D> {
@@ -846,11 +2095,14 @@ impl<D: Dimension> From<ScalarTensor<D>> for Parameter<D> {
 
 impl<D: Dimension> From<ScalarArcTensor<D>> for Parameter<D> {
     fn from(tensor: ScalarArcTensor<D>) -> Self {
-        Self {
-            value: tensor,
-            grad: None,
-            optim_state: OptimState::default(),
-        }
+        Self::new(tensor)
+    }
+}
+
+/// Uses the value of the variable, the parameter is not connected to the node of the variable.
+impl<D: Dimension> From<Variable<D>> for Parameter<D> {
+    fn from(variable: Variable<D>) -> Self {
+        Self::new(variable.into_value())
     }
 }
 
@@ -864,6 +2116,88 @@ impl<S: ScalarData, D: Dimension> Debug for ParameterBase<S, D> {
     }
 }
 
+// The copies of a tied parameter, see [`Parameter::share()`].
+//
+// The copies hold the same value, which is a single tensor until it is modified through a
+// mutable view. The view holds the copies that were tied to it and updates them when dropped.
+#[derive(Default)]
+struct Tie {
+    // The gradient of the copies, which also identifies them.
+    grad: Option<Arc<RwLock<Option<ScalarArcTensorD>>>>,
+    // The parameter a mutable view was made from.
+    origin: Option<TieOrigin>,
+    // The copies tied to a mutable view.
+    copies: Vec<TieOrigin>,
+}
+
+impl Clone for Tie {
+    fn clone(&self) -> Self {
+        Self {
+            grad: self.grad.clone(),
+            origin: None,
+            copies: Vec::new(),
+        }
+    }
+}
+
+impl Drop for Tie {
+    fn drop(&mut self) {
+        if let Some(origin) = self.origin.as_ref() {
+            // The tie is dropped after the value and optimizer state of the view, so the
+            // parameters are no longer borrowed.
+            unsafe {
+                let value = (origin.get)(origin.value);
+                let optim_state = origin.optim_state.as_ref().clone();
+                for copy in self.copies.iter() {
+                    (copy.set)(copy.value, value.clone());
+                    *copy.optim_state.as_ptr() = optim_state.clone();
+                }
+            }
+        }
+    }
+}
+
+// A tied parameter that is borrowed by a mutable view.
+struct TieOrigin {
+    value: NonNull<()>,
+    get: unsafe fn(NonNull<()>) -> ScalarArcTensorD,
+    set: unsafe fn(NonNull<()>, ScalarArcTensorD),
+    optim_state: NonNull<Option<Arc<OptimizerState>>>,
+}
+
+// The parameter is borrowed mutably for the lifetime of the view.
+unsafe impl Send for TieOrigin {}
+unsafe impl Sync for TieOrigin {}
+
+impl TieOrigin {
+    fn new<D: Dimension>(
+        value: *mut ScalarArcTensor<D>,
+        optim_state: *mut Option<Arc<OptimizerState>>,
+    ) -> Self {
+        unsafe fn get<D: Dimension>(value: NonNull<()>) -> ScalarArcTensorD {
+            unsafe {
+                value
+                    .cast::<ScalarArcTensor<D>>()
+                    .as_ref()
+                    .clone()
+                    .into_dyn()
+            }
+        }
+        unsafe fn set<D: Dimension>(value: NonNull<()>, tensor: ScalarArcTensorD) {
+            let tensor = tensor.into_dimensionality().unwrap();
+            unsafe {
+                *value.cast::<ScalarArcTensor<D>>().as_ptr() = tensor;
+            }
+        }
+        Self {
+            value: NonNull::new(value).unwrap().cast(),
+            get: get::<D>,
+            set: set::<D>,
+            optim_state: NonNull::new(optim_state).unwrap(),
+        }
+    }
+}
+
 #[derive(Debug)]
 enum OptimState<'a> {
     State(Option<Arc<OptimizerState>>),
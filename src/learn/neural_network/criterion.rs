@@ -1,11 +1,14 @@
-use super::autograd::{Variable0, Variable2};
+use super::{
+    autograd::{Variable0, Variable1, Variable2},
+    functional::margin_hinge,
+};
 #[cfg(feature = "device")]
-use crate::tensor::{ScalarTensor, ScalarTensorView, Tensor};
+use crate::tensor::{ScalarTensor, ScalarTensorView};
 use crate::{
-    learn::criterion::CrossEntropyLoss,
-    tensor::{ScalarArcTensor, ScalarArcTensor1, Tensor2, TensorView1, TensorView2},
+    learn::criterion::{CrossEntropyLoss, CrossEntropyLossOptions, MultiMarginLoss},
+    tensor::{ScalarArcTensor, ScalarArcTensor1, Tensor, Tensor2, TensorView1, TensorView2},
 };
-use anyhow::{bail, Result};
+use anyhow::{bail, Error, Result};
 use dry::macro_for;
 use half::bf16;
 #[cfg(feature = "device")]
@@ -14,7 +17,7 @@ use krnl::{
     device::Device,
     scalar::{Scalar, ScalarElem, ScalarType},
 };
-use ndarray::Array2;
+use ndarray::{linalg::Dot, Array2};
 #[cfg(feature = "device")]
 use num_traits::ToPrimitive;
 use num_traits::{Float, Unsigned};
@@ -26,7 +29,12 @@ use paste::paste;
 /// - target: u8, u16, u32
 impl CrossEntropyLoss<ScalarArcTensor1> for Variable2 {
     type Output = Variable0;
-    fn cross_entropy_loss(&self, target: ScalarArcTensor1) -> Result<Variable0> {
+    /// The gradient is computed in f32.
+    fn cross_entropy_loss_with(
+        &self,
+        target: ScalarArcTensor1,
+        options: CrossEntropyLossOptions,
+    ) -> Result<Variable0> {
         if !matches!(self.scalar_type(), ScalarType::BF16 | ScalarType::F32)
             || !matches!(
                 target.scalar_type(),
@@ -67,12 +75,85 @@ impl CrossEntropyLoss<ScalarArcTensor1> for Variable2 {
                 unreachable!()
             });
         }
-        let loss = self.value().cross_entropy_loss(target)?;
+        let loss = self.value().cross_entropy_loss_with(target, options)?;
         let value = ScalarArcTensor::from_elem(Device::host(), (), ScalarElem::F32(loss)).unwrap();
         Ok(builder.build(value))
     }
 }
 
+/// Implemented for:
+/// - input: bf16, f32
+/// - target: u8, u16, u32
+impl MultiMarginLoss<ScalarArcTensor1> for Variable2 {
+    type Output = Variable0;
+    /// The loss has the scalar type of the input.
+    ///
+    /// The gradient of each class is the output gradient where its hinge is positive, which is
+    /// subtracted from the target, and 0 otherwise.
+    fn multi_margin_loss(&self, target: ScalarArcTensor1, margin: f32) -> Result<Variable0> {
+        let scalar_type = self.scalar_type();
+        if !matches!(scalar_type, ScalarType::BF16 | ScalarType::F32)
+            || !matches!(
+                target.scalar_type(),
+                ScalarType::U8 | ScalarType::U16 | ScalarType::U32
+            )
+        {
+            bail!(
+                "MultiMarginLoss {:?} {:?} unimplemented!",
+                scalar_type,
+                target.scalar_type()
+            );
+        }
+        let (batch_size, classes) = self.dim();
+        if classes < 2 || target.len() != batch_size {
+            bail!(
+                "MultiMarginLoss input shape {:?} incompatible with target shape {:?}!",
+                self.shape(),
+                target.shape()
+            );
+        }
+        let target = target
+            .into_device(Device::host())?
+            .cast_into_tensor::<u32>()?
+            .into_vec()?;
+        let mut indices = Vec::with_capacity(batch_size);
+        let mut mask = vec![0u8; batch_size * classes];
+        for (i, t) in target.into_iter().enumerate() {
+            if t as usize >= classes {
+                bail!("MultiMarginLoss target {t} out of bounds for {classes} classes!");
+            }
+            let index = i * classes + t as usize;
+            indices.push(index as u32);
+            mask[index] = 1;
+        }
+        let device = self.device();
+        let indices = Tensor::from(indices)
+            .into_device(device.clone())?
+            .into_shared()?;
+        let mask = Tensor::from(mask)
+            .into_shape([batch_size, classes])
+            .unwrap()
+            .into_device(device.clone())?
+            .into_shared()?;
+        let x_target = self
+            .clone()
+            .into_shape([batch_size * classes, 1])
+            .map_err(Error::msg)?
+            .take(indices)?;
+        let hinge = margin_hinge(self.clone(), &x_target, margin)?
+            .masked_fill(mask, ScalarElem::zero(scalar_type))?;
+        let len = batch_size * classes;
+        let scale = ScalarElem::F32(1. / len as f32).scalar_cast(scalar_type);
+        let scale = Variable1::from(ScalarArcTensor::from_elem(device, len, scale)?);
+        hinge
+            .into_shape([1, len])
+            .map_err(Error::msg)?
+            .dot(&scale)?
+            .into_shape(())
+            .map_err(Error::msg)
+    }
+}
+
 // public for testing
 #[doc(hidden)]
 pub fn cross_entropy_loss_backward<T1: Scalar + Float, T2: Scalar + Unsigned>(
@@ -163,7 +244,7 @@ mod kernels {
                     #[global] dx: UnsafeSlice<$X>,
                 ) {
                     let idx = kernel.global_id;
-                    if idx as usize > t.len() {
+                    if idx as usize >= t.len() {
                         return;
                     }
                     let mut m = x[(idx * classes) as usize].cast::<f32>();
@@ -0,0 +1,501 @@
+/*!
+Stateless functions of variables.
+
+Like `torch.nn.functional`, these apply the operations of layers without constructing them.
+Parameters are passed as variables, ie from [`Parameter::to_variable()`], so that gradients are
+computed for them while training.
+
+```no_run
+# use autograph::{anyhow::Result, learn::neural_network::{autograd::{Parameter1, Parameter2, Variable2}, functional}};
+# fn main() -> Result<()> {
+# let (input, weight, bias): (Variable2, Parameter2, Parameter1) = todo!();
+let x = functional::linear(input, &weight.to_variable(), Some(&bias.to_variable()))?;
+let y = functional::softmax(functional::relu(x)?)?;
+# Ok(())
+# }
+```
+*/
+#[cfg(doc)]
+use super::autograd::Parameter;
+use super::{
+    autograd::{Variable, Variable0, Variable1, Variable2, Variable4},
+    layer::{conv2, inv_std, ConvAlgo, ConvOptions, Forward, MaxPool2, MemoryFormat, Relu},
+};
+#[cfg(feature = "device")]
+use crate::tensor::ScalarTensorView;
+use crate::{
+    ops::{AddAssign, Conv2dOptions, MaxPool2dOptions},
+    tensor::{random::Rng, ScalarArcTensor, ScalarTensor, Tensor, TensorView},
+};
+use anyhow::{bail, Error, Result};
+use dry::macro_for;
+use half::bf16;
+#[cfg(feature = "device")]
+use krnl::macros::module;
+use krnl::scalar::{Scalar, ScalarElem};
+use ndarray::{linalg::Dot, Array2, Dimension, IntoDimension};
+#[cfg(feature = "device")]
+use num_traits::ToPrimitive;
+use once_cell::sync::OnceCell;
+#[cfg(feature = "device")]
+use paste::paste;
+
+/// Rectified linear unit.
+///
+/// See [`Relu`].
+pub fn relu<D: Dimension + 'static>(input: Variable<D>) -> Result<Variable<D>> {
+    Relu.forward(input)
+}
+
+/// Softmax over the last axis.
+///
+/// Computed in f32.
+///
+/// **Errors**
+/// - The scalar type is not BF16 or F32.
+/// - The operation could not be executed on the device.
+pub fn softmax<D: Dimension + 'static>(input: Variable<D>) -> Result<Variable<D>> {
+    let scalar_type = input.scalar_type();
+    let mut builder = Variable::builder();
+    macro_for!($T in [bf16, f32] {
+        if scalar_type == $T::scalar_type() {
+            let x = input.value().view().try_into_tensor_view::<$T>().unwrap();
+            let output: ScalarArcTensor<D> = softmax_impl(x)?.into_shared()?.into();
+            if let Some(node) = input.node() {
+                let output = output.clone();
+                builder.edge(node, move |output_grad| {
+                    let y = output.view().try_into_tensor_view::<$T>().unwrap();
+                    let dy = output_grad.view().try_into_tensor_view::<$T>().unwrap();
+                    Ok(softmax_backward_impl(y, dy)?.into_shared()?.into())
+                });
+            }
+            return Ok(builder.build(output));
+        }
+    });
+    bail!("softmax {scalar_type:?} unimplemented!")
+}
+
+/// 2D convolution.
+///
+/// The weight has shape `[outputs, inputs, fh, fw]`, and the bias has length `outputs`.
+///
+/// See [`Conv`](super::layer::Conv).
+///
+/// **Errors**
+/// - The shapes are incompatible.
+/// - The stride or dilation is 0, or the dilated filter is larger than the padded input.
+/// - See [`ScalarTensorBase::conv2d()`](crate::tensor::ScalarTensorBase::conv2d).
+pub fn conv2d(
+    input: Variable4,
+    weight: &Variable4,
+    bias: Option<&Variable1>,
+    options: &Conv2dOptions,
+) -> Result<Variable4> {
+    let (_, inputs, ih, iw) = input.dim();
+    let (outputs, weight_inputs, fh, fw) = weight.dim();
+    if inputs != weight_inputs || bias.map_or(false, |bias| bias.dim() != outputs) {
+        bail!(
+            "conv2d input shape {:?} incompatible with weight shape {:?} and bias shape {:?}!",
+            input.shape(),
+            weight.shape(),
+            bias.map(Variable::shape),
+        );
+    }
+    let Conv2dOptions {
+        padding,
+        stride,
+        dilation,
+    } = *options;
+    for (((x, f), p), (s, d)) in [ih, iw]
+        .into_iter()
+        .zip([fh, fw])
+        .zip(padding)
+        .zip(stride.into_iter().zip(dilation))
+    {
+        if s == 0 || d == 0 || f == 0 || x + 2 * p < d * (f - 1) + 1 {
+            bail!(
+                "conv2d invalid options {options:?} for input shape {:?} and weight shape {:?}!",
+                input.shape(),
+                weight.shape()
+            );
+        }
+    }
+    conv2(
+        input,
+        weight.clone(),
+        ConvOptions {
+            padding: padding.into_dimension(),
+            padding_after: padding.into_dimension(),
+            stride: stride.into_dimension(),
+            dilation: dilation.into_dimension(),
+        },
+        bias.cloned(),
+        ConvAlgo::Im2Col,
+        &OnceCell::new(),
+        false,
+        None,
+        MemoryFormat::ChannelsFirst,
+    )
+}
+
+/// 2D max pooling.
+///
+/// See [`MaxPool`](super::layer::MaxPool).
+///
+/// **Errors**
+///
+/// See [`ScalarTensorBase::max_pool2d()`](crate::tensor::ScalarTensorBase::max_pool2d).
+pub fn max_pool2d(input: Variable4, options: &MaxPool2dOptions) -> Result<Variable4> {
+    MaxPool2::builder()
+        .filter(options.filter)
+        .stride(options.stride)
+        .build()
+        .forward(input)
+}
+
+/// Linear transformation, ie `input * weight + bias`.
+///
+/// The weight has shape `[inputs, outputs]`, and the bias has length `outputs`.
+///
+/// See [`Dense`](super::layer::Dense).
+///
+/// **Errors**
+/// - The shapes are incompatible.
+/// - The operation could not be executed on the device.
+pub fn linear(input: Variable2, weight: &Variable2, bias: Option<&Variable1>) -> Result<Variable2> {
+    let mut output = input.dot(weight)?;
+    if let Some(bias) = bias {
+        output.add_assign(bias)?;
+    }
+    Ok(output)
+}
+
+/// Dropout.
+///
+/// While `training`, zeros each element with probability `p`, and scales the rest by
+/// `1 / (1 - p)`. Otherwise returns the input.
+///
+/// **Errors**
+/// - `p` is not in [0, 1).
+/// - `rng` is not on the same device as `input`.
+/// - The operation could not be executed on the device.
+pub fn dropout<D: Dimension + 'static>(
+    input: Variable<D>,
+    p: f32,
+    training: bool,
+    rng: &mut Rng,
+) -> Result<Variable<D>> {
+    if !(0. ..1.).contains(&p) {
+        bail!("dropout p {p} not in [0, 1)!");
+    }
+    if !training || p == 0. {
+        return Ok(input);
+    }
+    if rng.device() != input.device() {
+        bail!(
+            "dropout rng device {:?} != input device {:?}!",
+            rng.device(),
+            input.device()
+        );
+    }
+    let scale = ScalarElem::F32(1. / (1. - p)).scalar_cast(input.scalar_type());
+    let mask = ScalarTensor::from(Tensor::<f32, D>::bernoulli(rng, input.raw_dim(), 1. - p)?)
+        .scaled_cast(scale)?
+        .into_shared()?;
+    input.mul(&Variable::from(mask))
+}
+
+/// Layer normalization over the last axis.
+///
+/// Normalizes each lane of the last axis to zero mean and unit variance, then applies the
+/// elementwise `weight` and `bias`, with length equal to the last axis.
+///
+/// **Errors**
+/// - The input has no axes, or the last axis is empty.
+/// - The shapes of `weight` or `bias` are incompatible.
+/// - The operation could not be executed on the device.
+pub fn layer_norm<D: Dimension + 'static>(
+    input: Variable<D>,
+    weight: Option<&Variable1>,
+    bias: Option<&Variable1>,
+    eps: f32,
+) -> Result<Variable<D>> {
+    let dim = input.raw_dim();
+    let n = dim.slice().last().copied().unwrap_or_default();
+    if n == 0 {
+        bail!("layer_norm invalid input shape {:?}!", input.shape());
+    }
+    for x in weight.iter().chain(bias.iter()) {
+        if x.dim() != n {
+            bail!(
+                "layer_norm input shape {:?} incompatible with {:?}!",
+                input.shape(),
+                x.shape()
+            );
+        }
+    }
+    let rows = dim.size() / n;
+    let device = input.device();
+    let scalar_type = input.scalar_type();
+    let means = |alpha: f32| {
+        ScalarArcTensor::from_elem(
+            device.clone(),
+            [n, 1],
+            ScalarElem::F32(alpha / n as f32).scalar_cast(scalar_type),
+        )
+        .map(Variable2::from)
+    };
+    let input = input.into_shape([rows, n]).map_err(Error::msg)?;
+    let mut centered = input.clone();
+    centered.add_assign(input.dot(&means(-1.)?)?)?;
+    let var = centered.mul(&centered)?.dot(&means(1.)?)?;
+    let inv_std = inv_std(&var, eps)?.broadcast([rows, n]).unwrap();
+    let mut output = centered.mul(&inv_std)?;
+    if let Some(weight) = weight {
+        let weight = weight
+            .clone()
+            .into_shape([1, n])
+            .map_err(Error::msg)?
+            .broadcast([rows, n])
+            .unwrap();
+        output = output.mul(&weight)?;
+    }
+    if let Some(bias) = bias {
+        output.add_assign(bias)?;
+    }
+    output.into_shape(dim).map_err(Error::msg)
+}
+
+/// Triplet margin loss for metric learning.
+///
+/// The mean over the batch of `max(0, d(anchor, positive) - d(anchor, negative) + margin)`, where
+/// `d` is the Euclidean distance between rows. Like PyTorch, 1e-6 is added to the differences so
+/// that the gradient is finite when rows are equal.
+///
+/// **Errors**
+/// - The shapes are not the same.
+/// - The scalar type is not BF16 or F32.
+/// - The operation could not be executed on the device.
+pub fn triplet_margin_loss(
+    anchor: &Variable2,
+    positive: &Variable2,
+    negative: &Variable2,
+    margin: f32,
+) -> Result<Variable0> {
+    if anchor.shape() != positive.shape() || anchor.shape() != negative.shape() {
+        bail!(
+            "triplet_margin_loss anchor shape {:?} incompatible with positive shape {:?} and negative shape {:?}!",
+            anchor.shape(),
+            positive.shape(),
+            negative.shape(),
+        );
+    }
+    let loss = margin_hinge(
+        pairwise_distance(anchor, positive)?,
+        &pairwise_distance(anchor, negative)?,
+        margin,
+    )?;
+    let batch_size = loss.dim();
+    let scale = loss.full_like(ScalarElem::F32(1. / batch_size as f32))?;
+    loss.into_shape([1, batch_size])
+        .map_err(Error::msg)?
+        .dot(&scale)?
+        .into_shape(())
+        .map_err(Error::msg)
+}
+
+// max(0, x1 - x2 + margin), where `x2` is broadcast to `x1`.
+pub(crate) fn margin_hinge<D1: Dimension + 'static, D2: Dimension + 'static>(
+    x1: Variable<D1>,
+    x2: &Variable<D2>,
+    margin: f32,
+) -> Result<Variable<D1>> {
+    let mut hinge = x1;
+    hinge.add_assign(x2.neg()?)?;
+    let margin = hinge.full_like(ScalarElem::F32(margin))?;
+    hinge.add_assign(margin)?;
+    // ties route the gradient to the first operand, so it is 0 at the margin
+    hinge.zeros_like()?.maximum(&hinge)
+}
+
+// The Euclidean distance between the rows of `x1` and `x2`.
+fn pairwise_distance(x1: &Variable2, x2: &Variable2) -> Result<Variable1> {
+    let mut diff = x1.clone();
+    diff.add_assign(x2.neg()?)?;
+    diff.add_assign(x1.full_like(ScalarElem::F32(1e-6))?)?;
+    let ones = Variable1::from(ScalarArcTensor::from_elem(
+        x1.device(),
+        x1.dim().1,
+        ScalarElem::F32(1.).scalar_cast(x1.scalar_type()),
+    )?);
+    let distance = diff.mul(&diff)?.dot(&ones)?;
+    let exponent = distance.full_like(ScalarElem::F32(0.5))?;
+    distance.pow(&exponent)
+}
+
+fn softmax_impl<T: Scalar, D: Dimension>(x: TensorView<T, D>) -> Result<Tensor<T, D>> {
+    let dim = x.raw_dim();
+    let classes = dim.slice().last().copied().unwrap_or(1);
+    let rows = if classes > 0 { dim.size() / classes } else { 0 };
+    let x = x.as_standard_layout()?;
+    if let Some(x) = x.as_array() {
+        let x = x.into_shape([rows, classes]).unwrap();
+        let mut y = Array2::zeros([rows, classes]);
+        for (x, mut y) in x.outer_iter().zip(y.outer_iter_mut()) {
+            let m = x
+                .iter()
+                .map(|x| x.cast::<f32>())
+                .fold(f32::NEG_INFINITY, f32::max);
+            let s: f32 = x.iter().map(|x| (x.cast::<f32>() - m).exp()).sum();
+            for (x, y) in x.iter().zip(y.iter_mut()) {
+                *y = ((x.cast::<f32>() - m).exp() / s).cast();
+            }
+        }
+        return Ok(y.into_shape(dim).unwrap().into());
+    }
+    #[cfg(not(feature = "device"))]
+    {
+        unreachable!()
+    }
+    #[cfg(feature = "device")]
+    {
+        macro_for!($T in [bf16, f32] {
+            if T::scalar_type() == $T::scalar_type() {
+                let x = ScalarTensorView::from(x.view()).try_into_tensor_view::<$T>().unwrap();
+                let mut y = unsafe { Tensor::<$T, D>::uninit(x.device(), dim)? };
+                let kernel = paste! { kernels::[<softmax_ $T>]::builder()?.with_threads(256).build(y.device())? };
+                kernel
+                    .with_global_threads(rows.to_u32().unwrap())
+                    .dispatch(
+                        x.as_slice().unwrap(),
+                        rows.to_u32().unwrap(),
+                        classes.to_u32().unwrap(),
+                        y.as_slice_mut().unwrap(),
+                    )?;
+                return Ok(ScalarTensor::from(y).try_into_tensor().unwrap());
+            }
+        });
+        unreachable!()
+    }
+}
+
+// dx = y * (dy - sum(dy * y)) for each lane of the last axis.
+fn softmax_backward_impl<T: Scalar, D: Dimension>(
+    y: TensorView<T, D>,
+    dy: TensorView<T, D>,
+) -> Result<Tensor<T, D>> {
+    let dim = y.raw_dim();
+    let classes = dim.slice().last().copied().unwrap_or(1);
+    let rows = if classes > 0 { dim.size() / classes } else { 0 };
+    let y = y.as_standard_layout()?;
+    let dy = dy.as_standard_layout()?;
+    if let Some((y, dy)) = y.as_array().zip(dy.as_array()) {
+        let y = y.into_shape([rows, classes]).unwrap();
+        let dy = dy.into_shape([rows, classes]).unwrap();
+        let mut dx = Array2::zeros([rows, classes]);
+        for ((y, dy), mut dx) in y.outer_iter().zip(dy.outer_iter()).zip(dx.outer_iter_mut()) {
+            let s: f32 = y
+                .iter()
+                .zip(dy.iter())
+                .map(|(y, dy)| y.cast::<f32>() * dy.cast::<f32>())
+                .sum();
+            for ((y, dy), dx) in y.iter().zip(dy.iter()).zip(dx.iter_mut()) {
+                *dx = (y.cast::<f32>() * (dy.cast::<f32>() - s)).cast();
+            }
+        }
+        return Ok(dx.into_shape(dim).unwrap().into());
+    }
+    #[cfg(not(feature = "device"))]
+    {
+        unreachable!()
+    }
+    #[cfg(feature = "device")]
+    {
+        macro_for!($T in [bf16, f32] {
+            if T::scalar_type() == $T::scalar_type() {
+                let y = ScalarTensorView::from(y.view()).try_into_tensor_view::<$T>().unwrap();
+                let dy = ScalarTensorView::from(dy.view()).try_into_tensor_view::<$T>().unwrap();
+                let mut dx = unsafe { Tensor::<$T, D>::uninit(y.device(), dim)? };
+                let kernel = paste! { kernels::[<softmax_backward_ $T>]::builder()?.with_threads(256).build(dx.device())? };
+                kernel
+                    .with_global_threads(rows.to_u32().unwrap())
+                    .dispatch(
+                        y.as_slice().unwrap(),
+                        dy.as_slice().unwrap(),
+                        rows.to_u32().unwrap(),
+                        classes.to_u32().unwrap(),
+                        dx.as_slice_mut().unwrap(),
+                    )?;
+                return Ok(ScalarTensor::from(dx).try_into_tensor().unwrap());
+            }
+        });
+        unreachable!()
+    }
+}
+
+#[cfg(feature = "device")]
+#[module]
+mod kernels {
+    use dry::macro_for;
+    #[cfg(not(target_arch = "spirv"))]
+    use krnl::krnl_core;
+    use krnl_core::macros::kernel;
+    #[cfg(target_arch = "spirv")]
+    use krnl_core::{buffer::UnsafeIndex, half::bf16, num_traits::Float, scalar::Scalar};
+    use paste::paste;
+
+    macro_for!($T in [bf16, f32] {
+        paste! {
+            #[kernel]
+            pub fn [<softmax_ $T>](
+                #[global] x: Slice<$T>,
+                rows: u32,
+                classes: u32,
+                #[global] y: UnsafeSlice<$T>,
+            ) {
+                let idx = kernel.global_id;
+                if idx >= rows {
+                    return;
+                }
+                let start = (idx * classes) as usize;
+                let classes = classes as usize;
+                let mut m = f32::NEG_INFINITY;
+                for i in 0..classes {
+                    m = m.max(x[start + i].cast::<f32>());
+                }
+                let mut s = 0f32;
+                for i in 0..classes {
+                    s += (x[start + i].cast::<f32>() - m).exp();
+                }
+                for i in 0..classes {
+                    let y = unsafe { y.unsafe_index_mut(start + i) };
+                    *y = ((x[start + i].cast::<f32>() - m).exp() / s).cast();
+                }
+            }
+
+            #[kernel]
+            pub fn [<softmax_backward_ $T>](
+                #[global] y: Slice<$T>,
+                #[global] dy: Slice<$T>,
+                rows: u32,
+                classes: u32,
+                #[global] dx: UnsafeSlice<$T>,
+            ) {
+                let idx = kernel.global_id;
+                if idx >= rows {
+                    return;
+                }
+                let start = (idx * classes) as usize;
+                let classes = classes as usize;
+                let mut s = 0f32;
+                for i in 0..classes {
+                    s += y[start + i].cast::<f32>() * dy[start + i].cast::<f32>();
+                }
+                for i in 0..classes {
+                    let dx = unsafe { dx.unsafe_index_mut(start + i) };
+                    *dx = (y[start + i].cast::<f32>() * (dy[start + i].cast::<f32>() - s)).cast();
+                }
+            }
+        }
+    });
+}
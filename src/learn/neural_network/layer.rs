@@ -1,45 +1,60 @@
 use super::autograd::{
-    Parameter, Parameter1, Parameter2, ParameterD, ParameterViewMut, ParameterViewMut1,
-    ParameterViewMut2, ParameterViewMutD, Variable, Variable1, Variable2, Variable3, Variable4,
+    no_grad, Parameter, Parameter1, Parameter2, Parameter3, ParameterD, ParameterViewMut,
+    ParameterViewMut1, ParameterViewMut2, ParameterViewMutD, Variable, Variable1, Variable2,
+    Variable3, Variable4,
 };
+use super::onnx::{OnnxGraph, ToOnnx};
 #[cfg(doc)]
 use super::optimizer::Optimizer;
 use crate::{
+    error::AutographError,
     ops::{
-        AddAssign, Col2ImConv2, Col2ImConv2Options, Im2ColConv2, Im2ColConv2Options, MaxPool2 as _,
-        MaxPool2Backward as _, MaxPool2Options,
+        conv_output_size, AddAssign, Col2ImConv2, Col2ImConv2Options, Conv2Direct, Conv2dOptions,
+        Im2ColConv2, Im2ColConv2Options, MaxPool2Backward as _, MaxPool2Options, MaxPool2dOptions,
     },
     tensor::{
-        ScalarArcTensor, ScalarArcTensor4, ScalarTensor, ScalarTensorBase, Tensor, TensorView,
-        TensorViewMut,
+        ArcTensor1, ElementwiseTuning, ScalarArcTensor, ScalarArcTensor2, ScalarArcTensor4,
+        ScalarCowTensor4, ScalarTensor, ScalarTensor2, ScalarTensorBase, ScalarTensorD,
+        ScalarTensorView, Tensor, TensorView, TensorViewMut,
     },
 };
 use anyhow::{bail, Error, Result};
-pub use autograph_derive::*;
-#[cfg(feature = "device")]
+pub use autograph_derive::{Forward, Layer};
 use dry::macro_for;
-use half::bf16;
+use half::{bf16, f16};
 #[cfg(feature = "device")]
 use krnl::buffer::ScalarSliceMut;
 use krnl::{
     buffer::{Buffer, ScalarBuffer, ScalarData},
     device::Device,
-    scalar::{Scalar, ScalarType},
+    scalar::{Scalar, ScalarElem, ScalarType},
 };
 #[cfg(feature = "device")]
 use paste::paste;
 
 #[cfg(feature = "device")]
 use krnl::macros::module;
-use ndarray::{linalg::Dot, Array, Dimension, IntoDimension, Ix1, Ix2};
+use ndarray::{
+    linalg::Dot, s, Array, ArrayBase, Axis, Data as ArrayData, Dimension, IntoDimension, Ix1, Ix2,
+    IxDyn, RemoveAxis,
+};
+use once_cell::sync::OnceCell;
+use parking_lot::{Mutex, RwLock};
 
 use rand::{
     distributions::{Distribution, Uniform},
-    thread_rng,
+    rngs::StdRng,
+    SeedableRng,
 };
 use serde::{Deserialize, Serialize};
 use smallvec::SmallVec;
-use std::any::Any;
+use std::{
+    any::Any,
+    collections::BTreeMap,
+    fmt::{self, Debug, Formatter},
+    io::{Read, Write},
+    ops::Range,
+};
 
 /// Layer builders.
 pub mod builder {
@@ -51,15 +66,53 @@ pub mod builder {
         dim
     }
 
+    // `a` is the bound of the uniform distribution of the weight.
+    fn init_bias(
+        bias_init: BiasInit,
+        a: f32,
+        rng: &mut StdRng,
+        outputs: usize,
+        scalar_type: ScalarType,
+        device: Device,
+    ) -> Result<Parameter1> {
+        let bias = match bias_init {
+            BiasInit::Zeros => ScalarTensor::zeros(device, outputs, scalar_type)?,
+            BiasInit::Constant(value) => {
+                ScalarTensor::from_elem(device, outputs, ScalarElem::F32(value))?
+                    .cast_into(scalar_type)?
+            }
+            BiasInit::Uniform => {
+                let bias_iter = Uniform::new(-a, a).sample_iter(rng).take(outputs);
+                let bias = match scalar_type {
+                    ScalarType::BF16 => ScalarBuffer::from(Buffer::from(
+                        bias_iter.map(bf16::from_f32).collect::<Vec<_>>(),
+                    )),
+                    ScalarType::F32 => {
+                        ScalarBuffer::from(Buffer::from(bias_iter.collect::<Vec<_>>()))
+                    }
+                    _ => unreachable!(),
+                };
+                ScalarTensor::from(bias.into_device(device)?)
+            }
+        };
+        Ok(Parameter::from(bias))
+    }
+
     /// Builder for creating a [`Conv`].
     pub struct ConvBuilder<D: Dimension, A = Identity> {
         inputs: usize,
         outputs: usize,
         filter: D,
         padding: D,
+        padding_after: Option<D>,
         stride: D,
         dilation: D,
         bias: bool,
+        bias_init: BiasInit,
+        weight_standardization: bool,
+        pub(super) memory_format: MemoryFormat,
+        compute_scalar_type: Option<ScalarType>,
+        seed: Option<u64>,
         scalar_type: ScalarType,
         device: Device,
         activation: A,
@@ -72,9 +125,15 @@ pub mod builder {
                 outputs: 0,
                 filter: D::default(),
                 padding: D::default(),
+                padding_after: None,
                 stride: dim_ones(),
                 dilation: dim_ones(),
                 bias: false,
+                bias_init: BiasInit::default(),
+                weight_standardization: false,
+                memory_format: MemoryFormat::default(),
+                compute_scalar_type: None,
+                seed: None,
                 scalar_type: ScalarType::F32,
                 device: Device::host(),
                 activation: Identity,
@@ -102,6 +161,21 @@ pub mod builder {
         pub fn padding(self, padding: impl IntoDimension<Dim = D>) -> Self {
             Self {
                 padding: padding.into_dimension(),
+                padding_after: None,
+                ..self
+            }
+        }
+        /// Adds `before` and `after` padding to the start and end of each spatial axis.
+        ///
+        /// Used for "same" padding with even filters, ie filter 4 with before 1 and after 2.
+        pub fn asymmetric_padding(
+            self,
+            before: impl IntoDimension<Dim = D>,
+            after: impl IntoDimension<Dim = D>,
+        ) -> Self {
+            Self {
+                padding: before.into_dimension(),
+                padding_after: Some(after.into_dimension()),
                 ..self
             }
         }
@@ -113,6 +187,10 @@ pub mod builder {
             }
         }
         /// Sets the dilation. Defaults to 1.
+        ///
+        /// The filter is applied to inputs spaced `dilation` apart, so each spatial axis has an
+        /// effective filter size of `dilation * (filter - 1) + 1`, and the output size is
+        /// `(input + padding + padding_after - dilation * (filter - 1) - 1) / stride + 1`.
         pub fn dilation(self, dilation: impl IntoDimension<Dim = D>) -> Self {
             Self {
                 dilation: dilation.into_dimension(),
@@ -123,6 +201,48 @@ pub mod builder {
         pub fn bias(self, bias: bool) -> Self {
             Self { bias, ..self }
         }
+        /// Sets the initialization of the bias. Defaults to [`BiasInit::Uniform`].
+        pub fn bias_init(self, bias_init: BiasInit) -> Self {
+            Self { bias_init, ..self }
+        }
+        /// Standardizes the weight on each forward. Defaults to false.
+        ///
+        /// See [`Conv::set_weight_standardization()`].
+        pub fn weight_standardization(self, weight_standardization: bool) -> Self {
+            Self {
+                weight_standardization,
+                ..self
+            }
+        }
+        /// Sets the memory format of the input and output. Defaults to
+        /// [`MemoryFormat::ChannelsFirst`].
+        pub fn memory_format(self, memory_format: MemoryFormat) -> Self {
+            Self {
+                memory_format,
+                ..self
+            }
+        }
+        /// Computes the forward pass in `scalar_type`, instead of the scalar type of the input.
+        ///
+        /// The input and parameters are cast to `scalar_type`, so that ie a layer with BF16
+        /// parameters can accumulate and output F32. The gradients are cast back in the backward
+        /// pass. BF16 and F32 are implemented.
+        pub fn compute_scalar_type(self, scalar_type: ScalarType) -> Self {
+            Self {
+                compute_scalar_type: Some(scalar_type),
+                ..self
+            }
+        }
+        /// Seeds the random initialization of the parameters.
+        ///
+        /// Parameters are always sampled on the host and then transferred to the device, so
+        /// layers built with the same seed have the same initial parameters on any device.
+        pub fn seed(self, seed: u64) -> Self {
+            Self {
+                seed: Some(seed),
+                ..self
+            }
+        }
         /// Add an activation layer.
         pub fn activation<A2>(self, activation: A2) -> ConvBuilder<D, A2> {
             let Self {
@@ -130,9 +250,15 @@ pub mod builder {
                 outputs,
                 filter,
                 padding,
+                padding_after,
                 stride,
                 dilation,
                 bias,
+                bias_init,
+                weight_standardization,
+                memory_format,
+                compute_scalar_type,
+                seed,
                 activation: _,
                 scalar_type,
                 device,
@@ -142,14 +268,26 @@ pub mod builder {
                 outputs,
                 filter,
                 padding,
+                padding_after,
                 stride,
                 dilation,
                 bias,
+                bias_init,
+                weight_standardization,
+                memory_format,
+                compute_scalar_type,
+                seed,
                 activation,
                 scalar_type,
                 device,
             }
         }
+        /// Adds an activation selected at runtime.
+        ///
+        /// See [`Activation`].
+        pub fn activation_kind(self, activation: Activation) -> ConvBuilder<D, Activation> {
+            self.activation(activation)
+        }
         /// Sets the scalar type. Defaults to F32.
         ///
         /// BF16 and F32 are implemented.
@@ -169,27 +307,81 @@ pub mod builder {
         /// - The `scalar_type` is not BF16 or F32.
         /// - Initializing parameters on the `device` failed.
         pub fn build(self) -> Result<Conv<D, A>> {
+            self.try_build().map_err(|(_, e)| e)
+        }
+        /// Builds the layer, returning the builder if initialization failed.
+        pub(super) fn try_build(self) -> Result<Conv<D, A>, (Self, Error)> {
+            let (weight, bias) = match self.init_parameters() {
+                Ok(parameters) => parameters,
+                Err(e) => return Err((self, e)),
+            };
             let Self {
-                inputs,
-                outputs,
-                filter,
                 padding,
+                padding_after,
+                stride,
+                dilation,
+                weight_standardization,
+                memory_format,
+                compute_scalar_type,
+                activation,
+                ..
+            } = self;
+            Ok(Conv {
+                weight,
+                padding,
+                padding_after,
                 stride,
                 dilation,
                 bias,
+                weight_standardization,
+                memory_format,
+                compute_scalar_type,
+                algorithm: ConvAlgo::default(),
+                auto_algorithm: OnceCell::new(),
+                deterministic: false,
+                tile_size: None,
                 activation,
+            })
+        }
+        #[allow(clippy::type_complexity)]
+        fn init_parameters(
+            &self,
+        ) -> Result<(
+            Parameter<<D::Larger as Dimension>::Larger>,
+            Option<Parameter1>,
+        )> {
+            let Self {
+                inputs,
+                outputs,
+                filter,
+                bias,
+                bias_init,
+                compute_scalar_type,
+                seed,
                 scalar_type,
                 device,
+                ..
             } = self;
+            let (inputs, outputs, scalar_type) = (*inputs, *outputs, *scalar_type);
             if !matches!(scalar_type, ScalarType::BF16 | ScalarType::F32) {
-                bail!("Conv {scalar_type:?} not implemented!");
+                bail!(AutographError::unsupported_scalar_type("Conv", scalar_type));
+            }
+            if let Some(compute_scalar_type) = *compute_scalar_type {
+                if !matches!(compute_scalar_type, ScalarType::BF16 | ScalarType::F32) {
+                    bail!(AutographError::unsupported_scalar_type(
+                        "Conv compute_scalar_type",
+                        compute_scalar_type
+                    ));
+                }
             }
             let a = if inputs > 0 {
                 f32::sqrt(2. / (inputs * filter.size()) as f32)
             } else {
                 0.
             };
-            let mut rng = thread_rng();
+            let mut rng = seed
+                .map(StdRng::seed_from_u64)
+                .unwrap_or_else(StdRng::from_entropy);
             let mut weight_dim = <D::Larger as Dimension>::Larger::zeros(2 + filter.ndim());
             weight_dim[0] = outputs;
             weight_dim[1] = inputs;
@@ -209,29 +401,35 @@ pub mod builder {
             let weight = weight.into_device(device.clone())?;
             let weight =
                 Parameter::from(ScalarTensor::from(weight).into_shape(weight_dim).unwrap());
-            let bias = if bias {
-                let bias_iter = Uniform::new(-a, a).sample_iter(rng).take(outputs);
-                let bias = match scalar_type {
-                    ScalarType::BF16 => ScalarBuffer::from(Buffer::from(
-                        bias_iter.map(bf16::from_f32).collect::<Vec<_>>(),
-                    )),
-                    ScalarType::F32 => {
-                        ScalarBuffer::from(Buffer::from(bias_iter.collect::<Vec<_>>()))
-                    }
-                    _ => unreachable!(),
-                };
-                let bias = bias.into_device(device)?;
-                Some(Parameter::from(ScalarTensor::from(bias)))
+            let bias = if *bias {
+                Some(init_bias(
+                    *bias_init,
+                    a,
+                    &mut rng,
+                    outputs,
+                    scalar_type,
+                    device.clone(),
+                )?)
             } else {
                 None
             };
-            Ok(Conv {
-                weight,
-                padding,
-                stride,
-                dilation,
-                bias,
-                activation,
+            Ok((weight, bias))
+        }
+        /// Builds a [`LazyConv`] that sets `inputs` on the first forward.
+        ///
+        /// **Errors**
+        /// - The `scalar_type` is not BF16 or F32.
+        pub fn build_lazy(self) -> Result<LazyConv<D, A>> {
+            if !matches!(self.scalar_type, ScalarType::BF16 | ScalarType::F32) {
+                bail!(AutographError::unsupported_scalar_type(
+                    "Conv",
+                    self.scalar_type
+                ));
+            }
+            Ok(LazyConv {
+                builder: Mutex::new(Some(self)),
+                training: false,
+                conv: OnceCell::new(),
             })
         }
     }
@@ -240,7 +438,11 @@ pub mod builder {
     pub struct DenseBuilder<A = Identity> {
         inputs: usize,
         outputs: usize,
+        weight: Option<Parameter2>,
         bias: bool,
+        bias_init: BiasInit,
+        compute_scalar_type: Option<ScalarType>,
+        seed: Option<u64>,
         scalar_type: ScalarType,
         device: Device,
         activation: A,
@@ -251,7 +453,11 @@ pub mod builder {
             Self {
                 inputs: 0,
                 outputs: 0,
+                weight: None,
                 bias: false,
+                bias_init: BiasInit::default(),
+                compute_scalar_type: None,
+                seed: None,
                 scalar_type: ScalarType::F32,
                 device: Device::host(),
                 activation: Identity,
@@ -268,16 +474,60 @@ pub mod builder {
         pub fn outputs(self, outputs: usize) -> Self {
             Self { outputs, ..self }
         }
+        /// Uses `weight` instead of initializing a new weight.
+        ///
+        /// Sets `inputs`, `outputs`, `scalar_type`, and `device` from `weight`. Use
+        /// [`Parameter::share()`] to tie the weight with another layer.
+        pub fn weight(self, weight: Parameter2) -> Self {
+            let (inputs, outputs) = weight.dim();
+            Self {
+                inputs,
+                outputs,
+                scalar_type: weight.scalar_type(),
+                device: weight.device(),
+                weight: Some(weight),
+                ..self
+            }
+        }
         /// Adds a bias. Defaults to false.
         pub fn bias(self, bias: bool) -> Self {
             Self { bias, ..self }
         }
+        /// Sets the initialization of the bias. Defaults to [`BiasInit::Uniform`].
+        pub fn bias_init(self, bias_init: BiasInit) -> Self {
+            Self { bias_init, ..self }
+        }
+        /// Computes the forward pass in `scalar_type`, instead of the scalar type of the input.
+        ///
+        /// The input and parameters are cast to `scalar_type`, so that ie a layer with BF16
+        /// parameters can accumulate and output F32. The gradients are cast back in the backward
+        /// pass. BF16 and F32 are implemented.
+        pub fn compute_scalar_type(self, scalar_type: ScalarType) -> Self {
+            Self {
+                compute_scalar_type: Some(scalar_type),
+                ..self
+            }
+        }
+        /// Seeds the random initialization of the parameters.
+        ///
+        /// Parameters are always sampled on the host and then transferred to the device, so
+        /// layers built with the same seed have the same initial parameters on any device.
+        pub fn seed(self, seed: u64) -> Self {
+            Self {
+                seed: Some(seed),
+                ..self
+            }
+        }
         /// Adds and activation layer.
         pub fn activation<A2>(self, activation: A2) -> DenseBuilder<A2> {
             let Self {
                 inputs,
                 outputs,
+                weight,
                 bias,
+                bias_init,
+                compute_scalar_type,
+                seed,
                 activation: _,
                 scalar_type,
                 device,
@@ -285,12 +535,22 @@ pub mod builder {
             DenseBuilder {
                 inputs,
                 outputs,
+                weight,
                 bias,
+                bias_init,
+                compute_scalar_type,
+                seed,
                 activation,
                 scalar_type,
                 device,
             }
         }
+        /// Adds an activation selected at runtime.
+        ///
+        /// See [`Activation`].
+        pub fn activation_kind(self, activation: Activation) -> DenseBuilder<Activation> {
+            self.activation(activation)
+        }
         /// Sets the scalar type. Defaults to F32.
         ///
         /// BF16 and F32 are implemented.
@@ -310,117 +570,475 @@ pub mod builder {
         /// - The `scalar_type` is not BF16 or F32.
         /// - Initializing parameters on the `device` failed.
         pub fn build(self) -> Result<Dense<A>> {
+            self.try_build().map_err(|(_, e)| e)
+        }
+        /// Builds the layer, returning the builder if initialization failed.
+        pub(super) fn try_build(self) -> Result<Dense<A>, (Self, Error)> {
+            let (weight, bias) = match self.init_parameters() {
+                Ok(parameters) => parameters,
+                Err(e) => return Err((self, e)),
+            };
+            let Self {
+                compute_scalar_type,
+                activation,
+                ..
+            } = self;
+            Ok(Dense {
+                weight,
+                bias,
+                compute_scalar_type,
+                activation,
+            })
+        }
+        fn init_parameters(&self) -> Result<(Parameter2, Option<Parameter1>)> {
             let Self {
                 inputs,
                 outputs,
+                weight,
                 bias,
-                activation,
+                bias_init,
+                compute_scalar_type,
+                seed,
                 scalar_type,
                 device,
+                ..
             } = self;
+            let (inputs, outputs, scalar_type) = (*inputs, *outputs, *scalar_type);
             if !matches!(scalar_type, ScalarType::BF16 | ScalarType::F32) {
-                bail!("Dense {scalar_type:?} not implemented!");
+                bail!(AutographError::unsupported_scalar_type(
+                    "Dense",
+                    scalar_type
+                ));
+            }
+            if let Some(compute_scalar_type) = *compute_scalar_type {
+                if !matches!(compute_scalar_type, ScalarType::BF16 | ScalarType::F32) {
+                    bail!(AutographError::unsupported_scalar_type(
+                        "Dense compute_scalar_type",
+                        compute_scalar_type
+                    ));
+                }
             }
             let a = if inputs > 0 {
                 f32::sqrt(2. / inputs as f32)
             } else {
                 0.
             };
-            let mut rng = thread_rng();
-            let weight_iter = Uniform::new(-a, a)
-                .sample_iter(&mut rng)
-                .take(inputs * outputs);
-            let weight = match scalar_type {
-                ScalarType::BF16 => ScalarBuffer::from(Buffer::from(
-                    weight_iter.map(bf16::from_f32).collect::<Vec<_>>(),
-                )),
-                ScalarType::F32 => {
-                    ScalarBuffer::from(Buffer::from(weight_iter.collect::<Vec<_>>()))
-                }
-                _ => unreachable!(),
-            };
-            let weight = weight.into_device(device.clone())?;
-            let weight = Parameter::from(
-                ScalarTensor::from(weight)
-                    .into_shape([inputs, outputs])
-                    .unwrap(),
-            );
-            let bias = if bias {
-                let bias_iter = Uniform::new(-a, a).sample_iter(rng).take(outputs);
-                let bias = match scalar_type {
+            let mut rng = seed
+                .map(StdRng::seed_from_u64)
+                .unwrap_or_else(StdRng::from_entropy);
+            let weight = if let Some(weight) = weight {
+                weight.clone()
+            } else {
+                let weight_iter = Uniform::new(-a, a)
+                    .sample_iter(&mut rng)
+                    .take(inputs * outputs);
+                let weight = match scalar_type {
                     ScalarType::BF16 => ScalarBuffer::from(Buffer::from(
-                        bias_iter.map(bf16::from_f32).collect::<Vec<_>>(),
+                        weight_iter.map(bf16::from_f32).collect::<Vec<_>>(),
                     )),
                     ScalarType::F32 => {
-                        ScalarBuffer::from(Buffer::from(bias_iter.collect::<Vec<_>>()))
+                        ScalarBuffer::from(Buffer::from(weight_iter.collect::<Vec<_>>()))
                     }
                     _ => unreachable!(),
                 };
-                let bias = bias.into_device(device)?;
-                Some(Parameter::from(ScalarTensor::from(bias)))
+                let weight = weight.into_device(device.clone())?;
+                Parameter::from(
+                    ScalarTensor::from(weight)
+                        .into_shape([inputs, outputs])
+                        .unwrap(),
+                )
+            };
+            let bias = if *bias {
+                Some(init_bias(
+                    *bias_init,
+                    a,
+                    &mut rng,
+                    outputs,
+                    scalar_type,
+                    device.clone(),
+                )?)
             } else {
                 None
             };
-            Ok(Dense {
-                weight,
-                bias,
-                activation,
+            Ok((weight, bias))
+        }
+        /// Builds a [`LazyDense`] that sets `inputs` on the first forward.
+        ///
+        /// **Errors**
+        /// - The `scalar_type` is not BF16 or F32.
+        pub fn build_lazy(self) -> Result<LazyDense<A>> {
+            if !matches!(self.scalar_type, ScalarType::BF16 | ScalarType::F32) {
+                bail!(AutographError::unsupported_scalar_type(
+                    "Dense",
+                    self.scalar_type
+                ));
+            }
+            Ok(LazyDense {
+                builder: Mutex::new(Some(self)),
+                training: false,
+                dense: OnceCell::new(),
             })
         }
     }
 
-    /// Builder for creating a [`MaxPool`].
-    pub struct MaxPoolBuilder<D: Dimension> {
-        filter: D,
-        stride: Option<D>,
+    /// Builder for creating a [`Bilinear`].
+    pub struct BilinearBuilder {
+        inputs1: usize,
+        inputs2: usize,
+        outputs: usize,
+        weight: Option<Parameter3>,
+        bias: bool,
+        seed: Option<u64>,
+        scalar_type: ScalarType,
+        device: Device,
     }
 
-    impl<D: Dimension> MaxPoolBuilder<D> {
+    impl BilinearBuilder {
         pub(super) fn new() -> Self {
             Self {
-                filter: D::default(),
-                stride: None,
+                inputs1: 0,
+                inputs2: 0,
+                outputs: 0,
+                weight: None,
+                bias: false,
+                seed: None,
+                scalar_type: ScalarType::F32,
+                device: Device::host(),
             }
         }
-        /// Sets the size of the pool filter.
-        pub fn filter(self, filter: impl IntoDimension<Dim = D>) -> Self {
+        /// Sets the number of channels of the first input.
+        pub fn inputs1(self, inputs1: usize) -> Self {
+            Self { inputs1, ..self }
+        }
+        /// Sets the number of channels of the second input.
+        pub fn inputs2(self, inputs2: usize) -> Self {
+            Self { inputs2, ..self }
+        }
+        /// Sets the number of output channels.
+        pub fn outputs(self, outputs: usize) -> Self {
+            Self { outputs, ..self }
+        }
+        /// Uses `weight` instead of initializing a new weight.
+        ///
+        /// Sets `outputs`, `inputs1`, `inputs2`, `scalar_type`, and `device` from `weight`.
+        pub fn weight(self, weight: Parameter3) -> Self {
+            let (outputs, inputs1, inputs2) = weight.dim();
             Self {
-                filter: filter.into_dimension(),
+                inputs1,
+                inputs2,
+                outputs,
+                scalar_type: weight.scalar_type(),
+                device: weight.device(),
+                weight: Some(weight),
                 ..self
             }
         }
-        /// Sets the stride. Defaults to filter.
-        pub fn stride(self, stride: impl IntoDimension<Dim = D>) -> Self {
+        /// Adds a bias. Defaults to false.
+        pub fn bias(self, bias: bool) -> Self {
+            Self { bias, ..self }
+        }
+        /// Seeds the random initialization of the parameters.
+        ///
+        /// See [`DenseBuilder::seed()`].
+        pub fn seed(self, seed: u64) -> Self {
             Self {
-                stride: Some(stride.into_dimension()),
+                seed: Some(seed),
+                ..self
+            }
+        }
+        /// Sets the scalar type. Defaults to F32.
+        ///
+        /// BF16 and F32 are implemented.
+        pub fn scalar_type(self, scalar_type: ScalarType) -> Self {
+            Self {
+                scalar_type,
                 ..self
             }
         }
+        /// Sets the device. Defaults to the host.
+        pub fn device(self, device: Device) -> Self {
+            Self { device, ..self }
+        }
         /// Builds the layer.
-        pub fn build(self) -> MaxPool<D> {
-            let Self { filter, stride } = self;
-            let stride = stride.unwrap_or(filter.clone());
-            MaxPool { filter, stride }
+        ///
+        /// **Errors**
+        /// - The `scalar_type` is not BF16 or F32.
+        /// - Initializing parameters on the `device` failed.
+        pub fn build(self) -> Result<Bilinear> {
+            let Self {
+                inputs1,
+                inputs2,
+                outputs,
+                weight,
+                bias,
+                seed,
+                scalar_type,
+                device,
+            } = self;
+            if !matches!(scalar_type, ScalarType::BF16 | ScalarType::F32) {
+                bail!("Bilinear {scalar_type:?} not implemented!");
+            }
+            let a = if inputs1 > 0 {
+                f32::sqrt(1. / inputs1 as f32)
+            } else {
+                0.
+            };
+            let mut rng = seed
+                .map(StdRng::seed_from_u64)
+                .unwrap_or_else(StdRng::from_entropy);
+            let mut sample = |len: usize| -> Result<ScalarTensor<Ix1>> {
+                let iter = Uniform::new(-a, a).sample_iter(&mut rng).take(len);
+                let buffer = match scalar_type {
+                    ScalarType::BF16 => ScalarBuffer::from(Buffer::from(
+                        iter.map(bf16::from_f32).collect::<Vec<_>>(),
+                    )),
+                    ScalarType::F32 => ScalarBuffer::from(Buffer::from(iter.collect::<Vec<_>>())),
+                    _ => unreachable!(),
+                };
+                Ok(ScalarTensor::from(buffer.into_device(device.clone())?))
+            };
+            let weight = if let Some(weight) = weight {
+                weight
+            } else {
+                Parameter::from(
+                    sample(outputs * inputs1 * inputs2)?
+                        .into_shape([outputs, inputs1, inputs2])
+                        .unwrap(),
+                )
+            };
+            let bias = if bias {
+                Some(Parameter::from(sample(outputs)?))
+            } else {
+                None
+            };
+            Ok(Bilinear { weight, bias })
         }
     }
-}
-use builder::*;
 
-/// ParameterVec
-///
-/// See [`Layer::parameters()`](Layer::parameters).
-pub type ParameterVec = SmallVec<[ParameterD; 2]>;
-/// ParameterMutVec
-///
-/// See [`Layer::parameters_mut()`](Layer::parameters_mut).
-pub type ParameterMutVec<'a> = SmallVec<[ParameterViewMutD<'a>; 2]>;
+    /// Builder for creating a [`BatchNorm`].
+    pub struct BatchNormBuilder {
+        channels: usize,
+        momentum: f32,
+        eps: f32,
+        track_running_stats: bool,
+        scalar_type: ScalarType,
+        device: Device,
+    }
 
-/// Layer.
-///
-/// Typically Layers implement [`Forward<Variable<D>>`](Forward) for the appropriate
-/// dimension `D`.
-///
-/// Layers with parameters or those that store the `device` or `scalar_type` should implement the
+    impl BatchNormBuilder {
+        pub(super) fn new() -> Self {
+            Self {
+                channels: 0,
+                momentum: 0.1,
+                eps: 1e-5,
+                track_running_stats: true,
+                scalar_type: ScalarType::F32,
+                device: Device::host(),
+            }
+        }
+        /// Sets the number of channels.
+        pub fn channels(self, channels: usize) -> Self {
+            Self { channels, ..self }
+        }
+        /// Sets the momentum of the running statistics. Defaults to 0.1.
+        pub fn momentum(self, momentum: f32) -> Self {
+            Self { momentum, ..self }
+        }
+        /// Sets the value added to the variance for numerical stability. Defaults to 1e-5.
+        pub fn eps(self, eps: f32) -> Self {
+            Self { eps, ..self }
+        }
+        /// Whether to update and use the running statistics. Defaults to true.
+        ///
+        /// See [`BatchNorm::set_track_running_stats()`].
+        pub fn track_running_stats(self, track_running_stats: bool) -> Self {
+            Self {
+                track_running_stats,
+                ..self
+            }
+        }
+        /// Sets the scalar type. Defaults to F32.
+        ///
+        /// BF16 and F32 are implemented.
+        pub fn scalar_type(self, scalar_type: ScalarType) -> Self {
+            Self {
+                scalar_type,
+                ..self
+            }
+        }
+        /// Sets the device. Defaults to the host.
+        pub fn device(self, device: Device) -> Self {
+            Self { device, ..self }
+        }
+        /// Builds the layer.
+        ///
+        /// The weight is initialized to 1's and the bias to 0's. The running mean is initialized
+        /// to 0's and the running variance to 1's.
+        ///
+        /// **Errors**
+        /// - The `scalar_type` is not BF16 or F32.
+        /// - `momentum` is not in [0, 1].
+        /// - `eps` is negative.
+        /// - Initializing parameters on the `device` failed.
+        pub fn build(self) -> Result<BatchNorm> {
+            let Self {
+                channels,
+                momentum,
+                eps,
+                track_running_stats,
+                scalar_type,
+                device,
+            } = self;
+            if !matches!(scalar_type, ScalarType::BF16 | ScalarType::F32) {
+                bail!("BatchNorm {scalar_type:?} not implemented!");
+            }
+            if !(0. ..=1.).contains(&momentum) {
+                bail!("BatchNorm momentum {momentum} not in [0, 1]!");
+            }
+            if eps.is_nan() || eps < 0. {
+                bail!("BatchNorm eps {eps} must be non-negative!");
+            }
+            let weight = ScalarTensor::ones(device.clone(), channels, scalar_type)?;
+            let bias = ScalarTensor::zeros(device.clone(), channels, scalar_type)?;
+            let running_mean = Tensor::zeros(device.clone(), channels)?.into_shared()?;
+            let running_var = Tensor::ones(device, channels)?.into_shared()?;
+            Ok(BatchNorm {
+                weight: Parameter::from(weight),
+                bias: Parameter::from(bias),
+                running_stats: RwLock::new(RunningStats {
+                    mean: running_mean,
+                    var: running_var,
+                }),
+                momentum,
+                eps,
+                track_running_stats,
+                training: false,
+            })
+        }
+    }
+
+    /// Builder for creating an [`Affine`].
+    pub struct AffineBuilder {
+        channels: usize,
+        scalar_type: ScalarType,
+        device: Device,
+    }
+
+    impl AffineBuilder {
+        pub(super) fn new() -> Self {
+            Self {
+                channels: 0,
+                scalar_type: ScalarType::F32,
+                device: Device::host(),
+            }
+        }
+        /// Sets the number of channels.
+        pub fn channels(self, channels: usize) -> Self {
+            Self { channels, ..self }
+        }
+        /// Sets the scalar type. Defaults to F32.
+        ///
+        /// BF16 and F32 are implemented.
+        pub fn scalar_type(self, scalar_type: ScalarType) -> Self {
+            Self {
+                scalar_type,
+                ..self
+            }
+        }
+        /// Sets the device. Defaults to the host.
+        pub fn device(self, device: Device) -> Self {
+            Self { device, ..self }
+        }
+        /// Builds the layer.
+        ///
+        /// The scale is initialized to 1's and the shift to 0's, such that the layer is
+        /// initially the identity.
+        ///
+        /// **Errors**
+        /// - The `scalar_type` is not BF16 or F32.
+        /// - Initializing parameters on the `device` failed.
+        pub fn build(self) -> Result<Affine> {
+            let Self {
+                channels,
+                scalar_type,
+                device,
+            } = self;
+            if !matches!(scalar_type, ScalarType::BF16 | ScalarType::F32) {
+                bail!("Affine {scalar_type:?} not implemented!");
+            }
+            let scale = ScalarTensor::ones(device.clone(), channels, scalar_type)?;
+            let shift = ScalarTensor::zeros(device, channels, scalar_type)?;
+            Ok(Affine {
+                scale: Parameter::from(scale),
+                shift: Parameter::from(shift),
+            })
+        }
+    }
+
+    /// Builder for creating a [`MaxPool`].
+    pub struct MaxPoolBuilder<D: Dimension> {
+        filter: D,
+        stride: Option<D>,
+    }
+
+    impl<D: Dimension> MaxPoolBuilder<D> {
+        pub(super) fn new() -> Self {
+            Self {
+                filter: D::default(),
+                stride: None,
+            }
+        }
+        /// Sets the size of the pool filter.
+        pub fn filter(self, filter: impl IntoDimension<Dim = D>) -> Self {
+            Self {
+                filter: filter.into_dimension(),
+                ..self
+            }
+        }
+        /// Sets the stride. Defaults to filter.
+        ///
+        /// The stride may be larger than the filter, skipping the inputs between windows, which
+        /// receive a gradient of 0.
+        pub fn stride(self, stride: impl IntoDimension<Dim = D>) -> Self {
+            Self {
+                stride: Some(stride.into_dimension()),
+                ..self
+            }
+        }
+        /// Builds the layer.
+        pub fn build(self) -> MaxPool<D> {
+            let Self { filter, stride } = self;
+            let stride = stride.unwrap_or(filter.clone());
+            MaxPool { filter, stride }
+        }
+    }
+}
+use builder::*;
+
+/// ParameterVec
+///
+/// See [`Layer::parameters()`](Layer::parameters).
+pub type ParameterVec = SmallVec<[ParameterD; 2]>;
+/// ParameterMutVec
+///
+/// See [`Layer::parameters_mut()`](Layer::parameters_mut).
+pub type ParameterMutVec<'a> = SmallVec<[ParameterViewMutD<'a>; 2]>;
+
+/// NamedParameterVec
+///
+/// See [`Layer::named_parameters()`](Layer::named_parameters).
+pub type NamedParameterVec = Vec<(String, ParameterD)>;
+/// NamedParameterMutVec
+///
+/// See [`Layer::named_parameters_mut()`](Layer::named_parameters_mut).
+pub type NamedParameterMutVec<'a> = Vec<(String, ParameterViewMutD<'a>)>;
+
+/// Layer.
+///
+/// Typically Layers implement [`Forward<Variable<D>>`](Forward) for the appropriate
+/// dimension `D`.
+///
+/// Layers with parameters or those that store the `device` or `scalar_type` should implement the
 /// relevant methods. Functional layers and activations may only need the default implementation.
 ///
 /// Layer can be [derived](autograph_derive) for structs and enums where each field or variant
@@ -433,22 +1051,109 @@ pub trait Layer {
     fn set_training(&mut self, #[allow(unused_variables)] training: bool) -> Result<()> {
         Ok(())
     }
+    /// Prepares for training.
+    ///
+    /// Equivalent to [`.set_training(true)`](Layer::set_training).
+    fn train(&mut self) -> Result<()> {
+        self.set_training(true)
+    }
+    /// Prepares for evaluation.
+    ///
+    /// Equivalent to [`.set_training(false)`](Layer::set_training). Parameters no longer
+    /// track gradients, so the forward pass of an input without a node does not build the
+    /// graph, without wrapping each call in [`no_grad()`].
+    fn eval(&mut self) -> Result<()> {
+        self.set_training(false)
+    }
+    /// Clears the gradients of the parameters.
+    ///
+    /// See [`Parameter::zero_grad()`](Parameter::zero_grad).
+    fn zero_grad(&mut self) -> Result<()> {
+        for mut parameter in self.parameters() {
+            parameter.zero_grad();
+        }
+        Ok(())
+    }
     /// Parameters of the layer.
+    ///
+    /// Layers that create parameters on the first forward, like [`LazyDense`], return no
+    /// parameters until built. Parameters that are tied with
+    /// [`Parameter::share()`](Parameter::share) are returned once.
     fn parameters(&self) -> ParameterVec {
         ParameterVec::new()
     }
     /// Mutable parameter views of the parameters of the layer.
     ///
     /// The mutable parameter views can be provided to [`Optimizer::update()`](Optimizer::update).
+    /// Parameters that are tied with [`Parameter::share()`](Parameter::share) are returned
+    /// once, so that their gradient is applied once. The copies are updated when the view is
+    /// dropped, see [`ParameterViewMut::tie()`](ParameterViewMut::tie).
     ///
     /// See [`Parameter::make_view_mut()`](Parameter::make_view_mut).
     fn parameters_mut(&mut self) -> Result<ParameterMutVec> {
         Ok(ParameterMutVec::new())
     }
+    /// Parameters of the layer with their names.
+    ///
+    /// Names are joined with ".", ie "conv.weight", following PyTorch. Defaults to the index of
+    /// each parameter in [`.parameters()`](Layer::parameters).
+    fn named_parameters(&self) -> NamedParameterVec {
+        self.parameters()
+            .into_iter()
+            .enumerate()
+            .map(|(i, parameter)| (i.to_string(), parameter))
+            .collect()
+    }
+    /// Mutable parameter views of the parameters of the layer with their names.
+    ///
+    /// See [`.named_parameters()`](Layer::named_parameters).
+    fn named_parameters_mut(&mut self) -> Result<NamedParameterMutVec> {
+        Ok(self
+            .parameters_mut()?
+            .into_iter()
+            .enumerate()
+            .map(|(i, parameter)| (i.to_string(), parameter))
+            .collect())
+    }
+    /// Names of the parameters that are stored transposed relative to PyTorch.
+    ///
+    /// ie the weight of [`Dense`] is `[inputs, outputs]`, while the weight of a PyTorch Linear
+    /// is `[outputs, inputs]`. Names match [`.named_parameters()`](Layer::named_parameters).
+    /// Defaults to none.
+    fn transposed_parameter_names(&self) -> Vec<String> {
+        Vec::new()
+    }
     /// Casts the layer to `scalar_type` in place.
+    ///
+    /// See [`.to_dtype()`](Layer::to_dtype).
     fn cast_mut(&mut self, #[allow(unused_variables)] scalar_type: ScalarType) -> Result<()> {
         Ok(())
     }
+    /// Casts the layer into `scalar_type`.
+    ///
+    /// The counterpart to [`.into_device()`](Layer::into_device), ie to export a model trained in
+    /// f32 to bf16 for inference. Running statistics, like those of [`BatchNorm`], are kept in
+    /// f32.
+    ///
+    ///```no_run
+    /// # use autograph::{anyhow::Result, krnl::scalar::ScalarType, learn::neural_network::layer::{Dense, Layer}};
+    /// # fn main() -> Result<()> {
+    /// # let dense: Dense = todo!();
+    /// let dense = dense.to_dtype(ScalarType::BF16)?;
+    /// # Ok(())
+    /// # }
+    ///```
+    ///
+    /// **Errors**
+    /// - The scalar type is not supported by a layer.
+    /// - Casting failed.
+    fn to_dtype(mut self, scalar_type: ScalarType) -> Result<Self>
+    where
+        Self: Sized,
+    {
+        self.cast_mut(scalar_type)?;
+        Ok(self)
+    }
     /// Transfers the layer to `device` in place.
     fn to_device_mut(&mut self, #[allow(unused_variables)] device: Device) -> Result<()> {
         Ok(())
@@ -460,6 +1165,317 @@ pub trait Layer {
     {
         Ok(self)
     }
+    /// The device of the layer.
+    ///
+    /// Inspects the first parameter. Returns `None` if the layer has no parameters, like
+    /// [`Relu`] or a [`LazyDense`] that has not been built.
+    fn device(&self) -> Option<Device> {
+        self.parameters()
+            .first()
+            .map(|parameter| parameter.device())
+    }
+    /// The scalar type of the layer.
+    ///
+    /// Inspects the first parameter. Returns `None` if the layer has no parameters, like
+    /// [`Relu`] or a [`LazyDense`] that has not been built.
+    fn scalar_type(&self) -> Option<ScalarType> {
+        self.parameters()
+            .first()
+            .map(|parameter| parameter.scalar_type())
+    }
+    /// Transfers `input` to the device of the parameters and executes the forward pass.
+    ///
+    /// Layers without parameters use `input` as is.
+    ///
+    /// Note that this hides a transfer on each call, which may be significant when moving data
+    /// from the host to a device. For training it is typically faster to transfer batches ahead
+    /// of time, ie in a separate thread while the model is running.
+    ///
+    /// **Errors**
+    /// - The transfer failed.
+    /// - The forward pass failed.
+    fn forward_auto_device<D>(
+        &self,
+        input: Variable<D>,
+    ) -> Result<<Self as Forward<Variable<D>>>::Output>
+    where
+        Self: Forward<Variable<D>> + Sized,
+        D: Dimension + 'static,
+    {
+        let input = if let Some(device) = Layer::device(self) {
+            input.into_device(device)?
+        } else {
+            input
+        };
+        self.forward(input)
+    }
+    /// Predicts the output for `input` in batches of `batch_size`.
+    ///
+    /// Splits `input` along the first axis, executes the forward pass on each batch within
+    /// [`no_grad()`], and concatenates the outputs. Use
+    /// [`.set_training(false)`](Layer::set_training) first for layers that behave
+    /// differently during training.
+    ///
+    ///```no_run
+    /// # use autograph::{anyhow::Result, tensor::ScalarArcTensor2, learn::neural_network::layer::{Dense, Layer}};
+    /// # fn main() -> Result<()> {
+    /// # let dense: Dense = todo!();
+    /// # let input: ScalarArcTensor2 = todo!();
+    /// let output = dense.predict_batched(input, 256)?;
+    /// # Ok(())
+    /// # }
+    ///```
+    ///
+    /// **Errors**
+    /// - `batch_size` is 0 or `input` is empty.
+    /// - The forward pass failed.
+    /// - The outputs are not batched along the first axis or have different shapes.
+    fn predict_batched<D, D2>(
+        &self,
+        input: ScalarArcTensor<D>,
+        batch_size: usize,
+    ) -> Result<ScalarTensor<D2>>
+    where
+        Self: Forward<Variable<D>, Output = Variable<D2>> + Sized,
+        D: RemoveAxis,
+        D2: RemoveAxis,
+    {
+        let len = input.shape().first().copied().unwrap_or(0);
+        if batch_size == 0 || len == 0 {
+            bail!(
+                "predict_batched batch_size = {batch_size} input shape = {:?} is empty!",
+                input.shape()
+            );
+        }
+        no_grad(|| -> Result<_> {
+            let mut output: Option<ScalarTensor<D2>> = None;
+            for start in (0..len).step_by(batch_size) {
+                let end = (start + batch_size).min(len);
+                let x = input.clone().slice_axis_into(Axis(0), start..end);
+                let y = self.forward(x.into())?.into_value();
+                if output.is_none() {
+                    let mut dim = y.raw_dim();
+                    if dim.ndim() > 0 {
+                        dim[0] = len;
+                    }
+                    output.replace(unsafe {
+                        ScalarTensor::uninit(y.device(), dim, y.scalar_type())?
+                    });
+                }
+                let output = output.as_mut().unwrap();
+                if y.shape().first() != Some(&(end - start))
+                    || y.shape()[1..] != output.shape()[1..]
+                {
+                    bail!(
+                        "predict_batched output shape {:?} is not batched with output shape {:?}!",
+                        y.shape(),
+                        output.shape()
+                    );
+                }
+                output
+                    .view_mut()
+                    .slice_axis_into(Axis(0), start..end)
+                    .assign(&y)?;
+            }
+            Ok(output.unwrap())
+        })
+    }
+    /// Measures the average duration of the forward pass for an input with `input_shape`.
+    ///
+    /// The input is filled with 1's, with the scalar type and device of the layer, or F32 on
+    /// the host for layers without parameters. Executes a warmup pass, which compiles
+    /// kernels, and then `iterations` timed passes within [`no_grad()`], waiting for the
+    /// device to finish each pass.
+    ///
+    /// **Errors**
+    /// - `iterations` is 0.
+    /// - The forward pass failed.
+    #[cfg(not(target_arch = "wasm32"))]
+    fn benchmark_forward<D, E>(
+        &self,
+        input_shape: E,
+        iterations: usize,
+    ) -> Result<std::time::Duration>
+    where
+        Self: Forward<Variable<D>> + Sized,
+        D: Dimension + 'static,
+        E: IntoDimension<Dim = D>,
+    {
+        use std::time::Instant;
+
+        if iterations == 0 {
+            bail!("benchmark_forward iterations must be greater than 0!");
+        }
+        let device = Layer::device(self).unwrap_or(Device::host());
+        let scalar_type = Layer::scalar_type(self).unwrap_or(ScalarType::F32);
+        let input: Variable<D> =
+            ScalarTensor::ones(device.clone(), input_shape.into_dimension(), scalar_type)?
+                .into_shared()?
+                .into();
+        no_grad(|| {
+            self.forward(input.clone())?;
+            device.wait()?;
+            let start = Instant::now();
+            for _ in 0..iterations {
+                self.forward(input.clone())?;
+                device.wait()?;
+            }
+            Ok(start.elapsed() / iterations as u32)
+        })
+    }
+    /// Writes the parameters to `writer`.
+    ///
+    /// Each parameter is written with its name from
+    /// [`.named_parameters()`](Layer::named_parameters), scalar type, shape, and little endian
+    /// data. The writer can be a file or a `Vec<u8>`, ie to store checkpoints in a database or
+    /// send them over the network. Restore with [`.load_from_reader()`](Layer::load_from_reader).
+    ///
+    /// Only parameters are written, not the configuration of the layer.
+    ///
+    ///```no_run
+    /// # use autograph::{anyhow::Result, learn::neural_network::layer::{Dense, Layer}};
+    /// # fn main() -> Result<()> {
+    /// # let dense: Dense = todo!();
+    /// # let mut dense2: Dense = todo!();
+    /// let mut bytes = Vec::new();
+    /// dense.save_to_writer(&mut bytes)?;
+    /// dense2.load_from_reader(bytes.as_slice())?;
+    /// # Ok(())
+    /// # }
+    ///```
+    ///
+    /// **Errors**
+    /// - Copying a parameter to the host failed.
+    /// - Writing failed.
+    fn save_to_writer<W: Write>(&self, mut writer: W) -> Result<()>
+    where
+        Self: Sized,
+    {
+        write_parameters(self.named_parameters(), &mut writer)
+    }
+    /// Reads parameters written by [`.save_to_writer()`](Layer::save_to_writer) from `reader`.
+    ///
+    /// Parameters are matched by name, cast to the scalar type, and transferred to the device of
+    /// each parameter of the layer.
+    ///
+    /// **Errors**
+    /// - Reading failed or the data is invalid.
+    /// - A parameter was not saved.
+    /// - The shape of a parameter does not match.
+    /// - Casting or copying to the device failed.
+    fn load_from_reader<R: Read>(&mut self, mut reader: R) -> Result<()>
+    where
+        Self: Sized,
+    {
+        let mut tensors = read_parameters(&mut reader)?;
+        for (name, mut parameter) in self.named_parameters_mut()? {
+            let Some(tensor) = tensors.remove(&name) else {
+                bail!("load_from_reader parameter {name:?} not found!");
+            };
+            if tensor.shape() != parameter.shape() {
+                bail!(
+                    "load_from_reader parameter {name:?} expected shape {:?}, found {:?}!",
+                    parameter.shape(),
+                    tensor.shape()
+                );
+            }
+            let tensor = tensor
+                .cast_into(parameter.scalar_type())?
+                .into_device(parameter.device())?;
+            parameter.value_view_mut().assign(&tensor)?;
+        }
+        Ok(())
+    }
+}
+
+const PARAMETERS_MAGIC: &[u8; 8] = b"AGPARAMS";
+
+fn write_parameters(parameters: NamedParameterVec, writer: &mut dyn Write) -> Result<()> {
+    fn write_u64(writer: &mut dyn Write, x: u64) -> Result<()> {
+        writer.write_all(&x.to_le_bytes())?;
+        Ok(())
+    }
+    fn write_bytes(writer: &mut dyn Write, bytes: &[u8]) -> Result<()> {
+        write_u64(writer, bytes.len() as u64)?;
+        writer.write_all(bytes)?;
+        Ok(())
+    }
+    writer.write_all(PARAMETERS_MAGIC)?;
+    write_u64(writer, parameters.len() as u64)?;
+    for (name, parameter) in parameters {
+        let value = parameter.value().to_device(Device::host())?;
+        let scalar_type = value.scalar_type();
+        write_bytes(writer, name.as_bytes())?;
+        write_bytes(writer, scalar_type.name().as_bytes())?;
+        write_u64(writer, value.ndim() as u64)?;
+        for dim in value.shape() {
+            write_u64(writer, *dim as u64)?;
+        }
+        macro_for!($T in [u8, i8, u16, i16, f16, bf16, u32, i32, f32, u64, i64, f64] {
+            if scalar_type == $T::scalar_type() {
+                let value = value.view().try_into_tensor_view::<$T>().unwrap();
+                let value = value.as_standard_layout()?;
+                let bytes: Vec<u8> = value
+                    .as_array()
+                    .unwrap()
+                    .iter()
+                    .flat_map(|x| x.to_le_bytes())
+                    .collect();
+                write_bytes(writer, &bytes)?;
+            }
+        });
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+fn read_parameters(reader: &mut dyn Read) -> Result<BTreeMap<String, ScalarTensorD>> {
+    fn read_u64(reader: &mut dyn Read) -> Result<u64> {
+        let mut bytes = [0u8; 8];
+        reader.read_exact(&mut bytes)?;
+        Ok(u64::from_le_bytes(bytes))
+    }
+    fn read_bytes(reader: &mut dyn Read) -> Result<Vec<u8>> {
+        let len = read_u64(reader)?;
+        let mut bytes = Vec::new();
+        (&mut *reader).take(len).read_to_end(&mut bytes)?;
+        if bytes.len() as u64 != len {
+            bail!("load_from_reader unexpected end of data!");
+        }
+        Ok(bytes)
+    }
+    let mut magic = [0u8; 8];
+    reader.read_exact(&mut magic)?;
+    if &magic != PARAMETERS_MAGIC {
+        bail!("load_from_reader invalid data, expected parameters written by save_to_writer!");
+    }
+    let len = read_u64(reader)?;
+    let mut tensors = BTreeMap::new();
+    for _ in 0..len {
+        let name = String::from_utf8(read_bytes(reader)?)?;
+        let scalar_type_name = String::from_utf8(read_bytes(reader)?)?;
+        let ndim = read_u64(reader)?;
+        let shape = (0..ndim)
+            .map(|_| read_u64(reader).map(|x| x as usize))
+            .collect::<Result<Vec<_>>>()?;
+        let bytes = read_bytes(reader)?;
+        let mut tensor: Option<ScalarTensorD> = None;
+        macro_for!($T in [u8, i8, u16, i16, f16, bf16, u32, i32, f32, u64, i64, f64] {
+            if scalar_type_name == $T::scalar_type().name() {
+                let data: Vec<$T> = bytes
+                    .chunks_exact(std::mem::size_of::<$T>())
+                    .map(|x| <$T>::from_le_bytes(x.try_into().unwrap()))
+                    .collect();
+                let array = Array::from_shape_vec(IxDyn(&shape), data).map_err(Error::msg)?;
+                tensor.replace(Tensor::from(array).into());
+            }
+        });
+        let Some(tensor) = tensor else {
+            bail!("load_from_reader {name:?} unknown scalar type {scalar_type_name:?}!");
+        };
+        tensors.insert(name, tensor);
+    }
+    Ok(tensors)
 }
 
 /// Forward.
@@ -490,6 +1506,21 @@ impl<T: Layer> Layer for Option<T> {
             .map(|layer| layer.parameters_mut())
             .unwrap_or(Ok(ParameterMutVec::new()))
     }
+    fn named_parameters(&self) -> NamedParameterVec {
+        self.as_ref()
+            .map(|layer| layer.named_parameters())
+            .unwrap_or_default()
+    }
+    fn named_parameters_mut(&mut self) -> Result<NamedParameterMutVec> {
+        self.as_mut()
+            .map(|layer| layer.named_parameters_mut())
+            .unwrap_or(Ok(NamedParameterMutVec::new()))
+    }
+    fn transposed_parameter_names(&self) -> Vec<String> {
+        self.as_ref()
+            .map(|layer| layer.transposed_parameter_names())
+            .unwrap_or_default()
+    }
     fn cast_mut(&mut self, scalar_type: ScalarType) -> Result<()> {
         if let Some(layer) = self.as_mut() {
             layer.cast_mut(scalar_type)?;
@@ -527,7 +1558,13 @@ impl<T: Layer> Layer for Vec<T> {
             .try_for_each(|layer| layer.set_training(training))
     }
     fn parameters(&self) -> ParameterVec {
-        self.iter().flat_map(Layer::parameters).collect()
+        let mut parameters = ParameterVec::new();
+        for parameter in self.iter().flat_map(Layer::parameters) {
+            if !parameters.iter().any(|x| x.is_shared_with(&parameter)) {
+                parameters.push(parameter);
+            }
+        }
+        parameters
     }
     fn parameters_mut(&mut self) -> Result<ParameterMutVec> {
         if self.is_empty() {
@@ -535,13 +1572,56 @@ impl<T: Layer> Layer for Vec<T> {
         } else if self.len() == 1 {
             self.first_mut().unwrap().parameters_mut()
         } else {
-            let mut parameter_vecs = SmallVec::<[ParameterMutVec; 8]>::with_capacity(self.len());
+            let mut parameters = ParameterMutVec::new();
             for layer in self.iter_mut() {
-                parameter_vecs.push(layer.parameters_mut()?);
+                for parameter in layer.parameters_mut()? {
+                    if let Some(parameter) = parameters
+                        .iter_mut()
+                        .try_fold(parameter, |parameter, x| x.tie(parameter))
+                    {
+                        parameters.push(parameter);
+                    }
+                }
             }
-            Ok(parameter_vecs.into_iter().flatten().collect())
+            Ok(parameters)
         }
     }
+    fn named_parameters(&self) -> NamedParameterVec {
+        let mut parameters = NamedParameterVec::new();
+        for (i, layer) in self.iter().enumerate() {
+            for (name, parameter) in layer.named_parameters() {
+                if !parameters.iter().any(|(_, x)| x.is_shared_with(&parameter)) {
+                    parameters.push((format!("{i}.{name}"), parameter));
+                }
+            }
+        }
+        parameters
+    }
+    fn named_parameters_mut(&mut self) -> Result<NamedParameterMutVec> {
+        let mut parameters = NamedParameterMutVec::new();
+        for (i, layer) in self.iter_mut().enumerate() {
+            for (name, parameter) in layer.named_parameters_mut()? {
+                if let Some(parameter) = parameters
+                    .iter_mut()
+                    .try_fold(parameter, |parameter, (_, x)| x.tie(parameter))
+                {
+                    parameters.push((format!("{i}.{name}"), parameter));
+                }
+            }
+        }
+        Ok(parameters)
+    }
+    fn transposed_parameter_names(&self) -> Vec<String> {
+        self.iter()
+            .enumerate()
+            .flat_map(|(i, layer)| {
+                layer
+                    .transposed_parameter_names()
+                    .into_iter()
+                    .map(move |name| format!("{i}.{name}"))
+            })
+            .collect()
+    }
     fn cast_mut(&mut self, scalar_type: ScalarType) -> Result<()> {
         self.iter_mut()
             .try_for_each(|layer| layer.cast_mut(scalar_type))
@@ -569,6 +1649,76 @@ impl<X, T: Forward<X, Output = X>> Forward<X> for Vec<T> {
     }
 }
 
+/// A layer that can be boxed, ie in a [`Vec`] of different layers.
+///
+/// [`Layer`] is object safe, except for the methods that require `Self: Sized`. DynLayer
+/// combines it with [`Forward`], so that `Box<dyn DynLayer<X>>` implements both.
+///
+///```no_run
+/// # use autograph::{anyhow::Result, learn::neural_network::{autograd::Variable2, layer::{Dense, DynLayer, Relu}}};
+/// # fn main() -> Result<()> {
+/// let model: Vec<Box<dyn DynLayer<Variable2>>> = vec![
+///     Box::new(Dense::builder().inputs(8).outputs(16).build()?),
+///     Box::new(Relu),
+///     Box::new(Dense::builder().inputs(16).outputs(4).build()?),
+/// ];
+/// # Ok(())
+/// # }
+///```
+pub trait DynLayer<X>: Layer + Forward<X, Output = X> {}
+
+impl<X, T: Layer + Forward<X, Output = X> + ?Sized> DynLayer<X> for T {}
+
+impl<T: Layer + ?Sized> Layer for Box<T> {
+    fn set_training(&mut self, training: bool) -> Result<()> {
+        (**self).set_training(training)
+    }
+    fn zero_grad(&mut self) -> Result<()> {
+        (**self).zero_grad()
+    }
+    fn parameters(&self) -> ParameterVec {
+        (**self).parameters()
+    }
+    fn parameters_mut(&mut self) -> Result<ParameterMutVec> {
+        (**self).parameters_mut()
+    }
+    fn named_parameters(&self) -> NamedParameterVec {
+        (**self).named_parameters()
+    }
+    fn named_parameters_mut(&mut self) -> Result<NamedParameterMutVec> {
+        (**self).named_parameters_mut()
+    }
+    fn transposed_parameter_names(&self) -> Vec<String> {
+        (**self).transposed_parameter_names()
+    }
+    fn cast_mut(&mut self, scalar_type: ScalarType) -> Result<()> {
+        (**self).cast_mut(scalar_type)
+    }
+    fn to_device_mut(&mut self, device: Device) -> Result<()> {
+        (**self).to_device_mut(device)
+    }
+    fn into_device(mut self, device: Device) -> Result<Self>
+    where
+        Self: Sized,
+    {
+        self.to_device_mut(device)?;
+        Ok(self)
+    }
+    fn device(&self) -> Option<Device> {
+        (**self).device()
+    }
+    fn scalar_type(&self) -> Option<ScalarType> {
+        (**self).scalar_type()
+    }
+}
+
+impl<X, T: Forward<X> + ?Sized> Forward<X> for Box<T> {
+    type Output = T::Output;
+    fn forward(&self, input: X) -> Result<Self::Output> {
+        (**self).forward(input)
+    }
+}
+
 /// Convolutional layer.
 ///
 /// See [`Conv1`] and [`Conv2`].
@@ -592,7 +1742,7 @@ impl<X, T: Forward<X, Output = X>> Forward<X> for Vec<T> {
 /// # Ok(())
 /// # }
 ///```
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 #[serde(bound(
     serialize = "D: Serialize, <D::Larger as Dimension>::Larger: Serialize, A: Serialize",
     deserialize = "D: Deserialize<'de>, <D::Larger as Dimension>::Larger: Deserialize<'de>, A: Deserialize<'de>",
@@ -600,12 +1750,82 @@ impl<X, T: Forward<X, Output = X>> Forward<X> for Vec<T> {
 pub struct Conv<D: Dimension, A = Identity> {
     weight: Parameter<<D::Larger as Dimension>::Larger>,
     padding: D,
+    #[serde(default)]
+    padding_after: Option<D>,
     stride: D,
     dilation: D,
     bias: Option<Parameter1>,
+    #[serde(default)]
+    weight_standardization: bool,
+    #[serde(default)]
+    memory_format: MemoryFormat,
+    #[serde(skip)]
+    compute_scalar_type: Option<ScalarType>,
+    #[serde(skip)]
+    algorithm: ConvAlgo,
+    #[serde(skip)]
+    auto_algorithm: OnceCell<ConvAlgo>,
+    #[serde(skip)]
+    deterministic: bool,
+    #[serde(skip)]
+    tile_size: Option<usize>,
     activation: A,
 }
 
+/// Initialization of the bias of [`Conv`] and [`Dense`].
+///
+/// See [`ConvBuilder::bias_init()`](builder::ConvBuilder::bias_init) and
+/// [`DenseBuilder::bias_init()`](builder::DenseBuilder::bias_init).
+#[derive(Default, Clone, Copy, Debug, PartialEq)]
+pub enum BiasInit {
+    /// Sampled from the same uniform distribution as the weight.
+    #[default]
+    Uniform,
+    /// 0's.
+    Zeros,
+    /// Filled with a constant.
+    Constant(f32),
+}
+
+/// Memory format of the input and output of [`Conv`].
+///
+/// See [`ConvBuilder::memory_format()`](builder::ConvBuilder::memory_format).
+#[derive(Default, Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum MemoryFormat {
+    /// Channels before the spatial dimensions, ie `[batch_size, channels, h, w]` (NCHW).
+    #[default]
+    ChannelsFirst,
+    /// Channels after the spatial dimensions, ie `[batch_size, h, w, channels]` (NHWC).
+    ///
+    /// The output of the im2col matrix multiplication is channels last, so this avoids
+    /// permuting it into channels first.
+    ChannelsLast,
+}
+
+/// Convolution algorithm.
+///
+/// See [`Conv::set_algorithm()`].
+#[derive(Default, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ConvAlgo {
+    /// Benchmarks the other algorithms on the first forward and uses the fastest.
+    ///
+    /// The selection depends on timing, see [`Conv::set_deterministic()`].
+    Auto,
+    /// Unfolds the input into a matrix and computes the output with a matrix multiplication.
+    ///
+    /// The matrix has shape `[batch_size * output_size, inputs * filter_size]`, which can be
+    /// large for big images. When not training, the output is computed with
+    /// [`.conv2d()`](ScalarTensorBase::conv2d), which also uses im2col, unless the padding is
+    /// asymmetric.
+    #[default]
+    Im2Col,
+    /// Computes each output element directly from the input.
+    ///
+    /// Avoids the im2col matrix in the forward pass, but may be slower. The backward pass
+    /// computes the weight gradient with im2col.
+    Direct,
+}
+
 /// Convolutional layer with 1 dimension.
 ///
 /// See [`Conv`].
@@ -623,6 +1843,25 @@ impl<D: Dimension> Conv<D> {
 }
 
 impl<D: Dimension, A> Conv<D, A> {
+    /// The output shape given the `input_shape`.
+    ///
+    /// The shapes exclude the batch and channel dimensions, ie `[ih, iw]` -> `[oh, ow]`
+    /// for [`Conv2`].
+    ///
+    /// **Errors**
+    /// - The (padded) input is smaller than the (dilated) filter.
+    pub fn output_shape(&self, input_shape: impl IntoDimension<Dim = D>) -> Result<D> {
+        LayerSpec::from(self).output_shape(input_shape)
+    }
+    /// The padding after the end of each spatial axis.
+    ///
+    /// The same as the padding before, unless built with
+    /// [`ConvBuilder::asymmetric_padding()`](builder::ConvBuilder::asymmetric_padding).
+    pub fn padding_after(&self) -> D {
+        self.padding_after
+            .clone()
+            .unwrap_or_else(|| self.padding.clone())
+    }
     /// The weight as a mutable parameter view.
     pub fn weight_view_mut(
         &mut self,
@@ -633,6 +1872,113 @@ impl<D: Dimension, A> Conv<D, A> {
     pub fn bias_view_mut(&mut self) -> Result<Option<ParameterViewMut1>> {
         self.bias.as_mut().map(Parameter::make_view_mut).transpose()
     }
+    /// The memory format of the input and output.
+    pub fn memory_format(&self) -> MemoryFormat {
+        self.memory_format
+    }
+    /// The scalar type of the forward pass.
+    ///
+    /// See [`ConvBuilder::compute_scalar_type()`](builder::ConvBuilder::compute_scalar_type).
+    pub fn compute_scalar_type(&self) -> Option<ScalarType> {
+        self.compute_scalar_type
+    }
+    /// The convolution algorithm. Defaults to [`ConvAlgo::Im2Col`].
+    pub fn algorithm(&self) -> ConvAlgo {
+        self.algorithm
+    }
+    /// Sets the convolution algorithm.
+    ///
+    /// All algorithms compute the same output, up to rounding. With [`ConvAlgo::Auto`], the
+    /// algorithm is selected on the next forward and reused for subsequent inputs.
+    pub fn set_algorithm(&mut self, algorithm: ConvAlgo) {
+        self.algorithm = algorithm;
+        self.auto_algorithm = OnceCell::new();
+    }
+    /// Whether the convolution is deterministic.
+    pub fn deterministic(&self) -> bool {
+        self.deterministic
+    }
+    /// Makes the convolution deterministic.
+    ///
+    /// By default, [`ConvAlgo::Auto`] selects the algorithm by timing the first forward, so
+    /// different layers or runs may select different algorithms, whose outputs differ slightly
+    /// due to rounding. When enabled, `Auto` uses [`ConvAlgo::Im2Col`] instead, so the same inputs
+    /// on the same device produce bit identical outputs and gradients. This may be slower than
+    /// the algorithm that would have been selected. Defaults to false.
+    pub fn set_deterministic(&mut self, deterministic: bool) {
+        self.deterministic = deterministic;
+    }
+    /// The maximum number of output elements per channel computed at once.
+    pub fn tile_size(&self) -> Option<usize> {
+        self.tile_size
+    }
+    /// Limits the number of output elements per channel computed at once.
+    ///
+    /// With [`ConvAlgo::Im2Col`], the forward and backward passes are computed in tiles of whole
+    /// images or output rows of at most `tile_size` elements, such that the im2col matrix has at
+    /// most `tile_size` rows. This caps peak memory usage for large inputs, but may be slower.
+    /// A tile has at least 1 output row. Defaults to None, which computes all outputs at once.
+    ///
+    /// The outputs are the same with or without tiling.
+    pub fn set_tile_size(&mut self, tile_size: Option<usize>) {
+        self.tile_size = tile_size;
+    }
+    /// Whether the weight is standardized on each forward.
+    pub fn weight_standardization(&self) -> bool {
+        self.weight_standardization
+    }
+    /// Standardizes the weight on each forward.
+    ///
+    /// The weight of each output channel is normalized to zero mean and unit variance before it
+    /// is applied, and the gradient is propagated through the normalization to the weight. The
+    /// stored weight is not modified. Often combined with group normalization for training with
+    /// small batches.
+    pub fn set_weight_standardization(&mut self, weight_standardization: bool) {
+        self.weight_standardization = weight_standardization;
+    }
+    // The weight, standardized if enabled.
+    fn weight_variable(&self) -> Result<Variable<<D::Larger as Dimension>::Larger>>
+    where
+        <D::Larger as Dimension>::Larger: 'static,
+    {
+        let weight = self.weight.to_variable();
+        if self.weight_standardization {
+            standardize_weight(weight)
+        } else {
+            Ok(weight)
+        }
+    }
+    /// Sets the weight to `weight`.
+    ///
+    /// The weight has shape `[outputs, inputs, ..filter]`, and is cast to the scalar type and
+    /// transferred to the device of the layer.
+    ///
+    /// **Errors**
+    /// - The shape of `weight` is not the shape of the weight.
+    /// - The weight could not be cast or transferred to the device.
+    pub fn set_weight<T: Scalar, S: ArrayData<Elem = T>>(
+        &mut self,
+        weight: &ArrayBase<S, <D::Larger as Dimension>::Larger>,
+    ) -> Result<()> {
+        set_parameter(&mut self.weight, weight, "Conv weight")
+    }
+    /// Sets the bias to `bias`.
+    ///
+    /// See [`.set_weight()`](Conv::set_weight).
+    ///
+    /// **Errors**
+    /// - The layer does not have a bias.
+    /// - The shape of `bias` is not `[outputs]`.
+    /// - The bias could not be cast or transferred to the device.
+    pub fn set_bias<T: Scalar, S: ArrayData<Elem = T>>(
+        &mut self,
+        bias: &ArrayBase<S, Ix1>,
+    ) -> Result<()> {
+        let Some(parameter) = self.bias.as_mut() else {
+            bail!("Conv does not have a bias!");
+        };
+        set_parameter(parameter, bias, "Conv bias")
+    }
 }
 
 impl<D: Dimension, A> Layer for Conv<D, A> {
@@ -659,6 +2005,28 @@ impl<D: Dimension, A> Layer for Conv<D, A> {
         }
         Ok(parameters)
     }
+    fn named_parameters(&self) -> NamedParameterVec {
+        let mut parameters = vec![("weight".into(), self.weight.clone().into_dyn())];
+        if let Some(bias) = self.bias.as_ref() {
+            parameters.push(("bias".into(), bias.clone().into_dyn()));
+        }
+        parameters
+    }
+    fn named_parameters_mut(&mut self) -> Result<NamedParameterMutVec> {
+        let mut parameters = vec![("weight".into(), self.weight.make_view_mut()?.into_dyn())];
+        if let Some(bias) = self.bias.as_mut() {
+            parameters.push(("bias".into(), bias.make_view_mut()?.into_dyn()));
+        }
+        Ok(parameters)
+    }
+    fn cast_mut(&mut self, scalar_type: ScalarType) -> Result<()> {
+        check_cast_mut(scalar_type)?;
+        self.weight.cast_mut(scalar_type)?;
+        if let Some(bias) = self.bias.as_mut() {
+            bias.cast_mut(scalar_type)?;
+        }
+        Ok(())
+    }
     fn to_device_mut(&mut self, device: Device) -> Result<()> {
         self.weight.to_device_mut(device.clone())?;
         if let Some(bias) = self.bias.as_mut() {
@@ -678,31 +2046,89 @@ impl<D: Dimension, A> Layer for Conv<D, A> {
     }
 }
 
-struct ConvOptions<D: Dimension> {
-    padding: D,
-    stride: D,
-    dilation: D,
+pub(super) struct ConvOptions<D: Dimension> {
+    pub(super) padding: D,
+    pub(super) padding_after: D,
+    pub(super) stride: D,
+    pub(super) dilation: D,
 }
 
-fn conv2(
+pub(super) fn conv2(
     input: Variable4,
     weight: Variable4,
     options: ConvOptions<Ix2>,
     bias: Option<Variable1>,
+    algorithm: ConvAlgo,
+    auto_algorithm: &OnceCell<ConvAlgo>,
+    deterministic: bool,
+    tile_size: Option<usize>,
+    memory_format: MemoryFormat,
 ) -> Result<Variable4> {
+    if input.device() != weight.device() {
+        bail!(AutographError::device_mismatch(
+            "Conv input",
+            weight.device(),
+            input.device()
+        ));
+    }
+    let channels_last = memory_format == MemoryFormat::ChannelsLast;
+    let input = if channels_last {
+        input.permuted_axes([0, 3, 1, 2])
+    } else {
+        input
+    };
+    let into_memory_format = |output: Variable4| {
+        if channels_last {
+            output.permuted_axes([0, 2, 3, 1])
+        } else {
+            output
+        }
+    };
     let (batch_size, inputs, ih, iw) = input.dim();
     let (outputs, inputs2, fh, fw) = weight.dim();
     debug_assert_eq!(inputs, inputs2);
     let (ph, pw) = options.padding.into_pattern();
+    let (pha, pwa) = options.padding_after.into_pattern();
     let (sh, sw) = options.stride.into_pattern();
     let (dh, dw) = options.dilation.into_pattern();
     let options = Im2ColConv2Options {
         filter: [fh, fw],
-        padding: [ph, pw],
+        padding: [(ph, pha), (pw, pwa)],
         stride: [sh, sw],
         dilation: [dh, dw],
     };
     let [oh, ow] = options.output_shape([ih, iw]);
+    let algorithm = if algorithm == ConvAlgo::Auto && deterministic {
+        ConvAlgo::Im2Col
+    } else if algorithm == ConvAlgo::Auto {
+        *auto_algorithm
+            .get_or_try_init(|| conv2_fastest_algorithm(input.value(), weight.value(), &options))?
+    } else {
+        algorithm
+    };
+    if algorithm == ConvAlgo::Direct {
+        return conv2_direct(input, weight, options, bias).map(into_memory_format);
+    }
+    if let Some(tile_size) = tile_size.filter(|x| *x < batch_size * oh * ow) {
+        return conv2_tiled(input, weight, options, bias, tile_size).map(into_memory_format);
+    }
+    // Im2Col without the intermediate variables for the backward pass.
+    if input.node().is_none()
+        && weight.node().is_none()
+        && bias.as_ref().map_or(true, |bias| bias.node().is_none())
+        && [ph, pw] == [pha, pwa]
+    {
+        let output = input.value().conv2d(
+            weight.value(),
+            bias.as_ref().map(Variable::value),
+            &Conv2dOptions {
+                padding: [ph, pw],
+                stride: options.stride,
+                dilation: options.dilation,
+            },
+        )?;
+        return Ok(into_memory_format(output.into_shared()?.into()));
+    }
     let im2col_matrix = input.value().im2col_conv2(&options)?;
     let weight_matrix = weight
         .value()
@@ -712,20 +2138,17 @@ fn conv2(
     let output_matrix = im2col_matrix.dot(&weight_matrix.t())?;
     let mut builder = Variable::builder();
     if let Some(node) = input.node() {
+        let options = options.clone();
         builder.edge(node, move |output_grad| {
-            let options = Col2ImConv2Options {
-                shape: [oh, ow],
-                filter: [fh, fw],
-                ..Col2ImConv2Options::default()
-            };
-            output_grad
-                .dot(&weight_matrix)?
-                .col2im_conv2(&options)
-                .map(Into::into)
+            col2im_input_grad(
+                output_grad.dot(&weight_matrix)?,
+                [batch_size, inputs, ih, iw],
+                &options,
+            )
         });
     }
     if let Some(node) = weight.node() {
-        builder.edge(node, move |output_grad| {
+        builder.edge(node, move |output_grad: ScalarArcTensor2| {
             let weight_grad = output_grad
                 .t()
                 .dot(&im2col_matrix)?
@@ -735,6 +2158,15 @@ fn conv2(
         });
     }
     let output_matrix = builder.build(output_matrix.into());
+    if channels_last {
+        let mut output = output_matrix
+            .into_shape([batch_size, oh, ow, outputs])
+            .map_err(Error::msg)?;
+        if let Some(bias) = bias {
+            output.add_assign(bias)?;
+        }
+        return Ok(output);
+    }
     let mut builder = Variable::builder();
     if let Some(node) = output_matrix.node() {
         builder.edge(node, move |output_grad| {
@@ -755,151 +2187,1956 @@ fn conv2(
         .to_owned()?;
     let mut output = builder.build(output.into());
     if let Some(bias) = bias {
-        output.add_assign(&bias)?;
+        output.add_assign(bias.into_shape([outputs, 1, 1]).map_err(Error::msg)?)?;
+    }
+    Ok(output)
+}
+
+/// Folds the gradient of the im2col matrix into the gradient of the input.
+fn col2im_input_grad(
+    matrix: ScalarTensor2,
+    input_shape: [usize; 4],
+    options: &Im2ColConv2Options,
+) -> Result<ScalarArcTensor4> {
+    let [_, _, ih, iw] = input_shape;
+    let [oh, ow] = options.output_shape([ih, iw]);
+    let Im2ColConv2Options {
+        filter,
+        padding: [(ph, _), (pw, _)],
+        stride,
+        dilation,
+    } = options.clone();
+    // Elements past the last window do not have a gradient, so the after padding is reduced
+    // such that the output is not larger than the input.
+    let mut padding = [(ph, 0), (pw, 0)];
+    for ((pad, (x, o)), (f, (s, d))) in padding
+        .iter_mut()
+        .zip([ih, iw].into_iter().zip([oh, ow]))
+        .zip(filter.into_iter().zip(stride.into_iter().zip(dilation)))
+    {
+        let extent = (o - 1) * s + d * (f - 1) + 1;
+        pad.1 = extent.saturating_sub(pad.0 + x);
+    }
+    let col2im_options = Col2ImConv2Options {
+        shape: [oh, ow],
+        filter,
+        padding,
+        stride,
+        dilation,
+    };
+    let grad = matrix.col2im_conv2(&col2im_options)?;
+    let (_, _, h, w) = grad.dim();
+    if [h, w] == [ih, iw] {
+        return grad.into_shared();
+    }
+    let mut input_grad = ScalarTensor::zeros(grad.device(), input_shape, grad.scalar_type())?;
+    input_grad.slice_mut(s![.., .., ..h, ..w]).assign(&grad)?;
+    input_grad.into_shared()
+}
+
+fn conv2_direct(
+    input: Variable4,
+    weight: Variable4,
+    options: Im2ColConv2Options,
+    bias: Option<Variable1>,
+) -> Result<Variable4> {
+    let (batch_size, inputs, ih, iw) = input.dim();
+    let (outputs, _inputs, fh, fw) = weight.dim();
+    let [oh, ow] = options.output_shape([ih, iw]);
+    let output = input.value().conv2_direct(weight.value(), &options)?;
+    let output_grad_matrix = move |output_grad: ScalarArcTensor4| -> Result<_> {
+        Ok(output_grad
+            .permuted_axes([0, 2, 3, 1])
+            .into_owned()?
+            .into_shape([batch_size * oh * ow, outputs])
+            .unwrap())
+    };
+    let mut builder = Variable::builder();
+    if let Some(node) = input.node() {
+        let weight_matrix = weight
+            .value()
+            .clone()
+            .into_shape([outputs, inputs * fh * fw])
+            .unwrap();
+        let options = options.clone();
+        builder.edge(node, move |output_grad| {
+            col2im_input_grad(
+                output_grad_matrix(output_grad)?.dot(&weight_matrix)?,
+                [batch_size, inputs, ih, iw],
+                &options,
+            )
+        });
+    }
+    if let Some(node) = weight.node() {
+        let input = input.value().clone();
+        builder.edge(node, move |output_grad| {
+            let im2col_matrix = input.im2col_conv2(&options)?;
+            let weight_grad = output_grad_matrix(output_grad)?
+                .t()
+                .dot(&im2col_matrix)?
+                .into_shape([outputs, inputs, fh, fw])
+                .unwrap();
+            Ok(weight_grad.into())
+        });
+    }
+    let mut output = builder.build(output.into());
+    if let Some(bias) = bias {
+        output.add_assign(bias.into_shape([outputs, 1, 1]).map_err(Error::msg)?)?;
+    }
+    Ok(output)
+}
+
+fn conv2_tiled(
+    input: Variable4,
+    weight: Variable4,
+    options: Im2ColConv2Options,
+    bias: Option<Variable1>,
+    tile_size: usize,
+) -> Result<Variable4> {
+    let (batch_size, inputs, ih, iw) = input.dim();
+    let (outputs, _inputs, fh, fw) = weight.dim();
+    let [oh, ow] = options.output_shape([ih, iw]);
+    let Im2ColConv2Options {
+        padding: [(ph, _), (pw, _)],
+        stride: [sh, sw],
+        dilation: [dh, dw],
+        ..
+    } = options;
+    // tiles are padded before im2col
+    let tile_options = Im2ColConv2Options {
+        padding: [(0, 0), (0, 0)],
+        ..options.clone()
+    };
+    let images_per_tile = tile_size / (oh * ow);
+    let tiles: Vec<(Range<usize>, Range<usize>)> = if images_per_tile > 0 {
+        (0..batch_size)
+            .step_by(images_per_tile)
+            .map(|b| (b..batch_size.min(b + images_per_tile), 0..oh))
+            .collect()
+    } else {
+        let rows_per_tile = (tile_size / ow).max(1);
+        (0..batch_size)
+            .flat_map(|b| {
+                (0..oh)
+                    .step_by(rows_per_tile)
+                    .map(move |r| (b..b + 1, r..oh.min(r + rows_per_tile)))
+            })
+            .collect()
+    };
+    let weight_matrix = weight
+        .value()
+        .clone()
+        .into_shape([outputs, inputs * fh * fw])
+        .unwrap();
+    let mut output = unsafe {
+        ScalarTensor::uninit(
+            input.device(),
+            [batch_size, outputs, oh, ow],
+            input.scalar_type(),
+        )?
+    };
+    for (batch, rows) in tiles.iter().cloned() {
+        let tile = conv2_tile_input(input.value(), batch.clone(), rows.clone(), &options)?;
+        let output_tile = tile
+            .im2col_conv2(&tile_options)?
+            .dot(&weight_matrix.t())?
+            .into_shape([batch.len(), rows.len(), ow, outputs])
+            .unwrap()
+            .permuted_axes([0, 3, 1, 2]);
+        output
+            .slice_mut(s![batch, .., rows, ..])
+            .assign(&output_tile)?;
+    }
+    let output_grad_tile = move |output_grad: &ScalarArcTensor4,
+                                 batch: Range<usize>,
+                                 rows: Range<usize>|
+          -> Result<ScalarTensor2> {
+        let len = batch.len() * rows.len() * ow;
+        Ok(output_grad
+            .slice(s![batch, .., rows, ..])
+            .permuted_axes([0, 2, 3, 1])
+            .into_standard_layout()?
+            .into_shape([len, outputs])
+            .unwrap())
+    };
+    let mut builder = Variable::builder();
+    if let Some(node) = input.node() {
+        let tiles = tiles.clone();
+        let weight_matrix = weight_matrix.clone();
+        builder.edge(node, move |output_grad| {
+            let height = ((oh - 1) * sh + dh * (fh - 1) + 1).max(ph + ih);
+            let width = ((ow - 1) * sw + dw * (fw - 1) + 1).max(pw + iw);
+            let mut input_grad = ScalarTensor::zeros(
+                output_grad.device(),
+                [batch_size, inputs, height, width],
+                output_grad.scalar_type(),
+            )?;
+            for (batch, rows) in tiles {
+                let col2im_options = Col2ImConv2Options {
+                    shape: [rows.len(), ow],
+                    filter: [fh, fw],
+                    padding: [(0, 0), (0, 0)],
+                    stride: [sh, sw],
+                    dilation: [dh, dw],
+                };
+                let input_grad_tile = output_grad_tile(&output_grad, batch.clone(), rows.clone())?
+                    .dot(&weight_matrix)?
+                    .col2im_conv2(&col2im_options)?;
+                let (_, _, h, w) = input_grad_tile.dim();
+                let start = rows.start * sh;
+                input_grad
+                    .slice_mut(s![batch, .., start..start + h, ..w])
+                    .add_assign(&input_grad_tile)?;
+            }
+            input_grad
+                .slice(s![.., .., ph..ph + ih, pw..pw + iw])
+                .into_standard_layout()?
+                .into_shared()
+        });
+    }
+    if let Some(node) = weight.node() {
+        let input = input.value().clone();
+        builder.edge(node, move |output_grad| {
+            let mut weight_grad = ScalarTensor::zeros(
+                output_grad.device(),
+                [outputs, inputs * fh * fw],
+                output_grad.scalar_type(),
+            )?;
+            for (batch, rows) in tiles {
+                let im2col_matrix =
+                    conv2_tile_input(&input, batch.clone(), rows.clone(), &options)?
+                        .im2col_conv2(&tile_options)?;
+                weight_grad.add_assign(
+                    &output_grad_tile(&output_grad, batch, rows)?
+                        .t()
+                        .dot(&im2col_matrix)?,
+                )?;
+            }
+            Ok(weight_grad
+                .into_shape([outputs, inputs, fh, fw])
+                .unwrap()
+                .into())
+        });
+    }
+    let mut output = builder.build(output.into());
+    if let Some(bias) = bias {
+        output.add_assign(bias.into_shape([outputs, 1, 1]).map_err(Error::msg)?)?;
     }
     Ok(output)
 }
 
+/// The input for output images `batch` and rows `rows`, padded.
+fn conv2_tile_input<'a>(
+    input: &'a ScalarArcTensor4,
+    batch: Range<usize>,
+    rows: Range<usize>,
+    options: &Im2ColConv2Options,
+) -> Result<ScalarCowTensor4<'a>> {
+    let (_, inputs, ih, iw) = input.dim();
+    let [oh, ow] = options.output_shape([ih, iw]);
+    let Im2ColConv2Options {
+        filter: [fh, fw],
+        padding: [(ph, _), (pw, _)],
+        stride: [sh, sw],
+        dilation: [dh, dw],
+    } = options.clone();
+    debug_assert!(rows.end <= oh);
+    let height = (rows.len() - 1) * sh + dh * (fh - 1) + 1;
+    let width = (ow - 1) * sw + dw * (fw - 1) + 1;
+    // in padded coordinates
+    let start = rows.start * sh;
+    let end = start + height;
+    if ph <= start && end <= ph + ih && pw == 0 {
+        return Ok(input
+            .slice(s![batch, .., start - ph..end - ph, ..width])
+            .into());
+    }
+    let mut tile = ScalarTensor::zeros(
+        input.device(),
+        [batch.len(), inputs, height, width],
+        input.scalar_type(),
+    )?;
+    let row_begin = start.max(ph);
+    let row_end = end.min(ph + ih);
+    let col_end = width.min(pw + iw);
+    if row_begin < row_end && pw < col_end {
+        tile.slice_mut(s![.., .., row_begin - start..row_end - start, pw..col_end])
+            .assign(&input.slice(s![batch, .., row_begin - ph..row_end - ph, ..col_end - pw]))?;
+    }
+    Ok(tile.into())
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn conv2_fastest_algorithm(
+    input: &ScalarArcTensor4,
+    weight: &ScalarArcTensor4,
+    options: &Im2ColConv2Options,
+) -> Result<ConvAlgo> {
+    use std::time::{Duration, Instant};
+
+    let device = input.device();
+    let outputs = weight.shape()[0];
+    let weight_matrix = weight
+        .view()
+        .into_shape([outputs, weight.len() / outputs])
+        .unwrap();
+    let mut fastest = None;
+    for algorithm in [ConvAlgo::Im2Col, ConvAlgo::Direct] {
+        let mut duration = Duration::default();
+        // the first iteration compiles kernels
+        for i in 0..2 {
+            let start = Instant::now();
+            match algorithm {
+                ConvAlgo::Im2Col => {
+                    input.im2col_conv2(options)?.dot(&weight_matrix.t())?;
+                }
+                ConvAlgo::Direct => {
+                    input.conv2_direct(weight, options)?;
+                }
+                ConvAlgo::Auto => unreachable!(),
+            }
+            device.wait()?;
+            if i > 0 {
+                duration = start.elapsed();
+            }
+        }
+        if fastest.map_or(true, |(_, fastest)| duration < fastest) {
+            fastest.replace((algorithm, duration));
+        }
+    }
+    Ok(fastest.unwrap().0)
+}
+
+// time is not available
+#[cfg(target_arch = "wasm32")]
+fn conv2_fastest_algorithm(
+    _input: &ScalarArcTensor4,
+    _weight: &ScalarArcTensor4,
+    _options: &Im2ColConv2Options,
+) -> Result<ConvAlgo> {
+    Ok(ConvAlgo::Im2Col)
+}
+
+// Layers with parameters are implemented for bf16 and f32.
+fn check_cast_mut(scalar_type: ScalarType) -> Result<()> {
+    if !matches!(scalar_type, ScalarType::BF16 | ScalarType::F32) {
+        bail!(AutographError::unsupported_scalar_type(
+            "cast_mut",
+            scalar_type
+        ));
+    }
+    Ok(())
+}
+
+// Casts the input and parameters to the compute scalar type of the layer, if any.
+fn compute_cast<D1: Dimension + 'static, D2: Dimension + 'static>(
+    scalar_type: Option<ScalarType>,
+    input: Variable<D1>,
+    weight: Variable<D2>,
+    bias: Option<Variable1>,
+) -> Result<(Variable<D1>, Variable<D2>, Option<Variable1>)> {
+    let Some(scalar_type) = scalar_type else {
+        return Ok((input, weight, bias));
+    };
+    Ok((
+        input.cast(scalar_type)?,
+        weight.cast(scalar_type)?,
+        bias.map(|bias| bias.cast(scalar_type)).transpose()?,
+    ))
+}
+
 impl<A: Forward<Variable3, Output = Variable3>> Forward<Variable3> for Conv1<A> {
     type Output = Variable3;
     fn forward(&self, input: Variable3) -> Result<Variable3> {
-        let (n, ic, ih) = input.dim();
-        let input = input.into_shape([n, ic, ih, 1]).map_err(Error::msg)?;
-        let (outputs, inputs, fh) = self.weight.dim();
-        let weight = self
-            .weight
-            .to_variable()
+        self.forward_weight(input, self.weight_variable()?)
+    }
+}
+
+impl<A: Forward<Variable3, Output = Variable3>> Conv1<A> {
+    fn forward_weight(&self, input: Variable3, weight: Variable3) -> Result<Variable3> {
+        let channels_last = self.memory_format == MemoryFormat::ChannelsLast;
+        let input = if channels_last {
+            let (n, ih, ic) = input.dim();
+            input.into_shape([n, ih, 1, ic])
+        } else {
+            let (n, ic, ih) = input.dim();
+            input.into_shape([n, ic, ih, 1])
+        }
+        .map_err(Error::msg)?;
+        let (outputs, inputs, fh) = weight.dim();
+        let weight = weight
             .into_shape([outputs, inputs, fh, 1])
             .map_err(Error::msg)?;
         let ph = self.padding.into_pattern();
+        let pha = self.padding_after().into_pattern();
         let sh = self.stride.into_pattern();
         let dh = self.dilation.into_pattern();
         let options = ConvOptions {
-            padding: [ph, 1].into_dimension(),
+            padding: [ph, 0].into_dimension(),
+            padding_after: [pha, 0].into_dimension(),
             stride: [sh, 1].into_dimension(),
             dilation: [dh, 1].into_dimension(),
         };
         let bias = self.bias.as_ref().map(Parameter::to_variable);
-        let output = conv2(input, weight, options, bias)?;
-        let (n2, oc, oh, ow) = output.dim();
-        debug_assert_eq!(n, n2);
-        debug_assert_eq!(ow, 1);
-        let output = output.into_shape([n, oc, oh]).map_err(Error::msg)?;
+        let (input, weight, bias) = compute_cast(self.compute_scalar_type, input, weight, bias)?;
+        let output = conv2(
+            input,
+            weight,
+            options,
+            bias,
+            self.algorithm,
+            &self.auto_algorithm,
+            self.deterministic,
+            self.tile_size,
+            self.memory_format,
+        )?;
+        let output = if channels_last {
+            let (n, oh, ow, oc) = output.dim();
+            debug_assert_eq!(ow, 1);
+            output.into_shape([n, oh, oc])
+        } else {
+            let (n, oc, oh, ow) = output.dim();
+            debug_assert_eq!(ow, 1);
+            output.into_shape([n, oc, oh])
+        }
+        .map_err(Error::msg)?;
         self.activation.forward(output)
     }
 }
 
-impl<A: Forward<Variable4, Output = Variable4>> Forward<Variable4> for Conv2<A> {
+impl<A: Forward<Variable4, Output = Variable4>> Forward<Variable4> for Conv2<A> {
+    type Output = Variable4;
+    fn forward(&self, input: Variable4) -> Result<Variable4> {
+        self.forward_weight(input, self.weight_variable()?)
+    }
+}
+
+impl<A: Forward<Variable4, Output = Variable4>> Conv2<A> {
+    fn forward_weight(&self, input: Variable4, weight: Variable4) -> Result<Variable4> {
+        let options = ConvOptions {
+            padding: self.padding,
+            padding_after: self.padding_after(),
+            stride: self.stride,
+            dilation: self.dilation,
+        };
+        let bias = self.bias.as_ref().map(Parameter::to_variable);
+        let (input, weight, bias) = compute_cast(self.compute_scalar_type, input, weight, bias)?;
+        let output = conv2(
+            input,
+            weight,
+            options,
+            bias,
+            self.algorithm,
+            &self.auto_algorithm,
+            self.deterministic,
+            self.tile_size,
+            self.memory_format,
+        )?;
+        self.activation.forward(output)
+    }
+}
+
+impl<D: Dimension, A: ToOnnx> ToOnnx for Conv<D, A> {
+    fn to_onnx(&self, graph: &mut OnnxGraph, input: String) -> Result<String> {
+        if self.weight_standardization {
+            bail!("Conv with weight standardization can not be exported to ONNX!");
+        }
+        if self.memory_format == MemoryFormat::ChannelsLast {
+            bail!("Conv with channels last memory format can not be exported to ONNX!");
+        }
+        let ints = |dim: &[usize]| dim.iter().map(|x| *x as i64).collect::<Vec<_>>();
+        let mut inputs = vec![input, graph.initializer(self.weight.value().clone())?];
+        if let Some(bias) = self.bias.as_ref() {
+            inputs.push(graph.initializer(bias.value().clone())?);
+        }
+        let padding = ints(self.padding.slice());
+        let padding_after = ints(self.padding_after().slice());
+        let output = graph.node(
+            "Conv",
+            inputs,
+            vec![
+                ("kernel_shape", ints(&self.weight.shape()[2..]).into()),
+                (
+                    "pads",
+                    [padding.as_slice(), padding_after.as_slice()]
+                        .concat()
+                        .into(),
+                ),
+                ("strides", ints(self.stride.slice()).into()),
+                ("dilations", ints(self.dilation.slice()).into()),
+            ],
+        );
+        self.activation.to_onnx(graph, output)
+    }
+}
+
+/// A fully connected linear layer.
+///
+/// Implemented for bf16 and f32.
+///
+/// The input has shape `[batch_size, inputs]`. When following a [`Flatten`], the number of
+/// inputs depends on the spatial size of the input to the model, and a forward with a
+/// different resolution returns [`AutographError::ShapeMismatch`].
+///
+/// # Example
+///```no_run
+/// # use autograph::{krnl::{scalar::ScalarType, device::Device}, learn::neural_network::layer::{Dense, Relu}};
+/// # fn main() -> anyhow::Result<()> {
+/// # let device = Device::host();
+/// let dense = Dense::builder()
+///    .inputs(1)
+///    .outputs(1)
+///    .bias(true)
+///    .activation(Relu)
+///    .scalar_type(ScalarType::BF16)
+///    .device(device.clone())
+///    .build()?;
+/// # Ok(())
+/// # }
+///```
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Dense<A = Identity> {
+    weight: Parameter2,
+    bias: Option<Parameter1>,
+    #[serde(skip)]
+    compute_scalar_type: Option<ScalarType>,
+    activation: A,
+}
+
+impl Dense {
+    /// Returns a builder for creating a [`Dense`].
+    pub fn builder() -> DenseBuilder {
+        DenseBuilder::new()
+    }
+}
+
+impl<A> Dense<A> {
+    /// The weight.
+    pub fn weight(&self) -> &Parameter2 {
+        &self.weight
+    }
+    /// The weight, mutably.
+    ///
+    /// See [`Parameter::share()`].
+    pub fn weight_mut(&mut self) -> &mut Parameter2 {
+        &mut self.weight
+    }
+    /// The weight as a mutable parameter view.
+    pub fn weight_view_mut(&mut self) -> Result<ParameterViewMut2> {
+        self.weight.make_view_mut()
+    }
+    /// The bias as a mutable parameter view.
+    pub fn bias_view_mut(&mut self) -> Result<Option<ParameterViewMut1>> {
+        self.bias.as_mut().map(Parameter::make_view_mut).transpose()
+    }
+    /// The scalar type of the forward pass.
+    ///
+    /// See [`DenseBuilder::compute_scalar_type()`](builder::DenseBuilder::compute_scalar_type).
+    pub fn compute_scalar_type(&self) -> Option<ScalarType> {
+        self.compute_scalar_type
+    }
+    /// Sets the weight to `weight`.
+    ///
+    /// The weight has shape `[inputs, outputs]`, and is cast to the scalar type and transferred
+    /// to the device of the layer.
+    ///
+    ///```no_run
+    /// # use autograph::{anyhow::Result, learn::neural_network::layer::Dense};
+    /// # use ndarray::{arr1, arr2};
+    /// # fn main() -> Result<()> {
+    /// let mut dense = Dense::builder().inputs(2).outputs(3).bias(true).build()?;
+    /// dense.set_weight(&arr2(&[[1f32, 0., 1.], [0., 1., 1.]]))?;
+    /// dense.set_bias(&arr1(&[0f32, 0., 1.]))?;
+    /// # Ok(())
+    /// # }
+    ///```
+    ///
+    /// **Errors**
+    /// - The shape of `weight` is not the shape of the weight.
+    /// - The weight could not be cast or transferred to the device.
+    pub fn set_weight<T: Scalar, S: ArrayData<Elem = T>>(
+        &mut self,
+        weight: &ArrayBase<S, Ix2>,
+    ) -> Result<()> {
+        set_parameter(&mut self.weight, weight, "Dense weight")
+    }
+    /// Sets the weight to `weight` with shape `[outputs, inputs]`.
+    ///
+    /// Frameworks like PyTorch store the weight as `[outputs, inputs]`, this transposes `weight`
+    /// into the layout of the layer so imported weights don't need to be transposed manually.
+    ///
+    /// See [`.set_weight()`](Dense::set_weight).
+    ///
+    /// **Errors**
+    /// - The shape of `weight` is not `[outputs, inputs]`.
+    /// - The weight could not be cast or transferred to the device.
+    pub fn set_weight_transposed<T: Scalar, S: ArrayData<Elem = T>>(
+        &mut self,
+        weight: &ArrayBase<S, Ix2>,
+    ) -> Result<()> {
+        let (inputs, outputs) = self.weight.dim();
+        if weight.dim() != (outputs, inputs) {
+            bail!(
+                "Dense weight transposed expected shape {:?}, found {:?}!",
+                [outputs, inputs],
+                weight.shape()
+            );
+        }
+        set_parameter(&mut self.weight, &weight.t(), "Dense weight")
+    }
+    /// Sets the bias to `bias`.
+    ///
+    /// See [`.set_weight()`](Dense::set_weight).
+    ///
+    /// **Errors**
+    /// - The layer does not have a bias.
+    /// - The shape of `bias` is not `[outputs]`.
+    /// - The bias could not be cast or transferred to the device.
+    pub fn set_bias<T: Scalar, S: ArrayData<Elem = T>>(
+        &mut self,
+        bias: &ArrayBase<S, Ix1>,
+    ) -> Result<()> {
+        let Some(parameter) = self.bias.as_mut() else {
+            bail!("Dense does not have a bias!");
+        };
+        set_parameter(parameter, bias, "Dense bias")
+    }
+}
+
+fn set_parameter<T: Scalar, S: ArrayData<Elem = T>, D: Dimension>(
+    parameter: &mut Parameter<D>,
+    array: &ArrayBase<S, D>,
+    name: &str,
+) -> Result<()> {
+    if array.shape() != parameter.shape() {
+        bail!(
+            "{name} expected shape {:?}, found {:?}!",
+            parameter.shape(),
+            array.shape()
+        );
+    }
+    let tensor = ScalarTensor::from(Tensor::from(array.as_standard_layout().into_owned()))
+        .cast_into(parameter.scalar_type())?
+        .into_device(parameter.device())?;
+    let mut parameter = parameter.make_view_mut()?;
+    parameter.value_view_mut().assign(&tensor)
+}
+
+impl<A> Layer for Dense<A> {
+    fn set_training(&mut self, training: bool) -> Result<()> {
+        self.weight.set_training(training);
+        if let Some(bias) = self.bias.as_mut() {
+            bias.set_training(training);
+        }
+        Ok(())
+    }
+    fn parameters(&self) -> ParameterVec {
+        let mut parameters = ParameterVec::new();
+        parameters.push(self.weight.clone().into_dyn());
+        if let Some(bias) = self.bias.as_ref() {
+            parameters.push(bias.clone().into_dyn());
+        }
+        parameters
+    }
+    fn parameters_mut(&mut self) -> Result<ParameterMutVec> {
+        let mut parameters = ParameterMutVec::new();
+        parameters.push(self.weight.make_view_mut()?.into_dyn());
+        if let Some(bias) = self.bias.as_mut() {
+            parameters.push(bias.make_view_mut()?.into_dyn());
+        }
+        Ok(parameters)
+    }
+    fn named_parameters(&self) -> NamedParameterVec {
+        let mut parameters = vec![("weight".into(), self.weight.clone().into_dyn())];
+        if let Some(bias) = self.bias.as_ref() {
+            parameters.push(("bias".into(), bias.clone().into_dyn()));
+        }
+        parameters
+    }
+    fn named_parameters_mut(&mut self) -> Result<NamedParameterMutVec> {
+        let mut parameters = vec![("weight".into(), self.weight.make_view_mut()?.into_dyn())];
+        if let Some(bias) = self.bias.as_mut() {
+            parameters.push(("bias".into(), bias.make_view_mut()?.into_dyn()));
+        }
+        Ok(parameters)
+    }
+    fn transposed_parameter_names(&self) -> Vec<String> {
+        vec!["weight".into()]
+    }
+    fn cast_mut(&mut self, scalar_type: ScalarType) -> Result<()> {
+        check_cast_mut(scalar_type)?;
+        self.weight.cast_mut(scalar_type)?;
+        if let Some(bias) = self.bias.as_mut() {
+            bias.cast_mut(scalar_type)?;
+        }
+        Ok(())
+    }
+    fn to_device_mut(&mut self, device: Device) -> Result<()> {
+        self.weight.to_device_mut(device.clone())?;
+        if let Some(bias) = self.bias.as_mut() {
+            bias.to_device_mut(device)?;
+        }
+        Ok(())
+    }
+    fn into_device(self, device: Device) -> Result<Self>
+    where
+        Self: Sized,
+    {
+        Ok(Self {
+            weight: self.weight.into_device(device.clone())?,
+            bias: self.bias.map(|b| b.into_device(device)).transpose()?,
+            ..self
+        })
+    }
+}
+
+impl<A: Forward<Variable2, Output = Variable2> + Any> Forward<Variable2> for Dense<A> {
+    type Output = Variable2;
+    fn forward(&self, input: Variable2) -> Result<Self::Output> {
+        self.forward_weight(input, self.weight.to_variable())
+    }
+}
+
+impl<A: Forward<Variable2, Output = Variable2> + Any> Dense<A> {
+    fn forward_weight(&self, input: Variable2, weight: Variable2) -> Result<Variable2> {
+        if input.device() != self.weight.device() {
+            bail!(AutographError::device_mismatch(
+                "Dense input",
+                self.weight.device(),
+                input.device()
+            ));
+        }
+        let (batch_size, features) = input.dim();
+        let inputs = weight.shape()[0];
+        if features != inputs {
+            bail!(AutographError::shape_mismatch(
+                "Dense input",
+                &[batch_size, inputs],
+                &[batch_size, features]
+            ));
+        }
+        let bias = self.bias.as_ref().map(Parameter::to_variable);
+        let (input, weight, bias) = compute_cast(self.compute_scalar_type, input, weight, bias)?;
+        let mut output = input.dot(&weight)?;
+        if let Some(bias) = bias.as_ref() {
+            output.add_assign(bias)?;
+        }
+        self.activation.forward(output)
+    }
+}
+
+impl<A: ToOnnx> ToOnnx for Dense<A> {
+    fn to_onnx(&self, graph: &mut OnnxGraph, input: String) -> Result<String> {
+        let mut inputs = vec![input, graph.initializer(self.weight.value().clone())?];
+        if let Some(bias) = self.bias.as_ref() {
+            inputs.push(graph.initializer(bias.value().clone())?);
+        }
+        let output = graph.node("Gemm", inputs, Vec::new());
+        self.activation.to_onnx(graph, output)
+    }
+}
+
+/// A bilinear layer with two inputs.
+///
+/// Computes `y[n, o] = x1[n] W[o] x2[n] + b[o]`, where the weight has shape
+/// `[outputs, inputs1, inputs2]`. Used to score pairs of inputs, for example in relation or
+/// attention models.
+///
+/// Implemented for bf16 and f32.
+///
+/// # Example
+///```no_run
+/// # use autograph::{krnl::device::Device, learn::neural_network::{autograd::Variable2, layer::{Bilinear, Forward}}};
+/// # fn main() -> anyhow::Result<()> {
+/// # let device = Device::host();
+/// # let x1: Variable2 = todo!();
+/// # let x2: Variable2 = todo!();
+/// let bilinear = Bilinear::builder()
+///    .inputs1(16)
+///    .inputs2(8)
+///    .outputs(4)
+///    .bias(true)
+///    .device(device.clone())
+///    .build()?;
+/// let y = bilinear.forward((x1, x2))?;
+/// # Ok(())
+/// # }
+///```
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Bilinear {
+    weight: Parameter3,
+    bias: Option<Parameter1>,
+}
+
+impl Bilinear {
+    /// Returns a builder for creating a [`Bilinear`].
+    pub fn builder() -> BilinearBuilder {
+        BilinearBuilder::new()
+    }
+    /// The weight.
+    pub fn weight(&self) -> &Parameter3 {
+        &self.weight
+    }
+    /// The bias.
+    pub fn bias(&self) -> Option<&Parameter1> {
+        self.bias.as_ref()
+    }
+}
+
+impl Layer for Bilinear {
+    fn set_training(&mut self, training: bool) -> Result<()> {
+        self.weight.set_training(training);
+        if let Some(bias) = self.bias.as_mut() {
+            bias.set_training(training);
+        }
+        Ok(())
+    }
+    fn parameters(&self) -> ParameterVec {
+        let mut parameters = ParameterVec::new();
+        parameters.push(self.weight.clone().into_dyn());
+        if let Some(bias) = self.bias.as_ref() {
+            parameters.push(bias.clone().into_dyn());
+        }
+        parameters
+    }
+    fn parameters_mut(&mut self) -> Result<ParameterMutVec> {
+        let mut parameters = ParameterMutVec::new();
+        parameters.push(self.weight.make_view_mut()?.into_dyn());
+        if let Some(bias) = self.bias.as_mut() {
+            parameters.push(bias.make_view_mut()?.into_dyn());
+        }
+        Ok(parameters)
+    }
+    fn named_parameters(&self) -> NamedParameterVec {
+        let mut parameters = vec![("weight".into(), self.weight.clone().into_dyn())];
+        if let Some(bias) = self.bias.as_ref() {
+            parameters.push(("bias".into(), bias.clone().into_dyn()));
+        }
+        parameters
+    }
+    fn named_parameters_mut(&mut self) -> Result<NamedParameterMutVec> {
+        let mut parameters = vec![("weight".into(), self.weight.make_view_mut()?.into_dyn())];
+        if let Some(bias) = self.bias.as_mut() {
+            parameters.push(("bias".into(), bias.make_view_mut()?.into_dyn()));
+        }
+        Ok(parameters)
+    }
+    fn cast_mut(&mut self, scalar_type: ScalarType) -> Result<()> {
+        check_cast_mut(scalar_type)?;
+        self.weight.cast_mut(scalar_type)?;
+        if let Some(bias) = self.bias.as_mut() {
+            bias.cast_mut(scalar_type)?;
+        }
+        Ok(())
+    }
+    fn to_device_mut(&mut self, device: Device) -> Result<()> {
+        self.weight.to_device_mut(device.clone())?;
+        if let Some(bias) = self.bias.as_mut() {
+            bias.to_device_mut(device)?;
+        }
+        Ok(())
+    }
+    fn into_device(self, device: Device) -> Result<Self>
+    where
+        Self: Sized,
+    {
+        Ok(Self {
+            weight: self.weight.into_device(device.clone())?,
+            bias: self.bias.map(|b| b.into_device(device)).transpose()?,
+        })
+    }
+}
+
+impl Forward<(Variable2, Variable2)> for Bilinear {
+    type Output = Variable2;
+    fn forward(&self, (input1, input2): (Variable2, Variable2)) -> Result<Self::Output> {
+        let (batch_size, inputs1) = input1.dim();
+        let (outputs, weight_inputs1, weight_inputs2) = self.weight.dim();
+        if input2.dim() != (batch_size, weight_inputs2) || inputs1 != weight_inputs1 {
+            bail!(
+                "Bilinear inputs {:?} {:?} do not match weight {:?}!",
+                input1.shape(),
+                input2.shape(),
+                self.weight.shape(),
+            );
+        }
+        let device = self.weight.device();
+        for (name, input) in [("Bilinear input1", &input1), ("Bilinear input2", &input2)] {
+            if input.device() != device {
+                bail!(AutographError::device_mismatch(
+                    name,
+                    device,
+                    input.device()
+                ));
+            }
+        }
+        // z[n, o, i] = sum_j W[o, i, j] * x2[n, j]
+        let weight = self
+            .weight
+            .to_variable()
+            .into_shape([outputs * inputs1, weight_inputs2])
+            .map_err(Error::msg)?;
+        let z = input2
+            .dot(&weight.t())?
+            .into_shape([batch_size, outputs, inputs1])
+            .map_err(Error::msg)?;
+        // y[n, o] = sum_i z[n, o, i] * x1[n, i]
+        let x1 = input1
+            .into_shape([batch_size, 1, inputs1])
+            .map_err(Error::msg)?
+            .broadcast([batch_size, outputs, inputs1])
+            .unwrap();
+        let ones = Variable2::from(ScalarArcTensor::ones(
+            device,
+            [inputs1, 1],
+            self.weight.scalar_type(),
+        )?);
+        let mut output = z
+            .mul(&x1)?
+            .into_shape([batch_size * outputs, inputs1])
+            .map_err(Error::msg)?
+            .dot(&ones)?
+            .into_shape([batch_size, outputs])
+            .map_err(Error::msg)?;
+        if let Some(bias) = self.bias.as_ref() {
+            output.add_assign(&bias.to_variable())?;
+        }
+        Ok(output)
+    }
+}
+
+/// Batch normalization.
+///
+/// Normalizes each channel of the input with the mean and variance over the batch and any
+/// spatial axes, then applies a per channel affine transform, ie
+/// `y = (x - mean) / sqrt(var + eps) * weight + bias`.
+///
+/// While training, the statistics of the batch are used and the running mean and variance are
+/// updated with `momentum`. Otherwise the running statistics are used. With
+/// [`.set_track_running_stats(false)`](BatchNorm::set_track_running_stats), the statistics of
+/// the batch are always used and the running statistics are frozen, while the weight and bias
+/// are still trained. This is useful when fine-tuning a pretrained model on a different
+/// dataset.
+///
+/// Implemented for bf16 and f32, for inputs with shape [batch_size, channels] or
+/// [batch_size, channels, height, width]. The running statistics are stored as f32 on the device
+/// of the layer, and are computed with device ops.
+///
+/// # Example
+///```no_run
+/// # use autograph::{krnl::device::Device, learn::neural_network::{autograd::Variable4, layer::{BatchNorm, Forward, Layer}}};
+/// # fn main() -> anyhow::Result<()> {
+/// # let device = Device::host();
+/// # let x: Variable4 = todo!();
+/// let mut batch_norm = BatchNorm::builder()
+///    .channels(16)
+///    .device(device.clone())
+///    .build()?;
+/// batch_norm.set_training(true)?;
+/// // Fine-tune the weight and bias without updating the running statistics.
+/// batch_norm.set_track_running_stats(false);
+/// let y = batch_norm.forward(x)?;
+/// # Ok(())
+/// # }
+///```
+#[derive(Debug)]
+pub struct BatchNorm {
+    weight: Parameter1,
+    bias: Parameter1,
+    running_stats: RwLock<RunningStats>,
+    momentum: f32,
+    eps: f32,
+    track_running_stats: bool,
+    training: bool,
+}
+
+// The running statistics of a BatchNorm, on the device of the layer. Both are updated under one
+// lock, so that a concurrent forward does not see the mean of one step and the variance of another.
+#[derive(Debug)]
+struct RunningStats {
+    mean: ArcTensor1<f32>,
+    var: ArcTensor1<f32>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename = "BatchNorm")]
+struct BatchNormSerde {
+    weight: Parameter1,
+    bias: Parameter1,
+    running_mean: ArcTensor1<f32>,
+    running_var: ArcTensor1<f32>,
+    momentum: f32,
+    eps: f32,
+    track_running_stats: bool,
+}
+
+impl Serialize for BatchNorm {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        BatchNormSerde {
+            weight: self.weight.clone(),
+            bias: self.bias.clone(),
+            running_mean: self.running_mean(),
+            running_var: self.running_var(),
+            momentum: self.momentum,
+            eps: self.eps,
+            track_running_stats: self.track_running_stats,
+        }
+        .serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for BatchNorm {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let BatchNormSerde {
+            weight,
+            bias,
+            running_mean,
+            running_var,
+            momentum,
+            eps,
+            track_running_stats,
+        } = BatchNormSerde::deserialize(deserializer)?;
+        Ok(Self {
+            weight,
+            bias,
+            running_stats: RwLock::new(RunningStats {
+                mean: running_mean,
+                var: running_var,
+            }),
+            momentum,
+            eps,
+            track_running_stats,
+            training: false,
+        })
+    }
+}
+
+impl BatchNorm {
+    /// Returns a builder for creating a [`BatchNorm`].
+    pub fn builder() -> BatchNormBuilder {
+        BatchNormBuilder::new()
+    }
+    /// The weight.
+    pub fn weight(&self) -> &Parameter1 {
+        &self.weight
+    }
+    /// The bias.
+    pub fn bias(&self) -> &Parameter1 {
+        &self.bias
+    }
+    /// The running mean.
+    pub fn running_mean(&self) -> ArcTensor1<f32> {
+        self.running_stats.read().mean.clone()
+    }
+    /// The running variance.
+    pub fn running_var(&self) -> ArcTensor1<f32> {
+        self.running_stats.read().var.clone()
+    }
+    /// Whether the running statistics are updated and used.
+    pub fn track_running_stats(&self) -> bool {
+        self.track_running_stats
+    }
+    /// Sets whether the running statistics are updated and used.
+    ///
+    /// If false, the forward pass uses the statistics of the batch and does not update the
+    /// running statistics, whether training or not. The weight and bias still receive gradients
+    /// while training.
+    pub fn set_track_running_stats(&mut self, track_running_stats: bool) {
+        self.track_running_stats = track_running_stats;
+    }
+    fn forward4(&self, input: Variable4) -> Result<Variable4> {
+        let (batch_size, channels, height, width) = input.dim();
+        if channels != self.weight.dim() {
+            bail!(
+                "BatchNorm input shape {:?} does not match channels {}!",
+                input.shape(),
+                self.weight.dim(),
+            );
+        }
+        let device = self.weight.device();
+        if input.device() != device {
+            bail!(AutographError::device_mismatch(
+                "BatchNorm input",
+                device,
+                input.device()
+            ));
+        }
+        let scalar_type = self.weight.scalar_type();
+        let dim = input.raw_dim();
+        let len = batch_size * height * width;
+        let (centered, inv_std) = if self.training || !self.track_running_stats {
+            if len < 2 {
+                bail!(
+                    "BatchNorm expected more than 1 value per channel, found input shape {:?}!",
+                    input.shape()
+                );
+            }
+            let neg_mean = channel_sum(&input, -1. / len as f32)?;
+            let mut centered = input;
+            centered.add_assign(
+                neg_mean
+                    .clone()
+                    .into_shape([1, channels, 1, 1])
+                    .map_err(Error::msg)?,
+            )?;
+            let var = channel_sum(&centered.mul(&centered)?, 1. / len as f32)?;
+            if self.training && self.track_running_stats {
+                let momentum = self.momentum;
+                let mut mean = neg_mean
+                    .value()
+                    .clone()
+                    .cast_into_tensor::<f32>()?
+                    .into_shape(channels)
+                    .map_err(Error::msg)?
+                    .scaled_cast(-momentum)?;
+                // The running variance is unbiased.
+                let mut unbiased_var = var
+                    .value()
+                    .clone()
+                    .cast_into_tensor::<f32>()?
+                    .into_shape(channels)
+                    .map_err(Error::msg)?
+                    .scaled_cast(momentum * len as f32 / (len - 1) as f32)?;
+                let mut running_stats = self.running_stats.write();
+                mean.scaled_add(1. - momentum, &running_stats.mean)?;
+                unbiased_var.scaled_add(1. - momentum, &running_stats.var)?;
+                running_stats.mean = mean.into_shared()?;
+                running_stats.var = unbiased_var.into_shared()?;
+            }
+            (centered, inv_std(&var, self.eps)?)
+        } else {
+            let (mean, var) = {
+                let running_stats = self.running_stats.read();
+                (running_stats.mean.clone(), running_stats.var.clone())
+            };
+            let neg_mean = ScalarTensor::from(mean.scaled_cast(-1f32)?)
+                .cast_into(scalar_type)?
+                .into_shape([1, channels, 1, 1])
+                .map_err(Error::msg)?
+                .into_shared()?;
+            let var = ScalarArcTensor::from(var)
+                .cast_into(scalar_type)?
+                .into_shape([1, channels])
+                .map_err(Error::msg)?
+                .into_shared()?;
+            let mut centered = input;
+            centered.add_assign(Variable4::from(neg_mean))?;
+            (centered, inv_std(&Variable2::from(var), self.eps)?)
+        };
+        let weight = self
+            .weight
+            .to_variable()
+            .into_shape([1, channels])
+            .map_err(Error::msg)?;
+        let scale = inv_std
+            .mul(&weight)?
+            .into_shape([1, channels, 1, 1])
+            .map_err(Error::msg)?
+            .broadcast(dim)
+            .unwrap();
+        let mut output = centered.mul(&scale)?;
+        output.add_assign(
+            self.bias
+                .to_variable()
+                .into_shape([1, channels, 1, 1])
+                .map_err(Error::msg)?,
+        )?;
+        Ok(output)
+    }
+}
+
+// Sums each channel of `input` scaled by `alpha`, with shape [1, channels].
+fn channel_sum(input: &Variable4, alpha: f32) -> Result<Variable2> {
+    let (batch_size, channels, height, width) = input.dim();
+    let device = input.device();
+    let scalar_type = input.scalar_type();
+    let ones = Variable2::from(ScalarArcTensor::ones(
+        device.clone(),
+        [height * width, 1],
+        scalar_type,
+    )?);
+    let alphas = Variable2::from(ScalarArcTensor::from_elem(
+        device,
+        [1, batch_size],
+        ScalarElem::F32(alpha).scalar_cast(scalar_type),
+    )?);
+    let sums = input
+        .clone()
+        .into_shape([batch_size * channels, height * width])
+        .map_err(Error::msg)?
+        .dot(&ones)?
+        .into_shape([batch_size, channels])
+        .map_err(Error::msg)?;
+    alphas.dot(&sums)
+}
+
+// Standardizes each output filter of `weight` to zero mean and unit variance.
+fn standardize_weight<D: Dimension + 'static>(weight: Variable<D>) -> Result<Variable<D>> {
+    let dim = weight.raw_dim();
+    let weight = weight.flatten().map_err(Error::msg)?;
+    let (outputs, filter_size) = weight.dim();
+    let device = weight.device();
+    let scalar_type = weight.scalar_type();
+    let neg_means = Variable2::from(ScalarArcTensor::from_elem(
+        device.clone(),
+        [filter_size, 1],
+        ScalarElem::F32(-1. / filter_size as f32).scalar_cast(scalar_type),
+    )?);
+    let means = Variable2::from(ScalarArcTensor::from_elem(
+        device,
+        [filter_size, 1],
+        ScalarElem::F32(1. / filter_size as f32).scalar_cast(scalar_type),
+    )?);
+    let mut centered = weight.clone();
+    centered.add_assign(weight.dot(&neg_means)?)?;
+    let var = centered.mul(&centered)?.dot(&means)?;
+    let inv_std = inv_std(&var, 1e-5)?
+        .broadcast([outputs, filter_size])
+        .unwrap();
+    centered.mul(&inv_std)?.into_shape(dim).map_err(Error::msg)
+}
+
+// Computes 1 / sqrt(var + eps), ie (var + eps)^(-1/2), on the device.
+pub(super) fn inv_std(var: &Variable2, eps: f32) -> Result<Variable2> {
+    let device = var.device();
+    let scalar_type = var.scalar_type();
+    let mut var = var.clone();
+    var.add_assign(Variable2::from(ScalarArcTensor::from_elem(
+        device.clone(),
+        [1, 1],
+        ScalarElem::F32(eps).scalar_cast(scalar_type),
+    )?))?;
+    let neg_half = Variable2::from(ScalarArcTensor::from_elem(
+        device,
+        var.raw_dim(),
+        ScalarElem::F32(-0.5).scalar_cast(scalar_type),
+    )?);
+    var.pow(&neg_half)
+}
+
+impl Layer for BatchNorm {
+    fn set_training(&mut self, training: bool) -> Result<()> {
+        self.training = training;
+        self.weight.set_training(training);
+        self.bias.set_training(training);
+        Ok(())
+    }
+    fn parameters(&self) -> ParameterVec {
+        let mut parameters = ParameterVec::new();
+        parameters.push(self.weight.clone().into_dyn());
+        parameters.push(self.bias.clone().into_dyn());
+        parameters
+    }
+    fn parameters_mut(&mut self) -> Result<ParameterMutVec> {
+        let mut parameters = ParameterMutVec::new();
+        parameters.push(self.weight.make_view_mut()?.into_dyn());
+        parameters.push(self.bias.make_view_mut()?.into_dyn());
+        Ok(parameters)
+    }
+    fn named_parameters(&self) -> NamedParameterVec {
+        vec![
+            ("weight".into(), self.weight.clone().into_dyn()),
+            ("bias".into(), self.bias.clone().into_dyn()),
+        ]
+    }
+    fn named_parameters_mut(&mut self) -> Result<NamedParameterMutVec> {
+        Ok(vec![
+            ("weight".into(), self.weight.make_view_mut()?.into_dyn()),
+            ("bias".into(), self.bias.make_view_mut()?.into_dyn()),
+        ])
+    }
+    fn cast_mut(&mut self, scalar_type: ScalarType) -> Result<()> {
+        check_cast_mut(scalar_type)?;
+        self.weight.cast_mut(scalar_type)?;
+        self.bias.cast_mut(scalar_type)
+    }
+    fn to_device_mut(&mut self, device: Device) -> Result<()> {
+        self.weight.to_device_mut(device.clone())?;
+        self.bias.to_device_mut(device.clone())?;
+        let running_stats = self.running_stats.get_mut();
+        running_stats.mean = running_stats
+            .mean
+            .clone()
+            .into_device_shared(device.clone())?;
+        running_stats.var = running_stats.var.clone().into_device_shared(device)?;
+        Ok(())
+    }
+    fn into_device(mut self, device: Device) -> Result<Self>
+    where
+        Self: Sized,
+    {
+        self.to_device_mut(device)?;
+        Ok(self)
+    }
+}
+
+impl Forward<Variable2> for BatchNorm {
+    type Output = Variable2;
+    fn forward(&self, input: Variable2) -> Result<Self::Output> {
+        let (batch_size, channels) = input.dim();
+        self.forward4(
+            input
+                .into_shape([batch_size, channels, 1, 1])
+                .map_err(Error::msg)?,
+        )?
+        .into_shape([batch_size, channels])
+        .map_err(Error::msg)
+    }
+}
+
+impl Forward<Variable4> for BatchNorm {
     type Output = Variable4;
-    fn forward(&self, input: Variable4) -> Result<Variable4> {
-        let weight = self.weight.to_variable();
-        let options = ConvOptions {
-            padding: self.padding,
-            stride: self.stride,
-            dilation: self.dilation,
-        };
-        let bias = self.bias.as_ref().map(Parameter::to_variable);
-        let output = conv2(input, weight, options, bias)?;
-        self.activation.forward(output)
+    fn forward(&self, input: Variable4) -> Result<Self::Output> {
+        self.forward4(input)
     }
 }
 
-/// A fully connected linear layer.
+/// A per channel affine transform.
 ///
-/// Implemented for bf16 and f32.
+/// Scales and shifts each channel with learnable parameters, ie `y = scale * x + shift`,
+/// broadcast over the batch and spatial axes. Can be used for rescaling without normalization,
+/// or as the affine part of a custom normalization.
+///
+/// Implemented for bf16 and f32, for inputs with shape [batch_size, channels] or
+/// [batch_size, channels, height, width].
 ///
 /// # Example
 ///```no_run
-/// # use autograph::{krnl::{scalar::ScalarType, device::Device}, learn::neural_network::layer::{Dense, Relu}};
+/// # use autograph::{krnl::device::Device, learn::neural_network::layer::Affine};
 /// # fn main() -> anyhow::Result<()> {
 /// # let device = Device::host();
-/// let dense = Dense::builder()
-///    .inputs(1)
-///    .outputs(1)
-///    .bias(true)
-///    .activation(Relu)
-///    .scalar_type(ScalarType::BF16)
+/// let affine = Affine::builder()
+///    .channels(16)
 ///    .device(device.clone())
 ///    .build()?;
 /// # Ok(())
 /// # }
 ///```
-#[derive(Debug, Serialize, Deserialize)]
-pub struct Dense<A = Identity> {
-    weight: Parameter2,
-    bias: Option<Parameter1>,
-    activation: A,
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Affine {
+    scale: Parameter1,
+    shift: Parameter1,
 }
 
-impl Dense {
-    /// Returns a builder for creating a [`Dense`].
-    pub fn builder() -> DenseBuilder {
-        DenseBuilder::new()
+impl Affine {
+    /// Returns a builder for creating an [`Affine`].
+    pub fn builder() -> AffineBuilder {
+        AffineBuilder::new()
     }
-}
-
-impl<A> Dense<A> {
-    /// The weight as a mutable parameter view.
-    pub fn weight_view_mut(&mut self) -> Result<ParameterViewMut2> {
-        self.weight.make_view_mut()
+    /// The scale.
+    pub fn scale(&self) -> &Parameter1 {
+        &self.scale
     }
-    /// The bias as a mutable parameter view.
-    pub fn bias_view_mut(&mut self) -> Result<Option<ParameterViewMut1>> {
-        self.bias.as_mut().map(Parameter::make_view_mut).transpose()
+    /// The scale, mutably.
+    ///
+    /// See [`Parameter::share()`].
+    pub fn scale_mut(&mut self) -> &mut Parameter1 {
+        &mut self.scale
+    }
+    /// The shift.
+    pub fn shift(&self) -> &Parameter1 {
+        &self.shift
+    }
+    /// The shift, mutably.
+    ///
+    /// See [`Parameter::share()`].
+    pub fn shift_mut(&mut self) -> &mut Parameter1 {
+        &mut self.shift
+    }
+    fn forward4(&self, input: Variable4) -> Result<Variable4> {
+        let channels = self.scale.dim();
+        if input.dim().1 != channels {
+            bail!(
+                "Affine input shape {:?} does not match channels {channels}!",
+                input.shape(),
+            );
+        }
+        let device = self.scale.device();
+        if input.device() != device {
+            bail!(AutographError::device_mismatch(
+                "Affine input",
+                device,
+                input.device()
+            ));
+        }
+        let dim = input.raw_dim();
+        let scale = self
+            .scale
+            .to_variable()
+            .into_shape([1, channels, 1, 1])
+            .map_err(Error::msg)?
+            .broadcast(dim)
+            .unwrap();
+        let mut output = input.mul(&scale)?;
+        output.add_assign(
+            self.shift
+                .to_variable()
+                .into_shape([1, channels, 1, 1])
+                .map_err(Error::msg)?,
+        )?;
+        Ok(output)
     }
 }
 
-impl<A> Layer for Dense<A> {
+impl Layer for Affine {
     fn set_training(&mut self, training: bool) -> Result<()> {
-        self.weight.set_training(training);
-        if let Some(bias) = self.bias.as_mut() {
-            bias.set_training(training);
-        }
+        self.scale.set_training(training);
+        self.shift.set_training(training);
         Ok(())
     }
     fn parameters(&self) -> ParameterVec {
         let mut parameters = ParameterVec::new();
-        parameters.push(self.weight.clone().into_dyn());
-        if let Some(bias) = self.bias.as_ref() {
-            parameters.push(bias.clone().into_dyn());
-        }
+        parameters.push(self.scale.clone().into_dyn());
+        parameters.push(self.shift.clone().into_dyn());
         parameters
     }
     fn parameters_mut(&mut self) -> Result<ParameterMutVec> {
         let mut parameters = ParameterMutVec::new();
-        parameters.push(self.weight.make_view_mut()?.into_dyn());
-        if let Some(bias) = self.bias.as_mut() {
-            parameters.push(bias.make_view_mut()?.into_dyn());
-        }
+        parameters.push(self.scale.make_view_mut()?.into_dyn());
+        parameters.push(self.shift.make_view_mut()?.into_dyn());
         Ok(parameters)
     }
+    fn named_parameters(&self) -> NamedParameterVec {
+        vec![
+            ("scale".into(), self.scale.clone().into_dyn()),
+            ("shift".into(), self.shift.clone().into_dyn()),
+        ]
+    }
+    fn named_parameters_mut(&mut self) -> Result<NamedParameterMutVec> {
+        Ok(vec![
+            ("scale".into(), self.scale.make_view_mut()?.into_dyn()),
+            ("shift".into(), self.shift.make_view_mut()?.into_dyn()),
+        ])
+    }
+    fn cast_mut(&mut self, scalar_type: ScalarType) -> Result<()> {
+        check_cast_mut(scalar_type)?;
+        self.scale.cast_mut(scalar_type)?;
+        self.shift.cast_mut(scalar_type)
+    }
     fn to_device_mut(&mut self, device: Device) -> Result<()> {
-        self.weight.to_device_mut(device.clone())?;
-        if let Some(bias) = self.bias.as_mut() {
-            bias.to_device_mut(device)?;
-        }
+        self.scale.to_device_mut(device.clone())?;
+        self.shift.to_device_mut(device)?;
         Ok(())
     }
-    fn into_device(self, device: Device) -> Result<Self>
+    fn into_device(mut self, device: Device) -> Result<Self>
     where
         Self: Sized,
     {
+        self.to_device_mut(device)?;
+        Ok(self)
+    }
+}
+
+impl Forward<Variable2> for Affine {
+    type Output = Variable2;
+    fn forward(&self, input: Variable2) -> Result<Self::Output> {
+        let (batch_size, channels) = input.dim();
+        self.forward4(
+            input
+                .into_shape([batch_size, channels, 1, 1])
+                .map_err(Error::msg)?,
+        )?
+        .into_shape([batch_size, channels])
+        .map_err(Error::msg)
+    }
+}
+
+impl Forward<Variable4> for Affine {
+    type Output = Variable4;
+    fn forward(&self, input: Variable4) -> Result<Self::Output> {
+        self.forward4(input)
+    }
+}
+
+/// Spectral normalization.
+///
+/// Wraps a [`Dense`] or [`Conv`], dividing the weight by its largest singular value on each
+/// forward. The weight is viewed as a matrix with the first axis as rows, ie
+/// `[outputs, inputs * filter]` for [`Conv`]. This bounds the Lipschitz constant of the
+/// layer, and is typically applied to the layers of a GAN discriminator.
+///
+/// The singular value is estimated with power iteration on the device of the layer. The left
+/// singular vector `u` is persisted between forward passes, and refined with
+/// [`.power_iterations()`](SpectralNorm::power_iterations) steps on each forward while
+/// training. The gradient is propagated through the normalization to the weight, with `u` and
+/// `v` treated as constants.
+///
+/// # Example
+///```no_run
+/// # use autograph::{krnl::device::Device, learn::neural_network::layer::{Dense, SpectralNorm}};
+/// # fn main() -> anyhow::Result<()> {
+/// # let device = Device::host();
+/// let dense = SpectralNorm::new(
+///     Dense::builder()
+///         .inputs(16)
+///         .outputs(1)
+///         .device(device.clone())
+///         .build()?,
+/// )?;
+/// # Ok(())
+/// # }
+///```
+#[derive(Debug)]
+pub struct SpectralNorm<L> {
+    layer: L,
+    u: RwLock<ArcTensor1<f32>>,
+    power_iterations: usize,
+    eps: f32,
+    training: bool,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename = "SpectralNorm")]
+struct SpectralNormSerde<L> {
+    layer: L,
+    u: ArcTensor1<f32>,
+    power_iterations: usize,
+    eps: f32,
+}
+
+impl<L: Serialize> Serialize for SpectralNorm<L> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        SpectralNormSerde {
+            layer: &self.layer,
+            u: self.u(),
+            power_iterations: self.power_iterations,
+            eps: self.eps,
+        }
+        .serialize(serializer)
+    }
+}
+
+impl<'de, L: Deserialize<'de>> Deserialize<'de> for SpectralNorm<L> {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let SpectralNormSerde {
+            layer,
+            u,
+            power_iterations,
+            eps,
+        } = SpectralNormSerde::deserialize(deserializer)?;
         Ok(Self {
-            weight: self.weight.into_device(device.clone())?,
-            bias: self.bias.map(|b| b.into_device(device)).transpose()?,
-            ..self
+            layer,
+            u: RwLock::new(u),
+            power_iterations,
+            eps,
+            training: false,
         })
     }
 }
 
-impl<A: Forward<Variable2, Output = Variable2> + Any> Forward<Variable2> for Dense<A> {
+impl<L: Layer> SpectralNorm<L> {
+    /// Wraps `layer`.
+    ///
+    /// `u` is initialized randomly on the device of the layer, with 1 power iteration per
+    /// forward.
+    ///
+    /// **Errors**
+    /// - The layer does not have a weight.
+    /// - The vector `u` could not be transferred to the device.
+    pub fn new(layer: L) -> Result<Self> {
+        let parameters = layer.parameters();
+        let Some(weight) = parameters.first() else {
+            bail!("SpectralNorm layer does not have a weight!");
+        };
+        let Some(rows) = weight.shape().first().copied() else {
+            bail!("SpectralNorm weight must have at least 1 dimension!");
+        };
+        let mut rng = StdRng::seed_from_u64(0);
+        let dist = Uniform::new(-1f32, 1.);
+        let u = Tensor::from(Array::from_shape_fn(rows, |_| dist.sample(&mut rng)))
+            .into_device(weight.device())?;
+        let u = l2_normalize(u.into_shared()?, 1e-12)?;
+        Ok(Self {
+            layer,
+            u: RwLock::new(u),
+            power_iterations: 1,
+            eps: 1e-12,
+            training: false,
+        })
+    }
+}
+
+impl<L> SpectralNorm<L> {
+    /// The wrapped layer.
+    pub fn layer(&self) -> &L {
+        &self.layer
+    }
+    /// The wrapped layer, mutably.
+    pub fn layer_mut(&mut self) -> &mut L {
+        &mut self.layer
+    }
+    /// Unwraps the layer.
+    pub fn into_inner(self) -> L {
+        self.layer
+    }
+    /// The estimated left singular vector.
+    pub fn u(&self) -> ArcTensor1<f32> {
+        self.u.read().clone()
+    }
+    /// The number of power iterations per forward while training.
+    pub fn power_iterations(&self) -> usize {
+        self.power_iterations
+    }
+    /// Sets the number of power iterations per forward while training.
+    ///
+    /// Defaults to 1. More iterations improve the estimate, at the cost of 2 matrix vector
+    /// products each. With 0, `u` is not updated.
+    pub fn set_power_iterations(&mut self, power_iterations: usize) {
+        self.power_iterations = power_iterations;
+    }
+    // Divides `weight` by its estimated spectral norm.
+    fn normalize_weight<D: Dimension + 'static>(&self, weight: Variable<D>) -> Result<Variable<D>> {
+        let dim = weight.raw_dim();
+        let scalar_type = weight.scalar_type();
+        let weight = weight.flatten().map_err(Error::msg)?;
+        let (rows, cols) = weight.dim();
+        let mut u = self.u();
+        if u.dim() != rows {
+            bail!(
+                "SpectralNorm u len {} does not match weight shape {:?}!",
+                u.dim(),
+                dim.slice(),
+            );
+        }
+        // Power iteration is computed in f32 on the device, without gradients. `u` is only
+        // updated while training, so inference does not modify the layer.
+        let matrix = weight.value().clone().cast_into_tensor::<f32>()?;
+        if self.training && self.power_iterations > 0 {
+            for _ in 0..self.power_iterations {
+                let v = l2_normalize(u.dot(&matrix)?.into_shared()?, self.eps)?;
+                u = l2_normalize(matrix.dot(&v)?.into_shared()?, self.eps)?;
+            }
+            *self.u.write() = u.clone();
+        }
+        let v = l2_normalize(u.dot(&matrix)?.into_shared()?, self.eps)?;
+        let u = Variable2::from(
+            ScalarArcTensor::from(u)
+                .cast_into(scalar_type)?
+                .into_shape([1, rows])
+                .map_err(Error::msg)?
+                .into_shared()?,
+        );
+        let v = Variable2::from(
+            ScalarArcTensor::from(v)
+                .cast_into(scalar_type)?
+                .into_shape([cols, 1])
+                .map_err(Error::msg)?
+                .into_shared()?,
+        );
+        let sigma = u.dot(&weight)?.dot(&v)?.broadcast([rows, cols]).unwrap();
+        weight.div(&sigma)?.into_shape(dim).map_err(Error::msg)
+    }
+}
+
+// Scales `x` to unit length, ie `x / max(norm(x), eps)`, without copying to the host.
+fn l2_normalize(x: ArcTensor1<f32>, eps: f32) -> Result<ArcTensor1<f32>> {
+    let device = x.device();
+    let len = x.dim();
+    let column = x.into_shape([len, 1]).map_err(Error::msg)?;
+    let squared_norm = column.t().dot(&column)?;
+    let min = Tensor::from_elem(device.clone(), [1, 1], eps * eps)?;
+    let neg_half = Tensor::from_elem(device, [1, 1], -0.5f32)?;
+    let inv_norm =
+        ScalarTensor::from(squared_norm.maximum(&min)?).pow(&ScalarTensor::from(neg_half))?;
+    let inv_norm = inv_norm.try_into_tensor::<f32>().unwrap();
+    column
+        .dot(&inv_norm)?
+        .into_shape(len)
+        .map_err(Error::msg)?
+        .into_shared()
+}
+
+impl<L: Layer> Layer for SpectralNorm<L> {
+    fn set_training(&mut self, training: bool) -> Result<()> {
+        self.training = training;
+        self.layer.set_training(training)
+    }
+    fn parameters(&self) -> ParameterVec {
+        self.layer.parameters()
+    }
+    fn parameters_mut(&mut self) -> Result<ParameterMutVec> {
+        self.layer.parameters_mut()
+    }
+    fn named_parameters(&self) -> NamedParameterVec {
+        self.layer.named_parameters()
+    }
+    fn named_parameters_mut(&mut self) -> Result<NamedParameterMutVec> {
+        self.layer.named_parameters_mut()
+    }
+    fn transposed_parameter_names(&self) -> Vec<String> {
+        self.layer.transposed_parameter_names()
+    }
+    fn cast_mut(&mut self, scalar_type: ScalarType) -> Result<()> {
+        self.layer.cast_mut(scalar_type)
+    }
+    fn to_device_mut(&mut self, device: Device) -> Result<()> {
+        self.layer.to_device_mut(device.clone())?;
+        let u = self.u.get_mut();
+        *u = u.clone().into_device_shared(device)?;
+        Ok(())
+    }
+    fn into_device(mut self, device: Device) -> Result<Self>
+    where
+        Self: Sized,
+    {
+        self.to_device_mut(device)?;
+        Ok(self)
+    }
+}
+
+impl<A: Forward<Variable2, Output = Variable2> + Any> Forward<Variable2>
+    for SpectralNorm<Dense<A>>
+{
     type Output = Variable2;
-    fn forward(&self, input: Variable2) -> Result<Self::Output> {
-        let mut output = input.dot(&self.weight.to_variable())?;
-        if let Some(bias) = self.bias.as_ref() {
-            output.add_assign(&bias.to_variable())?;
+    fn forward(&self, input: Variable2) -> Result<Variable2> {
+        let weight = self.normalize_weight(self.layer.weight.to_variable())?;
+        self.layer.forward_weight(input, weight)
+    }
+}
+
+impl<A: Forward<Variable3, Output = Variable3>> Forward<Variable3> for SpectralNorm<Conv1<A>> {
+    type Output = Variable3;
+    fn forward(&self, input: Variable3) -> Result<Variable3> {
+        let weight = self.normalize_weight(self.layer.weight_variable()?)?;
+        self.layer.forward_weight(input, weight)
+    }
+}
+
+impl<A: Forward<Variable4, Output = Variable4>> Forward<Variable4> for SpectralNorm<Conv2<A>> {
+    type Output = Variable4;
+    fn forward(&self, input: Variable4) -> Result<Variable4> {
+        let weight = self.normalize_weight(self.layer.weight_variable()?)?;
+        self.layer.forward_weight(input, weight)
+    }
+}
+
+macro_rules! impl_lazy {
+    ($lazy:ident, $layer:ident, $field:ident, $name:literal) => {
+        impl_lazy!($lazy<>, $layer, $field, $name);
+    };
+    ($lazy:ident<$($g:ident: $b:path),*>, $layer:ident, $field:ident, $name:literal) => {
+        impl<$($g: $b,)* A> $lazy<$($g,)* A> {
+            /// The built layer, or None prior to the first forward.
+            pub fn get(&self) -> Option<&$layer<$($g,)* A>> {
+                self.$field.get()
+            }
+            /// The built layer as mutable, or None prior to the first forward.
+            pub fn get_mut(&mut self) -> Option<&mut $layer<$($g,)* A>> {
+                self.$field.get_mut()
+            }
         }
-        self.activation.forward(output)
+
+        impl<$($g: $b,)* A: Debug> Debug for $lazy<$($g,)* A> {
+            fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+                f.debug_struct($name)
+                    .field("training", &self.training)
+                    .field(stringify!($field), &self.$field.get())
+                    .finish()
+            }
+        }
+
+        impl<$($g: $b,)* A> Layer for $lazy<$($g,)* A> {
+            fn set_training(&mut self, training: bool) -> Result<()> {
+                self.training = training;
+                if let Some(layer) = self.$field.get_mut() {
+                    layer.set_training(training)?;
+                }
+                Ok(())
+            }
+            fn parameters(&self) -> ParameterVec {
+                self.$field
+                    .get()
+                    .map(Layer::parameters)
+                    .unwrap_or_default()
+            }
+            fn parameters_mut(&mut self) -> Result<ParameterMutVec> {
+                if let Some(layer) = self.$field.get_mut() {
+                    layer.parameters_mut()
+                } else {
+                    Ok(ParameterMutVec::new())
+                }
+            }
+            fn named_parameters(&self) -> NamedParameterVec {
+                self.$field
+                    .get()
+                    .map(Layer::named_parameters)
+                    .unwrap_or_default()
+            }
+            fn named_parameters_mut(&mut self) -> Result<NamedParameterMutVec> {
+                if let Some(layer) = self.$field.get_mut() {
+                    layer.named_parameters_mut()
+                } else {
+                    Ok(NamedParameterMutVec::new())
+                }
+            }
+            fn transposed_parameter_names(&self) -> Vec<String> {
+                self.$field
+                    .get()
+                    .map(Layer::transposed_parameter_names)
+                    .unwrap_or_default()
+            }
+            fn cast_mut(&mut self, scalar_type: ScalarType) -> Result<()> {
+                if let Some(layer) = self.$field.get_mut() {
+                    return layer.cast_mut(scalar_type);
+                }
+                if !matches!(scalar_type, ScalarType::BF16 | ScalarType::F32) {
+                    bail!("{} {scalar_type:?} not implemented!", $name);
+                }
+                let builder = self.builder.get_mut();
+                *builder = builder.take().map(|b| b.scalar_type(scalar_type));
+                Ok(())
+            }
+            fn to_device_mut(&mut self, device: Device) -> Result<()> {
+                if let Some(layer) = self.$field.get_mut() {
+                    return layer.to_device_mut(device);
+                }
+                let builder = self.builder.get_mut();
+                *builder = builder.take().map(|b| b.device(device));
+                Ok(())
+            }
+            fn into_device(mut self, device: Device) -> Result<Self>
+            where
+                Self: Sized,
+            {
+                self.to_device_mut(device)?;
+                Ok(self)
+            }
+        }
+    };
+}
+
+/// A [`Conv`] that infers the input channels on the first forward.
+///
+/// Created with [`ConvBuilder::build_lazy()`]. The parameters are allocated on the
+/// first forward, with `inputs` set to the channels of the input. Prior to this,
+/// [`.parameters()`](Layer::parameters) is empty, so the first forward should be
+/// executed before collecting the parameters for the optimizer.
+///
+/// Unlike [`Conv`], LazyConv can not be serialized.
+pub struct LazyConv<D: Dimension, A = Identity> {
+    builder: Mutex<Option<ConvBuilder<D, A>>>,
+    training: bool,
+    conv: OnceCell<Conv<D, A>>,
+}
+
+/// LazyConv with 1 dimension.
+///
+/// See [`LazyConv`].
+pub type LazyConv1<A = Identity> = LazyConv<Ix1, A>;
+/// LazyConv with 2 dimensions.
+///
+/// See [`LazyConv`].
+pub type LazyConv2<A = Identity> = LazyConv<Ix2, A>;
+
+impl_lazy!(LazyConv<D: Dimension>, Conv, conv, "LazyConv");
+
+impl<D: Dimension, X: Dimension, A> Forward<Variable<X>> for LazyConv<D, A>
+where
+    Conv<D, A>: Forward<Variable<X>>,
+{
+    type Output = <Conv<D, A> as Forward<Variable<X>>>::Output;
+    fn forward(&self, input: Variable<X>) -> Result<Self::Output> {
+        let conv = self.conv.get_or_try_init(|| -> Result<_> {
+            let memory_format = self
+                .builder
+                .lock()
+                .as_ref()
+                .map_or(MemoryFormat::default(), |builder| builder.memory_format);
+            let channel_axis = match memory_format {
+                MemoryFormat::ChannelsFirst => 1,
+                MemoryFormat::ChannelsLast => input.shape().len().max(2) - 1,
+            };
+            let Some(inputs) = input.shape().get(channel_axis).copied() else {
+                bail!(
+                    "LazyConv expected input with channels, found {:?}!",
+                    input.shape()
+                );
+            };
+            let mut guard = self.builder.lock();
+            let Some(builder) = guard.take() else {
+                bail!("LazyConv failed to build!");
+            };
+            // Restore the builder on failure, so that a later forward can retry.
+            let mut conv = builder.inputs(inputs).try_build().map_err(|(builder, e)| {
+                guard.replace(builder);
+                e
+            })?;
+            conv.set_training(self.training)?;
+            Ok(conv)
+        })?;
+        conv.forward(input)
+    }
+}
+
+impl<D: Dimension, A: ToOnnx> ToOnnx for LazyConv<D, A> {
+    fn to_onnx(&self, graph: &mut OnnxGraph, input: String) -> Result<String> {
+        let Some(conv) = self.conv.get() else {
+            bail!("LazyConv can not be exported before the first forward!");
+        };
+        conv.to_onnx(graph, input)
+    }
+}
+
+/// A [`Dense`] that infers the input features on the first forward.
+///
+/// Created with [`DenseBuilder::build_lazy()`]. The parameters are allocated on the
+/// first forward, with `inputs` set to the features of the input. Prior to this,
+/// [`.parameters()`](Layer::parameters) is empty, so the first forward should be
+/// executed before collecting the parameters for the optimizer.
+///
+/// Unlike [`Dense`], LazyDense can not be serialized.
+///
+/// # Example
+///```no_run
+/// # use autograph::learn::neural_network::layer::{Dense, Relu};
+/// # fn main() -> anyhow::Result<()> {
+/// let dense = Dense::builder()
+///    .outputs(10)
+///    .activation(Relu)
+///    .build_lazy()?;
+/// # Ok(())
+/// # }
+///```
+pub struct LazyDense<A = Identity> {
+    builder: Mutex<Option<DenseBuilder<A>>>,
+    training: bool,
+    dense: OnceCell<Dense<A>>,
+}
+
+impl_lazy!(LazyDense, Dense, dense, "LazyDense");
+
+impl<A> Forward<Variable2> for LazyDense<A>
+where
+    Dense<A>: Forward<Variable2, Output = Variable2>,
+{
+    type Output = Variable2;
+    fn forward(&self, input: Variable2) -> Result<Variable2> {
+        let dense = self.dense.get_or_try_init(|| -> Result<_> {
+            let mut guard = self.builder.lock();
+            let Some(builder) = guard.take() else {
+                bail!("LazyDense failed to build!");
+            };
+            // Restore the builder on failure, so that a later forward can retry.
+            let mut dense = builder
+                .inputs(input.dim().1)
+                .try_build()
+                .map_err(|(builder, e)| {
+                    guard.replace(builder);
+                    e
+                })?;
+            dense.set_training(self.training)?;
+            Ok(dense)
+        })?;
+        dense.forward(input)
+    }
+}
+
+impl<A: ToOnnx> ToOnnx for LazyDense<A> {
+    fn to_onnx(&self, graph: &mut OnnxGraph, input: String) -> Result<String> {
+        let Some(dense) = self.dense.get() else {
+            bail!("LazyDense can not be exported before the first forward!");
+        };
+        dense.to_onnx(graph, input)
     }
 }
 
@@ -907,7 +4144,12 @@ impl<A: Forward<Variable2, Output = Variable2> + Any> Forward<Variable2> for Den
 ///
 /// See [`MaxPool1`] and [`MaxPool2`].
 /// Implemented for bf16 and f32.
-#[derive(Debug, Serialize, Deserialize)]
+///
+/// **Errors**
+///
+/// The forward pass fails if the filter is larger than the input, or the filter or stride
+/// is 0. See [`.max_pool2d()`](ScalarTensorBase::max_pool2d).
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct MaxPool<D: Dimension> {
     filter: D,
     stride: D,
@@ -929,6 +4171,19 @@ impl<D: Dimension> MaxPool<D> {
     }
 }
 
+impl<D: Dimension> MaxPool<D> {
+    /// The output shape given the `input_shape`.
+    ///
+    /// The shapes exclude the batch and channel dimensions, ie `[ih, iw]` -> `[oh, ow]`
+    /// for [`MaxPool2`].
+    ///
+    /// **Errors**
+    /// - The input is smaller than the filter.
+    pub fn output_shape(&self, input_shape: impl IntoDimension<Dim = D>) -> Result<D> {
+        LayerSpec::from(self).output_shape(input_shape)
+    }
+}
+
 impl<D: Dimension> Layer for MaxPool<D> {}
 
 impl Forward<Variable3> for MaxPool1 {
@@ -971,11 +4226,28 @@ impl Forward<Variable4> for MaxPool2 {
                 Ok(input)
             });
         }
-        let output = input.value().max_pool2(options)?;
+        let output = input.value().max_pool2d(&MaxPool2dOptions {
+            filter: [fh, fw],
+            stride: [sh, sw],
+        })?;
         Ok(builder.build(output.into()))
     }
 }
 
+impl<D: Dimension> ToOnnx for MaxPool<D> {
+    fn to_onnx(&self, graph: &mut OnnxGraph, input: String) -> Result<String> {
+        let ints = |dim: &D| dim.slice().iter().map(|x| *x as i64).collect::<Vec<_>>();
+        Ok(graph.node(
+            "MaxPool",
+            vec![input],
+            vec![
+                ("kernel_shape", ints(&self.filter).into()),
+                ("strides", ints(&self.stride).into()),
+            ],
+        ))
+    }
+}
+
 // for testing
 #[doc(hidden)]
 impl MaxPool2 {
@@ -1012,6 +4284,202 @@ impl<D: Dimension + 'static> Forward<Variable<D>> for Flatten {
     }
 }
 
+impl ToOnnx for Flatten {
+    fn to_onnx(&self, graph: &mut OnnxGraph, input: String) -> Result<String> {
+        Ok(graph.node("Flatten", vec![input], vec![("axis", 1i64.into())]))
+    }
+}
+
+/// Layer specification for [`infer_flatten_size()`].
+///
+/// Can be created from a [`Conv`] or a [`MaxPool`] via [`From`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum LayerSpec<D: Dimension> {
+    /// Convolution.
+    Conv {
+        /// The output channels.
+        outputs: usize,
+        /// The filter shape.
+        filter: D,
+        /// The padding.
+        padding: D,
+        /// The padding after the end of each spatial axis.
+        padding_after: D,
+        /// The stride.
+        stride: D,
+        /// The dilation.
+        dilation: D,
+    },
+    /// MaxPool.
+    MaxPool {
+        /// The filter shape.
+        filter: D,
+        /// The stride.
+        stride: D,
+    },
+}
+
+impl<D: Dimension, A> From<&Conv<D, A>> for LayerSpec<D> {
+    fn from(conv: &Conv<D, A>) -> Self {
+        let weight_shape = conv.weight.shape();
+        let mut filter = D::zeros(weight_shape.len() - 2);
+        filter.slice_mut().copy_from_slice(&weight_shape[2..]);
+        Self::Conv {
+            outputs: weight_shape[0],
+            filter,
+            padding: conv.padding.clone(),
+            padding_after: conv.padding_after(),
+            stride: conv.stride.clone(),
+            dilation: conv.dilation.clone(),
+        }
+    }
+}
+
+impl<D: Dimension> From<&MaxPool<D>> for LayerSpec<D> {
+    fn from(pool: &MaxPool<D>) -> Self {
+        Self::MaxPool {
+            filter: pool.filter.clone(),
+            stride: pool.stride.clone(),
+        }
+    }
+}
+
+impl<D: Dimension> LayerSpec<D> {
+    /// The output channels given the input `channels`.
+    pub fn output_channels(&self, channels: usize) -> usize {
+        match self {
+            Self::Conv { outputs, .. } => *outputs,
+            Self::MaxPool { .. } => channels,
+        }
+    }
+    /// The output shape given the `input_shape`.
+    ///
+    /// The shapes exclude the batch and channel dimensions.
+    ///
+    /// **Errors**
+    /// - The (padded) input is smaller than the (dilated) filter.
+    pub fn output_shape(&self, input_shape: impl IntoDimension<Dim = D>) -> Result<D> {
+        let input_shape = input_shape.into_dimension();
+        let mut shape = input_shape.clone();
+        match self {
+            Self::Conv {
+                filter,
+                padding,
+                padding_after,
+                stride,
+                dilation,
+                ..
+            } => {
+                for (((a, f), s), ((pb, pa), d)) in shape
+                    .slice_mut()
+                    .iter_mut()
+                    .zip(filter.slice())
+                    .zip(stride.slice())
+                    .zip(
+                        padding
+                            .slice()
+                            .iter()
+                            .zip(padding_after.slice())
+                            .zip(dilation.slice()),
+                    )
+                {
+                    let Some(x) = conv_output_size(*a, *f, (*pb, *pa), *s, *d) else {
+                        bail!("Conv input_shape {input_shape:?} is too small for filter {filter:?} with padding {padding:?} and dilation {dilation:?}!");
+                    };
+                    *a = x;
+                }
+            }
+            Self::MaxPool { filter, stride } => {
+                for ((a, f), s) in shape
+                    .slice_mut()
+                    .iter_mut()
+                    .zip(filter.slice())
+                    .zip(stride.slice())
+                {
+                    let Some(x) = conv_output_size(*a, *f, (0, 0), *s, 1) else {
+                        bail!("MaxPool input_shape {input_shape:?} is too small for filter {filter:?}!");
+                    };
+                    *a = x;
+                }
+            }
+        }
+        Ok(shape)
+    }
+}
+
+/// Infers the flattened size after applying `layers`.
+///
+/// Given the input `channels` and `input_shape`, excluding the batch dimension, computes the
+/// output of `layers` in sequence and returns the number of features after [`Flatten`]. This
+/// can be passed to [`DenseBuilder::inputs()`](builder::DenseBuilder::inputs).
+///
+/// **Errors**
+/// - An intermediate shape is too small for the next layer.
+///
+/// # Example
+///```no_run
+/// # use autograph::{anyhow::Result, learn::neural_network::layer::{infer_flatten_size, Conv2, MaxPool2}};
+/// # fn main() -> Result<()> {
+/// let conv = Conv2::builder().inputs(1).outputs(6).filter([5, 5]).build()?;
+/// let pool = MaxPool2::builder().filter([2, 2]).stride([2, 2]).build();
+/// let inputs = infer_flatten_size(1, [28, 28], &[(&conv).into(), (&pool).into()])?;
+/// assert_eq!(inputs, 6 * 12 * 12);
+/// # Ok(())
+/// # }
+///```
+pub fn infer_flatten_size<D: Dimension>(
+    channels: usize,
+    input_shape: impl IntoDimension<Dim = D>,
+    layers: &[LayerSpec<D>],
+) -> Result<usize> {
+    let mut channels = channels;
+    let mut shape = input_shape.into_dimension();
+    for layer in layers {
+        shape = layer.output_shape(shape)?;
+        channels = layer.output_channels(channels);
+    }
+    Ok(channels * shape.size())
+}
+
+/// Computes the gradient L2 norm of each layer.
+///
+/// Given `named_parameters`, ie from [`Layer::named_parameters()`], groups parameters by the
+/// name of their layer, ie "conv1" for "conv1.weight", and returns the L2 norm of their
+/// gradients after a backward pass. Parameters directly on the model are grouped under "".
+/// Parameters without a gradient are skipped, so layers without parameters or that are not
+/// training are absent. Useful for diagnosing which layers are learning.
+///
+/// **Errors**
+/// - The operation could not be executed on the device.
+///
+/// # Example
+///```no_run
+/// # use autograph::{anyhow::Result, learn::neural_network::layer::{grad_norms, Layer}};
+/// # fn main() -> Result<()> {
+/// # let model: autograph::learn::neural_network::layer::Dense = todo!();
+/// for (layer, norm) in grad_norms(&model.named_parameters())? {
+///     println!("{layer}: {norm}");
+/// }
+/// # Ok(())
+/// # }
+///```
+pub fn grad_norms(named_parameters: &[(String, ParameterD)]) -> Result<BTreeMap<String, f32>> {
+    let mut sums = BTreeMap::<String, f32>::new();
+    for (name, parameter) in named_parameters {
+        let Some(grad) = parameter.grad() else {
+            continue;
+        };
+        let layer = name.rsplit_once('.').map_or("", |(layer, _)| layer);
+        let grad = grad.cast_into_tensor::<f32>()?;
+        let sum = grad.mul(&grad)?.sum()?;
+        *sums.entry(layer.to_string()).or_default() += sum;
+    }
+    Ok(sums
+        .into_iter()
+        .map(|(layer, sum)| (layer, sum.sqrt()))
+        .collect())
+}
+
 /// Identity.
 #[derive(Default, Clone, Copy, Debug, Serialize, Deserialize)]
 pub struct Identity;
@@ -1025,6 +4493,12 @@ impl<X> Forward<X> for Identity {
     }
 }
 
+impl ToOnnx for Identity {
+    fn to_onnx(&self, _graph: &mut OnnxGraph, input: String) -> Result<String> {
+        Ok(input)
+    }
+}
+
 /// ReLU.
 ///
 /// Implemented for bf16 and f32.
@@ -1036,14 +4510,39 @@ impl Layer for Relu {}
 impl<D: Dimension + 'static> Forward<Variable<D>> for Relu {
     type Output = Variable<D>;
     fn forward(&self, input: Variable<D>) -> Result<Self::Output> {
+        self.forward_with_tuning(input, ElementwiseTuning::default())
+    }
+}
+
+impl Relu {
+    /// Forward with the launch configuration of the device kernels.
+    ///
+    /// Like [`.forward()`](Forward::forward), but the device kernels of the forward and backward
+    /// passes are dispatched with `tuning`. The output and gradient are the same for any `tuning`.
+    ///
+    /// **Errors**
+    /// - `threads` or `items_per_thread` of `tuning` is 0.
+    /// - See [`.forward()`](Forward::forward).
+    pub fn forward_with_tuning<D: Dimension + 'static>(
+        &self,
+        input: Variable<D>,
+        tuning: ElementwiseTuning,
+    ) -> Result<Variable<D>> {
+        tuning.check()?;
         let mut builder = Variable::builder();
         if let Some(node) = input.node() {
             let input = input.value().clone();
             builder.edge(node, move |output_grad| {
-                scalar_relu_backward(input, output_grad)
+                scalar_relu_backward(input, output_grad, tuning)
             });
         }
-        Ok(builder.build(scalar_relu(input.into_value())?))
+        Ok(builder.build(scalar_relu(input.into_value(), tuning)?))
+    }
+}
+
+impl ToOnnx for Relu {
+    fn to_onnx(&self, graph: &mut OnnxGraph, input: String) -> Result<String> {
+        Ok(graph.node("Relu", vec![input], Vec::new()))
     }
 }
 
@@ -1055,22 +4554,23 @@ impl Relu {
         output: ScalarArcTensor<D>,
         output_grad: ScalarArcTensor<D>,
     ) -> Result<ScalarArcTensor<D>> {
-        scalar_relu_backward(output, output_grad)
+        scalar_relu_backward(output, output_grad, ElementwiseTuning::default())
     }
 }
 
 fn scalar_relu<S: ScalarData, D: Dimension>(
     mut input: ScalarTensorBase<S, D>,
+    tuning: ElementwiseTuning,
 ) -> Result<ScalarArcTensor<D>> {
     let scalar_type = input.scalar_type();
     if input.is_standard_layout() {
         if let Some(input_mut) = input.get_view_mut() {
             match scalar_type {
                 ScalarType::BF16 => {
-                    relu_mut::<bf16, D>(input_mut.try_into().unwrap())?;
+                    relu_mut::<bf16, D>(input_mut.try_into().unwrap(), tuning)?;
                 }
                 ScalarType::F32 => {
-                    relu_mut::<f32, D>(input_mut.try_into().unwrap())?;
+                    relu_mut::<f32, D>(input_mut.try_into().unwrap(), tuning)?;
                 }
                 _ => bail!("relu {scalar_type:?} unimplemented!"),
             }
@@ -1078,17 +4578,37 @@ fn scalar_relu<S: ScalarData, D: Dimension>(
         }
     }
     match scalar_type {
-        ScalarType::BF16 => Ok(relu::<bf16, D>(input.view().try_into().unwrap())?
+        ScalarType::BF16 => Ok(relu::<bf16, D>(input.view().try_into().unwrap(), tuning)?
             .into_shared()?
             .into()),
-        ScalarType::F32 => Ok(relu::<f32, D>(input.view().try_into().unwrap())?
+        ScalarType::F32 => Ok(relu::<f32, D>(input.view().try_into().unwrap(), tuning)?
             .into_shared()?
             .into()),
         _ => bail!("Relu {scalar_type:?} unimplemented!()"),
     }
 }
 
-fn relu_mut<T: Scalar, D: Dimension>(mut input: TensorViewMut<T, D>) -> Result<()> {
+// Builds an elementwise kernel for `items` elements, see `ElementwiseTuning`.
+#[cfg(feature = "device")]
+macro_rules! build_elementwise_kernel {
+    ($builder:expr, $device:expr, $items:expr, $tuning:expr) => {{
+        let tuning: ElementwiseTuning = $tuning;
+        let mut builder = $builder;
+        if let Some(threads) = tuning.threads {
+            builder = builder.with_threads(threads);
+        }
+        let mut kernel = builder.build($device)?;
+        if let Some(global_threads) = tuning.global_threads($items) {
+            kernel = kernel.with_global_threads(global_threads);
+        }
+        kernel
+    }};
+}
+
+fn relu_mut<T: Scalar, D: Dimension>(
+    mut input: TensorViewMut<T, D>,
+    #[allow(unused_variables)] tuning: ElementwiseTuning,
+) -> Result<()> {
     if let Some(mut x) = input.as_array_mut() {
         for x in x.iter_mut() {
             *x = relu_impl(*x);
@@ -1102,15 +4622,14 @@ fn relu_mut<T: Scalar, D: Dimension>(mut input: TensorViewMut<T, D>) -> Result<(
     #[cfg(feature = "device")]
     {
         let device = input.device();
+        let items = input.len();
         let mut x = input.as_slice_mut().unwrap();
         macro_for!($T in [bf16, f32] {
             if let Ok(x) = x.as_scalar_slice_mut().try_into() {
                 let kernel = paste! {
-                    kernels::[<relu_mut_ $T>]::builder()?
-                    .build(device)?
+                    build_elementwise_kernel!(kernels::[<relu_mut_ $T>]::builder()?, device, items, tuning)
                 };
-                kernel
-                    .dispatch(x)?;
+                kernel.dispatch(x)?;
                 return Ok(());
             }
         });
@@ -1118,7 +4637,10 @@ fn relu_mut<T: Scalar, D: Dimension>(mut input: TensorViewMut<T, D>) -> Result<(
     }
 }
 
-fn relu<T: Scalar, D: Dimension>(input: TensorView<T, D>) -> Result<Tensor<T, D>> {
+fn relu<T: Scalar, D: Dimension>(
+    input: TensorView<T, D>,
+    #[allow(unused_variables)] tuning: ElementwiseTuning,
+) -> Result<Tensor<T, D>> {
     let scalar_type = T::scalar_type();
     if !matches!(scalar_type, ScalarType::BF16 | ScalarType::F32) {
         bail!("Relu {scalar_type:?} unimplemented!");
@@ -1136,9 +4658,12 @@ fn relu<T: Scalar, D: Dimension>(input: TensorView<T, D>) -> Result<Tensor<T, D>
         macro_for!($T in [bf16, f32] {
             if scalar_type == $T::scalar_type() {
                 let mut output = unsafe { Tensor::<$T, D>::uninit(input.device(), input.raw_dim())? };
+                let input = input.contiguous()?;
                 let x = input.as_slice().unwrap();
                 let mut y = output.as_slice_mut().unwrap();
-                let kernel = paste!{ kernels::[<relu_ $T>]::builder()?.build(input.device())? };
+                let kernel = paste! {
+                    build_elementwise_kernel!(kernels::[<relu_ $T>]::builder()?, input.device(), x.len(), tuning)
+                };
                 kernel.dispatch(
                     x.as_scalar_slice().try_into().unwrap(),
                     y.as_scalar_slice_mut().try_into().unwrap(),
@@ -1153,20 +4678,28 @@ fn relu<T: Scalar, D: Dimension>(input: TensorView<T, D>) -> Result<Tensor<T, D>
 fn scalar_relu_backward<D: Dimension>(
     output: ScalarArcTensor<D>,
     mut output_grad: ScalarArcTensor<D>,
+    tuning: ElementwiseTuning,
 ) -> Result<ScalarArcTensor<D>> {
     let scalar_type = output.scalar_type();
-    if let Some(output_grad_mut) = output_grad.get_view_mut() {
+    let output_grad_mut = if output_grad.is_standard_layout() {
+        output_grad.get_view_mut()
+    } else {
+        None
+    };
+    if let Some(output_grad_mut) = output_grad_mut {
         match scalar_type {
             ScalarType::BF16 => {
                 relu_backward_mut::<bf16, D>(
                     output.view().try_into().unwrap(),
                     output_grad_mut.try_into().unwrap(),
+                    tuning,
                 )?;
             }
             ScalarType::F32 => {
                 relu_backward_mut::<f32, D>(
                     output.view().try_into().unwrap(),
                     output_grad_mut.try_into().unwrap(),
+                    tuning,
                 )?;
             }
             _ => unreachable!(),
@@ -1177,12 +4710,14 @@ fn scalar_relu_backward<D: Dimension>(
             ScalarType::BF16 => Ok(relu_backward::<bf16, D>(
                 output.view().try_into().unwrap(),
                 output_grad.view().try_into().unwrap(),
+                tuning,
             )?
             .into_shared()?
             .into()),
             ScalarType::F32 => Ok(relu_backward::<f32, D>(
                 output.view().try_into().unwrap(),
                 output_grad.view().try_into().unwrap(),
+                tuning,
             )?
             .into_shared()?
             .into()),
@@ -1194,6 +4729,7 @@ fn scalar_relu_backward<D: Dimension>(
 fn relu_backward_mut<T: Scalar, D: Dimension>(
     input: TensorView<T, D>,
     mut output_grad: TensorViewMut<T, D>,
+    #[allow(unused_variables)] tuning: ElementwiseTuning,
 ) -> Result<()> {
     if let Some((x, mut dy)) = input.as_array().zip(output_grad.as_array_mut()) {
         dy.zip_mut_with(&x, |dy, x| {
@@ -1207,6 +4743,7 @@ fn relu_backward_mut<T: Scalar, D: Dimension>(
     }
     #[cfg(feature = "device")]
     {
+        let input = input.contiguous()?;
         let x = input.as_slice().unwrap();
         let mut dy = output_grad.as_slice_mut().unwrap();
         macro_for!($T in [bf16, f32] {
@@ -1217,8 +4754,12 @@ fn relu_backward_mut<T: Scalar, D: Dimension>(
                 .zip(dy.as_scalar_slice_mut().try_into().ok())
             {
                 let kernel = paste! {
-                    kernels::[<relu_backward_mut_ $T>]::builder()?
-                    .build(input.device())?
+                    build_elementwise_kernel!(
+                        kernels::[<relu_backward_mut_ $T>]::builder()?,
+                        input.device(),
+                        input.len(),
+                        tuning
+                    )
                 };
                 kernel.dispatch(x, dy)?;
                 return Ok(());
@@ -1234,6 +4775,7 @@ fn relu_backward_mut<T: Scalar, D: Dimension>(
 fn relu_backward<T: Scalar, D: Dimension>(
     input: TensorView<T, D>,
     output_grad: TensorView<T, D>,
+    #[allow(unused_variables)] tuning: ElementwiseTuning,
 ) -> Result<Tensor<T, D>> {
     if let Some((x, dy)) = input.as_array().zip(output_grad.as_array()) {
         let dx: Vec<T> = x
@@ -1250,6 +4792,8 @@ fn relu_backward<T: Scalar, D: Dimension>(
     }
     #[cfg(feature = "device")]
     {
+        let input = input.contiguous()?;
+        let output_grad = output_grad.contiguous()?;
         let x = input.as_slice().unwrap();
         let dy = output_grad.as_slice().unwrap();
         macro_for!($T in [bf16, f32] {
@@ -1264,8 +4808,12 @@ fn relu_backward<T: Scalar, D: Dimension>(
                     .try_into()
                     .unwrap();
                 let kernel = paste! {
-                    kernels::[<relu_backward_ $T>]::builder()?
-                        .build(input.device())?
+                    build_elementwise_kernel!(
+                        kernels::[<relu_backward_ $T>]::builder()?,
+                        input.device(),
+                        input.len(),
+                        tuning
+                    )
                 };
                 kernel.dispatch(x, dy, dx)?;
                 return Ok(input_grad);
@@ -1275,6 +4823,333 @@ fn relu_backward<T: Scalar, D: Dimension>(
     }
 }
 
+/// SiLU (Swish).
+///
+/// `x * sigmoid(x)`.
+///
+/// Implemented for bf16 and f32.
+#[derive(Default, Clone, Copy, Debug, Serialize, Deserialize)]
+pub struct Silu;
+
+impl Layer for Silu {}
+
+impl<D: Dimension + 'static> Forward<Variable<D>> for Silu {
+    type Output = Variable<D>;
+    fn forward(&self, input: Variable<D>) -> Result<Self::Output> {
+        activation_forward(kernels::SILU, 0., input)
+    }
+}
+
+impl ToOnnx for Silu {
+    fn to_onnx(&self, graph: &mut OnnxGraph, input: String) -> Result<String> {
+        let sigmoid = graph.node("Sigmoid", vec![input.clone()], Vec::new());
+        Ok(graph.node("Mul", vec![input, sigmoid], Vec::new()))
+    }
+}
+
+/// ReLU6.
+///
+/// `min(max(x, 0), 6)`, used in MobileNet. The gradient is 0 outside of (0, 6).
+///
+/// Implemented for bf16 and f32.
+#[derive(Default, Clone, Copy, Debug, Serialize, Deserialize)]
+pub struct Relu6;
+
+impl Layer for Relu6 {}
+
+impl<D: Dimension + 'static> Forward<Variable<D>> for Relu6 {
+    type Output = Variable<D>;
+    fn forward(&self, input: Variable<D>) -> Result<Self::Output> {
+        activation_forward(kernels::RELU6, 0., input)
+    }
+}
+
+impl ToOnnx for Relu6 {
+    fn to_onnx(&self, graph: &mut OnnxGraph, input: String) -> Result<String> {
+        let min = graph.initializer(ScalarArcTensor::from_elem(
+            Device::host(),
+            (),
+            ScalarElem::F32(0.),
+        )?)?;
+        let max = graph.initializer(ScalarArcTensor::from_elem(
+            Device::host(),
+            (),
+            ScalarElem::F32(6.),
+        )?)?;
+        Ok(graph.node("Clip", vec![input, min, max], Vec::new()))
+    }
+}
+
+/// Hard sigmoid.
+///
+/// `min(max(x / 6 + 0.5, 0), 1)`, a piecewise linear approximation of the sigmoid used in
+/// MobileNetV3. The gradient is 0 outside of (-3, 3).
+///
+/// Implemented for bf16 and f32.
+#[derive(Default, Clone, Copy, Debug, Serialize, Deserialize)]
+pub struct HardSigmoid;
+
+impl Layer for HardSigmoid {}
+
+impl<D: Dimension + 'static> Forward<Variable<D>> for HardSigmoid {
+    type Output = Variable<D>;
+    fn forward(&self, input: Variable<D>) -> Result<Self::Output> {
+        activation_forward(kernels::HARD_SIGMOID, 0., input)
+    }
+}
+
+impl ToOnnx for HardSigmoid {
+    fn to_onnx(&self, graph: &mut OnnxGraph, input: String) -> Result<String> {
+        Ok(graph.node(
+            "HardSigmoid",
+            vec![input],
+            vec![("alpha", (1f32 / 6.).into()), ("beta", 0.5f32.into())],
+        ))
+    }
+}
+
+/// Activation selected at runtime.
+///
+/// [`Conv`] and [`Dense`] take the activation as a type parameter, like `Conv2<Relu>`. When the
+/// activation is not known at compile time, for example when a model is built from a config,
+/// use [`Activation`] instead, ie `Conv2<Activation>`. See
+/// [`ConvBuilder::activation_kind()`](builder::ConvBuilder::activation_kind) and
+/// [`DenseBuilder::activation_kind()`](builder::DenseBuilder::activation_kind).
+///
+/// Implemented for bf16 and f32.
+#[derive(Default, Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Activation {
+    /// Identity.
+    #[default]
+    None,
+    /// See [`Relu`].
+    Relu,
+    /// `x` if `x >= 0`, otherwise `alpha * x`.
+    LeakyRelu(f32),
+    /// `1 / (1 + exp(-x))`.
+    Sigmoid,
+    /// `tanh(x)`.
+    Tanh,
+    /// GELU with the tanh approximation.
+    ///
+    /// `0.5 * x * (1 + tanh(sqrt(2 / pi) * (x + 0.044715 * x^3)))`.
+    Gelu,
+    /// See [`Silu`].
+    Silu,
+    /// See [`Relu6`].
+    Relu6,
+    /// See [`HardSigmoid`].
+    HardSigmoid,
+}
+
+impl Layer for Activation {}
+
+impl<D: Dimension + 'static> Forward<Variable<D>> for Activation {
+    type Output = Variable<D>;
+    fn forward(&self, input: Variable<D>) -> Result<Self::Output> {
+        let (kind, alpha) = match *self {
+            Self::None => return Ok(input),
+            Self::Relu => return Relu.forward(input),
+            Self::LeakyRelu(alpha) => (kernels::LEAKY_RELU, alpha),
+            Self::Sigmoid => (kernels::SIGMOID, 0.),
+            Self::Tanh => (kernels::TANH, 0.),
+            Self::Gelu => (kernels::GELU, 0.),
+            Self::Silu => (kernels::SILU, 0.),
+            Self::Relu6 => (kernels::RELU6, 0.),
+            Self::HardSigmoid => (kernels::HARD_SIGMOID, 0.),
+        };
+        activation_forward(kind, alpha, input)
+    }
+}
+
+impl ToOnnx for Activation {
+    fn to_onnx(&self, graph: &mut OnnxGraph, input: String) -> Result<String> {
+        let scalar = |graph: &mut OnnxGraph, x: f32| {
+            graph.initializer(ScalarArcTensor::from_elem(
+                Device::host(),
+                (),
+                ScalarElem::F32(x),
+            )?)
+        };
+        let output = match *self {
+            Self::None => input,
+            Self::Relu => Relu.to_onnx(graph, input)?,
+            Self::LeakyRelu(alpha) => {
+                graph.node("LeakyRelu", vec![input], vec![("alpha", alpha.into())])
+            }
+            Self::Sigmoid => graph.node("Sigmoid", vec![input], Vec::new()),
+            Self::Tanh => graph.node("Tanh", vec![input], Vec::new()),
+            Self::Gelu => {
+                // Gelu requires opset 20
+                let x2 = graph.node("Mul", vec![input.clone(), input.clone()], Vec::new());
+                let x3 = graph.node("Mul", vec![x2, input.clone()], Vec::new());
+                let c = scalar(graph, kernels::GELU_C)?;
+                let cx3 = graph.node("Mul", vec![c, x3], Vec::new());
+                let u = graph.node("Add", vec![input.clone(), cx3], Vec::new());
+                let a = scalar(graph, kernels::GELU_A)?;
+                let u = graph.node("Mul", vec![a, u], Vec::new());
+                let t = graph.node("Tanh", vec![u], Vec::new());
+                let one = scalar(graph, 1.)?;
+                let t = graph.node("Add", vec![one, t], Vec::new());
+                let half = scalar(graph, 0.5)?;
+                let x = graph.node("Mul", vec![half, input], Vec::new());
+                graph.node("Mul", vec![x, t], Vec::new())
+            }
+            Self::Silu => Silu.to_onnx(graph, input)?,
+            Self::Relu6 => Relu6.to_onnx(graph, input)?,
+            Self::HardSigmoid => HardSigmoid.to_onnx(graph, input)?,
+        };
+        Ok(output)
+    }
+}
+
+fn activation_forward<D: Dimension + 'static>(
+    kind: u32,
+    alpha: f32,
+    input: Variable<D>,
+) -> Result<Variable<D>> {
+    let mut builder = Variable::builder();
+    if let Some(node) = input.node() {
+        let input = input.value().clone();
+        builder.edge(node, move |output_grad| {
+            scalar_activation_backward(kind, alpha, input, output_grad)
+        });
+    }
+    Ok(builder.build(scalar_activation(kind, alpha, input.value().view())?))
+}
+
+fn scalar_activation<D: Dimension>(
+    kind: u32,
+    alpha: f32,
+    input: ScalarTensorView<D>,
+) -> Result<ScalarArcTensor<D>> {
+    match input.scalar_type() {
+        ScalarType::BF16 => Ok(
+            activation::<bf16, D>(kind, alpha, input.try_into().unwrap())?
+                .into_shared()?
+                .into(),
+        ),
+        ScalarType::F32 => Ok(
+            activation::<f32, D>(kind, alpha, input.try_into().unwrap())?
+                .into_shared()?
+                .into(),
+        ),
+        scalar_type => bail!("Activation {scalar_type:?} unimplemented!()"),
+    }
+}
+
+fn activation<T: Scalar, D: Dimension>(
+    kind: u32,
+    alpha: f32,
+    input: TensorView<T, D>,
+) -> Result<Tensor<T, D>> {
+    if let Some(x) = input.as_array() {
+        return Ok(x.map(|x| activation_impl(kind, alpha, *x)).into());
+    }
+    #[cfg(not(feature = "device"))]
+    {
+        unreachable!()
+    }
+    #[cfg(feature = "device")]
+    {
+        let input = input.contiguous()?;
+        let x = input.as_slice().unwrap();
+        macro_for!($T in [bf16, f32] {
+            if let Ok(x) = x.as_scalar_slice().try_into() {
+                let mut output = unsafe { Tensor::uninit(input.device(), input.raw_dim())? };
+                let y = ScalarSliceMut::from(output.as_slice_mut().unwrap())
+                    .try_into()
+                    .unwrap();
+                let kernel = paste! {
+                    kernels::[<activation_ $T>]::builder()?
+                        .specialize(kind)
+                        .build(input.device())?
+                };
+                kernel.dispatch(alpha, x, y)?;
+                return Ok(output);
+            }
+        });
+        bail!("activation {:?} unimplemented!()", input.scalar_type());
+    }
+}
+
+fn scalar_activation_backward<D: Dimension>(
+    kind: u32,
+    alpha: f32,
+    input: ScalarArcTensor<D>,
+    output_grad: ScalarArcTensor<D>,
+) -> Result<ScalarArcTensor<D>> {
+    match input.scalar_type() {
+        ScalarType::BF16 => Ok(activation_backward::<bf16, D>(
+            kind,
+            alpha,
+            input.view().try_into().unwrap(),
+            output_grad.view().try_into().unwrap(),
+        )?
+        .into_shared()?
+        .into()),
+        ScalarType::F32 => Ok(activation_backward::<f32, D>(
+            kind,
+            alpha,
+            input.view().try_into().unwrap(),
+            output_grad.view().try_into().unwrap(),
+        )?
+        .into_shared()?
+        .into()),
+        _ => unreachable!(),
+    }
+}
+
+fn activation_backward<T: Scalar, D: Dimension>(
+    kind: u32,
+    alpha: f32,
+    input: TensorView<T, D>,
+    output_grad: TensorView<T, D>,
+) -> Result<Tensor<T, D>> {
+    if let Some((x, dy)) = input.as_array().zip(output_grad.as_array()) {
+        let mut dx = dy.to_owned();
+        dx.zip_mut_with(&x, |dy, x| {
+            *dy = activation_backward_impl(kind, alpha, *x, *dy);
+        });
+        return Ok(dx.into());
+    }
+    #[cfg(not(feature = "device"))]
+    {
+        unreachable!()
+    }
+    #[cfg(feature = "device")]
+    {
+        let input = input.contiguous()?;
+        let output_grad = output_grad.contiguous()?;
+        let x = input.as_slice().unwrap();
+        let dy = output_grad.as_slice().unwrap();
+        macro_for!($T in [bf16, f32] {
+            if let Some((x, dy)) = x
+                .as_scalar_slice()
+                .try_into()
+                .ok()
+                .zip(dy.as_scalar_slice().try_into().ok())
+            {
+                let mut input_grad = unsafe { Tensor::uninit(input.device(), input.raw_dim())? };
+                let dx = ScalarSliceMut::from(input_grad.as_slice_mut().unwrap())
+                    .try_into()
+                    .unwrap();
+                let kernel = paste! {
+                    kernels::[<activation_backward_ $T>]::builder()?
+                        .specialize(kind)
+                        .build(input.device())?
+                };
+                kernel.dispatch(alpha, x, dy, dx)?;
+                return Ok(input_grad);
+            }
+        });
+        bail!(
+            "activation_backward {:?} unimplemented!()",
+            input.scalar_type()
+        );
+    }
+}
+
 #[cfg_attr(feature = "device", module)]
 mod kernels {
     #[cfg(any(feature = "device", target_arch = "spirv"))]
@@ -1285,6 +5160,8 @@ mod kernels {
     use krnl_core::half::bf16;
     #[cfg(any(feature = "device", target_arch = "spirv"))]
     use krnl_core::macros::kernel;
+    #[cfg(target_arch = "spirv")]
+    use krnl_core::num_traits::Float;
     use krnl_core::scalar::Scalar;
     #[cfg(any(feature = "device", target_arch = "spirv"))]
     use paste::paste;
@@ -1305,6 +5182,89 @@ mod kernels {
         }
     }
 
+    pub const LEAKY_RELU: u32 = 1;
+    pub const SIGMOID: u32 = 2;
+    pub const TANH: u32 = 3;
+    pub const GELU: u32 = 4;
+    pub const SILU: u32 = 5;
+    pub const RELU6: u32 = 6;
+    pub const HARD_SIGMOID: u32 = 7;
+
+    // sqrt(2 / pi)
+    pub const GELU_A: f32 = core::f32::consts::FRAC_2_SQRT_PI * core::f32::consts::FRAC_1_SQRT_2;
+    pub const GELU_C: f32 = 0.044715;
+
+    fn sigmoid_impl(x: f32) -> f32 {
+        1. / (1. + (-x).exp())
+    }
+
+    pub fn activation_impl<T: Scalar>(kind: u32, alpha: f32, x: T) -> T {
+        let x = x.cast::<f32>();
+        let y = match kind {
+            LEAKY_RELU => {
+                if x >= 0. {
+                    x
+                } else {
+                    alpha * x
+                }
+            }
+            SIGMOID => sigmoid_impl(x),
+            TANH => x.tanh(),
+            GELU => 0.5 * x * (1. + (GELU_A * (x + GELU_C * x * x * x)).tanh()),
+            SILU => x * sigmoid_impl(x),
+            RELU6 => x.max(0.).min(6.),
+            HARD_SIGMOID => (x / 6. + 0.5).max(0.).min(1.),
+            _ => x,
+        };
+        y.cast()
+    }
+
+    pub fn activation_backward_impl<T: Scalar>(kind: u32, alpha: f32, x: T, dy: T) -> T {
+        let x = x.cast::<f32>();
+        let dy = dy.cast::<f32>();
+        let dx = match kind {
+            LEAKY_RELU => {
+                if x >= 0. {
+                    dy
+                } else {
+                    alpha * dy
+                }
+            }
+            SIGMOID => {
+                let s = sigmoid_impl(x);
+                dy * s * (1. - s)
+            }
+            TANH => {
+                let t = x.tanh();
+                dy * (1. - t * t)
+            }
+            GELU => {
+                let t = (GELU_A * (x + GELU_C * x * x * x)).tanh();
+                dy * (0.5 * (1. + t) + 0.5 * x * (1. - t * t) * GELU_A * (1. + 3. * GELU_C * x * x))
+            }
+            SILU => {
+                let s = sigmoid_impl(x);
+                dy * s * (1. + x * (1. - s))
+            }
+            RELU6 => {
+                if x > 0. && x < 6. {
+                    dy
+                } else {
+                    0.
+                }
+            }
+            HARD_SIGMOID => {
+                if x > -3. && x < 3. {
+                    dy / 6.
+                } else {
+                    0.
+                }
+            }
+            _ => dy,
+        };
+        dx.cast()
+    }
+
     #[cfg(any(feature = "device", target_arch = "spirv"))]
     macro_for!($T in [bf16, f32] {
         paste! {
@@ -1327,7 +5287,22 @@ mod kernels {
             pub fn [<relu_backward_ $T>](#[item] x: $T, #[item] dy: $T, #[item] dx: &mut $T) {
                 *dx = relu_backward_impl(x, dy);
             }
+
+            #[kernel]
+            pub fn [<activation_ $T>]<const KIND: u32>(alpha: f32, #[item] x: $T, #[item] y: &mut $T) {
+                *y = activation_impl(KIND, alpha, x);
+            }
+
+            #[kernel]
+            pub fn [<activation_backward_ $T>]<const KIND: u32>(
+                alpha: f32,
+                #[item] x: $T,
+                #[item] dy: $T,
+                #[item] dx: &mut $T,
+            ) {
+                *dx = activation_backward_impl(KIND, alpha, x, dy);
+            }
         }
     });
 }
-use kernels::{relu_backward_impl, relu_impl};
+use kernels::{activation_backward_impl, activation_impl, relu_backward_impl, relu_impl};
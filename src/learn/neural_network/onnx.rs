@@ -0,0 +1,338 @@
+/*!
+Layers implement [`ToOnnx`] to append their operations to an [`OnnxGraph`]. [`ToOnnx`] can be
+[derived](autograph_derive) for structs and enums, like [`Forward`](super::layer::Forward).
+
+Models are exported to [ONNX](https://onnx.ai) for inference only, and parameters are exported
+as f32. The supported operations are Conv, Gemm (Dense), MaxPool, Flatten, Softmax, and
+activations.
+
+# Example
+```no_run
+# use autograph::{anyhow::Result, learn::neural_network::{layer::{Conv2, Dense, Flatten, MaxPool2, Relu, Layer, Forward}, onnx::{self, ToOnnx}, autograd::{Variable4, Variable2}}};
+#[derive(Layer, Forward, ToOnnx)]
+#[autograph(forward(Variable4, Output=Variable2))]
+struct Network {
+    conv: Conv2<Relu>,
+    pool: MaxPool2,
+    flatten: Flatten,
+    dense: Dense,
+}
+# fn main() -> Result<()> {
+# let network: Network = todo!();
+onnx::save(&network, [1, 1, 28, 28], "network.onnx")?;
+# Ok(())
+# }
+```
+*/
+
+use crate::tensor::ScalarArcTensor;
+use anyhow::{bail, Result};
+pub use autograph_derive::ToOnnx;
+use ndarray::{Dimension, IntoDimension};
+use std::{fs, path::Path};
+
+/// The ONNX IR version.
+const IR_VERSION: i64 = 8;
+/// The ONNX opset version.
+const OPSET_VERSION: i64 = 13;
+/// TensorProto.DataType.FLOAT
+const FLOAT: i64 = 1;
+
+/// Exports to ONNX.
+///
+/// Can be [derived](autograph_derive).
+pub trait ToOnnx {
+    /// Appends the operations of the layer to `graph`.
+    ///
+    /// Returns the name of the output given the name of the `input`.
+    fn to_onnx(&self, graph: &mut OnnxGraph, input: String) -> Result<String>;
+}
+
+impl<T: ToOnnx> ToOnnx for Option<T> {
+    fn to_onnx(&self, graph: &mut OnnxGraph, input: String) -> Result<String> {
+        if let Some(layer) = self.as_ref() {
+            layer.to_onnx(graph, input)
+        } else {
+            Ok(input)
+        }
+    }
+}
+
+impl<T: ToOnnx> ToOnnx for Vec<T> {
+    fn to_onnx(&self, graph: &mut OnnxGraph, mut input: String) -> Result<String> {
+        for layer in self.iter() {
+            input = layer.to_onnx(graph, input)?;
+        }
+        Ok(input)
+    }
+}
+
+/// Attribute of an ONNX node.
+#[derive(Clone, Debug, PartialEq)]
+pub enum OnnxAttribute {
+    /// Float.
+    Float(f32),
+    /// Int.
+    Int(i64),
+    /// Ints.
+    Ints(Vec<i64>),
+}
+
+impl From<f32> for OnnxAttribute {
+    fn from(x: f32) -> Self {
+        Self::Float(x)
+    }
+}
+
+impl From<i64> for OnnxAttribute {
+    fn from(x: i64) -> Self {
+        Self::Int(x)
+    }
+}
+
+impl From<Vec<i64>> for OnnxAttribute {
+    fn from(x: Vec<i64>) -> Self {
+        Self::Ints(x)
+    }
+}
+
+#[derive(Debug)]
+struct OnnxNode {
+    op_type: String,
+    inputs: Vec<String>,
+    output: String,
+    attributes: Vec<(String, OnnxAttribute)>,
+}
+
+#[derive(Debug)]
+struct OnnxInitializer {
+    name: String,
+    dims: Vec<i64>,
+    data: Vec<f32>,
+}
+
+/// An ONNX graph.
+///
+/// Created with [`OnnxGraph::new()`] and populated with [`ToOnnx::to_onnx()`].
+#[derive(Debug)]
+pub struct OnnxGraph {
+    input_shape: Vec<i64>,
+    nodes: Vec<OnnxNode>,
+    initializers: Vec<OnnxInitializer>,
+}
+
+impl OnnxGraph {
+    /// Creates a new graph with an f32 input of shape `input_shape`.
+    pub fn new(input_shape: impl IntoDimension) -> Self {
+        let input_shape = input_shape
+            .into_dimension()
+            .slice()
+            .iter()
+            .map(|x| *x as i64)
+            .collect();
+        Self {
+            input_shape,
+            nodes: Vec::new(),
+            initializers: Vec::new(),
+        }
+    }
+    /// The name of the input.
+    pub fn input(&self) -> String {
+        "input".into()
+    }
+    /// Adds a node with `op_type`, `inputs`, and `attributes`.
+    ///
+    /// Returns the name of the output.
+    pub fn node(
+        &mut self,
+        op_type: &str,
+        inputs: Vec<String>,
+        attributes: Vec<(&str, OnnxAttribute)>,
+    ) -> String {
+        let output = format!("{}_{}", op_type.to_lowercase(), self.nodes.len());
+        self.nodes.push(OnnxNode {
+            op_type: op_type.into(),
+            inputs,
+            output: output.clone(),
+            attributes: attributes
+                .into_iter()
+                .map(|(name, attribute)| (name.into(), attribute))
+                .collect(),
+        });
+        output
+    }
+    /// Adds a constant `tensor`, ie a parameter.
+    ///
+    /// The tensor is cast to f32. Returns the name of the initializer.
+    ///
+    /// **Errors**
+    /// - The tensor could not be cast or transferred to the host.
+    pub fn initializer<D: Dimension>(&mut self, tensor: ScalarArcTensor<D>) -> Result<String> {
+        let name = format!("initializer_{}", self.initializers.len());
+        let dims = tensor.shape().iter().map(|x| *x as i64).collect();
+        let data = tensor.cast_into_tensor::<f32>()?.into_array()?;
+        let data = data.iter().copied().collect();
+        self.initializers.push(OnnxInitializer {
+            name: name.clone(),
+            dims,
+            data,
+        });
+        Ok(name)
+    }
+    /// Adds a Softmax over the last axis.
+    ///
+    /// Returns the name of the output.
+    pub fn softmax(&mut self, input: String) -> String {
+        self.node(
+            "Softmax",
+            vec![input],
+            vec![("axis", OnnxAttribute::Int(-1))],
+        )
+    }
+    /// Encodes the graph as an ONNX model with `output`.
+    ///
+    /// **Errors**
+    /// - `output` is not the input or the output of a node.
+    pub fn to_bytes(&self, output: &str) -> Result<Vec<u8>> {
+        if output != self.input() && !self.nodes.iter().any(|node| node.output == output) {
+            bail!("OnnxGraph output {output:?} not found!");
+        }
+        let mut graph = Vec::new();
+        for node in self.nodes.iter() {
+            let mut bytes = Vec::new();
+            for input in node.inputs.iter() {
+                write_bytes(&mut bytes, 1, input.as_bytes());
+            }
+            write_bytes(&mut bytes, 2, node.output.as_bytes());
+            write_bytes(&mut bytes, 3, node.output.as_bytes());
+            write_bytes(&mut bytes, 4, node.op_type.as_bytes());
+            for (name, attribute) in node.attributes.iter() {
+                let mut attribute_bytes = Vec::new();
+                write_bytes(&mut attribute_bytes, 1, name.as_bytes());
+                match attribute {
+                    OnnxAttribute::Float(x) => {
+                        write_key(&mut attribute_bytes, 2, 5);
+                        attribute_bytes.extend_from_slice(&x.to_le_bytes());
+                        write_int(&mut attribute_bytes, 20, 1);
+                    }
+                    OnnxAttribute::Int(x) => {
+                        write_int(&mut attribute_bytes, 3, *x);
+                        write_int(&mut attribute_bytes, 20, 2);
+                    }
+                    OnnxAttribute::Ints(xs) => {
+                        for x in xs.iter() {
+                            write_int(&mut attribute_bytes, 8, *x);
+                        }
+                        write_int(&mut attribute_bytes, 20, 7);
+                    }
+                }
+                write_bytes(&mut bytes, 5, &attribute_bytes);
+            }
+            write_bytes(&mut graph, 1, &bytes);
+        }
+        write_bytes(&mut graph, 2, b"autograph");
+        for initializer in self.initializers.iter() {
+            let mut bytes = Vec::new();
+            for dim in initializer.dims.iter() {
+                write_int(&mut bytes, 1, *dim);
+            }
+            write_int(&mut bytes, 2, FLOAT);
+            write_bytes(&mut bytes, 8, initializer.name.as_bytes());
+            let data: Vec<u8> = initializer
+                .data
+                .iter()
+                .flat_map(|x| x.to_le_bytes())
+                .collect();
+            write_bytes(&mut bytes, 9, &data);
+            write_bytes(&mut graph, 5, &bytes);
+        }
+        write_bytes(
+            &mut graph,
+            11,
+            &value_info(&self.input(), Some(&self.input_shape)),
+        );
+        write_bytes(&mut graph, 12, &value_info(output, None));
+        let mut model = Vec::new();
+        write_int(&mut model, 1, IR_VERSION);
+        write_bytes(&mut model, 2, b"autograph");
+        write_bytes(&mut model, 3, env!("CARGO_PKG_VERSION").as_bytes());
+        write_bytes(&mut model, 7, &graph);
+        let mut opset = Vec::new();
+        write_bytes(&mut opset, 1, b"");
+        write_int(&mut opset, 2, OPSET_VERSION);
+        write_bytes(&mut model, 8, &opset);
+        Ok(model)
+    }
+}
+
+/// Exports `layer` as an ONNX model with an f32 input of shape `input_shape`.
+///
+/// See [`OnnxGraph::to_bytes()`].
+///
+/// **Errors**
+/// - The layer could not be exported.
+pub fn to_bytes<L: ToOnnx>(layer: &L, input_shape: impl IntoDimension) -> Result<Vec<u8>> {
+    let mut graph = OnnxGraph::new(input_shape);
+    let input = graph.input();
+    let output = layer.to_onnx(&mut graph, input)?;
+    graph.to_bytes(&output)
+}
+
+/// Exports `layer` as an ONNX model to `path`.
+///
+/// See [`to_bytes()`].
+///
+/// **Errors**
+/// - The layer could not be exported.
+/// - Writing to `path` failed.
+pub fn save<L: ToOnnx>(
+    layer: &L,
+    input_shape: impl IntoDimension,
+    path: impl AsRef<Path>,
+) -> Result<()> {
+    fs::write(path, to_bytes(layer, input_shape)?)?;
+    Ok(())
+}
+
+fn value_info(name: &str, shape: Option<&[i64]>) -> Vec<u8> {
+    let mut tensor_type = Vec::new();
+    write_int(&mut tensor_type, 1, FLOAT);
+    if let Some(shape) = shape {
+        let mut shape_bytes = Vec::new();
+        for dim in shape.iter() {
+            let mut dim_bytes = Vec::new();
+            write_int(&mut dim_bytes, 1, *dim);
+            write_bytes(&mut shape_bytes, 1, &dim_bytes);
+        }
+        write_bytes(&mut tensor_type, 2, &shape_bytes);
+    }
+    let mut type_proto = Vec::new();
+    write_bytes(&mut type_proto, 1, &tensor_type);
+    let mut bytes = Vec::new();
+    write_bytes(&mut bytes, 1, name.as_bytes());
+    write_bytes(&mut bytes, 2, &type_proto);
+    bytes
+}
+
+fn write_varint(bytes: &mut Vec<u8>, mut x: u64) {
+    while x >= 0x80 {
+        bytes.push((x as u8) | 0x80);
+        x >>= 7;
+    }
+    bytes.push(x as u8);
+}
+
+fn write_key(bytes: &mut Vec<u8>, field: u64, wire_type: u64) {
+    write_varint(bytes, (field << 3) | wire_type);
+}
+
+fn write_int(bytes: &mut Vec<u8>, field: u64, x: i64) {
+    write_key(bytes, field, 0);
+    write_varint(bytes, x as u64);
+}
+
+fn write_bytes(bytes: &mut Vec<u8>, field: u64, data: &[u8]) {
+    write_key(bytes, field, 2);
+    write_varint(bytes, data.len() as u64);
+    bytes.extend_from_slice(data);
+}
@@ -1,6 +1,69 @@
+/*!
+Optimizers.
+
+# Custom optimizers
+Optimizers implement [`Optimizer`], which updates a single parameter, and can be implemented
+outside of this crate. The gradient is read with [`.grad()`](ParameterBase::grad) and can be
+modified with [`.grad_mut()`](ParameterBase::grad_mut), ie to clip it. The value is updated
+with [`.value_view_mut()`](ParameterBase::value_view_mut). Per parameter state, like moving
+averages, is created with [`.init_optimizer_state()`](ParameterBase::init_optimizer_state) and
+borrowed with [`.optimizer_state_mut()`](ParameterBase::optimizer_state_mut). The state is
+stored in the parameter, so that it is saved with the model and moved with the parameter between
+devices.
+
+```no_run
+# use autograph::{anyhow::{bail, Result}, krnl::scalar::{ScalarElem, ScalarType}, tensor::{ScalarTensor, Tensor}};
+use autograph::learn::neural_network::{
+    autograd::ParameterViewMutD,
+    optimizer::{Optimizer, TensorValue, Value, ValueMut},
+};
+use std::any::TypeId;
+
+// Divides the gradient by a moving average of its magnitude.
+struct RMSprop {
+    alpha: f32,
+    eps: f32,
+}
+
+impl Optimizer for RMSprop {
+    fn update(&self, learning_rate: f32, mut parameter: ParameterViewMutD) -> Result<()> {
+        if parameter.scalar_type() != ScalarType::F32 {
+            bail!("RMSprop {:?} unimplemented!", parameter.scalar_type());
+        }
+        let Some(grad) = parameter.grad() else {
+            return Ok(());
+        };
+        let device = parameter.device();
+        if parameter.optimizer_state().map(|state| state.id()) != Some(TypeId::of::<Self>()) {
+            let square_avg = ScalarTensor::zeros(device.clone(), parameter.raw_dim(), ScalarType::F32)?;
+            let square_avg = TensorValue::builder(square_avg).parameter_device(true).build();
+            parameter.init_optimizer_state(
+                "RMSprop",
+                TypeId::of::<Self>(),
+                [("square_avg".to_string(), Value::Tensor(square_avg))],
+            )?;
+        }
+        // Computed on the host for simplicity.
+        let grad = grad.cast_into_tensor::<f32>()?.into_array()?;
+        let state = parameter.optimizer_state_mut().unwrap();
+        let Some((_, ValueMut::Tensor(mut square_avg))) = state.iter_mut().next() else {
+            unreachable!()
+        };
+        let mut square_avg_array = square_avg.view().cast_into_tensor::<f32>()?.into_array()?;
+        square_avg_array.zip_mut_with(&grad, |s, g| {
+            *s = self.alpha * *s + (1. - self.alpha) * g * g;
+        });
+        let step = &grad / &square_avg_array.mapv(|s| s.sqrt() + self.eps);
+        square_avg.assign(&Tensor::from(square_avg_array).into_device(device.clone())?.into_scalar_tensor())?;
+        let step = Tensor::from(step).into_device(device)?.into_scalar_tensor();
+        parameter.value_view_mut().scaled_add(ScalarElem::F32(-learning_rate), &step)
+    }
+}
+```
+*/
 #[cfg(doc)]
-use super::autograd::Parameter;
-use super::autograd::ParameterViewMutD;
+use super::autograd::{Parameter, ParameterBase};
+use super::{autograd::ParameterViewMutD, layer::Layer};
 use crate::tensor::{
     ScalarTensor, ScalarTensorD, ScalarTensorViewMutD, TensorViewD, TensorViewMutD,
 };
@@ -99,6 +162,161 @@ pub mod builder {
             SGD { momentum }
         }
     }
+
+    /// Builder for creating a [`Warmup`].
+    pub struct WarmupBuilder<S> {
+        scheduler: S,
+        steps: usize,
+        start_learning_rate: f32,
+        kind: WarmupKind,
+    }
+
+    impl<S> WarmupBuilder<S> {
+        pub(super) fn new(scheduler: S, steps: usize) -> Self {
+            Self {
+                scheduler,
+                steps,
+                start_learning_rate: 0.,
+                kind: WarmupKind::default(),
+            }
+        }
+        /// The learning rate at step 0. Default is 0.
+        pub fn start_learning_rate(self, start_learning_rate: f32) -> Self {
+            Self {
+                start_learning_rate,
+                ..self
+            }
+        }
+        /// The curve of the warmup. Default is [`WarmupKind::Linear`].
+        pub fn kind(self, kind: WarmupKind) -> Self {
+            Self { kind, ..self }
+        }
+        /// Builds the scheduler.
+        pub fn build(self) -> Warmup<S> {
+            let Self {
+                scheduler,
+                steps,
+                start_learning_rate,
+                kind,
+            } = self;
+            Warmup {
+                scheduler,
+                steps,
+                start_learning_rate,
+                kind,
+            }
+        }
+    }
+
+    /// Builder for creating an [`Adam`].
+    pub struct AdamBuilder {
+        betas: [f32; 2],
+        eps: f32,
+        weight_decay: f32,
+    }
+
+    impl AdamBuilder {
+        pub(super) fn new() -> Self {
+            Self {
+                betas: [0.9, 0.999],
+                eps: 1e-8,
+                weight_decay: 0.,
+            }
+        }
+        /// Decay rates of the moving averages of the gradient and the squared gradient.
+        /// Default is (0.9, 0.999).
+        pub fn betas(self, beta1: f32, beta2: f32) -> Self {
+            Self {
+                betas: [beta1, beta2],
+                ..self
+            }
+        }
+        /// Added to the denominator for numerical stability. Default is 1e-8.
+        pub fn eps(self, eps: f32) -> Self {
+            Self { eps, ..self }
+        }
+        /// L2 penalty, added to the gradient. Default is 0.
+        ///
+        /// See [`AdamW`] for decoupled weight decay.
+        pub fn weight_decay(self, weight_decay: f32) -> Self {
+            Self {
+                weight_decay,
+                ..self
+            }
+        }
+        /// Builds the optimizer.
+        ///
+        /// **Errors**
+        /// - The betas are not in [0, 1).
+        /// - `eps` or `weight_decay` is negative.
+        pub fn build(self) -> Result<Adam> {
+            let Self {
+                betas,
+                eps,
+                weight_decay,
+            } = self;
+            check_adam_options("Adam", betas, eps, weight_decay)?;
+            Ok(Adam {
+                betas,
+                eps,
+                weight_decay,
+            })
+        }
+    }
+
+    /// Builder for creating an [`AdamW`].
+    pub struct AdamWBuilder {
+        betas: [f32; 2],
+        eps: f32,
+        weight_decay: f32,
+    }
+
+    impl AdamWBuilder {
+        pub(super) fn new() -> Self {
+            Self {
+                betas: [0.9, 0.999],
+                eps: 1e-8,
+                weight_decay: 0.01,
+            }
+        }
+        /// Decay rates of the moving averages of the gradient and the squared gradient.
+        /// Default is (0.9, 0.999).
+        pub fn betas(self, beta1: f32, beta2: f32) -> Self {
+            Self {
+                betas: [beta1, beta2],
+                ..self
+            }
+        }
+        /// Added to the denominator for numerical stability. Default is 1e-8.
+        pub fn eps(self, eps: f32) -> Self {
+            Self { eps, ..self }
+        }
+        /// Weight decay, applied directly to the weights. Default is 0.01.
+        pub fn weight_decay(self, weight_decay: f32) -> Self {
+            Self {
+                weight_decay,
+                ..self
+            }
+        }
+        /// Builds the optimizer.
+        ///
+        /// **Errors**
+        /// - The betas are not in [0, 1).
+        /// - `eps` or `weight_decay` is negative.
+        pub fn build(self) -> Result<AdamW> {
+            let Self {
+                betas,
+                eps,
+                weight_decay,
+            } = self;
+            check_adam_options("AdamW", betas, eps, weight_decay)?;
+            Ok(AdamW {
+                betas,
+                eps,
+                weight_decay,
+            })
+        }
+    }
 }
 use builder::*;
 
@@ -152,14 +370,13 @@ impl<'a> ValueMut<'a> {
             panic!("Expected tensor!")
         }
     }
-    /*
-    fn unwrap_elem(self) -> ScalarElem {
+    fn unwrap_elem(self) -> &'a mut ScalarElem {
         if let Self::Elem(elem) = self {
-            *elem
+            elem
         } else {
             panic!("Expected elem!")
         }
-    }*/
+    }
 }
 
 /// Optimizer State.
@@ -287,6 +504,96 @@ pub trait Optimizer {
     fn update(&self, learning_rate: f32, parameter: ParameterViewMutD) -> Result<()>;
 }
 
+/// Learning rate scheduler.
+///
+/// Computes the learning rate passed to [`Optimizer::update`] at each step. Implemented for
+/// f32, a constant learning rate, and for closures `Fn(usize) -> f32`.
+///
+///```no_run
+/// # use autograph::learn::neural_network::optimizer::{LrScheduler, Warmup};
+/// // Halve the learning rate every 10 steps, after 5 steps of warmup.
+/// let scheduler = Warmup::builder(|step: usize| 0.1 * 0.5f32.powi((step / 10) as i32), 5).build();
+/// for step in 0 .. 100 {
+///     let learning_rate = scheduler.learning_rate(step);
+///     // training step
+/// }
+///```
+pub trait LrScheduler {
+    /// The learning rate at `step`, starting from 0.
+    fn learning_rate(&self, step: usize) -> f32;
+}
+
+impl LrScheduler for f32 {
+    fn learning_rate(&self, _step: usize) -> f32 {
+        *self
+    }
+}
+
+impl<F: Fn(usize) -> f32> LrScheduler for F {
+    fn learning_rate(&self, step: usize) -> f32 {
+        (self)(step)
+    }
+}
+
+/// The curve of a [`Warmup`].
+#[derive(Default, Clone, Copy, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub enum WarmupKind {
+    /// Increases linearly.
+    #[default]
+    Linear,
+    /// Increases along half a cosine, starting and ending slowly.
+    Cosine,
+}
+
+/// Learning rate warmup.
+///
+/// Ramps the learning rate from the start learning rate to the base learning rate, the
+/// learning rate of the inner scheduler at step 0, over the first `steps` steps. Afterwards
+/// delegates to the inner scheduler, offset by `steps`, ie step `steps + i` has the learning
+/// rate of step `i` of the inner scheduler.
+///
+/// Warmup stabilizes the early steps of training, when the gradients of deep networks and
+/// transformers are large and the moving averages of adaptive optimizers are not yet
+/// meaningful.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Warmup<S> {
+    scheduler: S,
+    steps: usize,
+    start_learning_rate: f32,
+    kind: WarmupKind,
+}
+
+impl<S: LrScheduler> Warmup<S> {
+    /// A Warmup builder, warming up `scheduler` over `steps`.
+    pub fn builder(scheduler: S, steps: usize) -> WarmupBuilder<S> {
+        WarmupBuilder::new(scheduler, steps)
+    }
+    /// The inner scheduler.
+    pub fn scheduler(&self) -> &S {
+        &self.scheduler
+    }
+    /// The number of warmup steps.
+    pub fn steps(&self) -> usize {
+        self.steps
+    }
+}
+
+impl<S: LrScheduler> LrScheduler for Warmup<S> {
+    fn learning_rate(&self, step: usize) -> f32 {
+        if step >= self.steps {
+            return self.scheduler.learning_rate(step - self.steps);
+        }
+        let start = self.start_learning_rate;
+        let base = self.scheduler.learning_rate(0);
+        let x = step as f32 / self.steps as f32;
+        let alpha = match self.kind {
+            WarmupKind::Linear => x,
+            WarmupKind::Cosine => (1. - (std::f32::consts::PI * x).cos()) / 2.,
+        };
+        start + (base - start) * alpha
+    }
+}
+
 /// Stochastic Gradient Descent.
 ///
 /// Implemented for bf16 and f32.
@@ -372,6 +679,405 @@ impl Optimizer for SGD {
     }
 }
 
+/// Adam.
+///
+/// Maintains moving averages of the gradient and the squared gradient, scaling the update of
+/// each element by the inverse root of the latter. Weight decay is an L2 penalty added to the
+/// gradient, which is then scaled by the adaptive update. See [`AdamW`] for decoupled weight
+/// decay.
+///
+/// Implemented for bf16 and f32.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Adam {
+    betas: [f32; 2],
+    eps: f32,
+    weight_decay: f32,
+}
+
+impl Adam {
+    /// An Adam builder.
+    pub fn builder() -> AdamBuilder {
+        AdamBuilder::new()
+    }
+}
+
+impl Default for Adam {
+    fn default() -> Self {
+        Self::builder().build().unwrap()
+    }
+}
+
+/// Implemented for bf16 and f32.
+impl Optimizer for Adam {
+    fn update(&self, learning_rate: f32, parameter: ParameterViewMutD) -> Result<()> {
+        adam_update::<Self>(
+            "Adam",
+            learning_rate,
+            self.betas,
+            self.eps,
+            [self.weight_decay, 0.],
+            parameter,
+        )
+    }
+}
+
+/// Adam with decoupled weight decay.
+///
+/// Like [`Adam`], but the weights are decayed directly with
+/// `w -= learning_rate * weight_decay * w`, separate from the adaptive update. Unlike an L2
+/// penalty, the decay is not scaled by the moving average of the squared gradient, so all
+/// weights decay at the same rate.
+///
+/// Implemented for bf16 and f32.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AdamW {
+    betas: [f32; 2],
+    eps: f32,
+    weight_decay: f32,
+}
+
+impl AdamW {
+    /// An AdamW builder.
+    pub fn builder() -> AdamWBuilder {
+        AdamWBuilder::new()
+    }
+}
+
+impl Default for AdamW {
+    fn default() -> Self {
+        Self::builder().build().unwrap()
+    }
+}
+
+/// Implemented for bf16 and f32.
+impl Optimizer for AdamW {
+    fn update(&self, learning_rate: f32, parameter: ParameterViewMutD) -> Result<()> {
+        adam_update::<Self>(
+            "AdamW",
+            learning_rate,
+            self.betas,
+            self.eps,
+            [0., self.weight_decay],
+            parameter,
+        )
+    }
+}
+
+fn check_adam_options(name: &str, betas: [f32; 2], eps: f32, weight_decay: f32) -> Result<()> {
+    for beta in betas {
+        if !(0. ..1.).contains(&beta) {
+            bail!("{name} beta {beta} not in [0, 1)!");
+        }
+    }
+    if eps.is_nan() || eps < 0. {
+        bail!("{name} eps {eps} must be non-negative!");
+    }
+    if weight_decay.is_nan() || weight_decay < 0. {
+        bail!("{name} weight_decay {weight_decay} must be non-negative!");
+    }
+    Ok(())
+}
+
+// Shared by Adam and AdamW, `weight_decay` is [l2 penalty, decoupled decay].
+fn adam_update<O: 'static>(
+    name: &str,
+    learning_rate: f32,
+    [beta1, beta2]: [f32; 2],
+    eps: f32,
+    [l2, decay]: [f32; 2],
+    mut parameter: ParameterViewMutD,
+) -> Result<()> {
+    let scalar_type = parameter.scalar_type();
+    if !matches!(scalar_type, ScalarType::BF16 | ScalarType::F32) {
+        bail!("{name} {scalar_type:?} unimplemented!");
+    }
+    adam_init_state::<O>(name, &mut parameter)?;
+    if let Some(grad) = parameter.grad() {
+        let (value, state) = parameter.value_view_optimizer_state_mut();
+        let mut state = state.unwrap().iter_mut().map(|(_, value)| value);
+        let step = state.next().unwrap().unwrap_elem();
+        let t = step.cast::<u32>() + 1;
+        *step = ScalarElem::U32(t);
+        let exp_avg = state.next().unwrap().unwrap_tensor();
+        let exp_avg_sq = state.next().unwrap().unwrap_tensor();
+        let options = AdamOptions {
+            learning_rate,
+            beta1,
+            beta2,
+            eps,
+            l2,
+            decay,
+            bias_correction1: 1. - beta1.powi(t as i32),
+            bias_correction2: 1. - beta2.powi(t as i32),
+        };
+        let grad = grad.view();
+        match scalar_type {
+            ScalarType::BF16 => adam_update_impl::<bf16>(
+                value.try_into().unwrap(),
+                grad.try_into().unwrap(),
+                exp_avg.try_into().unwrap(),
+                exp_avg_sq.try_into().unwrap(),
+                options,
+            )?,
+            ScalarType::F32 => adam_update_impl::<f32>(
+                value.try_into().unwrap(),
+                grad.try_into().unwrap(),
+                exp_avg.try_into().unwrap(),
+                exp_avg_sq.try_into().unwrap(),
+                options,
+            )?,
+            _ => unreachable!(),
+        }
+    }
+    Ok(())
+}
+
+fn adam_init_state<O: 'static>(name: &str, parameter: &mut ParameterViewMutD) -> Result<()> {
+    if let Some(state) = parameter.optimizer_state() {
+        if state.id() == TypeId::of::<O>() {
+            return Ok(());
+        }
+    }
+    let mut key_values = vec![("step".to_string(), Value::Elem(ScalarElem::U32(0)))];
+    for key in ["exp_avg", "exp_avg_sq"] {
+        let tensor = ScalarTensor::zeros(
+            parameter.device(),
+            parameter.raw_dim(),
+            parameter.scalar_type(),
+        )?;
+        key_values.push((
+            key.to_string(),
+            Value::Tensor(
+                TensorValue::builder(tensor)
+                    .parameter_device(true)
+                    .parameter_type(true)
+                    .build(),
+            ),
+        ));
+    }
+    parameter.init_optimizer_state(name, TypeId::of::<O>(), key_values)
+}
+
+#[derive(Clone, Copy)]
+struct AdamOptions {
+    learning_rate: f32,
+    beta1: f32,
+    beta2: f32,
+    eps: f32,
+    l2: f32,
+    decay: f32,
+    bias_correction1: f32,
+    bias_correction2: f32,
+}
+
+fn adam_update_impl<T: Scalar>(
+    mut value: TensorViewMutD<T>,
+    grad: TensorViewD<T>,
+    mut exp_avg: TensorViewMutD<T>,
+    mut exp_avg_sq: TensorViewMutD<T>,
+    options: AdamOptions,
+) -> Result<()> {
+    let AdamOptions {
+        learning_rate,
+        beta1,
+        beta2,
+        eps,
+        l2,
+        decay,
+        bias_correction1,
+        bias_correction2,
+    } = options;
+    if let Some((((value, grad), exp_avg), exp_avg_sq)) = value
+        .as_array_mut()
+        .zip(grad.as_array())
+        .zip(exp_avg.as_array_mut())
+        .zip(exp_avg_sq.as_array_mut())
+    {
+        Zip::from(value)
+            .and(grad)
+            .and(exp_avg)
+            .and(exp_avg_sq)
+            .for_each(|value, grad, exp_avg, exp_avg_sq| {
+                let mut value_f32 = value.cast::<f32>();
+                let mut exp_avg_f32 = exp_avg.cast::<f32>();
+                let mut exp_avg_sq_f32 = exp_avg_sq.cast::<f32>();
+                kernels::adam_update(
+                    &mut value_f32,
+                    grad.cast::<f32>(),
+                    &mut exp_avg_f32,
+                    &mut exp_avg_sq_f32,
+                    learning_rate,
+                    beta1,
+                    beta2,
+                    eps,
+                    l2,
+                    decay,
+                    bias_correction1,
+                    bias_correction2,
+                );
+                *value = value_f32.cast();
+                *exp_avg = exp_avg_f32.cast();
+                *exp_avg_sq = exp_avg_sq_f32.cast();
+            });
+        return Ok(());
+    }
+    #[cfg(not(feature = "device"))]
+    {
+        unreachable!()
+    }
+    #[cfg(feature = "device")]
+    {
+        macro_for!($T in [bf16, f32] {
+            if value.scalar_type() == $T::scalar_type() {
+                let mut value = ScalarTensorViewMut::from(value)
+                    .try_into_tensor_view_mut::<$T>()
+                    .unwrap();
+                let grad = ScalarTensorView::from(grad)
+                    .try_into_tensor_view::<$T>()
+                    .unwrap();
+                let mut exp_avg = ScalarTensorViewMut::from(exp_avg)
+                    .try_into_tensor_view_mut::<$T>()
+                    .unwrap();
+                let mut exp_avg_sq = ScalarTensorViewMut::from(exp_avg_sq)
+                    .try_into_tensor_view_mut::<$T>()
+                    .unwrap();
+                let kernel = paste! {
+                    kernels::[<adam_update_ $T>]::builder()?
+                    .build(value.device())?
+                };
+                return kernel
+                    .dispatch(
+                        value.as_slice_mut().unwrap(),
+                        grad.as_slice().unwrap(),
+                        exp_avg.as_slice_mut().unwrap(),
+                        exp_avg_sq.as_slice_mut().unwrap(),
+                        learning_rate,
+                        beta1,
+                        beta2,
+                        eps,
+                        l2,
+                        decay,
+                        bias_correction1,
+                        bias_correction2,
+                    );
+            }
+        });
+        unreachable!()
+    }
+}
+
+/// Exponential moving average of the parameters of a model.
+///
+/// Maintains a shadow copy of the parameters, updated with
+/// `ema = decay * ema + (1 - decay) * parameter` after each step. The averaged parameters
+/// often generalize better than the parameters of the last step.
+///
+/// The shadow parameters are stored as f32.
+///
+///```no_run
+/// # use autograph::{anyhow::Result, learn::neural_network::{layer::{Dense, Layer}, optimizer::EmaModel}};
+/// # fn main() -> Result<()> {
+/// # let mut model: Dense = todo!();
+/// let mut ema = EmaModel::new(&model, 0.999)?;
+/// for _ in 0 .. 100 {
+///     // training step
+///     ema.update(&model)?;
+/// }
+/// // evaluate with the averaged parameters
+/// ema.swap(&mut model)?;
+/// // ...
+/// // restore the trained parameters
+/// ema.swap(&mut model)?;
+/// # Ok(())
+/// # }
+///```
+#[derive(Debug, Serialize, Deserialize)]
+pub struct EmaModel {
+    decay: f32,
+    parameters: Vec<ScalarTensorD>,
+}
+
+impl EmaModel {
+    /// Creates an EMA of the parameters of `model` with `decay`.
+    ///
+    /// **Errors**
+    /// - `decay` is not in [0, 1].
+    /// - The parameters could not be copied.
+    pub fn new<L: Layer>(model: &L, decay: f32) -> Result<Self> {
+        if !(0. ..=1.).contains(&decay) {
+            bail!("EmaModel decay {decay} not in [0, 1]!");
+        }
+        let parameters = model
+            .parameters()
+            .iter()
+            .map(|parameter| parameter.value().cast(ScalarType::F32))
+            .collect::<Result<_>>()?;
+        Ok(Self { decay, parameters })
+    }
+    /// The decay.
+    pub fn decay(&self) -> f32 {
+        self.decay
+    }
+    /// The averaged parameters.
+    pub fn parameters(&self) -> &[ScalarTensorD] {
+        &self.parameters
+    }
+    /// Updates the average with the parameters of `model`.
+    ///
+    /// Typically called after each optimizer step.
+    ///
+    /// **Errors**
+    /// - The parameters of `model` do not match.
+    /// - The operation could not be executed on the device.
+    pub fn update<L: Layer>(&mut self, model: &L) -> Result<()> {
+        let parameters = model.parameters();
+        self.check_parameters(parameters.iter().map(|x| x.shape()))?;
+        for (ema, parameter) in self.parameters.iter_mut().zip(parameters.iter()) {
+            let mut output = ema.scaled_cast(ScalarElem::F32(self.decay))?;
+            output.scaled_add(ScalarElem::F32(1. - self.decay), parameter.value())?;
+            *ema = output;
+        }
+        Ok(())
+    }
+    /// Swaps the averaged parameters with the parameters of `model`.
+    ///
+    /// Swap to evaluate `model` with the averaged parameters, and then swap again to restore the
+    /// parameters and resume training.
+    ///
+    /// **Errors**
+    /// - The parameters of `model` do not match.
+    /// - The operation could not be executed on the device.
+    pub fn swap<L: Layer>(&mut self, model: &mut L) -> Result<()> {
+        let mut parameters = model.parameters_mut()?;
+        self.check_parameters(parameters.iter().map(|x| x.shape()))?;
+        for (ema, parameter) in self.parameters.iter_mut().zip(parameters.iter_mut()) {
+            let mut value = parameter.value_view_mut();
+            let output = value.cast(ScalarType::F32)?;
+            value.assign(&*ema)?;
+            *ema = output;
+        }
+        Ok(())
+    }
+    fn check_parameters<'a>(
+        &self,
+        shapes: impl ExactSizeIterator<Item = &'a [usize]>,
+    ) -> Result<()> {
+        if shapes.len() != self.parameters.len() {
+            bail!(
+                "EmaModel expected {} parameters, found {}!",
+                self.parameters.len(),
+                shapes.len()
+            );
+        }
+        for (ema, shape) in self.parameters.iter().zip(shapes) {
+            if ema.shape() != shape {
+                bail!("EmaModel parameter shape {shape:?} != {:?}!", ema.shape());
+            }
+        }
+        Ok(())
+    }
+}
+
 fn sgd_update_with_momentum<T: Scalar>(
     mut value: TensorViewMutD<T>,
     learning_rate: f32,
@@ -444,12 +1150,38 @@ mod kernels {
     use krnl::krnl_core;
     #[cfg(any(feature = "device", target_arch = "spirv"))]
     use krnl_core::macros::kernel;
+    #[cfg(target_arch = "spirv")]
+    use krnl_core::num_traits::Float;
 
     pub fn sgd_update_with_momentum(w: &mut f32, dw: f32, lr: f32, m: f32, v: &mut f32) {
         *v = m * *v + dw;
         *w -= lr * *v;
     }
 
+    #[allow(clippy::too_many_arguments)]
+    pub fn adam_update(
+        w: &mut f32,
+        dw: f32,
+        m: &mut f32,
+        v: &mut f32,
+        lr: f32,
+        beta1: f32,
+        beta2: f32,
+        eps: f32,
+        l2: f32,
+        decay: f32,
+        bias_correction1: f32,
+        bias_correction2: f32,
+    ) {
+        let dw = dw + l2 * *w;
+        *w -= lr * decay * *w;
+        *m = beta1 * *m + (1. - beta1) * dw;
+        *v = beta2 * *v + (1. - beta2) * dw * dw;
+        let m_hat = *m / bias_correction1;
+        let v_hat = *v / bias_correction2;
+        *w -= lr * m_hat / (v_hat.sqrt() + eps);
+    }
+
     #[cfg(any(feature = "device", target_arch = "spirv"))]
     pub mod device {
         use super::*;
@@ -481,6 +1213,76 @@ mod kernels {
         ) {
             sgd_update_with_momentum(w, dw, lr, m, v);
         }
+
+        #[allow(clippy::too_many_arguments)]
+        #[kernel]
+        pub fn adam_update_bf16(
+            #[item] w: &mut bf16,
+            #[item] dw: bf16,
+            #[item] m: &mut bf16,
+            #[item] v: &mut bf16,
+            lr: f32,
+            beta1: f32,
+            beta2: f32,
+            eps: f32,
+            l2: f32,
+            decay: f32,
+            bias_correction1: f32,
+            bias_correction2: f32,
+        ) {
+            let mut w_f32 = w.to_f32();
+            let mut m_f32 = m.to_f32();
+            let mut v_f32 = v.to_f32();
+            adam_update(
+                &mut w_f32,
+                dw.to_f32(),
+                &mut m_f32,
+                &mut v_f32,
+                lr,
+                beta1,
+                beta2,
+                eps,
+                l2,
+                decay,
+                bias_correction1,
+                bias_correction2,
+            );
+            *w = bf16::from_f32(w_f32);
+            *m = bf16::from_f32(m_f32);
+            *v = bf16::from_f32(v_f32);
+        }
+
+        #[allow(clippy::too_many_arguments)]
+        #[kernel]
+        pub fn adam_update_f32(
+            #[item] w: &mut f32,
+            #[item] dw: f32,
+            #[item] m: &mut f32,
+            #[item] v: &mut f32,
+            lr: f32,
+            beta1: f32,
+            beta2: f32,
+            eps: f32,
+            l2: f32,
+            decay: f32,
+            bias_correction1: f32,
+            bias_correction2: f32,
+        ) {
+            adam_update(
+                w,
+                dw,
+                m,
+                v,
+                lr,
+                beta1,
+                beta2,
+                eps,
+                l2,
+                decay,
+                bias_correction1,
+                bias_correction2,
+            );
+        }
     }
     #[cfg(any(feature = "device", target_arch = "spirv"))]
     pub use device::*;
@@ -0,0 +1,603 @@
+/*!
+A [`StateDict`] is read from the zip archive created by `torch.save(model.state_dict(), path)`,
+and loaded into a model by matching the names returned by
+[`Layer::named_parameters_mut()`](super::layer::Layer::named_parameters_mut).
+
+Conv weights have the same layout, `[outputs, inputs, ..filter]`. PyTorch Linear weights are
+`[outputs, inputs]`, while [`Dense`](super::layer::Dense) weights are `[inputs, outputs]`, so
+the parameters named by
+[`Layer::transposed_parameter_names()`](super::layer::Layer::transposed_parameter_names) are
+transposed.
+
+Implemented for stored tensors of type f16, bf16, f32, f64, u8, i8, i16, i32, and i64. Integer
+tensors, ie the `num_batches_tracked` buffer of BatchNorm, are read but typically have no
+matching parameter.
+
+# Example
+```no_run
+# use autograph::{anyhow::Result, learn::neural_network::{layer::{Conv2, Dense, Flatten, MaxPool2, Relu, Layer, Forward}, pytorch::StateDict, autograd::{Variable4, Variable2}}};
+#[derive(Layer, Forward)]
+#[autograph(forward(Variable4, Output=Variable2))]
+struct Network {
+    conv: Conv2<Relu>,
+    pool: MaxPool2,
+    flatten: Flatten,
+    dense: Dense,
+}
+# fn main() -> Result<()> {
+# let mut network: Network = todo!();
+let state_dict = StateDict::load("network.pt")?;
+state_dict.load_into(&mut network)?;
+# Ok(())
+# }
+```
+*/
+
+use super::layer::Layer;
+use crate::tensor::{ScalarTensorD, Tensor};
+use anyhow::{bail, format_err, Result};
+use half::{bf16, f16};
+use krnl::scalar::ScalarType;
+use ndarray::{ArrayView, IxDyn, ShapeBuilder};
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{BufReader, Read, Seek},
+    path::Path,
+};
+use zip::ZipArchive;
+
+/// Tensors saved by PyTorch, by name.
+#[derive(Debug, Default)]
+pub struct StateDict {
+    names: Vec<String>,
+    tensors: HashMap<String, ScalarTensorD>,
+}
+
+impl StateDict {
+    /// Loads a state dict from `path`.
+    ///
+    /// See [`StateDict::from_reader()`].
+    ///
+    /// **Errors**
+    /// - The file could not be opened.
+    /// - See [`StateDict::from_reader()`].
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        Self::from_reader(BufReader::new(File::open(path)?))
+    }
+    /// Reads a state dict from a zip archive.
+    ///
+    /// **Errors**
+    /// - The archive is not a state dict saved by PyTorch.
+    /// - A tensor has an unsupported type.
+    pub fn from_reader<R: Read + Seek>(reader: R) -> Result<Self> {
+        let mut archive = ZipArchive::new(reader)?;
+        let Some(data_pkl) = archive
+            .file_names()
+            .find(|name| name.ends_with("/data.pkl"))
+            .map(ToString::to_string)
+        else {
+            bail!("StateDict data.pkl not found!");
+        };
+        let prefix = data_pkl.strip_suffix("data.pkl").unwrap();
+        let mut bytes = Vec::new();
+        archive.by_name(&data_pkl)?.read_to_end(&mut bytes)?;
+        let Object::Dict(items) = Unpickler::new(&bytes).load()? else {
+            bail!("StateDict expected a dict!");
+        };
+        let mut state_dict = Self::default();
+        for (name, tensor) in items {
+            let (
+                Object::String(name),
+                Object::Tensor {
+                    storage,
+                    offset,
+                    shape,
+                    strides,
+                },
+            ) = (name, tensor)
+            else {
+                continue;
+            };
+            let Object::Storage { scalar_type, key } = *storage else {
+                bail!("StateDict {name:?} expected storage!");
+            };
+            let mut data = Vec::new();
+            archive
+                .by_name(&format!("{prefix}data/{key}"))?
+                .read_to_end(&mut data)?;
+            let tensor = storage_to_tensor(scalar_type, &data, offset, &shape, &strides)
+                .map_err(|e| format_err!("StateDict {name:?}: {e}"))?;
+            state_dict.names.push(name.clone());
+            state_dict.tensors.insert(name, tensor);
+        }
+        Ok(state_dict)
+    }
+    /// The names of the tensors, in the saved order.
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.names.iter().map(String::as_str)
+    }
+    /// The tensor with `name`, on the host.
+    pub fn get(&self, name: &str) -> Option<&ScalarTensorD> {
+        self.tensors.get(name)
+    }
+    /// Copies the tensors into the parameters of `layer`.
+    ///
+    /// Tensors are cast to the type and transferred to the device of each parameter. The weights
+    /// of [`Dense`](super::layer::Dense) are transposed, see [`pytorch`](self).
+    ///
+    /// **Errors**
+    /// - A parameter is not in the state dict.
+    /// - The shape of a parameter does not match the tensor.
+    /// - Casting or copying to the device failed.
+    pub fn load_into<L: Layer>(&self, layer: &mut L) -> Result<()> {
+        self.load_into_impl(layer, false)?;
+        Ok(())
+    }
+    /// Copies the tensors into the parameters of `layer` that match.
+    ///
+    /// Like [`.load_into()`](StateDict::load_into), but parameters that are not in the state
+    /// dict or have a different shape are skipped, and keep their values. Useful for fine
+    /// tuning, ie when the final layer is resized for a different number of classes.
+    ///
+    /// Returns the names of the parameters that were loaded and skipped.
+    ///
+    /// **Errors**
+    /// - Casting or copying to the device failed.
+    pub fn load_partial_into<L: Layer>(&self, layer: &mut L) -> Result<PartialLoad> {
+        self.load_into_impl(layer, true)
+    }
+    fn load_into_impl<L: Layer>(&self, layer: &mut L, partial: bool) -> Result<PartialLoad> {
+        let mut report = PartialLoad::default();
+        let transposed = layer.transposed_parameter_names();
+        for (name, mut parameter) in layer.named_parameters_mut()? {
+            let Some(tensor) = self.tensors.get(&name) else {
+                if partial {
+                    report.missing.push(name);
+                    continue;
+                }
+                bail!("StateDict {name:?} not found!");
+            };
+            let tensor = if transposed.contains(&name) {
+                tensor.view().reversed_axes()
+            } else {
+                tensor.view()
+            };
+            if tensor.shape() != parameter.shape() {
+                if partial {
+                    report.mismatched.push(name);
+                    continue;
+                }
+                bail!(
+                    "StateDict {name:?} expected shape {:?}, found {:?}!",
+                    parameter.shape(),
+                    tensor.shape()
+                );
+            }
+            let tensor = tensor
+                .as_standard_layout()?
+                .cast_into(parameter.scalar_type())?
+                .into_device(parameter.device())?;
+            parameter.value_view_mut().assign(&tensor)?;
+            report.loaded.push(name);
+        }
+        Ok(report)
+    }
+}
+
+/// The parameters loaded by [`StateDict::load_partial_into()`].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct PartialLoad {
+    /// Parameters that were loaded.
+    pub loaded: Vec<String>,
+    /// Parameters that are not in the state dict.
+    pub missing: Vec<String>,
+    /// Parameters with a different shape than the tensor in the state dict.
+    pub mismatched: Vec<String>,
+}
+
+impl PartialLoad {
+    /// The parameters that were skipped, ie missing or mismatched.
+    pub fn skipped(&self) -> impl Iterator<Item = &str> {
+        self.missing
+            .iter()
+            .chain(self.mismatched.iter())
+            .map(String::as_str)
+    }
+}
+
+fn storage_to_tensor(
+    scalar_type: ScalarType,
+    data: &[u8],
+    offset: usize,
+    shape: &[usize],
+    strides: &[usize],
+) -> Result<ScalarTensorD> {
+    macro_rules! to_tensor {
+        ($T:ty) => {{
+            let size = std::mem::size_of::<$T>();
+            let data: Vec<$T> = data
+                .chunks_exact(size)
+                .map(|x| <$T>::from_le_bytes(x.try_into().unwrap()))
+                .collect();
+            let Some(data) = data.get(offset..) else {
+                bail!("storage offset {offset} out of bounds!");
+            };
+            let array = ArrayView::from_shape(IxDyn(shape).strides(IxDyn(strides)), data)?;
+            Tensor::from(array.as_standard_layout().into_owned()).into()
+        }};
+    }
+    let tensor: ScalarTensorD = match scalar_type {
+        ScalarType::F16 => to_tensor!(f16),
+        ScalarType::BF16 => to_tensor!(bf16),
+        ScalarType::F32 => to_tensor!(f32),
+        ScalarType::F64 => to_tensor!(f64),
+        ScalarType::U8 => to_tensor!(u8),
+        ScalarType::I8 => to_tensor!(i8),
+        ScalarType::I16 => to_tensor!(i16),
+        ScalarType::I32 => to_tensor!(i32),
+        ScalarType::I64 => to_tensor!(i64),
+        _ => bail!("{scalar_type:?} not implemented!"),
+    };
+    Ok(tensor)
+}
+
+/// Python objects used in state dicts.
+#[allow(dead_code)]
+#[derive(Clone, Debug)]
+enum Object {
+    None,
+    Bool(bool),
+    Int(i64),
+    Float(f64),
+    String(String),
+    Tuple(Vec<Object>),
+    List(Vec<Object>),
+    Dict(Vec<(Object, Object)>),
+    Global(String, String),
+    Storage {
+        scalar_type: ScalarType,
+        key: String,
+    },
+    Tensor {
+        storage: Box<Object>,
+        offset: usize,
+        shape: Vec<usize>,
+        strides: Vec<usize>,
+    },
+    Mark,
+}
+
+impl Object {
+    fn into_usize(self) -> Result<usize> {
+        match self {
+            Self::Int(x) if x >= 0 => Ok(x as usize),
+            x => bail!("expected int, found {x:?}!"),
+        }
+    }
+    fn into_usizes(self) -> Result<Vec<usize>> {
+        match self {
+            Self::Tuple(xs) | Self::List(xs) => xs.into_iter().map(Self::into_usize).collect(),
+            x => bail!("expected tuple, found {x:?}!"),
+        }
+    }
+}
+
+/// A minimal unpickler for the objects that PyTorch uses in state dicts.
+struct Unpickler<'a> {
+    bytes: &'a [u8],
+    stack: Vec<Object>,
+    memo: HashMap<u32, Object>,
+}
+
+impl<'a> Unpickler<'a> {
+    fn new(bytes: &'a [u8]) -> Self {
+        Self {
+            bytes,
+            stack: Vec::new(),
+            memo: HashMap::new(),
+        }
+    }
+    fn read(&mut self, n: usize) -> Result<&'a [u8]> {
+        if self.bytes.len() < n {
+            bail!("Unexpected end of pickle!");
+        }
+        let (output, bytes) = self.bytes.split_at(n);
+        self.bytes = bytes;
+        Ok(output)
+    }
+    fn read_u8(&mut self) -> Result<u8> {
+        Ok(self.read(1)?[0])
+    }
+    fn read_u32(&mut self) -> Result<u32> {
+        Ok(u32::from_le_bytes(self.read(4)?.try_into().unwrap()))
+    }
+    fn read_line(&mut self) -> Result<String> {
+        let Some(n) = self.bytes.iter().position(|x| *x == b'\n') else {
+            bail!("Unexpected end of pickle!");
+        };
+        let line = String::from_utf8(self.read(n)?.to_vec())?;
+        self.read(1)?;
+        Ok(line)
+    }
+    fn read_string(&mut self, n: usize) -> Result<Object> {
+        Ok(Object::String(String::from_utf8(self.read(n)?.to_vec())?))
+    }
+    fn pop(&mut self) -> Result<Object> {
+        self.stack
+            .pop()
+            .ok_or_else(|| format_err!("Unpickler stack is empty!"))
+    }
+    fn pop_mark(&mut self) -> Result<Vec<Object>> {
+        let Some(mark) = self.stack.iter().rposition(|x| matches!(x, Object::Mark)) else {
+            bail!("Unpickler mark not found!");
+        };
+        let items = self.stack.split_off(mark + 1);
+        self.stack.pop();
+        Ok(items)
+    }
+    fn top(&mut self) -> Result<&mut Object> {
+        self.stack
+            .last_mut()
+            .ok_or_else(|| format_err!("Unpickler stack is empty!"))
+    }
+    fn set_items(&mut self, items: Vec<Object>) -> Result<()> {
+        let Object::Dict(dict) = self.top()? else {
+            bail!("Unpickler expected dict!");
+        };
+        let mut items = items.into_iter();
+        while let (Some(key), Some(value)) = (items.next(), items.next()) {
+            dict.push((key, value));
+        }
+        Ok(())
+    }
+    fn append(&mut self, items: Vec<Object>) -> Result<()> {
+        let Object::List(list) = self.top()? else {
+            bail!("Unpickler expected list!");
+        };
+        list.extend(items);
+        Ok(())
+    }
+    fn persistent_load(pid: Object) -> Result<Object> {
+        let Object::Tuple(pid) = pid else {
+            bail!("Unpickler expected persistent id tuple, found {pid:?}!");
+        };
+        let mut pid = pid.into_iter();
+        let (
+            Some(Object::String(kind)),
+            Some(Object::Global(_, storage)),
+            Some(Object::String(key)),
+        ) = (pid.next(), pid.next(), pid.next())
+        else {
+            bail!("Unpickler unsupported persistent id!");
+        };
+        if kind != "storage" {
+            bail!("Unpickler unsupported persistent id {kind:?}!");
+        }
+        let scalar_type = match storage.as_str() {
+            "HalfStorage" => ScalarType::F16,
+            "BFloat16Storage" => ScalarType::BF16,
+            "FloatStorage" => ScalarType::F32,
+            "DoubleStorage" => ScalarType::F64,
+            "ByteStorage" => ScalarType::U8,
+            "CharStorage" => ScalarType::I8,
+            "ShortStorage" => ScalarType::I16,
+            "IntStorage" => ScalarType::I32,
+            "LongStorage" => ScalarType::I64,
+            _ => bail!("{storage} not implemented!"),
+        };
+        Ok(Object::Storage { scalar_type, key })
+    }
+    fn reduce(callable: Object, args: Object) -> Result<Object> {
+        let Object::Tuple(args) = args else {
+            bail!("Unpickler expected args tuple, found {args:?}!");
+        };
+        let Object::Global(module, name) = callable else {
+            bail!("Unpickler expected global, found {callable:?}!");
+        };
+        match (module.as_str(), name.as_str()) {
+            ("collections", "OrderedDict") => Ok(Object::Dict(Vec::new())),
+            ("torch._utils", "_rebuild_tensor_v2") => {
+                let mut args = args.into_iter();
+                let (Some(storage), Some(offset), Some(shape), Some(strides)) =
+                    (args.next(), args.next(), args.next(), args.next())
+                else {
+                    bail!("Unpickler _rebuild_tensor_v2 expected 4 args!");
+                };
+                Ok(Object::Tensor {
+                    storage: Box::new(storage),
+                    offset: offset.into_usize()?,
+                    shape: shape.into_usizes()?,
+                    strides: strides.into_usizes()?,
+                })
+            }
+            ("torch._utils", "_rebuild_parameter") => args
+                .into_iter()
+                .next()
+                .ok_or_else(|| format_err!("Unpickler _rebuild_parameter expected tensor!")),
+            _ => bail!("Unpickler {module}.{name} not implemented!"),
+        }
+    }
+    fn load(mut self) -> Result<Object> {
+        loop {
+            let opcode = self.read_u8()?;
+            match opcode {
+                // PROTO
+                0x80 => {
+                    self.read_u8()?;
+                }
+                // FRAME
+                0x95 => {
+                    self.read(8)?;
+                }
+                // STOP
+                b'.' => return self.pop(),
+                // MARK
+                b'(' => self.stack.push(Object::Mark),
+                b'N' => self.stack.push(Object::None),
+                // NEWTRUE
+                0x88 => self.stack.push(Object::Bool(true)),
+                // NEWFALSE
+                0x89 => self.stack.push(Object::Bool(false)),
+                // BININT
+                b'J' => {
+                    let x = i32::from_le_bytes(self.read(4)?.try_into().unwrap());
+                    self.stack.push(Object::Int(x.into()));
+                }
+                // BININT1
+                b'K' => {
+                    let x = self.read_u8()?;
+                    self.stack.push(Object::Int(x.into()));
+                }
+                // BININT2
+                b'M' => {
+                    let x = u16::from_le_bytes(self.read(2)?.try_into().unwrap());
+                    self.stack.push(Object::Int(x.into()));
+                }
+                // LONG1
+                0x8a => {
+                    let n = self.read_u8()? as usize;
+                    let bytes = self.read(n)?;
+                    if n > 8 {
+                        bail!("Unpickler LONG1 with {n} bytes not implemented!");
+                    }
+                    let fill = if bytes.last().map_or(false, |x| x & 0x80 != 0) {
+                        0xff
+                    } else {
+                        0
+                    };
+                    let mut buf = [fill; 8];
+                    buf[..n].copy_from_slice(bytes);
+                    self.stack.push(Object::Int(i64::from_le_bytes(buf)));
+                }
+                // BINFLOAT
+                b'G' => {
+                    let x = f64::from_be_bytes(self.read(8)?.try_into().unwrap());
+                    self.stack.push(Object::Float(x));
+                }
+                // BINUNICODE
+                b'X' => {
+                    let n = self.read_u32()? as usize;
+                    let x = self.read_string(n)?;
+                    self.stack.push(x);
+                }
+                // SHORT_BINUNICODE, SHORT_BINSTRING
+                0x8c | b'U' => {
+                    let n = self.read_u8()? as usize;
+                    let x = self.read_string(n)?;
+                    self.stack.push(x);
+                }
+                // BINSTRING
+                b'T' => {
+                    let n = self.read_u32()? as usize;
+                    let x = self.read_string(n)?;
+                    self.stack.push(x);
+                }
+                // EMPTY_TUPLE
+                b')' => self.stack.push(Object::Tuple(Vec::new())),
+                // TUPLE
+                b't' => {
+                    let items = self.pop_mark()?;
+                    self.stack.push(Object::Tuple(items));
+                }
+                // TUPLE1, TUPLE2, TUPLE3
+                0x85..=0x87 => {
+                    let n = (opcode - 0x84) as usize;
+                    if self.stack.len() < n {
+                        bail!("Unpickler stack is empty!");
+                    }
+                    let items = self.stack.split_off(self.stack.len() - n);
+                    self.stack.push(Object::Tuple(items));
+                }
+                // EMPTY_LIST
+                b']' => self.stack.push(Object::List(Vec::new())),
+                // APPEND
+                b'a' => {
+                    let item = self.pop()?;
+                    self.append(vec![item])?;
+                }
+                // APPENDS
+                b'e' => {
+                    let items = self.pop_mark()?;
+                    self.append(items)?;
+                }
+                // EMPTY_DICT
+                b'}' => self.stack.push(Object::Dict(Vec::new())),
+                // SETITEM
+                b's' => {
+                    let value = self.pop()?;
+                    let key = self.pop()?;
+                    self.set_items(vec![key, value])?;
+                }
+                // SETITEMS
+                b'u' => {
+                    let items = self.pop_mark()?;
+                    self.set_items(items)?;
+                }
+                // BINPUT
+                b'q' => {
+                    let index = self.read_u8()?.into();
+                    let x = self.top()?.clone();
+                    self.memo.insert(index, x);
+                }
+                // LONG_BINPUT
+                b'r' => {
+                    let index = self.read_u32()?;
+                    let x = self.top()?.clone();
+                    self.memo.insert(index, x);
+                }
+                // MEMOIZE
+                0x94 => {
+                    let index = self.memo.len() as u32;
+                    let x = self.top()?.clone();
+                    self.memo.insert(index, x);
+                }
+                // BINGET
+                b'h' => {
+                    let index = self.read_u8()?.into();
+                    let x = self.memo.get(&index).cloned();
+                    self.stack
+                        .push(x.ok_or_else(|| format_err!("Unpickler memo {index} not found!"))?);
+                }
+                // LONG_BINGET
+                b'j' => {
+                    let index = self.read_u32()?;
+                    let x = self.memo.get(&index).cloned();
+                    self.stack
+                        .push(x.ok_or_else(|| format_err!("Unpickler memo {index} not found!"))?);
+                }
+                // GLOBAL
+                b'c' => {
+                    let module = self.read_line()?;
+                    let name = self.read_line()?;
+                    self.stack.push(Object::Global(module, name));
+                }
+                // STACK_GLOBAL
+                0x93 => {
+                    let (Object::String(name), Object::String(module)) = (self.pop()?, self.pop()?)
+                    else {
+                        bail!("Unpickler STACK_GLOBAL expected strings!");
+                    };
+                    self.stack.push(Object::Global(module, name));
+                }
+                // BINPERSID
+                b'Q' => {
+                    let pid = self.pop()?;
+                    self.stack.push(Self::persistent_load(pid)?);
+                }
+                // REDUCE
+                b'R' => {
+                    let args = self.pop()?;
+                    let callable = self.pop()?;
+                    self.stack.push(Self::reduce(callable, args)?);
+                }
+                // BUILD
+                b'b' => {
+                    // The state, ie the `_metadata` of the state dict, is ignored.
+                    self.pop()?;
+                }
+                _ => bail!("Unpickler opcode {opcode:#x} not implemented!"),
+            }
+        }
+    }
+}
@@ -11,6 +11,13 @@ GPGPU kernels implemented with [**krnl**](https://github.com/charles-r-earp/krnl
    - Portable between platforms.
    - Save / resume training progress.
 - Fully extensible, in Rust.
+
+# Synchronization
+Operations on a device are enqueued and executed asynchronously. Reading a tensor on the host,
+for example with [`TensorBase::into_array()`](tensor::TensorBase::into_array), waits for any
+pending work. Use [`Device::wait()`](krnl::device::Device::wait) to block until all enqueued
+operations have finished, for example when timing the forward and backward passes of a model.
+On the host, operations are executed immediately and waiting is a no-op.
 */
 #![deny(unsafe_op_in_unsafe_fn)]
 #![warn(missing_docs)]
@@ -31,6 +38,9 @@ pub extern crate ndarray;
 /// num-traits
 pub extern crate num_traits;
 
+/// Errors.
+pub mod error;
+
 /// Ops.
 pub mod ops;
 
@@ -44,3 +54,7 @@ pub mod dataset;
 /// Machine Learning.
 #[cfg(feature = "learn")]
 pub mod learn;
+/// Prelude.
+///
+/// Re-exports commonly used items, ie `use autograph::prelude::*;`.
+pub mod prelude;
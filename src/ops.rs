@@ -7,13 +7,75 @@ pub trait AddAssign<R> {
     fn add_assign(&mut self, rhs: R) -> Result<()>;
 }
 
+/// Options for [`.conv2d()`](crate::tensor::ScalarTensorBase::conv2d).
+#[cfg(feature = "neural-network")]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Conv2dOptions {
+    /// Zero padding added to both sides of the height and width. Defaults to 0.
+    pub padding: [usize; 2],
+    /// Stride of the filter. Defaults to 1.
+    pub stride: [usize; 2],
+    /// Spacing between filter elements. Defaults to 1.
+    pub dilation: [usize; 2],
+}
+
+#[cfg(feature = "neural-network")]
+impl Default for Conv2dOptions {
+    fn default() -> Self {
+        Self {
+            padding: [0, 0],
+            stride: [1, 1],
+            dilation: [1, 1],
+        }
+    }
+}
+
+/// Options for [`.max_pool2d()`](crate::tensor::ScalarTensorBase::max_pool2d).
+#[cfg(feature = "neural-network")]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct MaxPool2dOptions {
+    /// Size of the pool filter.
+    pub filter: [usize; 2],
+    /// Stride of the filter.
+    pub stride: [usize; 2],
+}
+
+#[cfg(feature = "neural-network")]
+impl MaxPool2dOptions {
+    /// Options with `filter` and a stride equal to `filter`.
+    pub fn new(filter: [usize; 2]) -> Self {
+        Self {
+            filter,
+            stride: filter,
+        }
+    }
+}
+
+// The output size of a convolution or pool along an axis, or None if the padded input is
+// smaller than the dilated filter or the filter, stride or dilation is 0.
+#[cfg(feature = "neural-network")]
+pub(crate) fn conv_output_size(
+    input: usize,
+    filter: usize,
+    padding: (usize, usize),
+    stride: usize,
+    dilation: usize,
+) -> Option<usize> {
+    if filter == 0 || stride == 0 || dilation == 0 {
+        return None;
+    }
+    let x = (input + padding.0 + padding.1).checked_sub(dilation * (filter - 1) + 1)?;
+    Some(x / stride + 1)
+}
+
 // pub for tests
 #[doc(hidden)]
 #[cfg(feature = "neural-network")]
 #[derive(Clone)]
 pub struct Im2ColConv2Options {
     pub filter: [usize; 2],
-    pub padding: [usize; 2],
+    /// Zero padding (before, after) the height and width.
+    pub padding: [(usize, usize); 2],
     pub stride: [usize; 2],
     pub dilation: [usize; 2],
 }
@@ -23,7 +85,7 @@ impl Default for Im2ColConv2Options {
     fn default() -> Self {
         Self {
             filter: [0, 0],
-            padding: [0, 0],
+            padding: [(0, 0), (0, 0)],
             stride: [1, 1],
             dilation: [1, 1],
         }
@@ -36,12 +98,12 @@ impl Default for Im2ColConv2Options {
 impl Im2ColConv2Options {
     pub fn output_shape(&self, input_shape: [usize; 2]) -> [usize; 2] {
         let mut shape = input_shape;
-        for ((a, f), (s, (p, d))) in shape.iter_mut().zip(self.filter).zip(
+        for ((a, f), (s, ((pb, pa), d))) in shape.iter_mut().zip(self.filter).zip(
             self.stride
                 .into_iter()
                 .zip(self.padding.into_iter().zip(self.dilation)),
         ) {
-            *a = (*a + 2 * p - d * (f - 1) - 1) / s + 1;
+            *a = (*a + pb + pa - d * (f - 1) - 1) / s + 1;
         }
         shape
     }
@@ -55,6 +117,12 @@ pub trait Im2ColConv2 {
     fn im2col_conv2(&self, options: &Im2ColConv2Options) -> Result<Self::Output>;
 }
 
+#[cfg(feature = "neural-network")]
+pub(crate) trait Conv2Direct<W> {
+    type Output;
+    fn conv2_direct(&self, weight: &W, options: &Im2ColConv2Options) -> Result<Self::Output>;
+}
+
 // pub for tests
 #[doc(hidden)]
 #[cfg(feature = "neural-network")]
@@ -62,7 +130,8 @@ pub trait Im2ColConv2 {
 pub struct Col2ImConv2Options {
     pub shape: [usize; 2],
     pub filter: [usize; 2],
-    pub padding: [usize; 2],
+    /// Zero padding (before, after) the height and width.
+    pub padding: [(usize, usize); 2],
     pub stride: [usize; 2],
     pub dilation: [usize; 2],
 }
@@ -73,7 +142,7 @@ impl Default for Col2ImConv2Options {
         Self {
             shape: [0, 0],
             filter: [0, 0],
-            padding: [0, 0],
+            padding: [(0, 0), (0, 0)],
             stride: [1, 1],
             dilation: [1, 1],
         }
@@ -84,12 +153,12 @@ impl Default for Col2ImConv2Options {
 impl Col2ImConv2Options {
     pub(crate) fn output_shape(&self) -> [usize; 2] {
         let mut shape = self.shape;
-        for ((a, f), (s, (p, d))) in shape.iter_mut().zip(self.filter).zip(
+        for ((a, f), (s, ((pb, pa), d))) in shape.iter_mut().zip(self.filter).zip(
             self.stride
                 .into_iter()
                 .zip(self.padding.into_iter().zip(self.dilation)),
         ) {
-            *a = (*a - 1) * s + d * (f - 1) + 1 - (2 * p);
+            *a = (*a - 1) * s + d * (f - 1) + 1 - (pb + pa);
         }
         shape
     }
@@ -122,6 +191,25 @@ impl MaxPool2Options {
         }
         shape
     }
+    // Whether `index` is in a pooling window. Positions between windows, when the stride is
+    // larger than the size, and after the last window are skipped.
+    pub(crate) fn is_pooled(&self, input_shape: [usize; 2], index: [usize; 2]) -> bool {
+        let output_shape = self.output_shape(input_shape);
+        index
+            .into_iter()
+            .zip(output_shape)
+            .zip(self.size.into_iter().zip(self.strides))
+            .all(|((x, o), (f, s))| x < (o - 1) * s + f && x % s < f)
+    }
+    // Whether every position of the input is in a pooling window.
+    pub(crate) fn pools_all(&self, input_shape: [usize; 2]) -> bool {
+        let output_shape = self.output_shape(input_shape);
+        input_shape
+            .into_iter()
+            .zip(output_shape)
+            .zip(self.size.into_iter().zip(self.strides))
+            .all(|((x, o), (f, s))| s <= f && (o - 1) * s + f == x)
+    }
 }
 
 #[cfg(feature = "neural-network")]
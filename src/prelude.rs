@@ -0,0 +1,35 @@
+pub use crate::tensor::random::DeviceRng;
+pub use crate::tensor::{
+    ArcTensor, ArcTensor0, ArcTensor1, ArcTensor2, ArcTensor3, ArcTensor4, ArcTensor5, ArcTensor6,
+    ArcTensorD, CowTensor, CowTensor0, CowTensor1, CowTensor2, CowTensor3, CowTensor4, CowTensor5,
+    CowTensor6, CowTensorD, ScalarArcTensor, ScalarArcTensor0, ScalarArcTensor1, ScalarArcTensor2,
+    ScalarArcTensor3, ScalarArcTensor4, ScalarArcTensor5, ScalarArcTensor6, ScalarArcTensorD,
+    ScalarCowTensor, ScalarTensor, ScalarTensor0, ScalarTensor1, ScalarTensor2, ScalarTensor3,
+    ScalarTensor4, ScalarTensor5, ScalarTensor6, ScalarTensorD, ScalarTensorView,
+    ScalarTensorViewMut, Tensor, Tensor0, Tensor1, Tensor2, Tensor3, Tensor4, Tensor5, Tensor6,
+    TensorD, TensorView, TensorView0, TensorView1, TensorView2, TensorView3, TensorView4,
+    TensorView5, TensorView6, TensorViewD, TensorViewMut,
+};
+pub use half::{bf16, f16};
+pub use krnl::{
+    device::Device,
+    scalar::{Scalar, ScalarElem, ScalarType},
+};
+
+#[cfg(feature = "learn")]
+pub use crate::learn::criterion::{Accuracy, CrossEntropyLoss, CrossEntropyLossOptions};
+
+#[cfg(feature = "neural-network")]
+pub use crate::learn::neural_network::{
+    autograd::{
+        no_grad, Parameter, Parameter0, Parameter1, Parameter2, Parameter3, Parameter4, Parameter5,
+        Parameter6, ParameterD, Variable, Variable0, Variable1, Variable2, Variable3, Variable4,
+        Variable5, Variable6, VariableD,
+    },
+    layer::{
+        infer_flatten_size, Activation, Bilinear, Conv, Conv1, Conv2, ConvAlgo, Dense, Flatten,
+        Forward, HardSigmoid, Identity, Layer, LazyConv1, LazyConv2, LazyDense, MaxPool1, MaxPool2,
+        Relu, Relu6, Silu,
+    },
+    optimizer::{EmaModel, Optimizer, SGD},
+};
@@ -35,6 +35,43 @@ let c = c.into_array()?;
 # Ok(())
 # }
 ```
+
+# Custom kernels
+Tensors in standard layout can be borrowed as [`Slice`]s with [`.as_slice()`](TensorBase::as_slice)
+and [`.as_slice_mut()`](TensorBase::as_slice_mut), or as [`ScalarSlice`]s with
+[`.as_scalar_slice()`](ScalarTensorBase::as_scalar_slice) and
+[`.as_scalar_slice_mut()`](ScalarTensorBase::as_scalar_slice_mut). The slice begins at the first
+element of the tensor, and elements are in row major order. These return `None` if the tensor is
+not in standard layout, use [`.as_standard_layout()`](TensorBase::as_standard_layout) or
+[`.into_standard_layout()`](TensorBase::into_standard_layout) to copy if necessary.
+
+Slices can be passed to [**krnl**](https://github.com/charles-r-earp/krnl) kernels, which allows
+extending autograph without forking the crate. Kernels are compiled with krnlc, see the krnl
+documentation.
+```ignore
+use autograph::{krnl::{device::Device, macros::module}, tensor::Tensor};
+
+#[module]
+mod kernels {
+    #[cfg(not(target_arch = "spirv"))]
+    use krnl::krnl_core;
+    use krnl_core::macros::kernel;
+
+    #[kernel]
+    pub fn fill_f32(value: f32, #[item] y: &mut f32) {
+        *y = value;
+    }
+}
+
+fn main() -> anyhow::Result<()> {
+    let device = Device::builder().build()?;
+    let mut y = Tensor::<f32, _>::zeros(device.clone(), [2, 3])?;
+    kernels::fill_f32::builder()?
+        .build(device)?
+        .dispatch(1f32, y.as_slice_mut().unwrap())?;
+    Ok(())
+}
+```
 */
 use anyhow::{anyhow, bail, Result};
 use dry::macro_for;
@@ -55,7 +92,8 @@ use krnl::{
 };
 use ndarray::{
     Array, ArrayBase, ArrayView, ArrayViewMut, Axis, Dimension, IntoDimension, Ix0, Ix1, Ix2, Ix3,
-    Ix4, Ix5, Ix6, IxDyn, RawArrayView, RemoveAxis, ShapeBuilder, ShapeError, StrideShape,
+    Ix4, Ix5, Ix6, IxDyn, RawArrayView, RemoveAxis, ShapeBuilder, ShapeError, SliceArg,
+    SliceInfoElem, StrideShape,
 };
 #[cfg(feature = "device")]
 use num_traits::ToPrimitive;
@@ -64,11 +102,15 @@ use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use std::{
     convert::{TryFrom, TryInto},
     fmt::{self, Debug},
+    ops::Range,
 };
 
 mod linalg;
 mod ops;
+pub mod random;
 mod reduce;
+pub(crate) use linalg::MatmulShapes;
+pub use ops::ElementwiseTuning;
 
 fn strides_from_array<S, D>(array: &ArrayBase<S, D>) -> D
 where
@@ -118,6 +160,36 @@ where
     }
 }
 
+fn squeeze<D: RemoveAxis>(
+    dim: &D,
+    strides: &D,
+    axis: Axis,
+) -> Result<(D::Smaller, D::Smaller), ShapeError> {
+    match dim.slice().get(axis.index()) {
+        Some(1) => Ok((dim.remove_axis(axis), strides.remove_axis(axis))),
+        Some(_) => Err(ShapeError::from_kind(ndarray::ErrorKind::IncompatibleShape)),
+        None => Err(ShapeError::from_kind(ndarray::ErrorKind::OutOfBounds)),
+    }
+}
+
+fn unsqueeze<D: Dimension>(dim: &D, strides: &D, axis: Axis) -> (D::Larger, D::Larger) {
+    let index = axis.index();
+    assert!(
+        index <= dim.ndim(),
+        "axis {index} out of bounds for ndim {}",
+        dim.ndim()
+    );
+    // preserve standard layout
+    let stride = if index < dim.ndim() {
+        dim[index].wrapping_mul(strides[index])
+    } else {
+        1
+    };
+    let mut new_strides = strides.insert_axis(axis);
+    new_strides[index] = stride;
+    (dim.insert_axis(axis), new_strides)
+}
+
 pub(crate) fn flatten(shape: &[usize]) -> [usize; 2] {
     let mut iter = shape.iter().copied();
     let rows = iter.next().unwrap_or(1);
@@ -257,6 +329,72 @@ fn collapse_axis<D: Dimension>(dims: &mut D, strides: &D, Axis(axis): Axis, inde
     index as isize * strides[axis] as isize
 }
 
+fn slice_dim_strides<D: Dimension, I: SliceArg<D>>(
+    dim: &D,
+    strides: &D,
+    offset: usize,
+    info: &I,
+) -> (I::OutDim, I::OutDim, usize) {
+    assert_eq!(
+        info.in_ndim(),
+        dim.ndim(),
+        "The input dimension of the slice does not match the tensor!"
+    );
+    let abs_index = |len: usize, index: isize| {
+        if index < 0 {
+            len as isize + index
+        } else {
+            index
+        }
+    };
+    let mut out_dim = I::OutDim::zeros(info.out_ndim());
+    let mut out_strides = I::OutDim::zeros(info.out_ndim());
+    let mut offset = offset as isize;
+    let mut input_axis = 0;
+    let mut output_axis = 0;
+    for elem in info.as_ref().iter().copied() {
+        match elem {
+            SliceInfoElem::Slice { start, end, step } => {
+                let len = dim[input_axis];
+                let stride = strides[input_axis] as isize;
+                let start = abs_index(len, start);
+                let end = end.map_or(len as isize, |end| abs_index(len, end));
+                assert!(
+                    0 <= start && start <= len as isize && 0 <= end && end <= len as isize,
+                    "Slice {start}..{end} out of bounds for axis {input_axis} with length {len}!"
+                );
+                assert!(step > 0, "Slice step {step} must be positive!");
+                let (start, end, step) = (start as usize, end.max(start) as usize, step as usize);
+                let len = (end - start + step - 1) / step;
+                if len > 0 {
+                    offset += start as isize * stride;
+                }
+                out_dim[output_axis] = len;
+                out_strides[output_axis] = (stride * step as isize) as usize;
+                input_axis += 1;
+                output_axis += 1;
+            }
+            SliceInfoElem::Index(index) => {
+                let len = dim[input_axis];
+                let index = abs_index(len, index);
+                assert!(
+                    0 <= index && index < len as isize,
+                    "Index {index} out of bounds for axis {input_axis} with length {len}!"
+                );
+                offset += index * strides[input_axis] as isize;
+                input_axis += 1;
+            }
+            SliceInfoElem::NewAxis => {
+                out_dim[output_axis] = 1;
+                out_strides[output_axis] = 0;
+                output_axis += 1;
+            }
+        }
+    }
+    debug_assert!(offset >= 0);
+    (out_dim, out_strides, offset as usize)
+}
+
 fn tensor_buffer_len(offset: usize, shape: &[usize], strides: &[isize]) -> Option<usize> {
     if shape.iter().any(|x| *x == 0) {
         Some(0)
@@ -538,6 +676,33 @@ impl<S: ScalarData, D: Dimension> ScalarTensorBase<S, D> {
             offset: self.offset,
         })
     }
+    /// Removes the size 1 `axis`.
+    ///
+    /// See [`TensorBase::squeeze`].
+    pub fn squeeze(self, axis: Axis) -> Result<ScalarTensorBase<S, D::Smaller>, ShapeError>
+    where
+        D: RemoveAxis,
+    {
+        let (dim, strides) = squeeze(&self.dim, &self.strides, axis)?;
+        Ok(ScalarTensorBase {
+            dim,
+            strides,
+            buffer: self.buffer,
+            offset: self.offset,
+        })
+    }
+    /// Inserts a size 1 axis at `axis`.
+    ///
+    /// See [`TensorBase::unsqueeze`].
+    pub fn unsqueeze(self, axis: Axis) -> ScalarTensorBase<S, D::Larger> {
+        let (dim, strides) = unsqueeze(&self.dim, &self.strides, axis);
+        ScalarTensorBase {
+            dim,
+            strides,
+            buffer: self.buffer,
+            offset: self.offset,
+        }
+    }
     /// Act like a larger size and/or shape array by *broadcasting* into a larger shape, if possible.
     ///
     /// See [`TensorBase::broadcast`].
@@ -553,6 +718,23 @@ impl<S: ScalarData, D: Dimension> ScalarTensorBase<S, D> {
             offset: self.offset,
         })
     }
+    /// Expands into the larger shape `dim` as a view, without copying.
+    ///
+    /// See [`TensorBase::expand`].
+    ///
+    /// **Errors**
+    /// - The tensor can not be broadcast to `dim`.
+    pub fn expand<E>(&self, dim: E) -> Result<ScalarTensorView<E::Dim>>
+    where
+        E: IntoDimension,
+    {
+        let dim = dim.into_dimension();
+        if let Some(output) = self.broadcast(dim.clone()) {
+            Ok(output)
+        } else {
+            bail!("Can not expand {:?} to {:?}!", self.shape(), dim.slice());
+        }
+    }
     /// Borrows the tensor as a [`ScalarTensorView`].
     pub fn view(&self) -> ScalarTensorView<D> {
         ScalarTensorView {
@@ -696,6 +878,34 @@ impl<S: ScalarData, D: Dimension> ScalarTensorBase<S, D> {
             offset: self.offset,
         }
     }
+    /// Returns a view restricted to `info`.
+    ///
+    /// See [`TensorBase::slice`].
+    pub fn slice<I: SliceArg<D>>(&self, info: I) -> ScalarTensorView<I::OutDim> {
+        self.view().slice_move(info)
+    }
+    /// Returns a mutable view restricted to `info`.
+    ///
+    /// See [`TensorBase::slice_mut`].
+    pub fn slice_mut<I: SliceArg<D>>(&mut self, info: I) -> ScalarTensorViewMut<I::OutDim>
+    where
+        S: ScalarDataMut,
+    {
+        self.view_mut().slice_move(info)
+    }
+    /// Returns a tensor restricted to `info`.
+    ///
+    /// See [`TensorBase::slice_move`].
+    pub fn slice_move<I: SliceArg<D>>(self, info: I) -> ScalarTensorBase<S, I::OutDim> {
+        let (dim, strides, offset) =
+            slice_dim_strides(&self.dim, &self.strides, self.offset, &info);
+        ScalarTensorBase {
+            dim,
+            strides,
+            buffer: self.buffer,
+            offset,
+        }
+    }
     /// Selects `index` along the `axis`, collapsing the axis into length one.
     ///
     /// **Panics**
@@ -709,7 +919,26 @@ impl<S: ScalarData, D: Dimension> ScalarTensorBase<S, D> {
         self.offset = offset as usize;
         debug_assert!(self.offset < self.buffer.len());
     }
+    /// Restricts the tensor to `range` along `axis`.
+    ///
+    /// **Panics**
+    /// - `axis` or `range` is out of bounds.
+    pub(crate) fn slice_axis_into(mut self, Axis(axis): Axis, range: Range<usize>) -> Self {
+        assert!(range.start <= range.end && range.end <= self.dim[axis]);
+        if range.start < range.end {
+            let offset = range.start as isize * self.strides[axis] as isize + self.offset as isize;
+            debug_assert!(offset >= 0);
+            self.offset = offset as usize;
+        }
+        self.dim[axis] = range.end - range.start;
+        self
+    }
     /// Borrows the tensor as a [`ScalarSlice`] if standard layout.
+    ///
+    /// The slice begins at the first element, accounting for the offset of views. Returns None if
+    /// the tensor is not in standard layout.
+    ///
+    /// See [Custom kernels](crate::tensor#custom-kernels).
     pub fn as_scalar_slice(&self) -> Option<ScalarSlice> {
         if self.is_standard_layout() {
             let (slice, _offset) = self.as_raw_scalar_slice_offset();
@@ -728,6 +957,8 @@ impl<S: ScalarData, D: Dimension> ScalarTensorBase<S, D> {
         }
     }
     /// Mutably borrows the tensor as a [`ScalarSliceMut`] if standard layout.
+    ///
+    /// See [`.as_scalar_slice()`](ScalarTensorBase::as_scalar_slice).
     pub fn as_scalar_slice_mut(&mut self) -> Option<ScalarSliceMut>
     where
         S: ScalarDataMut,
@@ -878,6 +1109,20 @@ impl<S: ScalarData, D: Dimension> ScalarTensorBase<S, D> {
     pub fn to_owned(&self) -> Result<ScalarTensor<D>> {
         self.view().into_owned()
     }
+    /// Copies the tensor to a [`Vec`] in standard layout, ie row major order.
+    ///
+    /// See [`TensorBase::to_vec()`].
+    pub fn to_vec_scalar(&self) -> Result<Vec<ScalarElem>> {
+        use half::{bf16, f16};
+
+        macro_for!($T in [u8, i8, u16, i16, f16, bf16, u32, i32, f32, u64, i64, f64] {
+            if self.scalar_type() == $T::scalar_type() {
+                let vec = self.view().try_into_tensor_view::<$T>().unwrap().to_vec()?;
+                return Ok(vec.into_iter().map(Into::into).collect());
+            }
+        });
+        unreachable!()
+    }
     /// Converts into an [`ScalarArcTensor`].
     pub fn into_shared(self) -> Result<ScalarArcTensor<D>> {
         if self.offset == 0 && self.is_contiguous() {
@@ -1503,6 +1748,42 @@ impl<T: Scalar, S: Data<Elem = T>, D: Dimension> TensorBase<S, D> {
         let dim = flatten(self.shape());
         self.into_shape(dim)
     }
+    /// Removes the size 1 `axis`.
+    ///
+    /// The data is not copied, a standard layout tensor remains in standard layout.
+    ///
+    /// **Errors**
+    /// - `axis` is out of bounds.
+    /// - The length of `axis` is not 1.
+    pub fn squeeze(self, axis: Axis) -> Result<TensorBase<S, D::Smaller>, ShapeError>
+    where
+        D: RemoveAxis,
+    {
+        let (dim, strides) = squeeze(&self.dim, &self.strides, axis)?;
+        Ok(TensorBase {
+            dim,
+            strides,
+            buffer: self.buffer,
+            offset: self.offset,
+        })
+    }
+    /// Inserts a size 1 axis at `axis`.
+    ///
+    /// The data is not copied, a standard layout tensor remains in standard layout.
+    ///
+    /// **Panics**
+    /// - `axis` is greater than the number of axes.
+    ///
+    /// See <https://docs.rs/ndarray/0.15.6/ndarray/struct.ArrayBase.html#method.insert_axis>
+    pub fn unsqueeze(self, axis: Axis) -> TensorBase<S, D::Larger> {
+        let (dim, strides) = unsqueeze(&self.dim, &self.strides, axis);
+        TensorBase {
+            dim,
+            strides,
+            buffer: self.buffer,
+            offset: self.offset,
+        }
+    }
     /// Act like a larger size and/or shape array by *broadcasting* into a larger shape, if possible.
     ///
     /// See <https://docs.rs/ndarray/0.15.6/ndarray/struct.ArrayBase.html#method.broadcast>
@@ -1518,6 +1799,26 @@ impl<T: Scalar, S: Data<Elem = T>, D: Dimension> TensorBase<S, D> {
             offset: self.offset,
         })
     }
+    /// Expands into the larger shape `dim` as a view, without copying.
+    ///
+    /// Axes of length 1 are repeated and leading axes are prepended, with strides of 0. Element
+    /// wise operations read the expanded view directly, so the repeated elements are not
+    /// materialized. Like [`.broadcast()`](Self::broadcast), but returns an error instead of
+    /// None.
+    ///
+    /// **Errors**
+    /// - The tensor can not be broadcast to `dim`.
+    pub fn expand<E>(&self, dim: E) -> Result<TensorView<T, E::Dim>>
+    where
+        E: IntoDimension,
+    {
+        let dim = dim.into_dimension();
+        if let Some(output) = self.broadcast(dim.clone()) {
+            Ok(output)
+        } else {
+            bail!("Can not expand {:?} to {:?}!", self.shape(), dim.slice());
+        }
+    }
     /// Borrows the tensor as a [`TensorView`].
     pub fn view(&self) -> TensorView<T, D> {
         TensorView {
@@ -1671,6 +1972,59 @@ impl<T: Scalar, S: Data<Elem = T>, D: Dimension> TensorBase<S, D> {
             offset: self.offset,
         }
     }
+    /// Returns a view restricted to `info`.
+    ///
+    /// Slices are created with the [`s!`](ndarray::s) macro. The view is strided, no data is
+    /// copied. Use [`.contiguous()`](TensorBase::contiguous) for operations that require
+    /// standard layout.
+    ///
+    ///```no_run
+    /// # use autograph::{anyhow::Result, krnl::device::Device, tensor::Tensor};
+    /// # use ndarray::{arr2, s};
+    /// # fn main() -> Result<()> {
+    /// # let device = Device::host();
+    /// let x = Tensor::from(arr2(&[[1f32, 2., 3.], [4., 5., 6.]])).into_device(device)?;
+    /// let y = x.slice(s![.., 1..;2]);
+    /// assert_eq!(y.into_array()?, arr2(&[[2f32], [5.]]));
+    /// # Ok(())
+    /// # }
+    ///```
+    ///
+    /// **Panics**
+    /// - The number of axes of `info` does not match the tensor.
+    /// - An index or range is out of bounds.
+    /// - A step is not positive.
+    ///
+    /// See <https://docs.rs/ndarray/0.15.6/ndarray/struct.ArrayBase.html#method.slice>
+    pub fn slice<I: SliceArg<D>>(&self, info: I) -> TensorView<T, I::OutDim> {
+        self.view().slice_move(info)
+    }
+    /// Returns a mutable view restricted to `info`.
+    ///
+    /// **Panics**
+    ///
+    /// See [`.slice()`](TensorBase::slice).
+    pub fn slice_mut<I: SliceArg<D>>(&mut self, info: I) -> TensorViewMut<T, I::OutDim>
+    where
+        S: DataMut,
+    {
+        self.view_mut().slice_move(info)
+    }
+    /// Returns a tensor restricted to `info`.
+    ///
+    /// **Panics**
+    ///
+    /// See [`.slice()`](TensorBase::slice).
+    pub fn slice_move<I: SliceArg<D>>(self, info: I) -> TensorBase<S, I::OutDim> {
+        let (dim, strides, offset) =
+            slice_dim_strides(&self.dim, &self.strides, self.offset, &info);
+        TensorBase {
+            dim,
+            strides,
+            buffer: self.buffer,
+            offset,
+        }
+    }
     /// Selects `index` along the `axis`, collapsing the axis into length one.
     ///
     /// **Panics**
@@ -1686,6 +2040,11 @@ impl<T: Scalar, S: Data<Elem = T>, D: Dimension> TensorBase<S, D> {
         self.offset = offset;
     }
     /// Borrows the tensor as a [`Slice`] if standard layout.
+    ///
+    /// The slice begins at the first element, accounting for the offset of views. Returns None if
+    /// the tensor is not in standard layout.
+    ///
+    /// See [Custom kernels](crate::tensor#custom-kernels).
     pub fn as_slice(&self) -> Option<Slice<T>> {
         if self.is_standard_layout() {
             let (slice, _offset) = self.as_raw_slice_offset();
@@ -1704,6 +2063,8 @@ impl<T: Scalar, S: Data<Elem = T>, D: Dimension> TensorBase<S, D> {
         }
     }
     /// Mutably borrows the tensor as a [`SliceMut`] if standard layout.
+    ///
+    /// See [`.as_slice()`](TensorBase::as_slice).
     pub fn as_slice_mut(&mut self) -> Option<SliceMut<T>>
     where
         S: DataMut,
@@ -1915,6 +2276,20 @@ impl<T: Scalar, S: Data<Elem = T>, D: Dimension> TensorBase<S, D> {
             bail!("TensorBase::into_array tensor is not contiguous!")
         }
     }
+    /// Moves the tensor into a [`Vec`] in standard layout, ie row major order.
+    ///
+    /// **Errors**
+    /// - See [`.into_standard_layout()`](TensorBase::into_standard_layout).
+    /// - See [`.into_array()`](TensorBase::into_array).
+    pub fn into_vec(self) -> Result<Vec<T>> {
+        Ok(self.into_standard_layout()?.into_array()?.into_raw_vec())
+    }
+    /// Copies the tensor to a [`Vec`] in standard layout, ie row major order.
+    ///
+    /// See [`.into_vec()`](TensorBase::into_vec).
+    pub fn to_vec(&self) -> Result<Vec<T>> {
+        self.view().into_vec()
+    }
     /// Borrows the tensor as an array view if on the host.
     pub fn as_array(&self) -> Option<ArrayView<T, D>> {
         self.buffer.as_host_slice().map(|host_slice| unsafe {
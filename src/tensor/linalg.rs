@@ -46,13 +46,16 @@ mod kernels {
                     alpha: $a,
                     #[global] a: Slice<$t>,
                     offset_a: u32,
+                    batch_stride_a: i32,
                     #[group] a_group: UnsafeSlice<$a, { 16 * (8 + 1) }>,
                     #[global] b: Slice<$t>,
                     offset_b: u32,
+                    batch_stride_b: i32,
                     #[group] b_group: UnsafeSlice<$a, { 8 * (16 + 1) }>,
                     beta: $a,
                     #[global] c: UnsafeSlice<$t>,
                     offset_c: u32,
+                    batch_stride_c: i32,
                 ) {
                     type T = $t;
                     type A = $a;
@@ -77,7 +80,14 @@ mod kernels {
                     let groups_mn = groups_m * groups_n;
                     let global_unroll = groups_k * unroll;
 
-                    let group_id = kernel.group_id as usize;
+                    // Each matrix of the batch is computed by groups_k * groups_mn groups.
+                    let groups_batch = groups_k * groups_mn;
+                    let batch = kernel.group_id as usize / groups_batch;
+                    let offset_a = offset_a as i32 + batch as i32 * batch_stride_a;
+                    let offset_b = offset_b as i32 + batch as i32 * batch_stride_b;
+                    let offset_c = offset_c as i32 + batch as i32 * batch_stride_c;
+
+                    let group_id = kernel.group_id as usize % groups_batch;
                     let group_k = group_id / groups_mn;
                     let group_mn = group_id % groups_mn;
                     let group_m = group_mn / groups_n;
@@ -138,7 +148,7 @@ mod kernels {
                             let tile_k = u * threads_k_a + thread_k_a;
                             let global_k = global_k + tile_k;
                             let a = if global_m < m && global_k < k {
-                                a[(global_m as i32 * RSA + global_k as i32 * CSA + offset_b as i32) as usize].cast()
+                                a[(global_m as i32 * RSA + global_k as i32 * CSA + offset_a) as usize].cast()
                             } else {
                                 A::zero()
                             };
@@ -154,7 +164,7 @@ mod kernels {
                             let tile_k = u * threads_k_b + thread_k_b;
                             let global_k = global_k + tile_k;
                             let b = if global_k < k && global_n < n {
-                                b[(global_k as i32 * RSB + global_n as i32 * CSB + offset_b as i32) as usize].cast()
+                                b[(global_k as i32 * RSB + global_n as i32 * CSB + offset_b) as usize].cast()
                             } else {
                                 A::zero()
                             };
@@ -176,7 +186,7 @@ mod kernels {
                                 let tile_k = u * threads_k_a + thread_k_a;
                                 let global_k = global_k + tile_k;
                                 a_prefetch[u] = if global_m < m && global_k < k {
-                                    a[(global_m as i32 * RSA + global_k as i32 * CSA + offset_a as i32) as usize]
+                                    a[(global_m as i32 * RSA + global_k as i32 * CSA + offset_a) as usize]
                                 } else {
                                     T::zero()
                                 };
@@ -189,7 +199,7 @@ mod kernels {
                                 let tile_k = u * threads_k_b + thread_k_b;
                                 let global_k = global_k + tile_k;
                                 b_prefetch[u] = if global_k < k && global_n < n {
-                                    b[(global_k as i32 * RSB + global_n as i32 * CSB + offset_b as i32) as usize]
+                                    b[(global_k as i32 * RSB + global_n as i32 * CSB + offset_b) as usize]
                                 } else {
                                     T::zero()
                                 };
@@ -271,7 +281,7 @@ mod kernels {
                         unroll! { for j in 0 .. 2 {
                             let global_n = global_n + j * threads_n + thread_n;
                             if global_m < m && global_n < n {
-                                let index = ((global_m as i32 * RSC + global_n as i32 * CSC + offset_c as i32) as usize) * groups_k + group_k;
+                                let index = ((global_m as i32 * RSC + global_n as i32 * CSC + offset_c) as usize) * groups_k + group_k;
                                 if beta == A::zero() {
                                     unsafe {
                                         *c.unsafe_index_mut(index) = (alpha * c_thread[i][j]).cast();
@@ -385,17 +395,6 @@ fn gemm(
     beta: ScalarElem,
     mut c: ScalarTensorViewMut2,
 ) -> Result<()> {
-    let a_scalar_type = a.scalar_type();
-    let b_scalar_type = b.scalar_type();
-    let c_scalar_type = c.scalar_type();
-    if a_scalar_type != b_scalar_type {
-        bail!("a_scalar_type != b_scalar_type, {a_scalar_type:?} != {b_scalar_type:?}");
-    }
-    if a_scalar_type != c_scalar_type {
-        bail!("a_scalar_type != c_scalar_type, {a_scalar_type:?} != {c_scalar_type:?}");
-    }
-    let scalar_type = c_scalar_type;
-
     let (m, k) = a.dim();
     let (k2, n) = b.dim();
     let (m2, n2) = c.dim();
@@ -410,24 +409,133 @@ fn gemm(
         bail!("b_cols != c_rows, {} != {}", n, n2);
     }
 
-    let m = m.to_u32().unwrap();
-    let k = k.to_u32().unwrap();
-    let n = n.to_u32().unwrap();
-
     let [rsa, csa]: [isize; 2] = a.strides().try_into().unwrap();
-    let [rsa, csa] = [rsa.to_i32().unwrap(), csa.to_i32().unwrap()];
-
     let [rsb, csb]: [isize; 2] = b.strides().try_into().unwrap();
-    let [rsb, csb] = [rsb.to_i32().unwrap(), csb.to_i32().unwrap()];
-
     let [rsc, csc]: [isize; 2] = c.strides().try_into().unwrap();
-    let [rsc, csc] = [rsc.to_i32().unwrap(), csc.to_i32().unwrap()];
 
     let (a, offset_a) = a.as_raw_scalar_slice_offset();
-    let offset_a = offset_a.to_u32().unwrap();
     let (b, offset_b) = b.as_raw_scalar_slice_offset();
+    let (c, offset_c) = c.as_raw_scalar_slice_offset_mut();
+
+    gemm_impl(
+        alpha,
+        [1, m, k, n],
+        a,
+        offset_a,
+        [0, rsa, csa],
+        b,
+        offset_b,
+        [0, rsb, csb],
+        beta,
+        c,
+        offset_c,
+        [0, rsc, csc],
+    )
+}
+
+/// Computes `c[i] = alpha * a[i] x b[i] + beta * c[i]` for each matrix `i` of the batch in a
+/// single dispatch.
+///
+/// The batch strides may be 0, ie when an operand is broadcasted with
+/// [`.expand()`](TensorBase::expand).
+#[cfg(feature = "device")]
+fn gemm_strided_batched(
+    alpha: ScalarElem,
+    a: ScalarTensorView3,
+    b: ScalarTensorView3,
+    beta: ScalarElem,
+    mut c: ScalarTensorViewMut3,
+) -> Result<()> {
+    let (batch, m, k) = a.dim();
+    let (batch2, k2, n) = b.dim();
+    let (batch3, m2, n2) = c.dim();
+
+    if batch != batch2 || batch != batch3 {
+        bail!("a_batch, b_batch, c_batch not equal, {batch}, {batch2}, {batch3}");
+    }
+    if m != m2 {
+        bail!("a_rows != c_rows, {} != {}", m, m2);
+    }
+    if k != k2 {
+        bail!("a_cols != b_rows, {} != {}", k, k2);
+    }
+    if n != n2 {
+        bail!("b_cols != c_rows, {} != {}", n, n2);
+    }
+
+    let [bsa, rsa, csa]: [isize; 3] = a.strides().try_into().unwrap();
+    let [bsb, rsb, csb]: [isize; 3] = b.strides().try_into().unwrap();
+    let [bsc, rsc, csc]: [isize; 3] = c.strides().try_into().unwrap();
+
+    let (a, offset_a) = a.as_raw_scalar_slice_offset();
+    let (b, offset_b) = b.as_raw_scalar_slice_offset();
+    let (c, offset_c) = c.as_raw_scalar_slice_offset_mut();
+
+    gemm_impl(
+        alpha,
+        [batch, m, k, n],
+        a,
+        offset_a,
+        [bsa, rsa, csa],
+        b,
+        offset_b,
+        [bsb, rsb, csb],
+        beta,
+        c,
+        offset_c,
+        [bsc, rsc, csc],
+    )
+}
+
+#[cfg(feature = "device")]
+#[allow(clippy::too_many_arguments)]
+fn gemm_impl(
+    alpha: ScalarElem,
+    [batch, m, k, n]: [usize; 4],
+    a: ScalarSlice,
+    offset_a: usize,
+    [bsa, rsa, csa]: [isize; 3],
+    b: ScalarSlice,
+    offset_b: usize,
+    [bsb, rsb, csb]: [isize; 3],
+    beta: ScalarElem,
+    mut c: ScalarSliceMut,
+    offset_c: usize,
+    [bsc, rsc, csc]: [isize; 3],
+) -> Result<()> {
+    let a_scalar_type = a.scalar_type();
+    let b_scalar_type = b.scalar_type();
+    let c_scalar_type = c.scalar_type();
+    if a_scalar_type != b_scalar_type {
+        bail!("a_scalar_type != b_scalar_type, {a_scalar_type:?} != {b_scalar_type:?}");
+    }
+    if a_scalar_type != c_scalar_type {
+        bail!("a_scalar_type != c_scalar_type, {a_scalar_type:?} != {c_scalar_type:?}");
+    }
+
+    let batch = batch.to_u32().unwrap();
+    let m = m.to_u32().unwrap();
+    let k = k.to_u32().unwrap();
+    let n = n.to_u32().unwrap();
+
+    let [bsa, rsa, csa] = [
+        bsa.to_i32().unwrap(),
+        rsa.to_i32().unwrap(),
+        csa.to_i32().unwrap(),
+    ];
+    let [bsb, rsb, csb] = [
+        bsb.to_i32().unwrap(),
+        rsb.to_i32().unwrap(),
+        csb.to_i32().unwrap(),
+    ];
+    let [bsc, rsc, csc] = [
+        bsc.to_i32().unwrap(),
+        rsc.to_i32().unwrap(),
+        csc.to_i32().unwrap(),
+    ];
+
+    let offset_a = offset_a.to_u32().unwrap();
     let offset_b = offset_b.to_u32().unwrap();
-    let (mut c, offset_c) = c.as_raw_scalar_slice_offset_mut();
     let offset_c = offset_c.to_u32().unwrap();
 
     let device = c.device();
@@ -437,7 +545,8 @@ fn gemm(
         let b = Slice::<f32>::try_from(b.clone()).unwrap();
         let mut c = SliceMut::<f32>::try_from(c.as_scalar_slice_mut()).unwrap();
 
-        let groups_k = if k >= (2 * m * n).max(3 * 64) {
+        // Splitting k is only used for a single large matrix.
+        let groups_k = if batch == 1 && k >= (2 * m * n).max(3 * 64) {
             (k / 64).min(64)
         } else {
             1
@@ -452,7 +561,7 @@ fn gemm(
             .with_threads(64)
             .specialize(m, k, n, groups_k, rsa, csa, rsb, csb, rsc, csc)
             .build(device.clone())?
-            .with_groups(groups_k * groups_m * groups_n);
+            .with_groups(batch * groups_k * groups_m * groups_n);
         if groups_k > 1 {
             let mut c_tmp = unsafe {
                 Tensor::<f32, _>::uninit(device.clone(), [(m * n) as usize, groups_k as usize])?
@@ -462,11 +571,14 @@ fn gemm(
                     alpha,
                     a,
                     offset_a,
+                    bsa,
                     b,
                     offset_b,
+                    bsb,
                     0f32,
                     c_tmp.as_slice_mut().unwrap(),
                     offset_c,
+                    bsc,
                 )?;
                 /*if false {
                     unsafe {
@@ -485,11 +597,14 @@ fn gemm(
                     alpha,
                     a,
                     offset_a,
+                    bsa,
                     b,
                     offset_b,
+                    bsb,
                     beta,
                     c.as_slice_mut(),
                     offset_c,
+                    bsc,
                 )?;
             }
         }
@@ -512,10 +627,21 @@ fn gemm(
                 .with_threads(64)
                 .specialize(m, k, n, groups_k, rsa, csa, rsb, csb, rsc, csc)
                 .build(device.clone())?
-                .with_groups(groups_m * groups_n);
+                .with_groups(batch * groups_m * groups_n);
             unsafe {
-                gemm_kernel
-                    .dispatch(alpha, a, offset_a, b, offset_b, beta, c.as_slice_mut(), offset_c)?;
+                gemm_kernel.dispatch(
+                    alpha,
+                    a,
+                    offset_a,
+                    bsa,
+                    b,
+                    offset_b,
+                    bsb,
+                    beta,
+                    c.as_slice_mut(),
+                    offset_c,
+                    bsc,
+                )?;
             }
             return Ok(());
         }
@@ -593,6 +719,52 @@ impl<T: Scalar, S1: Data<Elem = T>, S2: Data<Elem = T>> Dot<TensorBase<S2, Ix2>>
     }
 }
 
+/// Matrix-vector product, `[m, k] x [k] -> [m]`.
+impl<T: Scalar, S1: Data<Elem = T>, S2: Data<Elem = T>> Dot<TensorBase<S2, Ix1>>
+    for TensorBase<S1, Ix2>
+{
+    type Output = Result<Tensor1<T>>;
+    fn dot(&self, rhs: &TensorBase<S2, Ix1>) -> Self::Output {
+        let (m, k) = self.dim();
+        if k != rhs.dim() {
+            bail!(
+                "Dot shapes {:?} x {:?} incompatible!",
+                self.shape(),
+                rhs.shape()
+            );
+        }
+        if let Some((lhs_array, rhs_array)) = self.as_array().zip(rhs.as_array()) {
+            return Ok(lhs_array.dot(&rhs_array).into());
+        }
+        let rhs = rhs.as_standard_layout()?;
+        let rhs = rhs.view().into_shape([k, 1]).unwrap();
+        Ok(self.dot(&rhs)?.into_shape(m).unwrap())
+    }
+}
+
+/// Vector-matrix product, `[k] x [k, n] -> [n]`.
+impl<T: Scalar, S1: Data<Elem = T>, S2: Data<Elem = T>> Dot<TensorBase<S2, Ix2>>
+    for TensorBase<S1, Ix1>
+{
+    type Output = Result<Tensor1<T>>;
+    fn dot(&self, rhs: &TensorBase<S2, Ix2>) -> Self::Output {
+        let (k, n) = rhs.dim();
+        if self.dim() != k {
+            bail!(
+                "Dot shapes {:?} x {:?} incompatible!",
+                self.shape(),
+                rhs.shape()
+            );
+        }
+        if let Some((lhs_array, rhs_array)) = self.as_array().zip(rhs.as_array()) {
+            return Ok(lhs_array.dot(&rhs_array).into());
+        }
+        let lhs = self.as_standard_layout()?;
+        let lhs = lhs.view().into_shape([1, k]).unwrap();
+        Ok(lhs.dot(rhs)?.into_shape(n).unwrap())
+    }
+}
+
 impl<S1: ScalarData, S2: ScalarData> Dot<ScalarTensorBase<S2, Ix2>> for ScalarTensorBase<S1, Ix2> {
     type Output = Result<ScalarTensor2>;
     fn dot(&self, rhs: &ScalarTensorBase<S2, Ix2>) -> Self::Output {
@@ -638,6 +810,369 @@ impl<S1: ScalarData, S2: ScalarData> Dot<ScalarTensorBase<S2, Ix2>> for ScalarTe
     }
 }
 
+/// Matrix-vector product, `[m, k] x [k] -> [m]`.
+impl<S1: ScalarData, S2: ScalarData> Dot<ScalarTensorBase<S2, Ix1>> for ScalarTensorBase<S1, Ix2> {
+    type Output = Result<ScalarTensor1>;
+    fn dot(&self, rhs: &ScalarTensorBase<S2, Ix1>) -> Self::Output {
+        let (m, k) = self.dim();
+        if k != rhs.dim() {
+            bail!(
+                "Dot shapes {:?} x {:?} incompatible!",
+                self.shape(),
+                rhs.shape()
+            );
+        }
+        let rhs = rhs.as_standard_layout()?;
+        let rhs = rhs.view().into_shape([k, 1]).unwrap();
+        Ok(self.dot(&rhs)?.into_shape(m).unwrap())
+    }
+}
+
+/// Vector-matrix product, `[k] x [k, n] -> [n]`.
+impl<S1: ScalarData, S2: ScalarData> Dot<ScalarTensorBase<S2, Ix2>> for ScalarTensorBase<S1, Ix1> {
+    type Output = Result<ScalarTensor1>;
+    fn dot(&self, rhs: &ScalarTensorBase<S2, Ix2>) -> Self::Output {
+        let (k, n) = rhs.dim();
+        if self.dim() != k {
+            bail!(
+                "Dot shapes {:?} x {:?} incompatible!",
+                self.shape(),
+                rhs.shape()
+            );
+        }
+        let lhs = self.as_standard_layout()?;
+        let lhs = lhs.view().into_shape([1, k]).unwrap();
+        Ok(lhs.dot(rhs)?.into_shape(n).unwrap())
+    }
+}
+
+impl<T: Scalar, S: Data<Elem = T>> TensorBase<S, Ix1> {
+    /// Outer product, `[m] x [n] -> [m, n]`.
+    ///
+    /// Computes `self[i] * rhs[j]` as the matrix product of a column and a row vector.
+    ///
+    /// **Errors**
+    /// - The tensors are on different devices.
+    /// - The operation could not be executed on the device.
+    pub fn outer<S2: Data<Elem = T>>(&self, rhs: &TensorBase<S2, Ix1>) -> Result<Tensor2<T>> {
+        let (m, n) = (self.dim(), rhs.dim());
+        let lhs = self.as_standard_layout()?;
+        let rhs = rhs.as_standard_layout()?;
+        lhs.view()
+            .into_shape([m, 1])
+            .unwrap()
+            .dot(&rhs.view().into_shape([1, n]).unwrap())
+    }
+}
+
+impl<S: ScalarData> ScalarTensorBase<S, Ix1> {
+    /// Outer product, `[m] x [n] -> [m, n]`.
+    ///
+    /// See [`TensorBase::outer()`].
+    ///
+    /// **Errors**
+    /// - The tensors have different scalar types.
+    /// - See [`TensorBase::outer()`].
+    pub fn outer<S2: ScalarData>(&self, rhs: &ScalarTensorBase<S2, Ix1>) -> Result<ScalarTensor2> {
+        let (m, n) = (self.dim(), rhs.dim());
+        let lhs = self.as_standard_layout()?;
+        let rhs = rhs.as_standard_layout()?;
+        lhs.view()
+            .into_shape([m, 1])
+            .unwrap()
+            .dot(&rhs.view().into_shape([1, n]).unwrap())
+    }
+}
+
+impl<T: Scalar, S: Data<Elem = T>> TensorBase<S, Ix3> {
+    /// Batched matrix multiplication, `[batch, m, k] x [batch, k, n] -> [batch, m, n]`.
+    ///
+    /// On device, the batch is computed in a single dispatch. Either operand may be
+    /// broadcasted along the batch axis with [`.expand()`](TensorBase::expand).
+    ///
+    ///```no_run
+    /// # use autograph::{krnl::device::Device, tensor::Tensor};
+    /// # fn main() -> anyhow::Result<()> {
+    /// # let device = Device::host();
+    /// let q = Tensor::<f32, _>::zeros(device.clone(), [8, 16, 64])?;
+    /// let k = Tensor::<f32, _>::zeros(device.clone(), [8, 16, 64])?;
+    /// let scores = q.bmm(&k.permuted_axes([0, 2, 1]))?;
+    /// # Ok(())
+    /// # }
+    ///```
+    ///
+    /// **Errors**
+    /// - The shapes are incompatible.
+    /// - The tensors are on different devices.
+    /// - The operation could not be executed on the device.
+    pub fn bmm<S2: Data<Elem = T>>(&self, rhs: &TensorBase<S2, Ix3>) -> Result<Tensor3<T>> {
+        let (batch, m, k) = self.dim();
+        let (batch2, k2, n) = rhs.dim();
+        if batch != batch2 || k != k2 {
+            bail!(
+                "bmm shapes {:?} x {:?} incompatible!",
+                self.shape(),
+                rhs.shape()
+            );
+        }
+        let device = self.device();
+        if device != rhs.device() {
+            bail!("bmm devices {device:?} != {:?}!", rhs.device());
+        }
+        if let Some((lhs_array, rhs_array)) = self.as_array().zip(rhs.as_array()) {
+            let mut output = Array::zeros([batch, m, n]);
+            for ((a, b), mut c) in lhs_array
+                .outer_iter()
+                .zip(rhs_array.outer_iter())
+                .zip(output.outer_iter_mut())
+            {
+                ndarray::linalg::general_mat_mul(T::one(), &a, &b, T::zero(), &mut c);
+            }
+            return Ok(output.into());
+        }
+        #[cfg(not(feature = "device"))]
+        {
+            unreachable!()
+        }
+        #[cfg(feature = "device")]
+        {
+            let mut output = unsafe { Tensor::uninit(device, [batch, m, n])? };
+            gemm_strided_batched(
+                T::one().into(),
+                self.view().into(),
+                rhs.view().into(),
+                T::zero().into(),
+                output.view_mut().into(),
+            )?;
+            Ok(output)
+        }
+    }
+}
+
+impl<T: Scalar, S: Data<Elem = T>, D: Dimension> TensorBase<S, D> {
+    /// Matrix product with NumPy semantics.
+    ///
+    /// - 1D x 1D is the inner product, with shape [].
+    /// - 2D x 2D is the matrix product, see [`.dot()`](Dot::dot).
+    /// - A 1D `self` is treated as a row vector [1, k], and a 1D `rhs` as a column vector
+    /// [k, 1]. The added axis is removed from the output.
+    /// - Otherwise the inputs are batches of matrices, ie [.., m, k] x [.., k, n] -> [.., m, n].
+    /// The batch axes are broadcasted together, see [`.expand()`](TensorBase::expand), and the
+    /// matrices are multiplied with [`.bmm()`](TensorBase::bmm).
+    ///
+    ///```no_run
+    /// # use autograph::{krnl::device::Device, tensor::Tensor};
+    /// # fn main() -> anyhow::Result<()> {
+    /// # let device = Device::host();
+    /// let x = Tensor::<f32, _>::zeros(device.clone(), [8, 1, 16, 32])?;
+    /// let w = Tensor::<f32, _>::zeros(device.clone(), [4, 32, 10])?;
+    /// let y = x.matmul(&w)?;
+    /// assert_eq!(y.shape(), &[8, 4, 16, 10]);
+    /// # Ok(())
+    /// # }
+    ///```
+    ///
+    /// **Errors**
+    /// - Either input is 0 dimensional.
+    /// - The shapes are incompatible.
+    /// - See [`.bmm()`](TensorBase::bmm).
+    pub fn matmul<S2: Data<Elem = T>, D2: Dimension>(
+        &self,
+        rhs: &TensorBase<S2, D2>,
+    ) -> Result<TensorD<T>> {
+        let MatmulShapes {
+            lhs_dim,
+            rhs_dim,
+            output_dim,
+        } = MatmulShapes::new(self.shape(), rhs.shape())?;
+        let lhs = matmul_operand(self.view().into_dyn(), lhs_dim, false);
+        let rhs = matmul_operand(rhs.view().into_dyn(), rhs_dim, true);
+        let output = merge_batch_axes(lhs)?.bmm(&merge_batch_axes(rhs)?)?;
+        Ok(output.into_shape(output_dim).unwrap())
+    }
+}
+
+/// The shapes of a [`matmul`](TensorBase::matmul).
+///
+/// The operands have shape batch + [m, k] and batch + [k, n], with 1D operands promoted to
+/// matrices. The output has shape batch + [m, n], with the promoted axes removed.
+#[derive(Debug)]
+pub(crate) struct MatmulShapes {
+    pub(crate) lhs_dim: IxDyn,
+    pub(crate) rhs_dim: IxDyn,
+    pub(crate) output_dim: IxDyn,
+}
+
+impl MatmulShapes {
+    pub(crate) fn new(lhs: &[usize], rhs: &[usize]) -> Result<Self> {
+        if lhs.is_empty() || rhs.is_empty() {
+            bail!("matmul shapes {lhs:?} x {rhs:?} must have at least 1 dimension!");
+        }
+        let (lhs_batch, [m, k]) = if let [k] = lhs {
+            (&[][..], [1, *k])
+        } else {
+            let (batch, matrix) = lhs.split_at(lhs.len() - 2);
+            (batch, [matrix[0], matrix[1]])
+        };
+        let (rhs_batch, [k2, n]) = if let [k] = rhs {
+            (&[][..], [*k, 1])
+        } else {
+            let (batch, matrix) = rhs.split_at(rhs.len() - 2);
+            (batch, [matrix[0], matrix[1]])
+        };
+        if k != k2 {
+            bail!("matmul shapes {lhs:?} x {rhs:?} incompatible!");
+        }
+        let batch_ndim = lhs_batch.len().max(rhs_batch.len());
+        let mut batch = vec![1; batch_ndim];
+        for (i, d) in batch.iter_mut().rev().enumerate() {
+            let a = lhs_batch.iter().rev().nth(i).copied().unwrap_or(1);
+            let b = rhs_batch.iter().rev().nth(i).copied().unwrap_or(1);
+            *d = match (a, b) {
+                (a, b) if a == b => a,
+                (1, b) => b,
+                (a, 1) => a,
+                _ => bail!("matmul shapes {lhs:?} x {rhs:?} can not be broadcast!"),
+            };
+        }
+        let dim = |tail: &[usize]| IxDyn(&[batch.as_slice(), tail].concat());
+        let output_tail: Vec<usize> = (lhs.len() > 1)
+            .then_some(m)
+            .into_iter()
+            .chain((rhs.len() > 1).then_some(n))
+            .collect();
+        Ok(Self {
+            lhs_dim: dim(&[m, k]),
+            rhs_dim: dim(&[k, n]),
+            output_dim: dim(&output_tail),
+        })
+    }
+}
+
+// Promotes a 1D `x` to a row, or a `column`, and broadcasts to `dim`.
+fn matmul_operand<T: Scalar>(x: TensorViewD<T>, dim: IxDyn, column: bool) -> TensorViewD<T> {
+    let (x_dim, x_strides) = if x.ndim() == 1 {
+        let (d, s) = (x.dim[0], x.strides[0]);
+        if column {
+            (IxDyn(&[d, 1]), IxDyn(&[s, 0]))
+        } else {
+            (IxDyn(&[1, d]), IxDyn(&[0, s]))
+        }
+    } else {
+        (x.dim.clone(), x.strides.clone())
+    };
+    let (dim, strides) = broadcast(&x_dim, &x_strides, dim).unwrap();
+    TensorView {
+        dim,
+        strides,
+        buffer: x.buffer,
+        offset: x.offset,
+    }
+}
+
+// Merges the batch axes of `x` into one axis, copying only if the strides are incompatible.
+fn merge_batch_axes<T: Scalar>(x: TensorViewD<T>) -> Result<CowTensor3<T>> {
+    let ndim = x.ndim();
+    let (m, k) = (x.dim[ndim - 2], x.dim[ndim - 1]);
+    let batch_size = x.shape()[..ndim - 2].iter().product();
+    let mut batch_stride = None;
+    let mut next_stride = 0;
+    let mut mergeable = true;
+    for (d, s) in x.shape()[..ndim - 2]
+        .iter()
+        .zip(&x.strides()[..ndim - 2])
+        .rev()
+    {
+        if *d == 1 {
+            continue;
+        }
+        if batch_stride.is_none() {
+            batch_stride.replace(*s);
+        } else if *s != next_stride {
+            mergeable = false;
+            break;
+        }
+        next_stride = s * *d as isize;
+    }
+    if mergeable {
+        let batch_stride = batch_stride.unwrap_or_default() as usize;
+        return Ok(TensorView {
+            dim: Ix3(batch_size, m, k),
+            strides: Ix3(batch_stride, x.strides[ndim - 2], x.strides[ndim - 1]),
+            buffer: x.buffer,
+            offset: x.offset,
+        }
+        .into());
+    }
+    Ok(x.into_standard_layout()?
+        .into_shape([batch_size, m, k])
+        .unwrap()
+        .into())
+}
+
+impl<S: ScalarData> ScalarTensorBase<S, Ix3> {
+    /// Batched matrix multiplication, `[batch, m, k] x [batch, k, n] -> [batch, m, n]`.
+    ///
+    /// See [`TensorBase::bmm()`].
+    ///
+    /// **Errors**
+    /// - The tensors have different scalar types.
+    /// - See [`TensorBase::bmm()`].
+    pub fn bmm<S2: ScalarData>(&self, rhs: &ScalarTensorBase<S2, Ix3>) -> Result<ScalarTensor3> {
+        let scalar_type = self.scalar_type();
+        if scalar_type != rhs.scalar_type() {
+            bail!(
+                "Can not bmm tensors of different types {:?} != {:?}!",
+                scalar_type,
+                rhs.scalar_type()
+            );
+        }
+        macro_wrap!(paste! { match scalar_type {
+            macro_for!($T in [u8, i8, u16, i16, f16, bf16, u32, i32, f32, u64, i64, f64] {
+                ScalarType::[<$T:upper>] => {
+                    let lhs = TensorView3::<$T>::try_from(self.view()).unwrap();
+                    let rhs = TensorView3::<$T>::try_from(rhs.view()).unwrap();
+                    lhs.bmm(&rhs).map(Into::into)
+                }
+            })
+            _ => bail!("bmm unimplemented for {scalar_type:?}!"),
+        }})
+    }
+}
+
+impl<S: ScalarData, D: Dimension> ScalarTensorBase<S, D> {
+    /// Matrix product with NumPy semantics.
+    ///
+    /// See [`TensorBase::matmul()`].
+    ///
+    /// **Errors**
+    /// - The tensors have different scalar types.
+    /// - See [`TensorBase::matmul()`].
+    pub fn matmul<S2: ScalarData, D2: Dimension>(
+        &self,
+        rhs: &ScalarTensorBase<S2, D2>,
+    ) -> Result<ScalarTensorD> {
+        let scalar_type = self.scalar_type();
+        if scalar_type != rhs.scalar_type() {
+            bail!(
+                "Can not matmul tensors of different types {:?} != {:?}!",
+                scalar_type,
+                rhs.scalar_type()
+            );
+        }
+        macro_wrap!(paste! { match scalar_type {
+            macro_for!($T in [u8, i8, u16, i16, f16, bf16, u32, i32, f32, u64, i64, f64] {
+                ScalarType::[<$T:upper>] => {
+                    let lhs = TensorViewD::<$T>::try_from(self.view().into_dyn()).unwrap();
+                    let rhs = TensorViewD::<$T>::try_from(rhs.view().into_dyn()).unwrap();
+                    lhs.matmul(&rhs).map(Into::into)
+                }
+            })
+            _ => bail!("matmul unimplemented for {scalar_type:?}!"),
+        }})
+    }
+}
+
 /*
 #[cfg(feature = "device")]
 #[test]
@@ -1,22 +1,57 @@
 use super::*;
+#[cfg(feature = "device")]
+use crate::error::AutographError;
 use crate::ops::AddAssign;
 #[cfg(feature = "neural-network")]
 use crate::ops::{
-    Col2ImConv2, Col2ImConv2Options, Im2ColConv2, Im2ColConv2Options, MaxPool2, MaxPool2Backward,
-    MaxPool2Options,
+    conv_output_size, Col2ImConv2, Col2ImConv2Options, Conv2Direct, Conv2dOptions, Im2ColConv2,
+    Im2ColConv2Options, MaxPool2, MaxPool2Backward, MaxPool2Options, MaxPool2dOptions,
 };
-#[cfg(feature = "device")]
 use anyhow::format_err;
 #[cfg(feature = "neural-network")]
 use dry::{macro_for, macro_wrap};
 use half::{bf16, f16};
 #[cfg(feature = "device")]
 use krnl::macros::module;
+use ndarray::Zip;
 #[cfg(feature = "neural-network")]
-use ndarray::{Array2, Array4, Data as ArrayData, DataMut as ArrayDataMut};
+use ndarray::{linalg::Dot, Array2, Array4, Data as ArrayData, DataMut as ArrayDataMut};
 #[cfg(feature = "device")]
 use num_traits::ToPrimitive;
 use num_traits::Unsigned;
+use std::cmp::Ordering;
+
+/// The launch configuration of device elementwise kernels, ie relu.
+///
+/// By default, each thread computes one element, with the default number of threads per group
+/// of each kernel. For large tensors, fewer threads that each compute several elements may
+/// improve occupancy. The optimal configuration depends on the device, so it is passed with
+/// each call, ie to `Relu::forward_with_tuning()`. The results are the same for any
+/// configuration. Has no effect on the host.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ElementwiseTuning {
+    /// The number of threads per group. Defaults to None, the default of the kernel.
+    pub threads: Option<u32>,
+    /// The number of elements processed by each thread. Defaults to None, ie 1.
+    pub items_per_thread: Option<u32>,
+}
+
+impl ElementwiseTuning {
+    #[cfg(feature = "neural-network")]
+    pub(crate) fn check(&self) -> Result<()> {
+        if self.threads == Some(0) || self.items_per_thread == Some(0) {
+            bail!("elementwise {self:?} threads and items_per_thread must be at least 1!");
+        }
+        Ok(())
+    }
+    // The number of threads to dispatch for `items` elements.
+    #[cfg(all(feature = "device", feature = "neural-network"))]
+    pub(crate) fn global_threads(&self, items: usize) -> Option<u32> {
+        let items_per_thread = self.items_per_thread? as usize;
+        let global_threads = items / items_per_thread + (items % items_per_thread != 0) as usize;
+        Some(global_threads.max(1).to_u32().unwrap())
+    }
+}
 
 impl<S: ScalarData, D: Dimension> ScalarTensorBase<S, D> {
     /// Converts to standard layout.
@@ -64,6 +99,21 @@ impl<S: ScalarData, D: Dimension> ScalarTensorBase<S, D> {
             self.as_standard_layout()?.into_shared()
         }
     }
+    /// Returns a contiguous tensor in standard layout.
+    ///
+    /// If in standard layout, borrows the tensor, which is cheap. Otherwise, copies into a new
+    /// standard layout tensor. Unlike [`.is_contiguous()`](ScalarTensorBase::is_contiguous),
+    /// Fortran layout and broadcasted tensors are copied.
+    ///
+    /// Use this to ensure that [`.as_scalar_slice()`](ScalarTensorBase::as_scalar_slice) returns
+    /// `Some`.
+    ///
+    /// **Errors**
+    ///
+    /// See [`.into_standard_layout()`](TensorBase::into_standard_layout()).
+    pub fn contiguous(&self) -> Result<ScalarCowTensor<D>> {
+        self.as_standard_layout()
+    }
     /// Performs the operation `self += alpha * rhs`.
     ///
     /// Broadcasts `rhs` to the shape of `self`.
@@ -123,6 +173,227 @@ impl<S: ScalarData, D: Dimension> ScalarTensorBase<S, D> {
             self.view_mut().into_dyn(),
         )
     }
+    /// Elementwise multiplication.
+    ///
+    /// Broadcasts `rhs` to the shape of `self`.
+    ///
+    /// **Errors**
+    /// - The scalar types are not the same.
+    /// - Broadcasting is not possible.
+    /// - The operation could not be executed on the device.
+    pub fn mul<S2, D2>(&self, rhs: &ScalarTensorBase<S2, D2>) -> Result<ScalarTensor<D>>
+    where
+        S2: ScalarData,
+        D2: Dimension,
+    {
+        let scalar_type = self.scalar_type();
+        if scalar_type != rhs.scalar_type() {
+            bail!(
+                "mul scalar_type {:?} != {:?}",
+                scalar_type,
+                rhs.scalar_type()
+            );
+        }
+        let mut output =
+            unsafe { ScalarTensor::uninit(self.device(), self.raw_dim(), scalar_type)? };
+        output.assign(rhs)?;
+        scalar_assign(
+            BinaryOp::Mul,
+            ScalarElem::one(scalar_type),
+            self.view().into_dyn(),
+            output.view_mut().into_dyn(),
+        )?;
+        Ok(output)
+    }
+    /// Elementwise division.
+    ///
+    /// Broadcasts `rhs` to the shape of `self`.
+    ///
+    /// For floating point types, division by zero produces inf or nan on both the host and the
+    /// device, it is not an error.
+    ///
+    /// **Errors**
+    /// - The scalar types are not the same.
+    /// - Broadcasting is not possible.
+    /// - The operation could not be executed on the device.
+    ///
+    /// **Panics**
+    /// - Integer division by zero on the host.
+    pub fn div<S2, D2>(&self, rhs: &ScalarTensorBase<S2, D2>) -> Result<ScalarTensor<D>>
+    where
+        S2: ScalarData,
+        D2: Dimension,
+    {
+        let scalar_type = self.scalar_type();
+        if scalar_type != rhs.scalar_type() {
+            bail!(
+                "div scalar_type {:?} != {:?}",
+                scalar_type,
+                rhs.scalar_type()
+            );
+        }
+        let mut output =
+            unsafe { ScalarTensor::uninit(self.device(), self.raw_dim(), scalar_type)? };
+        output.assign(rhs)?;
+        scalar_assign(
+            BinaryOp::Div,
+            ScalarElem::one(scalar_type),
+            self.view().into_dyn(),
+            output.view_mut().into_dyn(),
+        )?;
+        Ok(output)
+    }
+}
+
+impl<S: ScalarData, D: Dimension> ScalarTensorBase<S, D> {
+    /// Elementwise power, `self^exponent`.
+    ///
+    /// Implemented for bf16 and f32. Negative bases with non-integer exponents produce nan.
+    ///
+    /// **Errors**
+    /// - The scalar types are not the same, or are not bf16 or f32.
+    /// - The shapes are not the same.
+    /// - The tensors are on different devices.
+    /// - The operation could not be executed on the device.
+    pub fn pow<S2: ScalarData>(
+        &self,
+        exponent: &ScalarTensorBase<S2, D>,
+    ) -> Result<ScalarTensor<D>> {
+        self.pow_check("pow", exponent)?;
+        let x = self.as_standard_layout()?;
+        let e = exponent.as_standard_layout()?;
+        let scalar_type = self.scalar_type();
+        let mut y = unsafe { ScalarTensor::uninit(self.device(), self.raw_dim(), scalar_type)? };
+        macro_for!($T in [bf16, f32] {
+            if scalar_type == $T::scalar_type() {
+                let x = TensorView::<$T, D>::try_from(x.view()).unwrap();
+                let e = TensorView::<$T, D>::try_from(e.view()).unwrap();
+                let mut y_typed = TensorViewMut::<$T, D>::try_from(y.view_mut()).unwrap();
+                if let Some(((x_array, e_array), y_array)) = x
+                    .as_array()
+                    .zip(e.as_array())
+                    .zip(y_typed.as_array_mut())
+                {
+                    Zip::from(y_array)
+                        .and(&x_array)
+                        .and(&e_array)
+                        .for_each(|y, x, e| *y = pow::pow_impl(*x, *e));
+                    return Ok(y);
+                }
+                #[cfg(not(feature = "device"))]
+                {
+                    unreachable!()
+                }
+                #[cfg(feature = "device")]
+                {
+                    paste! {
+                        pow::[<pow_ $T>]::builder()?
+                            .build(self.device())?
+                            .dispatch(
+                                x.as_slice().unwrap(),
+                                e.as_slice().unwrap(),
+                                y_typed.as_slice_mut().unwrap(),
+                            )?;
+                    }
+                    return Ok(y);
+                }
+            }
+        });
+        unreachable!()
+    }
+    /// The gradients of [`.pow()`](ScalarTensorBase::pow) with respect to `self` and `exponent`.
+    ///
+    /// Returns `(output_grad * exponent * self^(exponent - 1), output_grad * self^exponent * ln(self))`.
+    /// The gradient of the exponent is nan where `self <= 0`.
+    pub(crate) fn pow_backward<S2: ScalarData, S3: ScalarData>(
+        &self,
+        exponent: &ScalarTensorBase<S2, D>,
+        output_grad: &ScalarTensorBase<S3, D>,
+    ) -> Result<(ScalarTensor<D>, ScalarTensor<D>)> {
+        self.pow_check("pow_backward", exponent)?;
+        self.pow_check("pow_backward", output_grad)?;
+        let x = self.as_standard_layout()?;
+        let e = exponent.as_standard_layout()?;
+        let dy = output_grad.as_standard_layout()?;
+        let scalar_type = self.scalar_type();
+        let mut dx = unsafe { ScalarTensor::uninit(self.device(), self.raw_dim(), scalar_type)? };
+        let mut de = unsafe { ScalarTensor::uninit(self.device(), self.raw_dim(), scalar_type)? };
+        macro_for!($T in [bf16, f32] {
+            if scalar_type == $T::scalar_type() {
+                let x = TensorView::<$T, D>::try_from(x.view()).unwrap();
+                let e = TensorView::<$T, D>::try_from(e.view()).unwrap();
+                let dy = TensorView::<$T, D>::try_from(dy.view()).unwrap();
+                let mut dx_typed = TensorViewMut::<$T, D>::try_from(dx.view_mut()).unwrap();
+                let mut de_typed = TensorViewMut::<$T, D>::try_from(de.view_mut()).unwrap();
+                if let (Some(x_array), Some(e_array), Some(dy_array), Some(dx_array), Some(de_array)) = (
+                    x.as_array(),
+                    e.as_array(),
+                    dy.as_array(),
+                    dx_typed.as_array_mut(),
+                    de_typed.as_array_mut(),
+                ) {
+                    Zip::from(dx_array)
+                        .and(de_array)
+                        .and(&x_array)
+                        .and(&e_array)
+                        .and(&dy_array)
+                        .for_each(|dx, de, x, e, dy| {
+                            let (dx_elem, de_elem) = pow::pow_backward_impl(*x, *e, *dy);
+                            *dx = dx_elem;
+                            *de = de_elem;
+                        });
+                    return Ok((dx, de));
+                }
+                #[cfg(not(feature = "device"))]
+                {
+                    unreachable!()
+                }
+                #[cfg(feature = "device")]
+                {
+                    paste! {
+                        pow::[<pow_backward_ $T>]::builder()?
+                            .build(self.device())?
+                            .dispatch(
+                                x.as_slice().unwrap(),
+                                e.as_slice().unwrap(),
+                                dy.as_slice().unwrap(),
+                                dx_typed.as_slice_mut().unwrap(),
+                                de_typed.as_slice_mut().unwrap(),
+                            )?;
+                    }
+                    return Ok((dx, de));
+                }
+            }
+        });
+        unreachable!()
+    }
+    fn pow_check<S2: ScalarData>(&self, name: &str, rhs: &ScalarTensorBase<S2, D>) -> Result<()> {
+        let scalar_type = self.scalar_type();
+        if !matches!(scalar_type, ScalarType::BF16 | ScalarType::F32) {
+            bail!(AutographError::unsupported_scalar_type(name, scalar_type));
+        }
+        if scalar_type != rhs.scalar_type() {
+            bail!(
+                "{name} scalar_type {scalar_type:?} != {:?}",
+                rhs.scalar_type()
+            );
+        }
+        if self.shape() != rhs.shape() {
+            bail!(AutographError::shape_mismatch(
+                name,
+                self.shape(),
+                rhs.shape()
+            ));
+        }
+        if self.device() != rhs.device() {
+            bail!(AutographError::device_mismatch(
+                name,
+                self.device(),
+                rhs.device()
+            ));
+        }
+        Ok(())
+    }
 }
 
 impl<S: ScalarDataMut, D: Dimension, S2: ScalarData, D2: Dimension>
@@ -184,6 +455,21 @@ impl<T: Scalar, S: Data<Elem = T>, D: Dimension> TensorBase<S, D> {
             self.as_standard_layout()?.into_shared()
         }
     }
+    /// Returns a contiguous tensor in standard layout.
+    ///
+    /// If in standard layout, borrows the tensor, which is cheap. Otherwise, copies into a new
+    /// standard layout tensor. Unlike [`.is_contiguous()`](TensorBase::is_contiguous),
+    /// Fortran layout and broadcasted tensors are copied.
+    ///
+    /// Use this to ensure that [`.as_slice()`](TensorBase::as_slice) returns
+    /// `Some`.
+    ///
+    /// **Errors**
+    ///
+    /// See [`.into_standard_layout()`](TensorBase::into_standard_layout()).
+    pub fn contiguous(&self) -> Result<CowTensor<T, D>> {
+        self.as_standard_layout()
+    }
     /// Performs the operation `self += alpha * rhs`.
     ///
     /// Broadcasts `rhs` to the shape of `self`.
@@ -238,6 +524,56 @@ impl<T: Scalar, S: Data<Elem = T>, D: Dimension> TensorBase<S, D> {
             self.view_mut().into_dyn(),
         )
     }
+    /// Elementwise multiplication.
+    ///
+    /// Broadcasts `rhs` to the shape of `self`.
+    ///
+    /// **Errors**
+    /// - Broadcasting is not possible.
+    /// - The operation could not be executed on the device.
+    pub fn mul<S2, D2>(&self, rhs: &TensorBase<S2, D2>) -> Result<Tensor<T, D>>
+    where
+        S2: Data<Elem = T>,
+        D2: Dimension,
+    {
+        let mut output = unsafe { Tensor::uninit(self.device(), self.raw_dim())? };
+        output.assign(rhs)?;
+        assign(
+            BinaryOp::Mul,
+            T::one(),
+            self.view().into_dyn(),
+            output.view_mut().into_dyn(),
+        )?;
+        Ok(output)
+    }
+    /// Elementwise division.
+    ///
+    /// Broadcasts `rhs` to the shape of `self`.
+    ///
+    /// For floating point types, division by zero produces inf or nan on both the host and the
+    /// device, it is not an error.
+    ///
+    /// **Errors**
+    /// - Broadcasting is not possible.
+    /// - The operation could not be executed on the device.
+    ///
+    /// **Panics**
+    /// - Integer division by zero on the host.
+    pub fn div<S2, D2>(&self, rhs: &TensorBase<S2, D2>) -> Result<Tensor<T, D>>
+    where
+        S2: Data<Elem = T>,
+        D2: Dimension,
+    {
+        let mut output = unsafe { Tensor::uninit(self.device(), self.raw_dim())? };
+        output.assign(rhs)?;
+        assign(
+            BinaryOp::Div,
+            T::one(),
+            self.view().into_dyn(),
+            output.view_mut().into_dyn(),
+        )?;
+        Ok(output)
+    }
 }
 
 impl<T: Scalar, S: DataMut<Elem = T>, D: Dimension, S2: Data<Elem = T>, D2: Dimension>
@@ -684,85 +1020,1614 @@ impl<T: Scalar + Unsigned, S: Data<Elem = T>, D: Dimension> TensorBase<S, D> {
     }
 }
 
-#[cfg(feature = "neural-network")]
-impl<T: Scalar, S: ArrayData<Elem = T>> Im2ColConv2 for ArrayBase<S, Ix4> {
-    type Output = Array2<T>;
-    fn im2col_conv2(&self, options: &Im2ColConv2Options) -> Result<Self::Output> {
-        let input = self.as_standard_layout();
-        let (bs, c, ih, iw) = input.dim();
-        let [oh, ow] = options.output_shape([ih, iw]);
-        let Im2ColConv2Options {
-            filter: [fh, fw],
-            padding: [ph, pw],
-            stride: [sh, sw],
-            dilation: [dh, dw],
-        } = options.clone();
-        let mut output = Array::uninit([bs, oh, ow, c, fh * fw]);
-        for (input, mut output) in input.outer_iter().zip(output.outer_iter_mut()) {
-            for (input, mut output) in input.outer_iter().zip(output.axis_iter_mut(Axis(2))) {
-                for (hid, mut output) in output.outer_iter_mut().enumerate() {
-                    for (wid, mut output) in output.outer_iter_mut().enumerate() {
-                        for fi in 0..fh {
-                            for fj in 0..fw {
-                                let hidx = -(ph as isize) + (fi * dh + sh * hid) as isize;
-                                let widx = -(pw as isize) + (fj * dw + sw * wid) as isize;
-                                let fidx = fi * fw + fj;
-                                if hidx >= 0
-                                    && hidx < ih as isize
-                                    && widx >= 0
-                                    && widx < iw as isize
-                                {
-                                    unsafe {
-                                        output
-                                            .uget_mut(fidx)
-                                            .write(*input.uget((hidx as usize, widx as usize)));
-                                    }
-                                }
-                            }
-                        }
-                    }
-                }
+impl<S: ScalarData, D: Dimension> ScalarTensorBase<S, D> {
+    /// Fills elements where `mask` is not zero with `value`.
+    ///
+    /// See [`TensorBase::masked_fill`].
+    pub fn masked_fill<D2: Dimension>(
+        &self,
+        mask: TensorView<u8, D2>,
+        value: ScalarElem,
+    ) -> Result<ScalarTensor<D>> {
+        macro_for!($T in [u8, i8, u16, i16, f16, bf16, u32, i32, f32, u64, i64, f64] {
+            if self.scalar_type() == $T::scalar_type() {
+                let input = self.view().try_into_tensor_view::<$T>().unwrap();
+                let output = input.masked_fill(mask, value.cast::<$T>())?;
+                return Ok(output.into());
             }
+        });
+        bail!("masked_fill {:?} unimplemented!", self.scalar_type());
+    }
+}
+
+impl<T: Scalar, S: Data<Elem = T>, D: Dimension> TensorBase<S, D> {
+    /// Fills elements where `mask` is not zero with `value`.
+    ///
+    /// Broadcasts `mask` to the shape of `self`.
+    ///
+    /// **Errors**
+    /// - Broadcasting is not possible.
+    /// - The operation could not be executed on the device.
+    pub fn masked_fill<D2: Dimension>(
+        &self,
+        mask: TensorView<u8, D2>,
+        value: T,
+    ) -> Result<Tensor<T, D>> {
+        let mask = if let Some(mask) = mask.broadcast(self.raw_dim()) {
+            mask
+        } else {
+            bail!("Broadcast not possible! {mask:?} -> {:?}", self.shape());
+        };
+        if let Some((input, mask)) = self.as_array().zip(mask.as_array()) {
+            let mut output = input.to_owned();
+            output.zip_mut_with(&mask, |y, mask| {
+                if *mask != 0 {
+                    *y = value;
+                }
+            });
+            return Ok(output.into());
+        }
+        #[cfg(not(feature = "device"))]
+        {
+            unreachable!()
+        }
+        #[cfg(feature = "device")]
+        {
+            let input = self.as_standard_layout()?;
+            let mask = mask.as_standard_layout()?;
+            macro_for!($T in [u8, i8, u16, i16, f16, bf16, u32, i32, f32, u64, i64, f64] {
+                if T::scalar_type() == $T::scalar_type() {
+                    let input = ScalarTensorView::from(input.view()).try_into_tensor_view::<$T>().unwrap();
+                    let mut output = unsafe {
+                        Tensor::<$T, D>::uninit(self.device(), self.raw_dim())?
+                    };
+                    let kernel = paste! {
+                        kernels::[<masked_fill_ $T>]::builder()?.build(self.device())?
+                    };
+                    kernel.dispatch(
+                        input.as_slice().unwrap(),
+                        mask.as_slice().unwrap(),
+                        value.cast(),
+                        output.as_slice_mut().unwrap(),
+                    )?;
+                    return Ok(output.cast_into().unwrap());
+                }
+            });
+            unreachable!()
         }
-        let output = unsafe { output.assume_init() };
-        Ok(output.into_shape([bs * oh * ow, c * fh * fw]).unwrap())
     }
 }
 
-#[cfg(feature = "neural-network")]
-impl<T: Scalar, S: Data<Elem = T>> Im2ColConv2 for TensorBase<S, Ix4> {
-    type Output = Tensor2<T>;
-    fn im2col_conv2(&self, options: &Im2ColConv2Options) -> Result<Self::Output> {
+impl<S: ScalarData, D: Dimension> ScalarTensorBase<S, D> {
+    /// Returns a mask of finite elements, 1 if not NaN or infinite, 0 otherwise.
+    ///
+    /// See [`TensorBase::is_finite`].
+    pub fn is_finite(&self) -> Result<Tensor<u8, D>> {
+        macro_for!($T in [u8, i8, u16, i16, f16, bf16, u32, i32, f32, u64, i64, f64] {
+            if self.scalar_type() == $T::scalar_type() {
+                let input = self.view().try_into_tensor_view::<$T>().unwrap();
+                return input.is_finite();
+            }
+        });
+        bail!("is_finite {:?} unimplemented!", self.scalar_type());
+    }
+    /// Returns true if any element is NaN or infinite.
+    ///
+    /// See [`TensorBase::has_nan_or_inf`].
+    pub fn has_nan_or_inf(&self) -> Result<bool> {
+        Ok(!self.is_finite()?.all()?)
+    }
+}
+
+impl<T: Scalar, S: Data<Elem = T>, D: Dimension> TensorBase<S, D> {
+    /// Returns a mask of finite elements, 1 if not NaN or infinite, 0 otherwise.
+    ///
+    /// Integer elements are always finite.
+    ///
+    /// **Errors**
+    /// - f64 is not supported on device.
+    /// - The operation could not be executed on the device.
+    pub fn is_finite(&self) -> Result<Tensor<u8, D>> {
+        if !matches!(
+            T::scalar_type(),
+            ScalarType::F16 | ScalarType::BF16 | ScalarType::F32 | ScalarType::F64
+        ) {
+            return Tensor::ones(self.device(), self.raw_dim());
+        }
         if let Some(input) = self.as_array() {
-            input.im2col_conv2(options).map(Into::into)
-        } else {
-            Ok(ScalarTensorView::from(self.view())
-                .im2col_conv2(options)?
-                .try_into_tensor()
-                .unwrap())
+            return Ok(input.map(|x| x.cast::<f64>().is_finite() as u8).into());
+        }
+        #[cfg(not(feature = "device"))]
+        {
+            unreachable!()
+        }
+        #[cfg(feature = "device")]
+        {
+            let input = self.as_standard_layout()?;
+            let mut output = unsafe { Tensor::<u8, D>::uninit(self.device(), self.raw_dim())? };
+            macro_for!($T in [f16, bf16, f32] {
+                if T::scalar_type() == $T::scalar_type() {
+                    let input = ScalarTensorView::from(input.view()).try_into_tensor_view::<$T>().unwrap();
+                    let kernel = paste! {
+                        kernels::[<is_finite_ $T>]::builder()?.build(self.device())?
+                    };
+                    kernel.dispatch(
+                        input.as_slice().unwrap(),
+                        output.as_slice_mut().unwrap(),
+                    )?;
+                    return Ok(output);
+                }
+            });
+            bail!("is_finite {:?} unimplemented!", T::scalar_type());
         }
     }
+    /// Returns true if any element is NaN or infinite.
+    ///
+    /// Useful to detect diverging values, ie skipping a batch or aborting training.
+    ///
+    /// **Errors**
+    ///
+    /// See [`.is_finite()`](TensorBase::is_finite()).
+    pub fn has_nan_or_inf(&self) -> Result<bool> {
+        Ok(!self.is_finite()?.all()?)
+    }
 }
 
-#[cfg(feature = "neural-network")]
-impl<S: ScalarData> Im2ColConv2 for ScalarTensorBase<S, Ix4> {
-    type Output = ScalarTensor2;
-    fn im2col_conv2(&self, options: &Im2ColConv2Options) -> Result<Self::Output> {
-        macro_wrap!(
-            paste! { #[allow(clippy::single_match)] match self.scalar_type() {
-                macro_for!($T in [bf16, f32] {
-                   ScalarType::[<$T:upper>] => {
-                        let input = self.view().try_into_tensor_view::<$T>().unwrap();
-                        if let Some(input) = input.as_array() {
-                            return Ok(Tensor::from(input.im2col_conv2(options)?).into());
-                        }
+impl<S: ScalarData, D: Dimension> ScalarTensorBase<S, D> {
+    /// Elementwise absolute value.
+    ///
+    /// See [`TensorBase::abs`].
+    pub fn abs(&self) -> Result<ScalarTensor<D>> {
+        macro_for!($T in [i8, i16, f16, bf16, i32, f32, i64, f64] {
+            if self.scalar_type() == $T::scalar_type() {
+                let input = self.view().try_into_tensor_view::<$T>().unwrap();
+                return input.abs().map(Into::into);
+            }
+        });
+        bail!("abs {:?} unimplemented!", self.scalar_type());
+    }
+    /// Elementwise sign.
+    ///
+    /// See [`TensorBase::sign`].
+    pub fn sign(&self) -> Result<ScalarTensor<D>> {
+        macro_for!($T in [i8, i16, f16, bf16, i32, f32, i64, f64] {
+            if self.scalar_type() == $T::scalar_type() {
+                let input = self.view().try_into_tensor_view::<$T>().unwrap();
+                return input.sign().map(Into::into);
+            }
+        });
+        bail!("sign {:?} unimplemented!", self.scalar_type());
+    }
+    /// Elementwise negation.
+    ///
+    /// See [`TensorBase::neg`].
+    pub fn neg(&self) -> Result<ScalarTensor<D>> {
+        macro_for!($T in [i8, i16, f16, bf16, i32, f32, i64, f64] {
+            if self.scalar_type() == $T::scalar_type() {
+                let input = self.view().try_into_tensor_view::<$T>().unwrap();
+                return input.neg().map(Into::into);
+            }
+        });
+        bail!("neg {:?} unimplemented!", self.scalar_type());
+    }
+}
+
+impl<T: Scalar, S: Data<Elem = T>, D: Dimension> TensorBase<S, D> {
+    /// Elementwise absolute value.
+    ///
+    /// **Errors**
+    /// - `T` is unsigned.
+    /// - The operation could not be executed on the device.
+    pub fn abs(&self) -> Result<Tensor<T, D>> {
+        self.abs_or_sign("abs")
+    }
+    /// Elementwise sign, 1 if positive, -1 if negative, and 0 otherwise.
+    ///
+    /// NaN and zero are mapped to 0, so that the subgradient of [`.abs()`](TensorBase::abs) at
+    /// zero is 0.
+    ///
+    /// **Errors**
+    /// - `T` is unsigned.
+    /// - The operation could not be executed on the device.
+    pub fn sign(&self) -> Result<Tensor<T, D>> {
+        self.abs_or_sign("sign")
+    }
+    /// Elementwise negation.
+    ///
+    /// **Errors**
+    /// - `T` is unsigned.
+    /// - The operation could not be executed on the device.
+    pub fn neg(&self) -> Result<Tensor<T, D>> {
+        if !is_signed(T::scalar_type()) {
+            bail!("neg {:?} unimplemented!", T::scalar_type());
+        }
+        self.scaled_cast(T::zero() - T::one())
+    }
+    fn abs_or_sign(&self, name: &str) -> Result<Tensor<T, D>> {
+        if !is_signed(T::scalar_type()) {
+            bail!("{name} {:?} unimplemented!", T::scalar_type());
+        }
+        let sign = name == "sign";
+        if let Some(input) = self.as_array() {
+            let zero = T::zero();
+            let output = if sign {
+                input.map(|x| {
+                    if *x > zero {
+                        T::one()
+                    } else if *x < zero {
+                        zero - T::one()
+                    } else {
+                        zero
+                    }
+                })
+            } else {
+                input.map(|x| if *x < zero { zero - *x } else { *x })
+            };
+            return Ok(output.into());
+        }
+        #[cfg(not(feature = "device"))]
+        {
+            unreachable!()
+        }
+        #[cfg(feature = "device")]
+        {
+            let input = self.as_standard_layout()?;
+            macro_for!($T in [i8, i16, f16, bf16, i32, f32, i64, f64] {
+                if T::scalar_type() == $T::scalar_type() {
+                    let input = ScalarTensorView::from(input.view()).try_into_tensor_view::<$T>().unwrap();
+                    let mut output = unsafe {
+                        Tensor::<$T, D>::uninit(self.device(), self.raw_dim())?
+                    };
+                    let x = input.as_slice().unwrap();
+                    let y = output.as_slice_mut().unwrap();
+                    if sign {
+                        let kernel = paste! {
+                            kernels::[<sign_ $T>]::builder()?.build(self.device())?
+                        };
+                        kernel.dispatch(x, y)?;
+                    } else {
+                        let kernel = paste! {
+                            kernels::[<abs_ $T>]::builder()?.build(self.device())?
+                        };
+                        kernel.dispatch(x, y)?;
+                    }
+                    return Ok(output.cast_into().unwrap());
+                }
+            });
+            unreachable!()
+        }
+    }
+}
+
+fn is_signed(scalar_type: ScalarType) -> bool {
+    use ScalarType::*;
+    matches!(scalar_type, I8 | I16 | F16 | BF16 | I32 | F32 | I64 | F64)
+}
+
+impl<S: ScalarData, D: Dimension> ScalarTensorBase<S, D> {
+    /// Returns a mask of elements greater than `threshold`, 1 if true, 0 otherwise.
+    ///
+    /// See [`TensorBase::gt`].
+    pub fn gt(&self, threshold: ScalarElem) -> Result<Tensor<u8, D>> {
+        self.compare(threshold, CompareOp::Gt)
+    }
+    /// Returns a mask of elements greater than or equal to `threshold`, 1 if true, 0 otherwise.
+    ///
+    /// See [`TensorBase::ge`].
+    pub fn ge(&self, threshold: ScalarElem) -> Result<Tensor<u8, D>> {
+        self.compare(threshold, CompareOp::Ge)
+    }
+    /// Returns a mask of elements less than `threshold`, 1 if true, 0 otherwise.
+    ///
+    /// See [`TensorBase::lt`].
+    pub fn lt(&self, threshold: ScalarElem) -> Result<Tensor<u8, D>> {
+        self.compare(threshold, CompareOp::Lt)
+    }
+    /// Returns a mask of elements less than or equal to `threshold`, 1 if true, 0 otherwise.
+    ///
+    /// See [`TensorBase::le`].
+    pub fn le(&self, threshold: ScalarElem) -> Result<Tensor<u8, D>> {
+        self.compare(threshold, CompareOp::Le)
+    }
+    /// Returns a mask of elements equal to `threshold`, 1 if true, 0 otherwise.
+    ///
+    /// See [`TensorBase::eq`].
+    pub fn eq(&self, threshold: ScalarElem) -> Result<Tensor<u8, D>> {
+        self.compare(threshold, CompareOp::Eq)
+    }
+    fn compare(&self, threshold: ScalarElem, op: CompareOp) -> Result<Tensor<u8, D>> {
+        macro_for!($T in [u8, i8, u16, i16, f16, bf16, u32, i32, f32, u64, i64, f64] {
+            if self.scalar_type() == $T::scalar_type() {
+                let input = self.view().try_into_tensor_view::<$T>().unwrap();
+                return input.compare(threshold.cast::<$T>(), op);
+            }
+        });
+        bail!("{op:?} {:?} unimplemented!", self.scalar_type());
+    }
+}
+
+impl<T: Scalar, S: Data<Elem = T>, D: Dimension> TensorBase<S, D> {
+    /// Returns a mask of elements greater than `threshold`, 1 if true, 0 otherwise.
+    ///
+    /// **Errors**
+    /// - The operation could not be executed on the device.
+    pub fn gt(&self, threshold: T) -> Result<Tensor<u8, D>> {
+        self.compare(threshold, CompareOp::Gt)
+    }
+    /// Returns a mask of elements greater than or equal to `threshold`, 1 if true, 0 otherwise.
+    ///
+    /// **Errors**
+    /// - The operation could not be executed on the device.
+    pub fn ge(&self, threshold: T) -> Result<Tensor<u8, D>> {
+        self.compare(threshold, CompareOp::Ge)
+    }
+    /// Returns a mask of elements less than `threshold`, 1 if true, 0 otherwise.
+    ///
+    /// **Errors**
+    /// - The operation could not be executed on the device.
+    pub fn lt(&self, threshold: T) -> Result<Tensor<u8, D>> {
+        self.compare(threshold, CompareOp::Lt)
+    }
+    /// Returns a mask of elements less than or equal to `threshold`, 1 if true, 0 otherwise.
+    ///
+    /// **Errors**
+    /// - The operation could not be executed on the device.
+    pub fn le(&self, threshold: T) -> Result<Tensor<u8, D>> {
+        self.compare(threshold, CompareOp::Le)
+    }
+    /// Returns a mask of elements equal to `threshold`, 1 if true, 0 otherwise.
+    ///
+    /// **Errors**
+    /// - The operation could not be executed on the device.
+    pub fn eq(&self, threshold: T) -> Result<Tensor<u8, D>> {
+        self.compare(threshold, CompareOp::Eq)
+    }
+    fn compare(&self, threshold: T, op: CompareOp) -> Result<Tensor<u8, D>> {
+        if let Some(input) = self.as_array() {
+            return Ok(input.map(|x| op.eval(*x, threshold) as u8).into());
+        }
+        #[cfg(not(feature = "device"))]
+        {
+            unreachable!()
+        }
+        #[cfg(feature = "device")]
+        {
+            let input = self.as_standard_layout()?;
+            let mut output = unsafe { Tensor::<u8, D>::uninit(self.device(), self.raw_dim())? };
+            macro_for!($T in [u8, i8, u16, i16, f16, bf16, u32, i32, f32, u64, i64, f64] {
+                if T::scalar_type() == $T::scalar_type() {
+                    let input = ScalarTensorView::from(input.view()).try_into_tensor_view::<$T>().unwrap();
+                    let kernel = paste! {
+                        kernels::[<compare_ $T>]::builder()?.build(self.device())?
+                    };
+                    kernel.dispatch(
+                        op as u32,
+                        threshold.cast::<$T>(),
+                        input.as_slice().unwrap(),
+                        output.as_slice_mut().unwrap(),
+                    )?;
+                    return Ok(output);
+                }
+            });
+            unreachable!()
+        }
+    }
+}
+
+// Must match the compare kernels.
+#[derive(Clone, Copy, Debug)]
+enum CompareOp {
+    Gt = 0,
+    Ge = 1,
+    Lt = 2,
+    Le = 3,
+    Eq = 4,
+}
+
+impl CompareOp {
+    fn eval<T: PartialOrd>(self, x: T, threshold: T) -> bool {
+        match self {
+            Self::Gt => x > threshold,
+            Self::Ge => x >= threshold,
+            Self::Lt => x < threshold,
+            Self::Le => x <= threshold,
+            Self::Eq => x == threshold,
+        }
+    }
+}
+
+impl<S: ScalarData, D: Dimension> ScalarTensorBase<S, D> {
+    /// Elementwise maximum of `self` and `rhs`.
+    ///
+    /// See [`TensorBase::maximum`].
+    pub fn maximum<S2: ScalarData>(
+        &self,
+        rhs: &ScalarTensorBase<S2, D>,
+    ) -> Result<ScalarTensor<D>> {
+        self.select(rhs, SelectOp::Maximum)
+    }
+    /// Elementwise minimum of `self` and `rhs`.
+    ///
+    /// See [`TensorBase::minimum`].
+    pub fn minimum<S2: ScalarData>(
+        &self,
+        rhs: &ScalarTensorBase<S2, D>,
+    ) -> Result<ScalarTensor<D>> {
+        self.select(rhs, SelectOp::Minimum)
+    }
+    fn select<S2: ScalarData>(
+        &self,
+        rhs: &ScalarTensorBase<S2, D>,
+        op: SelectOp,
+    ) -> Result<ScalarTensor<D>> {
+        let scalar_type = self.scalar_type();
+        if rhs.scalar_type() != scalar_type {
+            bail!(
+                "{op:?} scalar_type {scalar_type:?} != {:?}!",
+                rhs.scalar_type()
+            );
+        }
+        macro_for!($T in [u8, i8, u16, i16, f16, bf16, u32, i32, f32, u64, i64, f64] {
+            if scalar_type == $T::scalar_type() {
+                let x = self.view().try_into_tensor_view::<$T>().unwrap();
+                let y = rhs.view().try_into_tensor_view::<$T>().unwrap();
+                return x.select(&y, op).map(Into::into);
+            }
+        });
+        unreachable!()
+    }
+    /// The gradients of [`.maximum()`](ScalarTensorBase::maximum) with respect to `self` and
+    /// `rhs`.
+    ///
+    /// Routes `output_grad` to the selected operand, `self` if equal.
+    pub(crate) fn maximum_backward<S2: ScalarData, S3: ScalarData>(
+        &self,
+        rhs: &ScalarTensorBase<S2, D>,
+        output_grad: &ScalarTensorBase<S3, D>,
+    ) -> Result<(ScalarTensor<D>, ScalarTensor<D>)> {
+        self.select_backward(rhs, output_grad, SelectOp::Maximum)
+    }
+    /// The gradients of [`.minimum()`](ScalarTensorBase::minimum) with respect to `self` and
+    /// `rhs`.
+    ///
+    /// Routes `output_grad` to the selected operand, `self` if equal.
+    pub(crate) fn minimum_backward<S2: ScalarData, S3: ScalarData>(
+        &self,
+        rhs: &ScalarTensorBase<S2, D>,
+        output_grad: &ScalarTensorBase<S3, D>,
+    ) -> Result<(ScalarTensor<D>, ScalarTensor<D>)> {
+        self.select_backward(rhs, output_grad, SelectOp::Minimum)
+    }
+    fn select_backward<S2: ScalarData, S3: ScalarData>(
+        &self,
+        rhs: &ScalarTensorBase<S2, D>,
+        output_grad: &ScalarTensorBase<S3, D>,
+        op: SelectOp,
+    ) -> Result<(ScalarTensor<D>, ScalarTensor<D>)> {
+        let name = format!("{op:?} backward");
+        self.pow_check(&name, rhs)?;
+        self.pow_check(&name, output_grad)?;
+        let x = self.as_standard_layout()?;
+        let y = rhs.as_standard_layout()?;
+        let dz = output_grad.as_standard_layout()?;
+        let scalar_type = self.scalar_type();
+        let mut dx = unsafe { ScalarTensor::uninit(self.device(), self.raw_dim(), scalar_type)? };
+        let mut dy = unsafe { ScalarTensor::uninit(self.device(), self.raw_dim(), scalar_type)? };
+        macro_for!($T in [bf16, f32] {
+            if scalar_type == $T::scalar_type() {
+                let x = TensorView::<$T, D>::try_from(x.view()).unwrap();
+                let y = TensorView::<$T, D>::try_from(y.view()).unwrap();
+                let dz = TensorView::<$T, D>::try_from(dz.view()).unwrap();
+                let mut dx_typed = TensorViewMut::<$T, D>::try_from(dx.view_mut()).unwrap();
+                let mut dy_typed = TensorViewMut::<$T, D>::try_from(dy.view_mut()).unwrap();
+                if let (Some(x_array), Some(y_array), Some(dz_array), Some(dx_array), Some(dy_array)) = (
+                    x.as_array(),
+                    y.as_array(),
+                    dz.as_array(),
+                    dx_typed.as_array_mut(),
+                    dy_typed.as_array_mut(),
+                ) {
+                    Zip::from(dx_array)
+                        .and(dy_array)
+                        .and(&x_array)
+                        .and(&y_array)
+                        .and(&dz_array)
+                        .for_each(|dx, dy, x, y, dz| {
+                            if op.first(*x, *y) {
+                                *dx = *dz;
+                                *dy = $T::default();
+                            } else {
+                                *dx = $T::default();
+                                *dy = *dz;
+                            }
+                        });
+                    return Ok((dx, dy));
+                }
+                #[cfg(not(feature = "device"))]
+                {
+                    unreachable!()
+                }
+                #[cfg(feature = "device")]
+                {
+                    paste! {
+                        kernels::[<select_backward_ $T>]::builder()?
+                            .build(self.device())?
+                            .dispatch(
+                                op as u32,
+                                x.as_slice().unwrap(),
+                                y.as_slice().unwrap(),
+                                dz.as_slice().unwrap(),
+                                dx_typed.as_slice_mut().unwrap(),
+                                dy_typed.as_slice_mut().unwrap(),
+                            )?;
+                    }
+                    return Ok((dx, dy));
+                }
+            }
+        });
+        unreachable!()
+    }
+}
+
+impl<T: Scalar, S: Data<Elem = T>, D: Dimension> TensorBase<S, D> {
+    /// Elementwise maximum of `self` and `rhs`.
+    ///
+    /// NaN is selected if either element is NaN.
+    ///
+    /// **Errors**
+    /// - The shapes are not the same.
+    /// - The tensors are on different devices.
+    /// - The operation could not be executed on the device.
+    pub fn maximum<S2: Data<Elem = T>>(&self, rhs: &TensorBase<S2, D>) -> Result<Tensor<T, D>> {
+        self.select(rhs, SelectOp::Maximum)
+    }
+    /// Elementwise minimum of `self` and `rhs`.
+    ///
+    /// NaN is selected if either element is NaN.
+    ///
+    /// **Errors**
+    /// - The shapes are not the same.
+    /// - The tensors are on different devices.
+    /// - The operation could not be executed on the device.
+    pub fn minimum<S2: Data<Elem = T>>(&self, rhs: &TensorBase<S2, D>) -> Result<Tensor<T, D>> {
+        self.select(rhs, SelectOp::Minimum)
+    }
+    fn select<S2: Data<Elem = T>>(
+        &self,
+        rhs: &TensorBase<S2, D>,
+        op: SelectOp,
+    ) -> Result<Tensor<T, D>> {
+        let name = format!("{op:?}");
+        if self.shape() != rhs.shape() {
+            bail!(AutographError::shape_mismatch(
+                &name,
+                self.shape(),
+                rhs.shape()
+            ));
+        }
+        if self.device() != rhs.device() {
+            bail!(AutographError::device_mismatch(
+                &name,
+                self.device(),
+                rhs.device()
+            ));
+        }
+        if let Some((x, y)) = self.as_array().zip(rhs.as_array()) {
+            let z = Zip::from(&x)
+                .and(&y)
+                .map_collect(|x, y| if op.first(*x, *y) { *x } else { *y });
+            return Ok(z.into());
+        }
+        #[cfg(not(feature = "device"))]
+        {
+            unreachable!()
+        }
+        #[cfg(feature = "device")]
+        {
+            let x = self.as_standard_layout()?;
+            let y = rhs.as_standard_layout()?;
+            macro_for!($T in [u8, i8, u16, i16, f16, bf16, u32, i32, f32, u64, i64, f64] {
+                if T::scalar_type() == $T::scalar_type() {
+                    let x = ScalarTensorView::from(x.view()).try_into_tensor_view::<$T>().unwrap();
+                    let y = ScalarTensorView::from(y.view()).try_into_tensor_view::<$T>().unwrap();
+                    let mut z = unsafe {
+                        Tensor::<$T, D>::uninit(self.device(), self.raw_dim())?
+                    };
+                    let kernel = paste! {
+                        kernels::[<select_ $T>]::builder()?.build(self.device())?
+                    };
+                    kernel.dispatch(
+                        op as u32,
+                        x.as_slice().unwrap(),
+                        y.as_slice().unwrap(),
+                        z.as_slice_mut().unwrap(),
+                    )?;
+                    return Ok(z.cast_into().unwrap());
+                }
+            });
+            unreachable!()
+        }
+    }
+}
+
+// Must match the select kernels.
+#[derive(Clone, Copy, Debug)]
+enum SelectOp {
+    Maximum = 0,
+    Minimum = 1,
+}
+
+impl SelectOp {
+    // Whether the first operand is selected, including ties.
+    fn first<T: PartialOrd>(self, x: T, y: T) -> bool {
+        match self {
+            Self::Maximum => x >= y,
+            Self::Minimum => x <= y,
+        }
+    }
+}
+
+impl<S: ScalarData, D: Dimension> ScalarTensorBase<S, D> {
+    /// Shifts elements by `shift` along `axis`, wrapping around.
+    ///
+    /// See [`TensorBase::roll`].
+    pub fn roll(&self, shift: isize, axis: Axis) -> Result<ScalarTensor<D>> {
+        macro_for!($T in [u8, i8, u16, i16, f16, bf16, u32, i32, f32, u64, i64, f64] {
+            if self.scalar_type() == $T::scalar_type() {
+                let input = self.view().try_into_tensor_view::<$T>().unwrap();
+                let output = input.roll(shift, axis)?;
+                return Ok(output.into());
+            }
+        });
+        bail!("roll {:?} unimplemented!", self.scalar_type());
+    }
+}
+
+impl<T: Scalar, S: Data<Elem = T>, D: Dimension> TensorBase<S, D> {
+    /// Shifts elements by `shift` along `axis`, wrapping around.
+    ///
+    /// Elements shifted beyond the last position are reintroduced at the first. Negative
+    /// `shift` shifts toward the first position.
+    ///
+    /// **Errors**
+    /// - The operation could not be executed on the device.
+    ///
+    /// **Panics**
+    /// - `axis` is out of bounds.
+    pub fn roll(&self, shift: isize, axis: Axis) -> Result<Tensor<T, D>> {
+        let len = self.shape()[axis.0];
+        let shift = if len > 0 {
+            shift.rem_euclid(len as isize) as usize
+        } else {
+            0
+        };
+        if let Some(input) = self.as_array() {
+            let mut output = Array::uninit(input.raw_dim());
+            input
+                .slice_axis(axis, (..len - shift).into())
+                .assign_to(output.slice_axis_mut(axis, (shift..).into()));
+            input
+                .slice_axis(axis, (len - shift..).into())
+                .assign_to(output.slice_axis_mut(axis, (..shift).into()));
+            let output = unsafe { output.assume_init() };
+            return Ok(output.into());
+        }
+        #[cfg(not(feature = "device"))]
+        {
+            unreachable!()
+        }
+        #[cfg(feature = "device")]
+        {
+            let input = self.as_standard_layout()?;
+            let inner: usize = self.shape()[axis.0 + 1..].iter().product();
+            macro_for!($T in [u8, i8, u16, i16, f16, bf16, u32, i32, f32, u64, i64, f64] {
+                if T::scalar_type() == $T::scalar_type() {
+                    let input = ScalarTensorView::from(input.view()).try_into_tensor_view::<$T>().unwrap();
+                    let mut output = unsafe {
+                        Tensor::<$T, D>::uninit(self.device(), self.raw_dim())?
+                    };
+                    let kernel = paste! {
+                        kernels::[<roll_ $T>]::builder()?.build(self.device())?
+                    };
+                    kernel.dispatch(
+                        input.as_slice().unwrap(),
+                        len.to_u32().unwrap(),
+                        inner.to_u32().unwrap(),
+                        shift.to_u32().unwrap(),
+                        output.as_slice_mut().unwrap(),
+                    )?;
+                    return Ok(output.cast_into().unwrap());
+                }
+            });
+            unreachable!()
+        }
+    }
+}
+
+impl<S: ScalarData, D: Dimension> ScalarTensorBase<S, D> {
+    /// Reverses the order of elements along `axes`.
+    ///
+    /// See [`TensorBase::flip`].
+    pub fn flip(&self, axes: &[Axis]) -> Result<ScalarTensor<D>> {
+        macro_for!($T in [u8, i8, u16, i16, f16, bf16, u32, i32, f32, u64, i64, f64] {
+            if self.scalar_type() == $T::scalar_type() {
+                let input = self.view().try_into_tensor_view::<$T>().unwrap();
+                let output = input.flip(axes)?;
+                return Ok(output.into());
+            }
+        });
+        bail!("flip {:?} unimplemented!", self.scalar_type());
+    }
+}
+
+impl<T: Scalar, S: Data<Elem = T>, D: Dimension> TensorBase<S, D> {
+    /// Reverses the order of elements along `axes`.
+    ///
+    /// Each axis is flipped in turn, so an axis repeated twice is restored.
+    ///
+    /// **Errors**
+    /// - The operation could not be executed on the device.
+    ///
+    /// **Panics**
+    /// - An axis is out of bounds.
+    pub fn flip(&self, axes: &[Axis]) -> Result<Tensor<T, D>> {
+        if let Some(input) = self.as_array() {
+            let mut input = input;
+            for axis in axes.iter().copied() {
+                input.invert_axis(axis);
+            }
+            return Ok(input.as_standard_layout().into_owned().into());
+        }
+        #[cfg(not(feature = "device"))]
+        {
+            unreachable!()
+        }
+        #[cfg(feature = "device")]
+        {
+            let mut output = self.as_standard_layout()?.into_owned()?;
+            for axis in axes.iter().copied() {
+                let len = self.shape()[axis.0];
+                if len <= 1 {
+                    continue;
+                }
+                let inner: usize = self.shape()[axis.0 + 1..].iter().product();
+                macro_for!($T in [u8, i8, u16, i16, f16, bf16, u32, i32, f32, u64, i64, f64] {
+                    if T::scalar_type() == $T::scalar_type() {
+                        let x = ScalarTensorView::from(output.view()).try_into_tensor_view::<$T>().unwrap();
+                        let mut y = unsafe {
+                            Tensor::<$T, D>::uninit(self.device(), self.raw_dim())?
+                        };
+                        let kernel = paste! {
+                            kernels::[<flip_ $T>]::builder()?.build(self.device())?
+                        };
+                        kernel.dispatch(
+                            x.as_slice().unwrap(),
+                            len.to_u32().unwrap(),
+                            inner.to_u32().unwrap(),
+                            y.as_slice_mut().unwrap(),
+                        )?;
+                        output = y.cast_into().unwrap();
+                    }
+                });
+            }
+            Ok(output)
+        }
+    }
+}
+
+impl<S: ScalarData, D: RemoveAxis> ScalarTensorBase<S, D> {
+    /// Selects `indices` along `axis`.
+    ///
+    /// See [`TensorBase::index_select`].
+    pub fn index_select(&self, axis: Axis, indices: TensorView1<u32>) -> Result<ScalarTensor<D>> {
+        macro_for!($T in [u8, i8, u16, i16, f16, bf16, u32, i32, f32, u64, i64, f64] {
+            if self.scalar_type() == $T::scalar_type() {
+                let input = self.view().try_into_tensor_view::<$T>().unwrap();
+                let output = input.index_select(axis, indices)?;
+                return Ok(output.into());
+            }
+        });
+        bail!("index_select {:?} unimplemented!", self.scalar_type());
+    }
+}
+
+impl<T: Scalar, S: Data<Elem = T>, D: RemoveAxis> TensorBase<S, D> {
+    /// Selects `indices` along `axis`.
+    ///
+    /// The output has the shape of `self` with `indices.len()` along `axis`. Indices may be
+    /// repeated.
+    ///
+    ///```no_run
+    /// # use autograph::{anyhow::Result, krnl::device::Device, tensor::Tensor};
+    /// # use ndarray::{arr1, arr2, Axis};
+    /// # fn main() -> Result<()> {
+    /// let x = Tensor::from(arr2(&[[1f32, 2.], [3., 4.], [5., 6.]]));
+    /// let indices = Tensor::from(arr1(&[2u32, 0, 2]));
+    /// let y = x.index_select(Axis(0), indices.view())?;
+    /// assert_eq!(y.into_array()?, arr2(&[[5f32, 6.], [1., 2.], [5., 6.]]));
+    /// # Ok(())
+    /// # }
+    ///```
+    ///
+    /// **Errors**
+    /// - `indices` is not on the same device as `self`.
+    /// - On the host, an index is out of bounds. On device, out of bounds indices select zeros.
+    /// - The operation could not be executed on the device.
+    ///
+    /// **Panics**
+    /// - `axis` is out of bounds.
+    pub fn index_select(&self, axis: Axis, indices: TensorView1<u32>) -> Result<Tensor<T, D>> {
+        let len = self.shape()[axis.0];
+        if self.device() != indices.device() {
+            bail!(
+                "index_select indices device {:?} != {:?}!",
+                indices.device(),
+                self.device()
+            );
+        }
+        if let Some((input, indices)) = self.as_array().zip(indices.as_array()) {
+            let indices: Vec<usize> = indices.iter().map(|i| *i as usize).collect();
+            if let Some(index) = indices.iter().find(|i| **i >= len) {
+                bail!(
+                    "index_select index {index} out of bounds for axis {axis:?} with length {len}!"
+                );
+            }
+            return Ok(input.select(axis, &indices).into());
+        }
+        #[cfg(not(feature = "device"))]
+        {
+            unreachable!()
+        }
+        #[cfg(feature = "device")]
+        {
+            let input = self.as_standard_layout()?;
+            let indices = indices.as_standard_layout()?;
+            let inner: usize = self.shape()[axis.0 + 1..].iter().product();
+            let mut dim = self.raw_dim();
+            dim[axis.0] = indices.len();
+            macro_for!($T in [u8, i8, u16, i16, f16, bf16, u32, i32, f32, u64, i64, f64] {
+                if T::scalar_type() == $T::scalar_type() {
+                    let input = ScalarTensorView::from(input.view()).try_into_tensor_view::<$T>().unwrap();
+                    let mut output = unsafe { Tensor::<$T, D>::uninit(self.device(), dim)? };
+                    let kernel = paste! {
+                        kernels::[<index_select_ $T>]::builder()?.build(self.device())?
+                    };
+                    kernel.dispatch(
+                        input.as_slice().unwrap(),
+                        indices.as_slice().unwrap(),
+                        len.to_u32().unwrap(),
+                        indices.len().to_u32().unwrap(),
+                        inner.to_u32().unwrap(),
+                        output.as_slice_mut().unwrap(),
+                    )?;
+                    return Ok(output.cast_into().unwrap());
+                }
+            });
+            unreachable!()
+        }
+    }
+}
+
+impl<S: ScalarData, D: Dimension> ScalarTensorBase<S, D> {
+    /// Selects the elements where `mask` is non-zero.
+    ///
+    /// See [`TensorBase::masked_select`].
+    pub fn masked_select(&self, mask: TensorView<u8, D>) -> Result<ScalarTensor1> {
+        macro_for!($T in [u8, i8, u16, i16, f16, bf16, u32, i32, f32, u64, i64, f64] {
+            if self.scalar_type() == $T::scalar_type() {
+                let input = self.view().try_into_tensor_view::<$T>().unwrap();
+                let output = input.masked_select(mask)?;
+                return Ok(output.into());
+            }
+        });
+        bail!("masked_select {:?} unimplemented!", self.scalar_type());
+    }
+}
+
+impl<T: Scalar, S: Data<Elem = T>, D: Dimension> TensorBase<S, D> {
+    /// Selects the elements where `mask` is non-zero.
+    ///
+    /// Returns a 1 dimensional tensor of the selected elements, in logical order.
+    ///
+    /// The length of the output depends on the mask. On device, the non-zero elements are
+    /// counted per block and the counts are read back to the host to compute the offset of each
+    /// block, so this synchronizes with the device.
+    ///
+    ///```no_run
+    /// # use autograph::{anyhow::Result, tensor::Tensor};
+    /// # use ndarray::{arr1, arr2};
+    /// # fn main() -> Result<()> {
+    /// let x = Tensor::from(arr2(&[[1f32, 2.], [3., 4.]]));
+    /// let mask = Tensor::from(arr2(&[[1u8, 0], [0, 1]]));
+    /// let y = x.masked_select(mask.view())?;
+    /// assert_eq!(y.into_array()?, arr1(&[1f32, 4.]));
+    /// # Ok(())
+    /// # }
+    ///```
+    ///
+    /// **Errors**
+    /// - The shape of `mask` is not equal to the shape of `self`.
+    /// - `mask` is not on the same device as `self`.
+    /// - The operation could not be executed on the device.
+    pub fn masked_select(&self, mask: TensorView<u8, D>) -> Result<Tensor1<T>> {
+        if self.shape() != mask.shape() {
+            bail!(
+                "masked_select mask shape {:?} != {:?}!",
+                mask.shape(),
+                self.shape()
+            );
+        }
+        if self.device() != mask.device() {
+            bail!(
+                "masked_select mask device {:?} != {:?}!",
+                mask.device(),
+                self.device()
+            );
+        }
+        if let Some((input, mask)) = self.as_array().zip(mask.as_array()) {
+            let output: Vec<T> = input
+                .iter()
+                .zip(mask.iter())
+                .filter(|(_, mask)| **mask != 0)
+                .map(|(x, _)| *x)
+                .collect();
+            return Ok(output.into());
+        }
+        #[cfg(not(feature = "device"))]
+        {
+            unreachable!()
+        }
+        #[cfg(feature = "device")]
+        {
+            let device = self.device();
+            if self.is_empty() {
+                return Tensor::zeros(device, 0);
+            }
+            let input = self.as_standard_layout()?;
+            let mask = mask.as_standard_layout()?;
+            let block = 256;
+            let blocks = (input.len() + block - 1) / block;
+            let mut counts = unsafe { Tensor::<u32, _>::uninit(device.clone(), blocks)? };
+            kernels::masked_count::builder()?
+                .build(device.clone())?
+                .dispatch(
+                    mask.as_slice().unwrap(),
+                    block.to_u32().unwrap(),
+                    counts.as_slice_mut().unwrap(),
+                )?;
+            let mut len = 0;
+            let offsets: Vec<u32> = counts
+                .into_vec()?
+                .into_iter()
+                .map(|count| {
+                    let offset = len;
+                    len += count;
+                    offset
+                })
+                .collect();
+            if len == 0 {
+                return Tensor::zeros(device, 0);
+            }
+            let offsets = Tensor::from(offsets).into_device(device.clone())?;
+            macro_for!($T in [u8, i8, u16, i16, f16, bf16, u32, i32, f32, u64, i64, f64] {
+                if T::scalar_type() == $T::scalar_type() {
+                    let input = ScalarTensorView::from(input.view()).try_into_tensor_view::<$T>().unwrap();
+                    let mut output = unsafe { Tensor::<$T, _>::uninit(device.clone(), len as usize)? };
+                    let kernel = paste! {
+                        kernels::[<masked_select_ $T>]::builder()?
+                            .build(device)?
+                            .with_global_threads(blocks.to_u32().unwrap())
+                    };
+                    unsafe {
+                        kernel.dispatch(
+                            input.as_slice().unwrap(),
+                            mask.as_slice().unwrap(),
+                            block.to_u32().unwrap(),
+                            offsets.as_slice().unwrap(),
+                            output.as_slice_mut().unwrap(),
+                        )?;
+                    }
+                    return Ok(output.cast_into().unwrap());
+                }
+            });
+            unreachable!()
+        }
+    }
+}
+
+impl ScalarTensor<Ix2> {
+    /// The identity matrix with shape [`n`, `n`].
+    ///
+    /// See [`TensorBase::eye`].
+    pub fn eye(device: Device, n: usize, scalar_type: ScalarType) -> Result<Self> {
+        Self::eye_rect(device, n, n, scalar_type)
+    }
+    /// A matrix with shape [`rows`, `cols`] with 1's on the diagonal.
+    ///
+    /// See [`TensorBase::eye_rect`].
+    pub fn eye_rect(
+        device: Device,
+        rows: usize,
+        cols: usize,
+        scalar_type: ScalarType,
+    ) -> Result<Self> {
+        macro_for!($T in [u8, i8, u16, i16, f16, bf16, u32, i32, f32, u64, i64, f64] {
+            if scalar_type == $T::scalar_type() {
+                return Tensor::<$T, Ix2>::eye_rect(device, rows, cols).map(Into::into);
+            }
+        });
+        bail!("eye {scalar_type:?} unimplemented!");
+    }
+}
+
+impl<T: Scalar> Tensor<T, Ix2> {
+    /// The identity matrix with shape [`n`, `n`].
+    ///
+    /// See [`.eye_rect()`](TensorBase::eye_rect).
+    pub fn eye(device: Device, n: usize) -> Result<Self> {
+        Self::eye_rect(device, n, n)
+    }
+    /// A matrix with shape [`rows`, `cols`] with 1's on the diagonal and 0's elsewhere.
+    ///
+    ///```no_run
+    /// # use autograph::{anyhow::Result, krnl::device::Device, tensor::Tensor2};
+    /// # use ndarray::arr2;
+    /// # fn main() -> Result<()> {
+    /// let x = Tensor2::<f32>::eye_rect(Device::host(), 2, 3)?;
+    /// assert_eq!(x.into_array()?, arr2(&[[1f32, 0., 0.], [0., 1., 0.]]));
+    /// # Ok(())
+    /// # }
+    ///```
+    ///
+    /// **Errors**
+    /// - The operation could not be executed on the device.
+    pub fn eye_rect(device: Device, rows: usize, cols: usize) -> Result<Self> {
+        if device.is_host() {
+            let output =
+                Array::from_shape_fn(
+                    [rows, cols],
+                    |(i, j)| {
+                        if i == j {
+                            T::one()
+                        } else {
+                            T::default()
+                        }
+                    },
+                );
+            return Ok(output.into());
+        }
+        #[cfg(not(feature = "device"))]
+        {
+            unreachable!()
+        }
+        #[cfg(feature = "device")]
+        {
+            let mut output = unsafe { Tensor::<T, Ix2>::uninit(device.clone(), [rows, cols])? };
+            if output.is_empty() {
+                return Ok(output);
+            }
+            macro_for!($T in [u8, i8, u16, i16, f16, bf16, u32, i32, f32, u64, i64, f64] {
+                if T::scalar_type() == $T::scalar_type() {
+                    let kernel = paste! {
+                        kernels::[<eye_ $T>]::builder()?.build(device)?
+                    };
+                    kernel.dispatch(
+                        cols.to_u32().unwrap(),
+                        ScalarSliceMut::from(output.as_slice_mut().unwrap()).try_into().unwrap(),
+                    )?;
+                    return Ok(output);
+                }
+            });
+            unreachable!()
+        }
+    }
+    /// A square matrix with `diag` on the diagonal and 0's elsewhere.
+    ///
+    /// The inverse of [`.diagonal()`](TensorBase::diagonal).
+    ///
+    ///```no_run
+    /// # use autograph::{anyhow::Result, tensor::{Tensor, Tensor2}};
+    /// # use ndarray::{arr1, arr2};
+    /// # fn main() -> Result<()> {
+    /// let x = Tensor::from(arr1(&[1f32, 2.]));
+    /// let y = Tensor2::diag(&x)?;
+    /// assert_eq!(y.into_array()?, arr2(&[[1f32, 0.], [0., 2.]]));
+    /// # Ok(())
+    /// # }
+    ///```
+    ///
+    /// **Errors**
+    /// - The operation could not be executed on the device.
+    pub fn diag<S2: Data<Elem = T>>(diag: &TensorBase<S2, Ix1>) -> Result<Self> {
+        if let Some(x) = diag.as_array() {
+            return Ok(Array::from_diag(&x).into());
+        }
+        #[cfg(not(feature = "device"))]
+        {
+            unreachable!()
+        }
+        #[cfg(feature = "device")]
+        {
+            let device = diag.device();
+            let n = diag.len();
+            let mut output = unsafe { Tensor::<T, Ix2>::uninit(device.clone(), [n, n])? };
+            if output.is_empty() {
+                return Ok(output);
+            }
+            let x = diag.as_standard_layout()?;
+            macro_for!($T in [u8, i8, u16, i16, f16, bf16, u32, i32, f32, u64, i64, f64] {
+                if T::scalar_type() == $T::scalar_type() {
+                    let kernel = paste! {
+                        kernels::[<diag_ $T>]::builder()?.build(device)?
+                    };
+                    kernel.dispatch(
+                        ScalarSlice::from(x.as_slice().unwrap()).try_into().unwrap(),
+                        ScalarSliceMut::from(output.as_slice_mut().unwrap()).try_into().unwrap(),
+                    )?;
+                    return Ok(output);
+                }
+            });
+            unreachable!()
+        }
+    }
+}
+
+impl<T: Scalar, S: Data<Elem = T>> TensorBase<S, Ix2> {
+    /// The diagonal of the matrix.
+    ///
+    /// Returns the elements at [`i`, `i`] for `i` less than the smaller of the number of rows
+    /// and columns.
+    ///
+    ///```no_run
+    /// # use autograph::{anyhow::Result, tensor::Tensor};
+    /// # use ndarray::{arr1, arr2};
+    /// # fn main() -> Result<()> {
+    /// let x = Tensor::from(arr2(&[[1f32, 2., 3.], [4., 5., 6.]]));
+    /// assert_eq!(x.diagonal()?.into_array()?, arr1(&[1f32, 5.]));
+    /// # Ok(())
+    /// # }
+    ///```
+    ///
+    /// **Errors**
+    /// - The operation could not be executed on the device.
+    pub fn diagonal(&self) -> Result<Tensor1<T>> {
+        if let Some(x) = self.as_array() {
+            return Ok(x.diag().to_owned().into());
+        }
+        #[cfg(not(feature = "device"))]
+        {
+            unreachable!()
+        }
+        #[cfg(feature = "device")]
+        {
+            let device = self.device();
+            let (rows, cols) = self.dim();
+            let mut output = unsafe { Tensor::<T, Ix1>::uninit(device.clone(), rows.min(cols))? };
+            if output.is_empty() {
+                return Ok(output);
+            }
+            let x = self.as_standard_layout()?;
+            macro_for!($T in [u8, i8, u16, i16, f16, bf16, u32, i32, f32, u64, i64, f64] {
+                if T::scalar_type() == $T::scalar_type() {
+                    let kernel = paste! {
+                        kernels::[<diagonal_ $T>]::builder()?.build(device)?
+                    };
+                    kernel.dispatch(
+                        ScalarSlice::from(x.as_slice().unwrap()).try_into().unwrap(),
+                        cols.to_u32().unwrap(),
+                        ScalarSliceMut::from(output.as_slice_mut().unwrap()).try_into().unwrap(),
+                    )?;
+                    return Ok(output);
+                }
+            });
+            unreachable!()
+        }
+    }
+}
+
+impl<S: ScalarData, D: Dimension> ScalarTensorBase<S, D> {
+    /// Sorts along `axis` in ascending order.
+    ///
+    /// See [`TensorBase::sort`].
+    pub fn sort(&self, axis: Axis) -> Result<(ScalarTensor<D>, Tensor<u32, D>)> {
+        macro_for!($T in [u8, i8, u16, i16, f16, bf16, u32, i32, f32, u64, i64, f64] {
+            if self.scalar_type() == $T::scalar_type() {
+                let input = self.view().try_into_tensor_view::<$T>().unwrap();
+                let (values, indices) = input.sort(axis)?;
+                return Ok((values.into(), indices));
+            }
+        });
+        bail!("sort {:?} unimplemented!", self.scalar_type());
+    }
+    /// The `k` largest elements along `axis`.
+    ///
+    /// See [`TensorBase::topk`].
+    pub fn topk(&self, k: usize, axis: Axis) -> Result<(ScalarTensor<D>, Tensor<u32, D>)> {
+        macro_for!($T in [u8, i8, u16, i16, f16, bf16, u32, i32, f32, u64, i64, f64] {
+            if self.scalar_type() == $T::scalar_type() {
+                let input = self.view().try_into_tensor_view::<$T>().unwrap();
+                let (values, indices) = input.topk(k, axis)?;
+                return Ok((values.into(), indices));
+            }
+        });
+        bail!("topk {:?} unimplemented!", self.scalar_type());
+    }
+}
+
+impl<T: Scalar, S: Data<Elem = T>, D: Dimension> TensorBase<S, D> {
+    /// Sorts along `axis` in ascending order.
+    ///
+    /// Returns the sorted values and their indices along `axis`. The sort is stable, equal
+    /// elements keep their order. NaN is greater than all other values.
+    ///
+    ///```no_run
+    /// # use autograph::{anyhow::Result, tensor::Tensor};
+    /// # use ndarray::{arr1, Axis};
+    /// # fn main() -> Result<()> {
+    /// let x = Tensor::from(arr1(&[3f32, 1., 2., 1.]));
+    /// let (values, indices) = x.sort(Axis(0))?;
+    /// assert_eq!(values.into_array()?, arr1(&[1f32, 1., 2., 3.]));
+    /// assert_eq!(indices.into_array()?, arr1(&[1u32, 3, 2, 0]));
+    /// # Ok(())
+    /// # }
+    ///```
+    ///
+    /// **Errors**
+    /// - The operation could not be executed on the device.
+    ///
+    /// **Panics**
+    /// - `axis` is out of bounds.
+    pub fn sort(&self, axis: Axis) -> Result<(Tensor<T, D>, Tensor<u32, D>)> {
+        let len = self.shape()[axis.0];
+        self.sort_impl(axis, len, false)
+    }
+    /// The `k` largest elements along `axis`.
+    ///
+    /// Returns the values in descending order and their indices along `axis`. Of equal elements,
+    /// those with lower indices come first. NaN is greater than all other values.
+    ///
+    ///```no_run
+    /// # use autograph::{anyhow::Result, tensor::Tensor};
+    /// # use ndarray::{arr2, Axis};
+    /// # fn main() -> Result<()> {
+    /// let x = Tensor::from(arr2(&[[1f32, 4., 2.], [3., 3., 0.]]));
+    /// let (values, indices) = x.topk(2, Axis(1))?;
+    /// assert_eq!(values.into_array()?, arr2(&[[4f32, 2.], [3., 3.]]));
+    /// assert_eq!(indices.into_array()?, arr2(&[[1u32, 2], [0, 1]]));
+    /// # Ok(())
+    /// # }
+    ///```
+    ///
+    /// **Errors**
+    /// - `k` is greater than the length of `axis`.
+    /// - The operation could not be executed on the device.
+    ///
+    /// **Panics**
+    /// - `axis` is out of bounds.
+    pub fn topk(&self, k: usize, axis: Axis) -> Result<(Tensor<T, D>, Tensor<u32, D>)> {
+        let len = self.shape()[axis.0];
+        if k > len {
+            bail!(
+                "topk k {k} > {len} for axis {axis:?} with shape {:?}!",
+                self.shape()
+            );
+        }
+        self.sort_impl(axis, k, true)
+    }
+    fn sort_impl(
+        &self,
+        axis: Axis,
+        k: usize,
+        descending: bool,
+    ) -> Result<(Tensor<T, D>, Tensor<u32, D>)> {
+        let len = self.shape()[axis.0];
+        let mut dim = self.raw_dim();
+        dim[axis.0] = k;
+        if let Some(input) = self.as_array() {
+            let mut values = Array::from_elem(dim.clone(), T::default());
+            let mut indices = Array::from_elem(dim, 0u32);
+            Zip::from(input.lanes(axis))
+                .and(values.lanes_mut(axis))
+                .and(indices.lanes_mut(axis))
+                .for_each(|x, mut values, mut indices| {
+                    let mut order: Vec<u32> = (0..len as u32).collect();
+                    order.sort_by(|a, b| {
+                        let (a, b) = (x[*a as usize], x[*b as usize]);
+                        if descending {
+                            sort_cmp(b, a)
+                        } else {
+                            sort_cmp(a, b)
+                        }
+                    });
+                    for ((i, y), index) in order
+                        .into_iter()
+                        .zip(values.iter_mut())
+                        .zip(indices.iter_mut())
+                    {
+                        *y = x[i as usize];
+                        *index = i;
+                    }
+                });
+            return Ok((values.into(), indices.into()));
+        }
+        #[cfg(not(feature = "device"))]
+        {
+            unreachable!()
+        }
+        #[cfg(feature = "device")]
+        {
+            let device = self.device();
+            let mut indices = unsafe { Tensor::<u32, D>::uninit(device.clone(), dim.clone())? };
+            if self.is_empty() || k == 0 {
+                let values = unsafe { Tensor::uninit(device, dim)? };
+                return Ok((values, indices));
+            }
+            let input = self.as_standard_layout()?;
+            let inner: usize = self.shape()[axis.0 + 1..].iter().product();
+            macro_for!($T in [u8, i8, u16, i16, f16, bf16, u32, i32, f32, u64, i64, f64] {
+                if T::scalar_type() == $T::scalar_type() {
+                    let input = ScalarTensorView::from(input.view()).try_into_tensor_view::<$T>().unwrap();
+                    let mut values = unsafe { Tensor::<$T, D>::uninit(device.clone(), dim)? };
+                    let kernel = paste! {
+                        kernels::[<sort_ $T>]::builder()?
+                            .specialize(descending as u32)
+                            .build(device)?
+                            .with_global_threads(input.len().to_u32().unwrap())
+                    };
+                    unsafe {
+                        kernel.dispatch(
+                            input.as_slice().unwrap(),
+                            len.to_u32().unwrap(),
+                            inner.to_u32().unwrap(),
+                            k.to_u32().unwrap(),
+                            values.as_slice_mut().unwrap(),
+                            indices.as_slice_mut().unwrap(),
+                        )?;
+                    }
+                    return Ok((values.cast_into().unwrap(), indices));
+                }
+            });
+            unreachable!()
+        }
+    }
+}
+
+// Total order where NaN is greater than all other values.
+fn sort_cmp<T: Scalar>(a: T, b: T) -> Ordering {
+    #[allow(clippy::eq_op)]
+    a.partial_cmp(&b).unwrap_or_else(|| (a != a).cmp(&(b != b)))
+}
+
+impl<S: ScalarDataMut, D: RemoveAxis> ScalarTensorBase<S, D> {
+    /// Adds `src` into `self` at `indices` along `axis`.
+    ///
+    /// See [`TensorBase::scatter_add`].
+    pub fn scatter_add<S2: ScalarData>(
+        &mut self,
+        axis: Axis,
+        indices: TensorView1<u32>,
+        src: &ScalarTensorBase<S2, D>,
+    ) -> Result<()> {
+        if self.scalar_type() != src.scalar_type() {
+            bail!(
+                "scatter_add src scalar_type {:?} != {:?}!",
+                src.scalar_type(),
+                self.scalar_type()
+            );
+        }
+        macro_for!($T in [f16, bf16, f32, f64] {
+            if self.scalar_type() == $T::scalar_type() {
+                let mut output = self.view_mut().try_into_tensor_view_mut::<$T>().unwrap();
+                let src = src.view().try_into_tensor_view::<$T>().unwrap();
+                return output.scatter_add(axis, indices, &src);
+            }
+        });
+        bail!("scatter_add {:?} unimplemented!", self.scalar_type());
+    }
+}
+
+impl<T: Scalar, S: DataMut<Elem = T>, D: RemoveAxis> TensorBase<S, D> {
+    /// Adds `src` into `self` at `indices` along `axis`.
+    ///
+    /// For each `i`, the `i`th slice of `src` along `axis` is added to the `indices[i]`th slice
+    /// of `self`. Repeated indices accumulate. This is the backward of
+    /// [`.index_select()`](TensorBase::index_select).
+    ///
+    /// On device, each element of `self` sums the slices that map to it, avoiding atomics, so
+    /// the result is deterministic.
+    ///
+    ///```no_run
+    /// # use autograph::{anyhow::Result, tensor::Tensor};
+    /// # use ndarray::{arr1, arr2, Axis};
+    /// # fn main() -> Result<()> {
+    /// let mut x = Tensor::<f32, _>::zeros(Default::default(), [2, 2])?;
+    /// let indices = Tensor::from(arr1(&[1u32, 0, 1]));
+    /// let src = Tensor::from(arr2(&[[1f32, 2.], [3., 4.], [5., 6.]]));
+    /// x.scatter_add(Axis(0), indices.view(), &src)?;
+    /// assert_eq!(x.into_array()?, arr2(&[[3f32, 4.], [6., 8.]]));
+    /// # Ok(())
+    /// # }
+    ///```
+    ///
+    /// **Errors**
+    /// - Implemented for floats (f16, bf16, f32, f64).
+    /// - `indices` or `src` is not on the same device as `self`.
+    /// - The shape of `src` is not the shape of `self` with `indices.len()` along `axis`.
+    /// - On the host, an index is out of bounds. On device, out of bounds indices are ignored.
+    /// - The operation could not be executed on the device.
+    ///
+    /// **Panics**
+    /// - `axis` is out of bounds.
+    pub fn scatter_add<S2: Data<Elem = T>>(
+        &mut self,
+        axis: Axis,
+        indices: TensorView1<u32>,
+        src: &TensorBase<S2, D>,
+    ) -> Result<()> {
+        if !matches!(
+            T::scalar_type(),
+            ScalarType::F16 | ScalarType::BF16 | ScalarType::F32 | ScalarType::F64
+        ) {
+            bail!("scatter_add {:?} unimplemented!", T::scalar_type());
+        }
+        let device = self.device();
+        if indices.device() != device || src.device() != device {
+            bail!(
+                "scatter_add indices device {:?} src device {:?} != {device:?}!",
+                indices.device(),
+                src.device()
+            );
+        }
+        let len = self.shape()[axis.0];
+        let mut dim = self.raw_dim();
+        dim[axis.0] = indices.len();
+        if src.raw_dim() != dim {
+            bail!(
+                "scatter_add src shape {:?} != {:?}!",
+                src.shape(),
+                dim.slice()
+            );
+        }
+        if let Some(((src, indices), mut output)) = src
+            .as_array()
+            .zip(indices.as_array())
+            .zip(self.as_array_mut())
+        {
+            if let Some(index) = indices.iter().find(|i| **i as usize >= len) {
+                bail!(
+                    "scatter_add index {index} out of bounds for axis {axis:?} with length {len}!"
+                );
+            }
+            for (index, src) in indices.iter().zip(src.axis_iter(axis)) {
+                output
+                    .index_axis_mut(axis, *index as usize)
+                    .zip_mut_with(&src, |y, x| *y += *x);
+            }
+            return Ok(());
+        }
+        #[cfg(not(feature = "device"))]
+        {
+            unreachable!()
+        }
+        #[cfg(feature = "device")]
+        {
+            if !self.is_standard_layout() {
+                let mut output = self.as_standard_layout()?.into_owned()?;
+                output.scatter_add(axis, indices, src)?;
+                return self.assign(&output);
+            }
+            let src = src.as_standard_layout()?;
+            let indices = indices.as_standard_layout()?;
+            let inner: usize = self.shape()[axis.0 + 1..].iter().product();
+            let n = indices.len();
+            let mut output = ScalarTensorViewMut::from(self.view_mut());
+            let y = output.as_scalar_slice_mut().unwrap();
+            let x = ScalarTensorView::from(src.view());
+            let x = x.as_scalar_slice().unwrap();
+            macro_for!($T in [f16, bf16, f32, f64] {
+                if T::scalar_type() == $T::scalar_type() {
+                    let kernel = paste! {
+                        kernels::[<scatter_add_ $T>]::builder()?.build(device)?
+                    };
+                    kernel.dispatch(
+                        x.try_into().unwrap(),
+                        indices.as_slice().unwrap(),
+                        len.to_u32().unwrap(),
+                        n.to_u32().unwrap(),
+                        inner.to_u32().unwrap(),
+                        y.try_into().unwrap(),
+                    )?;
+                    return Ok(());
+                }
+            });
+            unreachable!()
+        }
+    }
+}
+
+#[cfg(feature = "neural-network")]
+impl<T: Scalar, S: ArrayData<Elem = T>> Im2ColConv2 for ArrayBase<S, Ix4> {
+    type Output = Array2<T>;
+    fn im2col_conv2(&self, options: &Im2ColConv2Options) -> Result<Self::Output> {
+        let input = self.as_standard_layout();
+        let (bs, c, ih, iw) = input.dim();
+        let [oh, ow] = options.output_shape([ih, iw]);
+        let Im2ColConv2Options {
+            filter: [fh, fw],
+            padding: [(ph, _), (pw, _)],
+            stride: [sh, sw],
+            dilation: [dh, dw],
+        } = options.clone();
+        let mut output = Array::uninit([bs, oh, ow, c, fh * fw]);
+        for (input, mut output) in input.outer_iter().zip(output.outer_iter_mut()) {
+            for (input, mut output) in input.outer_iter().zip(output.axis_iter_mut(Axis(2))) {
+                for (hid, mut output) in output.outer_iter_mut().enumerate() {
+                    for (wid, mut output) in output.outer_iter_mut().enumerate() {
+                        for fi in 0..fh {
+                            for fj in 0..fw {
+                                let hidx = -(ph as isize) + (fi * dh + sh * hid) as isize;
+                                let widx = -(pw as isize) + (fj * dw + sw * wid) as isize;
+                                let fidx = fi * fw + fj;
+                                if hidx >= 0
+                                    && hidx < ih as isize
+                                    && widx >= 0
+                                    && widx < iw as isize
+                                {
+                                    unsafe {
+                                        output
+                                            .uget_mut(fidx)
+                                            .write(*input.uget((hidx as usize, widx as usize)));
+                                    }
+                                } else {
+                                    unsafe {
+                                        output.uget_mut(fidx).write(T::default());
+                                    }
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        let output = unsafe { output.assume_init() };
+        Ok(output.into_shape([bs * oh * ow, c * fh * fw]).unwrap())
+    }
+}
+
+#[cfg(feature = "neural-network")]
+impl<T: Scalar, S: Data<Elem = T>> Im2ColConv2 for TensorBase<S, Ix4> {
+    type Output = Tensor2<T>;
+    fn im2col_conv2(&self, options: &Im2ColConv2Options) -> Result<Self::Output> {
+        if let Some(input) = self.as_array() {
+            input.im2col_conv2(options).map(Into::into)
+        } else {
+            Ok(ScalarTensorView::from(self.view())
+                .im2col_conv2(options)?
+                .try_into_tensor()
+                .unwrap())
+        }
+    }
+}
+
+#[cfg(feature = "neural-network")]
+impl<S: ScalarData> Im2ColConv2 for ScalarTensorBase<S, Ix4> {
+    type Output = ScalarTensor2;
+    fn im2col_conv2(&self, options: &Im2ColConv2Options) -> Result<Self::Output> {
+        macro_wrap!(
+            paste! { #[allow(clippy::single_match)] match self.scalar_type() {
+                macro_for!($T in [bf16, f32] {
+                   ScalarType::[<$T:upper>] => {
+                        let input = self.view().try_into_tensor_view::<$T>().unwrap();
+                        if let Some(input) = input.as_array() {
+                            return Ok(Tensor::from(input.im2col_conv2(options)?).into());
+                        }
                         #[cfg(feature = "device")] {
                             let input = input.as_standard_layout()?;
                             let (bs, c, ih, iw) = input.dim();
                             let [oh, ow] = options.output_shape([ih, iw]);
                             let Im2ColConv2Options {
                                 filter: [fh, fw],
-                                padding: [ph, pw],
+                                padding: [(ph, _), (pw, _)],
                                 stride: [sh, sw],
                                 dilation: [dh, dw],
                             } = options.clone();
@@ -804,6 +2669,148 @@ impl<S: ScalarData> Im2ColConv2 for ScalarTensorBase<S, Ix4> {
     }
 }
 
+#[cfg(feature = "neural-network")]
+impl<T: Scalar, S1: ArrayData<Elem = T>, S2: ArrayData<Elem = T>> Conv2Direct<ArrayBase<S2, Ix4>>
+    for ArrayBase<S1, Ix4>
+{
+    type Output = Array4<T>;
+    fn conv2_direct(
+        &self,
+        weight: &ArrayBase<S2, Ix4>,
+        options: &Im2ColConv2Options,
+    ) -> Result<Self::Output> {
+        let (bs, c, ih, iw) = self.dim();
+        let (outputs, c2, fh, fw) = weight.dim();
+        if c != c2 || [fh, fw] != options.filter {
+            bail!(
+                "conv2_direct input {:?} incompatible with weight {:?}!",
+                self.shape(),
+                weight.shape()
+            );
+        }
+        let [oh, ow] = options.output_shape([ih, iw]);
+        let Im2ColConv2Options {
+            padding: [(ph, _), (pw, _)],
+            stride: [sh, sw],
+            dilation: [dh, dw],
+            ..
+        } = options.clone();
+        let output = Array::from_shape_fn([bs, outputs, oh, ow], |(bid, ocid, hid, wid)| {
+            let mut acc = 0f32;
+            for cid in 0..c {
+                for fi in 0..fh {
+                    let Some(hidx) = (hid * sh + fi * dh).checked_sub(ph).filter(|x| *x < ih)
+                    else {
+                        continue;
+                    };
+                    for fj in 0..fw {
+                        let Some(widx) = (wid * sw + fj * dw).checked_sub(pw).filter(|x| *x < iw)
+                        else {
+                            continue;
+                        };
+                        let x = self[(bid, cid, hidx, widx)];
+                        let w = weight[(ocid, cid, fi, fj)];
+                        acc += x.cast::<f32>() * w.cast::<f32>();
+                    }
+                }
+            }
+            acc.cast()
+        });
+        Ok(output)
+    }
+}
+
+#[cfg(feature = "neural-network")]
+impl<S1: ScalarData, S2: ScalarData> Conv2Direct<ScalarTensorBase<S2, Ix4>>
+    for ScalarTensorBase<S1, Ix4>
+{
+    type Output = ScalarTensor4;
+    fn conv2_direct(
+        &self,
+        weight: &ScalarTensorBase<S2, Ix4>,
+        options: &Im2ColConv2Options,
+    ) -> Result<Self::Output> {
+        if self.scalar_type() != weight.scalar_type() {
+            bail!(
+                "Expected {:?} found {:?}",
+                self.scalar_type(),
+                weight.scalar_type()
+            );
+        }
+        if self.device() != weight.device() {
+            bail!(
+                "conv2_direct input device {:?} != weight device {:?}!",
+                self.device(),
+                weight.device()
+            );
+        }
+        let (_bs, c, _ih, _iw) = self.dim();
+        let (_outputs, c2, fh, fw) = weight.dim();
+        if c != c2 || [fh, fw] != options.filter {
+            bail!(
+                "conv2_direct input {:?} incompatible with weight {:?}!",
+                self.shape(),
+                weight.shape()
+            );
+        }
+        macro_wrap!(
+            paste! { #[allow(clippy::single_match)] match self.scalar_type() {
+                macro_for!($T in [bf16, f32] {
+                   ScalarType::[<$T:upper>] => {
+                        let input = self.view().try_into_tensor_view::<$T>().unwrap();
+                        let weight = weight.view().try_into_tensor_view::<$T>().unwrap();
+                        if let Some((input, weight)) = input.as_array().zip(weight.as_array()) {
+                            return Ok(Tensor::from(input.conv2_direct(&weight, options)?).into());
+                        }
+                        #[cfg(feature = "device")] {
+                            let input = input.as_standard_layout()?;
+                            let weight = weight.as_standard_layout()?;
+                            let (bs, c, ih, iw) = input.dim();
+                            let outputs = weight.shape()[0];
+                            let [oh, ow] = options.output_shape([ih, iw]);
+                            let Im2ColConv2Options {
+                                filter: [fh, fw],
+                                padding: [(ph, _), (pw, _)],
+                                stride: [sh, sw],
+                                dilation: [dh, dw],
+                            } = options.clone();
+                            let mut output = unsafe {
+                                Tensor::<$T, _>::uninit(input.device(), [bs, outputs, oh, ow])?
+                            };
+                            neural_network_kernels::[<conv2_direct_ $T>]::builder()?
+                                .specialize(
+                                    c.to_u32().unwrap(),
+                                    ih.to_u32().unwrap(),
+                                    iw.to_u32().unwrap(),
+                                    outputs.to_u32().unwrap(),
+                                    oh.to_u32().unwrap(),
+                                    ow.to_u32().unwrap(),
+                                    fh.to_u32().unwrap(),
+                                    fw.to_u32().unwrap(),
+                                    ph.to_u32().unwrap(),
+                                    pw.to_u32().unwrap(),
+                                    sh.to_u32().unwrap(),
+                                    sw.to_u32().unwrap(),
+                                    dh.to_u32().unwrap(),
+                                    dw.to_u32().unwrap(),
+                                )
+                                .build(output.device())?
+                                .dispatch(
+                                    input.as_slice().unwrap(),
+                                    weight.as_slice().unwrap(),
+                                    output.as_slice_mut().unwrap(),
+                                )?;
+                            return Ok(output.into());
+                        }
+                   }
+                })
+                _ => (),
+            }}
+        );
+        bail!("conv2_direct {:?} unimplemented!()", self.scalar_type())
+    }
+}
+
 #[cfg(feature = "neural-network")]
 impl<T: Scalar, S: ArrayData<Elem = T>> Col2ImConv2 for ArrayBase<S, Ix2> {
     type Output = Array4<T>;
@@ -814,7 +2821,7 @@ impl<T: Scalar, S: ArrayData<Elem = T>> Col2ImConv2 for ArrayBase<S, Ix2> {
         let Col2ImConv2Options {
             shape: [ih, iw],
             filter: [fh, fw],
-            padding: [ph, pw],
+            padding: [(ph, _), (pw, _)],
             stride: [sh, sw],
             dilation: [dh, dw],
         } = options.clone();
@@ -911,7 +2918,7 @@ impl<S: ScalarData> Col2ImConv2 for ScalarTensorBase<S, Ix2> {
                             let Col2ImConv2Options {
                                 shape: [ih, iw],
                                 filter: [fh, fw],
-                                padding: [ph, pw],
+                                padding: [(ph, _), (pw, _)],
                                 stride: [sh, sw],
                                 dilation: [dh, dw],
                             } = options.clone();
@@ -1082,6 +3089,14 @@ impl<T: Scalar, S1: ArrayDataMut<Elem = T>, S2: ArrayData<Elem = T>>
                 }
             }
         }
+        let (_, _, ih, iw) = self.dim();
+        if !options.pools_all([ih, iw]) {
+            for ((_, _, row, col), dx) in self.indexed_iter_mut() {
+                if !options.is_pooled([ih, iw], [row, col]) {
+                    *dx = T::default();
+                }
+            }
+        }
         Ok(())
     }
 }
@@ -1104,54 +3119,277 @@ impl<T: Scalar, S1: DataMut<Elem = T>, S2: Data<Elem = T>> MaxPool2Backward<Tens
     }
 }
 
-#[cfg(feature = "neural-network")]
-impl<S1: ScalarDataMut, S2: ScalarData> MaxPool2Backward<ScalarTensorBase<S2, Ix4>>
-    for ScalarTensorBase<S1, Ix4>
-{
-    fn max_pool2_backward(
-        &mut self,
-        output_grad: ScalarTensorBase<S2, Ix4>,
-        options: MaxPool2Options,
-    ) -> Result<()> {
-        if self.scalar_type() != output_grad.scalar_type() {
-            bail!(
-                "Expected {:?} found {:?}",
-                self.scalar_type(),
-                output_grad.scalar_type()
-            );
+#[cfg(feature = "neural-network")]
+impl<S1: ScalarDataMut, S2: ScalarData> MaxPool2Backward<ScalarTensorBase<S2, Ix4>>
+    for ScalarTensorBase<S1, Ix4>
+{
+    fn max_pool2_backward(
+        &mut self,
+        output_grad: ScalarTensorBase<S2, Ix4>,
+        options: MaxPool2Options,
+    ) -> Result<()> {
+        if self.scalar_type() != output_grad.scalar_type() {
+            bail!(
+                "Expected {:?} found {:?}",
+                self.scalar_type(),
+                output_grad.scalar_type()
+            );
+        }
+        macro_wrap!(
+            paste! { #[allow(clippy::single_match)] match self.scalar_type() {
+                macro_for!($T in [bf16, f32] {
+                   ScalarType::[<$T:upper>] => {
+                        let mut input_grad = self.view_mut().try_into_tensor_view_mut::<$T>().unwrap();
+                        let output_grad = output_grad.view().try_into_tensor_view().unwrap();
+                        if let Some((mut dx, dy)) = input_grad.as_array_mut().zip(output_grad.as_array()) {
+                            return dx.max_pool2_backward(dy, options);
+                        }
+                        #[cfg(feature = "device")] {
+                            let (_bs, _c, ih, iw) = input_grad.dim();
+                            let [oh, ow] = options.output_shape([ih, iw]);
+                            let MaxPool2Options {
+                                size: [h, w],
+                                strides: [sh, sw],
+                            } = options;
+                            if !options.pools_all([ih, iw]) {
+                                neural_network_kernels::[<max_pool2_backward_skipped_ $T>]::builder()?
+                                    .specialize(h.to_u32().unwrap(), w.to_u32().unwrap(), sh.to_u32().unwrap(), sw.to_u32().unwrap())
+                                    .build(input_grad.device())?
+                                    .dispatch(ih.to_u32().unwrap(), iw.to_u32().unwrap(), oh.to_u32().unwrap(), ow.to_u32().unwrap(), input_grad.as_slice_mut().unwrap())?;
+                            }
+                            neural_network_kernels::[<max_pool2_backward_ $T>]::builder()?
+                                .specialize(h.to_u32().unwrap(), w.to_u32().unwrap(), sh.to_u32().unwrap(), sw.to_u32().unwrap())
+                                .build(input_grad.device())?
+                                .dispatch(input_grad.as_slice_mut().unwrap(), ih.to_u32().unwrap(), iw.to_u32().unwrap(), output_grad.as_slice().unwrap(), oh.to_u32().unwrap(), ow.to_u32().unwrap())?;
+                            return Ok(());
+                        }
+                   }
+                })
+                _ => (),
+            }}
+        );
+        bail!(
+            "max_pool2_backward {:?} unimplemented!()",
+            self.scalar_type()
+        )
+    }
+}
+
+#[cfg(feature = "neural-network")]
+impl<S: ScalarData> ScalarTensorBase<S, Ix4> {
+    /// 2D convolution.
+    ///
+    /// A functional convolution without autograd, the forward of [`Conv2`](crate::learn::neural_network::layer::Conv2).
+    /// `self` has shape [batch_size, inputs, height, width], `weight` has shape
+    /// [outputs, inputs, filter_height, filter_width], and `bias` has shape [outputs].
+    ///
+    /// Returns the output with shape [batch_size, outputs, output_height, output_width].
+    ///
+    ///```no_run
+    /// # use autograph::{anyhow::Result, krnl::scalar::ScalarType, krnl::device::Device, ops::Conv2dOptions, tensor::ScalarTensor};
+    /// # fn main() -> Result<()> {
+    /// # let device = Device::host();
+    /// let x = ScalarTensor::ones(device.clone(), [1, 3, 8, 8], ScalarType::F32)?;
+    /// let weight = ScalarTensor::ones(device.clone(), [4, 3, 3, 3], ScalarType::F32)?;
+    /// let options = Conv2dOptions {
+    ///     padding: [1, 1],
+    ///     ..Conv2dOptions::default()
+    /// };
+    /// let y = x.conv2d(&weight, None::<&ScalarTensor<_>>, &options)?;
+    /// assert_eq!(y.shape(), &[1, 4, 8, 8]);
+    /// # Ok(())
+    /// # }
+    ///```
+    ///
+    /// **Errors**
+    /// - The scalar types are not the same, or are not BF16 or F32.
+    /// - The tensors are not on the same device.
+    /// - The shapes are incompatible.
+    /// - The stride or dilation is 0, or the dilated filter is larger than the padded input.
+    /// - The operation could not be executed on the device.
+    pub fn conv2d<S2: ScalarData, S3: ScalarData>(
+        &self,
+        weight: &ScalarTensorBase<S2, Ix4>,
+        bias: Option<&ScalarTensorBase<S3, Ix1>>,
+        options: &Conv2dOptions,
+    ) -> Result<ScalarTensor4> {
+        let (batch_size, inputs, ih, iw) = self.dim();
+        let (outputs, weight_inputs, fh, fw) = weight.dim();
+        if inputs != weight_inputs || bias.map_or(false, |bias| bias.len() != outputs) {
+            bail!(
+                "conv2d input shape {:?} incompatible with weight shape {:?} and bias shape {:?}!",
+                self.shape(),
+                weight.shape(),
+                bias.map(|bias| bias.shape()),
+            );
+        }
+        let scalar_type = self.scalar_type();
+        if weight.scalar_type() != scalar_type
+            || bias.map_or(false, |bias| bias.scalar_type() != scalar_type)
+        {
+            bail!(
+                "conv2d scalar types {scalar_type:?} {:?} {:?} do not match!",
+                weight.scalar_type(),
+                bias.map(|bias| bias.scalar_type()),
+            );
+        }
+        let device = self.device();
+        if weight.device() != device || bias.map_or(false, |bias| bias.device() != device) {
+            bail!(
+                "conv2d devices {device:?} {:?} {:?} do not match!",
+                weight.device(),
+                bias.map(|bias| bias.device()),
+            );
+        }
+        let Conv2dOptions {
+            padding,
+            stride,
+            dilation,
+        } = *options;
+        for (((x, f), p), (s, d)) in [ih, iw]
+            .into_iter()
+            .zip([fh, fw])
+            .zip(padding)
+            .zip(stride.into_iter().zip(dilation))
+        {
+            if conv_output_size(x, f, (p, p), s, d).is_none() {
+                bail!("conv2d invalid options {options:?} for input shape {:?} and weight shape {:?}!", self.shape(), weight.shape());
+            }
+        }
+        let options = Im2ColConv2Options {
+            filter: [fh, fw],
+            padding: padding.map(|p| (p, p)),
+            stride,
+            dilation,
+        };
+        let [oh, ow] = options.output_shape([ih, iw]);
+        let im2col_matrix = self.im2col_conv2(&options)?;
+        let weight = weight.as_standard_layout()?;
+        let weight_matrix = weight
+            .view()
+            .into_shape([outputs, inputs * fh * fw])
+            .unwrap();
+        let mut output_matrix = im2col_matrix.dot(&weight_matrix.t())?;
+        if let Some(bias) = bias {
+            output_matrix.add_assign(bias)?;
+        }
+        output_matrix
+            .view()
+            .into_shape([batch_size, oh, ow, outputs])
+            .unwrap()
+            .permuted_axes([0, 3, 1, 2])
+            .to_owned()
+    }
+    /// 2D max pooling.
+    ///
+    /// A functional max pool without autograd, the forward of [`MaxPool2`](crate::learn::neural_network::layer::MaxPool2).
+    /// `self` has shape [batch_size, channels, height, width].
+    ///
+    /// **Errors**
+    /// - The scalar type is not BF16 or F32.
+    /// - The filter or stride is 0, or the filter is larger than the input.
+    /// - The operation could not be executed on the device.
+    pub fn max_pool2d(&self, options: &MaxPool2dOptions) -> Result<ScalarTensor4> {
+        let MaxPool2dOptions { filter, stride } = *options;
+        let (_, _, ih, iw) = self.dim();
+        for ((x, f), s) in [ih, iw].into_iter().zip(filter).zip(stride) {
+            if conv_output_size(x, f, (0, 0), s, 1).is_none() {
+                bail!(
+                    "max_pool2d invalid options {options:?} for input shape {:?}!",
+                    self.shape()
+                );
+            }
+        }
+        self.max_pool2(MaxPool2Options {
+            size: filter,
+            strides: stride,
+        })
+    }
+}
+
+#[cfg(feature = "neural-network")]
+impl<T: Scalar, S: Data<Elem = T>> TensorBase<S, Ix4> {
+    /// 2D convolution.
+    ///
+    /// See [`ScalarTensorBase::conv2d()`].
+    pub fn conv2d<S2: Data<Elem = T>, S3: Data<Elem = T>>(
+        &self,
+        weight: &TensorBase<S2, Ix4>,
+        bias: Option<&TensorBase<S3, Ix1>>,
+        options: &Conv2dOptions,
+    ) -> Result<Tensor4<T>> {
+        let bias = bias.map(|bias| ScalarTensorView::from(bias.view()));
+        let output = ScalarTensorView::from(self.view()).conv2d(
+            &ScalarTensorView::from(weight.view()),
+            bias.as_ref(),
+            options,
+        )?;
+        Ok(output.try_into().unwrap())
+    }
+    /// 2D max pooling.
+    ///
+    /// See [`ScalarTensorBase::max_pool2d()`].
+    pub fn max_pool2d(&self, options: &MaxPool2dOptions) -> Result<Tensor4<T>> {
+        let output = ScalarTensorView::from(self.view()).max_pool2d(options)?;
+        Ok(output.try_into().unwrap())
+    }
+}
+
+#[cfg_attr(feature = "device", module)]
+mod pow {
+    #[cfg(any(feature = "device", target_arch = "spirv"))]
+    use dry::macro_for;
+    #[cfg(not(target_arch = "spirv"))]
+    use krnl::krnl_core;
+    #[cfg(target_arch = "spirv")]
+    use krnl_core::half::bf16;
+    #[cfg(any(feature = "device", target_arch = "spirv"))]
+    use krnl_core::macros::kernel;
+    #[cfg(target_arch = "spirv")]
+    use krnl_core::num_traits::Float;
+    use krnl_core::scalar::Scalar;
+    #[cfg(any(feature = "device", target_arch = "spirv"))]
+    use paste::paste;
+
+    pub fn pow_impl<T: Scalar>(x: T, e: T) -> T {
+        x.cast::<f32>().powf(e.cast::<f32>()).cast()
+    }
+
+    // ln is undefined for x <= 0, so the gradient of the exponent is nan on the host and the
+    // device.
+    pub fn pow_backward_impl<T: Scalar>(x: T, e: T, dy: T) -> (T, T) {
+        let (x, e, dy) = (x.cast::<f32>(), e.cast::<f32>(), dy.cast::<f32>());
+        let dx = dy * e * x.powf(e - 1.);
+        let de = if x > 0. {
+            dy * x.powf(e) * x.ln()
+        } else {
+            f32::NAN
+        };
+        (dx.cast(), de.cast())
+    }
+
+    #[cfg(any(feature = "device", target_arch = "spirv"))]
+    macro_for!($T in [bf16, f32] {
+        paste! {
+            #[kernel]
+            pub fn [<pow_ $T>](#[item] x: $T, #[item] e: $T, #[item] y: &mut $T) {
+                *y = pow_impl(x, e);
+            }
+
+            #[kernel]
+            pub fn [<pow_backward_ $T>](
+                #[item] x: $T,
+                #[item] e: $T,
+                #[item] dy: $T,
+                #[item] dx: &mut $T,
+                #[item] de: &mut $T,
+            ) {
+                let (dx_item, de_item) = pow_backward_impl(x, e, dy);
+                *dx = dx_item;
+                *de = de_item;
+            }
         }
-        macro_wrap!(
-            paste! { #[allow(clippy::single_match)] match self.scalar_type() {
-                macro_for!($T in [bf16, f32] {
-                   ScalarType::[<$T:upper>] => {
-                        let mut input_grad = self.view_mut().try_into_tensor_view_mut::<$T>().unwrap();
-                        let output_grad = output_grad.view().try_into_tensor_view().unwrap();
-                        if let Some((mut dx, dy)) = input_grad.as_array_mut().zip(output_grad.as_array()) {
-                            return dx.max_pool2_backward(dy, options);
-                        }
-                        #[cfg(feature = "device")] {
-                            let (_bs, _c, ih, iw) = input_grad.dim();
-                            let [oh, ow] = options.output_shape([ih, iw]);
-                            let MaxPool2Options {
-                                size: [h, w],
-                                strides: [sh, sw],
-                            } = options;
-                            neural_network_kernels::[<max_pool2_backward_ $T>]::builder()?
-                                .specialize(h.to_u32().unwrap(), w.to_u32().unwrap(), sh.to_u32().unwrap(), sw.to_u32().unwrap())
-                                .build(input_grad.device())?
-                                .dispatch(input_grad.as_slice_mut().unwrap(), ih.to_u32().unwrap(), iw.to_u32().unwrap(), output_grad.as_slice().unwrap(), oh.to_u32().unwrap(), ow.to_u32().unwrap())?;
-                            return Ok(());
-                        }
-                   }
-                })
-                _ => (),
-            }}
-        );
-        bail!(
-            "max_pool2_backward {:?} unimplemented!()",
-            self.scalar_type()
-        )
-    }
+    });
 }
 
 #[cfg_attr(feature = "device", module)]
@@ -1365,6 +3603,327 @@ mod kernels {
         });
     });
 
+    macro_for!($T in [u8, i8, u16, i16, f16, bf16, u32, i32, f32, u64, i64, f64] {
+        paste! {
+            #[kernel]
+            pub fn [<masked_fill_ $T>](
+                #[item] x: $T,
+                #[item] mask: u8,
+                value: $T,
+                #[item] y: &mut $T,
+            ) {
+                *y = if mask != 0 { value } else { x };
+            }
+        }
+    });
+
+    macro_for!($T in [u8, i8, u16, i16, f16, bf16, u32, i32, f32, u64, i64, f64] {
+        paste! {
+            #[kernel]
+            pub fn [<select_ $T>](op: u32, #[item] x: $T, #[item] y: $T, #[item] z: &mut $T) {
+                let first = if op == 0 { x >= y } else { x <= y };
+                *z = if first { x } else { y };
+            }
+        }
+    });
+
+    macro_for!($T in [bf16, f32] {
+        paste! {
+            #[kernel]
+            pub fn [<select_backward_ $T>](
+                op: u32,
+                #[item] x: $T,
+                #[item] y: $T,
+                #[item] dz: $T,
+                #[item] dx: &mut $T,
+                #[item] dy: &mut $T,
+            ) {
+                let first = if op == 0 { x >= y } else { x <= y };
+                if first {
+                    *dx = dz;
+                    *dy = $T::default();
+                } else {
+                    *dx = $T::default();
+                    *dy = dz;
+                }
+            }
+        }
+    });
+
+    macro_for!($T in [u8, i8, u16, i16, f16, bf16, u32, i32, f32, u64, i64, f64] {
+        paste! {
+            #[kernel]
+            pub fn [<compare_ $T>](op: u32, threshold: $T, #[item] x: $T, #[item] y: &mut u8) {
+                let z = match op {
+                    0 => x > threshold,
+                    1 => x >= threshold,
+                    2 => x < threshold,
+                    3 => x <= threshold,
+                    _ => x == threshold,
+                };
+                *y = if z { 1 } else { 0 };
+            }
+        }
+    });
+
+    macro_for!($T in [i8, i16, f16, bf16, i32, f32, i64, f64] {
+        paste! {
+            #[kernel]
+            pub fn [<abs_ $T>](#[item] x: $T, #[item] y: &mut $T) {
+                *y = if x < $T::default() { $T::default() - x } else { x };
+            }
+
+            #[kernel]
+            pub fn [<sign_ $T>](#[item] x: $T, #[item] y: &mut $T) {
+                use krnl_core::num_traits::One;
+
+                *y = if x > $T::default() {
+                    $T::one()
+                } else if x < $T::default() {
+                    $T::default() - $T::one()
+                } else {
+                    $T::default()
+                };
+            }
+        }
+    });
+
+    macro_for!($T in [f16, bf16, f32] {
+        paste! {
+            #[kernel]
+            pub fn [<is_finite_ $T>](#[item] x: $T, #[item] y: &mut u8) {
+                let x = x.cast::<f32>();
+                // Both NaN and inf - inf are NaN.
+                *y = if x - x == 0. { 1 } else { 0 };
+            }
+        }
+    });
+
+    macro_for!($T in [u8, i8, u16, i16, f16, bf16, u32, i32, f32, u64, i64, f64] {
+        paste! {
+            #[kernel]
+            pub fn [<roll_ $T>](
+                #[global] x: Slice<$T>,
+                len: u32,
+                inner: u32,
+                shift: u32,
+                #[item] y: &mut $T,
+            ) {
+                let idx = kernel.item_id;
+                let k = idx % inner;
+                let i = (idx / inner) % len;
+                let outer = idx / (inner * len);
+                let src = (i + len - shift) % len;
+                *y = x[((outer * len + src) * inner + k) as usize];
+            }
+        }
+    });
+
+    macro_for!($T in [u8, i8, u16, i16, f16, bf16, u32, i32, f32, u64, i64, f64] {
+        paste! {
+            #[kernel]
+            pub fn [<flip_ $T>](
+                #[global] x: Slice<$T>,
+                len: u32,
+                inner: u32,
+                #[item] y: &mut $T,
+            ) {
+                let idx = kernel.item_id;
+                let k = idx % inner;
+                let i = (idx / inner) % len;
+                let outer = idx / (inner * len);
+                let src = len - 1 - i;
+                *y = x[((outer * len + src) * inner + k) as usize];
+            }
+        }
+    });
+
+    macro_for!($T in [u8, i8, u16, i16, f16, bf16, u32, i32, f32, u64, i64, f64] {
+        paste! {
+            #[kernel]
+            pub fn [<index_select_ $T>](
+                #[global] x: Slice<$T>,
+                #[global] indices: Slice<u32>,
+                len: u32,
+                n: u32,
+                inner: u32,
+                #[item] y: &mut $T,
+            ) {
+                let idx = kernel.item_id;
+                let k = idx % inner;
+                let i = (idx / inner) % n;
+                let outer = idx / (inner * n);
+                let j = indices[i as usize];
+                *y = if j < len {
+                    x[((outer * len + j) * inner + k) as usize]
+                } else {
+                    Default::default()
+                };
+            }
+        }
+    });
+
+    macro_for!($T in [u8, i8, u16, i16, f16, bf16, u32, i32, f32, u64, i64, f64] {
+        paste! {
+            #[kernel]
+            pub fn [<eye_ $T>](cols: u32, #[item] y: &mut $T) {
+                use krnl_core::num_traits::One;
+
+                let idx = kernel.item_id;
+                *y = if idx / cols == idx % cols {
+                    $T::one()
+                } else {
+                    $T::default()
+                };
+            }
+
+            #[kernel]
+            pub fn [<diag_ $T>](#[global] x: Slice<$T>, #[item] y: &mut $T) {
+                let idx = kernel.item_id as usize;
+                let n = x.len();
+                *y = if idx / n == idx % n {
+                    x[idx % n]
+                } else {
+                    $T::default()
+                };
+            }
+
+            #[kernel]
+            pub fn [<diagonal_ $T>](#[global] x: Slice<$T>, cols: u32, #[item] y: &mut $T) {
+                let idx = kernel.item_id;
+                *y = x[(idx * cols + idx) as usize];
+            }
+        }
+    });
+
+    // Counts the non-zero elements of each block of the mask.
+    #[kernel]
+    pub fn masked_count(#[global] mask: Slice<u8>, block: u32, #[item] count: &mut u32) {
+        let start = kernel.item_id * block;
+        let mut end = start + block;
+        if end as usize > mask.len() {
+            end = mask.len() as u32;
+        }
+        let mut acc = 0;
+        for i in start..end {
+            if mask[i as usize] != 0 {
+                acc += 1;
+            }
+        }
+        *count = acc;
+    }
+
+    macro_for!($T in [u8, i8, u16, i16, f16, bf16, u32, i32, f32, u64, i64, f64] {
+        paste! {
+            // Writes the selected elements of each block of x to y, starting at the offset of
+            // the block.
+            #[kernel]
+            pub unsafe fn [<masked_select_ $T>](
+                #[global] x: Slice<$T>,
+                #[global] mask: Slice<u8>,
+                block: u32,
+                #[global] offsets: Slice<u32>,
+                #[global] y: UnsafeSlice<$T>,
+            ) {
+                let idx = kernel.global_id;
+                if idx as usize >= offsets.len() {
+                    return;
+                }
+                let start = idx * block;
+                let mut end = start + block;
+                if end as usize > x.len() {
+                    end = x.len() as u32;
+                }
+                let mut offset = offsets[idx as usize];
+                for i in start..end {
+                    if mask[i as usize] != 0 {
+                        unsafe {
+                            *y.unsafe_index_mut(offset as usize) = x[i as usize];
+                        }
+                        offset += 1;
+                    }
+                }
+            }
+        }
+    });
+
+    // Total order where NaN is greater than all other values.
+    #[allow(clippy::eq_op)]
+    fn sort_less<T: Scalar>(a: T, b: T) -> bool {
+        a < b || (a == a && b != b)
+    }
+
+    macro_for!($T in [u8, i8, u16, i16, f16, bf16, u32, i32, f32, u64, i64, f64] {
+        paste! {
+            // Computes the rank of each element of x along the axis, writing it to y if the
+            // rank is less than k. Ties are ranked by index.
+            #[kernel]
+            pub unsafe fn [<sort_ $T>]<const DESCENDING: u32>(
+                #[global] x: Slice<$T>,
+                len: u32,
+                inner: u32,
+                k: u32,
+                #[global] y: UnsafeSlice<$T>,
+                #[global] indices: UnsafeSlice<u32>,
+            ) {
+                let idx = kernel.global_id;
+                if idx as usize >= x.len() {
+                    return;
+                }
+                let c = idx % inner;
+                let i = (idx / inner) % len;
+                let outer = idx / (inner * len);
+                let xi = x[idx as usize];
+                let mut rank = 0;
+                for j in 0..len {
+                    let xj = x[((outer * len + j) * inner + c) as usize];
+                    let before = if DESCENDING == 1 {
+                        sort_less(xi, xj)
+                    } else {
+                        sort_less(xj, xi)
+                    };
+                    let tie = j < i && !sort_less(xi, xj) && !sort_less(xj, xi);
+                    if before || tie {
+                        rank += 1;
+                    }
+                }
+                if rank < k {
+                    let y_idx = ((outer * k + rank) * inner + c) as usize;
+                    unsafe {
+                        *y.unsafe_index_mut(y_idx) = xi;
+                        *indices.unsafe_index_mut(y_idx) = i;
+                    }
+                }
+            }
+        }
+    });
+
+    macro_for!($T in [f16, bf16, f32, f64] {
+        paste! {
+            #[kernel]
+            pub fn [<scatter_add_ $T>](
+                #[global] x: Slice<$T>,
+                #[global] indices: Slice<u32>,
+                len: u32,
+                n: u32,
+                inner: u32,
+                #[item] y: &mut $T,
+            ) {
+                let idx = kernel.item_id;
+                let k = idx % inner;
+                let j = (idx / inner) % len;
+                let outer = idx / (inner * len);
+                let mut acc = *y;
+                for i in 0..n {
+                    if indices[i as usize] == j {
+                        acc += x[((outer * n + i) * inner + k) as usize];
+                    }
+                }
+                *y = acc;
+            }
+        }
+    });
+
     macro_for!($X in [u8, u16, u32, u64] {
         macro_for!($Y in [u8, i8, u16, i16, f16, bf16, u32, i32, f32, u64, i64, f64] {
             paste! {
@@ -1632,7 +4191,7 @@ mod neural_network_kernels {
                 for i in 0..H {
                     let mut col = wid * SW;
                     for j in 0..W {
-                        let dx = unsafe { dx.unsafe_index_mut((dx_start + row * ih + col) as usize) };
+                        let dx = unsafe { dx.unsafe_index_mut((dx_start + row * iw + col) as usize) };
                         let x = dx.cast::<f32>();
                         *dx = $T::zero();
                         if (i == 0 && j == 0) || x > m {
@@ -1651,6 +4210,82 @@ mod neural_network_kernels {
                     *dx.unsafe_index_mut((dx_start + row * iw + col) as usize) = dy.cast();
                 }
             }
+
+            // Zeros positions of dx that are not in a pooling window.
+            #[kernel]
+            pub fn [<max_pool2_backward_skipped_ $T>]<const H: u32, const W: u32, const SH: u32, const SW: u32>(
+                ih: u32,
+                iw: u32,
+                oh: u32,
+                ow: u32,
+                #[item] dx: &mut $T,
+            ) {
+                let hwid = kernel.item_id % (ih * iw);
+                let row = hwid / iw;
+                let col = hwid % iw;
+                let pooled = row < (oh - 1) * SH + H
+                    && row % SH < H
+                    && col < (ow - 1) * SW + W
+                    && col % SW < W;
+                if !pooled {
+                    *dx = $T::zero();
+                }
+            }
+
+            #[kernel]
+            pub fn [<conv2_direct_ $T>]<
+                const C: u32,
+                const IH: u32,
+                const IW: u32,
+                const OC: u32,
+                const OH: u32,
+                const OW: u32,
+                const FH: u32,
+                const FW: u32,
+                const PH: u32,
+                const PW: u32,
+                const SH: u32,
+                const SW: u32,
+                const DH: u32,
+                const DW: u32,
+            >(
+                #[global] x: Slice<$T>,
+                #[global] w: Slice<$T>,
+                #[item] y: &mut $T,
+            ) {
+                let c = C;
+                let [ih, iw] = [IH, IW];
+                let [oc, oh, ow] = [OC, OH, OW];
+                let [fh, fw] = [FH, FW];
+                let [ph, pw] = [PH, PW];
+                let [sh, sw] = [SH, SW];
+                let [dh, dw] = [DH, DW];
+
+                let idx = kernel.item_id;
+                let bid = idx / (oc * oh * ow);
+                let ocid = (idx / (oh * ow)) % oc;
+                let hid = (idx / ow) % oh;
+                let wid = idx % ow;
+                let mut acc = 0f32;
+                for cid in 0..c {
+                    let x_offset = (bid * c + cid) * ih * iw;
+                    let w_offset = (ocid * c + cid) * fh * fw;
+                    for fi in 0..fh {
+                        let hidx = -(ph as i32) + (fi * dh + sh * hid) as i32;
+                        if hidx >= 0 && hidx < ih as i32 {
+                            for fj in 0..fw {
+                                let widx = -(pw as i32) + (fj * dw + sw * wid) as i32;
+                                if widx >= 0 && widx < iw as i32 {
+                                    let x = x[(x_offset + hidx as u32 * iw + widx as u32) as usize];
+                                    let w = w[(w_offset + fi * fw + fj) as usize];
+                                    acc += x.cast::<f32>() * w.cast::<f32>();
+                                }
+                            }
+                        }
+                    }
+                }
+                *y = acc.cast();
+            }
         }
     });
 }
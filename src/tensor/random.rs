@@ -0,0 +1,303 @@
+/*!
+Random number generation.
+
+[`Rng`] is a counter based generator (Philox 4x32-10). Each element of a random tensor is a
+pure function of the seed, the draw offset, and the element index, so tensors are generated
+directly on the device and the same seed yields the same values.
+
+```no_run
+# use autograph::{krnl::device::Device, tensor::{Tensor, random::{DeviceRng, Distribution}}};
+# fn main() -> anyhow::Result<()> {
+let device = Device::host();
+let mut rng = device.rng(0);
+let x = Tensor::<f32, _>::rand(&mut rng, [2, 3], Distribution::Normal)?;
+# Ok(())
+# }
+```
+*/
+use super::*;
+#[cfg(feature = "device")]
+use krnl::macros::module;
+
+/// Random distributions.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Distribution {
+    /// Uniform on [0, 1).
+    Uniform,
+    /// Normal with mean 0 and standard deviation 1.
+    Normal,
+}
+
+impl Distribution {
+    fn as_u32(self) -> u32 {
+        match self {
+            Self::Uniform => kernels::UNIFORM,
+            Self::Normal => kernels::NORMAL,
+        }
+    }
+}
+
+/// Random number generator.
+///
+/// Stores the device, the seed, and an offset that is advanced with each draw, such that
+/// successive draws are independent and the sequence of draws is reproducible.
+#[derive(Clone, Debug)]
+pub struct Rng {
+    device: Device,
+    seed: u64,
+    offset: u64,
+}
+
+impl Rng {
+    /// Creates a generator on `device` with `seed`.
+    pub fn new(device: Device, seed: u64) -> Self {
+        Self {
+            device,
+            seed,
+            offset: 0,
+        }
+    }
+    /// The device.
+    pub fn device(&self) -> Device {
+        self.device.clone()
+    }
+    /// The seed.
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+    /// The number of draws.
+    pub fn offset(&self) -> u64 {
+        self.offset
+    }
+    fn next_offset(&mut self) -> u64 {
+        let offset = self.offset;
+        self.offset += 1;
+        offset
+    }
+}
+
+/// Creates a [`Rng`] from a [`Device`].
+pub trait DeviceRng {
+    /// Creates a generator with `seed`.
+    ///
+    /// See [`Rng::new()`].
+    fn rng(&self, seed: u64) -> Rng;
+}
+
+impl DeviceRng for Device {
+    fn rng(&self, seed: u64) -> Rng {
+        Rng::new(self.clone(), seed)
+    }
+}
+
+impl<T: Scalar, D: Dimension> Tensor<T, D> {
+    /// Creates a tensor with `shape` sampled from `dist` on the device of `rng`.
+    ///
+    /// Values are sampled as f32 and then cast to `T`.
+    ///
+    /// **Errors**
+    /// - The tensor has more than [`u32::MAX`] elements.
+    /// - The operation could not be executed on the device.
+    pub fn rand<Sh>(rng: &mut Rng, shape: Sh, dist: Distribution) -> Result<Self>
+    where
+        Sh: ShapeBuilder<Dim = D>,
+    {
+        let (dim, _strides) = dim_strides_from_shape(shape.into_shape());
+        rand_f32(rng, dim, dist.as_u32(), 0., 1.)?.cast_into()
+    }
+    /// Creates a tensor with `shape` sampled from a normal distribution with `mean` and `std`.
+    ///
+    /// **Errors**
+    /// - `T` is not f32 or bf16.
+    /// - `std` is negative.
+    /// - See [`.rand()`](Self::rand).
+    pub fn randn<Sh>(rng: &mut Rng, shape: Sh, mean: f32, std: f32) -> Result<Self>
+    where
+        Sh: ShapeBuilder<Dim = D>,
+    {
+        if !matches!(T::scalar_type(), ScalarType::F32 | ScalarType::BF16) {
+            bail!("randn {:?} unimplemented!", T::scalar_type());
+        }
+        if std.is_nan() || std < 0. {
+            bail!("randn std {std} must be non-negative!");
+        }
+        let (dim, _strides) = dim_strides_from_shape(shape.into_shape());
+        rand_f32(rng, dim, kernels::NORMAL, mean, std)?.cast_into()
+    }
+    /// Creates a tensor with `shape` sampled uniformly from [`low`, `high`).
+    ///
+    /// **Errors**
+    /// - `T` is not f32 or bf16.
+    /// - `low` is greater than `high`.
+    /// - See [`.rand()`](Self::rand).
+    pub fn rand_uniform<Sh>(rng: &mut Rng, shape: Sh, low: f32, high: f32) -> Result<Self>
+    where
+        Sh: ShapeBuilder<Dim = D>,
+    {
+        if !matches!(T::scalar_type(), ScalarType::F32 | ScalarType::BF16) {
+            bail!("rand_uniform {:?} unimplemented!", T::scalar_type());
+        }
+        if low > high {
+            bail!("rand_uniform low {low} > high {high}!");
+        }
+        let (dim, _strides) = dim_strides_from_shape(shape.into_shape());
+        rand_f32(rng, dim, kernels::UNIFORM, low, high)?.cast_into()
+    }
+    /// Creates a tensor with `shape` of 1's with probability `p` and 0's otherwise.
+    ///
+    /// Useful for dropout masks.
+    ///
+    /// **Errors**
+    /// - `T` is not f32 or bf16.
+    /// - `p` is not in [0, 1].
+    /// - See [`.rand()`](Self::rand).
+    pub fn bernoulli<Sh>(rng: &mut Rng, shape: Sh, p: f32) -> Result<Self>
+    where
+        Sh: ShapeBuilder<Dim = D>,
+    {
+        if !matches!(T::scalar_type(), ScalarType::F32 | ScalarType::BF16) {
+            bail!("bernoulli {:?} unimplemented!", T::scalar_type());
+        }
+        if !(0. ..=1.).contains(&p) {
+            bail!("bernoulli p {p} not in [0, 1]!");
+        }
+        let (dim, _strides) = dim_strides_from_shape(shape.into_shape());
+        rand_f32(rng, dim, kernels::BERNOULLI, p, 0.)?.cast_into()
+    }
+}
+
+fn rand_f32<D: Dimension>(
+    rng: &mut Rng,
+    dim: D,
+    dist: u32,
+    a: f32,
+    b: f32,
+) -> Result<Tensor<f32, D>> {
+    if dim.size() > u32::MAX as usize {
+        bail!("rand size {} > u32::MAX!", dim.size());
+    }
+    let device = rng.device();
+    let [key0, key1] = split_u64(rng.seed());
+    let [offset0, offset1] = split_u64(rng.next_offset());
+    if device.is_host() {
+        let output = Array::from_shape_fn(dim.size(), |i| {
+            kernels::rand_impl(dist, a, b, key0, key1, i as u32, offset0, offset1)
+        })
+        .into_shape(dim)
+        .unwrap();
+        return Ok(output.into());
+    }
+    #[cfg(not(feature = "device"))]
+    {
+        unreachable!()
+    }
+    #[cfg(feature = "device")]
+    {
+        let mut output = unsafe { Tensor::<f32, D>::uninit(device.clone(), dim)? };
+        kernels::rand_f32::builder()?
+            .specialize(dist)
+            .build(device)?
+            .dispatch(
+                a,
+                b,
+                key0,
+                key1,
+                offset0,
+                offset1,
+                output.as_slice_mut().unwrap(),
+            )?;
+        Ok(output)
+    }
+}
+
+fn split_u64(x: u64) -> [u32; 2] {
+    [x as u32, (x >> 32) as u32]
+}
+
+#[cfg_attr(feature = "device", module)]
+mod kernels {
+    #[cfg(not(target_arch = "spirv"))]
+    use krnl::krnl_core;
+    #[cfg(any(feature = "device", target_arch = "spirv"))]
+    use krnl_core::macros::kernel;
+    #[cfg(target_arch = "spirv")]
+    use krnl_core::num_traits::Float;
+
+    pub const UNIFORM: u32 = 1;
+    pub const NORMAL: u32 = 2;
+    pub const BERNOULLI: u32 = 3;
+
+    const PHILOX_M0: u32 = 0xD2511F53;
+    const PHILOX_M1: u32 = 0xCD9E8D57;
+    const PHILOX_W0: u32 = 0x9E3779B9;
+    const PHILOX_W1: u32 = 0xBB67AE85;
+
+    // Computes the high and low words of a * b without 64 bit integers.
+    fn mul_hi_lo(a: u32, b: u32) -> (u32, u32) {
+        let (a_lo, a_hi) = (a & 0xFFFF, a >> 16);
+        let (b_lo, b_hi) = (b & 0xFFFF, b >> 16);
+        let ll = a_lo * b_lo;
+        let lh = a_lo * b_hi;
+        let hl = a_hi * b_lo;
+        let hh = a_hi * b_hi;
+        let mid = (ll >> 16) + (lh & 0xFFFF) + (hl & 0xFFFF);
+        let hi = hh + (lh >> 16) + (hl >> 16) + (mid >> 16);
+        (hi, a.wrapping_mul(b))
+    }
+
+    fn philox4x32_10(mut c: [u32; 4], mut k: [u32; 2]) -> [u32; 4] {
+        for _ in 0..10 {
+            let (hi0, lo0) = mul_hi_lo(PHILOX_M0, c[0]);
+            let (hi1, lo1) = mul_hi_lo(PHILOX_M1, c[2]);
+            c = [hi1 ^ c[1] ^ k[0], lo1, hi0 ^ c[3] ^ k[1], lo0];
+            k = [k[0].wrapping_add(PHILOX_W0), k[1].wrapping_add(PHILOX_W1)];
+        }
+        c
+    }
+
+    // Uniform on [0, 1) with 24 bits of precision.
+    fn uniform_f32(x: u32) -> f32 {
+        (x >> 8) as f32 * (1. / 16_777_216.)
+    }
+
+    // Uniform samples from [a, b), normal samples with mean a and std b, bernoulli samples 1
+    // with probability a.
+    #[allow(clippy::too_many_arguments)]
+    pub fn rand_impl(
+        dist: u32,
+        a: f32,
+        b: f32,
+        key0: u32,
+        key1: u32,
+        index: u32,
+        offset0: u32,
+        offset1: u32,
+    ) -> f32 {
+        let [x0, x1, _, _] = philox4x32_10([index, 0, offset0, offset1], [key0, key1]);
+        match dist {
+            NORMAL => {
+                // Box-Muller, u1 in (0, 1] to avoid ln(0).
+                let u1 = 1. - uniform_f32(x0);
+                let u2 = uniform_f32(x1);
+                let z = (-2. * u1.ln()).sqrt() * (2. * core::f32::consts::PI * u2).cos();
+                a + b * z
+            }
+            BERNOULLI => (uniform_f32(x0) < a) as u32 as f32,
+            _ => a + (b - a) * uniform_f32(x0),
+        }
+    }
+
+    #[cfg(any(feature = "device", target_arch = "spirv"))]
+    #[kernel]
+    pub fn rand_f32<const DIST: u32>(
+        a: f32,
+        b: f32,
+        key0: u32,
+        key1: u32,
+        offset0: u32,
+        offset1: u32,
+        #[item] y: &mut f32,
+    ) {
+        *y = rand_impl(DIST, a, b, key0, key1, kernel.item_id, offset0, offset1);
+    }
+}
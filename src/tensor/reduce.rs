@@ -3,6 +3,7 @@ use super::*;
 use half::f16;
 #[cfg(feature = "device")]
 use krnl::macros::module;
+use num_traits::{Float, PrimInt};
 
 impl<T: Scalar, S: Data<Elem = T>, D: Dimension> TensorBase<S, D> {
     /// Sums the tensor.
@@ -39,11 +40,111 @@ impl<T: Scalar, S: Data<Elem = T>, D: Dimension> TensorBase<S, D> {
                 self.view().into_dyn().into(),
                 beta.into(),
                 output.view_mut().into_dyn().into(),
+                None,
             )
         }
     }
 }
 
+impl<T: Scalar + PrimInt, S: Data<Elem = T>, D: Dimension> TensorBase<S, D> {
+    /// Sums the tensor in blocks of `block_size` elements.
+    ///
+    /// On a device, first sums blocks of `block_size` elements in parallel, one per group, then
+    /// sums the partial sums, repeating until a single block remains. [`.sum()`](TensorBase::sum)
+    /// sums the tensor with a single group, which is faster for small tensors. The optimal block
+    /// size depends on the device and the size of the tensor. Same as `.sum()` on the host.
+    ///
+    /// Only integers are supported, so that the result is the same for any block size.
+    ///
+    /// **Errors**
+    /// - `block_size` is less than 2.
+    /// - The operation could not be executed on the device.
+    pub fn sum_blocked(&self, block_size: usize) -> Result<T> {
+        if block_size < 2 {
+            bail!("sum_blocked block_size {block_size} must be at least 2!");
+        }
+        if let Some(input) = self.as_array() {
+            return Ok(input.sum());
+        }
+        #[cfg(not(feature = "device"))]
+        {
+            unreachable!()
+        }
+        #[cfg(feature = "device")]
+        {
+            let mut output = unsafe { Tensor::uninit(self.device(), ())? };
+            sum(
+                self.view().into_dyn().into(),
+                T::default().into(),
+                output.view_mut().into_dyn().into(),
+                Some(block_size),
+            )?;
+            Ok(output.into_array()?.into_scalar())
+        }
+    }
+}
+
+impl<T: Scalar, S: Data<Elem = T>, D: Dimension> TensorBase<S, D> {
+    /// The mean of all elements.
+    ///
+    /// Accumulates in f32, ie to compute the normalization constants of a dataset.
+    ///
+    /// **Errors**
+    /// - The tensor is empty.
+    /// - The operation could not be executed on the device.
+    pub fn mean_all(&self) -> Result<f32> {
+        if self.is_empty() {
+            bail!("mean_all of an empty tensor!");
+        }
+        Ok(self.sum_f32()? / self.len() as f32)
+    }
+    /// The standard deviation of all elements.
+    ///
+    /// Computes the population standard deviation, ie `sqrt(mean((x - mean)^2))`, accumulating
+    /// in f32.
+    ///
+    /// **Errors**
+    /// - The tensor is empty.
+    /// - The operation could not be executed on the device.
+    pub fn std_all(&self) -> Result<f32> {
+        if self.is_empty() {
+            bail!("std_all of an empty tensor!");
+        }
+        let mean = self.mean_all()?;
+        let len = self.len() as f32;
+        if let Some(input) = self.as_array() {
+            let var = input
+                .iter()
+                .map(|x| {
+                    let x = x.cast::<f32>() - mean;
+                    x * x
+                })
+                .sum::<f32>()
+                / len;
+            return Ok(var.sqrt());
+        }
+        #[cfg(not(feature = "device"))]
+        {
+            unreachable!()
+        }
+        #[cfg(feature = "device")]
+        {
+            let x = self.as_standard_layout()?.cast::<f32>()?;
+            let mut y = unsafe { Tensor1::<f32>::uninit(self.device(), x.len())? };
+            kernels::centered_square_f32::builder()?
+                .build(self.device())?
+                .dispatch(mean, x.as_slice().unwrap(), y.as_slice_mut().unwrap())?;
+            Ok((y.sum()? / len).sqrt())
+        }
+    }
+    fn sum_f32(&self) -> Result<f32> {
+        if let Some(input) = self.as_array() {
+            return Ok(input.iter().map(|x| x.cast::<f32>()).sum());
+        }
+        self.as_standard_layout()?.cast::<f32>()?.sum()
+    }
+}
+
 impl<T: Scalar, S: Data<Elem = T>, D: RemoveAxis> TensorBase<S, D> {
     /// Sums the tensor along `axis`.
     pub fn sum_axis(&self, axis: Axis) -> Result<Tensor<T, D::Smaller>> {
@@ -62,6 +163,21 @@ impl<T: Scalar, S: Data<Elem = T>, D: RemoveAxis> TensorBase<S, D> {
             Ok(output)
         }
     }
+    /// Sums the tensor along `axis`, keeping the reduced axis with size 1.
+    ///
+    /// Like `keepdim` in NumPy and PyTorch, the output can be broadcasted against the tensor,
+    /// see [`.broadcast()`](TensorBase::broadcast).
+    ///
+    /// **Errors**
+    /// - See [`.sum_axis()`](TensorBase::sum_axis).
+    ///
+    /// **Panics**
+    /// - `axis` is out of bounds.
+    pub fn sum_axis_keepdim(&self, axis: Axis) -> Result<Tensor<T, D>> {
+        let mut dim = self.raw_dim();
+        dim[axis.0] = 1;
+        Ok(self.sum_axis(axis)?.into_shape(dim).unwrap())
+    }
     /// Sums the tensor along `axis` with `output`.
     pub fn sum_axis_with<S2: DataMut<Elem = T>>(
         &self,
@@ -98,8 +214,311 @@ impl<T: Scalar, S: Data<Elem = T>, D: RemoveAxis> TensorBase<S, D> {
     }
 }
 
+impl<T: Scalar + Float, S: Data<Elem = T>, D: RemoveAxis> TensorBase<S, D> {
+    /// The mean of the tensor along `axis`.
+    ///
+    /// **Errors**
+    /// - The length of `axis` is 0.
+    /// - The operation could not be executed on the device.
+    ///
+    /// **Panics**
+    /// - `axis` is out of bounds.
+    pub fn mean_axis(&self, axis: Axis) -> Result<Tensor<T, D::Smaller>> {
+        let len = self.shape()[axis.0];
+        if len == 0 {
+            bail!(
+                "mean_axis of empty axis {axis:?} with shape {:?}!",
+                self.shape()
+            );
+        }
+        self.sum_axis(axis)?
+            .scaled_cast((len as f32).recip().cast::<T>())
+    }
+    /// The mean of the tensor along `axis`, keeping the reduced axis with size 1.
+    ///
+    /// See [`.sum_axis_keepdim()`](TensorBase::sum_axis_keepdim).
+    ///
+    /// **Errors**
+    /// - See [`.mean_axis()`](TensorBase::mean_axis).
+    ///
+    /// **Panics**
+    /// - `axis` is out of bounds.
+    pub fn mean_axis_keepdim(&self, axis: Axis) -> Result<Tensor<T, D>> {
+        let mut dim = self.raw_dim();
+        dim[axis.0] = 1;
+        Ok(self.mean_axis(axis)?.into_shape(dim).unwrap())
+    }
+}
+
+impl<T: Scalar, S: Data<Elem = T>, D: RemoveAxis> TensorBase<S, D> {
+    /// The maximum of the tensor along `axis`.
+    ///
+    /// NaN is greater than all other values, see [`.topk()`](TensorBase::topk).
+    ///
+    /// **Errors**
+    /// - See [`.max_axis_keepdim()`](TensorBase::max_axis_keepdim).
+    ///
+    /// **Panics**
+    /// - `axis` is out of bounds.
+    pub fn max_axis(&self, axis: Axis) -> Result<Tensor<T, D::Smaller>> {
+        let dim = self.raw_dim().remove_axis(axis);
+        Ok(self.max_axis_keepdim(axis)?.into_shape(dim).unwrap())
+    }
+    /// The maximum of the tensor along `axis`, keeping the reduced axis with size 1.
+    ///
+    /// See [`.sum_axis_keepdim()`](TensorBase::sum_axis_keepdim).
+    ///
+    /// **Errors**
+    /// - The length of `axis` is 0.
+    /// - The operation could not be executed on the device.
+    ///
+    /// **Panics**
+    /// - `axis` is out of bounds.
+    pub fn max_axis_keepdim(&self, axis: Axis) -> Result<Tensor<T, D>> {
+        if self.shape()[axis.0] == 0 {
+            bail!(
+                "max_axis of empty axis {axis:?} with shape {:?}!",
+                self.shape()
+            );
+        }
+        Ok(self.topk(1, axis)?.0)
+    }
+}
+
+impl<T: Scalar, S: Data<Elem = T>, D: Dimension> TensorBase<S, D> {
+    /// Sums the tensor along `axes`.
+    ///
+    /// The reduced axes are removed, ie summing a `[n, c, h, w]` tensor along axes 0, 2 and 3
+    /// returns the sum of each channel with shape `[c]`.
+    ///
+    /// The reduced axes are moved to the end and summed in a single reduction, rather than
+    /// summing each axis with [`.sum_axis()`](TensorBase::sum_axis).
+    ///
+    /// **Errors**
+    /// - An axis is out of bounds or repeated.
+    /// - The operation could not be executed on the device.
+    pub fn sum_axes(&self, axes: &[Axis]) -> Result<TensorD<T>> {
+        let ndim = self.ndim();
+        let mut reduce = vec![false; ndim];
+        for axis in axes {
+            if axis.0 >= ndim || reduce[axis.0] {
+                bail!("sum_axes axes {axes:?} invalid for ndim {ndim}!");
+            }
+            reduce[axis.0] = true;
+        }
+        let (kept, reduced): (Vec<usize>, Vec<usize>) = (0..ndim).partition(|i| !reduce[*i]);
+        let output_shape: Vec<usize> = kept.iter().map(|i| self.shape()[*i]).collect();
+        let outer = output_shape.iter().product::<usize>();
+        let inner = reduced.iter().map(|i| self.shape()[*i]).product::<usize>();
+        let input = self
+            .view()
+            .into_dyn()
+            .permuted_axes(kept.into_iter().chain(reduced).collect::<Vec<_>>());
+        let input = input.as_standard_layout()?;
+        let output = input.view().into_shape([outer, inner])?.sum_axis(Axis(1))?;
+        Ok(output.into_shape(output_shape)?)
+    }
+}
+
+impl<T: Scalar, S: Data<Elem = T>, D: Dimension> TensorBase<S, D> {
+    /// Counts the elements of the tensor in `bins` evenly spaced bins.
+    ///
+    /// The `bins + 1` edges are `low + i * (high - low) / bins` for `i` in `0..=bins`, where
+    /// `(low, high) = range`, and bin `i` counts values in `[edge[i], edge[i + 1])`. Values
+    /// outside of `range`, including `high`, are counted in the first or last bin, NaN's are
+    /// ignored.
+    ///
+    /// **Errors**
+    /// - `bins` is 0 or `range` is empty.
+    /// - The operation could not be executed on the device.
+    pub fn histogram(&self, bins: usize, range: (T, T)) -> Result<Tensor1<u32>> {
+        let (low, high) = (range.0.cast::<f32>(), range.1.cast::<f32>());
+        if bins == 0 || low.is_nan() || high.is_nan() || low >= high {
+            bail!("histogram bins = {bins} range = ({low}, {high}) is empty!");
+        }
+        if let Some(input) = self.as_array() {
+            let mut output = vec![0u32; bins];
+            for x in input.iter().map(|x| x.cast::<f32>()) {
+                if !x.is_nan() {
+                    let bin = ((x - low) / (high - low) * bins as f32) as i64;
+                    output[bin.clamp(0, bins as i64 - 1) as usize] += 1;
+                }
+            }
+            return Ok(output.into());
+        }
+        #[cfg(not(feature = "device"))]
+        {
+            unreachable!()
+        }
+        #[cfg(feature = "device")]
+        {
+            let mut output = Tensor1::<u32>::zeros(self.device(), bins)?;
+            if self.is_empty() {
+                return Ok(output);
+            }
+            let input = ScalarTensorView::from(self.view());
+            let input = input.as_standard_layout()?;
+            let x = input.as_scalar_slice().unwrap();
+            macro_for!($T in [u8, i8, u16, i16, f16, bf16, u32, i32, f32, u64, i64, f64] {
+                if x.scalar_type() == $T::scalar_type() {
+                    let kernel = paste! {
+                        kernels::[<histogram_ $T>]::builder()?.build(self.device())?
+                    };
+                    kernel.dispatch(
+                        Slice::try_from(x).unwrap(),
+                        low,
+                        high,
+                        output.as_slice_mut().unwrap(),
+                    )?;
+                    return Ok(output);
+                }
+            });
+            unreachable!()
+        }
+    }
+}
+
+impl<S: Data<Elem = u8>, D: Dimension> TensorBase<S, D> {
+    /// Returns true if any element is nonzero.
+    ///
+    /// Returns false if the tensor is empty.
+    ///
+    /// **Errors**
+    /// - The operation could not be executed on the device.
+    pub fn any(&self) -> Result<bool> {
+        self.any_all(false)
+    }
+    /// Returns true if all elements are nonzero.
+    ///
+    /// Returns true if the tensor is empty.
+    ///
+    /// **Errors**
+    /// - The operation could not be executed on the device.
+    pub fn all(&self) -> Result<bool> {
+        self.any_all(true)
+    }
+    fn any_all(&self, all: bool) -> Result<bool> {
+        if let Some(input) = self.as_array() {
+            return Ok(if all {
+                input.iter().all(|x| *x != 0)
+            } else {
+                input.iter().any(|x| *x != 0)
+            });
+        }
+        #[cfg(not(feature = "device"))]
+        {
+            unreachable!()
+        }
+        #[cfg(feature = "device")]
+        {
+            let input = self.as_standard_layout()?;
+            let mut output = unsafe { Tensor::<u8, _>::uninit(self.device(), ())? };
+            any_all(
+                input.as_slice().unwrap(),
+                self.len(),
+                all,
+                output.as_slice_mut().unwrap(),
+            )?;
+            Ok(output.into_array()?.into_scalar() != 0)
+        }
+    }
+}
+
+impl<S: Data<Elem = u8>, D: RemoveAxis> TensorBase<S, D> {
+    /// Reduces the tensor along `axis`, 1 if any element is nonzero, 0 otherwise.
+    ///
+    /// Empty reductions are 0.
+    ///
+    /// **Errors**
+    /// - The operation could not be executed on the device.
+    ///
+    /// **Panics**
+    /// - `axis` is out of bounds.
+    pub fn any_axis(&self, axis: Axis) -> Result<Tensor<u8, D::Smaller>> {
+        self.any_all_axis(axis, false)
+    }
+    /// Reduces the tensor along `axis`, 1 if all elements are nonzero, 0 otherwise.
+    ///
+    /// Empty reductions are 1.
+    ///
+    /// **Errors**
+    /// - The operation could not be executed on the device.
+    ///
+    /// **Panics**
+    /// - `axis` is out of bounds.
+    pub fn all_axis(&self, axis: Axis) -> Result<Tensor<u8, D::Smaller>> {
+        self.any_all_axis(axis, true)
+    }
+    fn any_all_axis(&self, axis: Axis, all: bool) -> Result<Tensor<u8, D::Smaller>> {
+        if let Some(input) = self.as_array() {
+            return Ok(input
+                .map_axis(axis, |x| {
+                    if all {
+                        x.iter().all(|x| *x != 0) as u8
+                    } else {
+                        x.iter().any(|x| *x != 0) as u8
+                    }
+                })
+                .into());
+        }
+        #[cfg(not(feature = "device"))]
+        {
+            unreachable!()
+        }
+        #[cfg(feature = "device")]
+        {
+            let ndim = self.ndim();
+            assert!(
+                axis.0 < ndim,
+                "axis {} out of bounds for ndim {ndim}",
+                axis.0
+            );
+            // Move axis to the end so that each reduction is contiguous.
+            let mut axes = D::zeros(ndim);
+            for (u, i) in (0..ndim).filter(|i| *i != axis.0).enumerate() {
+                axes[u] = i;
+            }
+            axes[ndim - 1] = axis.0;
+            let input = self.view().permuted_axes(axes).into_standard_layout()?;
+            let mut output =
+                unsafe { Tensor::uninit(self.device(), self.raw_dim().remove_axis(axis))? };
+            if !output.is_empty() {
+                any_all(
+                    input.as_slice().unwrap(),
+                    self.shape()[axis.0],
+                    all,
+                    output.as_slice_mut().unwrap(),
+                )?;
+            }
+            Ok(output)
+        }
+    }
+}
+
 #[cfg(feature = "device")]
-fn sum(x: ScalarTensorViewD, beta: ScalarElem, mut y: ScalarTensorViewMutD) -> Result<()> {
+fn any_all(x: Slice<u8>, n: usize, all: bool, y: SliceMut<u8>) -> Result<()> {
+    let device = y.device();
+    let n = n.to_u32().unwrap();
+    if all {
+        kernels::any_all_u8::builder()?
+            .specialize(1)
+            .build(device)?
+            .dispatch(n, x, y)
+    } else {
+        kernels::any_all_u8::builder()?
+            .specialize(0)
+            .build(device)?
+            .dispatch(n, x, y)
+    }
+}
+
+#[cfg(feature = "device")]
+fn sum(
+    x: ScalarTensorViewD,
+    beta: ScalarElem,
+    mut y: ScalarTensorViewMutD,
+    block_size: Option<usize>,
+) -> Result<()> {
     if x.scalar_type() != y.scalar_type() {
         todo!();
     }
@@ -112,11 +531,12 @@ fn sum(x: ScalarTensorViewD, beta: ScalarElem, mut y: ScalarTensorViewMutD) -> R
     let groups: u32 = y.len() as u32;
     let threads = info.subgroup_threads();
 
-    let x = if x.is_contiguous() {
-        x.into()
-    } else {
-        x.as_standard_layout()?
-    };
+    let x = x.contiguous()?;
+    if let Some(block_size) = block_size {
+        if x.len() > block_size {
+            return sum_blocks(x.view(), block_size, beta, y);
+        }
+    }
     let x = x.as_scalar_slice().unwrap();
     let y = y.as_scalar_slice_mut().unwrap();
 
@@ -138,6 +558,36 @@ fn sum(x: ScalarTensorViewD, beta: ScalarElem, mut y: ScalarTensorViewMutD) -> R
     todo!()
 }
 
+// Sums each block of `block_size` elements of contiguous `x` and then sums the blocks.
+#[cfg(feature = "device")]
+fn sum_blocks(
+    x: ScalarTensorViewD,
+    block_size: usize,
+    beta: ScalarElem,
+    y: ScalarTensorViewMutD,
+) -> Result<()> {
+    let device = y.device();
+    let info = device.info().unwrap();
+    let threads = info.subgroup_threads();
+    let blocks = (x.len() + block_size - 1) / block_size;
+    let mut partial = unsafe { ScalarTensor::uninit(device.clone(), blocks, x.scalar_type())? };
+    macro_for!($T in [u8, i8, u16, i16, f16, bf16, u32, i32, f32, u64, i64, f64] {
+        if x.scalar_type() == $T::scalar_type() {
+            let x = Slice::try_from(x.as_scalar_slice().unwrap()).unwrap();
+            let partial = SliceMut::try_from(partial.as_scalar_slice_mut().unwrap()).unwrap();
+            let kernel = paste! {
+                kernels::[<sum_blocks_ $T>]::builder()?.with_threads(threads).build(device.clone())?
+            };
+            kernel.with_groups(blocks.to_u32().unwrap()).dispatch(
+                block_size.to_u32().unwrap(),
+                x,
+                partial,
+            )?;
+        }
+    });
+    sum(partial.view().into_dyn(), beta, y, Some(block_size))
+}
+
 #[cfg(feature = "device")]
 fn sum_axis(
     x: ScalarTensorViewD,
@@ -403,6 +853,10 @@ mod kernels {
         buffer::UnsafeIndex,
         half::{bf16, f16},
         scalar::Scalar,
+        spirv_std::{
+            arch::atomic_i_increment,
+            memory::{Scope, Semantics},
+        },
     };
     use paste::paste;
 
@@ -600,6 +1054,42 @@ mod kernels {
                     }
                 }
 
+                #[kernel]
+                pub fn [<sum_blocks_ $t>](
+                    block_size: u32,
+                    #[global] x: Slice<$t>,
+                    #[global] y: UnsafeSlice<$t>,
+                ) {
+                    type T = $t;
+                    type A = $a;
+                    let group_id = kernel.group_id as usize;
+                    let thread_id = kernel.thread_id as usize;
+                    let subgroup_id = kernel.subgroup_id as usize;
+                    if subgroup_id > 0 {
+                        return;
+                    }
+                    let subgroup_threads = (kernel.threads / kernel.subgroups) as usize;
+                    let start = group_id * block_size as usize;
+                    let end = (start + block_size as usize).min(x.len());
+                    let mut y_thread = A::default();
+                    let mut idx = start;
+                    while idx < end {
+                        let x_idx = idx + thread_id;
+                        if x_idx < end {
+                            y_thread += x[x_idx].cast::<A>();
+                        }
+                        idx += subgroup_threads;
+                    }
+                    unsafe {
+                        y_thread = y_thread.subgroup_add();
+                    };
+                    if thread_id == 0 {
+                        unsafe {
+                            *y.unsafe_index_mut(group_id) = y_thread.cast::<T>();
+                        }
+                    }
+                }
+
                 #[kernel]
                 pub fn [<sum_axis2_ $t>]<const AXIS: u32>(
                     d0: u32,
@@ -811,4 +1301,64 @@ mod kernels {
     impl_sum!(i8, i16 => i32);
     impl_sum!(f16, bf16 => f32);
     impl_sum!(u32, i32, f32, u64, i64, f64);
+
+    // Each item counts one element, incrementing its bin atomically.
+    dry::macro_for!($T in [u8, i8, u16, i16, f16, bf16, u32, i32, f32, u64, i64, f64] {
+        paste! {
+            #[kernel]
+            pub fn [<histogram_ $T>](
+                #[item] x: $T,
+                low: f32,
+                high: f32,
+                #[global] y: UnsafeSlice<u32>,
+            ) {
+                let x = x.cast::<f32>();
+                #[allow(clippy::eq_op)]
+                if x != x {
+                    return;
+                }
+                let bins = y.len() as i32;
+                let bin = ((x - low) / (high - low) * bins as f32) as i32;
+                let bin = if bin < 0 {
+                    0
+                } else if bin >= bins {
+                    bins - 1
+                } else {
+                    bin
+                };
+                // Device scope requires the VulkanMemoryModelDeviceScope capability, the
+                // items of a dispatch are within one queue family.
+                unsafe {
+                    atomic_i_increment::<u32, { Scope::QueueFamily as u32 }, { Semantics::NONE.bits() }>(
+                        y.unsafe_index_mut(bin as usize),
+                    );
+                }
+            }
+        }
+    });
+
+    #[kernel]
+    pub fn centered_square_f32(mean: f32, #[item] x: f32, #[item] y: &mut f32) {
+        let x = x - mean;
+        *y = x * x;
+    }
+
+    // Each item reduces `n` contiguous elements.
+    #[kernel]
+    pub fn any_all_u8<const ALL: u32>(n: u32, #[global] x: Slice<u8>, #[item] y: &mut u8) {
+        let all = ALL == 1;
+        let start = kernel.item_id as usize * n as usize;
+        let mut acc = all;
+        let mut idx = 0;
+        while idx < n as usize {
+            let nonzero = x[start + idx] != 0;
+            if all {
+                acc &= nonzero;
+            } else {
+                acc |= nonzero;
+            }
+            idx += 1;
+        }
+        *y = if acc { 1 } else { 0 };
+    }
 }
@@ -0,0 +1,7 @@
+#![cfg(all(feature = "neural-network", not(target_arch = "wasm32")))]
+
+#[test]
+fn derive() {
+    let tests = trybuild::TestCases::new();
+    tests.pass("tests/ui/forward_tuple.rs");
+}
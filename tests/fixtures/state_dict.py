@@ -0,0 +1,159 @@
+"""Writes state_dict.pt and batch_norm.pt, PyTorch state dicts used by the pytorch tests.
+
+The archive has the same format as `torch.save(model.state_dict(), "state_dict.pt")`
+for the model below, without requiring torch:
+
+    class Network(nn.Module):
+        def __init__(self):
+            super().__init__()
+            self.conv = nn.Conv2d(1, 2, 3)
+            self.pool = nn.MaxPool2d(2)
+            self.dense = nn.Linear(8, 3)
+
+        def forward(self, x):
+            x = self.pool(F.relu(self.conv(x)))
+            return self.dense(x.flatten(1))
+
+The conv weight and bias and the dense weight are stored as f32, and the dense bias as f16.
+The "input" and "output" entries hold a test input and the expected output of the model.
+
+batch_norm.pt is the state dict of a Conv2d followed by a BatchNorm2d, which has the
+running_mean and running_var buffers and an int64 num_batches_tracked:
+
+    class Network(nn.Module):
+        def __init__(self):
+            super().__init__()
+            self.conv = nn.Conv2d(1, 2, 3)
+            self.norm = nn.BatchNorm2d(2)
+"""
+
+import collections
+import io
+import pickle
+import struct
+import sys
+import types
+import zipfile
+
+
+class Storage:
+    def __init__(self, name, fmt, data):
+        self.name = name
+        self.fmt = fmt
+        self.data = data
+
+
+class Tensor:
+    def __init__(self, storage, shape):
+        self.storage = storage
+        self.shape = shape
+
+    def __reduce__(self):
+        strides = []
+        stride = 1
+        for dim in reversed(self.shape):
+            strides.insert(0, stride)
+            stride *= dim
+        return (
+            _rebuild_tensor_v2,
+            (self.storage, 0, tuple(self.shape), tuple(strides), False, collections.OrderedDict()),
+        )
+
+
+def _rebuild_tensor_v2(*args):
+    raise NotImplementedError
+
+
+torch = types.ModuleType("torch")
+torch_utils = types.ModuleType("torch._utils")
+torch_utils._rebuild_tensor_v2 = _rebuild_tensor_v2
+_rebuild_tensor_v2.__module__ = "torch._utils"
+torch._utils = torch_utils
+STORAGE_FORMATS = {"FloatStorage": "<f", "HalfStorage": "<e", "LongStorage": "<q"}
+for name in STORAGE_FORMATS:
+    setattr(torch, name, type(name, (), {"__module__": "torch"}))
+sys.modules["torch"] = torch
+sys.modules["torch._utils"] = torch_utils
+
+
+def tensor(shape, values, storage="FloatStorage"):
+    return Tensor(Storage(storage, STORAGE_FORMATS[storage], values), shape)
+
+
+def values(n, scale):
+    return [((i * 5) % 7 - 3) * scale for i in range(n)]
+
+
+conv_weight = values(2 * 1 * 3 * 3, 0.125)
+conv_bias = [0.25, -0.5]
+dense_weight = values(3 * 8, 0.0625)
+dense_bias = [0.5, -0.25, 0.125]
+x = [((i * 3) % 11 - 5) / 4 for i in range(6 * 6)]
+
+
+def forward(x):
+    conv = [[[0.0] * 4 for _ in range(4)] for _ in range(2)]
+    for o in range(2):
+        for i in range(4):
+            for j in range(4):
+                y = conv_bias[o]
+                for a in range(3):
+                    for b in range(3):
+                        y += conv_weight[o * 9 + a * 3 + b] * x[(i + a) * 6 + j + b]
+                conv[o][i][j] = max(y, 0.0)
+    flat = []
+    for o in range(2):
+        for i in range(2):
+            for j in range(2):
+                flat.append(max(conv[o][2 * i + a][2 * j + b] for a in range(2) for b in range(2)))
+    return [
+        dense_bias[k] + sum(dense_weight[k * 8 + i] * flat[i] for i in range(8))
+        for k in range(3)
+    ]
+
+
+state_dict = collections.OrderedDict()
+state_dict["conv.weight"] = tensor([2, 1, 3, 3], conv_weight)
+state_dict["conv.bias"] = tensor([2], conv_bias)
+state_dict["dense.weight"] = tensor([3, 8], dense_weight)
+state_dict["dense.bias"] = tensor([3], dense_bias, "HalfStorage")
+state_dict["input"] = tensor([1, 1, 6, 6], x)
+state_dict["output"] = tensor([1, 3], forward(x))
+
+batch_norm = collections.OrderedDict()
+batch_norm["conv.weight"] = tensor([2, 1, 3, 3], conv_weight)
+batch_norm["conv.bias"] = tensor([2], conv_bias)
+batch_norm["norm.weight"] = tensor([2], [1.5, 0.5])
+batch_norm["norm.bias"] = tensor([2], [-0.25, 0.75])
+batch_norm["norm.running_mean"] = tensor([2], [0.125, -0.375])
+batch_norm["norm.running_var"] = tensor([2], [0.5, 2.0])
+batch_norm["norm.num_batches_tracked"] = tensor([], [10], "LongStorage")
+
+
+def save(path, state_dict):
+    """Saves `state_dict` like `torch.save`, in an archive with the name of the file."""
+    archive_name = path.removesuffix(".pt")
+    storages = []
+
+    class Pickler(pickle.Pickler):
+        def persistent_id(self, obj):
+            if isinstance(obj, Storage):
+                key = str(len(storages))
+                storages.append((key, obj))
+                return ("storage", getattr(torch, obj.name), key, "cpu", len(obj.data))
+            return None
+
+    buffer = io.BytesIO()
+    Pickler(buffer, protocol=2).dump(state_dict)
+
+    with zipfile.ZipFile(path, "w", zipfile.ZIP_STORED) as archive:
+        archive.writestr(f"{archive_name}/data.pkl", buffer.getvalue())
+        archive.writestr(f"{archive_name}/byteorder", "little")
+        for key, storage in storages:
+            data = b"".join(struct.pack(storage.fmt, x) for x in storage.data)
+            archive.writestr(f"{archive_name}/data/{key}", data)
+        archive.writestr(f"{archive_name}/version", "3\n")
+
+
+save("state_dict.pt", state_dict)
+save("batch_norm.pt", batch_norm)
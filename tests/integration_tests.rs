@@ -220,6 +220,7 @@ fn tensor_tests(device: &Device) -> Vec<Trial> {
             Ok(())
         }),
     ]);
+    tests.push(device_test(device, "tensor_to_vec", tensor_to_vec));
     tests.extend(
         linalg::linalg_tests(device)
             .into_iter()
@@ -227,6 +228,8 @@ fn tensor_tests(device: &Device) -> Vec<Trial> {
             .chain(reduce::reduce_tests(device))
             .chain(ops::ops_tests(device)),
     );
+    #[cfg(feature = "dataset")]
+    tests.extend(dataset::dataset_tests(device));
     #[cfg(feature = "learn")]
     tests.extend(learn::learn_tests(device));
     tests
@@ -239,11 +242,35 @@ fn tensor_from_array<D: Dimension>(x: Array<u32, D>) {
     assert_eq!(x.t(), y_t.as_array().unwrap());
 }
 
+fn tensor_to_vec(device: &Device) {
+    let x_vec: Vec<u32> = (0..12).collect();
+    let x = Tensor::from(x_vec.clone())
+        .into_shape([3, 4])
+        .unwrap()
+        .into_device(device.clone())
+        .unwrap();
+    assert_eq!(x.to_vec().unwrap(), x_vec);
+    let x_array = Array::from_shape_vec([3, 4], x_vec.clone()).unwrap();
+    assert_eq!(
+        x.t().to_vec().unwrap(),
+        x_array.t().iter().copied().collect::<Vec<_>>()
+    );
+    let x_scalar = ScalarTensorViewD::from(x.view().into_dyn());
+    assert_eq!(
+        x_scalar.to_vec_scalar().unwrap(),
+        x_vec
+            .iter()
+            .map(|x| ScalarElem::U32(*x))
+            .collect::<Vec<_>>()
+    );
+    assert_eq!(x.into_vec().unwrap(), x_vec);
+}
+
 mod linalg {
     use super::*;
     use approx::assert_relative_eq;
     use autograph::tensor::CowTensor;
-    use ndarray::{linalg::Dot, Array2};
+    use ndarray::{linalg::Dot, s, Array2};
     use std::fmt::{self, Display};
 
     #[cfg(not(target_arch = "wasm32"))]
@@ -270,6 +297,8 @@ mod linalg {
                 }
             }
         });
+        tests.push(device_test(device, "tensor_bmm_u32", tensor_bmm::<u32>));
+        tests.push(device_test(device, "tensor_bmm_f32", tensor_bmm::<f32>));
         tests
     }
 
@@ -355,6 +384,34 @@ mod linalg {
             assert_eq!(a_out, a_true);
         }
     }
+
+    fn tensor_bmm<T: Scalar>(device: &Device) {
+        let [batch, m, k, n] = [3, 5, 7, 19];
+        let a = gen_array::<T>([batch * m, k])
+            .into_shape([batch, m, k])
+            .unwrap();
+        let b = gen_array::<T>([batch * n, k])
+            .into_shape([batch, n, k])
+            .unwrap();
+        let ta = Tensor::from(a.clone()).into_device(device.clone()).unwrap();
+        let tb = Tensor::from(b.clone()).into_device(device.clone()).unwrap();
+        // Transposed rhs, and rhs broadcasted along the batch axis.
+        let b = b.permuted_axes([0, 2, 1]);
+        let tb = tb.permuted_axes([0, 2, 1]);
+        let b_slice = b.slice(s![1..2, .., ..]);
+        let tb_slice = tb.slice(s![1..2, .., ..]);
+        let b_expanded = b_slice.broadcast([batch, k, n]).unwrap();
+        let tb_expanded = tb_slice.expand([batch, k, n]).unwrap();
+        for (b, tb) in [(b.view(), tb.view()), (b_expanded, tb_expanded)] {
+            let c_true = ndarray::Array3::from_shape_fn([batch, m, n], |(i, r, c)| {
+                a.index_axis(Axis(0), i)
+                    .row(r)
+                    .dot(&b.index_axis(Axis(0), i).column(c))
+            });
+            let c_out = ta.bmm(&tb).unwrap().into_array().unwrap();
+            assert_eq!(c_out, c_true);
+        }
+    }
 }
 
 #[cfg(not(target_arch = "wasm32"))]
@@ -385,6 +442,10 @@ mod ops {
                     scaled_add::<$T>(device, &[21, 14]);
                 }).with_ignored_flag(ignore)
             );
+            tests.push(
+                device_test(device, &format!("eye_diag_{ty}"), eye_diag::<$T>)
+                    .with_ignored_flag(ignore)
+            );
         });
         macro_for!($X in [u8, u16, u32, u64] {
             let x_ty = $X::scalar_type();
@@ -402,9 +463,454 @@ mod ops {
                 }).with_ignored_flag(ignore));
             });
         });
+        macro_for!($T in [f16, bf16, f32] {
+            let scalar_type = $T::scalar_type();
+            let ignore = device.is_device()
+                && (!features.contains(&features_for_scalar(scalar_type))
+                    || !features.contains(&features_for_scalar(ScalarType::U8)));
+            tests.push(
+                device_test(device, &format!("is_finite_{}", scalar_type.name()), is_finite::<$T>)
+                    .with_ignored_flag(ignore),
+            );
+        });
+        macro_for!($T in [u8, i8, u16, i16, f16, bf16, u32, i32, f32, u64, i64, f64] {
+            let scalar_type = $T::scalar_type();
+            let ignore = device.is_device()
+                && (!features.contains(&features_for_scalar(scalar_type))
+                    || !features.contains(&features_for_scalar(ScalarType::U8)));
+            tests.push(
+                device_test(device, &format!("masked_select_{}", scalar_type.name()), masked_select::<$T>)
+                    .with_ignored_flag(ignore),
+            );
+        });
+        tests.push(device_test(device, "device_wait", device_wait));
+        tests.push(device_test(device, "roll_f32", roll_f32));
+        tests.push(device_test(device, "flip_f32", flip_f32));
+        tests.push(device_test(device, "sort_topk_f32", sort_topk_f32));
+        tests.push(device_test(device, "rand_f32", rand_f32));
+        macro_for!($T in [bf16, f32] {
+            let scalar_type = $T::scalar_type();
+            let ignore = device.is_device() &&
+                !features.contains(&features_for_scalar(scalar_type));
+            let ty = scalar_type.name();
+            tests.push(
+                device_test(device, &format!("rand_constructors_{ty}"), rand_constructors::<$T>)
+                    .with_ignored_flag(ignore),
+            );
+        });
+        tests.push(device_test(device, "slice_f32", slice_f32));
+        tests.push(device_test(device, "index_select_f32", index_select_f32));
+        tests.push(device_test(device, "scatter_add_f32", scatter_add_f32));
+        tests.push(device_test(device, "slice_mut_fill", slice_mut_fill));
+        tests.push(device_test(
+            device,
+            "contiguous_transposed",
+            contiguous_transposed,
+        ));
         tests
     }
 
+    fn eye_diag<T: Scalar>(device: &Device) {
+        use autograph::tensor::{ScalarTensor2, Tensor2};
+        use ndarray::Array2;
+
+        for (rows, cols) in [(0, 0), (1, 1), (4, 4), (3, 5), (70, 20)] {
+            let y_array =
+                Array2::from_shape_fn(
+                    [rows, cols],
+                    |(i, j)| {
+                        if i == j {
+                            T::one()
+                        } else {
+                            T::default()
+                        }
+                    },
+                );
+            let y = Tensor2::<T>::eye_rect(device.clone(), rows, cols).unwrap();
+            assert_eq!(y.into_array().unwrap(), y_array);
+            let y = ScalarTensor2::eye(device.clone(), rows, T::scalar_type())
+                .unwrap()
+                .try_into_tensor::<T>()
+                .unwrap();
+            assert_eq!(y.into_array().unwrap(), Array2::eye(rows));
+            let x_array = Array::from_shape_fn([rows, cols], |(i, j)| {
+                T::from_usize((i * cols + j) % 100).unwrap()
+            });
+            let x = Tensor::from(x_array.clone())
+                .into_device(device.clone())
+                .unwrap();
+            let d = x.diagonal().unwrap();
+            assert_eq!(d.to_vec().unwrap(), x_array.diag().to_vec());
+            let d_t = x.t().diagonal().unwrap();
+            assert_eq!(d_t.to_vec().unwrap(), x_array.t().diag().to_vec());
+            let y = Tensor2::diag(&d).unwrap();
+            assert_eq!(y.into_array().unwrap(), Array2::from_diag(&x_array.diag()));
+        }
+    }
+
+    fn device_wait(device: &Device) {
+        let n = 1 << 22;
+        let x = Tensor::<f32, _>::ones(device.clone(), n).unwrap();
+        let mut y = Tensor::<f32, _>::zeros(device.clone(), n).unwrap();
+        for _ in 0..4 {
+            y.scaled_add(1f32, &x).unwrap();
+        }
+        device.wait().unwrap();
+        let y = y.into_array().unwrap();
+        assert!(y.iter().all(|y| *y == 4f32));
+    }
+
+    fn slice_mut_fill(device: &Device) {
+        use autograph::tensor::ScalarTensor;
+        use krnl::buffer::SliceMut;
+
+        // An operation on the slice of a tensor. krnlc only compiles kernels in library crates,
+        // so the device path uses the fill kernel of krnl instead of a kernel defined here.
+        fn fill(mut y: SliceMut<f32>, value: f32) {
+            if let Some(y) = y.as_host_slice_mut() {
+                y.iter_mut().for_each(|y| *y = value);
+            } else {
+                y.fill(value).unwrap();
+            }
+        }
+
+        let mut x = Tensor::<f32, _>::zeros(device.clone(), [3, 4]).unwrap();
+        fill(x.index_axis_mut(Axis(0), 1).as_slice_mut().unwrap(), 1.);
+        assert!(x.t().as_slice().is_none());
+        let mut x = ScalarTensor::from(x);
+        let mut y = x.index_axis_mut(Axis(0), 2);
+        let y = y.as_scalar_slice_mut().unwrap().try_into().unwrap();
+        fill(y, 2.);
+        let x = x.try_into_tensor::<f32>().unwrap().into_array().unwrap();
+        let mut x_true = Array::zeros([3, 4]);
+        x_true.row_mut(1).fill(1f32);
+        x_true.row_mut(2).fill(2f32);
+        assert_eq!(x, x_true);
+    }
+
+    fn contiguous_transposed(device: &Device) {
+        let x_array = Array::from_iter((0..12).map(|x| x as f32))
+            .into_shape([3, 4])
+            .unwrap();
+        let x = Tensor::from(x_array.clone())
+            .into_device(device.clone())
+            .unwrap();
+        assert!(x.contiguous().unwrap().is_standard_layout());
+        let x_t = x.t();
+        assert!(x_t.as_slice().is_none());
+        let y = x_t.contiguous().unwrap();
+        assert!(y.is_standard_layout());
+        assert!(y.as_slice().is_some());
+        assert_eq!(y.into_array().unwrap(), x_array.t());
+        assert_eq!(x_t.sum().unwrap(), x_array.sum());
+    }
+
+    fn slice_f32(device: &Device) {
+        use ndarray::s;
+
+        let x_array = Array::from_iter((0..120).map(|x| x as f32))
+            .into_shape([4, 5, 6])
+            .unwrap();
+        let x = Tensor::from(x_array.clone())
+            .into_device(device.clone())
+            .unwrap();
+        macro_rules! check_slice {
+            ($($info:tt)*) => {{
+                let y_array = x_array.slice(s![$($info)*]);
+                let y = x.slice(s![$($info)*]);
+                assert_eq!(y.shape(), y_array.shape());
+                assert_eq!(
+                    y.contiguous().unwrap().into_array().unwrap(),
+                    y_array,
+                    stringify!($($info)*)
+                );
+                assert_eq!(y.sum().unwrap(), y_array.sum());
+            }};
+        }
+        check_slice!(.., .., ..);
+        check_slice!(1..3, .., 2..);
+        check_slice!(.., 1..;2, -3..);
+        check_slice!(2, .., ..;4);
+        check_slice!(.., -1, ndarray::NewAxis, 1..5;3);
+        check_slice!(1..1, .., ..);
+        let mut x = x;
+        x.slice_mut(s![1, .., ..])
+            .assign(&Tensor::<f32, _>::zeros(device.clone(), [5, 6]).unwrap())
+            .unwrap();
+        let mut y_array = x_array.clone();
+        y_array.slice_mut(s![1, .., ..]).fill(0.);
+        assert_eq!(x.into_array().unwrap(), y_array);
+    }
+
+    fn index_select_f32(device: &Device) {
+        let shape = [3, 5, 4];
+        let x_array = Array::from_iter((0..60).map(|x| x as f32))
+            .into_shape(shape)
+            .unwrap();
+        let x = Tensor::from(x_array.clone())
+            .into_device(device.clone())
+            .unwrap();
+        let indices_vec = vec![2u32, 0, 2, 1];
+        let indices = Tensor::from(indices_vec.clone())
+            .into_device(device.clone())
+            .unwrap();
+        for axis in 0..3 {
+            let mut y_shape = shape;
+            y_shape[axis] = indices_vec.len();
+            let y_array = Array::from_shape_fn(y_shape, |index| {
+                let mut index: [usize; 3] = index.into();
+                index[axis] = indices_vec[index[axis]] as usize;
+                x_array[index]
+            });
+            let y = x
+                .index_select(Axis(axis), indices.view())
+                .unwrap()
+                .into_array()
+                .unwrap();
+            assert_eq!(y, y_array, "axis: {axis}");
+        }
+        if device.is_host() {
+            let indices = Tensor::from(vec![3u32]);
+            assert!(x.index_select(Axis(0), indices.view()).is_err());
+        }
+    }
+
+    fn scatter_add_f32(device: &Device) {
+        let shape = [3, 4, 2];
+        let indices_vec = vec![1u32, 3, 1, 0, 1];
+        let indices = Tensor::from(indices_vec.clone())
+            .into_device(device.clone())
+            .unwrap();
+        for axis in 0..3 {
+            let mut src_shape = shape;
+            src_shape[axis] = indices_vec.len();
+            let src_array = Array::from_shape_fn(src_shape, |(i, j, k)| (i * 8 + j * 2 + k) as f32);
+            let src = Tensor::from(src_array.clone())
+                .into_device(device.clone())
+                .unwrap();
+            let mut y_shape = shape;
+            y_shape[axis] = 4;
+            let mut y_array = Array::from_elem(y_shape, 1f32);
+            for (i, index) in indices_vec.iter().copied().enumerate() {
+                let src = src_array.index_axis(Axis(axis), i);
+                y_array
+                    .index_axis_mut(Axis(axis), index as usize)
+                    .zip_mut_with(&src, |y, x| *y += *x);
+            }
+            let mut y = Tensor::from(Array::from_elem(y_shape, 1f32))
+                .into_device(device.clone())
+                .unwrap();
+            y.scatter_add(Axis(axis), indices.view(), &src).unwrap();
+            assert_eq!(y.into_array().unwrap(), y_array, "axis: {axis}");
+        }
+    }
+
+    fn is_finite<T: Scalar>(device: &Device) {
+        let mut x_vec: Vec<T> = (0..100).map(|x| T::from_i32(x - 50).unwrap()).collect();
+        let x = Tensor::from(x_vec.clone())
+            .into_device(device.clone())
+            .unwrap();
+        assert!(x.is_finite().unwrap().all().unwrap());
+        assert!(!x.has_nan_or_inf().unwrap());
+        for (index, value) in [(3, f32::NAN), (50, f32::INFINITY), (99, f32::NEG_INFINITY)] {
+            x_vec[index] = T::from_f32(value).unwrap();
+        }
+        let y_array: Vec<u8> = (0..100)
+            .map(|index| (![3, 50, 99].contains(&index)) as u8)
+            .collect();
+        let x = Tensor::from(x_vec).into_device(device.clone()).unwrap();
+        let y = x.is_finite().unwrap().into_array().unwrap().into_raw_vec();
+        assert_eq!(y, y_array);
+        assert!(x.has_nan_or_inf().unwrap());
+    }
+
+    fn masked_select<T: Scalar>(device: &Device) {
+        for (shape, n) in [([0, 3], 0), ([3, 5], 15), ([7, 100], 700)] {
+            let x_array = Array::from_iter((0..n).map(|x| T::from_usize(x % 100).unwrap()))
+                .into_shape(shape)
+                .unwrap();
+            let mask_array = Array::from_iter((0..n).map(|x| (x % 3 == 0 || x % 7 == 1) as u8))
+                .into_shape(shape)
+                .unwrap();
+            let x = Tensor::from(x_array.clone())
+                .into_device(device.clone())
+                .unwrap();
+            let mask = Tensor::from(mask_array.clone())
+                .into_device(device.clone())
+                .unwrap();
+            let y_array: Vec<T> = x_array
+                .iter()
+                .zip(mask_array.iter())
+                .filter(|(_, mask)| **mask != 0)
+                .map(|(x, _)| *x)
+                .collect();
+            let y = x.masked_select(mask.view()).unwrap().into_vec().unwrap();
+            assert_eq!(y, y_array, "{shape:?}");
+            let y_t = x.t().masked_select(mask.t()).unwrap().into_vec().unwrap();
+            let y_t_array: Vec<T> = x_array
+                .t()
+                .iter()
+                .zip(mask_array.t().iter())
+                .filter(|(_, mask)| **mask != 0)
+                .map(|(x, _)| *x)
+                .collect();
+            assert_eq!(y_t, y_t_array, "{shape:?}");
+        }
+        let mask = Tensor::<u8, _>::zeros(device.clone(), [2, 2]).unwrap();
+        let x = Tensor::<T, _>::zeros(device.clone(), [2, 3]).unwrap();
+        assert!(x.masked_select(mask.view()).is_err());
+    }
+
+    fn roll_f32(device: &Device) {
+        let shape = [3, 5, 4];
+        let x_array = Array::from_iter((0..60).map(|x| x as f32))
+            .into_shape(shape)
+            .unwrap();
+        let x = Tensor::from(x_array.clone())
+            .into_device(device.clone())
+            .unwrap();
+        for axis in 0..3 {
+            for shift in [-7, -1, 0, 2, 9] {
+                let len = shape[axis] as isize;
+                let y_array = Array::from_shape_fn(shape, |index| {
+                    let mut index: [usize; 3] = index.into();
+                    index[axis] = (index[axis] as isize - shift).rem_euclid(len) as usize;
+                    x_array[index]
+                });
+                let y = x.roll(shift, Axis(axis)).unwrap().into_array().unwrap();
+                assert_eq!(y, y_array, "axis: {axis} shift: {shift}");
+            }
+        }
+    }
+
+    fn flip_f32(device: &Device) {
+        let shape = [3, 5, 4];
+        let x_array = Array::from_iter((0..60).map(|x| x as f32))
+            .into_shape(shape)
+            .unwrap();
+        let x = Tensor::from(x_array.clone())
+            .into_device(device.clone())
+            .unwrap();
+        for axes in [&[0][..], &[1], &[2], &[0, 2], &[0, 1, 2]] {
+            let y_array = Array::from_shape_fn(shape, |index| {
+                let mut index: [usize; 3] = index.into();
+                for axis in axes.iter().copied() {
+                    index[axis] = shape[axis] - 1 - index[axis];
+                }
+                x_array[index]
+            });
+            let axes_vec: Vec<Axis> = axes.iter().copied().map(Axis).collect();
+            let y = x.flip(&axes_vec).unwrap().into_array().unwrap();
+            assert_eq!(y, y_array, "axes: {axes:?}");
+        }
+    }
+
+    fn sort_topk_f32(device: &Device) {
+        let shape = [3, 7, 4];
+        // Values repeat so that ties are ordered by index.
+        let x_array = Array::from_iter((0..84).map(|x| ((x * 5) % 7) as f32))
+            .into_shape(shape)
+            .unwrap();
+        let x = Tensor::from(x_array.clone())
+            .into_device(device.clone())
+            .unwrap();
+        for axis in 0..3 {
+            let len = shape[axis];
+            for (k, descending) in [(len, false), (len, true), (2, true), (0, true)] {
+                let (values, indices) = if descending {
+                    x.topk(k, Axis(axis)).unwrap()
+                } else {
+                    x.sort(Axis(axis)).unwrap()
+                };
+                let values = values.into_array().unwrap();
+                let indices = indices.into_array().unwrap();
+                let mut dim = x_array.raw_dim();
+                dim[axis] = k;
+                assert_eq!(values.raw_dim(), dim);
+                assert_eq!(indices.raw_dim(), dim);
+                for ((x, values), indices) in x_array
+                    .lanes(Axis(axis))
+                    .into_iter()
+                    .zip(values.lanes(Axis(axis)))
+                    .zip(indices.lanes(Axis(axis)))
+                {
+                    let mut order: Vec<u32> = (0..len as u32).collect();
+                    order.sort_by(|a, b| {
+                        let (a, b) = (x[*a as usize], x[*b as usize]);
+                        if descending {
+                            b.partial_cmp(&a).unwrap()
+                        } else {
+                            a.partial_cmp(&b).unwrap()
+                        }
+                    });
+                    order.truncate(k);
+                    let expected: Vec<f32> = order.iter().map(|i| x[*i as usize]).collect();
+                    assert_eq!(values.to_vec(), expected, "axis: {axis} k: {k}");
+                    assert_eq!(indices.to_vec(), order, "axis: {axis} k: {k}");
+                }
+            }
+        }
+        assert!(x.topk(8, Axis(1)).is_err());
+    }
+
+    fn rand_f32(device: &Device) {
+        use autograph::tensor::random::{DeviceRng, Distribution};
+
+        let n = 100_000;
+        for dist in [Distribution::Uniform, Distribution::Normal] {
+            let mut rng = device.rng(7);
+            let x1 = Tensor::<f32, _>::rand(&mut rng, n, dist)
+                .unwrap()
+                .into_array()
+                .unwrap();
+            let x2 = Tensor::<f32, _>::rand(&mut rng, n, dist)
+                .unwrap()
+                .into_array()
+                .unwrap();
+            assert_ne!(x1, x2, "{dist:?}");
+            let y1 = Tensor::<f32, _>::rand(&mut device.rng(7), n, dist)
+                .unwrap()
+                .into_array()
+                .unwrap();
+            assert_eq!(x1, y1, "{dist:?}");
+            let mean = x1.mean().unwrap();
+            let std = x1.std(0.);
+            let (mean_expected, std_expected) = match dist {
+                Distribution::Uniform => {
+                    assert!(x1.iter().all(|x| (0. ..1.).contains(x)));
+                    (0.5, (1f32 / 12.).sqrt())
+                }
+                Distribution::Normal => (0., 1.),
+            };
+            assert!((mean - mean_expected).abs() < 0.02, "{dist:?} mean: {mean}");
+            assert!((std - std_expected).abs() < 0.02, "{dist:?} std: {std}");
+        }
+    }
+
+    fn rand_constructors<T: Scalar>(device: &Device) {
+        use autograph::tensor::{random::DeviceRng, Tensor1};
+
+        let n = 100_000;
+        let moments = |x: Tensor1<T>| {
+            let x = x.cast::<f32>().unwrap().into_array().unwrap();
+            (x.mean().unwrap(), x.std(0.), x)
+        };
+        let mut rng = device.rng(11);
+        let (mean, std, x) = moments(Tensor::randn(&mut rng, n, 2., 3.).unwrap());
+        assert!((mean - 2.).abs() < 0.05, "randn mean: {mean}");
+        assert!((std - 3.).abs() < 0.05, "randn std: {std}");
+        let (_, _, y) = moments(Tensor::randn(&mut device.rng(11), n, 2., 3.).unwrap());
+        assert_eq!(x, y);
+        let (mean, std, x) = moments(Tensor::rand_uniform(&mut rng, n, -1., 3.).unwrap());
+        assert!(x.iter().all(|x| (-1. ..=3.).contains(x)));
+        assert!((mean - 1.).abs() < 0.05, "rand_uniform mean: {mean}");
+        let std_expected = 4. / 12f32.sqrt();
+        assert!((std - std_expected).abs() < 0.05, "rand_uniform std: {std}");
+        let (mean, _, x) = moments(Tensor::bernoulli(&mut rng, n, 0.3).unwrap());
+        assert!(x.iter().all(|x| *x == 0. || *x == 1.));
+        assert!((mean - 0.3).abs() < 0.01, "bernoulli mean: {mean}");
+    }
+
     fn scaled_add<T: Scalar>(device: &Device, shape: &[usize]) {
         let alpha = T::from_u32(2).unwrap();
         let shape = shape.into_dimension();
@@ -536,6 +1042,7 @@ mod reorder {
 #[cfg(not(target_arch = "wasm32"))]
 mod reduce {
     use super::*;
+    use approx::assert_relative_eq;
     use std::mem::size_of;
 
     pub fn reduce_tests(device: &Device) -> Vec<Trial> {
@@ -618,9 +1125,205 @@ mod reduce {
                 }).with_ignored_flag(ignore),
             ]);
         });
+        tests.push(
+            device_test(device, "any_all_u8", any_all).with_ignored_flag(
+                device.is_device() && !features.contains(&features_for_scalar(ScalarType::U8)),
+            ),
+        );
+        tests.push(device_test(device, "histogram_f32", |device| {
+            histogram(device, 1, 4);
+            histogram(device, 100, 10);
+            histogram(device, 1000, 7);
+        }));
+        tests.push(device_test(device, "sum_blocked", sum_blocked));
+        tests.push(device_test(device, "sum_axes", sum_axes));
+        tests.push(device_test(
+            device,
+            "reduce_axis_keepdim",
+            reduce_axis_keepdim,
+        ));
+        tests.push(
+            device_test(device, "mean_std_all", mean_std_all).with_ignored_flag(
+                device.is_device() && !features.contains(&features_for_scalar(ScalarType::U8)),
+            ),
+        );
         tests
     }
 
+    fn sum_axes(device: &Device) {
+        let shape = [2, 3, 4, 5];
+        let x_array = (0..shape.iter().product::<usize>())
+            .map(|x| (x % 7) as f32)
+            .collect::<Array1<_>>()
+            .into_shape(shape)
+            .unwrap();
+        let x = Tensor::from(x_array.clone())
+            .into_device(device.clone())
+            .unwrap();
+        let axes_list: &[&[usize]] = &[&[], &[1], &[0, 2, 3], &[3, 1], &[0, 1, 2, 3]];
+        for axes in axes_list.iter().copied() {
+            let mut sorted = axes.to_vec();
+            sorted.sort_unstable();
+            let mut y_true = x.to_owned().unwrap().into_dyn();
+            for axis in sorted.into_iter().rev() {
+                y_true = y_true.sum_axis(Axis(axis)).unwrap();
+            }
+            let axes: Vec<Axis> = axes.iter().copied().map(Axis).collect();
+            let y = x.sum_axes(&axes).unwrap();
+            assert_eq!(y.shape(), y_true.shape(), "{axes:?}");
+            assert_eq!(
+                y.into_array().unwrap(),
+                y_true.into_array().unwrap(),
+                "{axes:?}"
+            );
+        }
+        assert!(x.sum_axes(&[Axis(4)]).is_err());
+        assert!(x.sum_axes(&[Axis(1), Axis(1)]).is_err());
+    }
+
+    fn reduce_axis_keepdim(device: &Device) {
+        let x_array = (0..24)
+            .map(|x| (x % 5) as f32)
+            .collect::<Array1<_>>()
+            .into_shape([2, 3, 4])
+            .unwrap();
+        let x = Tensor::from(x_array.clone())
+            .into_device(device.clone())
+            .unwrap();
+        for axis in (0..3).map(Axis) {
+            let y = x.sum_axis_keepdim(axis).unwrap();
+            let mut shape = [2, 3, 4];
+            shape[axis.0] = 1;
+            assert_eq!(y.shape(), shape);
+            let y_true = x_array.sum_axis(axis).insert_axis(axis);
+            let y = y.broadcast(x.raw_dim()).unwrap().to_owned().unwrap();
+            assert_eq!(
+                y.into_array().unwrap(),
+                y_true.broadcast(x_array.raw_dim()).unwrap()
+            );
+            let y = x.mean_axis_keepdim(axis).unwrap();
+            assert_eq!(y.shape(), shape);
+            let y_true = x_array.mean_axis(axis).unwrap().insert_axis(axis);
+            let y = y.broadcast(x.raw_dim()).unwrap().to_owned().unwrap();
+            assert_relative_eq!(
+                y.into_array().unwrap(),
+                y_true.broadcast(x_array.raw_dim()).unwrap()
+            );
+            let y = x.max_axis_keepdim(axis).unwrap();
+            assert_eq!(y.shape(), shape);
+            let y_true = x_array
+                .fold_axis(axis, f32::NEG_INFINITY, |a, b| a.max(*b))
+                .insert_axis(axis);
+            assert_eq!(
+                x.max_axis(axis).unwrap().into_array().unwrap(),
+                y_true.index_axis(axis, 0)
+            );
+            let y = y.broadcast(x.raw_dim()).unwrap().to_owned().unwrap();
+            assert_eq!(
+                y.into_array().unwrap(),
+                y_true.broadcast(x_array.raw_dim()).unwrap()
+            );
+        }
+        let x = Tensor::<f32, _>::zeros(device.clone(), [2, 0]).unwrap();
+        assert!(x.mean_axis(Axis(1)).is_err());
+        assert!(x.max_axis(Axis(1)).is_err());
+    }
+
+    fn mean_std_all(device: &Device) {
+        let x_vec: Vec<u8> = (0..1000).map(|x| ((x * 37) % 256) as u8).collect();
+        let n = x_vec.len() as f64;
+        let mean_true = x_vec.iter().map(|x| *x as f64).sum::<f64>() / n;
+        let std_true = (x_vec
+            .iter()
+            .map(|x| (*x as f64 - mean_true).powi(2))
+            .sum::<f64>()
+            / n)
+            .sqrt();
+        let x = Tensor::from(x_vec)
+            .into_shape([10, 100])
+            .unwrap()
+            .into_device(device.clone())
+            .unwrap();
+        let mean = x.mean_all().unwrap();
+        let std = x.std_all().unwrap();
+        assert!((mean as f64 - mean_true).abs() < 1e-3, "{mean} {mean_true}");
+        assert!((std as f64 - std_true).abs() < 1e-3, "{std} {std_true}");
+        let empty = Tensor::<f32, _>::zeros(device.clone(), 0).unwrap();
+        assert!(empty.mean_all().is_err());
+        assert!(empty.std_all().is_err());
+    }
+
+    fn sum_blocked(device: &Device) {
+        let x = Tensor::<u32, _>::zeros(device.clone(), 10).unwrap();
+        assert!(x.sum_blocked(1).is_err());
+        for n in [1, 100, 1021, 5000] {
+            let x_vec: Vec<u32> = (0..n as u32).map(|x| (x * 7919) % 1013).collect();
+            let y_true: u32 = x_vec.iter().sum();
+            let x_u32 = Tensor::from(x_vec.clone())
+                .into_device(device.clone())
+                .unwrap();
+            let x_i32 = Tensor::from(x_vec.iter().map(|x| *x as i32 - 500).collect::<Vec<_>>())
+                .into_device(device.clone())
+                .unwrap();
+            let y_i32 = x_i32.sum().unwrap();
+            assert_eq!(x_u32.sum().unwrap(), y_true, "{n}");
+            for block_size in [2, 7, 256] {
+                assert_eq!(
+                    x_u32.sum_blocked(block_size).unwrap(),
+                    y_true,
+                    "{n} {block_size}"
+                );
+                assert_eq!(
+                    x_i32.sum_blocked(block_size).unwrap(),
+                    y_i32,
+                    "{n} {block_size}"
+                );
+            }
+        }
+    }
+
+    fn any_all(device: &Device) {
+        let shapes: &[&[usize]] = &[&[0], &[1], &[7], &[3, 0], &[0, 3], &[3, 5], &[2, 3, 4]];
+        for shape in shapes.iter().copied() {
+            for k in [1, 2, 3] {
+                let x_array = (0..shape.iter().product())
+                    .map(|x: usize| (x % k != 0) as u8)
+                    .collect::<Array1<_>>()
+                    .into_shape(shape)
+                    .unwrap();
+                let x = Tensor::from(x_array.clone())
+                    .into_device(device.clone())
+                    .unwrap();
+                assert_eq!(x.any().unwrap(), x_array.iter().any(|x| *x != 0));
+                assert_eq!(x.all().unwrap(), x_array.iter().all(|x| *x != 0));
+                for axis in (0..shape.len()).map(Axis) {
+                    let any = x_array.map_axis(axis, |x| x.iter().any(|x| *x != 0) as u8);
+                    let all = x_array.map_axis(axis, |x| x.iter().all(|x| *x != 0) as u8);
+                    let y = x.any_axis(axis).unwrap().into_array().unwrap();
+                    assert_eq!(y, any);
+                    let y = x.all_axis(axis).unwrap().into_array().unwrap();
+                    assert_eq!(y, all);
+                }
+            }
+        }
+        let x = Tensor::<u8, _>::zeros(device.clone(), 0).unwrap();
+        assert!(!x.any().unwrap());
+        assert!(x.all().unwrap());
+    }
+
+    fn histogram(device: &Device, n: usize, bins: usize) {
+        let x_vec: Vec<f32> = (0..n).map(|x| (x % 15) as f32 - 2.5).collect();
+        let (low, high) = (0f32, 10f32);
+        let mut y_vec = vec![0u32; bins];
+        for x in x_vec.iter().copied() {
+            let bin = ((x - low) / (high - low) * bins as f32).floor();
+            y_vec[bin.max(0.).min((bins - 1) as f32) as usize] += 1;
+        }
+        let x = Tensor::from(x_vec).into_device(device.clone()).unwrap();
+        let y = x.histogram(bins, (low, high)).unwrap();
+        assert_eq!(y.into_array().unwrap().into_raw_vec(), y_vec);
+    }
+
     fn sum<T: Scalar, E: IntoDimension>(device: &Device, shape: E) {
         let shape = shape.into_dimension();
         let x_array = (1..10)
@@ -684,6 +1387,100 @@ mod reduce {
     }
 }
 
+#[cfg(feature = "dataset")]
+mod dataset {
+    use super::*;
+    use autograph::dataset::loader::DataLoader;
+
+    pub fn dataset_tests(device: &Device) -> Vec<Trial> {
+        vec![
+            device_test(device, "data_loader_resume", |_| data_loader_resume()),
+            device_test(device, "binary_dataset", |_| binary_dataset()),
+        ]
+    }
+
+    fn data_loader_resume() {
+        let builder = || {
+            DataLoader::builder(10)
+                .batch_size(3)
+                .shuffle(1)
+                .build()
+                .unwrap()
+        };
+        let mut loader = builder();
+        assert_eq!(loader.batches(), 4);
+        let epoch: Vec<_> = loader.by_ref().collect();
+        assert_eq!(epoch.len(), 4);
+        let mut indices: Vec<u32> = epoch.concat();
+        indices.sort();
+        assert_eq!(indices, (0..10).collect::<Vec<_>>());
+        // Interrupt the second epoch after 2 batches.
+        loader.next().unwrap();
+        loader.next().unwrap();
+        let state = loader.state();
+        assert_eq!(state.epoch(), 1);
+        assert_eq!(state.index(), 6);
+        // The rest of the second epoch and the third epoch.
+        let remaining = |loader: &mut DataLoader| {
+            let mut batches: Vec<_> = loader.by_ref().collect();
+            batches.extend(loader.by_ref());
+            batches
+        };
+        let batches = remaining(&mut loader);
+        assert_eq!(batches.len(), 6);
+        let mut resumed = builder();
+        resumed.restore(state.clone()).unwrap();
+        assert_eq!(remaining(&mut resumed), batches);
+        let mut other = DataLoader::builder(5).build().unwrap();
+        assert!(other.restore(state).is_err());
+    }
+
+    fn binary_dataset() {
+        use autograph::dataset::binary::BinaryDataset;
+        use ndarray::Array;
+
+        let dir =
+            std::env::temp_dir().join(format!("autograph_binary_dataset_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let inputs_path = dir.join("inputs");
+        let targets_path = dir.join("targets");
+        // 4 byte headers, 5 samples of shape [2, 3].
+        let inputs: Vec<u8> = (0..5 * 6).collect();
+        let targets: Vec<u8> = (10..15).collect();
+        std::fs::write(&inputs_path, [&[0u8; 4][..], &inputs].concat()).unwrap();
+        std::fs::write(&targets_path, [&[0u8; 4][..], &targets].concat()).unwrap();
+        let builder = || {
+            BinaryDataset::builder(&inputs_path, &targets_path)
+                .input_shape(&[2, 3])
+                .inputs_offset(4)
+                .targets_offset(4)
+        };
+        let mut dataset = builder().build().unwrap();
+        assert_eq!(dataset.len(), 5);
+        let (x, t) = dataset.batch(&[3, 0]).unwrap();
+        let x_true = Array::from_shape_vec([2, 2, 3], [&inputs[18..24], &inputs[..6]].concat())
+            .unwrap()
+            .into_dyn();
+        assert_eq!(x.into_array().unwrap(), x_true);
+        assert_eq!(t.into_array().unwrap().to_vec(), [13, 10]);
+        assert!(dataset.batch(&[5]).is_err());
+        let mut loader = DataLoader::builder(dataset.len())
+            .batch_size(2)
+            .build()
+            .unwrap();
+        let mut count = 0;
+        for batch in dataset.batches(&mut loader) {
+            let (x, t) = batch.unwrap();
+            assert_eq!(x.shape()[1..], [2, 3]);
+            assert_eq!(x.shape()[0], t.len());
+            count += t.len();
+        }
+        assert_eq!(count, 5);
+        assert!(builder().input_shape(&[4]).build().is_err());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
+
 #[cfg(feature = "learn")]
 mod learn {
     use super::*;
@@ -693,6 +1490,8 @@ mod learn {
     pub fn learn_tests(device: &Device) -> Vec<Trial> {
         let mut tests = Vec::new();
         tests.extend(criterion::criterion_tests(device));
+        tests.push(device_test(device, "mixup_cutmix", mixup_cutmix));
+        tests.push(device_test(device, "auc_metrics", auc_metrics));
         #[cfg(feature = "neural-network")]
         {
             tests.extend(neural_network::neural_network_tests(device));
@@ -700,6 +1499,94 @@ mod learn {
         tests
     }
 
+    fn mixup_cutmix(device: &Device) {
+        use autograph::learn::augment::{cutmix, mixup};
+        use rand::{rngs::StdRng, SeedableRng};
+
+        let images_array = Array::from_iter((0..8 * 2 * 6 * 6).map(|x| x as f32))
+            .into_shape([8, 2, 6, 6])
+            .unwrap();
+        let images = Tensor::from(images_array.clone())
+            .into_device(device.clone())
+            .unwrap();
+        let labels = Tensor::from((0..8u8).collect::<Vec<_>>())
+            .into_device(device.clone())
+            .unwrap();
+        for cut in [false, true] {
+            let augment = |alpha: f32, seed: u64| {
+                let rng = &mut StdRng::seed_from_u64(seed);
+                let (images, (labels_a, labels_b, lambda)) = if cut {
+                    cutmix(&images, labels.view(), alpha, rng).unwrap()
+                } else {
+                    mixup(&images, labels.view(), alpha, rng).unwrap()
+                };
+                (
+                    images.into_array().unwrap(),
+                    labels_a.into_array().unwrap(),
+                    labels_b.into_array().unwrap(),
+                    lambda,
+                )
+            };
+            let (x1, a1, b1, lambda1) = augment(1., 0);
+            let (x2, a2, b2, lambda2) = augment(1., 0);
+            assert_eq!(x1, x2, "cut: {cut}");
+            assert_eq!((&a1, &b1, lambda1), (&a2, &b2, lambda2), "cut: {cut}");
+            assert!((0.5..=1.).contains(&lambda1) || cut, "lambda: {lambda1}");
+            let mut b_sorted = b1.to_vec();
+            b_sorted.sort();
+            assert_eq!(b_sorted, a1.to_vec(), "cut: {cut}");
+            for (i, b) in b1.iter().enumerate() {
+                let (x, xa, xb) = (
+                    x1.index_axis(Axis(0), i),
+                    images_array.index_axis(Axis(0), i),
+                    images_array.index_axis(Axis(0), *b as usize),
+                );
+                if !cut {
+                    let y = &xa * lambda1 + &xb * (1. - lambda1);
+                    assert_relative_eq!(x, y, epsilon = 1e-3);
+                } else {
+                    let kept = x.iter().zip(xa.iter()).filter(|(x, xa)| x == xa).count();
+                    let from_b = x.iter().zip(xb.iter()).filter(|(x, xb)| x == xb).count();
+                    assert!(kept + from_b >= x.len(), "cut: {cut}");
+                }
+            }
+            for alpha in [0., 1e-4] {
+                let (x, _, _, lambda) = augment(alpha, 1);
+                assert!(lambda > 0.99, "cut: {cut} alpha: {alpha} lambda: {lambda}");
+                assert_relative_eq!(x, images_array, epsilon = 1e-3);
+            }
+        }
+    }
+
+    fn auc_metrics(device: &Device) {
+        use autograph::{learn::metrics::AucMetrics, tensor::ScalarTensor1};
+
+        let batches = [
+            (vec![0.1f32, 0.4], vec![0u8, 0]),
+            (vec![0.35, 0.8], vec![1, 1]),
+        ];
+        let mut metrics = AucMetrics::new();
+        assert!(metrics.roc_auc().is_err());
+        for (scores, labels) in batches {
+            let scores = Tensor::from(scores).into_device(device.clone()).unwrap();
+            let labels = Tensor::from(labels).into_device(device.clone()).unwrap();
+            metrics
+                .update(&ScalarTensor1::from(scores), &ScalarTensor1::from(labels))
+                .unwrap();
+        }
+        assert_eq!(metrics.len(), 4);
+        assert_relative_eq!(metrics.roc_auc().unwrap(), 0.75);
+        assert_relative_eq!(metrics.pr_auc().unwrap(), 5. / 6.);
+        // ties are counted as half
+        metrics.reset();
+        let scores = Tensor::from(vec![0.5f32, 0.5, 0.2, 0.9]);
+        let labels = Tensor::from(vec![1u8, 0, 0, 1]);
+        metrics
+            .update(&ScalarTensor1::from(scores), &ScalarTensor1::from(labels))
+            .unwrap();
+        assert_relative_eq!(metrics.roc_auc().unwrap(), 0.875);
+    }
+
     mod criterion {
         use super::*;
         use autograph::learn::criterion::Accuracy;
@@ -747,6 +1634,20 @@ mod learn {
                     }).with_ignored_flag(ignore));
                 });
             });
+            tests.push(device_test(
+                device,
+                "cross_entropy_loss_large_classes",
+                cross_entropy_loss_large_classes,
+            ));
+            tests.push(
+                device_test(device, "cross_entropy_loss_accumulate_in_bf16", |device| {
+                    cross_entropy_loss_accumulate_in::<bf16>(device, 1000, 100);
+                })
+                .with_ignored_flag(
+                    device.is_device()
+                        && !features.contains(&features_for_scalar(ScalarType::BF16)),
+                ),
+            );
             tests
         }
 
@@ -774,8 +1675,18 @@ mod learn {
             let t_host = Tensor::from(t_array);
             let x_device = x_host.to_device(device.clone()).unwrap();
             let t_device = t_host.to_device(device.clone()).unwrap();
-            let y_host = x_host.accuracy(t_host).unwrap();
-            let y_device = x_device.accuracy(t_device).unwrap();
+            let y_host = x_host.accuracy(t_host.view()).unwrap();
+            let y_device = x_device.accuracy(t_device.view()).unwrap();
+            assert_eq!(y_host, y_device);
+            // ties select the first class
+            let x_ties_host = Tensor::from(
+                Array::from_iter((0..batch_size * classes).map(|x| X::from_usize(x % 3).unwrap()))
+                    .into_shape([batch_size, classes])
+                    .unwrap(),
+            );
+            let x_ties_device = x_ties_host.to_device(device.clone()).unwrap();
+            let y_host = x_ties_host.accuracy(t_host).unwrap();
+            let y_device = x_ties_device.accuracy(t_device).unwrap();
             assert_eq!(y_host, y_device);
         }
 
@@ -811,19 +1722,89 @@ mod learn {
             };
             assert_relative_eq!(y_host, y_device, epsilon = epsilon, max_relative = epsilon);
         }
-    }
+
+        fn cross_entropy_loss_large_classes(device: &Device) {
+            let (batch_size, classes) = (4, 50_000);
+            let x_array = Array::from_iter(
+                (0..batch_size * classes).map(|x| ((x * 7919) % 1000) as f32 / 50. - 10.),
+            )
+            .into_shape([batch_size, classes])
+            .unwrap();
+            let t_array = Array::from_iter((0..batch_size).map(|i| (i * 12_345) as u32));
+            let y_true: f64 = x_array
+                .outer_iter()
+                .zip(t_array.iter())
+                .map(|(x, t)| {
+                    let m = x.iter().fold(f64::NEG_INFINITY, |m, x| m.max(*x as f64));
+                    let s: f64 = x.iter().map(|x| (*x as f64 - m).exp()).sum();
+                    s.ln() - (x[*t as usize] as f64 - m)
+                })
+                .sum();
+            let x = Tensor::from(x_array).into_device(device.clone()).unwrap();
+            let t = Tensor::from(t_array).into_device(device.clone()).unwrap();
+            let y = x.cross_entropy_loss(t).unwrap();
+            assert_relative_eq!(y, y_true as f32, max_relative = 1e-4);
+        }
+
+        fn cross_entropy_loss_accumulate_in<X: Scalar + Float>(
+            device: &Device,
+            batch_size: usize,
+            classes: usize,
+        ) {
+            use autograph::learn::criterion::CrossEntropyLossOptions;
+
+            let x_vec: Vec<X> = (0..10u8)
+                .map(|x| X::from_u8(x).unwrap())
+                .cycle()
+                .take(batch_size * classes)
+                .collect();
+            let t_vec: Vec<u8> = (0..classes)
+                .cycle()
+                .map(|t| t as u8)
+                .take(batch_size)
+                .collect();
+            let x_host = Tensor::from(
+                Array::from(x_vec)
+                    .into_shape([batch_size, classes])
+                    .unwrap(),
+            );
+            let t_host = Tensor::from(Array::from(t_vec));
+            let x_device = x_host.to_device(device.clone()).unwrap();
+            let t_device = t_host.to_device(device.clone()).unwrap();
+            let options = CrossEntropyLossOptions::default();
+            let y_true = x_host
+                .cross_entropy_loss_with(t_host.view(), options.accumulate_in(ScalarType::F64))
+                .unwrap();
+            for y in [
+                x_host
+                    .cross_entropy_loss_with(t_host.view(), options.accumulate_in(ScalarType::F32))
+                    .unwrap(),
+                x_device
+                    .cross_entropy_loss_with(t_device, options.accumulate_in(ScalarType::F32))
+                    .unwrap(),
+            ] {
+                // tighter than the bf16 tolerance of batch_size * 0.001
+                let epsilon = batch_size as f32 * f32::EPSILON;
+                assert_relative_eq!(y_true, y, epsilon = epsilon, max_relative = epsilon);
+            }
+            assert!(x_host
+                .cross_entropy_loss_with(t_host, options.accumulate_in(ScalarType::F16))
+                .is_err());
+        }
+    }
 
     #[cfg(feature = "neural-network")]
     mod neural_network {
         use super::*;
         use autograph::{
             learn::neural_network::{
-                autograd::Variable,
+                autograd::{Variable, Variable1},
                 layer::{Forward, MaxPool2, Relu},
             },
             ops::{Col2ImConv2, Col2ImConv2Options, Im2ColConv2, Im2ColConv2Options},
             tensor::Tensor1,
         };
+        use ndarray::ArrayD;
         use num_traits::{Float, Unsigned};
 
         pub fn neural_network_tests(device: &Device) -> Vec<Trial> {
@@ -905,6 +1886,11 @@ mod learn {
                     }).with_ignored_flag(ignore),
                 ]);
             });
+            tests.push(device_test(
+                device,
+                "max_pool2_stride_gt_filter",
+                max_pool2_stride_gt_filter,
+            ));
             macro_for!($T in [bf16, f32] {
                 let ignore = device.is_device()
                 && !features.contains(&features_for_scalar($T::scalar_type()));
@@ -931,6 +1917,217 @@ mod learn {
                 broadcast(device, [3, 2], [5, 4, 3, 2]);
                 broadcast(device, [4, 1, 1, 3], [4, 2, 1, 3]);
             })]);
+            tests.push(device_test(device, "expand", expand));
+            tests.push(device_test(
+                device,
+                "dense_shared_weight",
+                dense_shared_weight,
+            ));
+            tests.push(device_test(
+                device,
+                "dense_shared_weight_optimizer",
+                dense_shared_weight_optimizer,
+            ));
+            tests.push(device_test(device, "save_load_reader", save_load_reader));
+            tests.push(device_test(device, "grad_accumulation", grad_accumulation));
+            tests.push(device_test(device, "two_heads", two_heads));
+            tests.push(device_test(device, "lenet5_grad_norms", lenet5_grad_norms));
+            tests.push(device_test(device, "checkpoint", checkpoint));
+            tests.push(device_test(device, "relu_permuted", relu_permuted));
+            tests.push(device_test(
+                device,
+                "relu_elementwise_tuning",
+                relu_elementwise_tuning,
+            ));
+            tests.push(device_test(
+                device,
+                "functional_relu_softmax",
+                functional_relu_softmax,
+            ));
+            tests.push(device_test(
+                device,
+                "silu_finite_difference",
+                silu_finite_difference,
+            ));
+            tests.push(device_test(
+                device,
+                "triplet_margin_loss_finite_difference",
+                triplet_margin_loss_finite_difference,
+            ));
+            tests.push(device_test(device, "activation_kind", activation_kind));
+            tests.push(device_test(device, "relu6", relu6));
+            tests.push(device_test(device, "bias_init", bias_init));
+            tests.push(device_test(
+                device,
+                "bilinear_finite_difference",
+                bilinear_finite_difference,
+            ));
+            tests.push(device_test(
+                device,
+                "variable_index_select",
+                variable_index_select,
+            ));
+            tests.push(device_test(device, "variable_take_put", variable_take_put));
+            tests.push(device_test(device, "variable_matmul", variable_matmul));
+            tests.push(device_test(device, "variable_cat", variable_cat));
+            tests.push(device_test(
+                device,
+                "custom_optimizer_rmsprop",
+                custom_optimizer_rmsprop,
+            ));
+            tests.push(device_test(device, "optimizer_adamw", optimizer_adamw));
+            tests.push(device_test(device, "lr_warmup", |_| lr_warmup()));
+            tests.push(device_test(device, "variable_outer", variable_outer));
+            tests.push(device_test(device, "variable_mul", variable_mul));
+            tests.push(device_test(device, "variable_div", variable_div));
+            tests.push(device_test(device, "variable_pow", variable_pow));
+            tests.push(device_test(
+                device,
+                "variable_abs_sign_neg",
+                variable_abs_sign_neg,
+            ));
+            tests.push(device_test(device, "variable_compare", variable_compare));
+            tests.push(device_test(
+                device,
+                "variable_maximum_minimum",
+                variable_maximum_minimum,
+            ));
+            tests.push(device_test(
+                device,
+                "variable_squeeze_unsqueeze",
+                variable_squeeze_unsqueeze,
+            ));
+            tests.push(device_test(device, "multi_margin_loss", multi_margin_loss));
+            tests.push(device_test(
+                device,
+                "shape_mismatch_error",
+                shape_mismatch_error,
+            ));
+            tests.push(device_test(
+                device,
+                "variable_dot_vector",
+                variable_dot_vector,
+            ));
+            tests.push(device_test(device, "custom_op_square", custom_op_square));
+            tests.push(device_test(
+                device,
+                "variable_full_like",
+                variable_full_like,
+            ));
+            tests.push(device_test(
+                device,
+                "custom_layer_trains",
+                custom_layer_trains,
+            ));
+            tests.push(device_test(device, "layer_seed", layer_seed));
+            tests.push(device_test(device, "masked_fill", masked_fill));
+            tests.push(device_test(device, "output_shape", output_shape));
+            tests.push(device_test(
+                device,
+                "infer_flatten_size_lenet5",
+                infer_flatten_size_lenet5,
+            ));
+            tests.push(device_test(device, "lazy_dense_mlp", lazy_dense_mlp));
+            tests.push(device_test(device, "lazy_build_error", lazy_build_error));
+            tests.push(device_test(device, "predict_batched", predict_batched));
+            tests.push(device_test(device, "set_weight", set_weight));
+            tests.push(device_test(
+                device,
+                "dense_flatten_shape_mismatch",
+                dense_flatten_shape_mismatch,
+            ));
+            tests.push(device_test(device, "conv_algorithms", conv_algorithms));
+            tests.push(device_test(
+                device,
+                "conv_deterministic",
+                conv_deterministic,
+            ));
+            tests.push(device_test(
+                device,
+                "conv_memory_format",
+                conv_memory_format,
+            ));
+            tests.push(device_test(
+                device,
+                "conv_asymmetric_padding",
+                conv_asymmetric_padding,
+            ));
+            tests.push(device_test(device, "conv1_dilation", conv1_dilation));
+            tests.push(device_test(device, "conv2_dilation", conv2_dilation));
+            tests.push(
+                device_test(device, "conv_compute_scalar_type", conv_compute_scalar_type)
+                    .with_ignored_flag(
+                        device.is_device()
+                            && !features.contains(&features_for_scalar(ScalarType::BF16)),
+                    ),
+            );
+            tests.push(
+                device_test(device, "layer_to_dtype", layer_to_dtype).with_ignored_flag(
+                    device.is_device()
+                        && !features.contains(&features_for_scalar(ScalarType::BF16)),
+                ),
+            );
+            tests.push(device_test(device, "conv2d_max_pool2d", conv2d_max_pool2d));
+            tests.push(device_test(device, "conv2_bias", conv2_bias));
+            tests.push(device_test(
+                device,
+                "conv2_max_pool2_no_grad",
+                conv2_max_pool2_no_grad,
+            ));
+            tests.push(device_test(
+                device,
+                "conv_weight_standardization",
+                conv_weight_standardization,
+            ));
+            tests.push(device_test(device, "conv_tiled", conv_tiled));
+            tests.push(device_test(
+                device,
+                "layer_device_mismatch",
+                layer_device_mismatch,
+            ));
+            tests.push(device_test(
+                device,
+                "forward_auto_device",
+                forward_auto_device,
+            ));
+            tests.push(device_test(
+                device,
+                "layer_device_scalar_type",
+                layer_device_scalar_type,
+            ));
+            tests.push(device_test(device, "ema_model", ema_model));
+            tests.push(device_test(device, "eval_mode", eval_mode));
+            tests.push(device_test(device, "boxed_layers", boxed_layers));
+            tests.push(device_test(
+                device,
+                "batch_norm_track_running_stats",
+                batch_norm_track_running_stats,
+            ));
+            tests.push(device_test(device, "affine", affine));
+            tests.push(device_test(
+                device,
+                "spectral_norm_dense",
+                spectral_norm_dense,
+            ));
+            tests.push(device_test(device, "onnx_export", onnx_export));
+            #[cfg(feature = "pytorch")]
+            tests.push(device_test(
+                device,
+                "pytorch_state_dict",
+                pytorch_state_dict,
+            ));
+            #[cfg(feature = "pytorch")]
+            tests.push(device_test(
+                device,
+                "pytorch_state_dict_partial",
+                pytorch_state_dict_partial,
+            ));
+            #[cfg(feature = "pytorch")]
+            tests.push(device_test(
+                device,
+                "pytorch_state_dict_batch_norm",
+                pytorch_state_dict_batch_norm,
+            ));
             tests
         }
 
@@ -1054,6 +2251,58 @@ mod learn {
             assert_eq!(y_host.into_array().unwrap(), y_device.into_array().unwrap());
         }
 
+        fn max_pool2_stride_gt_filter(device: &Device) {
+            let (ih, iw) = (7, 8);
+            let x_array = Array::from_iter((0..ih * iw).map(|x| ((x * 13) % 56) as f32))
+                .into_shape([1, 1, ih, iw])
+                .unwrap();
+            let x = Variable::builder().node().build(
+                Tensor::from(x_array.clone())
+                    .into_device(device.clone())
+                    .unwrap()
+                    .into_shared()
+                    .unwrap()
+                    .into(),
+            );
+            let pool = MaxPool2::builder().filter([2, 2]).stride([3, 3]).build();
+            let y = pool.forward(x.clone()).unwrap();
+            assert_eq!(y.shape(), [1, 1, 2, 3]);
+            let mut y_true = Array::zeros([1, 1, 2, 3]);
+            let mut dx_true = Array::<f32, _>::zeros([1, 1, ih, iw]);
+            for ((_, _, row, col), y) in y_true.indexed_iter_mut() {
+                let (mut m, mut mi, mut mj) = (f32::NEG_INFINITY, 0, 0);
+                for i in row * 3..row * 3 + 2 {
+                    for j in col * 3..col * 3 + 2 {
+                        if x_array[(0, 0, i, j)] > m {
+                            (m, mi, mj) = (x_array[(0, 0, i, j)], i, j);
+                        }
+                    }
+                }
+                *y = m;
+                dx_true[(0, 0, mi, mj)] = 1.;
+            }
+            let y_array = y
+                .value()
+                .clone()
+                .cast_into_tensor::<f32>()
+                .unwrap()
+                .into_array()
+                .unwrap();
+            assert_eq!(y_array, y_true);
+            y.node().unwrap().backward().unwrap();
+            let dx_array = x
+                .node()
+                .unwrap()
+                .grad()
+                .unwrap()
+                .cast_into_tensor::<f32>()
+                .unwrap()
+                .into_array()
+                .unwrap();
+            // rows 2, 5, and 6 and columns 2 and 5 are skipped and have 0 gradient
+            assert_eq!(dx_array, dx_true);
+        }
+
         fn max_pool2_backward<T: Scalar>(
             device: &Device,
             input_shape: [usize; 4],
@@ -1132,6 +2381,50 @@ mod learn {
             assert_eq!(y_host.into_array().unwrap(), y_device.into_array().unwrap());
         }
 
+        fn relu_elementwise_tuning(device: &Device) {
+            use autograph::tensor::ElementwiseTuning;
+
+            let x_array = Array::from_iter((0..33 * 51).map(|x| (x % 21) as f32 - 10.))
+                .into_shape([33, 51])
+                .unwrap();
+            let x = Tensor::from(x_array)
+                .into_device(device.clone())
+                .unwrap()
+                .into_shared()
+                .unwrap();
+            let run = |tuning| {
+                let x = Variable::builder().node().build(x.clone().into());
+                let y = Relu.forward_with_tuning(x.clone(), tuning).unwrap();
+                y.node().unwrap().backward().unwrap();
+                let dx = x.node().unwrap().grad().unwrap();
+                [y.into_value(), dx]
+                    .map(|x| x.cast_into_tensor::<f32>().unwrap().into_array().unwrap())
+            };
+            for tuning in [
+                ElementwiseTuning {
+                    threads: Some(0),
+                    items_per_thread: None,
+                },
+                ElementwiseTuning {
+                    threads: None,
+                    items_per_thread: Some(0),
+                },
+            ] {
+                let x = Variable::from(x.clone());
+                assert!(Relu.forward_with_tuning(x, tuning).is_err());
+            }
+            let outputs = run(ElementwiseTuning::default());
+            for threads in [None, Some(64), Some(256)] {
+                for items_per_thread in [None, Some(1), Some(3), Some(16)] {
+                    let tuning = ElementwiseTuning {
+                        threads,
+                        items_per_thread,
+                    };
+                    assert_eq!(run(tuning), outputs, "{tuning:?}");
+                }
+            }
+        }
+
         fn relu_backward<T: Scalar>(device: &Device, input_shape: [usize; 2]) {
             let len = input_shape.iter().product();
             let y_vec: Vec<T> = (-1i8..1)
@@ -1171,25 +2464,4596 @@ mod learn {
             }
         }
 
-        fn broadcast<D1: IntoDimension + 'static, D2: IntoDimension + 'static>(
-            device: &Device,
-            input_dim: D1,
-            output_dim: D2,
-        ) {
-            use autograph::tensor::ScalarArcTensor;
+        fn activation_kind(device: &Device) {
+            use autograph::learn::neural_network::{
+                autograd::Variable4,
+                layer::{Activation, Conv2},
+            };
 
-            let input_dim = input_dim.into_dimension();
-            let output_dim = output_dim.into_dimension();
-            let x = ScalarArcTensor::zeros(device.clone(), input_dim, ScalarType::F32).unwrap();
-            let y = x.broadcast_shared(output_dim.clone());
-            let x_var = Variable::builder().node().build(x.clone());
-            let y_var = x_var.broadcast(output_dim);
-            assert_eq!(y.is_some(), y_var.is_some());
-            if let Some((y, y_var)) = y.zip(y_var) {
-                assert_eq!(y.shape(), y_var.shape());
-                assert_eq!(y.strides(), y_var.value().strides());
-                y_var.node().unwrap().backward().unwrap();
+            let x = Tensor::from(
+                Array::from_iter((0..2 * 3 * 6 * 6).map(|x| ((x % 11) as f32 - 5.) / 3.))
+                    .into_shape([2, 3, 6, 6])
+                    .unwrap(),
+            )
+            .into_device(device.clone())
+            .unwrap()
+            .into_shared()
+            .unwrap();
+            let builder = || {
+                Conv2::builder()
+                    .inputs(3)
+                    .outputs(4)
+                    .filter([3, 3])
+                    .bias(true)
+                    .seed(1)
+                    .device(device.clone())
+            };
+            let forward = |y: Variable4| {
+                y.into_value()
+                    .cast_into_tensor::<f32>()
+                    .unwrap()
+                    .into_array()
+                    .unwrap()
+            };
+            let conv = builder().activation(Relu).build().unwrap();
+            let y = forward(conv.forward(Variable::from(x.clone())).unwrap());
+            let conv = builder().activation_kind(Activation::Relu).build().unwrap();
+            let y_kind = forward(conv.forward(Variable::from(x.clone())).unwrap());
+            assert_eq!(y_kind, y);
+
+            fn sigmoid(x: f64) -> f64 {
+                1. / (1. + (-x).exp())
+            }
+            let kinds: [(Activation, fn(f64) -> f64); 8] = [
+                (Activation::None, |x| x),
+                (Activation::LeakyRelu(0.1), |x| {
+                    if x >= 0. {
+                        x
+                    } else {
+                        0.1 * x
+                    }
+                }),
+                (Activation::Sigmoid, sigmoid),
+                (Activation::Tanh, f64::tanh),
+                (Activation::Gelu, |x| {
+                    let a = (2. / std::f64::consts::PI).sqrt();
+                    0.5 * x * (1. + (a * (x + 0.044715 * x.powi(3))).tanh())
+                }),
+                (Activation::Silu, |x| x * sigmoid(x)),
+                (Activation::Relu6, |x| x.clamp(0., 6.)),
+                (Activation::HardSigmoid, |x| (x / 6. + 0.5).clamp(0., 1.)),
+            ];
+            let x_array = Array::from_iter((-80..=80).map(|x| x as f32 / 10. + 0.05));
+            for (kind, f) in kinds {
+                let x = Variable::builder().node().build(
+                    Tensor::from(x_array.clone())
+                        .into_device(device.clone())
+                        .unwrap()
+                        .into_shared()
+                        .unwrap()
+                        .into(),
+                );
+                let y = kind.forward(x.clone()).unwrap();
+                let y_array = y
+                    .value()
+                    .clone()
+                    .cast_into_tensor::<f32>()
+                    .unwrap()
+                    .into_array()
+                    .unwrap();
+                let y_true = x_array.map(|x| f(*x as f64) as f32);
+                assert_relative_eq!(y_array, y_true, epsilon = 1e-6, max_relative = 1e-5);
+                y.node().unwrap().backward().unwrap();
+                let dx_array = x
+                    .node()
+                    .unwrap()
+                    .grad()
+                    .unwrap()
+                    .cast_into_tensor::<f32>()
+                    .unwrap()
+                    .into_array()
+                    .unwrap();
+                let h = 1e-4;
+                let dx_true = x_array.map(|x| {
+                    let x = *x as f64;
+                    ((f(x + h) - f(x - h)) / (2. * h)) as f32
+                });
+                assert_relative_eq!(dx_array, dx_true, epsilon = 1e-5, max_relative = 1e-4);
+            }
+        }
+
+        fn relu6(device: &Device) {
+            use autograph::learn::neural_network::layer::Relu6;
+
+            let x_array = Array::from(vec![-2f32, -0.5, 0.5, 3., 5.5, 6.5, 100.]);
+            let x = Variable::builder().node().build(
+                Tensor::from(x_array.clone())
+                    .into_device(device.clone())
+                    .unwrap()
+                    .into_shared()
+                    .unwrap()
+                    .into(),
+            );
+            let y = Relu6.forward(x.clone()).unwrap();
+            let y_array = y
+                .value()
+                .clone()
+                .cast_into_tensor::<f32>()
+                .unwrap()
+                .into_array()
+                .unwrap();
+            assert_eq!(y_array.as_slice().unwrap(), &[0., 0., 0.5, 3., 5.5, 6., 6.]);
+            y.node().unwrap().backward().unwrap();
+            let dx_array = x
+                .node()
+                .unwrap()
+                .grad()
+                .unwrap()
+                .cast_into_tensor::<f32>()
+                .unwrap()
+                .into_array()
+                .unwrap();
+            assert_eq!(dx_array.as_slice().unwrap(), &[0., 0., 1., 1., 1., 0., 0.]);
+        }
+
+        fn bias_init(device: &Device) {
+            use autograph::learn::neural_network::{
+                autograd::ParameterViewMut1,
+                layer::{BiasInit, Conv2, Dense},
+            };
+
+            let to_vec = |bias: ParameterViewMut1| {
+                bias.value()
+                    .cast(ScalarType::F32)
+                    .unwrap()
+                    .try_into_tensor::<f32>()
+                    .unwrap()
+                    .into_vec()
+                    .unwrap()
+            };
+            let mut dense = Dense::builder()
+                .inputs(8)
+                .outputs(4)
+                .bias(true)
+                .bias_init(BiasInit::Zeros)
+                .device(device.clone())
+                .build()
+                .unwrap();
+            let bias = to_vec(dense.bias_view_mut().unwrap().unwrap());
+            assert_eq!(bias, vec![0f32; 4]);
+            let mut conv = Conv2::builder()
+                .inputs(2)
+                .outputs(3)
+                .filter([3, 3])
+                .bias(true)
+                .bias_init(BiasInit::Constant(0.5))
+                .device(device.clone())
+                .build()
+                .unwrap();
+            let bias = to_vec(conv.bias_view_mut().unwrap().unwrap());
+            assert_eq!(bias, vec![0.5f32; 3]);
+            let mut dense = Dense::builder()
+                .inputs(8)
+                .outputs(100)
+                .bias(true)
+                .seed(0)
+                .device(device.clone())
+                .build()
+                .unwrap();
+            let a = f32::sqrt(2. / 8.);
+            let bias = to_vec(dense.bias_view_mut().unwrap().unwrap());
+            assert!(bias.iter().all(|x| x.abs() <= a), "{bias:?}");
+            assert!(bias.iter().any(|x| *x != 0.), "{bias:?}");
+        }
+
+        fn bilinear_finite_difference(device: &Device) {
+            use autograph::learn::neural_network::layer::{Bilinear, Layer};
+            use autograph::tensor::ScalarArcTensorD;
+            use ndarray::{Array1, Array2, Array3};
+
+            fn bilinear(
+                x1: &Array2<f64>,
+                x2: &Array2<f64>,
+                w: &Array3<f64>,
+                b: &Array1<f64>,
+            ) -> Array2<f64> {
+                let (n, _) = x1.dim();
+                let (o, _, _) = w.dim();
+                Array2::from_shape_fn([n, o], |(n, o)| {
+                    x1.row(n).dot(&w.index_axis(Axis(0), o).dot(&x2.row(n))) + b[o]
+                })
+            }
+
+            let (batch_size, inputs1, inputs2, outputs) = (2, 3, 4, 2);
+            let mut layer = Bilinear::builder()
+                .inputs1(inputs1)
+                .inputs2(inputs2)
+                .outputs(outputs)
+                .bias(true)
+                .seed(0)
+                .device(device.clone())
+                .build()
+                .unwrap();
+            layer.set_training(true).unwrap();
+            let to_array = |x: ScalarArcTensorD| {
+                x.cast_into_tensor::<f32>()
+                    .unwrap()
+                    .into_array()
+                    .unwrap()
+                    .map(|x| *x as f64)
+            };
+            let w = to_array(layer.weight().value().clone().into_dyn())
+                .into_dimensionality()
+                .unwrap();
+            let b = to_array(layer.bias().unwrap().value().clone().into_dyn())
+                .into_dimensionality()
+                .unwrap();
+            let x1_array = Array::from_shape_fn([batch_size, inputs1], |(n, i)| {
+                (n * inputs1 + i) as f32 / 4. - 1.
+            });
+            let x2_array = Array::from_shape_fn([batch_size, inputs2], |(n, j)| {
+                1. - (n * inputs2 + j) as f32 / 5.
+            });
+            let g = Array::from_shape_fn([batch_size, outputs], |(n, o)| (n + 2 * o) as f64 - 1.);
+            let variable = |x: &Array2<f32>| {
+                Variable::builder().node().build(
+                    Tensor::from(x.clone())
+                        .into_device(device.clone())
+                        .unwrap()
+                        .into_shared()
+                        .unwrap()
+                        .into(),
+                )
+            };
+            let x1 = variable(&x1_array);
+            let x2 = variable(&x2_array);
+            let y = layer.forward((x1.clone(), x2.clone())).unwrap();
+            assert_eq!(y.shape(), [batch_size, outputs]);
+            let x1_array = x1_array.map(|x| *x as f64);
+            let x2_array = x2_array.map(|x| *x as f64);
+            let y_true = bilinear(&x1_array, &x2_array, &w, &b);
+            assert_relative_eq!(
+                to_array(y.value().clone().into_dyn()),
+                y_true.clone().into_dyn(),
+                epsilon = 1e-5,
+                max_relative = 1e-5
+            );
+            y.node()
+                .unwrap()
+                .backward_grad(
+                    Tensor::from(g.map(|x| *x as f32))
+                        .into_device(device.clone())
+                        .unwrap()
+                        .into_shared()
+                        .unwrap()
+                        .into(),
+                )
+                .unwrap();
+            let loss = |x1: &Array2<f64>, x2: &Array2<f64>, w: &Array3<f64>| {
+                (bilinear(x1, x2, w, &b) * &g).sum()
+            };
+            let h = 1e-4;
+            let mut dx1_true = Array2::zeros(x1_array.raw_dim());
+            for (index, dx) in dx1_true.indexed_iter_mut() {
+                let (mut xp, mut xm) = (x1_array.clone(), x1_array.clone());
+                xp[index] += h;
+                xm[index] -= h;
+                *dx = (loss(&xp, &x2_array, &w) - loss(&xm, &x2_array, &w)) / (2. * h);
+            }
+            let mut dx2_true = Array2::zeros(x2_array.raw_dim());
+            for (index, dx) in dx2_true.indexed_iter_mut() {
+                let (mut xp, mut xm) = (x2_array.clone(), x2_array.clone());
+                xp[index] += h;
+                xm[index] -= h;
+                *dx = (loss(&x1_array, &xp, &w) - loss(&x1_array, &xm, &w)) / (2. * h);
+            }
+            let mut dw_true = Array3::zeros(w.raw_dim());
+            for (index, dw) in dw_true.indexed_iter_mut() {
+                let (mut xp, mut xm) = (w.clone(), w.clone());
+                xp[index] += h;
+                xm[index] -= h;
+                *dw =
+                    (loss(&x1_array, &x2_array, &xp) - loss(&x1_array, &x2_array, &xm)) / (2. * h);
+            }
+            let db_true = g.sum_axis(Axis(0));
+            for (grad, grad_true) in [
+                (
+                    x1.node().unwrap().grad().unwrap().into_dyn(),
+                    dx1_true.into_dyn(),
+                ),
+                (
+                    x2.node().unwrap().grad().unwrap().into_dyn(),
+                    dx2_true.into_dyn(),
+                ),
+                (
+                    layer.weight().grad().unwrap().into_dyn(),
+                    dw_true.into_dyn(),
+                ),
+                (
+                    layer.bias().unwrap().grad().unwrap().into_dyn(),
+                    db_true.into_dyn(),
+                ),
+            ] {
+                assert_relative_eq!(
+                    to_array(grad),
+                    grad_true,
+                    epsilon = 1e-4,
+                    max_relative = 1e-4
+                );
+            }
+        }
+
+        fn silu_finite_difference(device: &Device) {
+            use autograph::learn::neural_network::layer::Silu;
+
+            fn silu(x: f64) -> f64 {
+                x / (1. + (-x).exp())
+            }
+
+            let x_array = Array::from_iter((-200..=200).map(|x| x as f32 / 10.));
+            let x = Variable::builder().node().build(
+                Tensor::from(x_array.clone())
+                    .into_device(device.clone())
+                    .unwrap()
+                    .into_shared()
+                    .unwrap()
+                    .into(),
+            );
+            let y = Silu.forward(x.clone()).unwrap();
+            let y_array = y
+                .value()
+                .clone()
+                .cast_into_tensor::<f32>()
+                .unwrap()
+                .into_array()
+                .unwrap();
+            let y_true = x_array.map(|x| silu(*x as f64) as f32);
+            assert_relative_eq!(y_array, y_true, epsilon = 1e-6, max_relative = 1e-5);
+            y.node().unwrap().backward().unwrap();
+            let dx_array = x
+                .node()
+                .unwrap()
+                .grad()
+                .unwrap()
+                .cast_into_tensor::<f32>()
+                .unwrap()
+                .into_array()
+                .unwrap();
+            let h = 1e-4;
+            let dx_true = x_array.map(|x| {
+                let x = *x as f64;
+                ((silu(x + h) - silu(x - h)) / (2. * h)) as f32
+            });
+            assert_relative_eq!(dx_array, dx_true, epsilon = 1e-5, max_relative = 1e-4);
+        }
+
+        fn triplet_margin_loss_finite_difference(device: &Device) {
+            use autograph::learn::neural_network::functional;
+            use ndarray::Array2;
+
+            fn triplet_margin_loss(x: &[Array2<f64>; 3], margin: f64) -> f64 {
+                let [a, p, n] = x;
+                let distance = |x1: &Array2<f64>, x2: &Array2<f64>| {
+                    (x1 - x2 + 1e-6)
+                        .mapv(|x| x * x)
+                        .sum_axis(Axis(1))
+                        .mapv(f64::sqrt)
+                };
+                (distance(a, p) - distance(a, n) + margin)
+                    .mapv(|x| x.max(0.))
+                    .mean()
+                    .unwrap()
+            }
+
+            let (batch_size, dim) = (3, 4);
+            let margin = 0.4;
+            let inputs = [(5, 7, 0.5), (3, 5, 0.5), (2, 9, 1.)].map(|(a, m, b)| {
+                Array::from_iter((0..batch_size * dim).map(|x| ((x * a) % m) as f64 / 4. - b))
+                    .into_shape([batch_size, dim])
+                    .unwrap()
+            });
+            let [anchor, positive, negative] = inputs.clone().map(|x| {
+                Variable::builder().node().build(
+                    Tensor::from(x.mapv(|x| x as f32))
+                        .into_device(device.clone())
+                        .unwrap()
+                        .into_shared()
+                        .unwrap()
+                        .into(),
+                )
+            });
+            let y = functional::triplet_margin_loss(&anchor, &positive, &negative, margin as f32)
+                .unwrap();
+            let y_value = y
+                .value()
+                .clone()
+                .cast_into_tensor::<f32>()
+                .unwrap()
+                .into_array()
+                .unwrap()
+                .into_scalar();
+            let y_true = triplet_margin_loss(&inputs, margin) as f32;
+            assert_relative_eq!(y_value, y_true, epsilon = 1e-5);
+            y.backward().unwrap();
+            let h = 1e-4;
+            for (i, x) in [&anchor, &positive, &negative].into_iter().enumerate() {
+                let dx = x
+                    .node()
+                    .unwrap()
+                    .grad()
+                    .unwrap()
+                    .cast_into_tensor::<f32>()
+                    .unwrap()
+                    .into_array()
+                    .unwrap();
+                let mut dx_true = Array2::<f32>::zeros([batch_size, dim]);
+                for (index, dx) in dx_true.indexed_iter_mut() {
+                    let mut inputs_plus = inputs.clone();
+                    inputs_plus[i][index] += h;
+                    let mut inputs_minus = inputs.clone();
+                    inputs_minus[i][index] -= h;
+                    *dx = ((triplet_margin_loss(&inputs_plus, margin)
+                        - triplet_margin_loss(&inputs_minus, margin))
+                        / (2. * h)) as f32;
+                }
+                assert_relative_eq!(dx, dx_true, epsilon = 1e-4, max_relative = 1e-3);
+            }
+        }
+
+        fn functional_relu_softmax(device: &Device) {
+            use autograph::{
+                learn::neural_network::{autograd::Variable2, functional},
+                tensor::ScalarArcTensor,
+            };
+
+            let x_array = Array::from_iter((-6..6).map(|x| x as f32 / 2.))
+                .into_shape([3, 4])
+                .unwrap();
+            let x = Variable::builder().node().build(
+                Tensor::from(x_array.clone())
+                    .into_device(device.clone())
+                    .unwrap()
+                    .into_shared()
+                    .unwrap()
+                    .into(),
+            );
+            let to_array = |x: Variable2| {
+                x.into_value()
+                    .cast_into_tensor::<f32>()
+                    .unwrap()
+                    .into_array()
+                    .unwrap()
+            };
+            let y = functional::relu(x.clone()).unwrap();
+            let y_true = Relu.forward(x.clone()).unwrap();
+            assert_eq!(to_array(y), to_array(y_true));
+            let y = functional::softmax(x.clone()).unwrap();
+            let mut y_true = x_array.mapv(f32::exp);
+            for mut y in y_true.outer_iter_mut() {
+                let s = y.sum();
+                y /= s;
             }
+            assert_relative_eq!(to_array(y.clone()), y_true, epsilon = 1e-6);
+            // The softmax sums to 1, so the gradient of its sum is 0.
+            y.node()
+                .unwrap()
+                .backward_grad(
+                    ScalarArcTensor::ones(device.clone(), [3, 4], ScalarType::F32).unwrap(),
+                )
+                .unwrap();
+            let dx_array = to_array(Variable::from(x.node().unwrap().grad().unwrap()));
+            assert_relative_eq!(dx_array, Array::zeros([3, 4]), epsilon = 1e-6);
+        }
+
+        fn relu_permuted(device: &Device) {
+            let x_array = Array::from_iter((-6..6).map(|x| x as f32))
+                .into_shape([3, 4])
+                .unwrap();
+            let x = Variable::builder().node().build(
+                Tensor::from(x_array.clone())
+                    .into_device(device.clone())
+                    .unwrap()
+                    .into_shared()
+                    .unwrap()
+                    .into(),
+            );
+            let y = Relu.forward(x.t()).unwrap();
+            let y_array = y
+                .value()
+                .clone()
+                .cast_into_tensor::<f32>()
+                .unwrap()
+                .into_array()
+                .unwrap();
+            assert_eq!(y_array, x_array.t().map(|x| x.max(0.)));
+            y.node().unwrap().backward().unwrap();
+            let dx_array = x
+                .node()
+                .unwrap()
+                .grad()
+                .unwrap()
+                .cast_into_tensor::<f32>()
+                .unwrap()
+                .into_array()
+                .unwrap();
+            assert_eq!(dx_array, x_array.map(|x| if *x > 0. { 1f32 } else { 0. }));
+        }
+
+        fn variable_dot_vector(device: &Device) {
+            use autograph::tensor::ScalarArcTensorD;
+            use ndarray::{linalg::Dot, Array2};
+
+            let a_array = Array::from_iter((0..12).map(|x| (x % 5) as f32 - 2.))
+                .into_shape([3, 4])
+                .unwrap();
+            let x_array = Array::from_iter((0..4).map(|x| x as f32 - 1.5));
+            let z_array = Array::from_iter((0..3).map(|x| x as f32 + 0.5));
+            let leaf = |x: Array2<f32>| {
+                Variable::builder().node().build(
+                    Tensor::from(x)
+                        .into_device(device.clone())
+                        .unwrap()
+                        .into_shared()
+                        .unwrap()
+                        .into(),
+                )
+            };
+            let leaf1 = |x: Array1<f32>| -> Variable1 {
+                Variable::builder().node().build(
+                    Tensor::from(x)
+                        .into_device(device.clone())
+                        .unwrap()
+                        .into_shared()
+                        .unwrap()
+                        .into(),
+                )
+            };
+            let array =
+                |x: ScalarArcTensorD| x.cast_into_tensor::<f32>().unwrap().into_array().unwrap();
+            let outer = |a: &Array1<f32>, b: &Array1<f32>| -> Array2<f32> {
+                a.view()
+                    .insert_axis(Axis(1))
+                    .dot(&b.view().insert_axis(Axis(0)))
+            };
+            // [3, 4] x [4] -> [3]
+            let a = leaf(a_array.clone());
+            let x = leaf1(x_array.clone());
+            let y = a.dot(&x).unwrap();
+            assert_eq!(
+                array(y.value().clone().into_dyn()),
+                a_array.dot(&x_array).into_dyn()
+            );
+            y.node().unwrap().backward().unwrap();
+            let ones = Array1::<f32>::ones(3);
+            assert_eq!(
+                array(a.node().unwrap().grad().unwrap().into_dyn()),
+                outer(&ones, &x_array).into_dyn()
+            );
+            assert_eq!(
+                array(x.node().unwrap().grad().unwrap().into_dyn()),
+                a_array.t().dot(&ones).into_dyn()
+            );
+            // [3] x [3, 4] -> [4]
+            let a = leaf(a_array.clone());
+            let z = leaf1(z_array.clone());
+            let y = z.dot(&a).unwrap();
+            assert_eq!(
+                array(y.value().clone().into_dyn()),
+                z_array.dot(&a_array).into_dyn()
+            );
+            y.node().unwrap().backward().unwrap();
+            let ones = Array1::<f32>::ones(4);
+            assert_eq!(
+                array(z.node().unwrap().grad().unwrap().into_dyn()),
+                a_array.dot(&ones).into_dyn()
+            );
+            assert_eq!(
+                array(a.node().unwrap().grad().unwrap().into_dyn()),
+                outer(&z_array, &ones).into_dyn()
+            );
+        }
+
+        fn variable_outer(device: &Device) {
+            use autograph::tensor::ScalarArcTensorD;
+            use ndarray::{linalg::Dot, Array2};
+
+            let a_array = Array::from_iter((0..3).map(|x| x as f32 - 1.5));
+            let b_array = Array::from_iter((0..5).map(|x| (x % 3) as f32 + 0.5));
+            let g_array = Array::from_iter((0..15).map(|x| (x % 4) as f32 - 1.))
+                .into_shape([3, 5])
+                .unwrap();
+            let leaf = |x: Array1<f32>| -> Variable1 {
+                Variable::builder().node().build(
+                    Tensor::from(x)
+                        .into_device(device.clone())
+                        .unwrap()
+                        .into_shared()
+                        .unwrap()
+                        .into(),
+                )
+            };
+            let array =
+                |x: ScalarArcTensorD| x.cast_into_tensor::<f32>().unwrap().into_array().unwrap();
+            let y_true: Array2<f32> = a_array
+                .view()
+                .insert_axis(Axis(1))
+                .dot(&b_array.view().insert_axis(Axis(0)));
+            let a = leaf(a_array.clone());
+            let b = leaf(b_array.clone());
+            let y = a.outer(&b).unwrap();
+            assert_eq!(array(y.value().clone().into_dyn()), y_true.into_dyn());
+            y.node()
+                .unwrap()
+                .backward_grad(
+                    Tensor::from(g_array.clone())
+                        .into_device(device.clone())
+                        .unwrap()
+                        .into_shared()
+                        .unwrap()
+                        .into(),
+                )
+                .unwrap();
+            assert_eq!(
+                array(a.node().unwrap().grad().unwrap().into_dyn()),
+                g_array.dot(&b_array).into_dyn()
+            );
+            assert_eq!(
+                array(b.node().unwrap().grad().unwrap().into_dyn()),
+                g_array.t().dot(&a_array).into_dyn()
+            );
+        }
+
+        fn variable_mul(device: &Device) {
+            let a_array = Array::from_iter((-4..4).map(|x| x as f32 / 2.));
+            let b_array = Array::from_iter((0..8).map(|x| (x % 3) as f32 - 1.5));
+            let leaf = |x: &Array1<f32>| {
+                Variable::builder().node().build(
+                    Tensor::from(x.clone())
+                        .into_device(device.clone())
+                        .unwrap()
+                        .into_shared()
+                        .unwrap()
+                        .into(),
+                )
+            };
+            let grad = |x: &Variable1| {
+                x.node()
+                    .unwrap()
+                    .grad()
+                    .unwrap()
+                    .cast_into_tensor::<f32>()
+                    .unwrap()
+                    .into_array()
+                    .unwrap()
+            };
+            let a = leaf(&a_array);
+            let b = leaf(&b_array);
+            // b is used twice
+            let y = a.mul(&b).unwrap().mul(&b).unwrap();
+            let y_array = y
+                .value()
+                .clone()
+                .cast_into_tensor::<f32>()
+                .unwrap()
+                .into_array()
+                .unwrap();
+            assert_eq!(y_array, &a_array * &b_array * &b_array);
+            y.node().unwrap().backward().unwrap();
+            assert_eq!(grad(&a), &b_array * &b_array);
+            assert_eq!(grad(&b), 2. * &a_array * &b_array);
+            let a = leaf(&a_array);
+            let y = a.mul(&a).unwrap();
+            y.node().unwrap().backward().unwrap();
+            assert_eq!(grad(&a), 2. * &a_array);
+        }
+
+        fn variable_div(device: &Device) {
+            let a_array = Array::from_iter((-4..4).map(|x| x as f32 / 2.));
+            let b_array = Array::from_iter((1..9).map(|x| x as f32 / 4.));
+            let leaf = |x: &Array1<f32>| {
+                Variable::builder().node().build(
+                    Tensor::from(x.clone())
+                        .into_device(device.clone())
+                        .unwrap()
+                        .into_shared()
+                        .unwrap()
+                        .into(),
+                )
+            };
+            let grad = |x: &Variable1| {
+                x.node()
+                    .unwrap()
+                    .grad()
+                    .unwrap()
+                    .cast_into_tensor::<f32>()
+                    .unwrap()
+                    .into_array()
+                    .unwrap()
+            };
+            let a = leaf(&a_array);
+            let b = leaf(&b_array);
+            let y = a.div(&b).unwrap();
+            let y_array = y
+                .value()
+                .clone()
+                .cast_into_tensor::<f32>()
+                .unwrap()
+                .into_array()
+                .unwrap();
+            assert_relative_eq!(y_array, &a_array / &b_array, max_relative = 1e-6);
+            y.node().unwrap().backward().unwrap();
+            let h = 1e-4;
+            let (da_true, db_true): (Vec<f32>, Vec<f32>) = a_array
+                .iter()
+                .zip(b_array.iter())
+                .map(|(a, b)| {
+                    let (a, b) = (*a as f64, *b as f64);
+                    let da = ((a + h) / b - (a - h) / b) / (2. * h);
+                    let db = (a / (b + h) - a / (b - h)) / (2. * h);
+                    (da as f32, db as f32)
+                })
+                .unzip();
+            assert_relative_eq!(
+                grad(&a),
+                Array::from(da_true),
+                epsilon = 1e-5,
+                max_relative = 1e-4
+            );
+            assert_relative_eq!(
+                grad(&b),
+                Array::from(db_true),
+                epsilon = 1e-5,
+                max_relative = 1e-4
+            );
+            let x = Tensor::from(vec![1f32, -1., 0.])
+                .into_device(device.clone())
+                .unwrap();
+            let zeros = Tensor::<f32, _>::zeros(device.clone(), 3).unwrap();
+            let y = x.div(&zeros).unwrap().into_array().unwrap();
+            assert_eq!(y[0], f32::INFINITY);
+            assert_eq!(y[1], f32::NEG_INFINITY);
+            assert!(y[2].is_nan());
+        }
+
+        fn variable_pow(device: &Device) {
+            let a_array = Array::from_iter((1..9).map(|x| x as f32 / 4.));
+            let b_array = Array::from_iter((-4..4).map(|x| x as f32 / 2.));
+            let leaf = |x: &Array1<f32>| {
+                Variable::builder().node().build(
+                    Tensor::from(x.clone())
+                        .into_device(device.clone())
+                        .unwrap()
+                        .into_shared()
+                        .unwrap()
+                        .into(),
+                )
+            };
+            let grad = |x: &Variable1| {
+                x.node()
+                    .unwrap()
+                    .grad()
+                    .unwrap()
+                    .cast_into_tensor::<f32>()
+                    .unwrap()
+                    .into_array()
+                    .unwrap()
+            };
+            let a = leaf(&a_array);
+            let b = leaf(&b_array);
+            let y = a.pow(&b).unwrap();
+            let y_array = y
+                .value()
+                .clone()
+                .cast_into_tensor::<f32>()
+                .unwrap()
+                .into_array()
+                .unwrap();
+            let y_true =
+                Array::from_iter(a_array.iter().zip(b_array.iter()).map(|(a, b)| a.powf(*b)));
+            assert_relative_eq!(y_array, y_true, max_relative = 1e-5);
+            y.node().unwrap().backward().unwrap();
+            let (da_true, db_true): (Vec<f32>, Vec<f32>) = a_array
+                .iter()
+                .zip(b_array.iter())
+                .map(|(a, b)| (b * a.powf(b - 1.), a.powf(*b) * a.ln()))
+                .unzip();
+            assert_relative_eq!(
+                grad(&a),
+                Array::from(da_true),
+                epsilon = 1e-5,
+                max_relative = 1e-4
+            );
+            assert_relative_eq!(
+                grad(&b),
+                Array::from(db_true),
+                epsilon = 1e-5,
+                max_relative = 1e-4
+            );
+            let a = leaf(&Array::from(vec![-1f32, 0.]));
+            let b = leaf(&Array::from(vec![2f32, 2.]));
+            a.pow(&b).unwrap().node().unwrap().backward().unwrap();
+            assert!(grad(&b).iter().all(|x| x.is_nan()));
+        }
+
+        fn variable_abs_sign_neg(device: &Device) {
+            use autograph::tensor::ScalarArcTensor1;
+
+            let x_array = Array::from(vec![-2f32, -0.5, 0., 0.5, 2.]);
+            let x_true = x_array.clone();
+            let leaf = || {
+                Variable::builder().node().build(
+                    Tensor::from(x_array.clone())
+                        .into_device(device.clone())
+                        .unwrap()
+                        .into_shared()
+                        .unwrap()
+                        .into(),
+                )
+            };
+            let to_array =
+                |x: ScalarArcTensor1| x.cast_into_tensor::<f32>().unwrap().into_array().unwrap();
+            let sign_true = x_true.map(|x| {
+                if *x > 0. {
+                    1f32
+                } else if *x < 0. {
+                    -1.
+                } else {
+                    0.
+                }
+            });
+            for (name, f, y_true, dx_true) in [
+                (
+                    "abs",
+                    Variable1::abs as fn(&Variable1) -> Result<Variable1>,
+                    x_true.map(|x| x.abs()),
+                    sign_true.clone(),
+                ),
+                ("sign", Variable1::sign, sign_true.clone(), Array::zeros(5)),
+                (
+                    "neg",
+                    Variable1::neg,
+                    x_true.map(|x| -x),
+                    Array::from_elem(5, -1f32),
+                ),
+            ] {
+                let x = leaf();
+                let y = f(&x).unwrap();
+                assert_eq!(to_array(y.value().clone()), y_true, "{name}");
+                y.node().unwrap().backward().unwrap();
+                assert_eq!(
+                    to_array(x.node().unwrap().grad().unwrap()),
+                    dx_true,
+                    "{name}"
+                );
+            }
+            let x = Tensor::from(vec![-3i32, 0, 4])
+                .into_device(device.clone())
+                .unwrap();
+            assert_eq!(x.abs().unwrap().into_vec().unwrap(), [3, 0, 4]);
+            assert_eq!(x.sign().unwrap().into_vec().unwrap(), [-1, 0, 1]);
+            assert_eq!(x.neg().unwrap().into_vec().unwrap(), [3, 0, -4]);
+            let x = Tensor::from(vec![1u32])
+                .into_device(device.clone())
+                .unwrap();
+            assert!(x.abs().is_err());
+        }
+
+        fn shape_mismatch_error(device: &Device) {
+            use autograph::error::AutographError;
+
+            let x = |len: usize| {
+                Variable::from(
+                    Tensor::from(vec![1f32; len])
+                        .into_device(device.clone())
+                        .unwrap()
+                        .into_shared()
+                        .unwrap(),
+                )
+            };
+            let error = x(2).mul(&x(3)).unwrap_err();
+            assert_eq!(
+                error.downcast_ref::<AutographError>(),
+                Some(&AutographError::ShapeMismatch {
+                    op: "mul".to_string(),
+                    expected: vec![2],
+                    actual: vec![3],
+                })
+            );
+        }
+
+        fn variable_compare(device: &Device) {
+            let x_vec: Vec<f32> = (0..37).map(|x| (x % 7) as f32 - 3.).collect();
+            let x = Variable::builder().node().build(
+                Tensor::from(x_vec.clone())
+                    .into_device(device.clone())
+                    .unwrap()
+                    .into_shared()
+                    .unwrap()
+                    .into(),
+            );
+            let threshold = 1f32;
+            for (name, mask, f) in [
+                (
+                    "gt",
+                    x.gt(threshold),
+                    (|x, t| x > t) as fn(f32, f32) -> bool,
+                ),
+                ("ge", x.ge(threshold), |x, t| x >= t),
+                ("lt", x.lt(threshold), |x, t| x < t),
+                ("le", x.le(threshold), |x, t| x <= t),
+                ("eq", x.eq(threshold), |x, t| x == t),
+            ] {
+                let mask_true: Vec<u8> = x_vec.iter().map(|x| f(*x, threshold) as u8).collect();
+                assert_eq!(mask.unwrap().into_vec().unwrap(), mask_true, "{name}");
+            }
+            let x = Tensor::from(vec![-2i32, 0, 5])
+                .into_device(device.clone())
+                .unwrap();
+            assert_eq!(x.ge(0).unwrap().into_vec().unwrap(), [0, 1, 1]);
+        }
+
+        fn variable_squeeze_unsqueeze(device: &Device) {
+            let x_vec = vec![1f32, 2., 3.];
+            let x = Tensor::from(x_vec.clone())
+                .into_shape([3, 1])
+                .unwrap()
+                .into_device(device.clone())
+                .unwrap()
+                .into_shared()
+                .unwrap();
+            assert!(x.clone().squeeze(Axis(0)).is_err());
+            assert!(x.clone().squeeze(Axis(2)).is_err());
+            let x = Variable::builder().node().build(x.into());
+            let y = x.clone().squeeze(Axis(1)).unwrap().unsqueeze(Axis(0));
+            assert_eq!(y.shape(), [1, 3]);
+            assert!(y.value().is_standard_layout());
+            let w = Variable::from(
+                Tensor::from(vec![2f32, -1., 4.])
+                    .into_shape([1, 3])
+                    .unwrap()
+                    .into_device(device.clone())
+                    .unwrap(),
+            );
+            let z = y.mul(&w).unwrap();
+            assert_eq!(
+                z.value()
+                    .clone()
+                    .cast_into_tensor::<f32>()
+                    .unwrap()
+                    .into_vec()
+                    .unwrap(),
+                [2., -2., 12.]
+            );
+            z.node().unwrap().backward().unwrap();
+            let dx = x
+                .node()
+                .unwrap()
+                .grad()
+                .unwrap()
+                .cast_into_tensor::<f32>()
+                .unwrap();
+            assert_eq!(dx.shape(), [3, 1]);
+            assert_eq!(dx.into_vec().unwrap(), [2., -1., 4.]);
+        }
+
+        fn variable_maximum_minimum(device: &Device) {
+            let a_vec = vec![1f32, -2., 3., 0., 5.];
+            let b_vec = vec![2f32, -2., 1., 0., 7.];
+            let leaf = |x: &[f32]| {
+                Variable::builder().node().build(
+                    Tensor::from(x.to_vec())
+                        .into_device(device.clone())
+                        .unwrap()
+                        .into_shared()
+                        .unwrap()
+                        .into(),
+                )
+            };
+            let grad = |x: &Variable1| {
+                x.node()
+                    .unwrap()
+                    .grad()
+                    .unwrap()
+                    .cast_into_tensor::<f32>()
+                    .unwrap()
+                    .into_vec()
+                    .unwrap()
+            };
+            for (name, maximum) in [("maximum", true), ("minimum", false)] {
+                let a = leaf(&a_vec);
+                let b = leaf(&b_vec);
+                let y = if maximum {
+                    a.maximum(&b).unwrap()
+                } else {
+                    a.minimum(&b).unwrap()
+                };
+                let (y_true, first): (Vec<f32>, Vec<bool>) = a_vec
+                    .iter()
+                    .zip(b_vec.iter())
+                    .map(|(a, b)| {
+                        // ties are routed to the first operand
+                        let first = if maximum { a >= b } else { a <= b };
+                        (if first { *a } else { *b }, first)
+                    })
+                    .unzip();
+                let y_vec = y
+                    .value()
+                    .clone()
+                    .cast_into_tensor::<f32>()
+                    .unwrap()
+                    .into_vec()
+                    .unwrap();
+                assert_eq!(y_vec, y_true, "{name}");
+                y.node().unwrap().backward().unwrap();
+                let da_true: Vec<f32> = first.iter().map(|f| *f as u8 as f32).collect();
+                let db_true: Vec<f32> = first.iter().map(|f| !*f as u8 as f32).collect();
+                assert_eq!(grad(&a), da_true, "{name}");
+                assert_eq!(grad(&b), db_true, "{name}");
+            }
+            let x = Tensor::from(vec![1u32, 5, 3])
+                .into_device(device.clone())
+                .unwrap();
+            let y = Tensor::from(vec![4u32, 2, 3])
+                .into_device(device.clone())
+                .unwrap();
+            assert_eq!(x.maximum(&y).unwrap().into_vec().unwrap(), [4, 5, 3]);
+            assert_eq!(x.minimum(&y).unwrap().into_vec().unwrap(), [1, 2, 3]);
+        }
+
+        fn multi_margin_loss(device: &Device) {
+            use autograph::learn::criterion::MultiMarginLoss;
+
+            let margin = 1f32;
+            for (batch_size, classes) in [(1, 2), (3, 4), (7, 5)] {
+                let x_vec: Vec<f32> = (0..batch_size * classes)
+                    .map(|x| ((x * 7) % 11) as f32 / 4.)
+                    .collect();
+                let t_vec: Vec<u8> = (0..batch_size).map(|i| ((i * 3) % classes) as u8).collect();
+                let x = Variable::builder().node().build(
+                    Tensor::from(x_vec.clone())
+                        .into_shape([batch_size, classes])
+                        .unwrap()
+                        .into_device(device.clone())
+                        .unwrap()
+                        .into_shared()
+                        .unwrap()
+                        .into(),
+                );
+                let t = Tensor::from(t_vec.clone())
+                    .into_device(device.clone())
+                    .unwrap()
+                    .into_shared()
+                    .unwrap()
+                    .into();
+                let n = (batch_size * classes) as f32;
+                let mut y_true = 0f32;
+                let mut dx_true = vec![0f32; batch_size * classes];
+                for (i, t) in t_vec.iter().map(|t| *t as usize).enumerate() {
+                    let x_t = x_vec[i * classes + t];
+                    for j in (0..classes).filter(|j| *j != t) {
+                        let hinge = margin - x_t + x_vec[i * classes + j];
+                        if hinge > 0. {
+                            y_true += hinge / n;
+                            dx_true[i * classes + j] += 1. / n;
+                            dx_true[i * classes + t] -= 1. / n;
+                        }
+                    }
+                }
+                let y = x.multi_margin_loss(t, margin).unwrap();
+                let y_value = y
+                    .value()
+                    .clone()
+                    .cast_into_tensor::<f32>()
+                    .unwrap()
+                    .into_array()
+                    .unwrap()
+                    .into_scalar();
+                assert_relative_eq!(y_value, y_true, epsilon = 1e-5);
+                y.backward().unwrap();
+                let dx = x
+                    .node()
+                    .unwrap()
+                    .grad()
+                    .unwrap()
+                    .cast_into_tensor::<f32>()
+                    .unwrap()
+                    .into_array()
+                    .unwrap()
+                    .into_shape(batch_size * classes)
+                    .unwrap();
+                assert_relative_eq!(dx, Array::from(dx_true), epsilon = 1e-5);
+            }
+            // example from the PyTorch docs
+            let x = Variable::from(
+                Tensor::from(vec![0.1f32, 0.2, 0.4, 0.8])
+                    .into_shape([1, 4])
+                    .unwrap()
+                    .into_device(device.clone())
+                    .unwrap()
+                    .into_shared()
+                    .unwrap(),
+            );
+            let t = Tensor::from(vec![3u8])
+                .into_device(device.clone())
+                .unwrap()
+                .into_shared()
+                .unwrap()
+                .into();
+            let y = x
+                .multi_margin_loss(t, margin)
+                .unwrap()
+                .into_value()
+                .cast_into_tensor::<f32>()
+                .unwrap()
+                .into_array()
+                .unwrap()
+                .into_scalar();
+            assert_relative_eq!(y, 0.325, epsilon = 1e-6);
+        }
+
+        fn custom_layer_trains(device: &Device) {
+            use autograph::learn::neural_network::{
+                autograd::Parameter1,
+                layer::{Layer, ParameterMutVec, ParameterVec},
+                optimizer::{Optimizer, SGD},
+            };
+
+            struct Scale {
+                scale: Parameter1,
+            }
+
+            impl Layer for Scale {
+                fn set_training(&mut self, training: bool) -> Result<()> {
+                    self.scale.set_training(training);
+                    Ok(())
+                }
+                fn parameters(&self) -> ParameterVec {
+                    [self.scale.clone().into_dyn()].into_iter().collect()
+                }
+                fn parameters_mut(&mut self) -> Result<ParameterMutVec> {
+                    Ok([self.scale.make_view_mut()?.into_dyn()]
+                        .into_iter()
+                        .collect())
+                }
+            }
+
+            impl Forward<Variable1> for Scale {
+                type Output = Variable1;
+                fn forward(&self, input: Variable1) -> Result<Variable1> {
+                    let scale = self.scale.to_variable().broadcast(input.raw_dim()).unwrap();
+                    input.mul(&scale)
+                }
+            }
+
+            let scale = Tensor::from(vec![0f32])
+                .into_device(device.clone())
+                .unwrap();
+            let mut layer = Scale {
+                scale: Parameter1::new(scale.into()),
+            };
+            assert_eq!(layer.parameters().len(), 1);
+            let x_array = ndarray::arr1(&[1f32, 2., 3.]);
+            let t_array = x_array.map(|x| 2. * x);
+            let x = Variable1::from(Tensor::from(x_array).into_device(device.clone()).unwrap());
+            let optimizer = SGD::builder().build();
+            let scale = |layer: &Scale| {
+                layer
+                    .scale
+                    .value()
+                    .clone()
+                    .cast_into_tensor::<f32>()
+                    .unwrap()
+                    .into_array()
+                    .unwrap()[0]
+            };
+            let mut error = (scale(&layer) - 2.).abs();
+            for _ in 0..50 {
+                layer.set_training(true).unwrap();
+                let y = layer.forward(x.clone()).unwrap();
+                // gradient of 0.5 * (y - t)^2
+                let y_array = y.value().clone().cast_into_tensor::<f32>().unwrap();
+                let dy = Tensor::from(y_array.into_array().unwrap() - &t_array)
+                    .into_device(device.clone())
+                    .unwrap();
+                y.node()
+                    .unwrap()
+                    .backward_grad(dy.into_shared().unwrap().into())
+                    .unwrap();
+                for parameter in layer.parameters_mut().unwrap() {
+                    optimizer.update(0.01, parameter).unwrap();
+                }
+                layer.set_training(false).unwrap();
+                let next_error = (scale(&layer) - 2.).abs();
+                assert!(next_error < error, "{next_error} {error}");
+                error = next_error;
+            }
+            assert!(error < 1e-2, "{error}");
+        }
+
+        fn variable_full_like(device: &Device) {
+            let x = Variable::builder().node().build(
+                Tensor::<f32, _>::zeros(device.clone(), [2, 3])
+                    .unwrap()
+                    .into_shared()
+                    .unwrap()
+                    .into(),
+            );
+            for (y, value) in [
+                (x.zeros_like().unwrap(), 0f32),
+                (x.ones_like().unwrap(), 1.),
+                (x.full_like(ScalarElem::F32(2.5)).unwrap(), 2.5),
+            ] {
+                assert!(y.node().is_none());
+                assert_eq!(y.shape(), x.shape());
+                assert_eq!(y.device(), x.device());
+                assert_eq!(y.scalar_type(), x.scalar_type());
+                let y = y
+                    .into_value()
+                    .cast_into_tensor::<f32>()
+                    .unwrap()
+                    .into_array()
+                    .unwrap();
+                assert!(y.iter().all(|y| *y == value));
+            }
+        }
+
+        fn custom_op_square(device: &Device) {
+            use autograph::tensor::{ScalarArcTensor1, ScalarTensor};
+
+            fn square(x: &Variable1) -> Result<Variable1> {
+                let device = x.device();
+                let scalar_type = x.scalar_type();
+                let mut builder = Variable::builder();
+                if let Some(node) = x.node() {
+                    let x = x.value().clone();
+                    let device = device.clone();
+                    builder.edge(node, move |output_grad: ScalarArcTensor1| {
+                        let x = x.cast_into_tensor::<f32>()?.into_array()?;
+                        let dy = output_grad.cast_into_tensor::<f32>()?.into_array()?;
+                        let dx =
+                            ScalarTensor::from(Tensor::from(2. * &x * &dy).into_device(device)?);
+                        dx.cast_into(scalar_type)?.into_shared()
+                    });
+                }
+                let x = x.value().clone().cast_into_tensor::<f32>()?.into_array()?;
+                let y = ScalarTensor::from(Tensor::from(&x * &x).into_device(device)?);
+                Ok(builder.build(y.cast_into(scalar_type)?.into_shared()?))
+            }
+
+            let x_array = Array::from_iter((-3..5).map(|x| x as f32));
+            let x = Variable::builder().node().build(
+                Tensor::from(x_array.clone())
+                    .into_device(device.clone())
+                    .unwrap()
+                    .into_shared()
+                    .unwrap()
+                    .into(),
+            );
+            let y = square(&x).unwrap();
+            y.node().unwrap().backward().unwrap();
+            let dx_array = x
+                .node()
+                .unwrap()
+                .grad()
+                .unwrap()
+                .cast_into_tensor::<f32>()
+                .unwrap()
+                .into_array()
+                .unwrap();
+            assert_eq!(dx_array, x_array.map(|x| 2. * x));
+        }
+
+        fn variable_index_select(device: &Device) {
+            let x_array = Array::from_iter((0..12).map(|x| x as f32))
+                .into_shape([4, 3])
+                .unwrap();
+            let x = Variable::builder().node().build(
+                Tensor::from(x_array.clone())
+                    .into_device(device.clone())
+                    .unwrap()
+                    .into_shared()
+                    .unwrap()
+                    .into(),
+            );
+            let indices_vec = vec![1u32, 3, 1, 1];
+            let indices = Tensor::from(indices_vec.clone())
+                .into_device(device.clone())
+                .unwrap()
+                .into_shared()
+                .unwrap();
+            let y = x.index_select(Axis(0), indices).unwrap();
+            let y_array = y
+                .value()
+                .clone()
+                .cast_into_tensor::<f32>()
+                .unwrap()
+                .into_array()
+                .unwrap();
+            let y_true =
+                Array::from_shape_fn([4, 3], |(i, j)| x_array[(indices_vec[i] as usize, j)]);
+            assert_eq!(y_array, y_true);
+            y.node().unwrap().backward().unwrap();
+            let dx_array = x
+                .node()
+                .unwrap()
+                .grad()
+                .unwrap()
+                .cast_into_tensor::<f32>()
+                .unwrap()
+                .into_array()
+                .unwrap();
+            let mut dx_true = Array::<f32, _>::zeros([4, 3]);
+            for index in indices_vec.iter().copied() {
+                dx_true.row_mut(index as usize).map_inplace(|x| *x += 1.);
+            }
+            assert_eq!(dx_array, dx_true);
+        }
+
+        fn variable_take_put(device: &Device) {
+            let x_array = Array::from_iter((0..12).map(|x| x as f32))
+                .into_shape([4, 3])
+                .unwrap();
+            let x = Variable::builder().node().build(
+                Tensor::from(x_array.clone())
+                    .into_device(device.clone())
+                    .unwrap()
+                    .into_shared()
+                    .unwrap()
+                    .into(),
+            );
+            let indices = |indices: &[u32]| {
+                Tensor::from(indices.to_vec())
+                    .into_device(device.clone())
+                    .unwrap()
+                    .into_shared()
+                    .unwrap()
+            };
+            let take_vec = [1u32, 3, 1, 1];
+            let put_vec = [2u32, 0, 2, 1];
+            let y = x.take(indices(&take_vec)).unwrap();
+            let z = y.put(indices(&put_vec), 3).unwrap();
+            assert!(y.put(indices(&put_vec[..2]), 3).is_err());
+            let z_array = z
+                .value()
+                .clone()
+                .cast_into_tensor::<f32>()
+                .unwrap()
+                .into_array()
+                .unwrap();
+            let mut z_true = Array::<f32, _>::zeros([3, 3]);
+            for (t, p) in take_vec.iter().zip(put_vec.iter()) {
+                let mut row = z_true.row_mut(*p as usize);
+                row += &x_array.row(*t as usize);
+            }
+            assert_eq!(z_array, z_true);
+            let g_array = Array::from_shape_fn([3, 3], |(i, j)| (i * 3 + j) as f32 - 4.);
+            z.node()
+                .unwrap()
+                .backward_grad(
+                    Tensor::from(g_array.clone())
+                        .into_device(device.clone())
+                        .unwrap()
+                        .into_shared()
+                        .unwrap()
+                        .into(),
+                )
+                .unwrap();
+            let dx_array = x
+                .node()
+                .unwrap()
+                .grad()
+                .unwrap()
+                .cast_into_tensor::<f32>()
+                .unwrap()
+                .into_array()
+                .unwrap();
+            // Row 1 is taken 3 times, 2 of which are put into the same row.
+            let mut dx_true = Array::<f32, _>::zeros([4, 3]);
+            for (t, p) in take_vec.iter().zip(put_vec.iter()) {
+                let mut row = dx_true.row_mut(*t as usize);
+                row += &g_array.row(*p as usize);
+            }
+            assert_eq!(dx_array, dx_true);
+        }
+
+        // NumPy matmul on the host.
+        fn matmul_true(a: &ArrayD<f32>, b: &ArrayD<f32>) -> ArrayD<f32> {
+            use ndarray::ArrayViewD;
+
+            let a = if a.ndim() == 1 {
+                a.view().insert_axis(Axis(0))
+            } else {
+                a.view()
+            };
+            let b = if b.ndim() == 1 {
+                b.view().insert_axis(Axis(1))
+            } else {
+                b.view()
+            };
+            let batch_ndim = a.ndim().max(b.ndim()) - 2;
+            let batch_dim = |x: &ArrayViewD<f32>, i: usize| {
+                (i + x.ndim())
+                    .checked_sub(batch_ndim + 2)
+                    .map_or(1, |i| x.shape()[i])
+            };
+            let batch: Vec<usize> = (0..batch_ndim)
+                .map(|i| batch_dim(&a, i).max(batch_dim(&b, i)))
+                .collect();
+            let [m, k] = [a.shape()[a.ndim() - 2], a.shape()[a.ndim() - 1]];
+            let n = b.shape()[b.ndim() - 1];
+            let a = a.broadcast([batch.as_slice(), &[m, k]].concat()).unwrap();
+            let b = b.broadcast([batch.as_slice(), &[k, n]].concat()).unwrap();
+            ArrayD::from_shape_fn([batch.as_slice(), &[m, n]].concat(), |index| {
+                let mut a_index = index.slice().to_vec();
+                let mut b_index = index.slice().to_vec();
+                (0..k)
+                    .map(|l| {
+                        a_index[batch_ndim + 1] = l;
+                        b_index[batch_ndim] = l;
+                        a[a_index.as_slice()] * b[b_index.as_slice()]
+                    })
+                    .sum()
+            })
+        }
+
+        fn variable_matmul(device: &Device) {
+            use autograph::tensor::ScalarArcTensorD;
+
+            let shapes: &[(&[usize], &[usize])] = &[
+                (&[3], &[3]),
+                (&[2, 3], &[3]),
+                (&[3], &[3, 4]),
+                (&[2, 3], &[3, 4]),
+                (&[5, 2, 3], &[3, 4]),
+                (&[3], &[5, 3, 4]),
+                (&[5, 2, 3], &[3]),
+                (&[2, 1, 2, 3], &[5, 3, 4]),
+            ];
+            let to_variable = |x: &ArrayD<f32>| {
+                Variable::builder().node().build(
+                    Tensor::from(x.clone())
+                        .into_device(device.clone())
+                        .unwrap()
+                        .into_shared()
+                        .unwrap()
+                        .into(),
+                )
+            };
+            let to_array =
+                |x: ScalarArcTensorD| x.cast_into_tensor::<f32>().unwrap().into_array().unwrap();
+            for (a_shape, b_shape) in shapes.iter().copied() {
+                let gen = |shape: &[usize], offset: usize| {
+                    ArrayD::from_shape_fn(shape, |index| {
+                        ((index.slice().iter().sum::<usize>() + offset) % 5) as f32 - 2.
+                    })
+                };
+                let a_array = gen(a_shape, 0);
+                let b_array = gen(b_shape, 1);
+                let a = to_variable(&a_array);
+                let b = to_variable(&b_array);
+                let c = a.matmul(&b).unwrap();
+                let c_true = matmul_true(&a_array, &b_array);
+                assert_eq!(
+                    to_array(c.value().clone()),
+                    c_true,
+                    "{a_shape:?} x {b_shape:?}"
+                );
+                let g_array = gen(c_true.shape(), 2);
+                c.node()
+                    .unwrap()
+                    .backward_grad(
+                        Tensor::from(g_array.clone())
+                            .into_device(device.clone())
+                            .unwrap()
+                            .into_shared()
+                            .unwrap()
+                            .into(),
+                    )
+                    .unwrap();
+                // The output is linear in each input, so the gradient of sum(g * c) with
+                // respect to an element is the output for the unit input.
+                let grad_true = |x: &ArrayD<f32>, f: &dyn Fn(&ArrayD<f32>) -> ArrayD<f32>| {
+                    let mut grad = ArrayD::zeros(x.shape());
+                    for (i, dx) in grad.iter_mut().enumerate() {
+                        let mut unit = ArrayD::zeros(x.shape());
+                        unit.as_slice_mut().unwrap()[i] = 1.;
+                        *dx = (f(&unit) * &g_array).sum();
+                    }
+                    grad
+                };
+                let da_true = grad_true(&a_array, &|a| matmul_true(a, &b_array));
+                let db_true = grad_true(&b_array, &|b| matmul_true(&a_array, b));
+                let da = a.node().unwrap().grad().unwrap();
+                let db = b.node().unwrap().grad().unwrap();
+                assert_eq!(to_array(da), da_true, "{a_shape:?} x {b_shape:?}");
+                assert_eq!(to_array(db), db_true, "{a_shape:?} x {b_shape:?}");
+            }
+            let a = to_variable(&ArrayD::zeros(vec![2, 3]));
+            let b = to_variable(&ArrayD::zeros(vec![2, 3]));
+            assert!(a.matmul(&b).is_err());
+            let b = to_variable(&ArrayD::zeros(vec![3, 3, 2]));
+            assert!(a.matmul(&b).is_ok());
+            let a = to_variable(&ArrayD::zeros(vec![2, 2, 3]));
+            assert!(a.matmul(&b).is_err());
+        }
+
+        fn variable_cat(device: &Device) {
+            use autograph::{learn::neural_network::autograd::Variable2, tensor::ScalarArcTensor2};
+            use ndarray::{concatenate, Array2};
+
+            let to_variable = |x: &Array2<f32>| {
+                Variable::builder().node().build(
+                    Tensor::from(x.clone())
+                        .into_device(device.clone())
+                        .unwrap()
+                        .into_shared()
+                        .unwrap()
+                        .into(),
+                )
+            };
+            let to_array =
+                |x: ScalarArcTensor2| x.cast_into_tensor::<f32>().unwrap().into_array().unwrap();
+            for (axis, shapes) in [
+                (Axis(0), [[2, 3], [1, 3], [3, 3]]),
+                (Axis(1), [[2, 1], [2, 4], [2, 2]]),
+            ] {
+                let arrays: Vec<Array2<f32>> = shapes
+                    .iter()
+                    .enumerate()
+                    .map(|(i, shape)| {
+                        Array2::from_shape_fn(*shape, |(r, c)| (10 * i + 3 * r + c) as f32)
+                    })
+                    .collect();
+                let views: Vec<_> = arrays.iter().map(Array2::view).collect();
+                let y_true = concatenate(axis, &views).unwrap();
+                let x: Vec<Variable2> = arrays.iter().map(to_variable).collect();
+                let y = Variable::cat(&x, axis).unwrap();
+                let mut builder = Variable::concat_builder(axis);
+                for x in x.iter() {
+                    builder.push(x.clone());
+                }
+                assert_eq!(builder.len(), x.len());
+                let y2 = builder.build().unwrap();
+                assert_eq!(to_array(y.value().clone()), y_true);
+                assert_eq!(to_array(y2.value().clone()), y_true);
+                let g_array = y_true.map(|x| 2. * x + 1.);
+                y.node()
+                    .unwrap()
+                    .backward_grad(
+                        Tensor::from(g_array.clone())
+                            .into_device(device.clone())
+                            .unwrap()
+                            .into_shared()
+                            .unwrap()
+                            .into(),
+                    )
+                    .unwrap();
+                for (x, x_array) in x.iter().zip(arrays.iter()) {
+                    let dx = to_array(x.node().unwrap().grad().unwrap());
+                    assert_eq!(dx, x_array.map(|x| 2. * x + 1.));
+                }
+            }
+            let x = to_variable(&Array2::zeros([2, 3]));
+            assert!(
+                Variable::cat(&[x.clone(), to_variable(&Array2::zeros([3, 2]))], Axis(0)).is_err()
+            );
+            assert!(Variable2::cat(&[], Axis(0)).is_err());
+            assert!(Variable::cat(&[x], Axis(2)).is_err());
+            assert!(Variable2::concat_builder(Axis(0)).build().is_err());
+        }
+
+        fn custom_optimizer_rmsprop(device: &Device) {
+            use autograph::{
+                learn::neural_network::{
+                    autograd::{Parameter1, ParameterViewMutD},
+                    optimizer::{Optimizer, TensorValue, Value, ValueMut},
+                },
+                tensor::ScalarTensor,
+            };
+            use krnl::scalar::ScalarElem;
+            use std::any::TypeId;
+
+            struct RMSprop {
+                alpha: f32,
+                eps: f32,
+            }
+
+            impl Optimizer for RMSprop {
+                fn update(
+                    &self,
+                    learning_rate: f32,
+                    mut parameter: ParameterViewMutD,
+                ) -> autograph::anyhow::Result<()> {
+                    let Some(grad) = parameter.grad() else {
+                        return Ok(());
+                    };
+                    let device = parameter.device();
+                    if parameter.optimizer_state().map(|state| state.id())
+                        != Some(TypeId::of::<Self>())
+                    {
+                        let square_avg = ScalarTensor::zeros(
+                            device.clone(),
+                            parameter.raw_dim(),
+                            ScalarType::F32,
+                        )?;
+                        let square_avg = TensorValue::builder(square_avg)
+                            .parameter_device(true)
+                            .build();
+                        parameter.init_optimizer_state(
+                            "RMSprop",
+                            TypeId::of::<Self>(),
+                            [("square_avg".to_string(), Value::Tensor(square_avg))],
+                        )?;
+                    }
+                    let grad = grad.cast_into_tensor::<f32>()?.into_array()?;
+                    let state = parameter.optimizer_state_mut().unwrap();
+                    let Some((_, ValueMut::Tensor(mut square_avg))) = state.iter_mut().next()
+                    else {
+                        unreachable!()
+                    };
+                    let mut square_avg_array =
+                        square_avg.view().cast_into_tensor::<f32>()?.into_array()?;
+                    square_avg_array.zip_mut_with(&grad, |s, g| {
+                        *s = self.alpha * *s + (1. - self.alpha) * g * g;
+                    });
+                    let step = &grad / &square_avg_array.mapv(|s| s.sqrt() + self.eps);
+                    square_avg.assign(
+                        &Tensor::from(square_avg_array)
+                            .into_device(device.clone())?
+                            .into_scalar_tensor(),
+                    )?;
+                    let step = Tensor::from(step).into_device(device)?.into_scalar_tensor();
+                    parameter
+                        .value_view_mut()
+                        .scaled_add(ScalarElem::F32(-learning_rate), &step)
+                }
+            }
+
+            let x_array = Array::from(vec![3f32, -2., 1., 5.]);
+            let mut x =
+                Parameter1::from(Tensor::from(x_array).into_device(device.clone()).unwrap());
+            let loss = |x: &Parameter1| {
+                let x = x
+                    .value()
+                    .clone()
+                    .cast_into_tensor::<f32>()
+                    .unwrap()
+                    .into_array()
+                    .unwrap();
+                x.dot(&x)
+            };
+            let loss_initial = loss(&x);
+            let optimizer = RMSprop {
+                alpha: 0.9,
+                eps: 1e-8,
+            };
+            for i in 0..200 {
+                x.set_training(true);
+                // loss = x * x
+                let y = x.to_variable();
+                let y = y.mul(&y).unwrap();
+                let ones = Tensor::<f32, _>::ones(device.clone(), 4).unwrap();
+                y.node()
+                    .unwrap()
+                    .backward_grad(ones.into_shared().unwrap().into())
+                    .unwrap();
+                if i == 0 {
+                    let grad = x.grad().unwrap();
+                    {
+                        let mut grad_mut = x.grad_mut().unwrap();
+                        assert_eq!(
+                            grad_mut.view().to_vec_scalar().unwrap(),
+                            grad.to_vec_scalar().unwrap()
+                        );
+                        // scale invariant
+                        grad_mut
+                            .view_mut()
+                            .unwrap()
+                            .scaled_add(ScalarElem::F32(-0.5), &grad)
+                            .unwrap();
+                    }
+                    let grad = grad
+                        .cast_into_tensor::<f32>()
+                        .unwrap()
+                        .into_array()
+                        .unwrap();
+                    let grad_half = x
+                        .grad()
+                        .unwrap()
+                        .cast_into_tensor::<f32>()
+                        .unwrap()
+                        .into_array()
+                        .unwrap();
+                    assert_eq!(grad_half, grad / 2.);
+                }
+                optimizer
+                    .update(0.05, x.make_view_mut().unwrap().into_dyn())
+                    .unwrap();
+                x.set_training(false);
+            }
+            let loss_final = loss(&x);
+            assert!(
+                loss_final < loss_initial / 1000.,
+                "{loss_initial} -> {loss_final}"
+            );
+        }
+
+        fn optimizer_adamw(device: &Device) {
+            use autograph::learn::neural_network::{
+                autograd::Parameter1,
+                optimizer::{Adam, AdamW, Optimizer},
+            };
+
+            fn train(
+                device: &Device,
+                optimizer: &dyn Optimizer,
+                grads: &[[f32; 4]],
+            ) -> Array1<f32> {
+                let x = Array::from(vec![1f32, -2., 0.5, 4.]);
+                let mut x = Parameter1::from(Tensor::from(x).into_device(device.clone()).unwrap());
+                for grad in grads {
+                    x.set_training(true);
+                    let grad = Tensor::from(grad.to_vec())
+                        .into_device(device.clone())
+                        .unwrap();
+                    x.to_variable()
+                        .node()
+                        .unwrap()
+                        .backward_grad(grad.into_shared().unwrap().into())
+                        .unwrap();
+                    optimizer
+                        .update(0.1, x.make_view_mut().unwrap().into_dyn())
+                        .unwrap();
+                    x.set_training(false);
+                }
+                x.value()
+                    .clone()
+                    .cast_into_tensor::<f32>()
+                    .unwrap()
+                    .into_array()
+                    .unwrap()
+            }
+
+            let grads = [[0.5, -1., 2., 0.], [0.25, 1., -3., 0.], [1., 0., 1., 0.]];
+            let adam = train(device, &Adam::default(), &grads);
+            let adamw = train(
+                device,
+                &AdamW::builder().weight_decay(0.).build().unwrap(),
+                &grads,
+            );
+            assert_eq!(adam, adamw);
+            // The first step is -learning_rate * sign(grad).
+            let adam = train(device, &Adam::default(), &grads[..1]);
+            assert_relative_eq!(
+                adam,
+                Array::from(vec![0.9, -1.9, 0.4, 4.]),
+                max_relative = 1e-6
+            );
+            // Decoupled weight decay decays the weights without a gradient.
+            let zeros = [[0.; 4]; 2];
+            let adamw = train(
+                device,
+                &AdamW::builder().weight_decay(0.5).build().unwrap(),
+                &zeros,
+            );
+            let decay = (1f32 - 0.1 * 0.5).powi(2);
+            assert_relative_eq!(
+                adamw,
+                Array::from(vec![decay, -2. * decay, 0.5 * decay, 4. * decay]),
+                max_relative = 1e-6
+            );
+            // The L2 penalty is scaled by the adaptive update.
+            let adam = train(
+                device,
+                &Adam::builder().weight_decay(0.5).build().unwrap(),
+                &zeros[..1],
+            );
+            assert_relative_eq!(
+                adam,
+                Array::from(vec![0.9, -1.9, 0.4, 3.9]),
+                max_relative = 1e-6
+            );
+            assert!(Adam::builder().betas(0.9, 1.).build().is_err());
+            assert!(AdamW::builder().weight_decay(-1.).build().is_err());
+        }
+
+        fn lr_warmup() {
+            use autograph::learn::neural_network::optimizer::{LrScheduler, Warmup, WarmupKind};
+
+            let inner = |step: usize| 0.1 * 0.5f32.powi((step / 10) as i32);
+            let warmup = Warmup::builder(inner, 5).start_learning_rate(0.01).build();
+            assert_eq!(warmup.learning_rate(0), 0.01);
+            assert_relative_eq!(warmup.learning_rate(2), 0.01 + 0.09 * 0.4);
+            assert_eq!(warmup.learning_rate(5), 0.1);
+            for step in 5..40 {
+                assert_eq!(warmup.learning_rate(step), inner(step - 5));
+            }
+            let warmup = Warmup::builder(inner, 4).kind(WarmupKind::Cosine).build();
+            assert_eq!(warmup.learning_rate(0), 0.);
+            assert_relative_eq!(warmup.learning_rate(2), 0.05);
+            assert!(warmup.learning_rate(1) < 0.025);
+            assert_eq!(warmup.learning_rate(4), 0.1);
+            let warmup = Warmup::builder(0.1, 0).build();
+            assert_eq!(warmup.learning_rate(0), 0.1);
+        }
+
+        fn grad_accumulation(device: &Device) {
+            use autograph::{
+                learn::neural_network::layer::{Dense, Layer},
+                tensor::ScalarArcTensor2,
+            };
+            use ndarray::Array2;
+
+            let mut dense = Dense::builder()
+                .inputs(4)
+                .outputs(3)
+                .bias(true)
+                .device(device.clone())
+                .build()
+                .unwrap();
+            dense.set_training(true).unwrap();
+            let to_tensor = |x: Array2<f32>| -> ScalarArcTensor2 {
+                Tensor::from(x)
+                    .into_device(device.clone())
+                    .unwrap()
+                    .into_shared()
+                    .unwrap()
+                    .into()
+            };
+            let x = to_tensor(Array::from_shape_fn([2, 4], |(i, j)| (i + j) as f32));
+            let grad_a = Array::from_shape_fn([2, 3], |(i, j)| (i * 3 + j) as f32);
+            let grad_b = Array::from_shape_fn([2, 3], |(i, j)| (2 * i + j) as f32 - 2.);
+            let grads = |dense: &Dense| {
+                dense
+                    .parameters()
+                    .iter()
+                    .map(|parameter| {
+                        parameter
+                            .grad()
+                            .unwrap()
+                            .cast_into_tensor::<f32>()
+                            .unwrap()
+                            .into_array()
+                            .unwrap()
+                    })
+                    .collect::<Vec<_>>()
+            };
+            // The gradient of the sum of the losses.
+            let y = dense.forward(Variable::from(x.clone())).unwrap();
+            y.node()
+                .unwrap()
+                .backward_grad(to_tensor(&grad_a + &grad_b))
+                .unwrap();
+            let grads_sum = grads(&dense);
+            dense.zero_grad().unwrap();
+            assert!(dense.parameters().iter().all(|x| x.grad().is_none()));
+            // Accumulated over backward passes of each loss.
+            for grad in [grad_a, grad_b] {
+                let y = dense.forward(Variable::from(x.clone())).unwrap();
+                y.node().unwrap().backward_grad(to_tensor(grad)).unwrap();
+            }
+            assert_eq!(grads(&dense), grads_sum);
+        }
+
+        fn save_load_reader(device: &Device) {
+            use autograph::learn::neural_network::layer::{Dense, Layer};
+            use std::io::Cursor;
+
+            let dense = || {
+                Dense::builder()
+                    .inputs(4)
+                    .outputs(3)
+                    .bias(true)
+                    .device(device.clone())
+                    .build()
+                    .unwrap()
+            };
+            let parameters = |dense: &Dense| {
+                dense
+                    .parameters()
+                    .iter()
+                    .map(|parameter| {
+                        parameter
+                            .value()
+                            .clone()
+                            .cast_into_tensor::<f32>()
+                            .unwrap()
+                            .into_array()
+                            .unwrap()
+                    })
+                    .collect::<Vec<_>>()
+            };
+            let a = dense();
+            let mut b = dense();
+            assert_ne!(parameters(&a), parameters(&b));
+            let mut cursor = Cursor::new(Vec::new());
+            a.save_to_writer(&mut cursor).unwrap();
+            cursor.set_position(0);
+            b.load_from_reader(&mut cursor).unwrap();
+            assert_eq!(parameters(&a), parameters(&b));
+            let mut c = Dense::builder().inputs(2).outputs(3).build().unwrap();
+            cursor.set_position(0);
+            assert!(c.load_from_reader(&mut cursor).is_err());
+            assert!(b.load_from_reader([0u8; 8].as_slice()).is_err());
+        }
+
+        fn dense_shared_weight(device: &Device) {
+            use autograph::learn::neural_network::{
+                layer::{Dense, Layer},
+                optimizer::{Optimizer, SGD},
+            };
+
+            #[derive(Layer)]
+            struct Network {
+                a: Dense,
+                b: Dense,
+            }
+
+            let mut a = Dense::builder()
+                .inputs(4)
+                .outputs(3)
+                .device(device.clone())
+                .build()
+                .unwrap();
+            let b = Dense::builder()
+                .weight(a.weight_mut().share())
+                .build()
+                .unwrap();
+            let mut network = Network { a, b };
+            assert!(network.a.weight().is_shared_with(network.b.weight()));
+            let weight = network.a.weight().value().to_owned().unwrap();
+            network.set_training(true).unwrap();
+            let x = Tensor::from(Array::from_elem([2, 4], 1f32))
+                .into_device(device.clone())
+                .unwrap();
+            for layer in [&network.a, &network.b] {
+                let y = layer
+                    .forward(Variable::from(x.to_owned().unwrap()))
+                    .unwrap();
+                y.node().unwrap().backward().unwrap();
+            }
+            let grad = |layer: &Dense| {
+                layer
+                    .weight()
+                    .grad()
+                    .unwrap()
+                    .into_owned()
+                    .unwrap()
+                    .try_into_tensor::<f32>()
+                    .unwrap()
+                    .into_array()
+                    .unwrap()
+            };
+            assert_eq!(grad(&network.a), Array::from_elem([4, 3], 4f32));
+            let optimizer = SGD::builder().build();
+            let learning_rate = 0.5;
+            let parameters = network.parameters_mut().unwrap();
+            // the tied weight is returned once, so the gradient is applied once
+            assert_eq!(parameters.len(), 1);
+            for parameter in parameters {
+                optimizer.update(learning_rate, parameter).unwrap();
+            }
+            let weight = weight
+                .try_into_tensor::<f32>()
+                .unwrap()
+                .into_array()
+                .unwrap()
+                - 2f32;
+            for layer in [&network.a, &network.b] {
+                let value = layer
+                    .weight()
+                    .value()
+                    .to_owned()
+                    .unwrap()
+                    .try_into_tensor::<f32>()
+                    .unwrap()
+                    .into_array()
+                    .unwrap();
+                assert_eq!(value, weight);
+            }
+            // the gradient accumulates until zero_grad
+            assert_eq!(grad(&network.b), Array::from_elem([4, 3], 4f32));
+            network.a.zero_grad().unwrap();
+            assert!(network.b.weight().grad().is_none());
+        }
+
+        fn dense_shared_weight_optimizer(device: &Device) {
+            use autograph::learn::neural_network::{
+                autograd::Parameter2,
+                layer::{Dense, Layer},
+                optimizer::{EmaModel, Optimizer, SGD},
+            };
+
+            let mut a = Dense::builder()
+                .inputs(4)
+                .outputs(3)
+                .device(device.clone())
+                .build()
+                .unwrap();
+            let b = Dense::builder()
+                .weight(a.weight_mut().share())
+                .build()
+                .unwrap();
+            let mut layers = vec![a, b];
+            layers.set_training(true).unwrap();
+            let names = layers
+                .named_parameters_mut()
+                .unwrap()
+                .into_iter()
+                .map(|(name, _)| name)
+                .collect::<Vec<_>>();
+            assert_eq!(names, ["0.weight"]);
+            assert_eq!(layers.parameters().len(), 1);
+            assert_eq!(layers.named_parameters().len(), 1);
+            let x = Tensor::from(Array::from_elem([2, 4], 1f32))
+                .into_device(device.clone())
+                .unwrap();
+            let value = |parameter: &Parameter2| {
+                parameter
+                    .value()
+                    .to_owned()
+                    .unwrap()
+                    .try_into_tensor::<f32>()
+                    .unwrap()
+                    .into_array()
+                    .unwrap()
+            };
+            let optimizer = SGD::builder().momentum(0.5).build();
+            let learning_rate = 0.5;
+            let mut weight = value(layers[0].weight());
+            let mut velocity = Array::<f32, _>::zeros(weight.raw_dim());
+            for _ in 0..2 {
+                for layer in layers.iter() {
+                    let y = layer
+                        .forward(Variable::from(x.to_owned().unwrap()))
+                        .unwrap();
+                    y.node().unwrap().backward().unwrap();
+                }
+                velocity = velocity * 0.5 + 4f32;
+                weight = weight - learning_rate * &velocity;
+                for parameter in layers.parameters_mut().unwrap() {
+                    optimizer.update(learning_rate, parameter).unwrap();
+                }
+                layers.zero_grad().unwrap();
+                for layer in layers.iter() {
+                    assert_eq!(value(layer.weight()), weight);
+                    assert_eq!(
+                        layer
+                            .weight()
+                            .to_variable()
+                            .into_value()
+                            .into_owned()
+                            .unwrap()
+                            .try_into_tensor::<f32>()
+                            .unwrap()
+                            .into_array()
+                            .unwrap(),
+                        weight
+                    );
+                    // the copies share the optimizer state
+                    assert!(layer.weight().optimizer_state().is_some());
+                }
+            }
+            let mut ema = EmaModel::new(&layers, 0.5).unwrap();
+            ema.update(&layers).unwrap();
+            ema.swap(&mut layers).unwrap();
+        }
+
+        fn layer_seed(device: &Device) {
+            use autograph::learn::neural_network::layer::{Conv2, Dense, Layer};
+
+            fn parameter_values(layer: &impl Layer) -> Vec<Vec<f32>> {
+                layer
+                    .parameters()
+                    .into_iter()
+                    .map(|parameter| {
+                        parameter
+                            .value()
+                            .clone()
+                            .into_owned()
+                            .unwrap()
+                            .try_into_tensor::<f32>()
+                            .unwrap()
+                            .into_array()
+                            .unwrap()
+                            .into_raw_vec()
+                    })
+                    .collect()
+            }
+
+            let conv = |device: Device| {
+                Conv2::builder()
+                    .inputs(2)
+                    .outputs(3)
+                    .filter([3, 3])
+                    .bias(true)
+                    .seed(7)
+                    .device(device)
+                    .build()
+                    .unwrap()
+            };
+            assert_eq!(
+                parameter_values(&conv(Device::host())),
+                parameter_values(&conv(device.clone())),
+            );
+            let dense = |device: Device| {
+                Dense::builder()
+                    .inputs(4)
+                    .outputs(3)
+                    .bias(true)
+                    .seed(7)
+                    .device(device)
+                    .build()
+                    .unwrap()
+            };
+            assert_eq!(
+                parameter_values(&dense(Device::host())),
+                parameter_values(&dense(device.clone())),
+            );
+        }
+
+        fn masked_fill(device: &Device) {
+            let x_array =
+                Array::from_shape_vec([2, 3], (1..=6).map(|x| x as f32).collect()).unwrap();
+            let mask_array = Array::from_shape_vec([3], vec![1u8, 0, 1]).unwrap();
+            let x = Tensor::from(x_array.clone())
+                .into_device(device.clone())
+                .unwrap();
+            let mask = Tensor::from(mask_array)
+                .into_device(device.clone())
+                .unwrap()
+                .into_shared()
+                .unwrap();
+            let x_var = Variable::builder()
+                .node()
+                .build(x.into_shared().unwrap().into());
+            let y_var = x_var
+                .masked_fill(mask, ScalarElem::F32(f32::NEG_INFINITY))
+                .unwrap();
+            let y = y_var
+                .value()
+                .clone()
+                .into_owned()
+                .unwrap()
+                .try_into_tensor::<f32>()
+                .unwrap()
+                .into_array()
+                .unwrap();
+            let inf = f32::NEG_INFINITY;
+            assert_eq!(y, ndarray::arr2(&[[inf, 2., inf], [inf, 5., inf]]));
+            y_var.node().unwrap().backward().unwrap();
+            let dx = x_var
+                .node()
+                .unwrap()
+                .grad()
+                .unwrap()
+                .into_owned()
+                .unwrap()
+                .try_into_tensor::<f32>()
+                .unwrap()
+                .into_array()
+                .unwrap();
+            assert_eq!(dx, ndarray::arr2(&[[0., 1., 0.], [0., 1., 0.]]));
+        }
+
+        fn output_shape(device: &Device) {
+            use autograph::learn::neural_network::layer::Conv2;
+
+            for (filter, padding, stride, dilation) in [
+                ([3, 3], [0, 0], [1, 1], [1, 1]),
+                ([5, 3], [2, 1], [1, 1], [1, 1]),
+                ([3, 3], [1, 1], [2, 2], [1, 1]),
+                ([3, 2], [0, 1], [1, 2], [2, 1]),
+            ] {
+                let conv = Conv2::builder()
+                    .inputs(2)
+                    .outputs(3)
+                    .filter(filter)
+                    .padding(padding)
+                    .stride(stride)
+                    .dilation(dilation)
+                    .device(device.clone())
+                    .build()
+                    .unwrap();
+                let x = Tensor::<f32, _>::zeros(device.clone(), [1, 2, 11, 12]).unwrap();
+                let y = conv.forward(Variable::from(x)).unwrap();
+                assert_eq!(
+                    conv.output_shape([11, 12]).unwrap().slice(),
+                    &y.shape()[2..],
+                    "{filter:?} {padding:?} {stride:?} {dilation:?}"
+                );
+            }
+            for (filter, stride) in [([2, 2], [2, 2]), ([3, 2], [1, 2])] {
+                let pool = MaxPool2::builder().filter(filter).stride(stride).build();
+                let x = Tensor::<f32, _>::zeros(device.clone(), [1, 2, 11, 12]).unwrap();
+                let y = pool.forward(Variable::from(x)).unwrap();
+                assert_eq!(
+                    pool.output_shape([11, 12]).unwrap().slice(),
+                    &y.shape()[2..]
+                );
+            }
+            let conv = Conv2::builder()
+                .inputs(2)
+                .outputs(3)
+                .filter([3, 3])
+                .dilation([2, 1])
+                .build()
+                .unwrap();
+            assert!(conv.output_shape([4, 12]).is_err());
+            assert!(conv.output_shape([0, 0]).is_err());
+            let pool = MaxPool2::builder().filter([3, 3]).build();
+            assert!(pool.output_shape([2, 12]).is_err());
+        }
+
+        fn infer_flatten_size_lenet5(device: &Device) {
+            use autograph::learn::neural_network::layer::{
+                infer_flatten_size, Conv2, Flatten, LayerSpec,
+            };
+            use ndarray::Ix2;
+
+            let conv1 = Conv2::builder()
+                .device(device.clone())
+                .inputs(1)
+                .outputs(6)
+                .filter([5, 5])
+                .build()
+                .unwrap();
+            let pool1 = MaxPool2::builder().filter([2, 2]).stride([2, 2]).build();
+            let conv2 = Conv2::builder()
+                .device(device.clone())
+                .inputs(6)
+                .outputs(16)
+                .filter([5, 5])
+                .build()
+                .unwrap();
+            let pool2 = MaxPool2::builder().filter([2, 2]).stride([2, 2]).build();
+            let layers = [
+                (&conv1).into(),
+                (&pool1).into(),
+                (&conv2).into(),
+                (&pool2).into(),
+            ];
+            let inputs = infer_flatten_size(1, [28, 28], &layers).unwrap();
+            assert_eq!(inputs, 16 * 4 * 4);
+            let x = Tensor::<f32, _>::zeros(device.clone(), [2, 1, 28, 28]).unwrap();
+            let y = conv1.forward(Variable::from(x)).unwrap();
+            let y = pool1.forward(y).unwrap();
+            let y = conv2.forward(y).unwrap();
+            let y = pool2.forward(y).unwrap();
+            let y = Flatten.forward(y).unwrap();
+            assert_eq!(y.shape(), &[2, inputs]);
+            assert!(infer_flatten_size(1, [8, 8], &layers).is_err());
+            let zero_stride = LayerSpec::MaxPool {
+                filter: Ix2(2, 2),
+                stride: Ix2(0, 2),
+            };
+            assert!(infer_flatten_size(1, [28, 28], &[zero_stride]).is_err());
+        }
+
+        fn lazy_dense_mlp(device: &Device) {
+            use autograph::learn::{
+                criterion::CrossEntropyLoss,
+                neural_network::{
+                    layer::{Dense, Layer},
+                    optimizer::{Optimizer, SGD},
+                },
+            };
+
+            let mut dense1 = Dense::builder()
+                .outputs(8)
+                .seed(0)
+                .activation(Relu)
+                .device(device.clone())
+                .build_lazy()
+                .unwrap();
+            let mut dense2 = Dense::builder()
+                .outputs(2)
+                .seed(1)
+                .device(device.clone())
+                .build_lazy()
+                .unwrap();
+            assert!(dense1.parameters().is_empty());
+            assert!(dense2.parameters_mut().unwrap().is_empty());
+            let x = Tensor::from(Array::from_shape_fn([4, 3], |(i, j)| (i + j) as f32 / 4.))
+                .into_device(device.clone())
+                .unwrap();
+            let t = Tensor::from(vec![0u8, 1, 0, 1])
+                .into_device(device.clone())
+                .unwrap()
+                .into_shared()
+                .unwrap();
+            let optimizer = SGD::builder().build();
+            let mut losses = Vec::new();
+            for _ in 0..10 {
+                dense1.set_training(true).unwrap();
+                dense2.set_training(true).unwrap();
+                let y = dense1
+                    .forward(Variable::from(x.to_owned().unwrap()))
+                    .unwrap();
+                let y = dense2.forward(y).unwrap();
+                let loss = y.cross_entropy_loss(t.clone().into()).unwrap();
+                losses.push(
+                    loss.value()
+                        .clone()
+                        .cast_into_tensor::<f32>()
+                        .unwrap()
+                        .into_array()
+                        .unwrap()
+                        .into_scalar(),
+                );
+                loss.backward().unwrap();
+                let parameters = dense1
+                    .parameters_mut()
+                    .unwrap()
+                    .into_iter()
+                    .chain(dense2.parameters_mut().unwrap());
+                for parameter in parameters {
+                    optimizer.update(0.5, parameter).unwrap();
+                }
+                dense1.set_training(false).unwrap();
+                dense2.set_training(false).unwrap();
+            }
+            assert_eq!(dense1.get().unwrap().weight().dim(), (3, 8));
+            assert_eq!(dense2.get().unwrap().weight().dim(), (8, 2));
+            assert_eq!(dense1.parameters().len(), 1);
+            assert!(
+                losses.last().unwrap() < losses.first().unwrap(),
+                "{losses:?}"
+            );
+        }
+
+        fn lazy_build_error(device: &Device) {
+            use autograph::learn::neural_network::layer::{Conv2, Dense, Layer};
+
+            let mut dense = Dense::builder()
+                .outputs(2)
+                .compute_scalar_type(ScalarType::U8)
+                .device(device.clone())
+                .build_lazy()
+                .unwrap();
+            let x = Tensor::from(vec![1f32; 4 * 3])
+                .into_shape([4, 3])
+                .unwrap()
+                .into_device(device.clone())
+                .unwrap();
+            // The builder is kept when the build fails, so each forward reports the cause.
+            for _ in 0..2 {
+                let error = dense
+                    .forward(Variable::from(x.to_owned().unwrap()))
+                    .unwrap_err();
+                assert!(error.to_string().contains("compute_scalar_type"), "{error}");
+                assert!(dense.get().is_none());
+            }
+            dense.cast_mut(ScalarType::BF16).unwrap();
+            assert!(dense.parameters().is_empty());
+
+            let conv = Conv2::builder()
+                .outputs(2)
+                .filter([3, 3])
+                .compute_scalar_type(ScalarType::U8)
+                .device(device.clone())
+                .build_lazy()
+                .unwrap();
+            let x = Tensor::from(vec![1f32; 3 * 5 * 5])
+                .into_shape([1, 3, 5, 5])
+                .unwrap()
+                .into_device(device.clone())
+                .unwrap();
+            for _ in 0..2 {
+                let error = conv
+                    .forward(Variable::from(x.to_owned().unwrap()))
+                    .unwrap_err();
+                assert!(error.to_string().contains("compute_scalar_type"), "{error}");
+                assert!(conv.get().is_none());
+            }
+        }
+
+        fn predict_batched(device: &Device) {
+            use approx::assert_relative_eq;
+            use autograph::learn::neural_network::{
+                autograd::no_grad,
+                layer::{Dense, Layer},
+            };
+
+            let mut dense = Dense::builder()
+                .inputs(5)
+                .outputs(3)
+                .bias(true)
+                .seed(0)
+                .device(device.clone())
+                .build()
+                .unwrap();
+            dense.set_training(true).unwrap();
+            let x = Tensor::from(Array::from_shape_fn([10, 5], |(i, j)| {
+                (i * 5 + j) as f32 / 50.
+            }))
+            .into_device(device.clone())
+            .unwrap()
+            .into_shared()
+            .unwrap();
+            let y = dense.forward(Variable::from(x.clone())).unwrap();
+            assert!(y.node().is_some());
+            let y = y.into_value().cast_into_tensor::<f32>().unwrap();
+            let y = y.into_array().unwrap();
+            for batch_size in [1, 4, 10, 16] {
+                let y_batched = dense
+                    .predict_batched(x.clone().into(), batch_size)
+                    .unwrap()
+                    .cast_into_tensor::<f32>()
+                    .unwrap()
+                    .into_array()
+                    .unwrap();
+                assert_relative_eq!(y_batched, y, epsilon = 1e-6);
+            }
+            let y = no_grad(|| dense.forward(Variable::from(x.clone()))).unwrap();
+            assert!(y.node().is_none());
+        }
+
+        fn dense_flatten_shape_mismatch(device: &Device) {
+            use autograph::{
+                error::AutographError,
+                learn::neural_network::layer::{Dense, Flatten},
+            };
+
+            let dense = Dense::builder()
+                .inputs(16)
+                .outputs(3)
+                .device(device.clone())
+                .build()
+                .unwrap();
+            let forward = |size: usize| {
+                let x = Tensor::<f32, _>::zeros(device.clone(), [2, 1, size, size]).unwrap();
+                dense.forward(Flatten.forward(Variable::from(x))?)
+            };
+            assert_eq!(forward(4).unwrap().shape(), [2, 3]);
+            let error = forward(5).unwrap_err();
+            assert_eq!(
+                error.downcast_ref::<AutographError>(),
+                Some(&AutographError::ShapeMismatch {
+                    op: "Dense input".to_string(),
+                    expected: vec![2, 16],
+                    actual: vec![2, 25],
+                })
+            );
+        }
+
+        fn set_weight(device: &Device) {
+            use autograph::learn::neural_network::layer::{Conv2, Dense};
+            use ndarray::{arr1, arr2, Array4};
+
+            let mut dense = Dense::builder()
+                .inputs(2)
+                .outputs(3)
+                .bias(true)
+                .device(device.clone())
+                .build()
+                .unwrap();
+            dense
+                .set_weight(&arr2(&[[1f32, 0., 1.], [0., 1., 1.]]))
+                .unwrap();
+            dense.set_bias(&arr1(&[0f32, 0., 1.])).unwrap();
+            assert!(dense.set_weight(&arr2(&[[1f32, 0.], [0., 1.]])).is_err());
+            let x = Tensor::from(arr2(&[[1f32, 2.], [3., 4.]]))
+                .into_device(device.clone())
+                .unwrap();
+            let y = dense
+                .forward(Variable::from(x))
+                .unwrap()
+                .into_value()
+                .cast_into_tensor::<f32>()
+                .unwrap()
+                .into_array()
+                .unwrap();
+            assert_eq!(y, arr2(&[[1f32, 2., 4.], [3., 4., 8.]]));
+            let mut dense_t = Dense::builder()
+                .inputs(2)
+                .outputs(3)
+                .bias(true)
+                .device(device.clone())
+                .build()
+                .unwrap();
+            dense_t
+                .set_weight_transposed(&arr2(&[[1f32, 0.], [0., 1.], [1., 1.]]))
+                .unwrap();
+            dense_t.set_bias(&arr1(&[0f32, 0., 1.])).unwrap();
+            assert!(dense_t
+                .set_weight_transposed(&arr2(&[[1f32, 0., 1.], [0., 1., 1.]]))
+                .is_err());
+            let x = Tensor::from(arr2(&[[1f32, 2.], [3., 4.]]))
+                .into_device(device.clone())
+                .unwrap();
+            let y_t = dense_t
+                .forward(Variable::from(x))
+                .unwrap()
+                .into_value()
+                .cast_into_tensor::<f32>()
+                .unwrap()
+                .into_array()
+                .unwrap();
+            assert_eq!(y_t, y);
+
+            let mut conv = Conv2::builder()
+                .inputs(1)
+                .outputs(1)
+                .filter([2, 2])
+                .device(device.clone())
+                .build()
+                .unwrap();
+            conv.set_weight(
+                &Array4::from_shape_vec([1, 1, 2, 2], vec![1f32, 0., 0., -1.]).unwrap(),
+            )
+            .unwrap();
+            assert!(conv.set_bias(&arr1(&[1f32])).is_err());
+            let x = Tensor::from(
+                Array4::from_shape_vec([1, 1, 3, 3], (0..9).map(|x| x as f32).collect()).unwrap(),
+            )
+            .into_device(device.clone())
+            .unwrap();
+            let y = conv
+                .forward(Variable::from(x))
+                .unwrap()
+                .into_value()
+                .cast_into_tensor::<f32>()
+                .unwrap()
+                .into_array()
+                .unwrap();
+            assert_eq!(y, Array4::from_elem([1, 1, 2, 2], -4f32));
+        }
+
+        fn conv_weight_standardization(device: &Device) {
+            use approx::assert_relative_eq;
+            use autograph::{
+                learn::neural_network::layer::{Conv2, Layer},
+                ops::Conv2dOptions,
+            };
+            use ndarray::{Array4, Ix4};
+
+            let x = Tensor::from(
+                Array::from_iter((0..2 * 3 * 5 * 4).map(|x| ((x % 7) as f32 - 3.) / 2.))
+                    .into_shape([2, 3, 5, 4])
+                    .unwrap(),
+            )
+            .into_device(device.clone())
+            .unwrap()
+            .into_shared()
+            .unwrap();
+            let weight = Array::from_shape_fn([4, 3, 2, 2], |(o, i, h, w)| {
+                ((o * 5 + i * 3 + h * 2 + w) % 9) as f32 / 4. + o as f32
+            });
+            let mut conv = Conv2::builder()
+                .inputs(3)
+                .outputs(4)
+                .filter([2, 2])
+                .weight_standardization(true)
+                .device(device.clone())
+                .build()
+                .unwrap();
+            assert!(conv.weight_standardization());
+            conv.set_weight(&weight).unwrap();
+            conv.set_training(true).unwrap();
+            let mut weight_standardized = weight.clone();
+            for mut filter in weight_standardized.outer_iter_mut() {
+                let mean = filter.mean().unwrap();
+                let var = filter.mapv(|x| (x - mean).powi(2)).mean().unwrap();
+                filter.mapv_inplace(|x| (x - mean) / (var + 1e-5).sqrt());
+            }
+            for filter in weight_standardized.outer_iter() {
+                assert_relative_eq!(filter.mean().unwrap(), 0., epsilon = 1e-5);
+                assert_relative_eq!(filter.var(0.), 1., epsilon = 1e-3);
+            }
+            let y = conv.forward(Variable::from(x.clone())).unwrap();
+            let y_array = y
+                .value()
+                .clone()
+                .cast_into_tensor::<f32>()
+                .unwrap()
+                .into_array()
+                .unwrap();
+            let y_expected = x
+                .conv2d(
+                    &Tensor::from(weight_standardized)
+                        .into_device(device.clone())
+                        .unwrap(),
+                    None::<&Tensor<f32, _>>,
+                    &Conv2dOptions::default(),
+                )
+                .unwrap()
+                .into_array()
+                .unwrap();
+            assert_relative_eq!(y_array, y_expected, epsilon = 1e-4, max_relative = 1e-4);
+            let output_grad = Array::from_shape_fn(y_array.raw_dim(), |(n, c, h, w)| {
+                ((n + 2 * c + 3 * h + w) % 5) as f32 / 2. - 1.
+            });
+            y.node()
+                .unwrap()
+                .backward_grad(
+                    Tensor::from(output_grad.clone())
+                        .into_device(device.clone())
+                        .unwrap()
+                        .into_shared()
+                        .unwrap()
+                        .into(),
+                )
+                .unwrap();
+            let weight_grad = conv.parameters()[0]
+                .grad()
+                .unwrap()
+                .cast_into_tensor::<f32>()
+                .unwrap()
+                .into_array()
+                .unwrap();
+            conv.set_training(false).unwrap();
+            let mut loss = |weight: &Array4<f32>| {
+                conv.set_weight(weight).unwrap();
+                let y = conv
+                    .forward(Variable::from(x.clone()))
+                    .unwrap()
+                    .into_value()
+                    .cast_into_tensor::<f32>()
+                    .unwrap()
+                    .into_array()
+                    .unwrap()
+                    .into_dimensionality::<Ix4>()
+                    .unwrap();
+                (&y * &output_grad).sum()
+            };
+            let h = 1e-2;
+            for index in [(0, 0, 0, 0), (1, 2, 1, 0), (3, 1, 0, 1)] {
+                let mut weight_plus = weight.clone();
+                weight_plus[index] += h;
+                let mut weight_minus = weight.clone();
+                weight_minus[index] -= h;
+                let grad = (loss(&weight_plus) - loss(&weight_minus)) / (2. * h);
+                assert_relative_eq!(
+                    weight_grad[index],
+                    grad,
+                    epsilon = 1e-2,
+                    max_relative = 1e-2
+                );
+            }
+        }
+
+        fn conv2d_max_pool2d(device: &Device) {
+            use approx::assert_relative_eq;
+            use autograph::{
+                learn::neural_network::layer::{Conv2, Layer},
+                ops::{Conv2dOptions, MaxPool2dOptions},
+            };
+            use ndarray::{Ix1, Ix4};
+
+            let x_array = Array::from_iter((0..2 * 3 * 9 * 8).map(|x| ((x % 13) as f32 - 6.) / 4.))
+                .into_shape([2, 3, 9, 8])
+                .unwrap();
+            let x = Tensor::from(x_array.clone())
+                .into_device(device.clone())
+                .unwrap()
+                .into_shared()
+                .unwrap();
+            for (padding, stride, dilation) in [
+                ([0, 0], [1, 1], [1, 1]),
+                ([1, 2], [2, 1], [1, 1]),
+                ([1, 1], [1, 2], [2, 1]),
+            ] {
+                let mut conv = Conv2::builder()
+                    .inputs(3)
+                    .outputs(4)
+                    .filter([3, 2])
+                    .padding(padding)
+                    .stride(stride)
+                    .dilation(dilation)
+                    .bias(true)
+                    .seed(0)
+                    .device(device.clone())
+                    .build()
+                    .unwrap();
+                // Training uses the autograd path of the layer.
+                conv.set_training(true).unwrap();
+                let y_layer = conv
+                    .forward(Variable::from(x.clone()))
+                    .unwrap()
+                    .into_value()
+                    .cast_into_tensor::<f32>()
+                    .unwrap()
+                    .into_array()
+                    .unwrap();
+                let parameters = conv.parameters();
+                let weight = parameters[0]
+                    .value()
+                    .clone()
+                    .into_dimensionality::<Ix4>()
+                    .unwrap()
+                    .cast_into_tensor::<f32>()
+                    .unwrap();
+                let bias = parameters[1]
+                    .value()
+                    .clone()
+                    .into_dimensionality::<Ix1>()
+                    .unwrap()
+                    .cast_into_tensor::<f32>()
+                    .unwrap();
+                let options = Conv2dOptions {
+                    padding,
+                    stride,
+                    dilation,
+                };
+                let y = x
+                    .conv2d(&weight, Some(&bias), &options)
+                    .unwrap()
+                    .into_array()
+                    .unwrap();
+                assert_relative_eq!(y, y_layer, epsilon = 1e-5, max_relative = 1e-5);
+            }
+            assert!(x
+                .conv2d(
+                    &Tensor::<f32, _>::zeros(device.clone(), [4, 2, 3, 3]).unwrap(),
+                    None::<&Tensor<f32, _>>,
+                    &Conv2dOptions::default()
+                )
+                .is_err());
+            for (filter, stride) in [([2, 2], [2, 2]), ([3, 2], [2, 1])] {
+                let y = x
+                    .max_pool2d(&MaxPool2dOptions { filter, stride })
+                    .unwrap()
+                    .into_array()
+                    .unwrap();
+                let (oh, ow) = (
+                    (9 - filter[0]) / stride[0] + 1,
+                    (8 - filter[1]) / stride[1] + 1,
+                );
+                let y_array = Array::from_shape_fn([2, 3, oh, ow], |(n, c, i, j)| {
+                    let mut max = f32::NEG_INFINITY;
+                    for fi in 0..filter[0] {
+                        for fj in 0..filter[1] {
+                            max = max.max(x_array[(n, c, i * stride[0] + fi, j * stride[1] + fj)]);
+                        }
+                    }
+                    max
+                });
+                assert_eq!(y, y_array, "filter: {filter:?} stride: {stride:?}");
+            }
+        }
+
+        fn conv2_bias(device: &Device) {
+            use approx::assert_relative_eq;
+            use autograph::learn::neural_network::layer::{Conv2, ConvAlgo, Layer};
+            use ndarray::{Ix1, Ix4};
+
+            // The output width is not equal to the number of outputs.
+            let x_array = Array::from_iter((0..2 * 3).map(|x| x as f32))
+                .into_shape([1, 1, 2, 3])
+                .unwrap();
+            let x = Tensor::from(x_array.clone())
+                .into_device(device.clone())
+                .unwrap()
+                .into_shared()
+                .unwrap();
+            for (algorithm, tile_size) in [
+                (ConvAlgo::Im2Col, None),
+                (ConvAlgo::Im2Col, Some(3)),
+                (ConvAlgo::Direct, None),
+            ] {
+                let mut conv = Conv2::builder()
+                    .inputs(1)
+                    .outputs(2)
+                    .filter([1, 1])
+                    .bias(true)
+                    .seed(0)
+                    .device(device.clone())
+                    .build()
+                    .unwrap();
+                conv.set_algorithm(algorithm);
+                conv.set_tile_size(tile_size);
+                conv.set_training(true).unwrap();
+                let y = conv
+                    .forward(Variable::from(x.clone()))
+                    .unwrap()
+                    .into_value()
+                    .cast_into_tensor::<f32>()
+                    .unwrap()
+                    .into_array()
+                    .unwrap();
+                let parameters = conv.parameters();
+                let weight = parameters[0]
+                    .value()
+                    .clone()
+                    .into_dimensionality::<Ix4>()
+                    .unwrap()
+                    .cast_into_tensor::<f32>()
+                    .unwrap()
+                    .into_array()
+                    .unwrap();
+                let bias = parameters[1]
+                    .value()
+                    .clone()
+                    .into_dimensionality::<Ix1>()
+                    .unwrap()
+                    .cast_into_tensor::<f32>()
+                    .unwrap()
+                    .into_array()
+                    .unwrap();
+                let y_array = Array::from_shape_fn([1, 2, 2, 3], |(_, c, i, j)| {
+                    weight[(c, 0, 0, 0)] * x_array[(0, 0, i, j)] + bias[c]
+                });
+                assert_relative_eq!(y, y_array, epsilon = 1e-5, max_relative = 1e-5);
+            }
+        }
+
+        fn conv2_max_pool2_no_grad(device: &Device) {
+            use approx::assert_relative_eq;
+            use autograph::learn::neural_network::{
+                autograd::Variable4,
+                layer::{Conv2, ConvAlgo, Layer},
+            };
+
+            let x = Tensor::from(
+                Array::from_iter((0..2 * 3 * 9 * 8).map(|x| ((x % 13) as f32 - 6.) / 4.))
+                    .into_shape([2, 3, 9, 8])
+                    .unwrap(),
+            )
+            .into_device(device.clone())
+            .unwrap()
+            .into_shared()
+            .unwrap();
+            let into_array = |y: Variable4| {
+                y.into_value()
+                    .cast_into_tensor::<f32>()
+                    .unwrap()
+                    .into_array()
+                    .unwrap()
+            };
+            for (algorithm, tile_size) in [
+                (ConvAlgo::Im2Col, None),
+                (ConvAlgo::Im2Col, Some(16)),
+                (ConvAlgo::Direct, None),
+                (ConvAlgo::Auto, None),
+            ] {
+                let mut conv = Conv2::builder()
+                    .inputs(3)
+                    .outputs(4)
+                    .filter([3, 2])
+                    .padding([1, 2])
+                    .stride([2, 1])
+                    .bias(true)
+                    .seed(0)
+                    .device(device.clone())
+                    .build()
+                    .unwrap();
+                conv.set_algorithm(algorithm);
+                conv.set_tile_size(tile_size);
+                let y = conv.forward(Variable::from(x.clone())).unwrap();
+                assert!(y.node().is_none());
+                let y = into_array(y);
+                conv.set_training(true).unwrap();
+                let y_grad = conv.forward(Variable::from(x.clone())).unwrap();
+                assert!(y_grad.node().is_some());
+                let y_grad = into_array(y_grad);
+                assert_relative_eq!(y, y_grad, epsilon = 1e-5, max_relative = 1e-5);
+            }
+            let x_grad = Variable::builder().node().build(x.clone().into());
+            for (filter, stride) in [([2, 2], [2, 2]), ([3, 2], [2, 3])] {
+                let pool = MaxPool2::builder().filter(filter).stride(stride).build();
+                let y = pool.forward(Variable::from(x.clone())).unwrap();
+                assert!(y.node().is_none());
+                let y_grad = pool.forward(x_grad.clone()).unwrap();
+                assert!(y_grad.node().is_some());
+                assert_eq!(into_array(y), into_array(y_grad));
+            }
+            let pool = MaxPool2::builder().filter([10, 2]).build();
+            assert!(pool.forward(Variable::from(x)).is_err());
+            assert!(pool.forward(x_grad).is_err());
+        }
+
+        fn conv_algorithms(device: &Device) {
+            use autograph::learn::neural_network::layer::{Conv2, ConvAlgo, Layer};
+
+            let x_array = Array::from_iter((0..2 * 3 * 9 * 8).map(|x| ((x % 13) as f32 - 6.) / 4.))
+                .into_shape([2, 3, 9, 8])
+                .unwrap();
+            let x = Tensor::from(x_array)
+                .into_device(device.clone())
+                .unwrap()
+                .into_shared()
+                .unwrap();
+            let conv = |padding: [usize; 2], stride: [usize; 2], dilation: [usize; 2]| {
+                Conv2::builder()
+                    .inputs(3)
+                    .outputs(4)
+                    .filter([3, 2])
+                    .padding(padding)
+                    .stride(stride)
+                    .dilation(dilation)
+                    .bias(true)
+                    .seed(0)
+                    .device(device.clone())
+                    .build()
+                    .unwrap()
+            };
+            for (padding, stride, dilation) in [
+                ([0, 0], [1, 1], [1, 1]),
+                ([1, 2], [2, 1], [1, 1]),
+                ([1, 1], [1, 2], [2, 1]),
+            ] {
+                let mut outputs = Vec::new();
+                for algorithm in [ConvAlgo::Im2Col, ConvAlgo::Direct, ConvAlgo::Auto] {
+                    let mut conv = conv(padding, stride, dilation);
+                    conv.set_algorithm(algorithm);
+                    let y = conv
+                        .forward(Variable::from(x.clone()))
+                        .unwrap()
+                        .into_value()
+                        .cast_into_tensor::<f32>()
+                        .unwrap()
+                        .into_array()
+                        .unwrap();
+                    outputs.push(y);
+                }
+                for y in outputs[1..].iter() {
+                    assert_relative_eq!(y, &outputs[0], epsilon = 1e-5, max_relative = 1e-5);
+                }
+            }
+            let mut grads = Vec::new();
+            for algorithm in [ConvAlgo::Im2Col, ConvAlgo::Direct] {
+                let mut conv = conv([0, 0], [1, 1], [1, 1]);
+                conv.set_algorithm(algorithm);
+                conv.set_training(true).unwrap();
+                let x = Variable::builder().node().build(x.clone().into());
+                let y = conv.forward(x.clone()).unwrap();
+                y.node().unwrap().backward().unwrap();
+                let dx = x.node().unwrap().grad().unwrap();
+                let dw = conv.parameters()[0].grad().unwrap();
+                grads.push([dx.into_dyn(), dw].map(|grad| {
+                    grad.cast_into_tensor::<f32>()
+                        .unwrap()
+                        .into_array()
+                        .unwrap()
+                }));
+            }
+            for (a, b) in grads[0].iter().zip(grads[1].iter()) {
+                assert_relative_eq!(a, b, epsilon = 1e-5, max_relative = 1e-5);
+            }
+        }
+
+        fn conv_deterministic(device: &Device) {
+            use autograph::learn::neural_network::layer::{Conv2, ConvAlgo, Layer};
+
+            let x_array =
+                Array::from_iter((0..4 * 3 * 16 * 16).map(|x| ((x % 17) as f32 - 8.) / 7.))
+                    .into_shape([4, 3, 16, 16])
+                    .unwrap();
+            let x = Tensor::from(x_array)
+                .into_device(device.clone())
+                .unwrap()
+                .into_shared()
+                .unwrap();
+            let run = || {
+                let mut conv = Conv2::builder()
+                    .inputs(3)
+                    .outputs(4)
+                    .filter([3, 3])
+                    .padding([1, 1])
+                    .bias(true)
+                    .seed(0)
+                    .device(device.clone())
+                    .build()
+                    .unwrap();
+                conv.set_algorithm(ConvAlgo::Auto);
+                conv.set_deterministic(true);
+                conv.set_training(true).unwrap();
+                let x = Variable::builder().node().build(x.clone().into());
+                let y = conv.forward(x.clone()).unwrap();
+                y.node().unwrap().backward().unwrap();
+                let dw = conv.parameters()[0].grad().unwrap();
+                [y.into_value().into_dyn(), dw].map(|x| {
+                    x.cast_into_tensor::<f32>()
+                        .unwrap()
+                        .into_vec()
+                        .unwrap()
+                        .into_iter()
+                        .map(f32::to_bits)
+                        .collect::<Vec<_>>()
+                })
+            };
+            let outputs = run();
+            for _ in 0..3 {
+                assert_eq!(run(), outputs);
+            }
+        }
+
+        fn boxed_layers(device: &Device) {
+            use autograph::learn::neural_network::{
+                autograd::Variable2,
+                layer::{Dense, DynLayer, Layer},
+            };
+
+            let dense = |inputs, outputs| {
+                Dense::builder()
+                    .inputs(inputs)
+                    .outputs(outputs)
+                    .bias(true)
+                    .build()
+                    .unwrap()
+            };
+            let mut model: Vec<Box<dyn DynLayer<Variable2>>> =
+                vec![Box::new(dense(4, 6)), Box::new(Relu), Box::new(dense(6, 2))];
+            model.to_device_mut(device.clone()).unwrap();
+            assert_eq!(Layer::device(&model), Some(device.clone()));
+            let names: Vec<_> = model
+                .named_parameters()
+                .into_iter()
+                .map(|(name, _)| name)
+                .collect();
+            assert_eq!(names, ["0.weight", "0.bias", "2.weight", "2.bias"]);
+            assert_eq!(model.parameters_mut().unwrap().len(), 4);
+            model.train().unwrap();
+            let x = Tensor::from(Array::from_elem([3, 4], 1f32))
+                .into_device(device.clone())
+                .unwrap()
+                .into_shared()
+                .unwrap();
+            let y = model.forward(Variable::from(x)).unwrap();
+            assert_eq!(y.shape(), [3, 2]);
+            y.node().unwrap().backward().unwrap();
+            assert!(model.parameters().iter().all(|x| x.grad().is_some()));
+        }
+
+        fn eval_mode(device: &Device) {
+            use autograph::learn::neural_network::layer::{Dense, Layer};
+
+            let mut dense = Dense::builder()
+                .inputs(4)
+                .outputs(3)
+                .bias(true)
+                .activation(Relu)
+                .device(device.clone())
+                .build()
+                .unwrap();
+            let x = Tensor::from(Array::from_elem([2, 4], 1f32))
+                .into_device(device.clone())
+                .unwrap()
+                .into_shared()
+                .unwrap();
+            dense.train().unwrap();
+            let y = dense.forward(Variable::from(x.clone())).unwrap();
+            assert!(y.node().is_some());
+            dense.eval().unwrap();
+            let y = dense.forward(Variable::from(x)).unwrap();
+            assert!(y.node().is_none());
+            assert!(dense.parameters().iter().all(|x| x.grad().is_none()));
+        }
+
+        fn benchmark_forward(device: &Device) {
+            use autograph::learn::neural_network::layer::{Dense, Layer};
+
+            let dense = Dense::builder()
+                .inputs(16)
+                .outputs(8)
+                .bias(true)
+                .device(device.clone())
+                .build()
+                .unwrap();
+            let duration = dense.benchmark_forward([4, 16], 3).unwrap();
+            assert!(duration.as_nanos() > 0);
+            assert!(dense.benchmark_forward([4, 16], 0).is_err());
+            assert!(dense.benchmark_forward([4, 15], 1).is_err());
+            let duration = Relu.benchmark_forward([4, 16], 2).unwrap();
+            assert!(duration.as_nanos() > 0);
+        }
+
+        fn conv_memory_format(device: &Device) {
+            use autograph::{
+                learn::neural_network::layer::{BiasInit, Conv2, ConvAlgo, Layer, MemoryFormat},
+                tensor::ScalarArcTensor4,
+            };
+            use ndarray::Array4;
+
+            let x_array = Array::from_iter((0..2 * 3 * 7 * 6).map(|x| ((x % 13) as f32 - 6.) / 5.))
+                .into_shape([2, 3, 7, 6])
+                .unwrap();
+            let to_array = |x: ScalarArcTensor4| -> Array4<f32> {
+                x.cast_into_tensor::<f32>().unwrap().into_array().unwrap()
+            };
+            let run = |memory_format: MemoryFormat, algorithm: ConvAlgo| {
+                let mut conv = Conv2::builder()
+                    .inputs(3)
+                    .outputs(4)
+                    .filter([3, 3])
+                    .padding([1, 1])
+                    .stride([1, 2])
+                    .bias(true)
+                    .bias_init(BiasInit::Uniform)
+                    .memory_format(memory_format)
+                    .seed(0)
+                    .device(device.clone())
+                    .build()
+                    .unwrap();
+                conv.set_algorithm(algorithm);
+                conv.set_training(true).unwrap();
+                let x_array = if memory_format == MemoryFormat::ChannelsLast {
+                    x_array.view().permuted_axes([0, 2, 3, 1])
+                } else {
+                    x_array.view()
+                };
+                let x = Tensor::from(x_array.as_standard_layout().into_owned())
+                    .into_device(device.clone())
+                    .unwrap()
+                    .into_shared()
+                    .unwrap();
+                let x = Variable::builder().node().build(x.into());
+                let y = conv.forward(x.clone()).unwrap();
+                y.node().unwrap().backward().unwrap();
+                let dw = to_array(
+                    conv.parameters()[0]
+                        .grad()
+                        .unwrap()
+                        .into_dimensionality()
+                        .unwrap(),
+                );
+                let mut y = to_array(y.into_value());
+                let mut dx = to_array(x.node().unwrap().grad().unwrap());
+                if memory_format == MemoryFormat::ChannelsLast {
+                    y = y.permuted_axes([0, 3, 1, 2]);
+                    dx = dx.permuted_axes([0, 3, 1, 2]);
+                }
+                [y, dx, dw]
+            };
+            for algorithm in [ConvAlgo::Im2Col, ConvAlgo::Direct] {
+                let first = run(MemoryFormat::ChannelsFirst, algorithm);
+                let last = run(MemoryFormat::ChannelsLast, algorithm);
+                for (a, b) in first.iter().zip(last.iter()) {
+                    assert_eq!(a.shape(), b.shape(), "{algorithm:?}");
+                    for (a, b) in a.iter().zip(b.iter()) {
+                        assert!((a - b).abs() < 1e-5, "{algorithm:?} {a} != {b}");
+                    }
+                }
+            }
+        }
+
+        fn conv_asymmetric_padding(device: &Device) {
+            use autograph::learn::neural_network::layer::{Conv2, Layer};
+            use ndarray::{s, Array4, Ix2};
+
+            let x_array = Array::from_iter((0..2 * 2 * 5 * 6).map(|x| ((x % 11) as f32 - 5.) / 4.))
+                .into_shape([2, 2, 5, 6])
+                .unwrap();
+            let mut x_padded_array = Array4::<f32>::zeros([2, 2, 5 + 1 + 2, 6 + 1]);
+            x_padded_array
+                .slice_mut(s![.., .., 1..6, ..6])
+                .assign(&x_array);
+            let builder = || {
+                Conv2::builder()
+                    .inputs(2)
+                    .outputs(3)
+                    .filter([4, 3])
+                    .seed(0)
+                    .device(device.clone())
+            };
+            let conv = builder()
+                .asymmetric_padding([1, 0], [2, 1])
+                .build()
+                .unwrap();
+            let reference = builder().build().unwrap();
+            assert_eq!(conv.output_shape([5, 6]).unwrap(), Ix2(5, 5));
+            let run = |conv: &Conv2, x_array: Array4<f32>| {
+                let x = Variable::builder().node().build(
+                    Tensor::from(x_array)
+                        .into_device(device.clone())
+                        .unwrap()
+                        .into_shared()
+                        .unwrap()
+                        .into(),
+                );
+                let y = conv.forward(x.clone()).unwrap();
+                y.node().unwrap().backward().unwrap();
+                let y = y
+                    .into_value()
+                    .cast_into_tensor::<f32>()
+                    .unwrap()
+                    .into_array()
+                    .unwrap();
+                let dx = x
+                    .node()
+                    .unwrap()
+                    .grad()
+                    .unwrap()
+                    .cast_into_tensor::<f32>()
+                    .unwrap()
+                    .into_array()
+                    .unwrap();
+                (y, dx)
+            };
+            let (y, dx) = run(&conv, x_array);
+            let (y_true, dx_padded) = run(&reference, x_padded_array);
+            assert_eq!(y.shape(), [2, 3, 5, 5]);
+            assert_relative_eq!(y, y_true, epsilon = 1e-5);
+            assert_relative_eq!(dx, dx_padded.slice(s![.., .., 1..6, ..6]), epsilon = 1e-5);
+        }
+
+        fn conv2_reference(
+            x: &ndarray::Array4<f32>,
+            w: &ndarray::Array4<f32>,
+            padding: [usize; 2],
+            stride: [usize; 2],
+            dilation: [usize; 2],
+        ) -> ndarray::Array4<f32> {
+            let (n, c, ih, iw) = x.dim();
+            let (oc, _, fh, fw) = w.dim();
+            let oh = (ih + 2 * padding[0] - dilation[0] * (fh - 1) - 1) / stride[0] + 1;
+            let ow = (iw + 2 * padding[1] - dilation[1] * (fw - 1) - 1) / stride[1] + 1;
+            ndarray::Array4::from_shape_fn([n, oc, oh, ow], |(b, o, i, j)| {
+                let mut acc = 0f32;
+                for ci in 0..c {
+                    for fi in 0..fh {
+                        for fj in 0..fw {
+                            let hi =
+                                (i * stride[0] + fi * dilation[0]) as isize - padding[0] as isize;
+                            let wi =
+                                (j * stride[1] + fj * dilation[1]) as isize - padding[1] as isize;
+                            if hi >= 0 && hi < ih as isize && wi >= 0 && wi < iw as isize {
+                                acc += x[(b, ci, hi as usize, wi as usize)] * w[(o, ci, fi, fj)];
+                            }
+                        }
+                    }
+                }
+                acc
+            })
+        }
+
+        fn conv1_dilation(device: &Device) {
+            use autograph::learn::neural_network::layer::{Conv1, ConvAlgo};
+            use ndarray::{Array3, Ix1};
+
+            let x_array = Array::from_iter((0..2 * 2 * 9).map(|x| ((x % 7) as f32 - 3.) / 4.))
+                .into_shape([2, 2, 9])
+                .unwrap();
+            let w_array = Array::from_iter((0..3 * 2 * 3).map(|x| ((x % 5) as f32 - 2.) / 3.))
+                .into_shape([3, 2, 3])
+                .unwrap();
+            let y_true = conv2_reference(
+                &x_array.clone().into_shape([2, 2, 9, 1]).unwrap(),
+                &w_array.clone().into_shape([3, 2, 3, 1]).unwrap(),
+                [1, 0],
+                [1, 1],
+                [2, 1],
+            )
+            .into_shape([2, 3, 7])
+            .unwrap();
+            let mut conv = Conv1::builder()
+                .inputs(2)
+                .outputs(3)
+                .filter(3)
+                .padding(1)
+                .dilation(2)
+                .device(device.clone())
+                .build()
+                .unwrap();
+            conv.set_weight(&w_array).unwrap();
+            assert_eq!(conv.output_shape(9).unwrap(), Ix1(7));
+            for algorithm in [ConvAlgo::Im2Col, ConvAlgo::Direct] {
+                conv.set_algorithm(algorithm);
+                let x = Tensor::from(x_array.clone())
+                    .into_device(device.clone())
+                    .unwrap();
+                let y: Array3<f32> = conv
+                    .forward(Variable::from(x))
+                    .unwrap()
+                    .into_value()
+                    .cast_into_tensor::<f32>()
+                    .unwrap()
+                    .into_array()
+                    .unwrap();
+                assert_relative_eq!(y, y_true, epsilon = 1e-5);
+            }
+        }
+
+        fn conv2_dilation(device: &Device) {
+            use autograph::learn::neural_network::layer::{Conv2, ConvAlgo};
+            use ndarray::{Array4, Ix2};
+
+            let x_array = Array::from_iter((0..2 * 2 * 7 * 8).map(|x| ((x % 11) as f32 - 5.) / 4.))
+                .into_shape([2, 2, 7, 8])
+                .unwrap();
+            let w_array = Array::from_iter((0..3 * 2 * 3 * 2).map(|x| ((x % 5) as f32 - 2.) / 3.))
+                .into_shape([3, 2, 3, 2])
+                .unwrap();
+            let y_true = conv2_reference(&x_array, &w_array, [1, 1], [1, 2], [2, 3]);
+            assert_eq!(y_true.shape(), [2, 3, 5, 4]);
+            let mut conv = Conv2::builder()
+                .inputs(2)
+                .outputs(3)
+                .filter([3, 2])
+                .padding([1, 1])
+                .stride([1, 2])
+                .dilation([2, 3])
+                .device(device.clone())
+                .build()
+                .unwrap();
+            conv.set_weight(&w_array).unwrap();
+            assert_eq!(conv.output_shape([7, 8]).unwrap(), Ix2(5, 4));
+            for algorithm in [ConvAlgo::Im2Col, ConvAlgo::Direct] {
+                conv.set_algorithm(algorithm);
+                let x = Tensor::from(x_array.clone())
+                    .into_device(device.clone())
+                    .unwrap();
+                let y: Array4<f32> = conv
+                    .forward(Variable::from(x))
+                    .unwrap()
+                    .into_value()
+                    .cast_into_tensor::<f32>()
+                    .unwrap()
+                    .into_array()
+                    .unwrap();
+                assert_relative_eq!(y, y_true, epsilon = 1e-5);
+            }
+        }
+
+        fn layer_to_dtype(device: &Device) {
+            use autograph::{
+                learn::neural_network::{
+                    autograd::Variable2,
+                    layer::{BatchNorm, Dense, Layer},
+                },
+                tensor::ScalarTensor,
+            };
+
+            let mut dense = Dense::builder()
+                .inputs(8)
+                .outputs(4)
+                .bias(true)
+                .device(device.clone())
+                .build()
+                .unwrap();
+            let mut batch_norm = BatchNorm::builder()
+                .channels(4)
+                .device(device.clone())
+                .build()
+                .unwrap();
+            batch_norm.eval().unwrap();
+            let x = Tensor::from(
+                Array::from_iter((0..16 * 8).map(|x| ((x * 7) % 13) as f32 / 13. - 0.5))
+                    .into_shape([16, 8])
+                    .unwrap(),
+            )
+            .into_device(device.clone())
+            .unwrap();
+            let forward = |dense: &Dense, batch_norm: &BatchNorm, scalar_type| {
+                let x = ScalarTensor::from(x.to_owned().unwrap())
+                    .cast_into(scalar_type)
+                    .unwrap()
+                    .into_shared()
+                    .unwrap();
+                let y = batch_norm
+                    .forward(dense.forward(Variable2::from(x)).unwrap())
+                    .unwrap();
+                assert_eq!(y.scalar_type(), scalar_type);
+                y.into_value()
+                    .cast_into_tensor::<f32>()
+                    .unwrap()
+                    .into_array()
+                    .unwrap()
+            };
+            let y_f32 = forward(&dense, &batch_norm, ScalarType::F32);
+            dense = dense.to_dtype(ScalarType::BF16).unwrap();
+            batch_norm = batch_norm.to_dtype(ScalarType::BF16).unwrap();
+            assert_eq!(Layer::scalar_type(&dense), Some(ScalarType::BF16));
+            assert_eq!(Layer::scalar_type(&batch_norm), Some(ScalarType::BF16));
+            let y_bf16 = forward(&dense, &batch_norm, ScalarType::BF16);
+            assert_relative_eq!(y_bf16, y_f32, epsilon = 0.05, max_relative = 0.05);
+        }
+
+        fn conv_compute_scalar_type(device: &Device) {
+            use autograph::{
+                learn::neural_network::layer::{BiasInit, Conv2, Layer},
+                tensor::ScalarArcTensor4,
+            };
+            use ndarray::Array4;
+
+            let builder = || {
+                Conv2::builder()
+                    .inputs(3)
+                    .outputs(4)
+                    .filter([3, 3])
+                    .padding([1, 1])
+                    .bias(true)
+                    .bias_init(BiasInit::Uniform)
+                    .seed(0)
+                    .scalar_type(ScalarType::BF16)
+                    .device(device.clone())
+            };
+            let conv = builder().build().unwrap();
+            let mixed = builder()
+                .compute_scalar_type(ScalarType::F32)
+                .build()
+                .unwrap();
+            let mut reference = conv.clone();
+            reference.cast_mut(ScalarType::F32).unwrap();
+            let x_array = Array::from_iter(
+                (0..2 * 3 * 9 * 9).map(|x| bf16::from_f32(((x % 17) as f32 - 8.) / 7.)),
+            )
+            .into_shape([2, 3, 9, 9])
+            .unwrap();
+            let x = Tensor::from(x_array)
+                .into_device(device.clone())
+                .unwrap()
+                .into_shared()
+                .unwrap();
+            let forward = |conv: &Conv2, x: ScalarArcTensor4| {
+                let y = conv.forward(Variable::builder().build(x)).unwrap();
+                let scalar_type = y.scalar_type();
+                let y = y
+                    .into_value()
+                    .cast_into_tensor::<f32>()
+                    .unwrap()
+                    .into_array()
+                    .unwrap();
+                (y, scalar_type)
+            };
+            let (y_bf16, scalar_type) = forward(&conv, x.clone().into());
+            assert_eq!(scalar_type, ScalarType::BF16);
+            let (y_mixed, scalar_type) = forward(&mixed, x.clone().into());
+            assert_eq!(scalar_type, ScalarType::F32);
+            let (y_true, _) = forward(
+                &reference,
+                x.cast::<f32>().unwrap().into_shared().unwrap().into(),
+            );
+            let error = |y: &Array4<f32>| (y - &y_true).map(|x| x.abs()).sum();
+            assert!(error(&y_mixed) < error(&y_bf16));
+        }
+
+        fn layer_device_mismatch(device: &Device) {
+            use autograph::learn::neural_network::layer::{Conv2, Dense, Layer};
+
+            if device.is_host() {
+                return;
+            }
+            let mut conv = Conv2::builder()
+                .inputs(1)
+                .outputs(2)
+                .filter([3, 3])
+                .device(device.clone())
+                .build()
+                .unwrap();
+            let x = Variable::from(Tensor::<f32, _>::zeros(Device::host(), [1, 1, 5, 5]).unwrap());
+            let error = conv.forward(x.clone()).unwrap_err();
+            assert!(error.to_string().contains("does not match"), "{error}");
+            conv.to_device_mut(Device::host()).unwrap();
+            conv.forward(x).unwrap();
+            let mut dense = Dense::builder()
+                .inputs(3)
+                .outputs(2)
+                .device(device.clone())
+                .build()
+                .unwrap();
+            let x = Variable::from(Tensor::<f32, _>::zeros(Device::host(), [4, 3]).unwrap());
+            let error = dense.forward(x.clone()).unwrap_err();
+            assert!(error.to_string().contains("does not match"), "{error}");
+            dense.to_device_mut(Device::host()).unwrap();
+            dense.forward(x).unwrap();
+        }
+
+        fn forward_auto_device(device: &Device) {
+            use autograph::learn::neural_network::layer::{Conv2, Layer};
+
+            let mut conv = Conv2::builder()
+                .inputs(2)
+                .outputs(3)
+                .filter([3, 3])
+                .bias(true)
+                .seed(0)
+                .device(device.clone())
+                .build()
+                .unwrap();
+            conv.set_training(true).unwrap();
+            let x_array = Array::from_iter((0..2 * 2 * 6 * 5).map(|x| (x % 9) as f32 - 4.))
+                .into_shape([2, 2, 6, 5])
+                .unwrap();
+            let mut outputs = Vec::new();
+            for auto in [false, true] {
+                let x = Tensor::from(x_array.clone());
+                let x = if auto {
+                    x
+                } else {
+                    x.into_device(device.clone()).unwrap()
+                };
+                let x = Variable::builder().node().build(x.into());
+                let y = if auto {
+                    conv.forward_auto_device(x.clone()).unwrap()
+                } else {
+                    conv.forward(x.clone()).unwrap()
+                };
+                y.node().unwrap().backward().unwrap();
+                let dx = x.node().unwrap().grad().unwrap();
+                assert_eq!(dx.device(), x.device());
+                outputs.push(
+                    [y.into_value().into_dyn(), dx.into_dyn()]
+                        .map(|x| x.cast_into_tensor::<f32>().unwrap().into_array().unwrap()),
+                );
+            }
+            assert_eq!(outputs[0], outputs[1]);
+        }
+
+        fn two_heads(device: &Device) {
+            use autograph::{
+                learn::{
+                    criterion::CrossEntropyLoss,
+                    neural_network::{
+                        autograd::Variable2,
+                        layer::{Dense, Layer},
+                    },
+                },
+                ops::AddAssign,
+            };
+
+            #[derive(Layer)]
+            struct TwoHeads {
+                trunk: Dense<Relu>,
+                head: Dense,
+                aux: Dense,
+            }
+
+            impl Forward<Variable2> for TwoHeads {
+                type Output = (Variable2, Variable2);
+                fn forward(&self, input: Variable2) -> autograph::anyhow::Result<Self::Output> {
+                    let x = self.trunk.forward(input)?;
+                    Ok((self.head.forward(x.clone())?, self.aux.forward(x)?))
+                }
+            }
+
+            let dense = |inputs, outputs, seed| {
+                Dense::builder()
+                    .inputs(inputs)
+                    .outputs(outputs)
+                    .bias(true)
+                    .seed(seed)
+                    .device(device.clone())
+            };
+            let mut model = TwoHeads {
+                trunk: dense(3, 5, 0).activation(Relu).build().unwrap(),
+                head: dense(5, 2, 1).build().unwrap(),
+                aux: dense(5, 4, 2).build().unwrap(),
+            };
+            let x = Tensor::from(Array::from_shape_fn([4, 3], |(i, j)| (i + j) as f32 / 4.))
+                .into_device(device.clone())
+                .unwrap();
+            let t = Tensor::from(vec![0u8, 1, 1, 0])
+                .into_device(device.clone())
+                .unwrap()
+                .into_shared()
+                .unwrap();
+            let grads = |model: &TwoHeads| {
+                model
+                    .parameters()
+                    .iter()
+                    .map(|parameter| {
+                        parameter
+                            .grad()
+                            .unwrap()
+                            .cast_into_tensor::<f32>()
+                            .unwrap()
+                            .into_array()
+                            .unwrap()
+                    })
+                    .collect::<Vec<_>>()
+            };
+            model.set_training(true).unwrap();
+            let (y, y_aux) = model
+                .forward(Variable::from(x.to_owned().unwrap()))
+                .unwrap();
+            let mut loss = y.cross_entropy_loss(t.clone().into()).unwrap();
+            loss.add_assign(y_aux.cross_entropy_loss(t.clone().into()).unwrap())
+                .unwrap();
+            loss.backward().unwrap();
+            // Gradients reach the trunk and both heads.
+            let grads_sum = grads(&model);
+            assert_eq!(grads_sum.len(), 6);
+            model.zero_grad().unwrap();
+            // The trunk gradient is the sum of the gradients of each loss.
+            for aux in [false, true] {
+                let (y, y_aux) = model
+                    .forward(Variable::from(x.to_owned().unwrap()))
+                    .unwrap();
+                let y = if aux { y_aux } else { y };
+                let loss = y.cross_entropy_loss(t.clone().into()).unwrap();
+                loss.backward().unwrap();
+            }
+            for (a, b) in grads(&model).iter().zip(grads_sum.iter()) {
+                assert_relative_eq!(a, b, epsilon = 1e-6, max_relative = 1e-5);
+            }
+        }
+
+        fn lenet5_grad_norms(device: &Device) {
+            use autograph::learn::{
+                criterion::CrossEntropyLoss,
+                neural_network::{
+                    autograd::{Variable2, Variable4},
+                    layer::{grad_norms, Conv2, Dense, Flatten, Layer},
+                },
+            };
+
+            #[derive(Layer, Forward)]
+            #[autograph(forward(Variable4, Output=Variable2))]
+            struct LeNet5 {
+                conv1: Conv2<Relu>,
+                pool1: MaxPool2,
+                conv2: Conv2<Relu>,
+                pool2: MaxPool2,
+                flatten: Flatten,
+                dense1: Dense<Relu>,
+                dense2: Dense<Relu>,
+                dense3: Dense,
+            }
+
+            let conv = |inputs, outputs| {
+                Conv2::builder()
+                    .device(device.clone())
+                    .inputs(inputs)
+                    .outputs(outputs)
+                    .filter([5, 5])
+                    .activation(Relu)
+                    .build()
+                    .unwrap()
+            };
+            let dense = |inputs, outputs| {
+                Dense::builder()
+                    .device(device.clone())
+                    .inputs(inputs)
+                    .outputs(outputs)
+            };
+            let pool = || MaxPool2::builder().filter([2, 2]).stride([2, 2]).build();
+            let mut model = LeNet5 {
+                conv1: conv(1, 6),
+                pool1: pool(),
+                conv2: conv(6, 16),
+                pool2: pool(),
+                flatten: Flatten,
+                dense1: dense(16 * 4 * 4, 120).activation(Relu).build().unwrap(),
+                dense2: dense(120, 84).activation(Relu).build().unwrap(),
+                dense3: dense(84, 10).bias(true).build().unwrap(),
+            };
+            let x = Tensor::from(Array::from_shape_fn([2, 1, 28, 28], |(b, _, i, j)| {
+                ((b + i * j) % 7) as f32 / 7.
+            }))
+            .into_device(device.clone())
+            .unwrap();
+            let t = Tensor::from(vec![3u8, 7])
+                .into_device(device.clone())
+                .unwrap()
+                .into_shared()
+                .unwrap();
+            assert!(grad_norms(&model.named_parameters()).unwrap().is_empty());
+            model.set_training(true).unwrap();
+            let y = model.forward(Variable::from(x)).unwrap();
+            y.cross_entropy_loss(t.into()).unwrap().backward().unwrap();
+            let norms = grad_norms(&model.named_parameters()).unwrap();
+            assert_eq!(
+                norms.keys().map(String::as_str).collect::<Vec<_>>(),
+                ["conv1", "conv2", "dense1", "dense2", "dense3"]
+            );
+            for (layer, norm) in norms {
+                assert!(norm.is_finite(), "{layer}: {norm}");
+            }
+        }
+
+        fn checkpoint(device: &Device) {
+            use autograph::learn::neural_network::{
+                autograd::{self, Variable2},
+                layer::{Dense, Layer},
+            };
+            use std::sync::{
+                atomic::{AtomicUsize, Ordering},
+                Arc,
+            };
+
+            // Counts the edges it adds to the graph.
+            #[derive(Clone, Default)]
+            struct Probe {
+                edges: Arc<AtomicUsize>,
+            }
+
+            impl Layer for Probe {}
+
+            impl Forward<Variable2> for Probe {
+                type Output = Variable2;
+                fn forward(&self, input: Variable2) -> autograph::anyhow::Result<Variable2> {
+                    let mut builder = Variable::builder();
+                    if let Some(node) = input.node() {
+                        builder.edge(node, Ok);
+                    }
+                    let output = builder.build(input.into_value());
+                    if output.node().is_some() {
+                        self.edges.fetch_add(1, Ordering::SeqCst);
+                    }
+                    Ok(output)
+                }
+            }
+
+            #[derive(Layer, Forward, Clone)]
+            #[autograph(forward(Variable2, Output=Variable2))]
+            struct Block {
+                dense1: Dense<Relu>,
+                probe1: Probe,
+                dense2: Dense<Relu>,
+                probe2: Probe,
+            }
+
+            let dense = |inputs, outputs, seed| {
+                Dense::builder()
+                    .inputs(inputs)
+                    .outputs(outputs)
+                    .bias(true)
+                    .activation(Relu)
+                    .seed(seed)
+                    .device(device.clone())
+                    .build()
+                    .unwrap()
+            };
+            let edges = Arc::new(AtomicUsize::default());
+            let probe = Probe {
+                edges: edges.clone(),
+            };
+            let mut block = Block {
+                dense1: dense(3, 8, 0),
+                probe1: probe.clone(),
+                dense2: dense(8, 4, 1),
+                probe2: probe,
+            };
+            block.set_training(true).unwrap();
+            let x = Tensor::from(Array::from_shape_fn([5, 3], |(i, j)| {
+                (i as f32 - j as f32) / 4.
+            }))
+            .into_device(device.clone())
+            .unwrap()
+            .into_scalar_tensor()
+            .into_shared()
+            .unwrap();
+            let mut grads = Vec::new();
+            for checkpoint in [false, true] {
+                block.zero_grad().unwrap();
+                edges.store(0, Ordering::SeqCst);
+                let x = Variable::builder().node().build(x.clone());
+                let y = if checkpoint {
+                    autograd::checkpoint(&block, x.clone()).unwrap()
+                } else {
+                    block.forward(x.clone()).unwrap()
+                };
+                // Activations within the checkpoint are not stored.
+                assert_eq!(edges.load(Ordering::SeqCst), if checkpoint { 0 } else { 2 });
+                y.node().unwrap().backward().unwrap();
+                let mut grad = vec![x.node().unwrap().grad().unwrap().into_dyn()];
+                grad.extend(block.parameters().iter().map(|p| p.grad().unwrap()));
+                let grad = grad
+                    .into_iter()
+                    .map(|g| g.cast_into_tensor::<f32>().unwrap().into_array().unwrap())
+                    .collect::<Vec<_>>();
+                grads.push(grad);
+            }
+            assert_eq!(grads[0].len(), 5);
+            for (a, b) in grads[0].iter().zip(grads[1].iter()) {
+                assert_relative_eq!(a, b, epsilon = 1e-6, max_relative = 1e-5);
+            }
+        }
+
+        fn layer_device_scalar_type(device: &Device) {
+            use autograph::learn::neural_network::{
+                autograd::{Variable2, Variable4},
+                layer::{infer_flatten_size, Conv2, Dense, Flatten, Layer},
+            };
+
+            #[derive(Layer, Forward)]
+            #[autograph(forward(Variable4, Output=Variable2))]
+            struct LeNet5 {
+                conv1: Conv2<Relu>,
+                pool1: MaxPool2,
+                conv2: Conv2<Relu>,
+                pool2: MaxPool2,
+                flatten: Flatten,
+                dense1: Dense<Relu>,
+                dense2: Dense<Relu>,
+                dense3: Dense,
+            }
+
+            let scalar_type = ScalarType::F32;
+            let conv = |inputs, outputs| {
+                Conv2::builder()
+                    .device(device.clone())
+                    .scalar_type(scalar_type)
+                    .inputs(inputs)
+                    .outputs(outputs)
+                    .filter([5, 5])
+                    .activation(Relu)
+                    .build()
+                    .unwrap()
+            };
+            let dense = |inputs, outputs| {
+                Dense::builder()
+                    .device(device.clone())
+                    .scalar_type(scalar_type)
+                    .inputs(inputs)
+                    .outputs(outputs)
+            };
+            let conv1 = conv(1, 6);
+            let pool1 = MaxPool2::builder().filter([2, 2]).stride([2, 2]).build();
+            let conv2 = conv(6, 16);
+            let pool2 = MaxPool2::builder().filter([2, 2]).stride([2, 2]).build();
+            let inputs = infer_flatten_size(
+                1,
+                [28, 28],
+                &[
+                    (&conv1).into(),
+                    (&pool1).into(),
+                    (&conv2).into(),
+                    (&pool2).into(),
+                ],
+            )
+            .unwrap();
+            let model = LeNet5 {
+                conv1,
+                pool1,
+                conv2,
+                pool2,
+                flatten: Flatten,
+                dense1: dense(inputs, 120).activation(Relu).build().unwrap(),
+                dense2: dense(120, 84).activation(Relu).build().unwrap(),
+                dense3: dense(84, 10).bias(true).build().unwrap(),
+            };
+            assert_eq!(model.device(), Some(device.clone()));
+            assert_eq!(model.scalar_type(), Some(scalar_type));
+            assert_eq!(Layer::device(&model.pool1), None);
+            assert_eq!(Layer::scalar_type(&model.flatten), None);
+            let lazy = Dense::builder()
+                .outputs(2)
+                .device(device.clone())
+                .build_lazy()
+                .unwrap();
+            assert_eq!(lazy.device(), None);
+            assert_eq!(lazy.scalar_type(), None);
+        }
+
+        fn batch_norm_track_running_stats(device: &Device) {
+            use approx::assert_relative_eq;
+            use autograph::{
+                learn::neural_network::{
+                    autograd::Parameter1,
+                    layer::{BatchNorm, Layer},
+                    optimizer::{Optimizer, SGD},
+                },
+                tensor::ArcTensor1,
+            };
+
+            let mut batch_norm = BatchNorm::builder()
+                .channels(3)
+                .device(device.clone())
+                .build()
+                .unwrap();
+            batch_norm.set_training(true).unwrap();
+            let to_array = |x: ArcTensor1<f32>| x.into_owned().unwrap().into_array().unwrap();
+            let parameter_array = |x: &Parameter1| {
+                x.value()
+                    .clone()
+                    .cast_into_tensor::<f32>()
+                    .unwrap()
+                    .into_array()
+                    .unwrap()
+            };
+            let optimizer = SGD::builder().build();
+            let step = |batch_norm: &mut BatchNorm, i: usize| {
+                let x = Array::from_shape_fn([4, 3, 2, 2], |(n, c, h, w)| {
+                    ((n * 7 + c * 5 + h * 3 + w + i) % 11) as f32 + c as f32
+                });
+                let y = batch_norm
+                    .forward(Variable::from(
+                        Tensor::from(x).into_device(device.clone()).unwrap(),
+                    ))
+                    .unwrap();
+                let output = y
+                    .value()
+                    .clone()
+                    .cast_into_tensor::<f32>()
+                    .unwrap()
+                    .into_array()
+                    .unwrap();
+                let mean = output
+                    .mean_axis(Axis(0))
+                    .unwrap()
+                    .mean_axis(Axis(1))
+                    .unwrap()
+                    .mean_axis(Axis(1))
+                    .unwrap();
+                let bias = parameter_array(batch_norm.bias());
+                assert_relative_eq!(mean, bias, epsilon = 1e-4);
+                let grad = Tensor::from(Array::from_shape_fn([4, 3, 2, 2], |(n, c, h, w)| {
+                    (n + c + h * w) as f32 / 8. - 0.5
+                }))
+                .into_device(device.clone())
+                .unwrap()
+                .into_shared()
+                .unwrap();
+                y.node().unwrap().backward_grad(grad.into()).unwrap();
+                for parameter in batch_norm.parameters_mut().unwrap() {
+                    optimizer.update(0.1, parameter).unwrap();
+                }
+            };
+            step(&mut batch_norm, 0);
+            let running_mean = to_array(batch_norm.running_mean());
+            let running_var = to_array(batch_norm.running_var());
+            assert_ne!(running_mean, Array::zeros(3));
+            assert_ne!(running_var, Array::ones(3));
+            batch_norm.set_track_running_stats(false);
+            let weight = parameter_array(batch_norm.weight());
+            let bias = parameter_array(batch_norm.bias());
+            for i in 1..4 {
+                step(&mut batch_norm, i);
+            }
+            assert_eq!(to_array(batch_norm.running_mean()), running_mean);
+            assert_eq!(to_array(batch_norm.running_var()), running_var);
+            assert_ne!(parameter_array(batch_norm.weight()), weight);
+            assert_ne!(parameter_array(batch_norm.bias()), bias);
+        }
+
+        fn affine(device: &Device) {
+            use approx::assert_relative_eq;
+            use autograph::learn::neural_network::{
+                autograd::{Parameter, Variable4},
+                layer::{Affine, Conv2, Layer},
+            };
+            use ndarray::{arr1, Array4};
+
+            let mut affine = Affine::builder()
+                .channels(3)
+                .device(device.clone())
+                .build()
+                .unwrap();
+            let scale = arr1(&[0.5f32, -1., 2.]);
+            let shift = arr1(&[0.1f32, 0.2, -0.3]);
+            *affine.scale_mut() = Parameter::from(
+                Tensor::from(scale.clone())
+                    .into_device(device.clone())
+                    .unwrap(),
+            );
+            *affine.shift_mut() = Parameter::from(
+                Tensor::from(shift.clone())
+                    .into_device(device.clone())
+                    .unwrap(),
+            );
+            affine.set_training(true).unwrap();
+            let x_array = Array::from_shape_fn([2, 3, 2, 2], |(n, c, h, w)| {
+                (n * 12 + c * 4 + h * 2 + w) as f32 / 8. - 1.
+            });
+            let g = Array::from_shape_fn([2, 3, 2, 2], |(n, c, h, w)| {
+                ((n + 2 * c + 3 * h + w) % 5) as f32 / 2. - 1.
+            });
+            let to_tensor = |x: &Array4<f32>| {
+                Tensor::from(x.clone())
+                    .into_device(device.clone())
+                    .unwrap()
+                    .into_shared()
+                    .unwrap()
+            };
+            let to_array = |x: autograph::tensor::ScalarArcTensorD| {
+                x.cast_into_tensor::<f32>().unwrap().into_array().unwrap()
+            };
+            let x: Variable4 = Variable::builder().node().build(to_tensor(&x_array).into());
+            let y = affine.forward(x.clone()).unwrap();
+            let y_true = Array::from_shape_fn([2, 3, 2, 2], |(n, c, h, w)| {
+                scale[c] * x_array[(n, c, h, w)] + shift[c]
+            });
+            assert_relative_eq!(
+                to_array(y.value().clone().into_dyn()),
+                y_true.into_dyn(),
+                epsilon = 1e-6
+            );
+            y.node()
+                .unwrap()
+                .backward_grad(to_tensor(&g).into())
+                .unwrap();
+            let dx_true =
+                Array::from_shape_fn([2, 3, 2, 2], |(n, c, h, w)| scale[c] * g[(n, c, h, w)]);
+            let sum_channels =
+                |x: Array4<f32>| x.sum_axis(Axis(0)).sum_axis(Axis(1)).sum_axis(Axis(1));
+            let dscale_true = sum_channels(&x_array * &g);
+            let dshift_true = sum_channels(g.clone());
+            for (grad, grad_true) in [
+                (
+                    x.node().unwrap().grad().unwrap().into_dyn(),
+                    dx_true.into_dyn(),
+                ),
+                (
+                    affine.scale().grad().unwrap().into_dyn(),
+                    dscale_true.into_dyn(),
+                ),
+                (
+                    affine.shift().grad().unwrap().into_dyn(),
+                    dshift_true.into_dyn(),
+                ),
+            ] {
+                assert_relative_eq!(to_array(grad), grad_true, epsilon = 1e-5);
+            }
+
+            #[derive(Layer, Forward)]
+            #[autograph(forward(Variable4, Output=Variable4))]
+            struct Network {
+                conv: Conv2,
+                affine: Affine,
+            }
+
+            let mut network = Network {
+                conv: Conv2::builder()
+                    .inputs(3)
+                    .outputs(2)
+                    .filter([1, 1])
+                    .device(device.clone())
+                    .build()
+                    .unwrap(),
+                affine: Affine::builder()
+                    .channels(2)
+                    .device(device.clone())
+                    .build()
+                    .unwrap(),
+            };
+            network.set_training(true).unwrap();
+            let names: Vec<String> = network
+                .named_parameters()
+                .into_iter()
+                .map(|(name, _)| name)
+                .collect();
+            assert_eq!(names, ["conv.weight", "affine.scale", "affine.shift"]);
+            let y = network
+                .forward(Variable::from(to_tensor(&x_array)))
+                .unwrap();
+            assert_eq!(y.shape(), [2, 2, 2, 2]);
+            y.node()
+                .unwrap()
+                .backward_grad(to_tensor(&Array4::ones([2, 2, 2, 2])).into())
+                .unwrap();
+            assert!(network
+                .parameters()
+                .iter()
+                .all(|parameter| parameter.grad().is_some()));
+        }
+
+        fn spectral_norm_dense(device: &Device) {
+            use approx::assert_relative_eq;
+            use autograph::learn::neural_network::layer::{Dense, Layer, SpectralNorm};
+            use ndarray::{Array1, Array2};
+
+            let mut dense = Dense::builder()
+                .inputs(6)
+                .outputs(4)
+                .device(device.clone())
+                .build()
+                .unwrap();
+            let weight = Array2::from_shape_fn([6, 4], |(i, j)| {
+                if i == j {
+                    [3f32, 1., 0.5, 0.25][i]
+                } else {
+                    ((i + 2 * j) % 5) as f32 / 20.
+                }
+            });
+            dense.set_weight(&weight).unwrap();
+            let mut dense = SpectralNorm::new(dense).unwrap();
+            dense.set_power_iterations(2);
+            dense.set_training(true).unwrap();
+            let identity = Tensor::from(Array2::<f32>::eye(6))
+                .into_device(device.clone())
+                .unwrap()
+                .into_shared()
+                .unwrap();
+            let mut effective_weight = Array2::zeros([6, 4]);
+            for _ in 0..4 {
+                effective_weight = dense
+                    .forward(Variable::from(identity.clone()))
+                    .unwrap()
+                    .into_value()
+                    .cast_into_tensor::<f32>()
+                    .unwrap()
+                    .into_array()
+                    .unwrap();
+            }
+            // The largest singular value is the square root of the largest eigenvalue of W^T W.
+            let gram = effective_weight.t().dot(&effective_weight);
+            let mut v = Array1::<f32>::ones(4);
+            for _ in 0..100 {
+                let w = gram.dot(&v);
+                v = &w / w.dot(&w).sqrt();
+            }
+            let sigma = v.dot(&gram.dot(&v)).sqrt();
+            assert_relative_eq!(sigma, 1., epsilon = 1e-3);
+            assert_eq!(dense.u().len(), 6);
+            // u is not updated while evaluating.
+            dense.set_training(false).unwrap();
+            let u = dense.u().to_vec().unwrap();
+            dense.forward(Variable::from(identity)).unwrap();
+            assert_eq!(dense.u().to_vec().unwrap(), u);
+        }
+
+        fn ema_model(device: &Device) {
+            use approx::assert_relative_eq;
+            use autograph::{
+                learn::neural_network::{
+                    layer::{Dense, Layer},
+                    optimizer::EmaModel,
+                },
+                tensor::ScalarTensor,
+            };
+
+            let mut dense = Dense::builder()
+                .inputs(3)
+                .outputs(2)
+                .bias(true)
+                .seed(0)
+                .device(device.clone())
+                .build()
+                .unwrap();
+            let mut ema = EmaModel::new(&dense, 0.9).unwrap();
+            for mut parameter in dense.parameters_mut().unwrap() {
+                let mut value = parameter.value_view_mut();
+                let ones = ScalarTensor::ones(device.clone(), value.raw_dim(), ScalarType::F32);
+                value.assign(&ones.unwrap()).unwrap();
+            }
+            for _ in 0..200 {
+                ema.update(&dense).unwrap();
+            }
+            for x in ema.parameters() {
+                let x = x
+                    .to_owned()
+                    .unwrap()
+                    .cast_into_tensor::<f32>()
+                    .unwrap()
+                    .into_array()
+                    .unwrap();
+                assert_relative_eq!(x, Array::ones(x.raw_dim()), epsilon = 1e-6);
+            }
+            ema.swap(&mut dense).unwrap();
+            for x in ema.parameters() {
+                let x = x
+                    .to_owned()
+                    .unwrap()
+                    .cast_into_tensor::<f32>()
+                    .unwrap()
+                    .into_array()
+                    .unwrap();
+                assert_eq!(x, Array::ones(x.raw_dim()));
+            }
+            let dense2 = Dense::builder()
+                .inputs(3)
+                .outputs(4)
+                .device(device.clone())
+                .build()
+                .unwrap();
+            assert!(ema.update(&dense2).is_err());
+        }
+
+        fn conv_tiled(device: &Device) {
+            use autograph::learn::neural_network::layer::{Conv2, Layer};
+            use ndarray::Array4;
+
+            let x_array = Array::from_iter((0..3 * 2 * 9 * 7).map(|x| (x % 7) as f32 - 3.))
+                .into_shape([3, 2, 9, 7])
+                .unwrap();
+            let x = Tensor::from(x_array)
+                .into_device(device.clone())
+                .unwrap()
+                .into_shared()
+                .unwrap();
+            let w_array = Array4::from_shape_fn([4, 2, 3, 2], |(o, i, h, w)| {
+                ((o + 2 * i + 3 * h + w) % 5) as f32 - 2.
+            });
+            let conv = |padding: [usize; 2],
+                        stride: [usize; 2],
+                        dilation: [usize; 2],
+                        tile_size: Option<usize>| {
+                let mut conv = Conv2::builder()
+                    .inputs(2)
+                    .outputs(4)
+                    .filter([3, 2])
+                    .padding(padding)
+                    .stride(stride)
+                    .dilation(dilation)
+                    .device(device.clone())
+                    .build()
+                    .unwrap();
+                conv.set_weight(&w_array).unwrap();
+                conv.set_tile_size(tile_size);
+                conv
+            };
+            let forward = |conv: &Conv2| {
+                conv.forward(Variable::from(x.clone()))
+                    .unwrap()
+                    .into_value()
+                    .cast_into_tensor::<f32>()
+                    .unwrap()
+                    .into_array()
+                    .unwrap()
+            };
+            let tile_sizes = [1, 5, 8, 30, 70, 1000];
+            for (padding, stride, dilation) in [
+                ([0, 0], [1, 1], [1, 1]),
+                ([1, 2], [2, 1], [1, 1]),
+                ([2, 1], [1, 2], [2, 1]),
+            ] {
+                let y = forward(&conv(padding, stride, dilation, None));
+                for tile_size in tile_sizes {
+                    let y_tiled = forward(&conv(padding, stride, dilation, Some(tile_size)));
+                    assert_eq!(
+                        y_tiled, y,
+                        "{padding:?} {stride:?} {dilation:?} {tile_size}"
+                    );
+                }
+            }
+            let backward = |tile_size: Option<usize>| {
+                let mut conv = conv([0, 0], [1, 1], [1, 1], tile_size);
+                conv.set_training(true).unwrap();
+                let x = Variable::builder().node().build(x.clone().into());
+                let y = conv.forward(x.clone()).unwrap();
+                y.node().unwrap().backward().unwrap();
+                let dx = x.node().unwrap().grad().unwrap().into_dyn();
+                let dw = conv.parameters()[0].grad().unwrap();
+                [dx, dw].map(|grad| {
+                    grad.cast_into_tensor::<f32>()
+                        .unwrap()
+                        .into_array()
+                        .unwrap()
+                })
+            };
+            let grads = backward(None);
+            for tile_size in tile_sizes {
+                assert_eq!(backward(Some(tile_size)), grads, "{tile_size}");
+            }
+        }
+
+        fn onnx_export(device: &Device) {
+            use approx::assert_relative_eq;
+            use autograph::learn::neural_network::{
+                autograd::{Variable2, Variable4},
+                layer::{Conv2, Dense, Flatten, Layer},
+                onnx::{self, ToOnnx},
+            };
+            use tract_onnx::prelude::{tvec, Framework, InferenceModelExt};
+
+            #[derive(Layer, Forward, ToOnnx)]
+            #[autograph(forward(Variable4, Output=Variable2))]
+            struct Network {
+                conv: Conv2<Relu>,
+                pool: MaxPool2,
+                flatten: Flatten,
+                dense: Dense,
+            }
+
+            let network = Network {
+                conv: Conv2::builder()
+                    .device(device.clone())
+                    .inputs(1)
+                    .outputs(4)
+                    .filter([3, 3])
+                    .padding([1, 1])
+                    .bias(true)
+                    .seed(0)
+                    .activation(Relu)
+                    .build()
+                    .unwrap(),
+                pool: MaxPool2::builder().filter([2, 2]).build(),
+                flatten: Flatten,
+                dense: Dense::builder()
+                    .device(device.clone())
+                    .inputs(4 * 4 * 4)
+                    .outputs(3)
+                    .bias(true)
+                    .seed(1)
+                    .build()
+                    .unwrap(),
+            };
+            let input_shape = [2, 1, 8, 8];
+            let x = Array::from_shape_fn(input_shape, |(n, _, h, w)| {
+                ((n + 2 * h + 3 * w) % 7) as f32 / 7. - 0.5
+            });
+            let y = network
+                .forward(Variable::from(
+                    Tensor::from(x.clone()).into_device(device.clone()).unwrap(),
+                ))
+                .unwrap()
+                .into_value()
+                .cast_into_tensor::<f32>()
+                .unwrap()
+                .into_array()
+                .unwrap();
+            let bytes = onnx::to_bytes(&network, input_shape).unwrap();
+            let model = tract_onnx::onnx()
+                .model_for_read(&mut bytes.as_slice())
+                .unwrap()
+                .into_optimized()
+                .unwrap()
+                .into_runnable()
+                .unwrap();
+            let outputs = model
+                .run(tvec!(tract_onnx::prelude::Tensor::from(x).into()))
+                .unwrap();
+            let y_onnx = outputs[0]
+                .to_array_view::<f32>()
+                .unwrap()
+                .into_dimensionality()
+                .unwrap()
+                .to_owned();
+            assert_relative_eq!(y, y_onnx, epsilon = 1e-4);
+        }
+
+        #[cfg(feature = "pytorch")]
+        fn pytorch_state_dict(device: &Device) {
+            use approx::assert_relative_eq;
+            use autograph::learn::neural_network::{
+                autograd::{Variable2, Variable4},
+                layer::{Conv2, Dense, Flatten, Layer},
+                pytorch::StateDict,
+            };
+
+            #[derive(Layer, Forward)]
+            #[autograph(forward(Variable4, Output=Variable2))]
+            struct Network {
+                conv: Conv2<Relu>,
+                pool: MaxPool2,
+                flatten: Flatten,
+                dense: Dense,
+            }
+
+            let mut network = Network {
+                conv: Conv2::builder()
+                    .device(device.clone())
+                    .inputs(1)
+                    .outputs(2)
+                    .filter([3, 3])
+                    .bias(true)
+                    .activation(Relu)
+                    .build()
+                    .unwrap(),
+                pool: MaxPool2::builder().filter([2, 2]).build(),
+                flatten: Flatten,
+                dense: Dense::builder()
+                    .device(device.clone())
+                    .inputs(8)
+                    .outputs(3)
+                    .bias(true)
+                    .build()
+                    .unwrap(),
+            };
+            let names: Vec<_> = network
+                .named_parameters()
+                .into_iter()
+                .map(|(name, _)| name)
+                .collect();
+            assert_eq!(
+                names,
+                ["conv.weight", "conv.bias", "dense.weight", "dense.bias"]
+            );
+            let state_dict = StateDict::load(concat!(
+                env!("CARGO_MANIFEST_DIR"),
+                "/tests/fixtures/state_dict.pt"
+            ))
+            .unwrap();
+            assert_eq!(
+                state_dict.get("dense.bias").unwrap().scalar_type(),
+                ScalarType::F16
+            );
+            state_dict.load_into(&mut network).unwrap();
+            let x = state_dict
+                .get("input")
+                .unwrap()
+                .clone()
+                .into_device(device.clone())
+                .unwrap()
+                .into_dimensionality()
+                .unwrap();
+            let y = network
+                .forward(Variable::from(x.into_shared().unwrap()))
+                .unwrap()
+                .into_value()
+                .cast_into_tensor::<f32>()
+                .unwrap()
+                .into_array()
+                .unwrap();
+            let y_true = state_dict
+                .get("output")
+                .unwrap()
+                .clone()
+                .cast_into_tensor::<f32>()
+                .unwrap()
+                .into_array()
+                .unwrap()
+                .into_dimensionality()
+                .unwrap();
+            assert_relative_eq!(y, y_true, epsilon = 1e-5);
+        }
+
+        #[cfg(feature = "pytorch")]
+        fn pytorch_state_dict_partial(device: &Device) {
+            use autograph::{
+                learn::neural_network::{
+                    autograd::{Variable2, Variable4},
+                    layer::{Conv2, Dense, Flatten, Layer},
+                    pytorch::StateDict,
+                },
+                tensor::ScalarArcTensorD,
+            };
+
+            #[derive(Layer, Forward)]
+            #[autograph(forward(Variable4, Output=Variable2))]
+            struct Network {
+                conv: Conv2<Relu>,
+                pool: MaxPool2,
+                flatten: Flatten,
+                dense: Dense,
+            }
+
+            // The head is resized from 3 to 5 classes.
+            let mut network = Network {
+                conv: Conv2::builder()
+                    .device(device.clone())
+                    .inputs(1)
+                    .outputs(2)
+                    .filter([3, 3])
+                    .bias(true)
+                    .activation(Relu)
+                    .build()
+                    .unwrap(),
+                pool: MaxPool2::builder().filter([2, 2]).build(),
+                flatten: Flatten,
+                dense: Dense::builder()
+                    .device(device.clone())
+                    .inputs(8)
+                    .outputs(5)
+                    .bias(true)
+                    .build()
+                    .unwrap(),
+            };
+            let to_array =
+                |x: ScalarArcTensorD| x.cast_into_tensor::<f32>().unwrap().into_array().unwrap();
+            let dense_weight = to_array(network.dense.parameters()[0].value().clone());
+            let state_dict = StateDict::load(concat!(
+                env!("CARGO_MANIFEST_DIR"),
+                "/tests/fixtures/state_dict.pt"
+            ))
+            .unwrap();
+            assert!(state_dict.load_into(&mut network).is_err());
+            let report = state_dict.load_partial_into(&mut network).unwrap();
+            assert_eq!(report.loaded, ["conv.weight", "conv.bias"]);
+            assert!(report.missing.is_empty());
+            assert_eq!(report.mismatched, ["dense.weight", "dense.bias"]);
+            assert_eq!(
+                report.skipped().collect::<Vec<_>>(),
+                ["dense.weight", "dense.bias"]
+            );
+            let conv_weight = state_dict
+                .get("conv.weight")
+                .unwrap()
+                .to_owned()
+                .unwrap()
+                .cast_into_tensor::<f32>()
+                .unwrap()
+                .into_array()
+                .unwrap();
+            assert_eq!(
+                to_array(network.conv.parameters()[0].value().clone()),
+                conv_weight
+            );
+            assert_eq!(
+                to_array(network.dense.parameters()[0].value().clone()),
+                dense_weight
+            );
+        }
+
+        #[cfg(feature = "pytorch")]
+        fn pytorch_state_dict_batch_norm(device: &Device) {
+            use autograph::{
+                learn::neural_network::{
+                    layer::{BatchNorm, Conv2, Layer},
+                    pytorch::StateDict,
+                },
+                tensor::ScalarArcTensorD,
+            };
+
+            #[derive(Layer)]
+            struct Network {
+                conv: Conv2,
+                norm: BatchNorm,
+            }
+
+            let mut network = Network {
+                conv: Conv2::builder()
+                    .device(device.clone())
+                    .inputs(1)
+                    .outputs(2)
+                    .filter([3, 3])
+                    .bias(true)
+                    .build()
+                    .unwrap(),
+                norm: BatchNorm::builder()
+                    .device(device.clone())
+                    .channels(2)
+                    .build()
+                    .unwrap(),
+            };
+            let state_dict = StateDict::load(concat!(
+                env!("CARGO_MANIFEST_DIR"),
+                "/tests/fixtures/batch_norm.pt"
+            ))
+            .unwrap();
+            let num_batches_tracked = state_dict.get("norm.num_batches_tracked").unwrap();
+            assert_eq!(num_batches_tracked.scalar_type(), ScalarType::I64);
+            assert_eq!(
+                num_batches_tracked
+                    .to_owned()
+                    .unwrap()
+                    .try_into_tensor::<i64>()
+                    .unwrap()
+                    .into_vec()
+                    .unwrap(),
+                [10]
+            );
+            state_dict.load_into(&mut network).unwrap();
+            let to_vec =
+                |x: ScalarArcTensorD| x.cast_into_tensor::<f32>().unwrap().into_vec().unwrap();
+            assert_eq!(
+                to_vec(network.norm.weight().value().clone().into_dyn()),
+                [1.5, 0.5]
+            );
+            assert_eq!(
+                to_vec(network.norm.bias().value().clone().into_dyn()),
+                [-0.25, 0.75]
+            );
+        }
+
+        fn broadcast<D1: IntoDimension + 'static, D2: IntoDimension + 'static>(
+            device: &Device,
+            input_dim: D1,
+            output_dim: D2,
+        ) {
+            use autograph::tensor::ScalarArcTensor;
+
+            let input_dim = input_dim.into_dimension();
+            let output_dim = output_dim.into_dimension();
+            let x = ScalarArcTensor::zeros(device.clone(), input_dim, ScalarType::F32).unwrap();
+            let y = x.broadcast_shared(output_dim.clone());
+            let x_var = Variable::builder().node().build(x.clone());
+            let y_var = x_var.broadcast(output_dim);
+            assert_eq!(y.is_some(), y_var.is_some());
+            if let Some((y, y_var)) = y.zip(y_var) {
+                assert_eq!(y.shape(), y_var.shape());
+                assert_eq!(y.strides(), y_var.value().strides());
+                y_var.node().unwrap().backward().unwrap();
+            }
+        }
+
+        fn expand(device: &Device) {
+            use ndarray::{arr1, Array2};
+
+            let x = Tensor::from(arr1(&[1f32, 2., 3.]))
+                .into_device(device.clone())
+                .unwrap();
+            let x_expanded = x.expand([2, 3]).unwrap();
+            assert_eq!(x_expanded.shape(), [2, 3]);
+            assert_eq!(x_expanded.strides(), [0, 1]);
+            assert!(x.expand([2, 4]).is_err());
+            let y_array = Array2::from_shape_fn([2, 3], |(i, j)| (i * 3 + j) as f32);
+            let y = Tensor::from(y_array.clone())
+                .into_device(device.clone())
+                .unwrap();
+            let z = y.mul(&x_expanded).unwrap().into_array().unwrap();
+            let z_true = Array2::from_shape_fn([2, 3], |(i, j)| y_array[(i, j)] * (j + 1) as f32);
+            assert_eq!(z, z_true);
+            let x_var = Variable::builder()
+                .node()
+                .build(x.into_shared().unwrap().into());
+            let y_var = Variable::from(y.into_shared().unwrap());
+            let z_var = y_var.mul(&x_var.expand([2, 3]).unwrap()).unwrap();
+            assert_eq!(z_var.value().strides(), [3, 1]);
+            z_var.node().unwrap().backward().unwrap();
+            let dx = x_var
+                .node()
+                .unwrap()
+                .grad()
+                .unwrap()
+                .cast_into_tensor::<f32>()
+                .unwrap()
+                .into_array()
+                .unwrap();
+            assert_eq!(dx, y_array.sum_axis(Axis(0)));
         }
     }
 }
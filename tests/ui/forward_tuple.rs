@@ -0,0 +1,24 @@
+use autograph::learn::neural_network::{
+    autograd::Variable2,
+    layer::{Bilinear, Forward, Layer, Relu},
+};
+
+#[derive(Layer, Forward)]
+#[autograph(forward((Variable2, Variable2), Output=Variable2))]
+struct Scorer {
+    bilinear: Bilinear,
+    relu: Relu,
+}
+
+fn main() {
+    let scorer = Scorer {
+        bilinear: Bilinear::builder()
+            .inputs1(2)
+            .inputs2(3)
+            .outputs(1)
+            .build()
+            .unwrap(),
+        relu: Relu,
+    };
+    let _ = |x1: Variable2, x2: Variable2| scorer.forward((x1, x2));
+}